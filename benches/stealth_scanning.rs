@@ -0,0 +1,75 @@
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
+use dchat::messaging::stealth::{open_wire, open_wire_batch, StealthMessenger};
+use dchat::privacy::stealth::{StealthGenerator, StealthScanner};
+use dchat_core::types::UserId;
+use rand::rngs::OsRng;
+use std::hint::black_box;
+
+/// Scanning cost is dominated by foreign payloads, so the corpus is mostly
+/// traffic for other users with one message per batch addressed to us
+fn bench_scan_throughput(c: &mut Criterion) {
+    let mut rng = OsRng;
+    let scanner = StealthScanner::generate(&mut rng);
+    let generator = StealthGenerator::new(&mut rng);
+    let mut group = c.benchmark_group("stealth_scan");
+    
+    for batch in [100, 1000].iter() {
+        group.throughput(Throughput::Elements(*batch as u64));
+        
+        let mut payloads: Vec<_> = (0..*batch - 1)
+            .map(|_| {
+                let other = StealthScanner::generate(&mut rng).address();
+                generator.create_payload(&other, &[0u8; 200], &mut rng).unwrap()
+            })
+            .collect();
+        payloads.push(generator.create_payload(&scanner.address(), &[0u8; 200], &mut rng).unwrap());
+        
+        group.bench_with_input(BenchmarkId::new("single", batch), &payloads, |b, payloads| {
+            b.iter(|| {
+                let matched = payloads
+                    .iter()
+                    .filter(|p| matches!(scanner.scan(p), Ok(Some(_))))
+                    .count();
+                black_box(matched)
+            })
+        });
+        
+        group.bench_with_input(BenchmarkId::new("batched", batch), &payloads, |b, payloads| {
+            b.iter(|| {
+                let matched = scanner.scan_batch(payloads).into_iter().flatten().count();
+                black_box(matched)
+            })
+        });
+    }
+    
+    group.finish();
+}
+
+fn bench_wire_scan_with_decoys(c: &mut Criterion) {
+    let mut rng = OsRng;
+    let scanner = StealthScanner::generate(&mut rng);
+    let messenger = StealthMessenger::new(&mut rng).with_decoys(9);
+    let wires = messenger
+        .seal(UserId::new(), &scanner.address(), vec![0u8; 200], &mut rng)
+        .unwrap();
+    
+    let mut group = c.benchmark_group("stealth_wire_scan");
+    group.throughput(Throughput::Elements(wires.len() as u64));
+    group.bench_function("decode_and_scan", |b| {
+        b.iter(|| {
+            let opened = wires.iter().filter_map(|w| open_wire(&scanner, w)).count();
+            black_box(opened)
+        })
+    });
+    group.bench_function("decode_and_scan_batch", |b| {
+        b.iter(|| black_box(open_wire_batch(&scanner, &wires).len()))
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_scan_throughput,
+    bench_wire_scan_with_decoys
+);
+criterion_main!(benches);
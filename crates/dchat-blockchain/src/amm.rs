@@ -0,0 +1,572 @@
+//! Constant-product market maker for swapping ledger tokens
+//!
+//! Each pool pairs the native token with one other ledger token, keyed by
+//! that token's symbol, and prices swaps so that `native * token` never
+//! falls. A swap leaves `swap_fee_bps` of its input in the reserves, so the
+//! product grows with every trade and the fee accrues to liquidity
+//! providers. Traders name the least they will accept; a swap that would pay
+//! less is rejected.
+//!
+//! Providers hold LP shares. The first deposit mints `sqrt(native * token)`
+//! shares, of which `MINIMUM_LIQUIDITY` are locked for good so a pool can
+//! never be emptied. Later deposits mint shares at the pool's current ratio
+//! and only take what that ratio needs; withdrawals pay out both reserves in
+//! proportion to the shares burned.
+//!
+//! Each pool keeps cumulative prices in Q64.64 fixed point, advanced by the
+//! spot price times the blocks it held. A pool is observed before the first
+//! change in a block, so trades inside a block cannot move the time-weighted
+//! average.
+//!
+//! This module only tracks reserves and shares; `CurrencyChainClient` moves
+//! the balances that back them.
+
+use dchat_core::error::{Error, Result};
+use dchat_core::types::UserId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+use crate::assets::NATIVE_TOKEN;
+
+/// Swap fee a new pool charges, in basis points
+pub const DEFAULT_SWAP_FEE_BPS: u16 = 30;
+
+/// Highest swap fee a pool may charge, in basis points
+pub const MAX_SWAP_FEE_BPS: u16 = 1_000;
+
+/// LP shares locked in every pool on its first deposit
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+/// Price observations each pool keeps for its oracle
+pub const MAX_OBSERVATIONS: usize = 1_024;
+
+/// 1.0 in Q64.64 fixed point
+pub const Q64: u128 = 1 << 64;
+
+/// Which way a swap trades
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SwapDirection {
+    /// Pay native tokens, receive the pool's token
+    NativeToToken,
+    /// Pay the pool's token, receive native tokens
+    TokenToNative,
+}
+
+/// Cumulative prices at a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Observation {
+    pub block: u64,
+    /// Sum of the token's price in native tokens over blocks, Q64.64
+    pub token_price_cumulative: u128,
+    /// Sum of the native token's price in the pool's token over blocks, Q64.64
+    pub native_price_cumulative: u128,
+}
+
+/// Time-weighted average prices over a window, Q64.64
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Twap {
+    /// Native tokens per pool token
+    pub token_price: u128,
+    /// Pool tokens per native token
+    pub native_price: u128,
+}
+
+impl Twap {
+    /// Native tokens per pool token as a float, for display
+    pub fn token_price_f64(&self) -> f64 {
+        self.token_price as f64 / Q64 as f64
+    }
+}
+
+/// Tokens a deposit took and the shares it minted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deposit {
+    pub native: u64,
+    pub token: u64,
+    pub shares: u64,
+}
+
+/// Tokens paid out for burned shares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Withdrawal {
+    pub native: u64,
+    pub token: u64,
+}
+
+/// A native/token pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pool {
+    /// Symbol of the token paired with the native token
+    pub token: String,
+    pub native_reserve: u64,
+    pub token_reserve: u64,
+    /// LP shares outstanding, the locked minimum included
+    pub total_shares: u64,
+    pub swap_fee_bps: u16,
+    shares: HashMap<UserId, u64>,
+    /// Block the cumulative prices were last advanced to
+    last_block: u64,
+    token_price_cumulative: u128,
+    native_price_cumulative: u128,
+    observations: VecDeque<Observation>,
+}
+
+impl Pool {
+    fn new(token: &str, swap_fee_bps: u16, height: u64) -> Self {
+        Self {
+            token: token.to_string(),
+            native_reserve: 0,
+            token_reserve: 0,
+            total_shares: 0,
+            swap_fee_bps,
+            shares: HashMap::new(),
+            last_block: height,
+            token_price_cumulative: 0,
+            native_price_cumulative: 0,
+            observations: VecDeque::new(),
+        }
+    }
+
+    /// Shares `provider` holds
+    pub fn shares_of(&self, provider: &UserId) -> u64 {
+        self.shares.get(provider).copied().unwrap_or(0)
+    }
+
+    /// Native tokens per pool token at the current reserves, Q64.64
+    pub fn spot_price(&self) -> Option<u128> {
+        price(self.native_reserve, self.token_reserve)
+    }
+
+    /// Tokens `amount_in` buys at the current reserves, after the fee
+    pub fn amount_out(&self, direction: SwapDirection, amount_in: u64) -> u64 {
+        let (reserve_in, reserve_out) = match direction {
+            SwapDirection::NativeToToken => (self.native_reserve, self.token_reserve),
+            SwapDirection::TokenToNative => (self.token_reserve, self.native_reserve),
+        };
+        amount_out(reserve_in, reserve_out, amount_in, self.swap_fee_bps)
+    }
+
+    /// Cumulative prices extrapolated to `height`
+    pub fn observation_at(&self, height: u64) -> Observation {
+        let elapsed = height.saturating_sub(self.last_block) as u128;
+        let advance = |cumulative: u128, price: Option<u128>| {
+            cumulative.wrapping_add(price.unwrap_or(0).wrapping_mul(elapsed))
+        };
+        Observation {
+            block: height.max(self.last_block),
+            token_price_cumulative: advance(self.token_price_cumulative, self.spot_price()),
+            native_price_cumulative: advance(
+                self.native_price_cumulative,
+                price(self.token_reserve, self.native_reserve),
+            ),
+        }
+    }
+
+    /// Average prices over the `window` blocks before `height`
+    ///
+    /// `None` until the pool has an observation at least `window` blocks old.
+    pub fn twap(&self, window: u64, height: u64) -> Option<Twap> {
+        if window == 0 {
+            return None;
+        }
+        let start = height.checked_sub(window)?;
+        let then = self.observations.iter().rev().find(|o| o.block <= start)?;
+        let now = self.observation_at(height);
+        let elapsed = (now.block - then.block) as u128;
+        Some(Twap {
+            token_price: now.token_price_cumulative.wrapping_sub(then.token_price_cumulative) / elapsed,
+            native_price: now.native_price_cumulative.wrapping_sub(then.native_price_cumulative) / elapsed,
+        })
+    }
+
+    /// Advance the cumulative prices to `height` at the current reserves
+    fn observe(&mut self, height: u64) {
+        if height <= self.last_block && !self.observations.is_empty() {
+            return;
+        }
+        let observation = self.observation_at(height);
+        self.last_block = observation.block;
+        self.token_price_cumulative = observation.token_price_cumulative;
+        self.native_price_cumulative = observation.native_price_cumulative;
+        if self.observations.len() == MAX_OBSERVATIONS {
+            self.observations.pop_front();
+        }
+        self.observations.push_back(observation);
+    }
+}
+
+/// `numerator / denominator` in Q64.64, if the denominator is nonzero
+fn price(numerator: u64, denominator: u64) -> Option<u128> {
+    ((numerator as u128) << 64).checked_div(denominator as u128)
+}
+
+/// Constant-product output for `amount_in`, with the fee left in the pool
+pub fn amount_out(reserve_in: u64, reserve_out: u64, amount_in: u64, fee_bps: u16) -> u64 {
+    let in_after_fee = amount_in as u128 * (10_000 - fee_bps as u128);
+    let denominator = reserve_in as u128 * 10_000 + in_after_fee;
+    if denominator == 0 {
+        return 0;
+    }
+    (in_after_fee * reserve_out as u128 / denominator) as u64
+}
+
+/// Integer square root, rounded down
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = 1u128 << (128 - n.leading_zeros()).div_ceil(2);
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// All pools, keyed by token symbol
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AmmLedger {
+    pools: HashMap<String, Pool>,
+}
+
+impl AmmLedger {
+    /// Create a ledger with no pools
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pool pairing the native token with `token`
+    pub fn pool(&self, token: &str) -> Option<&Pool> {
+        self.pools.get(token)
+    }
+
+    /// All pools
+    pub fn pools(&self) -> Vec<&Pool> {
+        self.pools.values().collect()
+    }
+
+    fn pool_mut(&mut self, token: &str) -> Result<&mut Pool> {
+        self.pools
+            .get_mut(token)
+            .ok_or_else(|| Error::NotFound(format!("No liquidity pool for {}", token)))
+    }
+
+    /// Open an empty pool for `token`
+    pub fn create_pool(&mut self, token: &str, swap_fee_bps: u16, height: u64) -> Result<()> {
+        if self.pools.contains_key(token) {
+            return Err(Error::AlreadyExists(format!("Liquidity pool already exists for {}", token)));
+        }
+        check_pooled_token(token)?;
+        if swap_fee_bps > MAX_SWAP_FEE_BPS {
+            return Err(Error::InvalidInput(format!(
+                "Swap fee {} bps exceeds the maximum {}",
+                swap_fee_bps, MAX_SWAP_FEE_BPS
+            )));
+        }
+        self.pools.insert(token.to_string(), Pool::new(token, swap_fee_bps, height));
+        Ok(())
+    }
+
+    /// Deposit up to `native` and `token_amount` into the `token` pool
+    ///
+    /// A pool without a deposit yet is opened at the default fee and priced by
+    /// this deposit. Otherwise the deposit is taken at the pool's ratio,
+    /// rounded in the pool's favor, and the rest stays with the provider.
+    pub fn add_liquidity(
+        &mut self,
+        provider: &UserId,
+        token: &str,
+        native: u64,
+        token_amount: u64,
+        min_shares: u64,
+        height: u64,
+    ) -> Result<Deposit> {
+        check_pooled_token(token)?;
+        // A new pool is only kept if the deposit goes through
+        let mut opened = (!self.pools.contains_key(token)).then(|| Pool::new(token, DEFAULT_SWAP_FEE_BPS, height));
+        let pool = match opened.as_mut() {
+            Some(pool) => pool,
+            None => self.pool_mut(token)?,
+        };
+
+        let deposit = if pool.total_shares == 0 {
+            let shares = isqrt(native as u128 * token_amount as u128) as u64;
+            if shares <= MINIMUM_LIQUIDITY {
+                return Err(Error::InvalidInput(format!(
+                    "First deposit must mint more than {} shares",
+                    MINIMUM_LIQUIDITY
+                )));
+            }
+            Deposit { native, token: token_amount, shares }
+        } else {
+            let total = pool.total_shares as u128;
+            let shares = (native as u128 * total / pool.native_reserve as u128)
+                .min(token_amount as u128 * total / pool.token_reserve as u128) as u64;
+            if shares == 0 {
+                return Err(Error::InvalidInput("Deposit is too small to mint a share".to_string()));
+            }
+            let take = |reserve: u64| (shares as u128 * reserve as u128).div_ceil(total) as u64;
+            Deposit {
+                native: take(pool.native_reserve),
+                token: take(pool.token_reserve),
+                shares,
+            }
+        };
+
+        let minted = if pool.total_shares == 0 { deposit.shares - MINIMUM_LIQUIDITY } else { deposit.shares };
+        if minted < min_shares {
+            return Err(Error::InvalidInput(format!("Deposit mints {} shares, below the minimum {}", minted, min_shares)));
+        }
+
+        pool.observe(height);
+        pool.native_reserve += deposit.native;
+        pool.token_reserve += deposit.token;
+        pool.total_shares += deposit.shares;
+        *pool.shares.entry(provider.clone()).or_insert(0) += minted;
+        if let Some(pool) = opened {
+            self.pools.insert(token.to_string(), pool);
+        }
+        Ok(Deposit { shares: minted, ..deposit })
+    }
+
+    /// Burn `shares` of the `token` pool for their cut of both reserves
+    pub fn remove_liquidity(
+        &mut self,
+        provider: &UserId,
+        token: &str,
+        shares: u64,
+        min_native: u64,
+        min_token: u64,
+        height: u64,
+    ) -> Result<Withdrawal> {
+        let pool = self.pool_mut(token)?;
+        let held = pool.shares_of(provider);
+        if shares == 0 || shares > held {
+            return Err(Error::InvalidInput(format!("Cannot burn {} shares: {} held", shares, held)));
+        }
+
+        let total = pool.total_shares as u128;
+        let cut = |reserve: u64| (shares as u128 * reserve as u128 / total) as u64;
+        let withdrawal = Withdrawal {
+            native: cut(pool.native_reserve),
+            token: cut(pool.token_reserve),
+        };
+        if withdrawal.native < min_native || withdrawal.token < min_token {
+            return Err(Error::InvalidInput(format!(
+                "Withdrawal of {} native and {} {} is below the minimum",
+                withdrawal.native, withdrawal.token, token
+            )));
+        }
+
+        pool.observe(height);
+        pool.native_reserve -= withdrawal.native;
+        pool.token_reserve -= withdrawal.token;
+        pool.total_shares -= shares;
+        if shares == held {
+            pool.shares.remove(provider);
+        } else {
+            pool.shares.insert(provider.clone(), held - shares);
+        }
+        Ok(withdrawal)
+    }
+
+    /// Swap `amount_in` through the `token` pool, returning the amount paid out
+    pub fn swap(
+        &mut self,
+        token: &str,
+        direction: SwapDirection,
+        amount_in: u64,
+        min_out: u64,
+        height: u64,
+    ) -> Result<u64> {
+        let pool = self.pool_mut(token)?;
+        if pool.total_shares == 0 {
+            return Err(Error::InvalidInput(format!("Liquidity pool for {} is empty", token)));
+        }
+
+        let out = pool.amount_out(direction, amount_in);
+        if out == 0 {
+            return Err(Error::InvalidInput("Swap is too small to pay anything out".to_string()));
+        }
+        if out < min_out {
+            return Err(Error::InvalidInput(format!("Swap pays {}, below the minimum {}", out, min_out)));
+        }
+
+        pool.observe(height);
+        match direction {
+            SwapDirection::NativeToToken => {
+                pool.native_reserve += amount_in;
+                pool.token_reserve -= out;
+            }
+            SwapDirection::TokenToNative => {
+                pool.token_reserve += amount_in;
+                pool.native_reserve -= out;
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Pools pair the native token with some other token
+fn check_pooled_token(token: &str) -> Result<()> {
+    if token == NATIVE_TOKEN {
+        return Err(Error::InvalidInput("Cannot pool the native token against itself".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn seeded(native: u64, token: u64) -> (AmmLedger, UserId) {
+        let mut amm = AmmLedger::new();
+        let provider = UserId::new();
+        amm.add_liquidity(&provider, "GEM", native, token, 0, 1).unwrap();
+        (amm, provider)
+    }
+
+    #[test]
+    fn test_first_deposit_locks_minimum_liquidity() {
+        let mut amm = AmmLedger::new();
+        let provider = UserId::new();
+
+        assert!(amm.add_liquidity(&provider, "GEM", 1_000, 1_000, 0, 1).is_err());
+        let deposit = amm.add_liquidity(&provider, "GEM", 40_000, 10_000, 0, 1).unwrap();
+        assert_eq!(deposit.shares, 20_000 - MINIMUM_LIQUIDITY);
+
+        let pool = amm.pool("GEM").unwrap();
+        assert_eq!(pool.total_shares, 20_000);
+        assert_eq!(pool.shares_of(&provider), 19_000);
+
+        // Withdrawing every share leaves the locked minimum's reserves behind
+        let withdrawal = amm.remove_liquidity(&provider, "GEM", 19_000, 0, 0, 2).unwrap();
+        assert_eq!(withdrawal, Withdrawal { native: 38_000, token: 9_500 });
+        let pool = amm.pool("GEM").unwrap();
+        assert_eq!((pool.native_reserve, pool.token_reserve), (2_000, 500));
+    }
+
+    #[test]
+    fn test_deposit_takes_pool_ratio() {
+        let (mut amm, _) = seeded(40_000, 10_000);
+        let provider = UserId::new();
+
+        // Offering too much native only takes what the ratio needs
+        let deposit = amm.add_liquidity(&provider, "GEM", 50_000, 5_000, 0, 2).unwrap();
+        assert_eq!(deposit, Deposit { native: 20_000, token: 5_000, shares: 10_000 });
+        assert!(amm.add_liquidity(&provider, "GEM", 4_000, 1_000, 2_001, 2).is_err());
+    }
+
+    #[test]
+    fn test_swap_charges_fee_and_enforces_slippage() {
+        let (mut amm, _) = seeded(1_000_000, 1_000_000);
+
+        let quoted = amm.pool("GEM").unwrap().amount_out(SwapDirection::NativeToToken, 10_000);
+        // 10_000 * 0.997 * 1e6 / (1e6 + 9_970)
+        assert_eq!(quoted, 9_871);
+        assert!(amm.swap("GEM", SwapDirection::NativeToToken, 10_000, 9_872, 2).is_err());
+        assert_eq!(amm.swap("GEM", SwapDirection::NativeToToken, 10_000, 9_871, 2).unwrap(), 9_871);
+
+        let pool = amm.pool("GEM").unwrap();
+        assert_eq!((pool.native_reserve, pool.token_reserve), (1_010_000, 990_129));
+        assert!(amm.swap("SILVER", SwapDirection::NativeToToken, 10, 0, 2).is_err());
+    }
+
+    #[test]
+    fn test_twap_ignores_trades_within_a_block() {
+        let (mut amm, _) = seeded(1_000_000, 1_000_000);
+        assert!(amm.pool("GEM").unwrap().twap(10, 5).is_none());
+
+        // Price is 1 from block 1 to 11; a swap at block 11 moves the spot
+        // price but not the average up to it
+        amm.swap("GEM", SwapDirection::NativeToToken, 500_000, 0, 11).unwrap();
+        let pool = amm.pool("GEM").unwrap();
+        assert_eq!(pool.twap(10, 11).unwrap().token_price, Q64);
+
+        // Over the next ten blocks the average tends to the new spot price
+        let twap = pool.twap(20, 21).unwrap();
+        let spot = pool.spot_price().unwrap();
+        assert!(twap.token_price > Q64 && twap.token_price < spot);
+        assert_eq!(pool.twap(10, 21).unwrap().token_price, spot);
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Swap(bool, u64),
+        Add(u64, u64),
+        Remove(u64),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (any::<bool>(), 1..10_000_000u64).prop_map(|(native_in, amount)| Op::Swap(native_in, amount)),
+            (1..10_000_000u64, 1..10_000_000u64).prop_map(|(native, token)| Op::Add(native, token)),
+            (1..1_000_000u64).prop_map(Op::Remove),
+        ]
+    }
+
+    proptest! {
+        /// A swap never pays out the whole reserve and never shrinks the product
+        #[test]
+        fn prop_swaps_keep_the_product(
+            native in 1_000u64..1_000_000_000_000,
+            token in 1_000u64..1_000_000_000_000,
+            amount_in in 1u64..1_000_000_000_000,
+            fee_bps in 0u16..=MAX_SWAP_FEE_BPS,
+        ) {
+            let out = amount_out(native, token, amount_in, fee_bps);
+            prop_assert!(out < token);
+            let before = native as u128 * token as u128;
+            let after = (native + amount_in) as u128 * (token - out) as u128;
+            prop_assert!(after >= before);
+        }
+
+        /// Under any mix of swaps, deposits and withdrawals, a share is never
+        /// worth less of either reserve and the pool never runs dry
+        #[test]
+        fn prop_share_value_never_falls(ops in prop::collection::vec(op(), 1..40)) {
+            let (mut amm, provider) = seeded(5_000_000, 5_000_000);
+            let mut height = 1;
+
+            for op in ops {
+                height += 1;
+                let before = amm.pool("GEM").unwrap().clone();
+                let result = match op {
+                    Op::Swap(native_in, amount) => {
+                        let direction = if native_in { SwapDirection::NativeToToken } else { SwapDirection::TokenToNative };
+                        amm.swap("GEM", direction, amount, 0, height).map(|_| ())
+                    }
+                    Op::Add(native, token) => amm.add_liquidity(&provider, "GEM", native, token, 0, height).map(|_| ()),
+                    Op::Remove(shares) => {
+                        let shares = shares.min(amm.pool("GEM").unwrap().shares_of(&provider));
+                        amm.remove_liquidity(&provider, "GEM", shares, 0, 0, height).map(|_| ())
+                    }
+                };
+                let after = amm.pool("GEM").unwrap();
+                if result.is_err() {
+                    prop_assert_eq!(after.total_shares, before.total_shares);
+                    prop_assert_eq!(after.native_reserve, before.native_reserve);
+                    continue;
+                }
+
+                prop_assert!(after.native_reserve > 0 && after.token_reserve > 0);
+                prop_assert!(after.total_shares >= MINIMUM_LIQUIDITY);
+                if let Op::Swap(..) = op {
+                    let k_before = before.native_reserve as u128 * before.token_reserve as u128;
+                    let k_after = after.native_reserve as u128 * after.token_reserve as u128;
+                    prop_assert!(k_after >= k_before);
+                } else {
+                    for (then, now) in [
+                        (before.native_reserve, after.native_reserve),
+                        (before.token_reserve, after.token_reserve),
+                    ] {
+                        prop_assert!(now as u128 * before.total_shares as u128 >= then as u128 * after.total_shares as u128);
+                    }
+                }
+            }
+        }
+    }
+}
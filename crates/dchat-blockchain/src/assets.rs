@@ -0,0 +1,248 @@
+//! User-issued fungible tokens and bonding locks
+//!
+//! Any account can issue a token under an unused symbol, with a name,
+//! decimals and an optional supply cap. Only the issuer mints more of it;
+//! any holder can burn their own. `NATIVE_TOKEN` names the chain's own token
+//! and cannot be issued.
+//!
+//! A bond locks a holder's tokens, native or issued, until a height. Bonded
+//! tokens still belong to the holder but cannot be spent or moved; adding to
+//! a bond keeps whichever unlock height is later.
+//!
+//! This module only tracks token metadata, supply and bonds;
+//! `CurrencyChainClient` moves the balances that back them.
+
+use dchat_core::error::{Error, Result};
+use dchat_core::types::UserId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Symbol of the chain's native token
+pub const NATIVE_TOKEN: &str = "DCHAT";
+
+/// Longest symbol a token may have
+pub const MAX_SYMBOL_LEN: usize = 12;
+
+/// Most decimals a token may declare
+pub const MAX_DECIMALS: u8 = 18;
+
+/// An issued token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+    /// Account that can mint more
+    pub issuer: UserId,
+    /// Cap on `total_supply`, if any
+    pub max_supply: Option<u64>,
+    pub total_supply: u64,
+    /// Height the token was issued at
+    pub issued_at: u64,
+}
+
+/// A token to issue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueToken {
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+    /// Cap on the total supply, if any
+    pub max_supply: Option<u64>,
+    /// Credited to the issuer
+    pub initial_supply: u64,
+}
+
+/// Tokens locked by a holder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bond {
+    pub amount: u64,
+    /// Height from which the tokens can be unbonded
+    pub unlock_at: u64,
+}
+
+/// Issued tokens and bonds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AssetLedger {
+    tokens: HashMap<String, TokenMetadata>,
+    /// (holder, token symbol) -> bond
+    #[serde(with = "crate::pair_map")]
+    bonds: HashMap<(UserId, String), Bond>,
+}
+
+impl AssetLedger {
+    /// Create a ledger with no issued tokens
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issue a new token, `initial_supply` of which goes to the issuer
+    pub fn issue(&mut self, issuer: &UserId, token: &IssueToken, height: u64) -> Result<()> {
+        let IssueToken { symbol, name, decimals, max_supply, initial_supply } = token;
+        let (decimals, max_supply, initial_supply) = (*decimals, *max_supply, *initial_supply);
+        if symbol.is_empty()
+            || symbol.len() > MAX_SYMBOL_LEN
+            || !symbol.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        {
+            return Err(Error::InvalidInput(format!(
+                "Token symbols are 1 to {} uppercase letters or digits, got {:?}",
+                MAX_SYMBOL_LEN, symbol
+            )));
+        }
+        if symbol == NATIVE_TOKEN || self.tokens.contains_key(symbol.as_str()) {
+            return Err(Error::AlreadyExists(format!("Token already exists: {}", symbol)));
+        }
+        if decimals > MAX_DECIMALS {
+            return Err(Error::InvalidInput(format!("Decimals {} exceed the maximum {}", decimals, MAX_DECIMALS)));
+        }
+        if max_supply.is_some_and(|max| initial_supply > max) {
+            return Err(Error::InvalidInput("Initial supply exceeds the max supply".to_string()));
+        }
+
+        self.tokens.insert(
+            symbol.clone(),
+            TokenMetadata {
+                symbol: symbol.clone(),
+                name: name.clone(),
+                decimals,
+                issuer: issuer.clone(),
+                max_supply,
+                total_supply: initial_supply,
+                issued_at: height,
+            },
+        );
+        Ok(())
+    }
+
+    /// Metadata of an issued token
+    pub fn token(&self, symbol: &str) -> Option<&TokenMetadata> {
+        self.tokens.get(symbol)
+    }
+
+    fn token_mut(&mut self, symbol: &str) -> Result<&mut TokenMetadata> {
+        self.tokens
+            .get_mut(symbol)
+            .ok_or_else(|| Error::NotFound(format!("Token not found: {}", symbol)))
+    }
+
+    /// Every issued token
+    pub fn tokens(&self) -> Vec<&TokenMetadata> {
+        self.tokens.values().collect()
+    }
+
+    /// Add `amount` to a token's supply, if `by` issued it
+    pub fn mint(&mut self, by: &UserId, symbol: &str, amount: u64) -> Result<()> {
+        let token = self.token_mut(symbol)?;
+        if &token.issuer != by {
+            return Err(Error::PermissionDenied(format!("{} is not the issuer of {}", by, symbol)));
+        }
+        let supply = token.total_supply.checked_add(amount)
+            .filter(|supply| token.max_supply.is_none_or(|max| *supply <= max))
+            .ok_or_else(|| Error::InvalidInput(format!("Minting {} {} would exceed its max supply", amount, symbol)))?;
+        token.total_supply = supply;
+        Ok(())
+    }
+
+    /// Take `amount` out of a token's supply
+    ///
+    /// The caller checks the burner holds the tokens.
+    pub fn burn(&mut self, symbol: &str, amount: u64) -> Result<()> {
+        let token = self.token_mut(symbol)?;
+        token.total_supply = token.total_supply.checked_sub(amount)
+            .ok_or_else(|| Error::InvalidInput(format!("Cannot burn {} {}: supply is {}", amount, symbol, token.total_supply)))?;
+        Ok(())
+    }
+
+    /// Lock `amount` of `token` held by `holder` for at least `lock_blocks`
+    ///
+    /// The caller checks the holder has the tokens free.
+    pub fn bond(&mut self, holder: &UserId, token: &str, amount: u64, lock_blocks: u64, height: u64) -> Result<()> {
+        if token != NATIVE_TOKEN && !self.tokens.contains_key(token) {
+            return Err(Error::NotFound(format!("Token not found: {}", token)));
+        }
+        if amount == 0 {
+            return Err(Error::InvalidInput("Bond amount must be positive".to_string()));
+        }
+
+        let unlock_at = height.saturating_add(lock_blocks);
+        let bond = self.bonds
+            .entry((holder.clone(), token.to_string()))
+            .or_insert(Bond { amount: 0, unlock_at });
+        bond.amount += amount;
+        bond.unlock_at = bond.unlock_at.max(unlock_at);
+        Ok(())
+    }
+
+    /// Release `amount` of a bond whose lock has passed
+    pub fn unbond(&mut self, holder: &UserId, token: &str, amount: u64, height: u64) -> Result<()> {
+        let key = (holder.clone(), token.to_string());
+        let bond = self.bonds.get_mut(&key)
+            .ok_or_else(|| Error::NotFound(format!("No {} bond for {}", token, holder)))?;
+        if amount == 0 || amount > bond.amount {
+            return Err(Error::InvalidInput(format!("Cannot unbond {}: {} bonded", amount, bond.amount)));
+        }
+        if height < bond.unlock_at {
+            return Err(Error::InvalidInput(format!("Bond is locked until block {}", bond.unlock_at)));
+        }
+
+        bond.amount -= amount;
+        if bond.amount == 0 {
+            self.bonds.remove(&key);
+        }
+        Ok(())
+    }
+
+    /// `holder`'s bond of `token`
+    pub fn bond_of(&self, holder: &UserId, token: &str) -> Option<Bond> {
+        self.bonds.get(&(holder.clone(), token.to_string())).copied()
+    }
+
+    /// Amount of `token` `holder` has bonded
+    pub fn bonded(&self, holder: &UserId, token: &str) -> u64 {
+        self.bond_of(holder, token).map_or(0, |bond| bond.amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(symbol: &str, decimals: u8, max_supply: Option<u64>, initial_supply: u64) -> IssueToken {
+        IssueToken { symbol: symbol.to_string(), name: "Gem".to_string(), decimals, max_supply, initial_supply }
+    }
+
+    #[test]
+    fn test_issue_and_mint() {
+        let mut ledger = AssetLedger::new();
+        let (issuer, other) = (UserId::new(), UserId::new());
+
+        assert!(ledger.issue(&issuer, &token("gem", 6, None, 0), 1).is_err());
+        assert!(ledger.issue(&issuer, &token(NATIVE_TOKEN, 6, None, 0), 1).is_err());
+        assert!(ledger.issue(&issuer, &token("GEM", 19, None, 0), 1).is_err());
+        assert!(ledger.issue(&issuer, &token("GEM", 6, Some(10), 11), 1).is_err());
+        ledger.issue(&issuer, &token("GEM", 6, Some(1_000), 400), 1).unwrap();
+        assert!(ledger.issue(&other, &token("GEM", 6, None, 0), 1).is_err());
+
+        assert!(ledger.mint(&other, "GEM", 100).is_err());
+        assert!(ledger.mint(&issuer, "GEM", 601).is_err());
+        ledger.mint(&issuer, "GEM", 600).unwrap();
+        ledger.burn("GEM", 250).unwrap();
+        assert_eq!(ledger.token("GEM").unwrap().total_supply, 750);
+    }
+
+    #[test]
+    fn test_bonds_unlock_at_the_later_height() {
+        let mut ledger = AssetLedger::new();
+        let holder = UserId::new();
+
+        assert!(ledger.bond(&holder, "GEM", 10, 5, 1).is_err());
+        ledger.bond(&holder, NATIVE_TOKEN, 100, 10, 1).unwrap();
+        ledger.bond(&holder, NATIVE_TOKEN, 50, 2, 5).unwrap();
+        assert_eq!(ledger.bond_of(&holder, NATIVE_TOKEN), Some(Bond { amount: 150, unlock_at: 11 }));
+
+        assert!(ledger.unbond(&holder, NATIVE_TOKEN, 50, 10).is_err());
+        assert!(ledger.unbond(&holder, NATIVE_TOKEN, 151, 11).is_err());
+        ledger.unbond(&holder, NATIVE_TOKEN, 150, 11).unwrap();
+        assert_eq!(ledger.bonded(&holder, NATIVE_TOKEN), 0);
+    }
+}
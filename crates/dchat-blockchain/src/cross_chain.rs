@@ -1,14 +1,98 @@
 //! Cross-chain bridge for atomic transactions between chat chain and currency chain
+//!
+//! Each chain queues outbound bridge messages in an outbox. Committing the
+//! outbox produces a header whose message root is the Merkle root of the
+//! messages committed at that height; once the chain's validators sign it,
+//! the other side's light client finalizes it. A message executes on its
+//! destination only with an inclusion proof against a finalized header, and
+//! at most once per source chain and nonce.
+//!
+//! Cross-chain operations post a receipt from each chain they touch, and
+//! become atomic successes once every receipt is proven final.
+//!
+//! Tokens cross by being locked in the bridge account on the currency chain,
+//! which credits the sender's bridged balance. An `Unlock` is only queued
+//! against that balance, which it debits, so every unlock is backed by an
+//! earlier lock. Outboxes, executed messages, bridged balances and light
+//! client state make up `BridgeState`, which the node saves so replay
+//! protection survives a restart.
 
 use crate::chat_chain::ChatChainClient;
-use crate::currency_chain::CurrencyChainClient;
+use crate::currency_chain::{CurrencyChainClient, SignedTransaction, TransactionPayload};
 use chrono::Utc;
+use dchat_bridge::light_client::{ChainHeader, LightClient, LightClientState, SignedHeader, GENESIS_PARENT};
+use dchat_bridge::multisig::{MultiSigConfig, ValidatorRotation};
+use dchat_bridge::slashing::SlashEvent;
+use dchat_bridge::ChainId;
+use dchat_chain::insurance_fund::{BridgeEvents, BridgeLoss};
+use dchat_chain::merkle::{MerklePath, MerkleTree};
 use dchat_core::types::UserId;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 use uuid::Uuid;
 
+/// Domain separator for bridge message leaves
+const MESSAGE_DOMAIN: &[u8] = b"dchat-bridge-message-v1";
+
+/// Domain separator the bridge account ID is derived from
+const BRIDGE_ACCOUNT_DOMAIN: &[u8] = b"dchat-bridge-account-v1";
+
+/// Source chain and nonce, which identify a bridge message
+type MessageKey = (ChainId, u64);
+
+/// What a bridge message asks its destination chain to do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BridgeMessagePayload {
+    /// Release tokens held by the bridge account on the currency chain
+    Unlock { to: UserId, amount: u64 },
+    /// Adjust a user's reputation on the chat chain
+    UpdateReputation { user: UserId, delta: i32 },
+    /// Attest that the source chain applied its part of a cross-chain operation
+    Receipt { bridge_tx: Uuid, chain_tx: Uuid },
+}
+
+/// A message from one chain to the other
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BridgeMessage {
+    pub source: ChainId,
+    pub destination: ChainId,
+    /// Position in the source chain's outbox; unique per source chain
+    pub nonce: u64,
+    pub sender: UserId,
+    pub payload: BridgeMessagePayload,
+}
+
+impl BridgeMessage {
+    /// Merkle leaf committed in the source chain's header
+    pub fn leaf_bytes(&self) -> Vec<u8> {
+        let mut bytes = MESSAGE_DOMAIN.to_vec();
+        bytes.extend(serde_json::to_vec(self).expect("bridge messages serialize"));
+        bytes
+    }
+}
+
+/// A bridge message with its inclusion proof in a source chain header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageProof {
+    pub message: BridgeMessage,
+    /// Height of the header that committed the message
+    pub height: u64,
+    pub path: MerklePath,
+}
+
+/// Outbound messages of one chain
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Outbox {
+    next_nonce: u64,
+    pending: Vec<BridgeMessage>,
+    /// Committed messages by header height, in leaf order
+    committed: BTreeMap<u64, Vec<BridgeMessage>>,
+    /// Header height and leaf index of each committed nonce
+    locations: HashMap<u64, (u64, usize)>,
+    last_header: Option<ChainHeader>,
+}
+
 /// Atomic cross-chain transaction
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CrossChainTransaction {
@@ -32,12 +116,35 @@ pub enum CrossChainStatus {
     Failed,
 }
 
+/// Everything the bridge must keep across restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BridgeState {
+    transactions: HashMap<Uuid, CrossChainTransaction>,
+    outboxes: HashMap<ChainId, Outbox>,
+    light_clients: HashMap<ChainId, LightClientState>,
+    executed: HashSet<MessageKey>,
+    receipts: HashMap<Uuid, Vec<MessageKey>>,
+    locked: HashMap<UserId, u64>,
+}
+
 /// Bridge for coordinating transactions between chat and currency chains
 pub struct CrossChainBridge {
     chat_chain: Arc<ChatChainClient>,
     currency_chain: Arc<CurrencyChainClient>,
     /// Track cross-chain transactions
     transactions: Arc<RwLock<HashMap<Uuid, CrossChainTransaction>>>,
+    /// Outbound messages per source chain
+    outboxes: Arc<RwLock<HashMap<ChainId, Outbox>>>,
+    /// Light clients per followed chain
+    light_clients: Arc<RwLock<HashMap<ChainId, LightClient>>>,
+    /// Source chain and nonce of every executed message
+    executed: Arc<RwLock<HashSet<MessageKey>>>,
+    /// Receipts each cross-chain transaction waits on
+    receipts: Arc<RwLock<HashMap<Uuid, Vec<MessageKey>>>>,
+    /// Tokens each user has locked and not yet unlocked
+    locked: Arc<RwLock<HashMap<UserId, u64>>>,
+    /// Currency chain account holding tokens locked in the bridge
+    bridge_account: UserId,
 }
 
 impl CrossChainBridge {
@@ -46,32 +153,312 @@ impl CrossChainBridge {
         chat_chain: Arc<ChatChainClient>,
         currency_chain: Arc<CurrencyChainClient>,
     ) -> Self {
+        Self::with_state(chat_chain, currency_chain, BridgeState::default())
+    }
+
+    /// Resume a bridge from saved state
+    pub fn with_state(
+        chat_chain: Arc<ChatChainClient>,
+        currency_chain: Arc<CurrencyChainClient>,
+        state: BridgeState,
+    ) -> Self {
+        let bridge_account = Self::account_id();
+        if !currency_chain.is_module_account(&bridge_account) {
+            currency_chain
+                .create_module_account(&bridge_account, 0)
+                .expect("bridge account ID is reserved");
+        }
+
+        let light_clients = state.light_clients.into_iter()
+            .map(|(chain, client)| (chain, LightClient::from_state(client)))
+            .collect();
+
         Self {
             chat_chain,
             currency_chain,
-            transactions: Arc::new(RwLock::new(HashMap::new())),
+            transactions: Arc::new(RwLock::new(state.transactions)),
+            outboxes: Arc::new(RwLock::new(state.outboxes)),
+            light_clients: Arc::new(RwLock::new(light_clients)),
+            executed: Arc::new(RwLock::new(state.executed)),
+            receipts: Arc::new(RwLock::new(state.receipts)),
+            locked: Arc::new(RwLock::new(state.locked)),
+            bridge_account,
         }
     }
 
+    /// State to save
+    pub fn state(&self) -> BridgeState {
+        BridgeState {
+            transactions: self.transactions.read().unwrap().clone(),
+            outboxes: self.outboxes.read().unwrap().clone(),
+            light_clients: self.light_clients.read().unwrap().iter()
+                .map(|(chain, client)| (chain.clone(), client.state()))
+                .collect(),
+            executed: self.executed.read().unwrap().clone(),
+            receipts: self.receipts.read().unwrap().clone(),
+            locked: self.locked.read().unwrap().clone(),
+        }
+    }
+
+    /// The bridge account's ID, the same on every node
+    pub fn account_id() -> UserId {
+        let digest = dchat_crypto::hash(BRIDGE_ACCOUNT_DOMAIN);
+        UserId(uuid::Builder::from_custom_bytes(digest[..16].try_into().expect("16 bytes")).into_uuid())
+    }
+
+    /// Currency chain account that holds locked tokens
+    ///
+    /// Users lock tokens with `lock_tokens`; `Unlock` messages from the chat
+    /// chain release them.
+    pub fn bridge_account(&self) -> &UserId {
+        &self.bridge_account
+    }
+
+    /// Lock tokens in the bridge with a signed transfer to the bridge account
+    ///
+    /// The amount the bridge account receives is credited to the sender's
+    /// bridged balance, which later `Unlock` messages draw on.
+    pub fn lock_tokens(&self, transfer: &SignedTransaction) -> Result<Uuid, String> {
+        match &transfer.envelope.payload {
+            TransactionPayload::Transfer { to, .. } if to == &self.bridge_account => {}
+            _ => return Err("A lock must be a transfer to the bridge account".to_string()),
+        }
+
+        let tx_id = self.currency_chain.submit_transaction(transfer).map_err(|e| e.to_string())?;
+        let received = self.currency_chain.get_transaction(&tx_id)
+            .map_err(|e| e.to_string())?
+            .map(|tx| tx.received)
+            .unwrap_or(0);
+        *self.locked.write().unwrap().entry(transfer.envelope.from.clone()).or_default() += received;
+        Ok(tx_id)
+    }
+
+    /// Tokens `user` has locked in the bridge and not yet unlocked
+    pub fn locked_balance(&self, user: &UserId) -> u64 {
+        self.locked.read().unwrap().get(user).copied().unwrap_or(0)
+    }
+
+    /// Follow `chain`'s finalized headers, trusting `validators` as its genesis set
+    ///
+    /// A chain that is already followed keeps its current validator set and
+    /// headers.
+    pub fn follow_chain(&self, chain: ChainId, validators: MultiSigConfig) {
+        self.light_clients
+            .write()
+            .unwrap()
+            .entry(chain.clone())
+            .or_insert_with(|| LightClient::new(chain, validators));
+    }
+
+    /// Hand a followed chain's validator set off to its successor
+    pub fn rotate_validators(&self, chain: &ChainId, rotation: &ValidatorRotation) -> Result<(), String> {
+        let clients = self.light_clients.read().unwrap();
+        let client = clients.get(chain).ok_or_else(|| "Chain is not followed".to_string())?;
+        client.apply_rotation(rotation).map_err(|e| e.to_string())
+    }
+
+    /// Queue a message in `source`'s outbox, returning its nonce
+    ///
+    /// An `Unlock` debits the sender's bridged balance when it is queued, so
+    /// it is refused unless the sender locked at least that much.
+    pub fn send_message(
+        &self,
+        source: ChainId,
+        destination: ChainId,
+        sender: &UserId,
+        payload: BridgeMessagePayload,
+    ) -> Result<u64, String> {
+        if source == destination {
+            return Err("Source and destination chains must be different".to_string());
+        }
+
+        let mut outboxes = self.outboxes.write().unwrap();
+        if let BridgeMessagePayload::Unlock { amount, .. } = &payload {
+            if source != ChainId::ChatChain {
+                return Err("Unlocks are sent from the chat chain".to_string());
+            }
+            let mut locked = self.locked.write().unwrap();
+            let balance = locked.get(sender).copied().unwrap_or(0);
+            if balance < *amount {
+                return Err(format!("Insufficient bridged balance: have {}, need {}", balance, amount));
+            }
+            locked.insert(sender.clone(), balance - amount);
+        }
+
+        let outbox = outboxes.entry(source.clone()).or_default();
+        let nonce = outbox.next_nonce;
+        outbox.next_nonce += 1;
+        outbox.pending.push(BridgeMessage {
+            source,
+            destination,
+            nonce,
+            sender: sender.clone(),
+            payload,
+        });
+        Ok(nonce)
+    }
+
+    /// Commit `source`'s pending messages at its current block height
+    ///
+    /// Returns the header for the chain's validators to sign. Headers must be
+    /// signed and submitted in the order they are committed.
+    pub fn commit_messages(&self, source: &ChainId) -> Result<ChainHeader, String> {
+        let height = match source {
+            ChainId::ChatChain => self.chat_chain.get_current_block()?,
+            ChainId::CurrencyChain => self.currency_chain.get_current_block(),
+        };
+        let validator_epoch = self.light_clients.read().unwrap()
+            .get(source)
+            .map(|client| client.validator_epoch())
+            .unwrap_or(0);
+
+        let mut outboxes = self.outboxes.write().unwrap();
+        let outbox = outboxes.entry(source.clone()).or_default();
+        if outbox.last_header.as_ref().is_some_and(|last| last.height >= height) {
+            return Err(format!("A header was already committed at height {}", height));
+        }
+
+        let messages = std::mem::take(&mut outbox.pending);
+        let tree = MerkleTree::from_leaves(messages.iter().map(BridgeMessage::leaf_bytes));
+        let header = ChainHeader {
+            chain: source.clone(),
+            height,
+            parent_hash: outbox.last_header.as_ref().map(ChainHeader::hash).unwrap_or(GENESIS_PARENT),
+            message_root: tree.root(),
+            validator_epoch,
+        };
+
+        for (index, message) in messages.iter().enumerate() {
+            outbox.locations.insert(message.nonce, (height, index));
+        }
+        outbox.committed.insert(height, messages);
+        outbox.last_header = Some(header.clone());
+        Ok(header)
+    }
+
+    /// Finalize a signed header in the light client following its chain
+    pub fn submit_header(&self, signed: &SignedHeader) -> Result<(), String> {
+        let mut clients = self.light_clients.write().unwrap();
+        let client = clients
+            .get_mut(&signed.header.chain)
+            .ok_or_else(|| "Chain is not followed".to_string())?;
+        client.submit_header(signed).map_err(|e| e.to_string())
+    }
+
+    /// Inclusion proof for a committed message
+    pub fn prove_message(&self, source: &ChainId, nonce: u64) -> Option<MessageProof> {
+        let outboxes = self.outboxes.read().unwrap();
+        let outbox = outboxes.get(source)?;
+        let (height, index) = *outbox.locations.get(&nonce)?;
+        let messages = outbox.committed.get(&height)?;
+        let tree = MerkleTree::from_leaves(messages.iter().map(BridgeMessage::leaf_bytes));
+
+        Some(MessageProof {
+            message: messages[index].clone(),
+            height,
+            path: tree.path(index)?,
+        })
+    }
+
+    /// Check a message proof against the source chain's finalized headers
+    pub fn verify_message(&self, proof: &MessageProof) -> Result<(), String> {
+        let clients = self.light_clients.read().unwrap();
+        let client = clients
+            .get(&proof.message.source)
+            .ok_or_else(|| "Source chain is not followed".to_string())?;
+        client
+            .verify_inclusion(proof.height, &proof.message.leaf_bytes(), &proof.path)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Execute a proven message on its destination chain
+    ///
+    /// Each message executes at most once. A message whose execution fails
+    /// is not marked executed and can be retried. Returns the currency chain
+    /// transaction for unlocks.
+    pub fn execute_message(&self, proof: &MessageProof) -> Result<Option<Uuid>, String> {
+        self.verify_message(proof)?;
+
+        let message = &proof.message;
+        let key = (message.source.clone(), message.nonce);
+        let mut executed = self.executed.write().unwrap();
+        if executed.contains(&key) {
+            return Err("Message already executed".to_string());
+        }
+
+        let result = match (&message.destination, &message.payload) {
+            (ChainId::CurrencyChain, BridgeMessagePayload::Unlock { to, amount }) => Some(
+                self.currency_chain
+                    .module_transfer(&self.bridge_account, to, *amount)
+                    .map_err(|e| e.to_string())?,
+            ),
+            (ChainId::ChatChain, BridgeMessagePayload::UpdateReputation { user, delta }) => {
+                self.chat_chain.update_reputation(user, *delta)?;
+                None
+            }
+            (_, BridgeMessagePayload::Receipt { .. }) => None,
+            _ => return Err("Payload cannot execute on its destination chain".to_string()),
+        };
+
+        executed.insert(key);
+        Ok(result)
+    }
+
+    /// Whether the message `nonce` from `source` has executed
+    pub fn is_executed(&self, source: &ChainId, nonce: u64) -> bool {
+        self.executed.read().unwrap().contains(&(source.clone(), nonce))
+    }
+
+    /// Post receipts from both chains for a cross-chain operation
+    fn post_receipts(&self, bridge_tx: Uuid, user_id: &UserId, chat_tx: Uuid, currency_tx: Uuid) -> Result<(), String> {
+        let chat_receipt = self.send_message(
+            ChainId::ChatChain,
+            ChainId::CurrencyChain,
+            user_id,
+            BridgeMessagePayload::Receipt { bridge_tx, chain_tx: chat_tx },
+        )?;
+        let currency_receipt = self.send_message(
+            ChainId::CurrencyChain,
+            ChainId::ChatChain,
+            user_id,
+            BridgeMessagePayload::Receipt { bridge_tx, chain_tx: currency_tx },
+        )?;
+
+        self.receipts.write().unwrap().insert(
+            bridge_tx,
+            vec![(ChainId::ChatChain, chat_receipt), (ChainId::CurrencyChain, currency_receipt)],
+        );
+        Ok(())
+    }
+
     /// Register user with initial stake (atomic operation)
+    ///
+    /// `stake` must be a stake transaction signed by the user's currency
+    /// account. It is applied before the chat chain registration, so a
+    /// rejected stake registers nothing.
     pub fn register_user_with_stake(
         &self,
         user_id: &UserId,
         public_key: Vec<u8>,
-        stake_amount: u64,
+        stake: &SignedTransaction,
     ) -> Result<Uuid, String> {
+        if stake.envelope.from != *user_id {
+            return Err("Stake transaction is not from the registering user".to_string());
+        }
+        if !matches!(stake.envelope.payload, TransactionPayload::Stake { .. }) {
+            return Err("Registration requires a stake transaction".to_string());
+        }
+
         let bridge_tx_id = Uuid::new_v4();
-        
-        // Step 1: Create wallet on currency chain
-        let _wallet = self.currency_chain.create_wallet(user_id, stake_amount).map_err(|e| e.to_string())?;
+
+        // Step 1: Stake tokens on currency chain
+        let currency_tx = self.currency_chain.submit_transaction(stake).map_err(|e| e.to_string())?;
 
         // Step 2: Register identity on chat chain
         let chat_tx = self.chat_chain.register_user(user_id, public_key)?;
 
-        // Step 3: Stake tokens on currency chain
-        let currency_tx = self.currency_chain.stake(user_id, stake_amount, 86400).map_err(|e| e.to_string())?;
-
         // Record cross-chain transaction
+        self.post_receipts(bridge_tx_id, user_id, chat_tx, currency_tx)?;
         let cross_tx = CrossChainTransaction {
             id: bridge_tx_id,
             operation: "register_with_stake".to_string(),
@@ -89,24 +476,34 @@ impl CrossChainBridge {
     }
 
     /// Create channel with creation fee (atomic operation)
+    ///
+    /// `fee` must be a transfer signed by the channel owner.
     pub fn create_channel_with_fee(
         &self,
         owner: &UserId,
         channel_name: String,
-        creation_fee: u64,
+        fee: &SignedTransaction,
     ) -> Result<Uuid, String> {
         use dchat_core::types::ChannelId;
 
+        if fee.envelope.from != *owner {
+            return Err("Fee transaction is not from the channel owner".to_string());
+        }
+        if !matches!(fee.envelope.payload, TransactionPayload::Transfer { .. }) {
+            return Err("Channel creation fee must be a transfer".to_string());
+        }
+
         let bridge_tx_id = Uuid::new_v4();
         let channel_id = ChannelId(uuid::Uuid::new_v4());
 
         // Step 1: Pay creation fee on currency chain
-        let fee_tx = self.currency_chain.transfer(owner, &UserId(uuid::Uuid::new_v4()), creation_fee).map_err(|e| e.to_string())?;
+        let fee_tx = self.currency_chain.submit_transaction(fee).map_err(|e| e.to_string())?;
 
         // Step 2: Create channel on chat chain
         let chat_tx = self.chat_chain.create_channel(owner, &channel_id, channel_name)?;
 
         // Record cross-chain transaction
+        self.post_receipts(bridge_tx_id, owner, chat_tx, fee_tx)?;
         let cross_tx = CrossChainTransaction {
             id: bridge_tx_id,
             operation: "channel_creation_with_fee".to_string(),
@@ -128,35 +525,29 @@ impl CrossChainBridge {
         Ok(self.transactions.read().unwrap().get(bridge_tx_id).cloned())
     }
 
-    /// Check and finalize cross-chain transactions
+    /// Finalize cross-chain transactions whose receipts are all proven
+    ///
+    /// A transaction becomes an atomic success once each chain's receipt is
+    /// included in a header finalized by the other side's light client.
     pub fn finalize_pending_transactions(&self) -> Result<(), String> {
+        let receipts = self.receipts.read().unwrap();
         let mut txs = self.transactions.write().unwrap();
-        
+
         for tx in txs.values_mut() {
-            if tx.status == CrossChainStatus::Pending {
-                // Check if both chains confirmed
-                let chat_confirmed = if let Some(chat_tx_id) = tx.chat_chain_tx {
-                    match self.chat_chain.get_transaction(&chat_tx_id) {
-                        Ok(chat_tx) => matches!(chat_tx.status, dchat_chain::TransactionStatus::Confirmed { .. }),
-                        _ => false,
-                    }
-                } else {
-                    false
-                };
-
-                let currency_confirmed = if let Some(currency_tx_id) = tx.currency_chain_tx {
-                    match self.currency_chain.get_transaction(&currency_tx_id) {
-                        Ok(tx_opt) => tx_opt.map(|t| t.status == "confirmed").unwrap_or(false),
-                        _ => false,
-                    }
-                } else {
-                    false
-                };
-
-                if chat_confirmed && currency_confirmed {
-                    tx.status = CrossChainStatus::AtomicSuccess;
-                    tx.finalized_at = Some(Utc::now().timestamp());
-                }
+            if tx.status != CrossChainStatus::Pending {
+                continue;
+            }
+            let Some(expected) = receipts.get(&tx.id) else {
+                continue;
+            };
+
+            let all_final = expected.iter().all(|(chain, nonce)| {
+                self.prove_message(chain, *nonce)
+                    .is_some_and(|proof| self.verify_message(&proof).is_ok())
+            });
+            if all_final {
+                tx.status = CrossChainStatus::AtomicSuccess;
+                tx.finalized_at = Some(Utc::now().timestamp());
             }
         }
 
@@ -173,27 +564,218 @@ impl CrossChainBridge {
     }
 }
 
+impl CrossChainBridge {
+    /// A cross-chain transaction, as what its user paid on the currency chain
+    fn currency_loss(&self, tx: &CrossChainTransaction) -> Option<BridgeLoss> {
+        let currency_tx = self.currency_chain.get_transaction(&tx.currency_chain_tx?).ok()??;
+        Some(BridgeLoss {
+            transaction_id: tx.id,
+            initiator: tx.user_id.clone(),
+            amount: currency_tx.amount,
+        })
+    }
+}
+
+impl BridgeEvents for CrossChainBridge {
+    /// This bridge's validators only sign headers, so nothing slashes them
+    fn slash_events(&self) -> Vec<SlashEvent> {
+        Vec::new()
+    }
+
+    fn transaction_loss(&self, transaction_id: Uuid) -> Option<BridgeLoss> {
+        let txs = self.transactions.read().unwrap();
+        self.currency_loss(txs.get(&transaction_id)?)
+    }
+
+    fn rolled_back(&self) -> Vec<BridgeLoss> {
+        let txs = self.transactions.read().unwrap();
+        let mut rolled_back: Vec<_> = txs.values()
+            .filter(|tx| tx.status == CrossChainStatus::RolledBack)
+            .collect();
+        rolled_back.sort_by_key(|tx| tx.created_at);
+        rolled_back.into_iter().filter_map(|tx| self.currency_loss(tx)).collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::chat_chain::ChatChainConfig;
-    use crate::currency_chain::CurrencyChainConfig;
+    use crate::currency_chain::{CurrencyChainConfig, TransactionEnvelope, DEFAULT_BASE_FEE};
+    use dchat_bridge::multisig::{ValidatorId, ValidatorSignature};
+    use dchat_crypto::keys::PrivateKey;
+
+    fn validator_set(seeds: &[u8]) -> (MultiSigConfig, Vec<(ValidatorId, PrivateKey)>) {
+        let signers: Vec<_> = seeds
+            .iter()
+            .map(|seed| {
+                let key = PrivateKey::from_bytes([*seed; 32]);
+                (ValidatorId::new(UserId(Uuid::new_v4()), key.public_key().as_bytes().to_vec()), key)
+            })
+            .collect();
+        let config = MultiSigConfig::new(2, signers.iter().map(|(v, _)| v.clone()).collect()).unwrap();
+        (config, signers)
+    }
+
+    fn sign_header(header: ChainHeader, signers: &[(ValidatorId, PrivateKey)]) -> SignedHeader {
+        let signatures = signers
+            .iter()
+            .map(|(v, key)| ValidatorSignature::sign(v.clone(), key, &header.signing_bytes()))
+            .collect();
+        SignedHeader { header, signatures }
+    }
 
     #[test]
     fn test_register_user_with_stake() {
         let chat_chain = Arc::new(ChatChainClient::new(ChatChainConfig::default()));
         let currency_chain = Arc::new(CurrencyChainClient::new(CurrencyChainConfig::default()));
-        let bridge = CrossChainBridge::new(chat_chain, currency_chain);
+        let bridge = CrossChainBridge::new(chat_chain, currency_chain.clone());
+
+        let validator = UserId(Uuid::new_v4());
+        let validator_key = PrivateKey::generate();
+        currency_chain.create_account(&validator, validator_key.public_key(), 20_000).unwrap();
+        let register = TransactionEnvelope {
+            from: validator.clone(),
+            nonce: 0,
+            max_fee: DEFAULT_BASE_FEE,
+            tip: 0,
+            payload: TransactionPayload::RegisterValidator { self_bond: 10_000, commission_bps: 0 },
+        };
+        currency_chain.submit_transaction(&register.sign(&validator_key)).unwrap();
 
         let user_id = UserId(Uuid::new_v4());
+        let key = PrivateKey::generate();
+        currency_chain.create_account(&user_id, key.public_key(), 1000 + DEFAULT_BASE_FEE).unwrap();
         let public_key = vec![1, 2, 3, 4];
 
-        let bridge_tx_id = bridge.register_user_with_stake(&user_id, public_key, 1000).unwrap();
+        let stake = TransactionEnvelope {
+            from: user_id.clone(),
+            nonce: 0,
+            max_fee: DEFAULT_BASE_FEE,
+            tip: 0,
+            payload: TransactionPayload::Stake { validator, amount: 1000 },
+        }
+        .sign(&key);
+
+        // Another user's stake cannot register this user
+        let other = UserId(Uuid::new_v4());
+        assert!(bridge.register_user_with_stake(&other, vec![5], &stake).is_err());
+
+        let bridge_tx_id = bridge.register_user_with_stake(&user_id, public_key.clone(), &stake).unwrap();
         let status = bridge.get_status(&bridge_tx_id).unwrap();
         
         assert!(status.is_some());
         let tx = status.unwrap();
         assert_eq!(tx.user_id, user_id);
         assert_eq!(tx.operation, "register_with_stake");
+        assert_eq!(currency_chain.get_wallet(&user_id).unwrap().unwrap().staked, 1000);
+
+        // The same stake cannot be replayed
+        assert!(bridge.register_user_with_stake(&user_id, public_key, &stake).is_err());
+
+        // The operation is final once both chains' receipts are finalized
+        let (chat_validators, chat_signers) = validator_set(&[1, 2, 3]);
+        let (currency_validators, currency_signers) = validator_set(&[4, 5, 6]);
+        bridge.follow_chain(ChainId::ChatChain, chat_validators);
+        bridge.follow_chain(ChainId::CurrencyChain, currency_validators);
+
+        let chat_header = bridge.commit_messages(&ChainId::ChatChain).unwrap();
+        bridge.submit_header(&sign_header(chat_header, &chat_signers)).unwrap();
+        bridge.finalize_pending_transactions().unwrap();
+        assert_eq!(bridge.get_status(&bridge_tx_id).unwrap().unwrap().status, CrossChainStatus::Pending);
+
+        let currency_header = bridge.commit_messages(&ChainId::CurrencyChain).unwrap();
+        bridge.submit_header(&sign_header(currency_header, &currency_signers)).unwrap();
+        bridge.finalize_pending_transactions().unwrap();
+        assert_eq!(bridge.get_status(&bridge_tx_id).unwrap().unwrap().status, CrossChainStatus::AtomicSuccess);
+    }
+
+    #[test]
+    fn test_messages_execute_once_with_proof() {
+        let chat_chain = Arc::new(ChatChainClient::new(ChatChainConfig::default()));
+        let currency_chain = Arc::new(CurrencyChainClient::new(CurrencyChainConfig::default()));
+        let bridge = CrossChainBridge::new(chat_chain.clone(), currency_chain.clone());
+        let (validators, signers) = validator_set(&[1, 2, 3]);
+        bridge.follow_chain(ChainId::ChatChain, validators);
+
+        // Lock tokens in the bridge
+        let user = UserId(Uuid::new_v4());
+        let key = PrivateKey::generate();
+        currency_chain.create_account(&user, key.public_key(), 1000 + DEFAULT_BASE_FEE).unwrap();
+        let lock = TransactionEnvelope {
+            from: user.clone(),
+            nonce: 0,
+            max_fee: DEFAULT_BASE_FEE,
+            tip: 0,
+            payload: TransactionPayload::Transfer { to: bridge.bridge_account().clone(), amount: 1000 },
+        };
+        bridge.lock_tokens(&lock.sign(&key)).unwrap();
+        assert_eq!(bridge.locked_balance(&user), 1000);
+
+        // Nobody can unlock more than they locked
+        let recipient = UserId(Uuid::new_v4());
+        let unbacked = BridgeMessagePayload::Unlock { to: recipient.clone(), amount: 1 };
+        assert!(bridge.send_message(ChainId::ChatChain, ChainId::CurrencyChain, &recipient, unbacked).is_err());
+        let too_much = BridgeMessagePayload::Unlock { to: recipient.clone(), amount: 1001 };
+        assert!(bridge.send_message(ChainId::ChatChain, ChainId::CurrencyChain, &user, too_much).is_err());
+
+        let nonce = bridge
+            .send_message(
+                ChainId::ChatChain,
+                ChainId::CurrencyChain,
+                &user,
+                BridgeMessagePayload::Unlock { to: recipient.clone(), amount: 400 },
+            )
+            .unwrap();
+        bridge
+            .send_message(
+                ChainId::ChatChain,
+                ChainId::CurrencyChain,
+                &user,
+                BridgeMessagePayload::Unlock { to: recipient.clone(), amount: 100 },
+            )
+            .unwrap();
+        let header = bridge.commit_messages(&ChainId::ChatChain).unwrap();
+        assert!(bridge.commit_messages(&ChainId::ChatChain).is_err());
+        let proof = bridge.prove_message(&ChainId::ChatChain, nonce).unwrap();
+
+        // Nothing executes before the header is finalized
+        assert!(bridge.execute_message(&proof).is_err());
+
+        // A single validator cannot finalize the header
+        assert!(bridge.submit_header(&sign_header(header.clone(), &signers[..1])).is_err());
+        bridge.submit_header(&sign_header(header, &signers[..2])).unwrap();
+
+        // A tampered message does not match the proof
+        let mut tampered = proof.clone();
+        tampered.message.payload = BridgeMessagePayload::Unlock { to: recipient.clone(), amount: 1000 };
+        assert!(bridge.execute_message(&tampered).is_err());
+
+        bridge.execute_message(&proof).unwrap();
+        assert_eq!(currency_chain.get_balance(&recipient).unwrap(), 400);
+        assert!(bridge.is_executed(&ChainId::ChatChain, nonce));
+
+        // Replays are rejected
+        assert!(bridge.execute_message(&proof).is_err());
+        assert_eq!(currency_chain.get_balance(&recipient).unwrap(), 400);
+
+        assert_eq!(bridge.locked_balance(&user), 500);
+
+        // A bridge restarted from its saved state still refuses the replay
+        // and keeps following the header chain
+        let state: BridgeState = serde_json::from_slice(&serde_json::to_vec(&bridge.state()).unwrap()).unwrap();
+        let restored = CrossChainBridge::with_state(chat_chain.clone(), currency_chain.clone(), state);
+        assert_eq!(restored.bridge_account(), bridge.bridge_account());
+        assert!(restored.is_executed(&ChainId::ChatChain, nonce));
+        assert!(restored.execute_message(&proof).is_err());
+        assert_eq!(restored.locked_balance(&user), 500);
+        restored.execute_message(&restored.prove_message(&ChainId::ChatChain, nonce + 1).unwrap()).unwrap();
+        assert_eq!(currency_chain.get_balance(&recipient).unwrap(), 500);
+
+        // The next header must link to the finalized one
+        chat_chain.advance_block().unwrap();
+        let mut next = restored.commit_messages(&ChainId::ChatChain).unwrap();
+        next.parent_hash = GENESIS_PARENT;
+        assert!(restored.submit_header(&sign_header(next, &signers)).is_err());
     }
 }
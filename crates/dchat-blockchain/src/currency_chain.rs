@@ -1,14 +1,66 @@
 //! Currency Chain client for payments, staking, rewards, and economics
+//!
+//! User accounts are bound to an Ed25519 key when they are created and only
+//! move funds through signed transactions. A transaction must carry its
+//! account's next nonce, so each signed transaction applies at most once, and
+//! every check runs before any balance changes.
+//!
+//! Fees follow a base-fee market: a transaction pays the current base fee,
+//! which is burned, plus its tip up to `max_fee`, which goes to the fee
+//! recipient. The base fee moves toward a target number of transactions per
+//! block by at most 1/8 per block.
+//!
+//! Protocol accounts (treasury, insurance fund, fee pool) have no key; the
+//! node moves their funds with `module_transfer`.
+//!
+//! Staked tokens stay in the owner's wallet under `staked` while they are
+//! bonded or unbonding; `StakingLedger` tracks which validator backs them.
+//!
+//! Wallets also hold other ledger tokens by symbol. Signed swaps and
+//! liquidity deposits trade them against the native token through
+//! `AmmLedger` pools; pooled tokens leave the wallet for the pool's reserves
+//! and skip the transfer burn.
+//!
+//! Any account can issue its own token through `AssetLedger`; the issuer
+//! mints it and holders transfer or burn it with signed transactions, without
+//! the transfer burn. A bond moves native or issued tokens out of the wallet
+//! until its lock passes, and token-gated channels read it through
+//! `TokenLedger`.
 
 use chrono::Utc;
 use dchat_core::error::{Error, Result};
 use dchat_core::types::UserId;
+use dchat_crypto::keys::{PrivateKey, PublicKey};
+use dchat_crypto::signatures::{self, Signature};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 use uuid::Uuid;
 
-use crate::tokenomics::{TokenomicsManager, BurnReason};
+use crate::amm::{AmmLedger, Pool, SwapDirection, Twap};
+use crate::assets::{AssetLedger, Bond, IssueToken, TokenMetadata, NATIVE_TOKEN};
+use crate::staking::{StakingConfig, StakingLedger, UnbondingEntry, Validator};
+use crate::tokenomics::{TokenomicsManager, BurnReason, MintReason};
+use dchat_bridge::slashing::SlashingManager;
+use dchat_chain::insurance_fund::FundLedger;
+use dchat_marketplace::escrow::{EscrowLedger, LedgerPayment};
+use dchat_messaging::channel_access::TokenLedger;
+
+/// Domain separator for signed currency transactions
+const TX_DOMAIN: &[u8] = b"dchat-currency-tx-v1";
+
+/// Base fee a new chain starts at
+pub const DEFAULT_BASE_FEE: u64 = 10;
+
+/// The base fee never drops below this
+pub const MIN_BASE_FEE: u64 = 1;
+
+/// Transactions per block the base fee steers toward
+pub const TARGET_TXS_PER_BLOCK: u64 = 100;
+
+/// The base fee changes by at most 1/8 per block
+const BASE_FEE_CHANGE_DENOMINATOR: u64 = 8;
 
 /// Configuration for Currency Chain client
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +75,9 @@ pub struct CurrencyChainConfig {
     pub tx_timeout_seconds: u64,
     /// Retry attempts for failed transactions
     pub max_retries: u32,
+    /// Validator staking parameters
+    #[serde(default)]
+    pub staking: StakingConfig,
 }
 
 impl Default for CurrencyChainConfig {
@@ -33,6 +88,7 @@ impl Default for CurrencyChainConfig {
             confirmation_blocks: 6,
             tx_timeout_seconds: 300,
             max_retries: 3,
+            staking: StakingConfig::default(),
         }
     }
 }
@@ -41,10 +97,16 @@ impl Default for CurrencyChainConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CurrencyTransaction {
     pub id: Uuid,
-    pub tx_type: String, // "payment", "stake", "reward", "slash", "swap"
+    pub tx_type: String, // "payment", "stake", "reward", "slash", "swap", "token_transfer"
     pub from: UserId,
     pub to: Option<UserId>,
     pub amount: u64,
+    /// Amount credited to `to` after the transfer burn
+    #[serde(default)]
+    pub received: u64,
+    /// Fee paid (base fee plus tip)
+    #[serde(default)]
+    pub fee: u64,
     pub status: String, // "pending", "confirmed", "failed"
     pub confirmations: u32,
     pub block_height: u64,
@@ -58,18 +120,210 @@ pub struct Wallet {
     pub balance: u64,
     pub staked: u64,
     pub rewards_pending: u64,
+    /// Nonce the account's next transaction must carry
+    #[serde(default)]
+    pub nonce: u64,
+    /// Key that signs the account's transactions; `None` for accounts that
+    /// can only receive or are moved by the protocol
+    #[serde(default)]
+    pub public_key: Option<PublicKey>,
+    /// Balances of other ledger tokens, by symbol
+    #[serde(default)]
+    pub tokens: HashMap<String, u64>,
+}
+
+impl Wallet {
+    fn empty(user_id: &UserId) -> Self {
+        Self {
+            user_id: user_id.clone(),
+            balance: 0,
+            staked: 0,
+            rewards_pending: 0,
+            nonce: 0,
+            public_key: None,
+            tokens: HashMap::new(),
+        }
+    }
+
+    /// Credit ledger token `token`
+    fn add_tokens(&mut self, token: &str, amount: u64) {
+        if amount > 0 {
+            *self.tokens.entry(token.to_string()).or_insert(0) += amount;
+        }
+    }
+
+    /// Debit ledger token `token`, which the wallet was checked to hold
+    fn take_tokens(&mut self, token: &str, amount: u64) {
+        if amount == 0 {
+            return;
+        }
+        let balance = self.tokens.get_mut(token).expect("checked tokens are held");
+        *balance -= amount;
+        if *balance == 0 {
+            self.tokens.remove(token);
+        }
+    }
+}
+
+/// What a signed transaction does
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionPayload {
+    /// Pay another account
+    Transfer { to: UserId, amount: u64 },
+    /// Become a validator, bonding `self_bond` as its own stake
+    RegisterValidator { self_bond: u64, commission_bps: u16 },
+    /// Bond tokens to a validator; bonding to yourself adds to your self-bond
+    Stake { validator: UserId, amount: u64 },
+    /// Start unbonding tokens from a validator
+    Unstake { validator: UserId, amount: u64 },
+    /// Move pending rewards into the balance
+    ClaimRewards,
+    /// Trade through the pool pairing the native token with `token`
+    Swap { token: String, direction: SwapDirection, amount_in: u64, min_out: u64 },
+    /// Deposit into the `token` pool at its current ratio
+    AddLiquidity { token: String, native_amount: u64, token_amount: u64, min_shares: u64 },
+    /// Burn LP shares of the `token` pool for both reserves
+    RemoveLiquidity { token: String, shares: u64, min_native: u64, min_token: u64 },
+    /// Claim vested tokens from one of the sender's grants, or all of them
+    ClaimVested { grant_id: Option<Uuid> },
+    /// Issue a new token, crediting `initial_supply` to the sender
+    IssueToken(IssueToken),
+    /// Mint more of a token the sender issued
+    MintToken { symbol: String, to: UserId, amount: u64 },
+    /// Destroy tokens the sender holds
+    BurnToken { symbol: String, amount: u64 },
+    /// Pay another account in an issued token
+    TransferToken { symbol: String, to: UserId, amount: u64 },
+    /// Lock native or issued tokens for at least `lock_blocks`
+    Bond { token: String, amount: u64, lock_blocks: u64 },
+    /// Release bonded tokens whose lock has passed
+    Unbond { token: String, amount: u64 },
+}
+
+impl TransactionPayload {
+    /// Tokens the payload spends from the balance, excluding fees
+    fn value(&self) -> u64 {
+        match self {
+            TransactionPayload::Transfer { amount, .. } | TransactionPayload::Stake { amount, .. } => *amount,
+            TransactionPayload::RegisterValidator { self_bond, .. } => *self_bond,
+            TransactionPayload::Swap { direction: SwapDirection::NativeToToken, amount_in, .. } => *amount_in,
+            TransactionPayload::AddLiquidity { native_amount, .. } => *native_amount,
+            TransactionPayload::Bond { token, amount, .. } if token == NATIVE_TOKEN => *amount,
+            TransactionPayload::Unstake { .. }
+            | TransactionPayload::ClaimRewards
+            | TransactionPayload::Swap { .. }
+            | TransactionPayload::RemoveLiquidity { .. }
+            | TransactionPayload::ClaimVested { .. }
+            | TransactionPayload::IssueToken(_)
+            | TransactionPayload::MintToken { .. }
+            | TransactionPayload::BurnToken { .. }
+            | TransactionPayload::TransferToken { .. }
+            | TransactionPayload::Bond { .. }
+            | TransactionPayload::Unbond { .. } => 0,
+        }
+    }
+
+    /// Ledger token the payload spends from the wallet, and how much
+    fn token_value(&self) -> Option<(&str, u64)> {
+        match self {
+            TransactionPayload::Swap { token, direction: SwapDirection::TokenToNative, amount_in, .. } => {
+                Some((token, *amount_in))
+            }
+            TransactionPayload::AddLiquidity { token, token_amount, .. } => Some((token, *token_amount)),
+            TransactionPayload::BurnToken { symbol, amount } | TransactionPayload::TransferToken { symbol, amount, .. } => {
+                Some((symbol, *amount))
+            }
+            TransactionPayload::Bond { token, amount, .. } if token != NATIVE_TOKEN => Some((token, *amount)),
+            _ => None,
+        }
+    }
+}
+
+/// Tokens a pool operation moves between the sender's wallet and the pool
+struct PoolTransfer {
+    native_in: u64,
+    token_in: u64,
+    native_out: u64,
+    token_out: u64,
+}
+
+/// An unsigned currency transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionEnvelope {
+    pub from: UserId,
+    /// Must equal the sender's current nonce
+    pub nonce: u64,
+    /// Most the sender will pay in fees
+    pub max_fee: u64,
+    /// Offered on top of the base fee, within `max_fee`
+    pub tip: u64,
+    pub payload: TransactionPayload,
+}
+
+impl TransactionEnvelope {
+    /// Bytes the sender signs
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = TX_DOMAIN.to_vec();
+        bytes.extend(serde_json::to_vec(self).expect("transactions serialize"));
+        bytes
+    }
+
+    /// Sign with the sender's key
+    pub fn sign(self, private_key: &PrivateKey) -> SignedTransaction {
+        let signature = signatures::sign(private_key, &self.signing_bytes()).to_bytes().to_vec();
+        SignedTransaction { envelope: self, signature }
+    }
+}
+
+/// A transaction with the sender's Ed25519 signature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub envelope: TransactionEnvelope,
+    pub signature: Vec<u8>,
 }
 
-/// Staking position
+impl SignedTransaction {
+    /// Whether `public_key` signed this transaction
+    pub fn is_signed_by(&self, public_key: &PublicKey) -> bool {
+        let Ok(bytes) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        signatures::verify(public_key, &self.envelope.signing_bytes(), &Signature::from_bytes(bytes)).is_ok()
+    }
+}
+
+/// Base fee state
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct StakePosition {
-    pub user_id: UserId,
-    pub amount: u64,
-    pub locked_until: i64,
-    pub rewards_earned: u64,
+struct FeeMarket {
+    base_fee: u64,
+    txs_in_block: u64,
+}
+
+impl FeeMarket {
+    /// Fee charged to a transaction, if it covers the base fee
+    fn fee_for(&self, envelope: &TransactionEnvelope) -> Option<u64> {
+        let headroom = envelope.max_fee.checked_sub(self.base_fee)?;
+        Some(self.base_fee + envelope.tip.min(headroom))
+    }
+
+    /// Move the base fee toward the block target and start a new block
+    fn close_block(&mut self) {
+        let base = self.base_fee;
+        let used = self.txs_in_block;
+        let change = |delta: u64| base * delta / TARGET_TXS_PER_BLOCK / BASE_FEE_CHANGE_DENOMINATOR;
+        self.base_fee = match used.cmp(&TARGET_TXS_PER_BLOCK) {
+            Ordering::Greater => base + change(used - TARGET_TXS_PER_BLOCK).max(1),
+            Ordering::Less => base.saturating_sub(change(TARGET_TXS_PER_BLOCK - used).max(1)).max(MIN_BASE_FEE),
+            Ordering::Equal => base,
+        };
+        self.txs_in_block = 0;
+    }
 }
 
 /// Currency Chain client for payments, staking, rewards, and economics
+///
+/// Methods that hold several locks take `wallets` first, then `staking`,
+/// `amm` and `assets`, so concurrent callers cannot deadlock.
 pub struct CurrencyChainClient {
     #[allow(dead_code)]
     config: CurrencyChainConfig,
@@ -79,34 +333,51 @@ pub struct CurrencyChainClient {
     current_block: Arc<RwLock<u64>>,
     /// User wallet balances
     wallets: Arc<RwLock<HashMap<UserId, Wallet>>>,
-    /// Staking positions
-    stakes: Arc<RwLock<HashMap<UserId, StakePosition>>>,
+    /// Validators, delegations and unbonding stake
+    staking: Arc<RwLock<StakingLedger>>,
+    /// Swap pools and their LP shares
+    amm: Arc<RwLock<AmmLedger>>,
+    /// Issued tokens and bonds
+    assets: Arc<RwLock<AssetLedger>>,
+    /// Slash events from the bridge already applied
+    slashes_applied: Arc<RwLock<usize>>,
     /// Tokenomics manager (optional - can be shared)
     tokenomics: Option<Arc<TokenomicsManager>>,
+    /// Base fee market
+    fee_market: Arc<RwLock<FeeMarket>>,
+    /// Account tips are paid to; tips are burned when unset
+    fee_recipient: Arc<RwLock<Option<UserId>>>,
+    /// Protocol accounts moved by `module_transfer`
+    module_accounts: Arc<RwLock<HashSet<UserId>>>,
 }
 
 impl CurrencyChainClient {
     /// Create new currency chain client
     pub fn new(config: CurrencyChainConfig) -> Self {
         Self {
+            staking: Arc::new(RwLock::new(StakingLedger::new(config.staking.clone()))),
+            amm: Arc::new(RwLock::new(AmmLedger::new())),
+            assets: Arc::new(RwLock::new(AssetLedger::new())),
+            slashes_applied: Arc::new(RwLock::new(0)),
             config,
             transactions: Arc::new(RwLock::new(HashMap::new())),
             current_block: Arc::new(RwLock::new(1)),
             wallets: Arc::new(RwLock::new(HashMap::new())),
-            stakes: Arc::new(RwLock::new(HashMap::new())),
             tokenomics: None,
+            fee_market: Arc::new(RwLock::new(FeeMarket {
+                base_fee: DEFAULT_BASE_FEE,
+                txs_in_block: 0,
+            })),
+            fee_recipient: Arc::new(RwLock::new(None)),
+            module_accounts: Arc::new(RwLock::new(HashSet::new())),
         }
     }
 
     /// Create new currency chain client with tokenomics integration
     pub fn with_tokenomics(config: CurrencyChainConfig, tokenomics: Arc<TokenomicsManager>) -> Self {
         Self {
-            config,
-            transactions: Arc::new(RwLock::new(HashMap::new())),
-            current_block: Arc::new(RwLock::new(1)),
-            wallets: Arc::new(RwLock::new(HashMap::new())),
-            stakes: Arc::new(RwLock::new(HashMap::new())),
             tokenomics: Some(tokenomics),
+            ..Self::new(config)
         }
     }
 
@@ -115,154 +386,462 @@ impl CurrencyChainClient {
         self.tokenomics.clone()
     }
 
-    /// Create wallet for user
+    /// Create a keyless wallet for user
+    ///
+    /// A keyless wallet can receive funds but not send them.
     pub fn create_wallet(&self, user_id: &UserId, initial_balance: u64) -> Result<Wallet> {
+        let mut wallets = self.wallets.write().unwrap();
+        Self::insert_account(&mut wallets, user_id, None, initial_balance)
+    }
+
+    /// Create an account whose transactions are signed by `public_key`
+    ///
+    /// The key is fixed for the life of the account.
+    pub fn create_account(&self, user_id: &UserId, public_key: PublicKey, initial_balance: u64) -> Result<Wallet> {
+        let mut wallets = self.wallets.write().unwrap();
+        Self::insert_account(&mut wallets, user_id, Some(public_key), initial_balance)
+    }
+
+    /// Create a protocol account moved by `module_transfer`
+    pub fn create_module_account(&self, account: &UserId, initial_balance: u64) -> Result<Wallet> {
+        let mut wallets = self.wallets.write().unwrap();
+        let wallet = Self::insert_account(&mut wallets, account, None, initial_balance)?;
+        self.module_accounts.write().unwrap().insert(account.clone());
+        Ok(wallet)
+    }
+
+    /// Whether `account` is a protocol account
+    pub fn is_module_account(&self, account: &UserId) -> bool {
+        self.module_accounts.read().unwrap().contains(account)
+    }
+
+    /// Add a new account; an existing one is never replaced, so its key,
+    /// nonce and balances cannot be reset
+    fn insert_account(
+        wallets: &mut HashMap<UserId, Wallet>,
+        user_id: &UserId,
+        public_key: Option<PublicKey>,
+        initial_balance: u64,
+    ) -> Result<Wallet> {
+        if wallets.contains_key(user_id) {
+            return Err(Error::AlreadyExists(format!("Account already exists: {}", user_id)));
+        }
+
         let wallet = Wallet {
-            user_id: user_id.clone(),
             balance: initial_balance,
-            staked: 0,
-            rewards_pending: 0,
+            public_key,
+            ..Wallet::empty(user_id)
         };
-        self.wallets.write().unwrap().insert(user_id.clone(), wallet.clone());
+        wallets.insert(user_id.clone(), wallet.clone());
         Ok(wallet)
     }
 
+    /// Pay tips to `account`
+    pub fn set_fee_recipient(&self, account: UserId) {
+        *self.fee_recipient.write().unwrap() = Some(account);
+    }
+
+    /// Base fee a transaction must cover in the current block
+    pub fn base_fee(&self) -> u64 {
+        self.fee_market.read().unwrap().base_fee
+    }
+
+    /// Nonce the account's next transaction must carry
+    pub fn get_nonce(&self, user_id: &UserId) -> u64 {
+        self.wallets.read().unwrap().get(user_id).map(|w| w.nonce).unwrap_or(0)
+    }
+
     /// Get wallet balance
     pub fn get_balance(&self, user_id: &UserId) -> Result<u64> {
         let wallets = self.wallets.read().unwrap();
         Ok(wallets.get(user_id).map(|w| w.balance).unwrap_or(0))
     }
 
-    /// Transfer tokens between users
-    pub fn transfer(
-        &self,
-        from: &UserId,
-        to: &UserId,
-        amount: u64,
-    ) -> Result<Uuid> {
+    /// Free balance of ledger token `token`; `NATIVE_TOKEN` reads the
+    /// native balance
+    pub fn get_token_balance(&self, user_id: &UserId, token: &str) -> u64 {
+        let wallets = self.wallets.read().unwrap();
+        let wallet = wallets.get(user_id);
+        if token == NATIVE_TOKEN {
+            return wallet.map_or(0, |w| w.balance);
+        }
+        wallet.and_then(|w| w.tokens.get(token).copied()).unwrap_or(0)
+    }
+
+    /// Metadata of an issued token
+    pub fn get_token_metadata(&self, symbol: &str) -> Option<TokenMetadata> {
+        self.assets.read().unwrap().token(symbol).cloned()
+    }
+
+    /// Every issued token
+    pub fn get_issued_tokens(&self) -> Vec<TokenMetadata> {
+        self.assets.read().unwrap().tokens().into_iter().cloned().collect()
+    }
+
+    /// `holder`'s bond of `token`
+    pub fn get_bond(&self, holder: &UserId, token: &str) -> Option<Bond> {
+        self.assets.read().unwrap().bond_of(holder, token)
+    }
+
+    /// Verify and apply a signed transaction
+    ///
+    /// The signature, nonce, fee and balance are all checked before anything
+    /// changes; a rejected transaction leaves every account untouched.
+    pub fn submit_transaction(&self, tx: &SignedTransaction) -> Result<Uuid> {
         let mut wallets = self.wallets.write().unwrap();
-        
-        let from_wallet = wallets.get_mut(from)
-            .ok_or_else(|| Error::NotFound(format!("User not found: {}", from)))?;
-        
-        if from_wallet.balance < amount {
-            return Err(Error::InvalidInput(format!("Insufficient balance: have {}, need {}", from_wallet.balance, amount)));
+        let envelope = &tx.envelope;
+        let fee = self.authorize(&wallets, tx)?;
+
+        // Staking payloads are checked by applying them to the staking
+        // ledger, which leaves it untouched on error
+        let height = self.get_current_block();
+        let mut staking = self.staking.write().unwrap();
+        match &envelope.payload {
+            TransactionPayload::RegisterValidator { self_bond, commission_bps } => {
+                staking.register_validator(&envelope.from, *self_bond, *commission_bps)?;
+            }
+            TransactionPayload::Stake { validator, amount } => {
+                staking.bond(&envelope.from, validator, *amount)?;
+            }
+            TransactionPayload::Unstake { validator, amount } => {
+                staking.unbond(&envelope.from, validator, *amount, height)?;
+            }
+            _ => {}
         }
-        
-        // Calculate transaction fee burn (1% default)
-        let burn_amount = if let Some(ref tokenomics) = self.tokenomics {
-            (amount * tokenomics.get_statistics().burn_rate_bps as u64) / 10000
-        } else {
-            0
+        drop(staking);
+
+        // Pool payloads likewise check by applying to the AMM ledger
+        let mut amm = self.amm.write().unwrap();
+        let pooled = match &envelope.payload {
+            TransactionPayload::Swap { token, direction, amount_in, min_out } => {
+                let out = amm.swap(token, *direction, *amount_in, *min_out, height)?;
+                Some(match direction {
+                    SwapDirection::NativeToToken => PoolTransfer { native_in: *amount_in, token_in: 0, native_out: 0, token_out: out },
+                    SwapDirection::TokenToNative => PoolTransfer { native_in: 0, token_in: *amount_in, native_out: out, token_out: 0 },
+                })
+            }
+            TransactionPayload::AddLiquidity { token, native_amount, token_amount, min_shares } => {
+                let deposit = amm.add_liquidity(&envelope.from, token, *native_amount, *token_amount, *min_shares, height)?;
+                Some(PoolTransfer { native_in: deposit.native, token_in: deposit.token, native_out: 0, token_out: 0 })
+            }
+            TransactionPayload::RemoveLiquidity { token, shares, min_native, min_token } => {
+                let withdrawal = amm.remove_liquidity(&envelope.from, token, *shares, *min_native, *min_token, height)?;
+                Some(PoolTransfer { native_in: 0, token_in: 0, native_out: withdrawal.native, token_out: withdrawal.token })
+            }
+            _ => None,
         };
-        
-        let net_amount = amount - burn_amount;
-        
-        from_wallet.balance -= amount;
-        
-        let to_wallet = wallets.entry(to.clone())
-            .or_insert_with(|| Wallet {
-                user_id: to.clone(),
-                balance: 0,
-                staked: 0,
-                rewards_pending: 0,
-            });
-        
-        to_wallet.balance += net_amount;
+        drop(amm);
 
-        // Burn transaction fee
-        if burn_amount > 0 {
-            if let Some(ref tokenomics) = self.tokenomics {
-                let _ = tokenomics.burn_tokens(burn_amount, BurnReason::TransactionFee, from.clone());
+        // Token and bond payloads check by applying to the asset ledger
+        let mut assets = self.assets.write().unwrap();
+        match &envelope.payload {
+            TransactionPayload::IssueToken(token) => {
+                assets.issue(&envelope.from, token, height)?;
             }
+            TransactionPayload::MintToken { symbol, amount, .. } => {
+                assets.mint(&envelope.from, symbol, *amount)?;
+            }
+            TransactionPayload::BurnToken { symbol, amount } => {
+                assets.burn(symbol, *amount)?;
+            }
+            TransactionPayload::TransferToken { symbol, .. } => {
+                if assets.token(symbol).is_none() {
+                    return Err(Error::NotFound(format!("Token not found: {}", symbol)));
+                }
+            }
+            TransactionPayload::Bond { token, amount, lock_blocks } => {
+                assets.bond(&envelope.from, token, *amount, *lock_blocks, height)?;
+            }
+            TransactionPayload::Unbond { token, amount } => {
+                assets.unbond(&envelope.from, token, *amount, height)?;
+            }
+            _ => {}
         }
+        drop(assets);
+
+        // A vesting claim mints last, once every other check has passed
+        let vested = match &envelope.payload {
+            TransactionPayload::ClaimVested { grant_id } => {
+                let tokenomics = self.tokenomics.as_ref()
+                    .ok_or_else(|| Error::chain("Currency chain has no tokenomics manager"))?;
+                tokenomics.claim_vested(&envelope.from, *grant_id)?
+            }
+            _ => 0,
+        };
 
-        let tx = CurrencyTransaction {
+        // Checks passed: consume the nonce and collect the fee
+        let base_fee = self.base_fee();
+        let sender = wallets.get_mut(&envelope.from).expect("authorized sender exists");
+        sender.nonce += 1;
+        sender.balance -= fee;
+        self.collect_fee(&mut wallets, &envelope.from, base_fee, fee - base_fee);
+
+        let record = |tx_type: &str, to: Option<UserId>, amount: u64| CurrencyTransaction {
             id: Uuid::new_v4(),
-            tx_type: "payment".to_string(),
-            from: from.clone(),
-            to: Some(to.clone()),
+            tx_type: tx_type.to_string(),
+            from: envelope.from.clone(),
+            to,
             amount,
+            received: amount,
+            fee,
             status: "pending".to_string(),
             confirmations: 0,
             block_height: 0,
             created_at: Utc::now().timestamp(),
         };
 
+        let tx = match &envelope.payload {
+            TransactionPayload::Transfer { to, amount } => {
+                let received = self.move_funds(&mut wallets, &envelope.from, to, *amount);
+                CurrencyTransaction { received, ..record("payment", Some(to.clone()), *amount) }
+            }
+            TransactionPayload::RegisterValidator { self_bond: amount, .. }
+            | TransactionPayload::Stake { amount, .. } => {
+                let wallet = wallets.get_mut(&envelope.from).expect("authorized sender exists");
+                wallet.balance -= amount;
+                wallet.staked += amount;
+                record("stake", None, *amount)
+            }
+            TransactionPayload::Unstake { validator, amount } => {
+                // Tokens stay in `staked` until the unbonding period ends
+                record("unstake", Some(validator.clone()), *amount)
+            }
+            TransactionPayload::ClaimRewards => {
+                let wallet = wallets.get_mut(&envelope.from).expect("authorized sender exists");
+                let rewards = wallet.rewards_pending;
+                wallet.balance += rewards;
+                wallet.rewards_pending = 0;
+                record("reward", None, rewards)
+            }
+            TransactionPayload::Swap { token, .. }
+            | TransactionPayload::AddLiquidity { token, .. }
+            | TransactionPayload::RemoveLiquidity { token, .. } => {
+                let moved = pooled.expect("pool payloads were applied");
+                let wallet = wallets.get_mut(&envelope.from).expect("authorized sender exists");
+                wallet.balance = wallet.balance - moved.native_in + moved.native_out;
+                wallet.take_tokens(token, moved.token_in);
+                wallet.add_tokens(token, moved.token_out);
+                let tx_type = if matches!(envelope.payload, TransactionPayload::Swap { .. }) { "swap" } else { "liquidity" };
+                CurrencyTransaction {
+                    received: moved.native_out.max(moved.token_out),
+                    ..record(tx_type, None, moved.native_in.max(moved.token_in))
+                }
+            }
+            TransactionPayload::ClaimVested { .. } => {
+                wallets.get_mut(&envelope.from).expect("authorized sender exists").balance += vested;
+                record("vesting", None, vested)
+            }
+            TransactionPayload::IssueToken(token) => {
+                let wallet = wallets.get_mut(&envelope.from).expect("authorized sender exists");
+                wallet.add_tokens(&token.symbol, token.initial_supply);
+                record("issue", None, token.initial_supply)
+            }
+            TransactionPayload::MintToken { symbol, to, amount } => {
+                wallets.entry(to.clone()).or_insert_with(|| Wallet::empty(to)).add_tokens(symbol, *amount);
+                record("mint", Some(to.clone()), *amount)
+            }
+            TransactionPayload::BurnToken { symbol, amount } => {
+                wallets.get_mut(&envelope.from).expect("authorized sender exists").take_tokens(symbol, *amount);
+                record("burn", None, *amount)
+            }
+            TransactionPayload::TransferToken { symbol, to, amount } => {
+                wallets.get_mut(&envelope.from).expect("authorized sender exists").take_tokens(symbol, *amount);
+                wallets.entry(to.clone()).or_insert_with(|| Wallet::empty(to)).add_tokens(symbol, *amount);
+                record("token_transfer", Some(to.clone()), *amount)
+            }
+            TransactionPayload::Bond { token, amount, .. } => {
+                let wallet = wallets.get_mut(&envelope.from).expect("authorized sender exists");
+                if token == NATIVE_TOKEN {
+                    wallet.balance -= amount;
+                } else {
+                    wallet.take_tokens(token, *amount);
+                }
+                record("bond", None, *amount)
+            }
+            TransactionPayload::Unbond { token, amount } => {
+                let wallet = wallets.get_mut(&envelope.from).expect("authorized sender exists");
+                if token == NATIVE_TOKEN {
+                    wallet.balance += amount;
+                } else {
+                    wallet.add_tokens(token, *amount);
+                }
+                record("unbond", None, *amount)
+            }
+        };
+
         let tx_id = tx.id;
         self.transactions.write().unwrap().insert(tx_id, tx);
-        
+        self.fee_market.write().unwrap().txs_in_block += 1;
+
         Ok(tx_id)
     }
 
-    /// Stake tokens for rewards
-    pub fn stake(&self, user_id: &UserId, amount: u64, lock_duration_seconds: i64) -> Result<Uuid> {
-        let mut wallets = self.wallets.write().unwrap();
-        
-        let wallet = wallets.get_mut(user_id)
-            .ok_or_else(|| Error::NotFound(format!("User not found: {}", user_id)))?;
-        
-        if wallet.balance < amount {
-            return Err(Error::InvalidInput(format!("Insufficient balance: have {}, need {}", wallet.balance, amount)));
+    /// Check a transaction against current state, returning its fee
+    fn authorize(&self, wallets: &HashMap<UserId, Wallet>, tx: &SignedTransaction) -> Result<u64> {
+        let envelope = &tx.envelope;
+        let wallet = wallets.get(&envelope.from)
+            .ok_or_else(|| Error::NotFound(format!("User not found: {}", envelope.from)))?;
+
+        let public_key = wallet.public_key.as_ref()
+            .ok_or_else(|| Error::PermissionDenied(format!("Account {} has no signing key", envelope.from)))?;
+        if !tx.is_signed_by(public_key) {
+            return Err(Error::PermissionDenied("Invalid transaction signature".to_string()));
         }
-        
-        wallet.balance -= amount;
-        wallet.staked += amount;
 
-        let locked_until = Utc::now().timestamp() + lock_duration_seconds;
-        let stake_position = StakePosition {
-            user_id: user_id.clone(),
-            amount,
-            locked_until,
-            rewards_earned: 0,
-        };
+        if envelope.nonce != wallet.nonce {
+            return Err(Error::InvalidInput(format!("Invalid nonce: expected {}, got {}", wallet.nonce, envelope.nonce)));
+        }
 
-        self.stakes.write().unwrap().insert(user_id.clone(), stake_position);
+        let base_fee = self.base_fee();
+        let fee = self.fee_market.read().unwrap().fee_for(envelope)
+            .ok_or_else(|| Error::InvalidInput(format!("Max fee {} is below the base fee {}", envelope.max_fee, base_fee)))?;
 
-        let tx = CurrencyTransaction {
-            id: Uuid::new_v4(),
-            tx_type: "stake".to_string(),
-            from: user_id.clone(),
-            to: None,
-            amount,
-            status: "pending".to_string(),
-            confirmations: 0,
-            block_height: 0,
-            created_at: Utc::now().timestamp(),
+        let needed = envelope.payload.value().checked_add(fee)
+            .ok_or_else(|| Error::InvalidInput("Transaction amount overflows".to_string()))?;
+        if wallet.balance < needed {
+            return Err(Error::InvalidInput(format!("Insufficient balance: have {}, need {}", wallet.balance, needed)));
+        }
+        if let Some((token, amount)) = envelope.payload.token_value() {
+            let held = wallet.tokens.get(token).copied().unwrap_or(0);
+            if held < amount {
+                return Err(Error::InvalidInput(format!("Insufficient {}: have {}, need {}", token, held, amount)));
+            }
+        }
+
+        Ok(fee)
+    }
+
+    /// Burn the base fee and pay the tip
+    fn collect_fee(&self, wallets: &mut HashMap<UserId, Wallet>, payer: &UserId, base_fee: u64, tip: u64) {
+        let recipient = self.fee_recipient.read().unwrap().clone();
+        let burned = match recipient {
+            Some(recipient) => {
+                wallets.entry(recipient.clone()).or_insert_with(|| Wallet::empty(&recipient)).balance += tip;
+                base_fee
+            }
+            None => base_fee + tip,
         };
 
-        let tx_id = tx.id;
-        self.transactions.write().unwrap().insert(tx_id, tx);
-        
-        Ok(tx_id)
+        if let Some(ref tokenomics) = self.tokenomics {
+            let _ = tokenomics.burn_tokens(burned, BurnReason::TransactionFee, payer.clone());
+        }
     }
 
-    /// Claim rewards
-    pub fn claim_rewards(&self, user_id: &UserId) -> Result<Uuid> {
-        let mut wallets = self.wallets.write().unwrap();
-        let wallet = wallets.get_mut(user_id)
-            .ok_or_else(|| Error::NotFound(format!("User not found: {}", user_id)))?;
+    /// Move `amount` from a checked sender to `to`, burning the transfer fee
+    ///
+    /// Deposits into protocol accounts skip the burn, so an escrow or fee
+    /// pool receives exactly what was paid in. Returns the amount credited to
+    /// `to`.
+    fn move_funds(&self, wallets: &mut HashMap<UserId, Wallet>, from: &UserId, to: &UserId, amount: u64) -> u64 {
+        // Calculate transaction fee burn (1% default)
+        let burn_amount = match self.tokenomics {
+            Some(ref tokenomics) if !self.is_module_account(to) => {
+                (amount * tokenomics.get_statistics().burn_rate_bps as u64) / 10000
+            }
+            _ => 0,
+        };
+        
+        let net_amount = amount - burn_amount;
         
-        let rewards = wallet.rewards_pending;
-        wallet.balance += rewards;
-        wallet.rewards_pending = 0;
+        wallets.get_mut(from).expect("checked sender exists").balance -= amount;
+        wallets.entry(to.clone()).or_insert_with(|| Wallet::empty(to)).balance += net_amount;
 
-        let tx = CurrencyTransaction {
-            id: Uuid::new_v4(),
-            tx_type: "reward".to_string(),
-            from: user_id.clone(),
-            to: None,
-            amount: rewards,
-            status: "pending".to_string(),
-            confirmations: 0,
-            block_height: 0,
-            created_at: Utc::now().timestamp(),
-        };
+        // Burn transaction fee
+        if burn_amount > 0 {
+            if let Some(ref tokenomics) = self.tokenomics {
+                let _ = tokenomics.burn_tokens(burn_amount, BurnReason::TransactionFee, from.clone());
+            }
+        }
 
-        let tx_id = tx.id;
-        self.transactions.write().unwrap().insert(tx_id, tx);
+        net_amount
+    }
+
+    /// Transfer from a protocol account
+    ///
+    /// Only accounts created with `create_module_account` can be moved this
+    /// way; user accounts need a signed transaction.
+    pub fn module_transfer(
+        &self,
+        from: &UserId,
+        to: &UserId,
+        amount: u64,
+    ) -> Result<Uuid> {
+        let tx_ids = self.module_transfer_batch(from, &[(to.clone(), amount)])?;
+        Ok(tx_ids[0])
+    }
+
+    /// Pay several recipients from a protocol account
+    ///
+    /// Either every transfer applies or, if the account cannot cover their
+    /// total, none does.
+    pub fn module_transfer_batch(&self, from: &UserId, payouts: &[(UserId, u64)]) -> Result<Vec<Uuid>> {
+        self.module_transfer_and_burn(from, payouts, 0)
+    }
+
+    /// Pay several recipients from a protocol account and burn a platform
+    /// fee from it
+    ///
+    /// All or nothing, like `module_transfer_batch`. Returns the payout
+    /// transaction IDs; the burn is recorded as a `"burn"` transaction.
+    pub fn module_transfer_and_burn(&self, from: &UserId, payouts: &[(UserId, u64)], burn: u64) -> Result<Vec<Uuid>> {
+        if !self.module_accounts.read().unwrap().contains(from) {
+            return Err(Error::PermissionDenied(format!("{} is not a module account", from)));
+        }
+
+        let mut wallets = self.wallets.write().unwrap();
         
-        Ok(tx_id)
+        let from_wallet = wallets.get(from)
+            .ok_or_else(|| Error::NotFound(format!("User not found: {}", from)))?;
+        
+        let total = payouts.iter().try_fold(burn, |sum, (_, amount)| sum.checked_add(*amount))
+            .ok_or_else(|| Error::InvalidInput("Transfer total overflows".to_string()))?;
+        if from_wallet.balance < total {
+            return Err(Error::InvalidInput(format!("Insufficient balance: have {}, need {}", from_wallet.balance, total)));
+        }
+        
+        let mut transactions = self.transactions.write().unwrap();
+        let tx_ids = payouts.iter().map(|(to, amount)| {
+            let received = self.move_funds(&mut wallets, from, to, *amount);
+            let tx = CurrencyTransaction {
+                id: Uuid::new_v4(),
+                tx_type: "payment".to_string(),
+                from: from.clone(),
+                to: Some(to.clone()),
+                amount: *amount,
+                received,
+                fee: 0,
+                status: "pending".to_string(),
+                confirmations: 0,
+                block_height: 0,
+                created_at: Utc::now().timestamp(),
+            };
+            let tx_id = tx.id;
+            transactions.insert(tx_id, tx);
+            tx_id
+        }).collect();
+        
+        if burn > 0 {
+            wallets.get_mut(from).expect("checked sender exists").balance -= burn;
+            if let Some(ref tokenomics) = self.tokenomics {
+                let _ = tokenomics.burn_tokens(burn, BurnReason::PlatformFee, from.clone());
+            }
+            let tx = CurrencyTransaction {
+                id: Uuid::new_v4(),
+                tx_type: "burn".to_string(),
+                from: from.clone(),
+                to: None,
+                amount: burn,
+                received: 0,
+                fee: 0,
+                status: "pending".to_string(),
+                confirmations: 0,
+                block_height: 0,
+                created_at: Utc::now().timestamp(),
+            };
+            transactions.insert(tx.id, tx);
+        }
+        
+        Ok(tx_ids)
     }
 
     /// Get transaction by ID
@@ -291,6 +870,133 @@ impl CurrencyChainClient {
                 tx.block_height = *block;
             }
         }
+
+        self.fee_market.write().unwrap().close_block();
+        let height = *block;
+        drop(txs);
+        drop(block);
+
+        // Tokenomics follows the chain's height
+        if let Some(ref tokenomics) = self.tokenomics {
+            let _ = tokenomics.set_block_height(height);
+        }
+
+        // Release matured unbonding stake and pay out a finished epoch
+        let mut wallets = self.wallets.write().unwrap();
+        let mut staking = self.staking.write().unwrap();
+        let released = staking.release_matured(height);
+        let rewards = staking.end_epoch(height);
+        drop(staking);
+
+        for UnbondingEntry { delegator, amount, .. } in released {
+            let wallet = wallets.entry(delegator.clone()).or_insert_with(|| Wallet::empty(&delegator));
+            wallet.staked -= amount;
+            wallet.balance += amount;
+        }
+        for (account, reward) in rewards {
+            wallets.entry(account.clone()).or_insert_with(|| Wallet::empty(&account)).rewards_pending += reward;
+        }
+    }
+
+    /// Mint inflation for blocks not yet processed and route the validator
+    /// share to staking
+    ///
+    /// Base inflation and validator block rewards go into the epoch's reward
+    /// pool; other scheduled mints are left to their own distribution. Each
+    /// block is processed once, however often this is called.
+    pub fn process_block_inflation(&self) -> Result<Vec<Uuid>> {
+        let tokenomics = self.tokenomics.as_ref()
+            .ok_or_else(|| Error::chain("Currency chain has no tokenomics manager"))?;
+        let mint_ids = tokenomics.process_block_inflation()?;
+
+        let minted: u64 = tokenomics.get_mint_history(mint_ids.len())
+            .iter()
+            .filter(|event| mint_ids.contains(&event.id))
+            .filter(|event| matches!(event.reason, MintReason::Inflation | MintReason::BlockReward))
+            .map(|event| event.amount)
+            .sum();
+        self.staking.write().unwrap().add_rewards(minted);
+
+        Ok(mint_ids)
+    }
+
+    /// Count a block signed by `validator` towards its uptime
+    pub fn record_block_signed(&self, validator: &UserId) -> Result<()> {
+        self.staking.write().unwrap().record_block_signed(validator)
+    }
+
+    /// Apply bridge slash events not yet applied, returning the amount burned
+    ///
+    /// Each slash reduces the validator's bonded and unbonding stake and that
+    /// of its delegators in equal proportion. Slashes of accounts that are not
+    /// validators here are skipped.
+    pub fn apply_slashes(&self, slashing: &SlashingManager) -> Result<u64> {
+        let events = slashing.get_all_slashes();
+        let mut applied = self.slashes_applied.write().unwrap();
+        let mut wallets = self.wallets.write().unwrap();
+        let mut staking = self.staking.write().unwrap();
+
+        let mut burned = 0;
+        for event in events.iter().skip(*applied) {
+            if staking.get_validator(&event.validator_id).is_none() {
+                continue;
+            }
+            let mut slashed = 0;
+            for (account, amount) in staking.slash(&event.validator_id, event.slash_amount)? {
+                wallets.get_mut(&account).expect("stakers have wallets").staked -= amount;
+                slashed += amount;
+            }
+            if slashed > 0 {
+                if let Some(ref tokenomics) = self.tokenomics {
+                    let _ = tokenomics.burn_tokens(slashed, BurnReason::Slash, event.validator_id.clone());
+                }
+            }
+            burned += slashed;
+        }
+        *applied = events.len();
+
+        Ok(burned)
+    }
+
+    /// Get a validator
+    pub fn get_validator(&self, validator: &UserId) -> Option<Validator> {
+        self.staking.read().unwrap().get_validator(validator).cloned()
+    }
+
+    /// All validators, ordered by ID
+    pub fn get_validators(&self) -> Vec<Validator> {
+        let mut validators: Vec<_> = self.staking.read().unwrap().validators().cloned().collect();
+        validators.sort_by(|a, b| a.id.cmp(&b.id));
+        validators
+    }
+
+    /// Amount `delegator` has bonded to `validator`
+    pub fn get_delegation(&self, delegator: &UserId, validator: &UserId) -> u64 {
+        self.staking.read().unwrap().delegation(delegator, validator)
+    }
+
+    /// Stake `delegator` has waiting out the unbonding period
+    pub fn get_unbonding(&self, delegator: &UserId) -> Vec<UnbondingEntry> {
+        self.staking.read().unwrap().unbonding_entries(delegator)
+    }
+
+    /// Pool pairing the native token with `token`
+    pub fn get_pool(&self, token: &str) -> Option<Pool> {
+        self.amm.read().unwrap().pool(token).cloned()
+    }
+
+    /// Tokens a swap of `amount_in` would pay out at the current reserves
+    pub fn quote_swap(&self, token: &str, direction: SwapDirection, amount_in: u64) -> Result<u64> {
+        let amm = self.amm.read().unwrap();
+        let pool = amm.pool(token)
+            .ok_or_else(|| Error::NotFound(format!("No liquidity pool for {}", token)))?;
+        Ok(pool.amount_out(direction, amount_in))
+    }
+
+    /// Time-weighted average prices of the `token` pool over the last
+    /// `window` blocks
+    pub fn get_twap(&self, token: &str, window: u64) -> Option<Twap> {
+        self.amm.read().unwrap().pool(token)?.twap(window, self.get_current_block())
     }
 
     /// Get all transactions for a user
@@ -308,9 +1014,108 @@ impl CurrencyChainClient {
     }
 }
 
+/// Serialized form of [`CurrencyChainClient`]
+///
+/// Generic over the tokenomics manager so it can be written from a borrow
+/// and read back owned.
+#[derive(Serialize, Deserialize)]
+struct CurrencyChainState<T> {
+    config: CurrencyChainConfig,
+    transactions: HashMap<Uuid, CurrencyTransaction>,
+    current_block: u64,
+    wallets: HashMap<UserId, Wallet>,
+    staking: StakingLedger,
+    amm: AmmLedger,
+    assets: AssetLedger,
+    slashes_applied: usize,
+    tokenomics: Option<T>,
+    fee_market: FeeMarket,
+    fee_recipient: Option<UserId>,
+    module_accounts: BTreeSet<UserId>,
+}
+
+impl Serialize for CurrencyChainClient {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        CurrencyChainState {
+            config: self.config.clone(),
+            transactions: self.transactions.read().unwrap().clone(),
+            current_block: *self.current_block.read().unwrap(),
+            wallets: self.wallets.read().unwrap().clone(),
+            staking: self.staking.read().unwrap().clone(),
+            amm: self.amm.read().unwrap().clone(),
+            assets: self.assets.read().unwrap().clone(),
+            slashes_applied: *self.slashes_applied.read().unwrap(),
+            tokenomics: self.tokenomics.as_deref(),
+            fee_market: self.fee_market.read().unwrap().clone(),
+            fee_recipient: self.fee_recipient.read().unwrap().clone(),
+            module_accounts: self.module_accounts.read().unwrap().iter().cloned().collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CurrencyChainClient {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let state = CurrencyChainState::<TokenomicsManager>::deserialize(deserializer)?;
+        Ok(Self {
+            config: state.config,
+            transactions: Arc::new(RwLock::new(state.transactions)),
+            current_block: Arc::new(RwLock::new(state.current_block)),
+            wallets: Arc::new(RwLock::new(state.wallets)),
+            staking: Arc::new(RwLock::new(state.staking)),
+            amm: Arc::new(RwLock::new(state.amm)),
+            assets: Arc::new(RwLock::new(state.assets)),
+            slashes_applied: Arc::new(RwLock::new(state.slashes_applied)),
+            tokenomics: state.tokenomics.map(Arc::new),
+            fee_market: Arc::new(RwLock::new(state.fee_market)),
+            fee_recipient: Arc::new(RwLock::new(state.fee_recipient)),
+            module_accounts: Arc::new(RwLock::new(state.module_accounts.into_iter().collect())),
+        })
+    }
+}
+
+impl FundLedger for CurrencyChainClient {
+    fn balance(&self, account: &UserId) -> Result<u64> {
+        self.get_balance(account)
+    }
+
+    fn transfer(&self, from: &UserId, to: &UserId, amount: u64) -> Result<Uuid> {
+        self.module_transfer(from, to, amount)
+    }
+}
+
+impl TokenLedger for CurrencyChainClient {
+    fn token_balance(&self, account: &UserId, token_id: &str) -> Result<u64> {
+        Ok(self.get_token_balance(account, token_id))
+    }
+
+    fn bonded_balance(&self, account: &UserId, token_id: &str) -> Result<u64> {
+        Ok(self.assets.read().unwrap().bonded(account, token_id))
+    }
+}
+
+impl EscrowLedger for CurrencyChainClient {
+    fn payment(&self, tx_id: &Uuid) -> Result<Option<LedgerPayment>> {
+        let tx = self.get_transaction(tx_id)?;
+        Ok(tx.and_then(|tx| match (tx.tx_type.as_str(), tx.to) {
+            ("payment", Some(to)) => Some(LedgerPayment { from: tx.from, to, amount: tx.received }),
+            _ => None,
+        }))
+    }
+
+    fn transfer_batch(&self, from: &UserId, payouts: &[(UserId, u64)]) -> Result<Vec<Uuid>> {
+        self.module_transfer_batch(from, payouts)
+    }
+
+    fn transfer_and_burn(&self, from: &UserId, payouts: &[(UserId, u64)], burn: u64) -> Result<Vec<Uuid>> {
+        self.module_transfer_and_burn(from, payouts, burn)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use dchat_crypto::keys::PrivateKey;
 
     #[test]
     fn test_create_wallet() {
@@ -320,35 +1125,665 @@ mod tests {
         assert_eq!(wallet.balance, 1000);
     }
 
+    #[test]
+    fn test_existing_accounts_are_never_replaced() {
+        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let (alice, key) = account(&client, 1_000);
+        let (bob, _) = account(&client, 0);
+        let transfer = TransactionEnvelope {
+            from: alice.clone(),
+            nonce: 0,
+            max_fee: client.base_fee(),
+            tip: 0,
+            payload: TransactionPayload::Transfer { to: bob.clone(), amount: 100 },
+        };
+        client.submit_transaction(&transfer.sign(&key)).unwrap();
+
+        assert!(matches!(client.create_wallet(&alice, 0), Err(Error::AlreadyExists(_))));
+        assert!(matches!(client.create_module_account(&alice, 0), Err(Error::AlreadyExists(_))));
+        assert!(matches!(client.create_account(&alice, PrivateKey::generate().public_key(), 0), Err(Error::AlreadyExists(_))));
+
+        // The account keeps its key and nonce and cannot be drained as a
+        // module account
+        let wallet = client.get_wallet(&alice).unwrap().unwrap();
+        assert_eq!(wallet.public_key, Some(key.public_key()));
+        assert_eq!(wallet.nonce, 1);
+        assert!(client.module_transfer(&alice, &bob, 10).is_err());
+    }
+
+    fn account(client: &CurrencyChainClient, balance: u64) -> (UserId, PrivateKey) {
+        let user_id = UserId(Uuid::new_v4());
+        let key = PrivateKey::generate();
+        client.create_account(&user_id, key.public_key(), balance).unwrap();
+        (user_id, key)
+    }
+
+    fn envelope(from: &UserId, nonce: u64, payload: TransactionPayload) -> TransactionEnvelope {
+        TransactionEnvelope {
+            from: from.clone(),
+            nonce,
+            max_fee: DEFAULT_BASE_FEE,
+            tip: 0,
+            payload,
+        }
+    }
+
+    fn pay(to: &UserId, amount: u64) -> TransactionPayload {
+        TransactionPayload::Transfer { to: to.clone(), amount }
+    }
+
     #[test]
     fn test_transfer() {
         let client = CurrencyChainClient::new(CurrencyChainConfig::default());
-        let alice = UserId(Uuid::new_v4());
+        let (alice, alice_key) = account(&client, 1000);
         let bob = UserId(Uuid::new_v4());
-        
-        client.create_wallet(&alice, 1000).unwrap();
         client.create_wallet(&bob, 0).unwrap();
         
-        let tx_id = client.transfer(&alice, &bob, 100).unwrap();
+        let tx = envelope(&alice, 0, pay(&bob, 100)).sign(&alice_key);
+        let tx_id = client.submit_transaction(&tx).unwrap();
         let tx = client.get_transaction(&tx_id).unwrap();
-        assert!(tx.is_some());
+        assert_eq!(tx.unwrap().fee, DEFAULT_BASE_FEE);
         
-        assert_eq!(client.get_balance(&alice).unwrap(), 900);
+        assert_eq!(client.get_balance(&alice).unwrap(), 900 - DEFAULT_BASE_FEE);
         assert_eq!(client.get_balance(&bob).unwrap(), 100);
+        assert_eq!(client.get_nonce(&alice), 1);
+
+        // Bob's wallet has no key, so it cannot send
+        let bob_tx = envelope(&bob, 0, pay(&alice, 10)).sign(&PrivateKey::generate());
+        assert!(matches!(client.submit_transaction(&bob_tx), Err(Error::PermissionDenied(_))));
+    }
+
+    fn staking_client() -> CurrencyChainClient {
+        CurrencyChainClient::new(CurrencyChainConfig {
+            staking: StakingConfig {
+                min_self_bond: 1_000,
+                unbonding_blocks: 5,
+                epoch_blocks: 10,
+                max_commission_bps: 1_000,
+            },
+            ..CurrencyChainConfig::default()
+        })
     }
 
     #[test]
     fn test_stake() {
-        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
-        let user_id = UserId(Uuid::new_v4());
+        let client = staking_client();
+        let (validator, validator_key) = account(&client, 10_000);
+        let (user_id, key) = account(&client, 1000);
+
+        // Delegating to an account that is not a validator fails before
+        // anything is charged
+        let stake = TransactionPayload::Stake { validator: validator.clone(), amount: 500 };
+        assert!(client.submit_transaction(&envelope(&user_id, 0, stake.clone()).sign(&key)).is_err());
+        assert_eq!(client.get_nonce(&user_id), 0);
+
+        let register = TransactionPayload::RegisterValidator { self_bond: 2_000, commission_bps: 500 };
+        client.submit_transaction(&envelope(&validator, 0, register).sign(&validator_key)).unwrap();
         
-        client.create_wallet(&user_id, 1000).unwrap();
-        let tx_id = client.stake(&user_id, 500, 86400).unwrap();
+        let tx_id = client.submit_transaction(&envelope(&user_id, 0, stake).sign(&key)).unwrap();
         let tx = client.get_transaction(&tx_id).unwrap();
         assert!(tx.is_some());
         
         let wallet = client.get_wallet(&user_id).unwrap().unwrap();
-        assert_eq!(wallet.balance, 500);
+        assert_eq!(wallet.balance, 500 - DEFAULT_BASE_FEE);
         assert_eq!(wallet.staked, 500);
+        assert_eq!(client.get_delegation(&user_id, &validator), 500);
+        assert_eq!(client.get_validator(&validator).unwrap().total_stake(), 2_500);
+    }
+
+    #[test]
+    fn test_unstake_waits_for_unbonding_period() {
+        let client = staking_client();
+        let (validator, key) = account(&client, 10_000);
+
+        let register = TransactionPayload::RegisterValidator { self_bond: 3_000, commission_bps: 0 };
+        client.submit_transaction(&envelope(&validator, 0, register).sign(&key)).unwrap();
+        let unstake = TransactionPayload::Unstake { validator: validator.clone(), amount: 1_000 };
+        client.submit_transaction(&envelope(&validator, 1, unstake).sign(&key)).unwrap();
+
+        let balance = 10_000 - 3_000 - 2 * DEFAULT_BASE_FEE;
+        let entries = client.get_unbonding(&validator);
+        assert_eq!(entries[0].complete_at, client.get_current_block() + 5);
+
+        // Still locked and counted as staked until the period ends
+        for _ in 0..4 {
+            client.advance_block();
+        }
+        let wallet = client.get_wallet(&validator).unwrap().unwrap();
+        assert_eq!((wallet.balance, wallet.staked), (balance, 3_000));
+
+        client.advance_block();
+        let wallet = client.get_wallet(&validator).unwrap().unwrap();
+        assert_eq!((wallet.balance, wallet.staked), (balance + 1_000, 2_000));
+        assert!(client.get_unbonding(&validator).is_empty());
+
+        // Unstaking more than is bonded is rejected
+        let over = TransactionPayload::Unstake { validator: validator.clone(), amount: 2_001 };
+        assert!(client.submit_transaction(&envelope(&validator, 2, over).sign(&key)).is_err());
+    }
+
+    #[test]
+    fn test_inflation_rewards_stakers_by_uptime() {
+        use crate::tokenomics::{RecipientType, TokenSupplyConfig};
+
+        let tokenomics = Arc::new(TokenomicsManager::new(TokenSupplyConfig {
+            inflation_rate_bps: 0,
+            ..TokenSupplyConfig::default()
+        }));
+        tokenomics.create_distribution_schedule(RecipientType::Validators, 1_000, 1, None).unwrap();
+        let client = CurrencyChainClient::with_tokenomics(staking_client().config.clone(), tokenomics);
+
+        let (online, online_key) = account(&client, 10_000);
+        let (offline, offline_key) = account(&client, 10_000);
+        let (delegator, delegator_key) = account(&client, 10_000);
+        let register = TransactionPayload::RegisterValidator { self_bond: 1_000, commission_bps: 0 };
+        client.submit_transaction(&envelope(&online, 0, register.clone()).sign(&online_key)).unwrap();
+        client.submit_transaction(&envelope(&offline, 0, register).sign(&offline_key)).unwrap();
+        let stake = TransactionPayload::Stake { validator: online.clone(), amount: 1_000 };
+        client.submit_transaction(&envelope(&delegator, 0, stake).sign(&delegator_key)).unwrap();
+
+        // The first validator signs every block of the epoch, the second none.
+        // The chain starts at height 1, so the first epoch closes after nine
+        for _ in 0..9 {
+            client.process_block_inflation().unwrap();
+            client.record_block_signed(&online).unwrap();
+            client.advance_block();
+        }
+
+        let pending = |id: &UserId| client.get_wallet(id).unwrap().unwrap().rewards_pending;
+        assert_eq!(pending(&online), 4_500);
+        assert_eq!(pending(&delegator), 4_500);
+        assert_eq!(pending(&offline), 0);
+
+        let before = client.get_balance(&delegator).unwrap();
+        let fee = client.base_fee();
+        let nonce = client.get_nonce(&delegator);
+        client.submit_transaction(&envelope(&delegator, nonce, TransactionPayload::ClaimRewards).sign(&delegator_key)).unwrap();
+        assert_eq!(client.get_balance(&delegator).unwrap(), before + 4_500 - fee);
+        assert_eq!(pending(&delegator), 0);
+    }
+
+    #[test]
+    fn test_bridge_slash_reduces_validator_and_delegators() {
+        use dchat_bridge::slashing::SlashReason;
+
+        let client = staking_client();
+        let (validator, validator_key) = account(&client, 10_000);
+        let (delegator, delegator_key) = account(&client, 10_000);
+        let register = TransactionPayload::RegisterValidator { self_bond: 3_000, commission_bps: 0 };
+        client.submit_transaction(&envelope(&validator, 0, register).sign(&validator_key)).unwrap();
+        let stake = TransactionPayload::Stake { validator: validator.clone(), amount: 1_000 };
+        client.submit_transaction(&envelope(&delegator, 0, stake).sign(&delegator_key)).unwrap();
+
+        let slashing = SlashingManager::new();
+        slashing
+            .slash_validator(validator.clone(), SlashReason::DoubleSigning, 400, None, vec![], None)
+            .unwrap();
+        assert_eq!(client.apply_slashes(&slashing).unwrap(), 400);
+
+        // 10% off every position, in the ledger and in the wallets
+        assert_eq!(client.get_delegation(&validator, &validator), 2_700);
+        assert_eq!(client.get_delegation(&delegator, &validator), 900);
+        assert_eq!(client.get_wallet(&validator).unwrap().unwrap().staked, 2_700);
+        assert_eq!(client.get_wallet(&delegator).unwrap().unwrap().staked, 900);
+
+        // Events are applied once
+        assert_eq!(client.apply_slashes(&slashing).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_slashing_alongside_transactions_does_not_deadlock() {
+        use dchat_bridge::slashing::SlashReason;
+
+        let client = Arc::new(staking_client());
+        let (validator, validator_key) = account(&client, 1_000_000);
+        let register = TransactionPayload::RegisterValidator { self_bond: 1_000, commission_bps: 0 };
+        client.submit_transaction(&envelope(&validator, 0, register).sign(&validator_key)).unwrap();
+        let slashing = Arc::new(SlashingManager::new());
+
+        let slasher = {
+            let (client, slashing, validator) = (client.clone(), slashing.clone(), validator.clone());
+            std::thread::spawn(move || {
+                for _ in 0..200 {
+                    let _ = slashing.slash_validator(validator.clone(), SlashReason::DoubleSigning, 1, None, vec![], None);
+                    client.apply_slashes(&slashing).unwrap();
+                }
+            })
+        };
+        let stake = TransactionPayload::Stake { validator: validator.clone(), amount: 1 };
+        for nonce in 1..=200 {
+            client.submit_transaction(&envelope(&validator, nonce, stake.clone()).sign(&validator_key)).unwrap();
+        }
+        slasher.join().unwrap();
+    }
+
+    #[test]
+    fn test_replay_is_rejected() {
+        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let (alice, alice_key) = account(&client, 1000);
+        let bob = UserId(Uuid::new_v4());
+
+        let tx = envelope(&alice, 0, pay(&bob, 100)).sign(&alice_key);
+        client.submit_transaction(&tx).unwrap();
+
+        // Resubmitting the same signed bytes fails and moves nothing
+        assert!(client.submit_transaction(&tx).is_err());
+        assert_eq!(client.get_balance(&bob).unwrap(), 100);
+        assert_eq!(client.get_nonce(&alice), 1);
+
+        // A future nonce is rejected too; nonces must be used in order
+        let ahead = envelope(&alice, 5, pay(&bob, 1)).sign(&alice_key);
+        assert!(client.submit_transaction(&ahead).is_err());
+    }
+
+    #[test]
+    fn test_double_spend_is_rejected() {
+        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let (alice, alice_key) = account(&client, 1000);
+        let bob = UserId(Uuid::new_v4());
+        let carol = UserId(Uuid::new_v4());
+
+        // Two spends of the same funds under the same nonce: only one lands
+        let to_bob = envelope(&alice, 0, pay(&bob, 900)).sign(&alice_key);
+        let to_carol = envelope(&alice, 0, pay(&carol, 900)).sign(&alice_key);
+        client.submit_transaction(&to_bob).unwrap();
+        assert!(client.submit_transaction(&to_carol).is_err());
+        assert_eq!(client.get_balance(&carol).unwrap(), 0);
+
+        // With the next nonce the remaining balance cannot cover it, and the
+        // failed attempt leaves the nonce and balance untouched
+        let retry = envelope(&alice, 1, pay(&carol, 900)).sign(&alice_key);
+        assert!(client.submit_transaction(&retry).is_err());
+        assert_eq!(client.get_balance(&alice).unwrap(), 100 - DEFAULT_BASE_FEE);
+        assert_eq!(client.get_nonce(&alice), 1);
+    }
+
+    #[test]
+    fn test_forged_signatures_are_rejected() {
+        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let (alice, alice_key) = account(&client, 1000);
+        let mallory = UserId(Uuid::new_v4());
+
+        // Signed by the wrong key
+        let forged = envelope(&alice, 0, pay(&mallory, 500)).sign(&PrivateKey::generate());
+        assert!(matches!(client.submit_transaction(&forged), Err(Error::PermissionDenied(_))));
+
+        // Signed by Alice, then the amount is changed
+        let mut tampered = envelope(&alice, 0, pay(&mallory, 5)).sign(&alice_key);
+        tampered.envelope.payload = pay(&mallory, 500);
+        assert!(matches!(client.submit_transaction(&tampered), Err(Error::PermissionDenied(_))));
+
+        assert_eq!(client.get_balance(&alice).unwrap(), 1000);
+        assert_eq!(client.get_nonce(&alice), 0);
+
+        // Accounts can only be bound to a key once
+        assert!(client.create_account(&alice, PrivateKey::generate().public_key(), 0).is_err());
+    }
+
+    #[test]
+    fn test_fee_market() {
+        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let (alice, alice_key) = account(&client, 1_000_000);
+        let pool = UserId(Uuid::new_v4());
+        client.create_module_account(&pool, 0).unwrap();
+        client.set_fee_recipient(pool.clone());
+
+        // A max fee below the base fee is rejected
+        let mut cheap = envelope(&alice, 0, pay(&pool, 1));
+        cheap.max_fee = DEFAULT_BASE_FEE - 1;
+        assert!(client.submit_transaction(&cheap.sign(&alice_key)).is_err());
+
+        // The tip is capped by max_fee and paid to the fee recipient
+        let mut tipped = envelope(&alice, 0, TransactionPayload::ClaimRewards);
+        tipped.max_fee = DEFAULT_BASE_FEE + 3;
+        tipped.tip = 50;
+        let tx_id = client.submit_transaction(&tipped.sign(&alice_key)).unwrap();
+        assert_eq!(client.get_transaction(&tx_id).unwrap().unwrap().fee, DEFAULT_BASE_FEE + 3);
+        assert_eq!(client.get_balance(&pool).unwrap(), 3);
+
+        // A block over target raises the base fee
+        for nonce in 1..=TARGET_TXS_PER_BLOCK * 2 {
+            let tx = envelope(&alice, nonce, TransactionPayload::ClaimRewards);
+            client.submit_transaction(&tx.sign(&alice_key)).unwrap();
+        }
+        client.advance_block();
+        let raised = client.base_fee();
+        assert!(raised > DEFAULT_BASE_FEE);
+
+        // Transactions priced at the old base fee no longer fit
+        let nonce = client.get_nonce(&alice);
+        let stale = envelope(&alice, nonce, TransactionPayload::ClaimRewards);
+        assert!(client.submit_transaction(&stale.sign(&alice_key)).is_err());
+
+        // Empty blocks lower it again, down to the floor
+        client.advance_block();
+        assert!(client.base_fee() < raised);
+        for _ in 0..200 {
+            client.advance_block();
+        }
+        assert_eq!(client.base_fee(), MIN_BASE_FEE);
+    }
+
+    #[test]
+    fn test_module_transfer_requires_module_account() {
+        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let (alice, _) = account(&client, 1000);
+        let treasury = UserId(Uuid::new_v4());
+        client.create_module_account(&treasury, 1000).unwrap();
+
+        assert!(matches!(client.module_transfer(&alice, &treasury, 10), Err(Error::PermissionDenied(_))));
+        client.module_transfer(&treasury, &alice, 10).unwrap();
+        assert_eq!(client.get_balance(&alice).unwrap(), 1010);
+    }
+
+    #[test]
+    fn test_insurance_fund_on_currency_chain() {
+        use dchat_chain::insurance_fund::{
+            ClaimType, FundConfiguration, InsuranceFund, PremiumSource,
+        };
+
+        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let fund_account = UserId(Uuid::new_v4());
+        let fee_pool = UserId(Uuid::new_v4());
+        let sender = UserId(Uuid::new_v4());
+        client.create_module_account(&fund_account, 200_000).unwrap();
+        client.create_module_account(&fee_pool, 1_000).unwrap();
+        client.create_wallet(&sender, 0).unwrap();
+
+        let config = FundConfiguration {
+            minimum_balance: 100_000,
+            min_votes_for_approval: 1,
+            ..Default::default()
+        };
+        let mut fund = InsuranceFund::with_account(fund_account.clone(), config, &client).unwrap();
+        assert_eq!(fund.balance(), 200_000);
+
+        // 10% of a 500 token message fee moves from the fee pool to the fund
+        let premium = fund
+            .collect_premium(&client, &fee_pool, 500, PremiumSource::Message { message_id: "m1".to_string() })
+            .unwrap();
+        assert_eq!(premium, 50);
+        assert_eq!(client.get_balance(&fee_pool).unwrap(), 950);
+        assert_eq!(client.get_balance(&fund_account).unwrap(), 200_050);
+
+        let claim_id = fund
+            .submit_claim(
+                sender.clone(),
+                ClaimType::AttackCompensation {
+                    attack_type: "relay".to_string(),
+                    affected_users: vec![sender.clone()],
+                    total_loss: 5_000,
+                },
+                5_000,
+                vec![],
+            )
+            .unwrap();
+        fund.vote_on_claim(claim_id, UserId(Uuid::new_v4()), true).unwrap();
+        fund.approve_claim(claim_id).unwrap();
+
+        let tx_id = fund.execute_payout(claim_id, &client).unwrap();
+        assert!(client.get_transaction(&tx_id).unwrap().is_some());
+        assert_eq!(client.get_balance(&sender).unwrap(), 5_000);
+        assert_eq!(client.get_balance(&fund_account).unwrap(), fund.balance());
+    }
+
+    #[test]
+    fn test_module_transfer_batch_is_atomic() {
+        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let pool = UserId(Uuid::new_v4());
+        let (alice, _) = account(&client, 0);
+        let (bob, _) = account(&client, 0);
+        client.create_module_account(&pool, 100).unwrap();
+
+        // The pool covers each payout but not both
+        assert!(client.module_transfer_batch(&pool, &[(alice.clone(), 60), (bob.clone(), 60)]).is_err());
+        assert_eq!(client.get_balance(&alice).unwrap(), 0);
+        assert_eq!(client.get_balance(&pool).unwrap(), 100);
+
+        client.module_transfer_batch(&pool, &[(alice.clone(), 60), (bob.clone(), 40)]).unwrap();
+        assert_eq!(client.get_balance(&alice).unwrap(), 60);
+        assert_eq!(client.get_balance(&bob).unwrap(), 40);
+    }
+
+    #[test]
+    fn test_module_transfer_and_burn() {
+        use crate::tokenomics::TokenSupplyConfig;
+
+        let tokenomics = Arc::new(TokenomicsManager::new(TokenSupplyConfig::default()));
+        let client = CurrencyChainClient::with_tokenomics(CurrencyChainConfig::default(), tokenomics.clone());
+        let market = UserId(Uuid::new_v4());
+        let (seller, _) = account(&client, 0);
+        client.create_module_account(&market, 100).unwrap();
+
+        // The burn counts toward what the account must cover
+        assert!(client.module_transfer_and_burn(&market, &[(seller.clone(), 95)], 10).is_err());
+        assert_eq!(client.get_balance(&market).unwrap(), 100);
+
+        let tx_ids = client.module_transfer_and_burn(&market, &[(seller.clone(), 90)], 10).unwrap();
+        assert_eq!(tx_ids.len(), 1);
+        assert_eq!(client.get_balance(&seller).unwrap(), 90);
+        assert_eq!(client.get_balance(&market).unwrap(), 0);
+        assert_eq!(tokenomics.get_total_burned(), 10);
+    }
+
+    #[test]
+    fn test_marketplace_escrow_on_currency_chain() {
+        use crate::tokenomics::TokenSupplyConfig;
+        use dchat_marketplace::escrow::{EscrowManager, EscrowState};
+
+        // With tokenomics the transfer burn applies, but not to deposits into
+        // the escrow account, so paying the exact price funds it in full
+        let tokenomics = Arc::new(TokenomicsManager::new(TokenSupplyConfig::default()));
+        let client = CurrencyChainClient::with_tokenomics(CurrencyChainConfig::default(), tokenomics);
+        let escrow = EscrowManager::new();
+        client.create_module_account(escrow.account(), 0).unwrap();
+        let (buyer, buyer_key) = account(&client, 1_000 + DEFAULT_BASE_FEE);
+        let (creator, _) = account(&client, 0);
+        let (platform, _) = account(&client, 0);
+
+        // The buyer's signed payment funds the escrow
+        let payment = client
+            .submit_transaction(&envelope(&buyer, 0, pay(escrow.account(), 1_000)).sign(&buyer_key))
+            .unwrap();
+        let escrow_id = escrow
+            .create_multi_party_escrow(
+                &client,
+                Uuid::new_v4(),
+                &buyer,
+                vec![(creator.clone(), 900), (platform.clone(), 100)],
+                payment,
+                86400,
+            )
+            .unwrap();
+        assert_eq!(client.get_balance(&buyer).unwrap(), 0);
+        assert_eq!(client.get_balance(escrow.account()).unwrap(), 1_000);
+        assert_eq!(client.payment(&payment).unwrap().unwrap().amount, 1_000);
+
+        escrow.mark_awaiting_release(escrow_id).unwrap();
+        escrow.release_funds(&client, escrow_id, &buyer).unwrap();
+        assert_eq!(escrow.get_escrow(escrow_id).unwrap().state, EscrowState::Released);
+        assert_eq!(client.get_balance(&creator).unwrap(), 891);
+        assert_eq!(client.get_balance(&platform).unwrap(), 99);
+        assert_eq!(client.get_balance(escrow.account()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_state_round_trips() {
+        use crate::tokenomics::TokenSupplyConfig;
+
+        let tokenomics = Arc::new(TokenomicsManager::new(TokenSupplyConfig::default()));
+        let client = CurrencyChainClient::with_tokenomics(staking_client().config.clone(), tokenomics);
+        let (validator, validator_key) = account(&client, 10_000);
+        let (holder, holder_key) = account(&client, 1_000);
+        let treasury = UserId(Uuid::new_v4());
+        client.create_module_account(&treasury, 500).unwrap();
+        let register = TransactionPayload::RegisterValidator { self_bond: 2_000, commission_bps: 500 };
+        client.submit_transaction(&envelope(&validator, 0, register).sign(&validator_key)).unwrap();
+        let bond = TransactionPayload::Bond { token: NATIVE_TOKEN.to_string(), amount: 100, lock_blocks: 5 };
+        client.submit_transaction(&envelope(&holder, 0, bond).sign(&holder_key)).unwrap();
+        client.advance_block();
+
+        let restored: CurrencyChainClient =
+            serde_json::from_str(&serde_json::to_string(&client).unwrap()).unwrap();
+        assert_eq!(restored.get_current_block(), 2);
+        assert_eq!(restored.get_nonce(&validator), 1);
+        assert_eq!(restored.get_balance(&holder).unwrap(), client.get_balance(&holder).unwrap());
+        assert_eq!(restored.get_bond(&holder, NATIVE_TOKEN).unwrap().amount, 100);
+        assert_eq!(restored.get_validator(&validator).unwrap().self_bond, 2_000);
+        assert_eq!(restored.get_delegation(&validator, &validator), 2_000);
+        assert!(restored.is_module_account(&treasury));
+        assert_eq!(restored.base_fee(), client.base_fee());
+        assert_eq!(
+            restored.get_tokenomics().unwrap().get_total_burned(),
+            client.get_tokenomics().unwrap().get_total_burned()
+        );
+
+        // The restored chain keeps applying transactions from where it left off
+        let transfer = envelope(&holder, 1, pay(&validator, 10)).sign(&holder_key);
+        restored.submit_transaction(&transfer).unwrap();
+        assert!(restored.submit_transaction(&transfer).is_err());
+    }
+
+    #[test]
+    fn test_swaps_and_liquidity_on_currency_chain() {
+        use crate::tokenomics::TokenSupplyConfig;
+
+        let tokenomics = Arc::new(TokenomicsManager::new(TokenSupplyConfig::default()));
+        let client = CurrencyChainClient::with_tokenomics(CurrencyChainConfig::default(), tokenomics.clone());
+        let (provider, provider_key) = account(&client, 1_000_000 + 3 * DEFAULT_BASE_FEE);
+        let (trader, trader_key) = account(&client, 10_000 + 2 * DEFAULT_BASE_FEE);
+        let issue = TransactionPayload::IssueToken(IssueToken {
+            symbol: "GEM".to_string(),
+            name: "Gem".to_string(),
+            decimals: 6,
+            max_supply: None,
+            initial_supply: 1_000_000,
+        });
+        client.submit_transaction(&envelope(&provider, 0, issue).sign(&provider_key)).unwrap();
+
+        let add = TransactionPayload::AddLiquidity {
+            token: "GEM".to_string(),
+            native_amount: 1_000_000,
+            token_amount: 1_000_000,
+            min_shares: 0,
+        };
+        client.submit_transaction(&envelope(&provider, 1, add).sign(&provider_key)).unwrap();
+        assert_eq!(client.get_balance(&provider).unwrap(), DEFAULT_BASE_FEE);
+        assert_eq!(client.get_token_balance(&provider, "GEM"), 0);
+        assert_eq!(client.get_pool("GEM").unwrap().shares_of(&provider), 999_000);
+
+        // A swap below its slippage limit changes nothing, nonce included
+        let swap = |min_out| TransactionPayload::Swap {
+            token: "GEM".to_string(),
+            direction: SwapDirection::NativeToToken,
+            amount_in: 10_000,
+            min_out,
+        };
+        let quoted = client.quote_swap("GEM", SwapDirection::NativeToToken, 10_000).unwrap();
+        assert!(client.submit_transaction(&envelope(&trader, 0, swap(quoted + 1)).sign(&trader_key)).is_err());
+        assert_eq!(client.get_nonce(&trader), 0);
+        assert_eq!(client.get_pool("GEM").unwrap().native_reserve, 1_000_000);
+
+        client.submit_transaction(&envelope(&trader, 0, swap(quoted)).sign(&trader_key)).unwrap();
+        assert_eq!(client.get_balance(&trader).unwrap(), DEFAULT_BASE_FEE);
+        assert_eq!(client.get_token_balance(&trader, "GEM"), quoted);
+        // Only the base fees were burned; pooled tokens skip the transfer burn
+        assert_eq!(tokenomics.get_total_burned(), 3 * DEFAULT_BASE_FEE);
+
+        // Selling more GEM than the wallet holds is rejected up front
+        let sell = TransactionPayload::Swap {
+            token: "GEM".to_string(),
+            direction: SwapDirection::TokenToNative,
+            amount_in: quoted + 1,
+            min_out: 0,
+        };
+        assert!(client.submit_transaction(&envelope(&trader, 1, sell).sign(&trader_key)).is_err());
+
+        // The provider's withdrawal includes the trader's fee
+        let remove = TransactionPayload::RemoveLiquidity {
+            token: "GEM".to_string(),
+            shares: 999_000,
+            min_native: 1_000_000,
+            min_token: 0,
+        };
+        client.submit_transaction(&envelope(&provider, 2, remove).sign(&provider_key)).unwrap();
+        assert!(client.get_balance(&provider).unwrap() > 1_000_000);
+        assert_eq!(client.get_pool("GEM").unwrap().total_shares, 1_000);
+    }
+
+    #[test]
+    fn test_vested_tokens_are_claimed_by_signed_transaction() {
+        use crate::tokenomics::TokenSupplyConfig;
+        use crate::vesting::ReleaseSchedule;
+
+        let tokenomics = Arc::new(TokenomicsManager::new(TokenSupplyConfig::default()));
+        let client = CurrencyChainClient::with_tokenomics(CurrencyChainConfig::default(), tokenomics.clone());
+        let (member, member_key) = account(&client, 2 * DEFAULT_BASE_FEE);
+        let treasury = UserId(Uuid::new_v4());
+        tokenomics
+            .create_vesting_grant(&member, &treasury, 1_000, ReleaseSchedule::Cliff { at: 3 }, true)
+            .unwrap();
+
+        // Nothing has vested yet, so the claim is rejected without a fee
+        let claim = TransactionPayload::ClaimVested { grant_id: None };
+        assert!(client.submit_transaction(&envelope(&member, 0, claim.clone()).sign(&member_key)).is_err());
+        assert_eq!(client.get_nonce(&member), 0);
+
+        // The cliff passes as the chain advances
+        client.advance_block();
+        client.advance_block();
+        assert_eq!(tokenomics.get_current_block(), 3);
+        let fee = client.base_fee();
+        client.submit_transaction(&envelope(&member, 0, claim).sign(&member_key)).unwrap();
+        assert_eq!(client.get_balance(&member).unwrap(), 1_000 + 2 * DEFAULT_BASE_FEE - fee);
+    }
+
+    #[test]
+    fn test_issued_tokens_and_bonds() {
+        let client = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let (issuer, issuer_key) = account(&client, 10 * DEFAULT_BASE_FEE);
+        let (holder, holder_key) = account(&client, 1_000 + 10 * DEFAULT_BASE_FEE);
+        let issue = TransactionPayload::IssueToken(IssueToken {
+            symbol: "GEM".to_string(),
+            name: "Gem".to_string(),
+            decimals: 0,
+            max_supply: Some(1_000),
+            initial_supply: 600,
+        });
+        client.submit_transaction(&envelope(&issuer, 0, issue).sign(&issuer_key)).unwrap();
+        assert_eq!(client.get_token_balance(&issuer, "GEM"), 600);
+
+        // Only the issuer mints, and only up to the cap
+        let mint = |amount| TransactionPayload::MintToken { symbol: "GEM".to_string(), to: holder.clone(), amount };
+        assert!(client.submit_transaction(&envelope(&holder, 0, mint(100)).sign(&holder_key)).is_err());
+        assert!(client.submit_transaction(&envelope(&issuer, 1, mint(401)).sign(&issuer_key)).is_err());
+        client.submit_transaction(&envelope(&issuer, 1, mint(400)).sign(&issuer_key)).unwrap();
+
+        let transfer = TransactionPayload::TransferToken { symbol: "GEM".to_string(), to: holder.clone(), amount: 100 };
+        client.submit_transaction(&envelope(&issuer, 2, transfer).sign(&issuer_key)).unwrap();
+        let burn = TransactionPayload::BurnToken { symbol: "GEM".to_string(), amount: 500 };
+        client.submit_transaction(&envelope(&issuer, 3, burn).sign(&issuer_key)).unwrap();
+        assert_eq!(client.get_token_balance(&holder, "GEM"), 500);
+        assert_eq!(client.get_token_balance(&issuer, "GEM"), 0);
+        assert_eq!(client.get_token_metadata("GEM").unwrap().total_supply, 500);
+
+        // Bonded tokens leave the wallet and count as bonded to channels
+        let bond = |token: &str, amount| TransactionPayload::Bond { token: token.to_string(), amount, lock_blocks: 2 };
+        client.submit_transaction(&envelope(&holder, 0, bond("GEM", 300)).sign(&holder_key)).unwrap();
+        client.submit_transaction(&envelope(&holder, 1, bond(NATIVE_TOKEN, 1_000)).sign(&holder_key)).unwrap();
+        assert_eq!(client.token_balance(&holder, "GEM").unwrap(), 200);
+        assert_eq!(client.bonded_balance(&holder, "GEM").unwrap(), 300);
+        assert_eq!(client.bonded_balance(&holder, NATIVE_TOKEN).unwrap(), 1_000);
+        let spend = TransactionPayload::TransferToken { symbol: "GEM".to_string(), to: issuer.clone(), amount: 201 };
+        assert!(client.submit_transaction(&envelope(&holder, 2, spend).sign(&holder_key)).is_err());
+
+        // Unbonding waits for the lock
+        let unbond = TransactionPayload::Unbond { token: "GEM".to_string(), amount: 300 };
+        assert!(client.submit_transaction(&envelope(&holder, 2, unbond.clone()).sign(&holder_key)).is_err());
+        client.advance_block();
+        client.advance_block();
+        client.submit_transaction(&envelope(&holder, 2, unbond).sign(&holder_key)).unwrap();
+        assert_eq!(client.get_token_balance(&holder, "GEM"), 500);
+        assert!(client.get_bond(&holder, "GEM").is_none());
     }
 }
@@ -1,19 +1,33 @@
 //! Blockchain client for parallel chain architecture
 //! Supports Chat Chain, Currency Chain, Cross-Chain Bridge, and Tokenomics
 
+pub mod amm;
+pub mod assets;
 pub mod chat_chain;
 pub mod client;
 pub mod cross_chain;
 pub mod currency_chain;
+mod pair_map;
 pub mod rpc;
+pub mod staking;
 pub mod tokenomics;
+pub mod vesting;
 
+pub use amm::{AmmLedger, Deposit, Pool, SwapDirection, Twap, Withdrawal};
+pub use assets::{AssetLedger, Bond, IssueToken, TokenMetadata, NATIVE_TOKEN};
 pub use chat_chain::{ChatChainClient, ChatChainConfig};
 pub use client::BlockchainClient;
-pub use cross_chain::{CrossChainBridge, CrossChainTransaction, CrossChainStatus};
-pub use currency_chain::{CurrencyChainClient, CurrencyChainConfig};
+pub use cross_chain::{
+    BridgeMessage, BridgeMessagePayload, BridgeState, CrossChainBridge, CrossChainStatus, CrossChainTransaction,
+    MessageProof,
+};
+pub use currency_chain::{
+    CurrencyChainClient, CurrencyChainConfig, SignedTransaction, TransactionEnvelope, TransactionPayload,
+};
 pub use rpc::{RpcClient, RpcConfig};
+pub use staking::{StakingConfig, StakingLedger, UnbondingEntry, Validator, ValidatorStatus};
 pub use tokenomics::{
     TokenomicsManager, TokenSupplyConfig, MintEvent, MintReason, BurnEvent, BurnReason,
     LiquidityPool, DistributionSchedule, RecipientType, TokenomicsStats,
 };
+pub use vesting::{Milestone, ReleaseSchedule, VestingGrant, VestingLedger};
@@ -1,6 +1,7 @@
 //! RPC client for blockchain communication (placeholder)
 
 use dchat_core::error::Result;
+use dchat_core::types::UserId;
 use serde::{Deserialize, Serialize};
 
 /// RPC client configuration
@@ -46,6 +47,12 @@ impl RpcClient {
         Ok(None)
     }
 
+    /// Get the nonce an account's next transaction must carry
+    pub async fn get_account_nonce(&self, _account: &UserId) -> Result<u64> {
+        // TODO: Implement actual RPC call
+        Ok(0)
+    }
+
     /// Get current block number
     pub async fn get_block_number(&self) -> Result<u64> {
         // TODO: Implement actual RPC call
@@ -0,0 +1,516 @@
+//! Validator bonding, delegation, unbonding and epoch rewards
+//!
+//! A validator registers with a self-bond of at least `min_self_bond`; other
+//! accounts delegate to it. Unstaked tokens wait in an unbonding queue for
+//! `unbonding_blocks` before they are spendable, and stay slashable while
+//! they wait. A validator whose self-bond drops below the minimum is jailed:
+//! it takes no new delegations and earns nothing until it bonds back up.
+//!
+//! Rewards accumulate over an epoch and are split at its end in proportion
+//! to each validator's stake times its uptime (blocks signed over blocks in
+//! the epoch). The validator takes its commission from its share and the rest
+//! goes to its stakers, self-bond included, in proportion to their stake.
+//!
+//! This module only tracks positions; `CurrencyChainClient` moves the
+//! balances that back them.
+
+use dchat_core::error::{Error, Result};
+use dchat_core::types::UserId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Staking parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakingConfig {
+    /// Smallest self-bond an active validator may hold
+    pub min_self_bond: u64,
+    /// Blocks unstaked tokens stay locked
+    pub unbonding_blocks: u64,
+    /// Blocks per reward epoch
+    pub epoch_blocks: u64,
+    /// Highest commission a validator may charge, in basis points
+    pub max_commission_bps: u16,
+}
+
+impl Default for StakingConfig {
+    fn default() -> Self {
+        Self {
+            min_self_bond: 10_000,
+            unbonding_blocks: 100_800, // ~7 days of 6 second blocks
+            epoch_blocks: 600,         // ~1 hour
+            max_commission_bps: 2_000, // 20%
+        }
+    }
+}
+
+/// Whether a validator takes part in consensus and rewards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValidatorStatus {
+    Active,
+    /// Self-bond is below the minimum
+    Jailed,
+}
+
+/// A registered validator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Validator {
+    pub id: UserId,
+    pub commission_bps: u16,
+    pub self_bond: u64,
+    /// Stake delegated by other accounts
+    pub delegated: u64,
+    pub status: ValidatorStatus,
+    /// Blocks signed in the current epoch
+    pub blocks_signed: u64,
+    /// Rewards paid out to the validator and its stakers
+    pub total_rewards: u64,
+}
+
+impl Validator {
+    /// Bonded stake, self-bond included
+    pub fn total_stake(&self) -> u64 {
+        self.self_bond + self.delegated
+    }
+}
+
+/// Tokens waiting out the unbonding period
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnbondingEntry {
+    pub delegator: UserId,
+    pub validator: UserId,
+    pub amount: u64,
+    /// Height at which the tokens are released
+    pub complete_at: u64,
+}
+
+/// Bonded positions, unbonding queue and the reward pool
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StakingLedger {
+    config: StakingConfig,
+    validators: HashMap<UserId, Validator>,
+    /// (delegator, validator) -> bonded amount; self-bonds included
+    #[serde(with = "crate::pair_map")]
+    delegations: HashMap<(UserId, UserId), u64>,
+    unbonding: Vec<UnbondingEntry>,
+    /// Height the current epoch started at
+    epoch_start: u64,
+    /// Rewards waiting for the end of the epoch
+    reward_pool: u64,
+}
+
+impl StakingLedger {
+    /// Create an empty ledger
+    pub fn new(config: StakingConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Staking parameters
+    pub fn config(&self) -> &StakingConfig {
+        &self.config
+    }
+
+    /// Register `id` as a validator with its initial self-bond
+    pub fn register_validator(&mut self, id: &UserId, self_bond: u64, commission_bps: u16) -> Result<()> {
+        if self.validators.contains_key(id) {
+            return Err(Error::AlreadyExists(format!("Validator already registered: {}", id)));
+        }
+        if self_bond < self.config.min_self_bond {
+            return Err(Error::InvalidInput(format!(
+                "Self-bond {} is below the minimum {}",
+                self_bond, self.config.min_self_bond
+            )));
+        }
+        if commission_bps > self.config.max_commission_bps {
+            return Err(Error::InvalidInput(format!(
+                "Commission {} bps exceeds the maximum {}",
+                commission_bps, self.config.max_commission_bps
+            )));
+        }
+
+        self.validators.insert(
+            id.clone(),
+            Validator {
+                id: id.clone(),
+                commission_bps,
+                self_bond,
+                delegated: 0,
+                status: ValidatorStatus::Active,
+                blocks_signed: 0,
+                total_rewards: 0,
+            },
+        );
+        self.delegations.insert((id.clone(), id.clone()), self_bond);
+        Ok(())
+    }
+
+    /// Bond `amount` from `delegator` to `validator`
+    ///
+    /// A validator bonding to itself adds to its self-bond, which reactivates
+    /// it once the minimum is met again.
+    pub fn bond(&mut self, delegator: &UserId, validator: &UserId, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Err(Error::InvalidInput("Stake amount must be positive".to_string()));
+        }
+        let min_self_bond = self.config.min_self_bond;
+        let entry = self.validators.get_mut(validator)
+            .ok_or_else(|| Error::NotFound(format!("Validator not found: {}", validator)))?;
+
+        if delegator == validator {
+            entry.self_bond += amount;
+            if entry.self_bond >= min_self_bond {
+                entry.status = ValidatorStatus::Active;
+            }
+        } else {
+            if entry.status == ValidatorStatus::Jailed {
+                return Err(Error::InvalidInput(format!("Validator {} is jailed", validator)));
+            }
+            entry.delegated += amount;
+        }
+
+        *self.delegations.entry((delegator.clone(), validator.clone())).or_insert(0) += amount;
+        Ok(())
+    }
+
+    /// Start unbonding `amount`, returning the height it is released at
+    pub fn unbond(&mut self, delegator: &UserId, validator: &UserId, amount: u64, height: u64) -> Result<u64> {
+        let key = (delegator.clone(), validator.clone());
+        let bonded = self.delegations.get(&key).copied().unwrap_or(0);
+        if amount == 0 || amount > bonded {
+            return Err(Error::InvalidInput(format!("Cannot unbond {} of {} bonded", amount, bonded)));
+        }
+
+        let min_self_bond = self.config.min_self_bond;
+        let entry = self.validators.get_mut(validator)
+            .ok_or_else(|| Error::NotFound(format!("Validator not found: {}", validator)))?;
+        if delegator == validator {
+            entry.self_bond -= amount;
+            if entry.self_bond < min_self_bond {
+                entry.status = ValidatorStatus::Jailed;
+            }
+        } else {
+            entry.delegated -= amount;
+        }
+
+        if amount == bonded {
+            self.delegations.remove(&key);
+        } else {
+            self.delegations.insert(key, bonded - amount);
+        }
+
+        let complete_at = height + self.config.unbonding_blocks;
+        self.unbonding.push(UnbondingEntry {
+            delegator: delegator.clone(),
+            validator: validator.clone(),
+            amount,
+            complete_at,
+        });
+        Ok(complete_at)
+    }
+
+    /// Remove and return the unbonding entries that are due at `height`
+    pub fn release_matured(&mut self, height: u64) -> Vec<UnbondingEntry> {
+        let (matured, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.unbonding)
+            .into_iter()
+            .partition(|entry| entry.complete_at <= height);
+        self.unbonding = waiting;
+        matured
+    }
+
+    /// Count a block signed by `validator` towards its uptime
+    pub fn record_block_signed(&mut self, validator: &UserId) -> Result<()> {
+        let entry = self.validators.get_mut(validator)
+            .ok_or_else(|| Error::NotFound(format!("Validator not found: {}", validator)))?;
+        entry.blocks_signed += 1;
+        Ok(())
+    }
+
+    /// Add minted rewards to the current epoch's pool
+    pub fn add_rewards(&mut self, amount: u64) {
+        self.reward_pool += amount;
+    }
+
+    /// Rewards waiting for the end of the epoch
+    pub fn reward_pool(&self) -> u64 {
+        self.reward_pool
+    }
+
+    /// Close the epoch if it has run its length, returning each account's reward
+    ///
+    /// Rounding dust, and the whole pool when no active validator signed a
+    /// block, carries over to the next epoch.
+    pub fn end_epoch(&mut self, height: u64) -> Vec<(UserId, u64)> {
+        if height < self.epoch_start + self.config.epoch_blocks {
+            return Vec::new();
+        }
+        self.epoch_start = height;
+
+        let epoch_blocks = self.config.epoch_blocks.max(1) as u128;
+        let weights: Vec<(UserId, u128)> = self.validators.values()
+            .filter(|v| v.status == ValidatorStatus::Active)
+            .map(|v| (v.id.clone(), v.total_stake() as u128 * (v.blocks_signed as u128).min(epoch_blocks)))
+            .filter(|(_, weight)| *weight > 0)
+            .collect();
+        let total_weight: u128 = weights.iter().map(|(_, weight)| weight).sum();
+
+        let mut payouts: HashMap<UserId, u64> = HashMap::new();
+        if total_weight > 0 {
+            let pool = self.reward_pool as u128;
+            for (id, weight) in &weights {
+                let share = (pool * weight / total_weight) as u64;
+                let validator = &self.validators[id];
+                let commission = share * validator.commission_bps as u64 / 10_000;
+                let to_stakers = (share - commission) as u128;
+                let stake = validator.total_stake() as u128;
+
+                let mut paid = commission;
+                *payouts.entry(id.clone()).or_insert(0) += commission;
+                for ((delegator, target), amount) in &self.delegations {
+                    if target == id {
+                        let reward = (to_stakers * *amount as u128 / stake) as u64;
+                        *payouts.entry(delegator.clone()).or_insert(0) += reward;
+                        paid += reward;
+                    }
+                }
+
+                self.reward_pool -= paid;
+                self.validators.get_mut(id).unwrap().total_rewards += paid;
+            }
+        }
+
+        for validator in self.validators.values_mut() {
+            validator.blocks_signed = 0;
+        }
+        payouts.into_iter().filter(|(_, amount)| *amount > 0).collect()
+    }
+
+    /// Slash up to `amount` from a validator's bonded and unbonding stake
+    ///
+    /// Every position backing the validator loses the same fraction; the
+    /// validator's self-bond absorbs rounding. Returns the amount taken from
+    /// each account.
+    pub fn slash(&mut self, validator: &UserId, amount: u64) -> Result<Vec<(UserId, u64)>> {
+        if !self.validators.contains_key(validator) {
+            return Err(Error::NotFound(format!("Validator not found: {}", validator)));
+        }
+
+        let unbonding: u64 = self.unbonding.iter()
+            .filter(|entry| &entry.validator == validator)
+            .map(|entry| entry.amount)
+            .sum();
+        let total = self.validators[validator].total_stake() + unbonding;
+        let amount = amount.min(total);
+        if amount == 0 {
+            return Ok(Vec::new());
+        }
+        let cut = |position: u64| (position as u128 * amount as u128 / total as u128) as u64;
+
+        let mut taken: HashMap<UserId, u64> = HashMap::new();
+        let mut remaining = amount;
+
+        for ((delegator, target), bonded) in self.delegations.iter_mut() {
+            if target == validator && delegator != validator {
+                let loss = cut(*bonded);
+                *bonded -= loss;
+                remaining -= loss;
+                *taken.entry(delegator.clone()).or_insert(0) += loss;
+            }
+        }
+        for entry in self.unbonding.iter_mut().filter(|entry| &entry.validator == validator) {
+            let loss = cut(entry.amount);
+            entry.amount -= loss;
+            remaining -= loss;
+            *taken.entry(entry.delegator.clone()).or_insert(0) += loss;
+        }
+
+        // The validator's own bond takes its share plus rounding, and any
+        // excess beyond it falls back on the other positions in turn
+        let self_key = (validator.clone(), validator.clone());
+        let self_bonded = self.delegations.get(&self_key).copied().unwrap_or(0);
+        let self_loss = remaining.min(self_bonded);
+        remaining -= self_loss;
+        if self_loss > 0 {
+            self.delegations.insert(self_key, self_bonded - self_loss);
+            *taken.entry(validator.clone()).or_insert(0) += self_loss;
+        }
+        for ((delegator, target), bonded) in self.delegations.iter_mut() {
+            if remaining == 0 {
+                break;
+            }
+            if target == validator && delegator != validator {
+                let loss = remaining.min(*bonded);
+                *bonded -= loss;
+                remaining -= loss;
+                *taken.entry(delegator.clone()).or_insert(0) += loss;
+            }
+        }
+        for entry in self.unbonding.iter_mut().filter(|entry| &entry.validator == validator) {
+            if remaining == 0 {
+                break;
+            }
+            let loss = remaining.min(entry.amount);
+            entry.amount -= loss;
+            remaining -= loss;
+            *taken.entry(entry.delegator.clone()).or_insert(0) += loss;
+        }
+
+        self.delegations.retain(|_, bonded| *bonded > 0);
+        self.unbonding.retain(|entry| entry.amount > 0);
+
+        let min_self_bond = self.config.min_self_bond;
+        let delegated = self.delegations.iter()
+            .filter(|((delegator, target), _)| target == validator && delegator != validator)
+            .map(|(_, bonded)| bonded)
+            .sum();
+        let entry = self.validators.get_mut(validator).unwrap();
+        entry.self_bond = self.delegations.get(&(validator.clone(), validator.clone())).copied().unwrap_or(0);
+        entry.delegated = delegated;
+        if entry.self_bond < min_self_bond {
+            entry.status = ValidatorStatus::Jailed;
+        }
+
+        Ok(taken.into_iter().collect())
+    }
+
+    /// Get a validator
+    pub fn get_validator(&self, id: &UserId) -> Option<&Validator> {
+        self.validators.get(id)
+    }
+
+    /// All validators
+    pub fn validators(&self) -> impl Iterator<Item = &Validator> {
+        self.validators.values()
+    }
+
+    /// Amount `delegator` has bonded to `validator`
+    pub fn delegation(&self, delegator: &UserId, validator: &UserId) -> u64 {
+        self.delegations.get(&(delegator.clone(), validator.clone())).copied().unwrap_or(0)
+    }
+
+    /// Unbonding entries for `delegator`
+    pub fn unbonding_entries(&self, delegator: &UserId) -> Vec<UnbondingEntry> {
+        self.unbonding.iter().filter(|entry| &entry.delegator == delegator).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> StakingConfig {
+        StakingConfig {
+            min_self_bond: 1_000,
+            unbonding_blocks: 10,
+            epoch_blocks: 100,
+            max_commission_bps: 1_000,
+        }
+    }
+
+    #[test]
+    fn test_self_bond_minimum_and_jailing() {
+        let mut ledger = StakingLedger::new(config());
+        let validator = UserId::new();
+        let delegator = UserId::new();
+
+        assert!(ledger.register_validator(&validator, 999, 500).is_err());
+        assert!(ledger.register_validator(&validator, 1_000, 1_001).is_err());
+        ledger.register_validator(&validator, 1_000, 500).unwrap();
+        ledger.bond(&delegator, &validator, 4_000).unwrap();
+        assert_eq!(ledger.get_validator(&validator).unwrap().total_stake(), 5_000);
+
+        // Dropping below the minimum jails the validator
+        ledger.unbond(&validator, &validator, 1, 0).unwrap();
+        assert_eq!(ledger.get_validator(&validator).unwrap().status, ValidatorStatus::Jailed);
+        assert!(ledger.bond(&delegator, &validator, 1).is_err());
+
+        // Bonding back up reactivates it
+        ledger.bond(&validator, &validator, 1).unwrap();
+        assert_eq!(ledger.get_validator(&validator).unwrap().status, ValidatorStatus::Active);
+    }
+
+    #[test]
+    fn test_unbonding_queue() {
+        let mut ledger = StakingLedger::new(config());
+        let validator = UserId::new();
+        let delegator = UserId::new();
+        ledger.register_validator(&validator, 1_000, 0).unwrap();
+        ledger.bond(&delegator, &validator, 500).unwrap();
+
+        assert!(ledger.unbond(&delegator, &validator, 501, 5).is_err());
+        assert_eq!(ledger.unbond(&delegator, &validator, 200, 5).unwrap(), 15);
+        assert_eq!(ledger.delegation(&delegator, &validator), 300);
+
+        assert!(ledger.release_matured(14).is_empty());
+        let released = ledger.release_matured(15);
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].amount, 200);
+        assert!(ledger.unbonding_entries(&delegator).is_empty());
+    }
+
+    #[test]
+    fn test_epoch_rewards_follow_stake_and_uptime() {
+        let mut ledger = StakingLedger::new(config());
+        let full = UserId::new();
+        let half = UserId::new();
+        let delegator = UserId::new();
+        ledger.register_validator(&full, 1_000, 1_000).unwrap();
+        ledger.register_validator(&half, 1_000, 0).unwrap();
+        ledger.bond(&delegator, &full, 1_000).unwrap();
+
+        for _ in 0..100 {
+            ledger.record_block_signed(&full).unwrap();
+        }
+        for _ in 0..50 {
+            ledger.record_block_signed(&half).unwrap();
+        }
+        ledger.add_rewards(50_000);
+
+        // Nothing is paid before the epoch ends
+        assert!(ledger.end_epoch(99).is_empty());
+        let payouts: HashMap<_, _> = ledger.end_epoch(100).into_iter().collect();
+
+        // Weights: full 2000 * 100, half 1000 * 50, so 4/5 and 1/5
+        assert_eq!(payouts[&half], 10_000);
+        // 40_000 for full: 10% commission, then split evenly with the delegator
+        assert_eq!(payouts[&full], 4_000 + 18_000);
+        assert_eq!(payouts[&delegator], 18_000);
+        assert_eq!(ledger.reward_pool(), 0);
+
+        // Uptime resets each epoch; with no signatures the pool carries over
+        ledger.add_rewards(1_000);
+        assert!(ledger.end_epoch(200).is_empty());
+        assert_eq!(ledger.reward_pool(), 1_000);
+    }
+
+    #[test]
+    fn test_slash_reduces_all_positions() {
+        let mut ledger = StakingLedger::new(config());
+        let validator = UserId::new();
+        let delegator = UserId::new();
+        let leaving = UserId::new();
+        ledger.register_validator(&validator, 2_000, 0).unwrap();
+        ledger.bond(&delegator, &validator, 1_000).unwrap();
+        ledger.bond(&leaving, &validator, 1_000).unwrap();
+        ledger.unbond(&leaving, &validator, 1_000, 0).unwrap();
+
+        // 10% of the 4_000 backing the validator, unbonding stake included
+        let taken: HashMap<_, _> = ledger.slash(&validator, 400).unwrap().into_iter().collect();
+        assert_eq!(taken[&validator], 200);
+        assert_eq!(taken[&delegator], 100);
+        assert_eq!(taken[&leaving], 100);
+
+        let entry = ledger.get_validator(&validator).unwrap();
+        assert_eq!(entry.self_bond, 1_800);
+        assert_eq!(entry.delegated, 900);
+        assert_eq!(ledger.unbonding_entries(&leaving)[0].amount, 900);
+
+        // A slash larger than the stake takes everything and jails
+        ledger.slash(&validator, u64::MAX).unwrap();
+        let entry = ledger.get_validator(&validator).unwrap();
+        assert_eq!(entry.total_stake(), 0);
+        assert_eq!(entry.status, ValidatorStatus::Jailed);
+        assert!(ledger.unbonding_entries(&leaving).is_empty());
+    }
+}
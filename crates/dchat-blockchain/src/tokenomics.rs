@@ -5,7 +5,13 @@
 //! - Token distribution mechanisms
 //! - Marketplace liquidity pools
 //! - Token rewards and incentives
+//! - Vesting grants claimed as they release
 //! - Supply management and economics
+//!
+//! Block height follows the chain through `set_block_height`, and each
+//! block's inflation and distributions are processed exactly once; the last
+//! processed height is saved with the rest of the state, so a restarted node
+//! picks up where it stopped instead of paying blocks again.
 
 use chrono::{DateTime, Utc};
 use dchat_core::error::{Error, Result};
@@ -15,8 +21,10 @@ use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use uuid::Uuid;
 
+use crate::vesting::{ReleaseSchedule, VestingGrant, VestingLedger};
+
 /// Token supply configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TokenSupplyConfig {
     /// Initial token supply at genesis
     pub initial_supply: u64,
@@ -70,6 +78,8 @@ pub enum MintReason {
     Airdrop,
     /// Governance proposal reward
     GovernanceReward,
+    /// Vested tokens claimed from a grant
+    Vesting,
 }
 
 /// Token burn event
@@ -94,9 +104,14 @@ pub enum BurnReason {
     Slash,
     /// Voluntary burn for scarcity
     VoluntaryBurn,
+    /// Marketplace fee on an asset transfer
+    PlatformFee,
 }
 
 /// Marketplace liquidity pool
+///
+/// A reserve marketplace sales allocate from; buying and selling tokens
+/// goes through the `amm` swap pools instead.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LiquidityPool {
     pub id: Uuid,
@@ -139,7 +154,8 @@ pub enum RecipientType {
 
 /// Tokenomics manager - handles all token lifecycle operations
 pub struct TokenomicsManager {
-    config: TokenSupplyConfig,
+    /// Supply parameters, which governance can change
+    config: Arc<RwLock<TokenSupplyConfig>>,
     /// Current circulating supply
     circulating_supply: Arc<RwLock<u64>>,
     /// Total burned tokens
@@ -152,8 +168,12 @@ pub struct TokenomicsManager {
     liquidity_pools: Arc<RwLock<HashMap<Uuid, LiquidityPool>>>,
     /// Distribution schedules
     distribution_schedules: Arc<RwLock<Vec<DistributionSchedule>>>,
+    /// Vesting grants
+    vesting: Arc<RwLock<VestingLedger>>,
     /// Current block height
     current_block: Arc<RwLock<u64>>,
+    /// Highest block whose inflation and distributions were processed
+    last_processed_block: Arc<RwLock<u64>>,
 }
 
 impl TokenomicsManager {
@@ -161,14 +181,16 @@ impl TokenomicsManager {
     pub fn new(config: TokenSupplyConfig) -> Self {
         let initial_supply = config.initial_supply;
         Self {
-            config,
+            config: Arc::new(RwLock::new(config)),
             circulating_supply: Arc::new(RwLock::new(initial_supply)),
             total_burned: Arc::new(RwLock::new(0)),
             mint_history: Arc::new(RwLock::new(Vec::new())),
             burn_history: Arc::new(RwLock::new(Vec::new())),
             liquidity_pools: Arc::new(RwLock::new(HashMap::new())),
             distribution_schedules: Arc::new(RwLock::new(Vec::new())),
+            vesting: Arc::new(RwLock::new(VestingLedger::new())),
             current_block: Arc::new(RwLock::new(1)),
+            last_processed_block: Arc::new(RwLock::new(0)),
         }
     }
 
@@ -194,17 +216,24 @@ impl TokenomicsManager {
     }
 
     /// Mint tokens (respects max supply cap)
+    ///
+    /// Mints other than vesting claims leave room under the cap for what
+    /// vesting grants still owe.
     pub fn mint_tokens(
         &self,
         amount: u64,
         reason: MintReason,
         recipient: Option<UserId>,
     ) -> Result<Uuid> {
+        let reserved = match reason {
+            MintReason::Vesting => 0,
+            _ => self.vesting.read().unwrap().committed(),
+        };
         let mut supply = self.circulating_supply.write().unwrap();
         
         // Check max supply cap
-        if let Some(max) = self.config.max_supply {
-            if *supply + amount > max {
+        if let Some(max) = self.supply_config().max_supply {
+            if *supply + reserved + amount > max {
                 return Err(Error::InvalidInput(format!(
                     "Minting {} tokens would exceed max supply of {}",
                     amount, max
@@ -392,15 +421,37 @@ impl TokenomicsManager {
         Ok(schedule_id)
     }
 
-    /// Process inflation for current block
+    /// Process inflation and distributions for every block up to the
+    /// current height that has not been processed yet
+    ///
+    /// A block is marked processed before it mints, so it is never paid
+    /// twice, even if one of its mints fails.
     pub fn process_block_inflation(&self) -> Result<Vec<Uuid>> {
         let current_block = *self.current_block.read().unwrap();
         let mut mint_ids = Vec::new();
+        loop {
+            let height = {
+                let mut last = self.last_processed_block.write().unwrap();
+                if *last >= current_block {
+                    break;
+                }
+                *last += 1;
+                *last
+            };
+            mint_ids.extend(self.process_block(height)?);
+        }
+        Ok(mint_ids)
+    }
+
+    /// Mint one block's inflation and scheduled distributions
+    fn process_block(&self, current_block: u64) -> Result<Vec<Uuid>> {
+        let mut mint_ids = Vec::new();
 
         // Calculate inflation amount per block
         let supply = *self.circulating_supply.read().unwrap();
-        let annual_inflation = (supply as f64 * self.config.inflation_rate_bps as f64) / 10000.0;
-        let blocks_per_year = (365 * 24 * 3600) / self.config.inflation_interval_seconds;
+        let config = self.supply_config();
+        let annual_inflation = (supply as f64 * config.inflation_rate_bps as f64) / 10000.0;
+        let blocks_per_year = (365 * 24 * 3600) / config.inflation_interval_seconds;
         let inflation_per_block = (annual_inflation / blocks_per_year as f64) as u64;
 
         if inflation_per_block > 0 {
@@ -481,6 +532,33 @@ impl TokenomicsManager {
         history.iter().rev().take(limit).cloned().collect()
     }
 
+    /// Current supply parameters
+    pub fn supply_config(&self) -> TokenSupplyConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Replace the supply parameters, e.g. after a governance vote
+    ///
+    /// The initial supply is fixed at genesis and a cap cannot be set below
+    /// the tokens already in circulation.
+    pub fn update_supply_config(&self, config: TokenSupplyConfig) -> Result<()> {
+        let mut current = self.config.write().unwrap();
+        if config.initial_supply != current.initial_supply {
+            return Err(Error::InvalidInput("Initial supply cannot change after genesis".to_string()));
+        }
+        if let Some(max_supply) = config.max_supply {
+            let circulating = *self.circulating_supply.read().unwrap();
+            if max_supply < circulating {
+                return Err(Error::InvalidInput(format!(
+                    "Max supply {} is below circulating supply {}",
+                    max_supply, circulating
+                )));
+            }
+        }
+        *current = config;
+        Ok(())
+    }
+
     /// Get tokenomics statistics
     pub fn get_statistics(&self) -> TokenomicsStats {
         let supply = *self.circulating_supply.read().unwrap();
@@ -488,6 +566,7 @@ impl TokenomicsManager {
         let mint_history = self.mint_history.read().unwrap();
         let _burn_history = self.burn_history.read().unwrap();
         let pools = self.liquidity_pools.read().unwrap();
+        let config = self.supply_config();
 
         let total_minted: u64 = mint_history.iter().map(|e| e.amount).sum();
         let total_pool_liquidity: u64 = pools.values().map(|p| p.total_tokens).sum();
@@ -497,14 +576,93 @@ impl TokenomicsManager {
             total_minted,
             total_burned: burned,
             effective_supply: supply,
-            max_supply: self.config.max_supply,
-            inflation_rate_bps: self.config.inflation_rate_bps,
-            burn_rate_bps: self.config.burn_rate_bps,
+            max_supply: config.max_supply,
+            inflation_rate_bps: config.inflation_rate_bps,
+            burn_rate_bps: config.burn_rate_bps,
             total_pool_liquidity,
             active_pools: pools.len() as u64,
         }
     }
 
+    /// Grant `total` tokens to `beneficiary` on a release schedule
+    ///
+    /// The grant must fit under the supply cap alongside the circulating
+    /// supply and every other grant's outstanding tokens.
+    pub fn create_vesting_grant(
+        &self,
+        beneficiary: &UserId,
+        grantor: &UserId,
+        total: u64,
+        schedule: ReleaseSchedule,
+        revocable: bool,
+    ) -> Result<Uuid> {
+        let mut vesting = self.vesting.write().unwrap();
+        if let Some(max) = self.supply_config().max_supply {
+            let supply = *self.circulating_supply.read().unwrap();
+            if supply + vesting.committed() + total > max {
+                return Err(Error::InvalidInput(format!(
+                    "Granting {} tokens would exceed max supply of {}",
+                    total, max
+                )));
+            }
+        }
+        vesting.create_grant(beneficiary, grantor, total, schedule, revocable, self.get_current_block())
+    }
+
+    /// Mint everything vested to `beneficiary` and not yet claimed, from one
+    /// grant or all of theirs, returning the amount
+    pub fn claim_vested(&self, beneficiary: &UserId, grant_id: Option<Uuid>) -> Result<u64> {
+        let mut vesting = self.vesting.write().unwrap();
+        let claims = vesting.claimable(beneficiary, grant_id, self.get_current_block())?;
+        let total: u64 = claims.iter().map(|(_, amount)| amount).sum();
+        if total == 0 {
+            return Err(Error::InvalidInput(format!("Nothing vested to claim for {}", beneficiary)));
+        }
+
+        self.mint_tokens(total, MintReason::Vesting, Some(beneficiary.clone()))?;
+        for (id, amount) in claims {
+            vesting.record_claim(&id, amount);
+        }
+        Ok(total)
+    }
+
+    /// Revoke a revocable grant, returning the tokens cancelled
+    pub fn revoke_vesting_grant(&self, grant_id: &Uuid, grantor: &UserId) -> Result<u64> {
+        self.vesting.write().unwrap().revoke(grant_id, grantor, self.get_current_block())
+    }
+
+    /// Mark a milestone of a grant reached, vesting its tranche
+    pub fn reach_vesting_milestone(&self, grant_id: &Uuid, grantor: &UserId, index: usize) -> Result<()> {
+        self.vesting.write().unwrap().reach_milestone(grant_id, grantor, index, self.get_current_block())
+    }
+
+    /// Get a vesting grant
+    pub fn get_vesting_grant(&self, grant_id: &Uuid) -> Option<VestingGrant> {
+        self.vesting.read().unwrap().get(grant_id).cloned()
+    }
+
+    /// Vesting grants to `beneficiary`, oldest first
+    pub fn get_vesting_grants(&self, beneficiary: &UserId) -> Vec<VestingGrant> {
+        self.vesting.read().unwrap().grants_for(beneficiary).into_iter().cloned().collect()
+    }
+
+    /// Move to the chain's block height
+    ///
+    /// Blocks up to it are paid by the next `process_block_inflation`.
+    pub fn set_block_height(&self, height: u64) -> Result<()> {
+        let mut block = self.current_block.write().unwrap();
+        if height < *block {
+            return Err(Error::InvalidInput(format!("Block height {} is behind {}", height, *block)));
+        }
+        *block = height;
+        Ok(())
+    }
+
+    /// Highest block whose inflation and distributions were processed
+    pub fn get_last_processed_block(&self) -> u64 {
+        *self.last_processed_block.read().unwrap()
+    }
+
     /// Advance block (for simulation and testing)
     pub fn advance_block(&self) -> Result<()> {
         let mut block = self.current_block.write().unwrap();
@@ -518,6 +676,57 @@ impl TokenomicsManager {
     }
 }
 
+/// Serialized form of [`TokenomicsManager`]
+#[derive(Serialize, Deserialize)]
+struct TokenomicsState {
+    config: TokenSupplyConfig,
+    circulating_supply: u64,
+    total_burned: u64,
+    mint_history: Vec<MintEvent>,
+    burn_history: Vec<BurnEvent>,
+    liquidity_pools: HashMap<Uuid, LiquidityPool>,
+    distribution_schedules: Vec<DistributionSchedule>,
+    vesting: VestingLedger,
+    current_block: u64,
+    last_processed_block: u64,
+}
+
+impl Serialize for TokenomicsManager {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        TokenomicsState {
+            config: self.supply_config(),
+            circulating_supply: *self.circulating_supply.read().unwrap(),
+            total_burned: *self.total_burned.read().unwrap(),
+            mint_history: self.mint_history.read().unwrap().clone(),
+            burn_history: self.burn_history.read().unwrap().clone(),
+            liquidity_pools: self.liquidity_pools.read().unwrap().clone(),
+            distribution_schedules: self.distribution_schedules.read().unwrap().clone(),
+            vesting: self.vesting.read().unwrap().clone(),
+            current_block: *self.current_block.read().unwrap(),
+            last_processed_block: *self.last_processed_block.read().unwrap(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenomicsManager {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let state = TokenomicsState::deserialize(deserializer)?;
+        Ok(Self {
+            config: Arc::new(RwLock::new(state.config)),
+            circulating_supply: Arc::new(RwLock::new(state.circulating_supply)),
+            total_burned: Arc::new(RwLock::new(state.total_burned)),
+            mint_history: Arc::new(RwLock::new(state.mint_history)),
+            burn_history: Arc::new(RwLock::new(state.burn_history)),
+            liquidity_pools: Arc::new(RwLock::new(state.liquidity_pools)),
+            distribution_schedules: Arc::new(RwLock::new(state.distribution_schedules)),
+            vesting: Arc::new(RwLock::new(state.vesting)),
+            current_block: Arc::new(RwLock::new(state.current_block)),
+            last_processed_block: Arc::new(RwLock::new(state.last_processed_block)),
+        })
+    }
+}
+
 /// Tokenomics statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenomicsStats {
@@ -616,4 +825,71 @@ mod tests {
 
         assert!(!schedule_id.is_nil());
     }
+
+    #[test]
+    fn test_blocks_are_processed_once() {
+        let manager = TokenomicsManager::new(TokenSupplyConfig {
+            inflation_rate_bps: 0,
+            ..TokenSupplyConfig::default()
+        });
+        manager.create_distribution_schedule(RecipientType::Treasury, 1_000, 1, None).unwrap();
+
+        assert_eq!(manager.process_block_inflation().unwrap().len(), 1);
+        assert!(manager.process_block_inflation().unwrap().is_empty());
+
+        // Catching up to the chain pays each missed block once
+        manager.set_block_height(4).unwrap();
+        assert_eq!(manager.process_block_inflation().unwrap().len(), 3);
+        assert!(manager.set_block_height(3).is_err());
+
+        // A restart resumes from the saved height
+        let restored: TokenomicsManager = serde_json::from_str(&serde_json::to_string(&manager).unwrap()).unwrap();
+        assert_eq!(restored.get_last_processed_block(), 4);
+        assert!(restored.process_block_inflation().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_vesting_claims_within_supply_cap() {
+        use crate::vesting::ReleaseSchedule;
+
+        let manager = TokenomicsManager::new(TokenSupplyConfig {
+            max_supply: Some(100_000_010_000),
+            ..TokenSupplyConfig::default()
+        });
+        let (team, treasury) = (UserId(Uuid::new_v4()), UserId(Uuid::new_v4()));
+        let schedule = ReleaseSchedule::Linear { start: 1, cliff: 1, end: 101 };
+        let grant = manager.create_vesting_grant(&team, &treasury, 8_000, schedule.clone(), true).unwrap();
+
+        // The grant's tokens are reserved under the cap
+        assert!(manager.create_vesting_grant(&team, &treasury, 3_000, schedule, true).is_err());
+        assert!(manager.mint_tokens(3_000, MintReason::Inflation, None).is_err());
+
+        assert!(manager.claim_vested(&team, None).is_err());
+        manager.set_block_height(51).unwrap();
+        assert!(manager.claim_vested(&treasury, Some(grant)).is_err());
+        assert_eq!(manager.claim_vested(&team, None).unwrap(), 4_000);
+        assert!(manager.claim_vested(&team, Some(grant)).is_err());
+        assert_eq!(manager.get_circulating_supply(), 100_000_004_000);
+
+        // Revoking frees the cancelled tokens for other mints
+        assert_eq!(manager.revoke_vesting_grant(&grant, &treasury).unwrap(), 4_000);
+        manager.mint_tokens(6_000, MintReason::Inflation, None).unwrap();
+    }
+
+    #[test]
+    fn test_state_roundtrip() {
+        let manager = TokenomicsManager::new(TokenSupplyConfig::default());
+        let pool_id = manager.create_liquidity_pool("Marketplace".to_string(), 5_000).unwrap();
+        manager.burn_tokens(100, BurnReason::VoluntaryBurn, UserId(Uuid::new_v4())).unwrap();
+        manager.advance_block().unwrap();
+
+        let json = serde_json::to_string(&manager).unwrap();
+        let restored: TokenomicsManager = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.get_circulating_supply(), manager.get_circulating_supply());
+        assert_eq!(restored.get_total_burned(), 100);
+        assert_eq!(restored.get_current_block(), 2);
+        assert_eq!(restored.get_pool(&pool_id).unwrap().total_tokens, 5_000);
+        assert_eq!(restored.get_mint_history(10).len(), 1);
+    }
 }
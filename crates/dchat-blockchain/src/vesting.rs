@@ -0,0 +1,305 @@
+//! Vesting grants released by block height
+//!
+//! A grant promises `total` tokens to a beneficiary on a release schedule:
+//! all at once at a cliff height, linearly between two heights with nothing
+//! before an optional cliff, or in tranches its grantor marks reached as
+//! milestones are met. Vested tokens are pulled, not pushed: a claim pays
+//! everything vested and not yet claimed.
+//!
+//! The grantor of a revocable grant (a team grant, say) may revoke it.
+//! Whatever vested by then stays claimable and the rest is cancelled.
+//!
+//! This module only tracks grants; `TokenomicsManager` mints the tokens a
+//! claim releases and keeps room under the supply cap for what grants still
+//! owe.
+
+use dchat_core::error::{Error, Result};
+use dchat_core::types::UserId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// A tranche of a milestone grant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Milestone {
+    pub description: String,
+    pub amount: u64,
+    /// Height the grantor marked it reached
+    pub reached_at: Option<u64>,
+}
+
+/// When a grant's tokens vest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReleaseSchedule {
+    /// Everything vests at `at`
+    Cliff { at: u64 },
+    /// Vests evenly from `start` to `end`; nothing is claimable before
+    /// `cliff`, when what accrued since `start` vests at once
+    Linear { start: u64, cliff: u64, end: u64 },
+    /// Each tranche vests when its milestone is reached
+    Milestones(Vec<Milestone>),
+}
+
+/// Tokens promised to a beneficiary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VestingGrant {
+    pub id: Uuid,
+    pub beneficiary: UserId,
+    /// Account that can revoke the grant and mark its milestones reached
+    pub grantor: UserId,
+    pub total: u64,
+    pub claimed: u64,
+    pub schedule: ReleaseSchedule,
+    pub revocable: bool,
+    pub revoked_at: Option<u64>,
+    pub created_at: u64,
+}
+
+impl VestingGrant {
+    /// Tokens vested by `height`, claimed or not
+    pub fn vested_at(&self, height: u64) -> u64 {
+        let height = self.revoked_at.map_or(height, |revoked| height.min(revoked));
+        match &self.schedule {
+            ReleaseSchedule::Cliff { at } => if height >= *at { self.total } else { 0 },
+            ReleaseSchedule::Linear { start, cliff, end } => {
+                if height < *cliff {
+                    0
+                } else if height >= *end {
+                    self.total
+                } else {
+                    (self.total as u128 * (height - start) as u128 / (end - start) as u128) as u64
+                }
+            }
+            ReleaseSchedule::Milestones(milestones) => milestones
+                .iter()
+                .filter(|m| m.reached_at.is_some_and(|reached| reached <= height))
+                .map(|m| m.amount)
+                .sum(),
+        }
+    }
+
+    /// Vested tokens not yet claimed at `height`
+    pub fn claimable(&self, height: u64) -> u64 {
+        self.vested_at(height) - self.claimed
+    }
+
+    /// Tokens the grant may still pay out
+    pub fn committed(&self) -> u64 {
+        match self.revoked_at {
+            Some(revoked) => self.vested_at(revoked) - self.claimed,
+            None => self.total - self.claimed,
+        }
+    }
+}
+
+/// All vesting grants
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VestingLedger {
+    grants: HashMap<Uuid, VestingGrant>,
+}
+
+impl VestingLedger {
+    /// Create a ledger with no grants
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a grant of `total` tokens to `beneficiary`
+    pub fn create_grant(
+        &mut self,
+        beneficiary: &UserId,
+        grantor: &UserId,
+        total: u64,
+        schedule: ReleaseSchedule,
+        revocable: bool,
+        height: u64,
+    ) -> Result<Uuid> {
+        if total == 0 {
+            return Err(Error::InvalidInput("Grant total must be positive".to_string()));
+        }
+        match &schedule {
+            ReleaseSchedule::Cliff { .. } => {}
+            ReleaseSchedule::Linear { start, cliff, end } => {
+                if !(start <= cliff && cliff <= end && start < end) {
+                    return Err(Error::InvalidInput(format!(
+                        "Linear schedule needs start <= cliff <= end and start < end, got {}/{}/{}",
+                        start, cliff, end
+                    )));
+                }
+            }
+            ReleaseSchedule::Milestones(milestones) => {
+                let sum = milestones.iter().try_fold(0u64, |sum, m| sum.checked_add(m.amount));
+                if milestones.is_empty() || sum != Some(total) {
+                    return Err(Error::InvalidInput(format!("Milestone amounts must add up to {}", total)));
+                }
+                if milestones.iter().any(|m| m.reached_at.is_some()) {
+                    return Err(Error::InvalidInput("Milestones start unreached".to_string()));
+                }
+            }
+        }
+
+        let grant = VestingGrant {
+            id: Uuid::new_v4(),
+            beneficiary: beneficiary.clone(),
+            grantor: grantor.clone(),
+            total,
+            claimed: 0,
+            schedule,
+            revocable,
+            revoked_at: None,
+            created_at: height,
+        };
+        let id = grant.id;
+        self.grants.insert(id, grant);
+        Ok(id)
+    }
+
+    /// Get a grant
+    pub fn get(&self, grant_id: &Uuid) -> Option<&VestingGrant> {
+        self.grants.get(grant_id)
+    }
+
+    /// Grants to `beneficiary`, oldest first
+    pub fn grants_for(&self, beneficiary: &UserId) -> Vec<&VestingGrant> {
+        let mut grants: Vec<_> = self.grants.values().filter(|g| &g.beneficiary == beneficiary).collect();
+        grants.sort_by_key(|g| (g.created_at, g.id));
+        grants
+    }
+
+    /// Tokens all grants may still pay out
+    pub fn committed(&self) -> u64 {
+        self.grants.values().map(VestingGrant::committed).sum()
+    }
+
+    /// What `beneficiary` can claim at `height`, from one grant or all of
+    /// theirs
+    pub fn claimable(&self, beneficiary: &UserId, grant_id: Option<Uuid>, height: u64) -> Result<Vec<(Uuid, u64)>> {
+        let grants = match grant_id {
+            Some(id) => {
+                let grant = self.grants.get(&id)
+                    .ok_or_else(|| Error::NotFound(format!("Vesting grant not found: {}", id)))?;
+                if &grant.beneficiary != beneficiary {
+                    return Err(Error::PermissionDenied(format!("Grant {} is not to {}", id, beneficiary)));
+                }
+                vec![grant]
+            }
+            None => self.grants_for(beneficiary),
+        };
+        Ok(grants
+            .into_iter()
+            .map(|g| (g.id, g.claimable(height)))
+            .filter(|(_, amount)| *amount > 0)
+            .collect())
+    }
+
+    /// Mark `amount` of a grant claimed
+    pub(crate) fn record_claim(&mut self, grant_id: &Uuid, amount: u64) {
+        self.grants.get_mut(grant_id).expect("claimed grants exist").claimed += amount;
+    }
+
+    /// Grant `grant_id` if `by` is its grantor and it is not revoked
+    fn grant_for_grantor(&mut self, grant_id: &Uuid, by: &UserId) -> Result<&mut VestingGrant> {
+        let grant = self.grants.get_mut(grant_id)
+            .ok_or_else(|| Error::NotFound(format!("Vesting grant not found: {}", grant_id)))?;
+        if &grant.grantor != by {
+            return Err(Error::PermissionDenied(format!("{} is not the grantor of {}", by, grant_id)));
+        }
+        if grant.revoked_at.is_some() {
+            return Err(Error::InvalidInput(format!("Grant {} is revoked", grant_id)));
+        }
+        Ok(grant)
+    }
+
+    /// Revoke a revocable grant at `height`, returning the tokens cancelled
+    pub fn revoke(&mut self, grant_id: &Uuid, by: &UserId, height: u64) -> Result<u64> {
+        let grant = self.grant_for_grantor(grant_id, by)?;
+        if !grant.revocable {
+            return Err(Error::PermissionDenied(format!("Grant {} is not revocable", grant_id)));
+        }
+        let cancelled = grant.total - grant.vested_at(height);
+        grant.revoked_at = Some(height);
+        Ok(cancelled)
+    }
+
+    /// Mark milestone `index` of a milestone grant reached at `height`
+    pub fn reach_milestone(&mut self, grant_id: &Uuid, by: &UserId, index: usize, height: u64) -> Result<()> {
+        let grant = self.grant_for_grantor(grant_id, by)?;
+        let ReleaseSchedule::Milestones(milestones) = &mut grant.schedule else {
+            return Err(Error::InvalidInput(format!("Grant {} has no milestones", grant_id)));
+        };
+        let milestone = milestones.get_mut(index)
+            .ok_or_else(|| Error::NotFound(format!("Grant {} has no milestone {}", grant_id, index)))?;
+        if milestone.reached_at.is_some() {
+            return Err(Error::AlreadyExists(format!("Milestone {} is already reached", index)));
+        }
+        milestone.reached_at = Some(height);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn milestone(description: &str, amount: u64) -> Milestone {
+        Milestone { description: description.to_string(), amount, reached_at: None }
+    }
+
+    #[test]
+    fn test_linear_vesting_with_cliff() {
+        let mut ledger = VestingLedger::new();
+        let (team, treasury) = (UserId::new(), UserId::new());
+        let schedule = ReleaseSchedule::Linear { start: 100, cliff: 200, end: 500 };
+        let id = ledger.create_grant(&team, &treasury, 4_000, schedule, false, 100).unwrap();
+        let grant = ledger.get(&id).unwrap();
+
+        assert_eq!(grant.vested_at(199), 0);
+        assert_eq!(grant.vested_at(200), 1_000);
+        assert_eq!(grant.vested_at(350), 2_500);
+        assert_eq!(grant.vested_at(600), 4_000);
+        assert!(ledger
+            .create_grant(&team, &treasury, 1, ReleaseSchedule::Linear { start: 5, cliff: 4, end: 9 }, false, 1)
+            .is_err());
+
+        ledger.record_claim(&id, 1_000);
+        assert_eq!(ledger.claimable(&team, None, 350).unwrap(), vec![(id, 1_500)]);
+        assert!(ledger.claimable(&treasury, Some(id), 350).is_err());
+        assert!(ledger.revoke(&id, &treasury, 350).is_err());
+    }
+
+    #[test]
+    fn test_revocation_keeps_what_vested() {
+        let mut ledger = VestingLedger::new();
+        let (team, treasury) = (UserId::new(), UserId::new());
+        let schedule = ReleaseSchedule::Linear { start: 0, cliff: 0, end: 1_000 };
+        let id = ledger.create_grant(&team, &treasury, 10_000, schedule, true, 0).unwrap();
+
+        assert!(ledger.revoke(&id, &team, 250).is_err());
+        assert_eq!(ledger.revoke(&id, &treasury, 250).unwrap(), 7_500);
+        assert_eq!(ledger.get(&id).unwrap().claimable(900), 2_500);
+        assert_eq!(ledger.committed(), 2_500);
+        assert!(ledger.revoke(&id, &treasury, 300).is_err());
+    }
+
+    #[test]
+    fn test_milestones_vest_when_reached() {
+        let mut ledger = VestingLedger::new();
+        let (dev, dao) = (UserId::new(), UserId::new());
+        let bad = ReleaseSchedule::Milestones(vec![milestone("beta", 400)]);
+        assert!(ledger.create_grant(&dev, &dao, 1_000, bad, true, 1).is_err());
+
+        let schedule = ReleaseSchedule::Milestones(vec![milestone("beta", 400), milestone("launch", 600)]);
+        let id = ledger.create_grant(&dev, &dao, 1_000, schedule, true, 1).unwrap();
+        assert_eq!(ledger.get(&id).unwrap().claimable(50), 0);
+
+        assert!(ledger.reach_milestone(&id, &dev, 0, 10).is_err());
+        ledger.reach_milestone(&id, &dao, 0, 10).unwrap();
+        assert!(ledger.reach_milestone(&id, &dao, 0, 11).is_err());
+        assert_eq!(ledger.get(&id).unwrap().claimable(10), 400);
+
+        // Revoking cancels the unreached tranche
+        assert_eq!(ledger.revoke(&id, &dao, 20).unwrap(), 600);
+        assert!(ledger.reach_milestone(&id, &dao, 1, 30).is_err());
+        assert_eq!(ledger.get(&id).unwrap().claimable(30), 400);
+    }
+}
@@ -5,6 +5,7 @@
 use crate::{Bot, BotId, CreateBotRequest, UpdateBotRequest, BotCommand};
 use dchat_core::{Error, Result};
 use dchat_core::types::UserId;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
@@ -310,10 +311,72 @@ impl Default for BotFather {
     }
 }
 
+/// Serialized form of [`BotFather`]; the indexes are rebuilt from the bots
+#[derive(Serialize, Deserialize)]
+struct BotFatherState {
+    bots: HashMap<BotId, Bot>,
+}
+
+impl Serialize for BotFather {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        BotFatherState {
+            bots: self.bots.read().unwrap().clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BotFather {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let state = BotFatherState::deserialize(deserializer)?;
+        let mut bots: Vec<&Bot> = state.bots.values().collect();
+        // Owners list their bots in creation order
+        bots.sort_by_key(|bot| (bot.created_at, bot.id));
+
+        let mut username_index = HashMap::new();
+        let mut token_index = HashMap::new();
+        let mut owner_index: HashMap<UserId, Vec<BotId>> = HashMap::new();
+        for bot in bots {
+            username_index.insert(bot.username.clone(), bot.id);
+            token_index.insert(bot.token.clone(), bot.id);
+            owner_index.entry(bot.owner_id.clone()).or_default().push(bot.id);
+        }
+
+        Ok(Self {
+            bots: Arc::new(RwLock::new(state.bots)),
+            username_index: Arc::new(RwLock::new(username_index)),
+            token_index: Arc::new(RwLock::new(token_index)),
+            owner_index: Arc::new(RwLock::new(owner_index)),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_bots_survive_serialization() {
+        let bot_father = BotFather::new();
+        let owner_id = UserId::new();
+        let mut ids = Vec::new();
+        for username in ["firstbot", "secondbot"] {
+            let request = CreateBotRequest {
+                username: username.to_string(),
+                display_name: username.to_string(),
+                description: None,
+            };
+            ids.push(bot_father.create_bot(owner_id.clone(), request).unwrap().id);
+        }
+        let token = bot_father.regenerate_token(&ids[0], &owner_id).unwrap();
+        
+        let restored: BotFather = serde_json::from_str(&serde_json::to_string(&bot_father).unwrap()).unwrap();
+        assert_eq!(restored.get_bot_by_token(&token).unwrap().id, ids[0]);
+        assert_eq!(restored.get_bot_by_username("secondbot").unwrap().id, ids[1]);
+        let owned: Vec<BotId> = restored.get_user_bots(&owner_id).iter().map(|bot| bot.id).collect();
+        assert_eq!(owned, ids);
+    }
+    
     #[test]
     fn test_create_bot() {
         let bot_father = BotFather::new();
@@ -10,6 +10,7 @@
 //! - State synchronization
 //! - Bridge validator consensus
 //! - Multi-signature validation (M-of-N)
+//! - Light clients that follow each chain's signed, finalized headers
 
 use chrono::{DateTime, Utc};
 use dchat_core::{types::UserId, Error, Result};
@@ -18,6 +19,7 @@ use std::collections::HashMap;
 use std::fmt;
 use uuid::Uuid;
 
+pub mod light_client;
 pub mod multisig;
 pub mod slashing;
 
@@ -37,6 +39,11 @@ pub mod types {
         InvalidSignature,
         TransactionAlreadyExists,
         TransactionNotFound,
+        QuorumNotReached,
+        InvalidRotation,
+        InvalidHeader,
+        UnknownHeader,
+        InvalidProof,
     }
 
     impl fmt::Display for BridgeError {
@@ -49,6 +56,11 @@ pub mod types {
                 BridgeError::InvalidSignature => write!(f, "Invalid signature"),
                 BridgeError::TransactionAlreadyExists => write!(f, "Transaction already exists"),
                 BridgeError::TransactionNotFound => write!(f, "Transaction not found"),
+                BridgeError::QuorumNotReached => write!(f, "Not enough validator signatures"),
+                BridgeError::InvalidRotation => write!(f, "Validator rotation is not for the next epoch"),
+                BridgeError::InvalidHeader => write!(f, "Header does not extend the finalized chain"),
+                BridgeError::UnknownHeader => write!(f, "No finalized header at that height"),
+                BridgeError::InvalidProof => write!(f, "Invalid inclusion proof"),
             }
         }
     }
@@ -99,6 +111,9 @@ pub struct BridgeTransaction {
 }
 
 /// Finality proof for a transaction
+///
+/// `proof_data` is a JSON-encoded `MerklePath` proving the transaction hash
+/// is in the message root of the finalized header at `block_number`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FinalityProof {
     pub chain: ChainId,
@@ -137,6 +152,7 @@ pub struct BridgeManager {
     finality_proofs: HashMap<String, FinalityProof>,
     state_sync: Vec<StateSyncRecord>,
     required_confirmations: HashMap<ChainId, u32>,
+    light_clients: HashMap<ChainId, light_client::LightClient>,
     pub multisig: multisig::MultiSigManager,
     pub slashing: slashing::SlashingManager,
 }
@@ -164,6 +180,7 @@ impl BridgeManager {
             finality_proofs: HashMap::new(),
             state_sync: Vec::new(),
             required_confirmations,
+            light_clients: HashMap::new(),
             multisig: multisig::MultiSigManager::new(multisig_config),
             slashing: slashing::SlashingManager::new(),
         }
@@ -204,15 +221,51 @@ impl BridgeManager {
         Ok(tx_id)
     }
 
+    /// Follow a chain's finalized headers with `client`
+    pub fn add_light_client(&mut self, client: light_client::LightClient) {
+        self.light_clients.insert(client.chain().clone(), client);
+    }
+
+    /// Light client following `chain`
+    pub fn light_client(&self, chain: &ChainId) -> Option<&light_client::LightClient> {
+        self.light_clients.get(chain)
+    }
+
+    /// Accept the next finalized header of the header's chain
+    pub fn submit_header(&mut self, signed: &light_client::SignedHeader) -> Result<()> {
+        let client = self
+            .light_clients
+            .get_mut(&signed.header.chain)
+            .ok_or_else(|| Error::validation("No light client for chain"))?;
+        client
+            .submit_header(signed)
+            .map_err(|e| Error::crypto(e.to_string()))
+    }
+
     /// Submit finality proof for a transaction
+    ///
+    /// The transaction hash must be proven included in a header the chain's
+    /// light client has finalized. Confirmations count the finalized headers
+    /// built on top of it, so resubmitting later can raise them.
     pub fn submit_finality_proof(
         &mut self,
         tx_hash: String,
         chain: ChainId,
         block_number: u64,
-        confirmations: u32,
         proof_data: Vec<u8>,
     ) -> Result<()> {
+        let client = self
+            .light_clients
+            .get(&chain)
+            .ok_or_else(|| Error::validation("No light client for chain"))?;
+
+        let path: dchat_crypto::merkle::MerklePath = serde_json::from_slice(&proof_data)
+            .map_err(|e| Error::validation(format!("Malformed finality proof: {}", e)))?;
+        client
+            .verify_inclusion(block_number, tx_hash.as_bytes(), &path)
+            .map_err(|e| Error::crypto(e.to_string()))?;
+
+        let confirmations = client.finalized_height().saturating_sub(block_number) as u32;
         let required = self
             .required_confirmations
             .get(&chain)
@@ -388,6 +441,16 @@ impl BridgeManager {
         self.transactions.get(&tx_id)
     }
 
+    /// All bridge transactions, in no particular order
+    pub fn transactions(&self) -> impl Iterator<Item = &BridgeTransaction> {
+        self.transactions.values()
+    }
+
+    /// Get a registered validator
+    pub fn get_validator(&self, validator_id: &UserId) -> Option<&BridgeValidator> {
+        self.validators.get(validator_id)
+    }
+
     /// Get all active validators
     pub fn get_active_validators(&self) -> Vec<&BridgeValidator> {
         self.validators
@@ -411,11 +474,53 @@ impl Default for BridgeManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use dchat_crypto::keys::PrivateKey;
+    use dchat_crypto::merkle::{empty_root, MerkleTree};
+    use light_client::{ChainHeader, LightClient, SignedHeader, GENESIS_PARENT};
 
     fn create_test_user(_name: &str) -> UserId {
         UserId::new()
     }
 
+    /// Bridge following a chat chain that committed `tx_hashes` at height
+    /// 100 and finalized headers up to `finalized_height`
+    fn bridge_with_chain(tx_hashes: &[&str], finalized_height: u64) -> (BridgeManager, MerkleTree) {
+        let signers: Vec<_> = (1..=3u8)
+            .map(|seed| {
+                let key = PrivateKey::from_bytes([seed; 32]);
+                (multisig::ValidatorId::new(UserId::new(), key.public_key().as_bytes().to_vec()), key)
+            })
+            .collect();
+        let config = multisig::MultiSigConfig::new(2, signers.iter().map(|(v, _)| v.clone()).collect()).unwrap();
+
+        let mut bridge = BridgeManager::new();
+        bridge.add_light_client(LightClient::new(ChainId::ChatChain, config));
+
+        let tree = MerkleTree::from_leaves(tx_hashes.iter().map(|hash| hash.as_bytes()));
+        let mut parent_hash = GENESIS_PARENT;
+        for height in 100..=finalized_height {
+            let header = ChainHeader {
+                chain: ChainId::ChatChain,
+                height,
+                parent_hash,
+                message_root: if height == 100 { tree.root() } else { empty_root() },
+                validator_epoch: 0,
+            };
+            let signatures = signers
+                .iter()
+                .map(|(v, key)| multisig::ValidatorSignature::sign(v.clone(), key, &header.signing_bytes()))
+                .collect();
+            parent_hash = header.hash();
+            bridge.submit_header(&SignedHeader { header, signatures }).unwrap();
+        }
+
+        (bridge, tree)
+    }
+
+    fn inclusion_proof(tree: &MerkleTree, index: usize) -> Vec<u8> {
+        serde_json::to_vec(&tree.path(index).unwrap()).unwrap()
+    }
+
     #[test]
     fn test_initiate_transaction() {
         let mut bridge = BridgeManager::new();
@@ -456,41 +561,59 @@ mod tests {
 
     #[test]
     fn test_finality_proof() {
-        let mut bridge = BridgeManager::new();
+        let (mut bridge, tree) = bridge_with_chain(&["tx_456", "tx_789"], 115);
 
         bridge
             .submit_finality_proof(
                 "tx_789".to_string(),
                 ChainId::ChatChain,
                 100,
-                15,
-                vec![1, 2, 3],
+                inclusion_proof(&tree, 1),
             )
             .unwrap();
 
         assert!(bridge.check_finality("tx_789"));
+        assert_eq!(bridge.get_finality_proof("tx_789").unwrap().confirmations, 15);
     }
 
     #[test]
     fn test_finality_not_reached() {
-        let mut bridge = BridgeManager::new();
+        let (mut bridge, tree) = bridge_with_chain(&["tx_abc"], 105);
 
         bridge
             .submit_finality_proof(
                 "tx_abc".to_string(),
                 ChainId::ChatChain,
                 100,
-                5,
-                vec![1, 2, 3],
+                inclusion_proof(&tree, 0),
             )
             .unwrap();
 
         assert!(!bridge.check_finality("tx_abc"));
     }
 
+    #[test]
+    fn test_finality_proof_must_prove_inclusion() {
+        let (mut bridge, tree) = bridge_with_chain(&["tx_456", "tx_789"], 115);
+
+        // A proof for another transaction, an unparseable proof, an
+        // unfinalized height and an untracked chain are all rejected
+        let wrong = bridge.submit_finality_proof("tx_forged".to_string(), ChainId::ChatChain, 100, inclusion_proof(&tree, 1));
+        assert!(wrong.is_err());
+        let malformed = bridge.submit_finality_proof("tx_789".to_string(), ChainId::ChatChain, 100, vec![1, 2, 3]);
+        assert!(malformed.is_err());
+        let unfinalized = bridge.submit_finality_proof("tx_789".to_string(), ChainId::ChatChain, 200, inclusion_proof(&tree, 1));
+        assert!(unfinalized.is_err());
+        let untracked = bridge.submit_finality_proof("tx_789".to_string(), ChainId::CurrencyChain, 100, inclusion_proof(&tree, 1));
+        assert!(untracked.is_err());
+
+        assert!(!bridge.check_finality("tx_forged"));
+        assert!(!bridge.check_finality("tx_789"));
+    }
+
     #[test]
     fn test_transaction_flow() {
-        let mut bridge = BridgeManager::new();
+        let (mut bridge, tree) = bridge_with_chain(&["tx_def"], 120);
         let user = create_test_user("charlie");
 
         let tx_id = bridge
@@ -510,9 +633,8 @@ mod tests {
             .submit_finality_proof(
                 "tx_def".to_string(),
                 ChainId::ChatChain,
-                200,
-                20,
-                vec![],
+                100,
+                inclusion_proof(&tree, 0),
             )
             .unwrap();
 
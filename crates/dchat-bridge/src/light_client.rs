@@ -0,0 +1,276 @@
+//! Light client for the other chain's finalized headers
+//!
+//! Each side of the bridge follows the other chain through a chain of
+//! headers, each signed by a quorum of that chain's validator set and linked
+//! to the previous one by hash. A header's `message_root` is the Merkle root
+//! of the bridge messages the chain committed at that height, so a message is
+//! only accepted with an inclusion proof against a finalized header.
+//!
+//! The tracked validator set changes only through `ValidatorRotation`s signed
+//! by the outgoing set; headers name the epoch of the set that signed them.
+
+use crate::multisig::{MultiSigConfig, MultiSigManager, ValidatorRotation, ValidatorSignature};
+use crate::types::BridgeError;
+use crate::ChainId;
+use dchat_crypto::merkle::MerklePath;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Domain separator for bridge headers
+const HEADER_DOMAIN: &[u8] = b"dchat-bridge-header-v1";
+
+/// Hash the first header of a chain links to
+pub const GENESIS_PARENT: [u8; 32] = [0; 32];
+
+/// A block header as seen by the bridge
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainHeader {
+    pub chain: ChainId,
+    pub height: u64,
+    /// Hash of the previous bridge header of this chain
+    pub parent_hash: [u8; 32],
+    /// Merkle root of the bridge messages committed at this height
+    pub message_root: [u8; 32],
+    /// Epoch of the validator set that signs this header
+    pub validator_epoch: u64,
+}
+
+impl ChainHeader {
+    /// Bytes validators sign
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = HEADER_DOMAIN.to_vec();
+        bytes.extend(serde_json::to_vec(self).expect("headers serialize"));
+        bytes
+    }
+
+    /// Hash the next header links to
+    pub fn hash(&self) -> [u8; 32] {
+        dchat_crypto::hash(&self.signing_bytes())
+    }
+}
+
+/// A header with its validators' signatures
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedHeader {
+    pub header: ChainHeader,
+    pub signatures: Vec<ValidatorSignature>,
+}
+
+/// A light client's validator set and finalized headers, saved so it can
+/// resume after a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightClientState {
+    pub chain: ChainId,
+    pub validators: MultiSigConfig,
+    pub validator_epoch: u64,
+    pub headers: BTreeMap<u64, ChainHeader>,
+}
+
+/// Follows one chain's finalized headers
+pub struct LightClient {
+    chain: ChainId,
+    validators: MultiSigManager,
+    /// Finalized headers by height
+    headers: BTreeMap<u64, ChainHeader>,
+}
+
+impl LightClient {
+    /// Start following `chain` from its genesis validator set
+    pub fn new(chain: ChainId, validators: MultiSigConfig) -> Self {
+        Self {
+            chain,
+            validators: MultiSigManager::new(validators),
+            headers: BTreeMap::new(),
+        }
+    }
+
+    /// Resume from a saved state
+    pub fn from_state(state: LightClientState) -> Self {
+        Self {
+            chain: state.chain,
+            validators: MultiSigManager::at_epoch(state.validators, state.validator_epoch),
+            headers: state.headers,
+        }
+    }
+
+    /// State to save
+    pub fn state(&self) -> LightClientState {
+        LightClientState {
+            chain: self.chain.clone(),
+            validators: self.validators.config(),
+            validator_epoch: self.validators.epoch(),
+            headers: self.headers.clone(),
+        }
+    }
+
+    /// Chain this client follows
+    pub fn chain(&self) -> &ChainId {
+        &self.chain
+    }
+
+    /// Accept the next finalized header
+    ///
+    /// The header must extend the latest one, be for the current validator
+    /// epoch and carry a quorum of that set's signatures.
+    pub fn submit_header(&mut self, signed: &SignedHeader) -> Result<(), BridgeError> {
+        let header = &signed.header;
+        if header.chain != self.chain || header.validator_epoch != self.validators.epoch() {
+            return Err(BridgeError::InvalidHeader);
+        }
+
+        let (latest_height, parent_hash) = match self.latest() {
+            Some(latest) => (Some(latest.height), latest.hash()),
+            None => (None, GENESIS_PARENT),
+        };
+        if latest_height.is_some_and(|latest| header.height <= latest) || header.parent_hash != parent_hash {
+            return Err(BridgeError::InvalidHeader);
+        }
+
+        self.validators.verify_quorum(&header.signing_bytes(), &signed.signatures)?;
+        self.headers.insert(header.height, header.clone());
+        Ok(())
+    }
+
+    /// Hand the tracked validator set off to its successor
+    pub fn apply_rotation(&self, rotation: &ValidatorRotation) -> Result<(), BridgeError> {
+        if rotation.chain != self.chain {
+            return Err(BridgeError::InvalidRotation);
+        }
+        self.validators.rotate_validators(rotation)
+    }
+
+    /// Epoch of the validator set signing new headers
+    pub fn validator_epoch(&self) -> u64 {
+        self.validators.epoch()
+    }
+
+    /// Latest finalized header
+    pub fn latest(&self) -> Option<&ChainHeader> {
+        self.headers.values().next_back()
+    }
+
+    /// Height of the latest finalized header, 0 before the first
+    pub fn finalized_height(&self) -> u64 {
+        self.latest().map(|header| header.height).unwrap_or(0)
+    }
+
+    /// Finalized header at `height`
+    pub fn header_at(&self, height: u64) -> Option<&ChainHeader> {
+        self.headers.get(&height)
+    }
+
+    /// Check that `leaf` was committed in the finalized header at `height`
+    pub fn verify_inclusion(&self, height: u64, leaf: &[u8], path: &MerklePath) -> Result<(), BridgeError> {
+        let header = self.header_at(height).ok_or(BridgeError::UnknownHeader)?;
+        if !path.verify(leaf, &header.message_root) {
+            return Err(BridgeError::InvalidProof);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dchat_core::types::UserId;
+    use dchat_crypto::keys::PrivateKey;
+    use dchat_crypto::merkle::MerkleTree;
+    use crate::multisig::ValidatorId;
+
+    fn signers(seeds: &[u8]) -> Vec<(ValidatorId, PrivateKey)> {
+        seeds
+            .iter()
+            .map(|seed| {
+                let key = PrivateKey::from_bytes([*seed; 32]);
+                (ValidatorId::new(UserId::new(), key.public_key().as_bytes().to_vec()), key)
+            })
+            .collect()
+    }
+
+    fn config(signers: &[(ValidatorId, PrivateKey)]) -> MultiSigConfig {
+        MultiSigConfig::new(2, signers.iter().map(|(v, _)| v.clone()).collect()).unwrap()
+    }
+
+    fn sign(header: ChainHeader, signers: &[(ValidatorId, PrivateKey)]) -> SignedHeader {
+        let signatures = signers
+            .iter()
+            .map(|(v, key)| ValidatorSignature::sign(v.clone(), key, &header.signing_bytes()))
+            .collect();
+        SignedHeader { header, signatures }
+    }
+
+    fn header(height: u64, parent_hash: [u8; 32], message_root: [u8; 32], validator_epoch: u64) -> ChainHeader {
+        ChainHeader {
+            chain: ChainId::CurrencyChain,
+            height,
+            parent_hash,
+            message_root,
+            validator_epoch,
+        }
+    }
+
+    #[test]
+    fn test_headers_must_be_signed_and_linked() {
+        let validators = signers(&[1, 2, 3]);
+        let mut client = LightClient::new(ChainId::CurrencyChain, config(&validators));
+
+        let tree = MerkleTree::from_leaves([b"m0", b"m1", b"m2"]);
+        let first = header(5, GENESIS_PARENT, tree.root(), 0);
+
+        // One signature is not a quorum, and outsiders cannot sign
+        assert!(client.submit_header(&sign(first.clone(), &validators[..1])).is_err());
+        assert!(client.submit_header(&sign(first.clone(), &signers(&[7, 8]))).is_err());
+
+        client.submit_header(&sign(first.clone(), &validators[..2])).unwrap();
+        assert_eq!(client.finalized_height(), 5);
+
+        // A header must link to the latest one and move forward
+        assert!(client.submit_header(&sign(header(6, GENESIS_PARENT, tree.root(), 0), &validators)).is_err());
+        assert!(client.submit_header(&sign(header(5, first.hash(), tree.root(), 0), &validators)).is_err());
+        client.submit_header(&sign(header(9, first.hash(), tree.root(), 0), &validators)).unwrap();
+
+        // Inclusion proofs are checked against the header's message root
+        let path = tree.path(1).unwrap();
+        client.verify_inclusion(5, b"m1", &path).unwrap();
+        assert!(client.verify_inclusion(5, b"m2", &path).is_err());
+        assert!(client.verify_inclusion(6, b"m1", &path).is_err());
+    }
+
+    #[test]
+    fn test_rotation_hands_off_signing() {
+        let old = signers(&[1, 2, 3]);
+        let new = signers(&[4, 5, 6]);
+        let mut client = LightClient::new(ChainId::CurrencyChain, config(&old));
+
+        let first = header(1, GENESIS_PARENT, [0; 32], 0);
+        client.submit_header(&sign(first.clone(), &old)).unwrap();
+
+        let mut rotation = ValidatorRotation::new(ChainId::CurrencyChain, 1, config(&new));
+        for (validator, key) in &old[..2] {
+            rotation.add_signature(validator.clone(), key);
+        }
+
+        // A hand-off for the other chain is refused
+        let mut misdirected = ValidatorRotation::new(ChainId::ChatChain, 1, config(&new));
+        for (validator, key) in &old[..2] {
+            misdirected.add_signature(validator.clone(), key);
+        }
+        assert!(client.apply_rotation(&misdirected).is_err());
+
+        client.apply_rotation(&rotation).unwrap();
+        assert_eq!(client.validator_epoch(), 1);
+
+        // The outgoing set can no longer finalize headers
+        let next = header(2, first.hash(), [0; 32], 1);
+        assert!(client.submit_header(&sign(next.clone(), &old)).is_err());
+        client.submit_header(&sign(next.clone(), &new)).unwrap();
+
+        // A restored client keeps the rotated set and the header chain
+        let state: LightClientState = serde_json::from_slice(&serde_json::to_vec(&client.state()).unwrap()).unwrap();
+        let mut restored = LightClient::from_state(state);
+        assert_eq!(restored.validator_epoch(), 1);
+        let third = header(3, next.hash(), [0; 32], 1);
+        assert!(restored.submit_header(&sign(third.clone(), &old)).is_err());
+        restored.submit_header(&sign(third, &new)).unwrap();
+    }
+}
@@ -1,9 +1,22 @@
+//! M-of-N validator signatures for bridge transactions and headers
+//!
+//! Signatures are Ed25519 and always checked against the key the validator
+//! set records for the signer, never the key a signature claims. The set
+//! itself only changes through a `ValidatorRotation` signed by a quorum of
+//! the outgoing validators.
+
 use crate::types::{BridgeError, TransactionId};
+use crate::ChainId;
 use dchat_core::types::UserId;
+use dchat_crypto::keys::{PrivateKey, PublicKey};
+use dchat_crypto::signatures::{self, Signature};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
+/// Domain separator for validator-set rotations
+const ROTATION_DOMAIN: &[u8] = b"dchat-bridge-rotation-v1";
+
 /// Validator identity with public key
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ValidatorId {
@@ -26,6 +39,25 @@ pub struct ValidatorSignature {
     pub signed_at: chrono::DateTime<chrono::Utc>,
 }
 
+impl ValidatorSignature {
+    /// Sign `message` as `validator_id`
+    pub fn sign(validator_id: ValidatorId, private_key: &PrivateKey, message: &[u8]) -> Self {
+        Self {
+            validator_id,
+            signature: signatures::sign(private_key, message).to_bytes().to_vec(),
+            signed_at: chrono::Utc::now(),
+        }
+    }
+}
+
+/// Whether `signature` is a valid Ed25519 signature over `message` by `public_key`
+fn verify_ed25519(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let (Ok(key), Ok(signature)) = (<[u8; 32]>::try_from(public_key), <[u8; 64]>::try_from(signature)) else {
+        return false;
+    };
+    signatures::verify(&PublicKey::from_bytes(key), message, &Signature::from_bytes(signature)).is_ok()
+}
+
 /// Multi-signature configuration (M-of-N)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MultiSigConfig {
@@ -72,6 +104,70 @@ impl MultiSigConfig {
     pub fn get_validator(&self, validator_id: &UserId) -> Option<&ValidatorId> {
         self.validators.iter().find(|v| &v.id == validator_id)
     }
+
+    /// Check that `signatures` are a quorum of this set over `message`
+    ///
+    /// Every signature must come from a distinct member and verify under the
+    /// key this set records for it.
+    pub fn verify_quorum(&self, message: &[u8], signatures: &[ValidatorSignature]) -> Result<(), BridgeError> {
+        let mut signers = HashSet::new();
+        for signature in signatures {
+            let validator = self
+                .get_validator(&signature.validator_id.id)
+                .ok_or(BridgeError::UnknownValidator)?;
+            if !signers.insert(&validator.id) {
+                return Err(BridgeError::DuplicateSignature);
+            }
+            if !verify_ed25519(&validator.public_key, message, &signature.signature) {
+                return Err(BridgeError::InvalidSignature);
+            }
+        }
+
+        if !self.has_quorum(signers.len()) {
+            return Err(BridgeError::QuorumNotReached);
+        }
+        Ok(())
+    }
+}
+
+/// A hand-off to a new validator set, signed by the current one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidatorRotation {
+    /// Chain whose validator set is rotated
+    pub chain: ChainId,
+    /// Epoch the new set takes over; one past the current epoch
+    pub epoch: u64,
+    pub new_config: MultiSigConfig,
+    /// Signatures from the outgoing set
+    pub signatures: Vec<ValidatorSignature>,
+}
+
+impl ValidatorRotation {
+    /// Create an unsigned rotation
+    pub fn new(chain: ChainId, epoch: u64, new_config: MultiSigConfig) -> Self {
+        Self {
+            chain,
+            epoch,
+            new_config,
+            signatures: Vec::new(),
+        }
+    }
+
+    /// Bytes the outgoing validators sign
+    ///
+    /// The chain is included so a rotation signed for one chain cannot be
+    /// replayed against another chain that shares validators.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = ROTATION_DOMAIN.to_vec();
+        bytes.extend(serde_json::to_vec(&(&self.chain, self.epoch, &self.new_config)).expect("rotations serialize"));
+        bytes
+    }
+
+    /// Add an outgoing validator's signature
+    pub fn add_signature(&mut self, validator_id: ValidatorId, private_key: &PrivateKey) {
+        let signature = ValidatorSignature::sign(validator_id, private_key, &self.signing_bytes());
+        self.signatures.push(signature);
+    }
 }
 
 /// Multi-signature state for a transaction
@@ -131,22 +227,18 @@ impl MultiSigState {
         self.signatures.len()
     }
 
-    /// Verify signature (simplified - in production use proper Ed25519 verification)
+    /// Verify a signature under the key the validator set records for its signer
     pub fn verify_signature(
         &self,
         signature: &ValidatorSignature,
-        _message: &[u8],
+        message: &[u8],
     ) -> Result<(), BridgeError> {
-        // In production: use ed25519_dalek or similar
-        // ed25519::verify(&signature.validator_id.public_key, message, &signature.signature)
-
-        // Simplified check: signature must be non-empty
-        if signature.signature.is_empty() {
-            return Err(BridgeError::InvalidSignature);
-        }
+        let validator = self
+            .config
+            .get_validator(&signature.validator_id.id)
+            .ok_or(BridgeError::UnknownValidator)?;
 
-        // Check signature length (Ed25519 signatures are 64 bytes)
-        if signature.signature.len() != 64 {
+        if !verify_ed25519(&validator.public_key, message, &signature.signature) {
             return Err(BridgeError::InvalidSignature);
         }
 
@@ -160,14 +252,22 @@ pub struct MultiSigManager {
     states: Arc<RwLock<HashMap<TransactionId, MultiSigState>>>,
     /// Global validator set (can be rotated)
     global_config: Arc<RwLock<MultiSigConfig>>,
+    /// Rotations applied to the global set
+    epoch: Arc<RwLock<u64>>,
 }
 
 impl MultiSigManager {
     /// Create a new multi-sig manager
     pub fn new(config: MultiSigConfig) -> Self {
+        Self::at_epoch(config, 0)
+    }
+
+    /// Resume a manager whose set has been rotated `epoch` times
+    pub fn at_epoch(config: MultiSigConfig, epoch: u64) -> Self {
         Self {
             states: Arc::new(RwLock::new(HashMap::new())),
             global_config: Arc::new(RwLock::new(config)),
+            epoch: Arc::new(RwLock::new(epoch)),
         }
     }
 
@@ -225,13 +325,42 @@ impl MultiSigManager {
             .unwrap_or(0)
     }
 
-    /// Rotate validator set (for dynamic validator management)
-    pub fn rotate_validators(&self, new_config: MultiSigConfig) -> Result<(), BridgeError> {
+    /// Hand off to a new validator set
+    ///
+    /// The rotation must be for the next epoch and carry a quorum of the
+    /// current set's signatures, so only the outgoing validators can name
+    /// their successors and an old rotation cannot be replayed. Transactions
+    /// already initialized keep the set they started with.
+    pub fn rotate_validators(&self, rotation: &ValidatorRotation) -> Result<(), BridgeError> {
         let mut config = self.global_config.write().unwrap();
-        *config = new_config;
+        let mut epoch = self.epoch.write().unwrap();
+
+        if rotation.epoch != *epoch + 1 {
+            return Err(BridgeError::InvalidRotation);
+        }
+        config.verify_quorum(&rotation.signing_bytes(), &rotation.signatures)?;
+
+        let new_config = &rotation.new_config;
+        *config = MultiSigConfig::new(new_config.threshold, new_config.validators.clone())?;
+        *epoch = rotation.epoch;
         Ok(())
     }
 
+    /// Rotations applied so far
+    pub fn epoch(&self) -> u64 {
+        *self.epoch.read().unwrap()
+    }
+
+    /// Check that `signatures` are a quorum of the current set over `message`
+    pub fn verify_quorum(&self, message: &[u8], signatures: &[ValidatorSignature]) -> Result<(), BridgeError> {
+        self.global_config.read().unwrap().verify_quorum(message, signatures)
+    }
+
+    /// Current validator set and threshold
+    pub fn config(&self) -> MultiSigConfig {
+        self.global_config.read().unwrap().clone()
+    }
+
     /// Get current validator set
     pub fn get_validators(&self) -> Vec<ValidatorId> {
         self.global_config.read().unwrap().validators.clone()
@@ -268,10 +397,12 @@ impl SignatureAggregator {
     }
 
     /// Verify aggregated signature (conceptual)
+    ///
+    /// Each 64-byte chunk must verify under the public key at the same position.
     pub fn verify_aggregated(
         aggregated: &[u8],
         public_keys: &[Vec<u8>],
-        _message: &[u8],
+        message: &[u8],
     ) -> Result<(), BridgeError> {
         // In production: use BLS signature verification
         // Verifies that aggregated signature is valid for all public keys
@@ -285,6 +416,14 @@ impl SignatureAggregator {
             return Err(BridgeError::InvalidSignature);
         }
 
+        let all_valid = aggregated
+            .chunks(64)
+            .zip(public_keys)
+            .all(|(signature, public_key)| verify_ed25519(public_key, message, signature));
+        if !all_valid {
+            return Err(BridgeError::InvalidSignature);
+        }
+
         Ok(())
     }
 }
@@ -294,18 +433,18 @@ mod tests {
     use super::*;
     use uuid::Uuid;
 
+    fn create_signer(id: u8) -> (ValidatorId, PrivateKey) {
+        let private_key = PrivateKey::from_bytes([id; 32]);
+        let public_key = private_key.public_key().as_bytes().to_vec();
+        (ValidatorId::new(UserId::new(), public_key), private_key)
+    }
+
     fn create_validator(id: u8) -> ValidatorId {
-        let user_id = UserId::new();
-        let public_key = vec![id; 32]; // 32-byte public key
-        ValidatorId::new(user_id, public_key)
+        create_signer(id).0
     }
 
-    fn create_signature(validator: ValidatorId) -> ValidatorSignature {
-        ValidatorSignature {
-            validator_id: validator,
-            signature: vec![0u8; 64], // 64-byte signature
-            signed_at: chrono::Utc::now(),
-        }
+    fn create_signature(signer: &(ValidatorId, PrivateKey), message: &[u8]) -> ValidatorSignature {
+        ValidatorSignature::sign(signer.0.clone(), &signer.1, message)
     }
 
     #[test]
@@ -351,20 +490,21 @@ mod tests {
 
     #[test]
     fn test_add_signature() {
-        let validators = vec![create_validator(1), create_validator(2), create_validator(3)];
-        let config = MultiSigConfig::new(2, validators.clone()).unwrap();
+        let signers = [create_signer(1), create_signer(2), create_signer(3)];
+        let validators = signers.iter().map(|(v, _)| v.clone()).collect();
+        let config = MultiSigConfig::new(2, validators).unwrap();
 
         let tx_id = Uuid::new_v4();
         let mut state = MultiSigState::new(tx_id, config);
 
         // Add first signature
-        let sig1 = create_signature(validators[0].clone());
+        let sig1 = create_signature(&signers[0], b"transaction_data");
         let quorum_reached = state.add_signature(sig1).unwrap();
         assert!(!quorum_reached);
         assert_eq!(state.signature_count(), 1);
 
         // Add second signature - quorum reached
-        let sig2 = create_signature(validators[1].clone());
+        let sig2 = create_signature(&signers[1], b"transaction_data");
         let quorum_reached = state.add_signature(sig2).unwrap();
         assert!(quorum_reached);
         assert_eq!(state.signature_count(), 2);
@@ -373,13 +513,14 @@ mod tests {
 
     #[test]
     fn test_duplicate_signature() {
-        let validators = vec![create_validator(1), create_validator(2)];
-        let config = MultiSigConfig::new(2, validators.clone()).unwrap();
+        let signers = [create_signer(1), create_signer(2)];
+        let validators = signers.iter().map(|(v, _)| v.clone()).collect();
+        let config = MultiSigConfig::new(2, validators).unwrap();
 
         let tx_id = Uuid::new_v4();
         let mut state = MultiSigState::new(tx_id, config);
 
-        let sig1 = create_signature(validators[0].clone());
+        let sig1 = create_signature(&signers[0], b"transaction_data");
         state.add_signature(sig1.clone()).unwrap();
 
         // Try to add same validator's signature again
@@ -396,8 +537,7 @@ mod tests {
         let mut state = MultiSigState::new(tx_id, config);
 
         // Unknown validator
-        let unknown = create_validator(99);
-        let sig = create_signature(unknown);
+        let sig = create_signature(&create_signer(99), b"transaction_data");
 
         let result = state.add_signature(sig);
         assert!(result.is_err());
@@ -418,8 +558,9 @@ mod tests {
 
     #[test]
     fn test_multisig_manager_submit_signatures() {
-        let validators = vec![create_validator(1), create_validator(2), create_validator(3)];
-        let config = MultiSigConfig::new(2, validators.clone()).unwrap();
+        let signers = [create_signer(1), create_signer(2), create_signer(3)];
+        let validators = signers.iter().map(|(v, _)| v.clone()).collect();
+        let config = MultiSigConfig::new(2, validators).unwrap();
         let manager = MultiSigManager::new(config);
 
         let tx_id = Uuid::new_v4();
@@ -428,21 +569,41 @@ mod tests {
         let message = b"transaction_data";
 
         // Submit first signature
-        let sig1 = create_signature(validators[0].clone());
+        let sig1 = create_signature(&signers[0], message);
         let quorum = manager.submit_signature(tx_id, sig1, message).unwrap();
         assert!(!quorum);
         assert_eq!(manager.get_signature_count(tx_id), 1);
 
+        // A signature over a different message is rejected
+        let forged = create_signature(&signers[1], b"other_data");
+        assert!(manager.submit_signature(tx_id, forged, message).is_err());
+
         // Submit second signature - quorum
-        let sig2 = create_signature(validators[1].clone());
+        let sig2 = create_signature(&signers[1], message);
         let quorum = manager.submit_signature(tx_id, sig2, message).unwrap();
         assert!(quorum);
         assert!(manager.has_quorum(tx_id));
     }
 
+    #[test]
+    fn test_signature_must_match_registered_key() {
+        let signer = create_signer(1);
+        let config = MultiSigConfig::new(1, vec![signer.0.clone()]).unwrap();
+        let state = MultiSigState::new(Uuid::new_v4(), config);
+
+        // An impostor claims the validator's ID with their own key
+        let impostor = create_signer(2);
+        let claimed = ValidatorId::new(signer.0.id.clone(), impostor.0.public_key.clone());
+        let sig = ValidatorSignature::sign(claimed, &impostor.1, b"message");
+
+        assert!(state.verify_signature(&sig, b"message").is_err());
+        assert!(state.verify_signature(&create_signature(&signer, b"message"), b"message").is_ok());
+    }
+
     #[test]
     fn test_validator_rotation() {
-        let validators = vec![create_validator(1), create_validator(2)];
+        let signers = [create_signer(1), create_signer(2)];
+        let validators = signers.iter().map(|(v, _)| v.clone()).collect();
         let config = MultiSigConfig::new(2, validators).unwrap();
         let manager = MultiSigManager::new(config);
 
@@ -450,16 +611,38 @@ mod tests {
         assert_eq!(original_validators.len(), 2);
 
         // Rotate to new set
-        let new_validators = vec![
-            create_validator(3),
-            create_validator(4),
-            create_validator(5),
-        ];
+        let new_signers = [create_signer(3), create_signer(4), create_signer(5)];
+        let new_validators = new_signers.iter().map(|(v, _)| v.clone()).collect();
         let new_config = MultiSigConfig::new(2, new_validators).unwrap();
-        manager.rotate_validators(new_config).unwrap();
+        let mut rotation = ValidatorRotation::new(ChainId::ChatChain, 1, new_config);
+
+        // One of two outgoing signatures is not a quorum
+        rotation.add_signature(signers[0].0.clone(), &signers[0].1);
+        assert!(matches!(manager.rotate_validators(&rotation), Err(BridgeError::QuorumNotReached)));
+
+        // The incoming set cannot approve itself
+        let mut self_approved = rotation.clone();
+        self_approved.signatures.clear();
+        for (validator, key) in &new_signers {
+            self_approved.add_signature(validator.clone(), key);
+        }
+        assert!(manager.rotate_validators(&self_approved).is_err());
+
+        rotation.add_signature(signers[1].0.clone(), &signers[1].1);
+
+        // The signatures do not carry over to the same hand-off on another chain
+        let mut other_chain = rotation.clone();
+        other_chain.chain = ChainId::CurrencyChain;
+        assert!(matches!(manager.rotate_validators(&other_chain), Err(BridgeError::InvalidSignature)));
+
+        manager.rotate_validators(&rotation).unwrap();
 
         let rotated_validators = manager.get_validators();
         assert_eq!(rotated_validators.len(), 3);
+        assert_eq!(manager.epoch(), 1);
+
+        // The same rotation cannot be applied twice
+        assert!(matches!(manager.rotate_validators(&rotation), Err(BridgeError::InvalidRotation)));
     }
 
     #[test]
@@ -479,28 +662,31 @@ mod tests {
 
     #[test]
     fn test_signature_aggregation() {
-        let validators = vec![create_validator(1), create_validator(2)];
-        let sig1 = create_signature(validators[0].clone());
-        let sig2 = create_signature(validators[1].clone());
+        let signers = [create_signer(1), create_signer(2)];
+        let message = b"transaction_data";
+        let sig1 = create_signature(&signers[0], message);
+        let sig2 = create_signature(&signers[1], message);
 
         let signatures = vec![sig1, sig2];
         let aggregated = SignatureAggregator::aggregate(&signatures);
 
         assert_eq!(aggregated.len(), 128); // 2 signatures × 64 bytes
 
-        let public_keys = vec![validators[0].public_key.clone(), validators[1].public_key.clone()];
-        let message = b"transaction_data";
+        let public_keys = vec![signers[0].0.public_key.clone(), signers[1].0.public_key.clone()];
         
         SignatureAggregator::verify_aggregated(&aggregated, &public_keys, message).unwrap();
+
+        let swapped = vec![public_keys[1].clone(), public_keys[0].clone()];
+        assert!(SignatureAggregator::verify_aggregated(&aggregated, &swapped, message).is_err());
     }
 
     #[test]
     fn test_invalid_signature_length() {
-        let validator = create_validator(1);
-        let mut sig = create_signature(validator.clone());
-        sig.signature = vec![0u8; 32]; // Invalid length (should be 64)
+        let signer = create_signer(1);
+        let mut sig = create_signature(&signer, b"message");
+        sig.signature.truncate(32); // Invalid length (should be 64)
 
-        let config = MultiSigConfig::new(1, vec![validator]).unwrap();
+        let config = MultiSigConfig::new(1, vec![signer.0]).unwrap();
         let tx_id = Uuid::new_v4();
         let state = MultiSigState::new(tx_id, config);
 
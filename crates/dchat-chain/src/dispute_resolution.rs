@@ -1,16 +1,38 @@
 //! Cryptographic dispute resolution for fork arbitration
 //!
 //! Implements Section 18 (Dispute Resolution) from ARCHITECTURE.md
-//! - Claim-challenge-respond mechanism
-//! - Fork arbitration with cryptographic proofs
-//! - Message integrity verification
-//! - Slashing for false claims
+//! - Claim-challenge-respond mechanism with windows measured in blocks
+//! - Fork arbitration from conflicting signed block headers
+//! - Message integrity verification against signed hash commitments
+//! - Automatic slashing and insurance claims for proven misbehaviour
+//!
+//! Fork and integrity claims are fraud proofs: the evidence either verifies
+//! against the accused validator's key or it does not, so they resolve
+//! without a vote once their windows close. Only these are slashed
+//! automatically. Other claims carry evidence nothing here can verify: an
+//! unanswered challenge clears the accused, and anything else is held for a
+//! governance vote.
+//!
+//! Evidence can back one claim only. A claim is refused if the same evidence
+//! was already submitted against the accused, or if an open or upheld fork
+//! claim already covers the same equivocation height.
 
+use crate::insurance_fund::{ClaimType, InsuranceFund};
+use dchat_bridge::slashing::{SlashReason, SlashingManager};
 use dchat_core::error::{Error, Result};
+use dchat_core::types::UserId;
+use dchat_crypto::keys::PublicKey;
+use dchat_crypto::signatures::{self, Signature};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use blake3::Hasher;
 
+/// Domain separator for block headers signed by validators
+const HEADER_DOMAIN: &[u8] = b"dchat-block-header-v1";
+
+/// Domain separator for message hash commitments
+const COMMITMENT_DOMAIN: &[u8] = b"dchat-message-commitment-v1";
+
 /// Dispute claim identifier
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ClaimId(pub String);
@@ -28,16 +50,32 @@ pub enum DisputeType {
     DoubleSpend,
 }
 
+impl DisputeType {
+    /// Whether the evidence for this type is a self-contained fraud proof
+    pub fn is_provable(&self) -> bool {
+        matches!(self, DisputeType::ForkDetected | DisputeType::IntegrityViolation)
+    }
+
+    fn slash_reason(&self) -> SlashReason {
+        match self {
+            DisputeType::ForkDetected | DisputeType::DoubleSpend => SlashReason::DoubleSigning,
+            DisputeType::IntegrityViolation | DisputeType::InvalidStateTransition => SlashReason::FalseProof,
+        }
+    }
+}
+
 /// Dispute claim
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisputeClaim {
     pub id: ClaimId,
     pub dispute_type: DisputeType,
-    pub claimant: String,
-    pub accused: String,
+    pub claimant: UserId,
+    pub accused: UserId,
     pub evidence: Vec<u8>,
     pub evidence_hash: Vec<u8>,
     pub timestamp: i64,
+    /// Block height the claim was submitted at
+    pub submitted_at_height: u64,
     pub status: DisputeStatus,
 }
 
@@ -64,75 +102,193 @@ pub enum DisputeStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisputeChallenge {
     pub claim_id: ClaimId,
-    pub challenger: String,
+    pub challenger: UserId,
     pub counter_evidence: Vec<u8>,
     pub counter_evidence_hash: Vec<u8>,
     pub timestamp: i64,
+    pub height: u64,
 }
 
 /// Response to a challenge
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisputeResponse {
     pub claim_id: ClaimId,
-    pub responder: String,
+    pub responder: UserId,
     pub additional_evidence: Vec<u8>,
     pub timestamp: i64,
+    pub height: u64,
+}
+
+/// Block header as signed by the validator that produced it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub height: u64,
+    pub parent_hash: [u8; 32],
+    pub state_root: [u8; 32],
+    pub timestamp: i64,
+}
+
+impl BlockHeader {
+    /// Bytes the validator signs
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = HEADER_DOMAIN.to_vec();
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&self.parent_hash);
+        bytes.extend_from_slice(&self.state_root);
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes
+    }
+
+    /// Header hash
+    pub fn hash(&self) -> [u8; 32] {
+        *blake3::hash(&self.signing_bytes()).as_bytes()
+    }
+}
+
+/// A block header with the producing validator's signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedBlockHeader {
+    pub header: BlockHeader,
+    pub signature: Vec<u8>,
+}
+
+impl SignedBlockHeader {
+    /// Whether `signer` signed this header
+    pub fn is_signed_by(&self, signer: &PublicKey) -> bool {
+        verify_signature(signer, &self.header.signing_bytes(), &self.signature)
+    }
 }
 
-/// Fork evidence
+/// Fork evidence: one validator signed two different blocks at one height
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ForkEvidence {
-    /// First message in fork
-    pub message_a: Vec<u8>,
-    /// Second conflicting message
-    pub message_b: Vec<u8>,
-    /// Signature on message A
-    pub signature_a: Vec<u8>,
-    /// Signature on message B
-    pub signature_b: Vec<u8>,
-    /// Sequence number (should be same for fork)
-    pub sequence_number: u64,
+    /// Key of the validator that signed both headers
+    pub validator_key: PublicKey,
+    /// First signed header
+    pub header_a: SignedBlockHeader,
+    /// Second, conflicting signed header
+    pub header_b: SignedBlockHeader,
 }
 
 /// Integrity violation evidence
+///
+/// The accused signed a commitment that `message` hashes to `claimed_hash`,
+/// and it does not.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IntegrityEvidence {
+    /// Key of the validator that signed the commitment
+    pub validator_key: PublicKey,
     pub message: Vec<u8>,
     pub claimed_hash: Vec<u8>,
     pub actual_hash: Vec<u8>,
+    /// Signature over the commitment to `claimed_hash`
     pub signature: Vec<u8>,
 }
 
+impl IntegrityEvidence {
+    /// Bytes a validator signs to commit to a message hash
+    pub fn commitment_bytes(message: &[u8], claimed_hash: &[u8]) -> Vec<u8> {
+        let mut bytes = COMMITMENT_DOMAIN.to_vec();
+        bytes.extend_from_slice(&(claimed_hash.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(claimed_hash);
+        bytes.extend_from_slice(message);
+        bytes
+    }
+}
+
+/// Dispute timing and penalties
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisputeConfig {
+    /// Blocks after submission during which the accused may challenge
+    pub challenge_window_blocks: u64,
+    /// Blocks after a challenge during which the claimant may respond
+    pub response_window_blocks: u64,
+    /// Stake slashed from a validator found at fault
+    pub slash_amount: u64,
+    /// Compensation claimed from the insurance fund for the claimant
+    pub compensation_amount: u64,
+    /// Share of votes needed to decide a voted dispute
+    pub vote_threshold: f64,
+}
+
+impl Default for DisputeConfig {
+    fn default() -> Self {
+        Self {
+            challenge_window_blocks: 5760, // ~1 day at 15s blocks
+            response_window_blocks: 2880,  // ~12 hours
+            slash_amount: 10_000,
+            compensation_amount: 1_000,
+            vote_threshold: 0.66,
+        }
+    }
+}
+
+/// What resolving a dispute did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisputeOutcome {
+    pub status: DisputeStatus,
+    /// Stake slashed from the accused
+    pub slashed: u64,
+    /// Insurance claim raised for the claimant
+    pub insurance_claim: Option<uuid::Uuid>,
+}
+
 /// Dispute resolver
 pub struct DisputeResolver {
+    config: DisputeConfig,
     claims: HashMap<ClaimId, DisputeClaim>,
     challenges: HashMap<ClaimId, Vec<DisputeChallenge>>,
     responses: HashMap<ClaimId, Vec<DisputeResponse>>,
-    slash_threshold: f64,
+    /// Signing keys of validators that can be accused
+    validators: HashMap<UserId, PublicKey>,
 }
 
 impl DisputeResolver {
     pub fn new() -> Self {
+        Self::with_config(DisputeConfig::default())
+    }
+
+    pub fn with_config(config: DisputeConfig) -> Self {
         Self {
+            config,
             claims: HashMap::new(),
             challenges: HashMap::new(),
             responses: HashMap::new(),
-            slash_threshold: 0.66, // 66% vote threshold for slashing
+            validators: HashMap::new(),
         }
     }
 
+    /// Register the key a validator signs blocks with
+    pub fn register_validator(&mut self, validator: UserId, public_key: PublicKey) {
+        self.validators.insert(validator, public_key);
+    }
+
     /// Submit a new dispute claim
     pub fn submit_claim(
         &mut self,
         dispute_type: DisputeType,
-        claimant: String,
-        accused: String,
+        claimant: UserId,
+        accused: UserId,
         evidence: Vec<u8>,
+        current_height: u64,
     ) -> Result<ClaimId> {
         // Validate evidence format based on dispute type
         self.validate_evidence(&dispute_type, &evidence)?;
 
         let evidence_hash = self.hash_evidence(&evidence);
+        if self.claims.values().any(|c| c.accused == accused && c.evidence_hash == evidence_hash) {
+            return Err(Error::AlreadyExists("Evidence was already submitted against this validator".to_string()));
+        }
+        if let Some(height) = equivocation_height(&dispute_type, &evidence) {
+            let covered = self.claims.values().any(|c| {
+                c.accused == accused
+                    && !matches!(c.status, DisputeStatus::ResolvedForAccused | DisputeStatus::Dismissed)
+                    && equivocation_height(&c.dispute_type, &c.evidence) == Some(height)
+            });
+            if covered {
+                return Err(Error::AlreadyExists(format!("Equivocation at height {} is already claimed", height)));
+            }
+        }
         let claim_id = ClaimId(uuid::Uuid::new_v4().to_string());
 
         let claim = DisputeClaim {
@@ -143,6 +299,7 @@ impl DisputeResolver {
             evidence,
             evidence_hash,
             timestamp: chrono::Utc::now().timestamp(),
+            submitted_at_height: current_height,
             status: DisputeStatus::Pending,
         };
 
@@ -182,15 +339,17 @@ impl DisputeResolver {
         hasher.finalize().as_bytes().to_vec()
     }
 
-    /// Challenge a claim
+    /// Challenge a claim within its challenge window
     pub fn challenge_claim(
         &mut self,
         claim_id: ClaimId,
-        challenger: String,
+        challenger: UserId,
         counter_evidence: Vec<u8>,
+        current_height: u64,
     ) -> Result<()> {
         // Compute hash before borrowing self mutably
         let counter_evidence_hash = self.hash_evidence(&counter_evidence);
+        let window = self.config.challenge_window_blocks;
         
         let claim = self.claims.get_mut(&claim_id)
             .ok_or_else(|| Error::network("Claim not found"))?;
@@ -204,12 +363,17 @@ impl DisputeResolver {
             return Err(Error::network("Only accused can challenge claim"));
         }
 
+        if current_height > claim.submitted_at_height.saturating_add(window) {
+            return Err(Error::network("Challenge window has closed"));
+        }
+
         let challenge = DisputeChallenge {
             claim_id: claim_id.clone(),
             challenger,
             counter_evidence,
             counter_evidence_hash,
             timestamp: chrono::Utc::now().timestamp(),
+            height: current_height,
         };
 
         self.challenges.entry(claim_id.clone())
@@ -221,13 +385,15 @@ impl DisputeResolver {
         Ok(())
     }
 
-    /// Respond to a challenge
+    /// Respond to a challenge within the response window
     pub fn respond_to_challenge(
         &mut self,
         claim_id: ClaimId,
-        responder: String,
+        responder: UserId,
         additional_evidence: Vec<u8>,
+        current_height: u64,
     ) -> Result<()> {
+        let deadline = self.response_deadline(&claim_id);
         let claim = self.claims.get_mut(&claim_id)
             .ok_or_else(|| Error::network("Claim not found"))?;
 
@@ -240,11 +406,16 @@ impl DisputeResolver {
             return Err(Error::network("Only claimant can respond to challenge"));
         }
 
+        if deadline.is_some_and(|deadline| current_height > deadline) {
+            return Err(Error::network("Response window has closed"));
+        }
+
         let response = DisputeResponse {
             claim_id: claim_id.clone(),
             responder,
             additional_evidence,
             timestamp: chrono::Utc::now().timestamp(),
+            height: current_height,
         };
 
         self.responses.entry(claim_id.clone())
@@ -256,62 +427,203 @@ impl DisputeResolver {
         Ok(())
     }
 
+    /// Last height at which the claimant may respond to a challenge
+    fn response_deadline(&self, claim_id: &ClaimId) -> Option<u64> {
+        self.challenges.get(claim_id)
+            .and_then(|challenges| challenges.last())
+            .map(|challenge| challenge.height.saturating_add(self.config.response_window_blocks))
+    }
+
     /// Verify fork evidence cryptographically
+    ///
+    /// Both headers must be at the same height, differ, and carry valid
+    /// signatures from `validator_key`.
     pub fn verify_fork_evidence(&self, evidence: &ForkEvidence) -> Result<bool> {
-        // Check that both messages claim same sequence number
-        if evidence.message_a.is_empty() || evidence.message_b.is_empty() {
-            return Ok(false);
-        }
+        let a = &evidence.header_a;
+        let b = &evidence.header_b;
 
-        // In production: verify signatures with Ed25519
-        // For now, check that messages are different
-        Ok(evidence.message_a != evidence.message_b)
+        Ok(a.header.height == b.header.height
+            && a.header.hash() != b.header.hash()
+            && a.is_signed_by(&evidence.validator_key)
+            && b.is_signed_by(&evidence.validator_key))
     }
 
     /// Verify integrity violation evidence
+    ///
+    /// The commitment must be signed by `validator_key` and the message must
+    /// not hash to the committed value.
     pub fn verify_integrity_evidence(&self, evidence: &IntegrityEvidence) -> Result<bool> {
         // Compute actual hash
         let computed_hash = self.hash_evidence(&evidence.message);
+        let commitment = IntegrityEvidence::commitment_bytes(&evidence.message, &evidence.claimed_hash);
 
         // Check if it matches the accused's claimed hash (should differ)
-        Ok(computed_hash != evidence.claimed_hash && computed_hash == evidence.actual_hash)
+        Ok(computed_hash != evidence.claimed_hash
+            && computed_hash == evidence.actual_hash
+            && verify_signature(&evidence.validator_key, &commitment, &evidence.signature))
     }
 
-    /// Submit claim to governance vote
-    pub fn submit_to_vote(&mut self, claim_id: ClaimId) -> Result<()> {
-        let claim = self.claims.get_mut(&claim_id)
-            .ok_or_else(|| Error::network("Claim not found"))?;
+    /// Whether a claim's evidence proves misbehaviour by the accused
+    fn evidence_proves_fault(&self, claim: &DisputeClaim) -> Result<bool> {
+        let Some(accused_key) = self.validators.get(&claim.accused) else {
+            return Ok(false);
+        };
 
-        if claim.status != DisputeStatus::Responded {
-            return Err(Error::network("Claim must be responded to before voting"));
+        match claim.dispute_type {
+            DisputeType::ForkDetected => {
+                let evidence: ForkEvidence = serde_json::from_slice(&claim.evidence)?;
+                Ok(&evidence.validator_key == accused_key && self.verify_fork_evidence(&evidence)?)
+            }
+            DisputeType::IntegrityViolation => {
+                let evidence: IntegrityEvidence = serde_json::from_slice(&claim.evidence)?;
+                Ok(&evidence.validator_key == accused_key && self.verify_integrity_evidence(&evidence)?)
+            }
+            _ => Ok(false),
         }
+    }
 
-        claim.status = DisputeStatus::UnderVote;
+    /// Resolve a dispute once its windows have closed
+    ///
+    /// Fraud proofs resolve on their evidence: proven faults are slashed and
+    /// the claimant is compensated from the insurance fund. Other claims fail
+    /// if a challenge goes unanswered and otherwise go to a governance vote;
+    /// they are never slashed without one.
+    pub fn resolve_dispute(
+        &mut self,
+        claim_id: ClaimId,
+        current_height: u64,
+        slashing: &SlashingManager,
+        insurance: &mut InsuranceFund,
+    ) -> Result<DisputeOutcome> {
+        let claim = self.claims.get(&claim_id)
+            .ok_or_else(|| Error::network("Claim not found"))?;
 
-        Ok(())
+        let window_open = match claim.status {
+            DisputeStatus::Pending => {
+                current_height <= claim.submitted_at_height.saturating_add(self.config.challenge_window_blocks)
+            }
+            DisputeStatus::Challenged => self.response_deadline(&claim_id)
+                .is_some_and(|deadline| current_height <= deadline),
+            DisputeStatus::Responded => false,
+            _ => return Err(Error::network("Claim is not awaiting resolution")),
+        };
+
+        // A fraud proof cannot be rebutted, so waiting only matters for the
+        // accused to see the claim
+        let for_claimant = if claim.dispute_type.is_provable() {
+            if window_open && claim.status == DisputeStatus::Pending {
+                return Err(Error::network("Challenge window is still open"));
+            }
+            self.evidence_proves_fault(claim)?
+        } else {
+            if window_open {
+                return Err(Error::network("Dispute windows are still open"));
+            }
+            match claim.status {
+                DisputeStatus::Challenged => false,
+                _ => {
+                    self.set_status(&claim_id, DisputeStatus::UnderVote);
+                    return Ok(DisputeOutcome {
+                        status: DisputeStatus::UnderVote,
+                        slashed: 0,
+                        insurance_claim: None,
+                    });
+                }
+            }
+        };
+
+        self.settle(claim_id, for_claimant, slashing, insurance)
     }
 
-    /// Resolve dispute based on vote
-    pub fn resolve_dispute(&mut self, claim_id: ClaimId, vote_for_claimant: f64) -> Result<()> {
-        let claim = self.claims.get_mut(&claim_id)
+    /// Resolve a disputed claim by governance vote
+    pub fn resolve_by_vote(
+        &mut self,
+        claim_id: ClaimId,
+        vote_for_claimant: f64,
+        slashing: &SlashingManager,
+        insurance: &mut InsuranceFund,
+    ) -> Result<DisputeOutcome> {
+        let claim = self.claims.get(&claim_id)
             .ok_or_else(|| Error::network("Claim not found"))?;
 
         if claim.status != DisputeStatus::UnderVote {
             return Err(Error::network("Claim not under vote"));
         }
 
-        if vote_for_claimant >= self.slash_threshold {
-            claim.status = DisputeStatus::ResolvedForClaimant;
-            // In production: slash accused's stake
-        } else if vote_for_claimant <= (1.0 - self.slash_threshold) {
-            claim.status = DisputeStatus::ResolvedForAccused;
-            // In production: slash claimant's stake for false claim
+        if vote_for_claimant >= self.config.vote_threshold {
+            self.settle(claim_id, true, slashing, insurance)
+        } else if vote_for_claimant <= (1.0 - self.config.vote_threshold) {
+            self.settle(claim_id, false, slashing, insurance)
         } else {
-            claim.status = DisputeStatus::Dismissed;
             // Inconclusive: no slashing
+            self.set_status(&claim_id, DisputeStatus::Dismissed);
+            Ok(DisputeOutcome {
+                status: DisputeStatus::Dismissed,
+                slashed: 0,
+                insurance_claim: None,
+            })
         }
+    }
 
-        Ok(())
+    /// Record the outcome, slashing the accused and compensating the claimant
+    /// if the claim stands
+    fn settle(
+        &mut self,
+        claim_id: ClaimId,
+        for_claimant: bool,
+        slashing: &SlashingManager,
+        insurance: &mut InsuranceFund,
+    ) -> Result<DisputeOutcome> {
+        if !for_claimant {
+            self.set_status(&claim_id, DisputeStatus::ResolvedForAccused);
+            return Ok(DisputeOutcome {
+                status: DisputeStatus::ResolvedForAccused,
+                slashed: 0,
+                insurance_claim: None,
+            });
+        }
+
+        let claim = self.claims[&claim_id].clone();
+        slashing
+            .slash_validator(
+                claim.accused.clone(),
+                claim.dispute_type.slash_reason(),
+                self.config.slash_amount,
+                None,
+                claim.evidence.clone(),
+                Some(claim.claimant.clone()),
+            )
+            .map_err(|e| Error::chain(format!("Slashing failed: {}", e)))?;
+
+        // Compensation is capped by what the fund holds
+        let compensation = self.config.compensation_amount.min(insurance.balance());
+        let insurance_claim = if compensation > 0 {
+            Some(insurance.submit_claim(
+                claim.claimant.clone(),
+                ClaimType::AttackCompensation {
+                    attack_type: format!("{:?}", claim.dispute_type),
+                    affected_users: vec![claim.claimant.clone()],
+                    total_loss: compensation,
+                },
+                compensation,
+                vec![format!("dispute:{}", claim.id.0)],
+            )?)
+        } else {
+            None
+        };
+
+        self.set_status(&claim_id, DisputeStatus::ResolvedForClaimant);
+        Ok(DisputeOutcome {
+            status: DisputeStatus::ResolvedForClaimant,
+            slashed: self.config.slash_amount,
+            insurance_claim,
+        })
+    }
+
+    fn set_status(&mut self, claim_id: &ClaimId, status: DisputeStatus) {
+        if let Some(claim) = self.claims.get_mut(claim_id) {
+            claim.status = status;
+        }
     }
 
     /// Get claim by ID
@@ -365,209 +677,311 @@ pub struct DisputeStats {
     pub dismissed_claims: usize,
 }
 
+/// Height a fork claim's evidence shows the accused signing twice at
+fn equivocation_height(dispute_type: &DisputeType, evidence: &[u8]) -> Option<u64> {
+    if *dispute_type != DisputeType::ForkDetected {
+        return None;
+    }
+    serde_json::from_slice::<ForkEvidence>(evidence).ok().map(|e| e.header_a.header.height)
+}
+
+fn verify_signature(signer: &PublicKey, message: &[u8], signature: &[u8]) -> bool {
+    let Ok(bytes) = <[u8; 64]>::try_from(signature) else {
+        return false;
+    };
+    signatures::verify(signer, message, &Signature::from_bytes(bytes)).is_ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_submit_claim() {
-        let mut resolver = DisputeResolver::new();
-
-        let evidence = ForkEvidence {
-            message_a: b"message 1".to_vec(),
-            message_b: b"message 2".to_vec(),
-            signature_a: vec![0; 64],
-            signature_b: vec![0; 64],
-            sequence_number: 42,
+    use crate::insurance_fund::FundConfiguration;
+    use dchat_crypto::keys::PrivateKey;
+
+    fn signed_header(key: &PrivateKey, height: u64, state_root: u8) -> SignedBlockHeader {
+        let header = BlockHeader {
+            height,
+            parent_hash: [1; 32],
+            state_root: [state_root; 32],
+            timestamp: 1_700_000_000,
         };
+        SignedBlockHeader {
+            signature: signatures::sign(key, &header.signing_bytes()).to_bytes().to_vec(),
+            header,
+        }
+    }
 
-        let claim_id = resolver.submit_claim(
-            DisputeType::ForkDetected,
-            "alice".to_string(),
-            "bob".to_string(),
-            serde_json::to_vec(&evidence).unwrap(),
-        ).unwrap();
+    fn fork_evidence(key: &PrivateKey) -> ForkEvidence {
+        fork_evidence_at(key, 42)
+    }
 
-        let claim = resolver.get_claim(&claim_id).unwrap();
-        assert_eq!(claim.status, DisputeStatus::Pending);
-        assert_eq!(claim.claimant, "alice");
-        assert_eq!(claim.accused, "bob");
+    fn fork_evidence_at(key: &PrivateKey, height: u64) -> ForkEvidence {
+        ForkEvidence {
+            validator_key: key.public_key(),
+            header_a: signed_header(key, height, 1),
+            header_b: signed_header(key, height, 2),
+        }
     }
 
-    #[test]
-    fn test_challenge_claim() {
+    /// A resolver with one registered validator
+    fn setup() -> (DisputeResolver, UserId, PrivateKey) {
         let mut resolver = DisputeResolver::new();
+        let validator = UserId::new();
+        let key = PrivateKey::generate();
+        resolver.register_validator(validator.clone(), key.public_key());
+        (resolver, validator, key)
+    }
 
-        let evidence = ForkEvidence {
-            message_a: b"message 1".to_vec(),
-            message_b: b"message 2".to_vec(),
-            signature_a: vec![0; 64],
-            signature_b: vec![0; 64],
-            sequence_number: 42,
-        };
-
-        let claim_id = resolver.submit_claim(
+    fn submit_fork(resolver: &mut DisputeResolver, claimant: &UserId, accused: &UserId, evidence: &ForkEvidence) -> ClaimId {
+        resolver.submit_claim(
             DisputeType::ForkDetected,
-            "alice".to_string(),
-            "bob".to_string(),
-            serde_json::to_vec(&evidence).unwrap(),
-        ).unwrap();
-
-        let counter_evidence = b"counter evidence".to_vec();
-        resolver.challenge_claim(claim_id.clone(), "bob".to_string(), counter_evidence).unwrap();
-
-        let claim = resolver.get_claim(&claim_id).unwrap();
-        assert_eq!(claim.status, DisputeStatus::Challenged);
+            claimant.clone(),
+            accused.clone(),
+            serde_json::to_vec(evidence).unwrap(),
+            100,
+        ).unwrap()
     }
 
     #[test]
-    fn test_respond_to_challenge() {
-        let mut resolver = DisputeResolver::new();
+    fn test_submit_claim() {
+        let (mut resolver, validator, key) = setup();
+        let claimant = UserId::new();
 
-        let evidence = ForkEvidence {
-            message_a: b"message 1".to_vec(),
-            message_b: b"message 2".to_vec(),
-            signature_a: vec![0; 64],
-            signature_b: vec![0; 64],
-            sequence_number: 42,
-        };
+        let claim_id = submit_fork(&mut resolver, &claimant, &validator, &fork_evidence(&key));
 
-        let claim_id = resolver.submit_claim(
+        let claim = resolver.get_claim(&claim_id).unwrap();
+        assert_eq!(claim.status, DisputeStatus::Pending);
+        assert_eq!(claim.claimant, claimant);
+        assert_eq!(claim.accused, validator);
+        assert_eq!(claim.submitted_at_height, 100);
+
+        // Malformed evidence is rejected up front
+        assert!(resolver.submit_claim(
             DisputeType::ForkDetected,
-            "alice".to_string(),
-            "bob".to_string(),
-            serde_json::to_vec(&evidence).unwrap(),
-        ).unwrap();
+            claimant,
+            validator,
+            b"not evidence".to_vec(),
+            100,
+        ).is_err());
+    }
 
-        resolver.challenge_claim(claim_id.clone(), "bob".to_string(), b"counter".to_vec()).unwrap();
-        resolver.respond_to_challenge(claim_id.clone(), "alice".to_string(), b"response".to_vec()).unwrap();
+    #[test]
+    fn test_challenge_and_response_windows() {
+        let (mut resolver, validator, key) = setup();
+        let claimant = UserId::new();
+        let window = DisputeConfig::default().challenge_window_blocks;
+        let response_window = DisputeConfig::default().response_window_blocks;
+
+        // Too late to challenge
+        let late = submit_fork(&mut resolver, &claimant, &validator, &fork_evidence(&key));
+        assert!(resolver.challenge_claim(late.clone(), validator.clone(), b"counter".to_vec(), 101 + window).is_err());
+
+        let claim_id = submit_fork(&mut resolver, &claimant, &validator, &fork_evidence_at(&key, 43));
+        // Only the accused may challenge, only the claimant may respond
+        assert!(resolver.challenge_claim(claim_id.clone(), claimant.clone(), b"counter".to_vec(), 150).is_err());
+        resolver.challenge_claim(claim_id.clone(), validator.clone(), b"counter".to_vec(), 150).unwrap();
+        assert_eq!(resolver.get_claim(&claim_id).unwrap().status, DisputeStatus::Challenged);
+        assert!(resolver.respond_to_challenge(claim_id.clone(), validator.clone(), b"response".to_vec(), 160).is_err());
+
+        // Too late to respond
+        assert!(resolver.respond_to_challenge(claim_id.clone(), claimant.clone(), b"response".to_vec(), 151 + response_window).is_err());
+        resolver.respond_to_challenge(claim_id.clone(), claimant, b"response".to_vec(), 150 + response_window).unwrap();
 
         let claim = resolver.get_claim(&claim_id).unwrap();
         assert_eq!(claim.status, DisputeStatus::Responded);
+        assert_eq!(resolver.get_challenges(&claim_id)[0].height, 150);
     }
 
     #[test]
     fn test_verify_fork_evidence() {
         let resolver = DisputeResolver::new();
+        let key = PrivateKey::generate();
 
-        let evidence = ForkEvidence {
-            message_a: b"message 1".to_vec(),
-            message_b: b"message 2".to_vec(),
-            signature_a: vec![0; 64],
-            signature_b: vec![0; 64],
-            sequence_number: 42,
-        };
-
-        let valid = resolver.verify_fork_evidence(&evidence).unwrap();
+        let valid = resolver.verify_fork_evidence(&fork_evidence(&key)).unwrap();
         assert!(valid);
 
-        // Same message should be invalid fork
-        let invalid_evidence = ForkEvidence {
-            message_a: b"message 1".to_vec(),
-            message_b: b"message 1".to_vec(),
-            signature_a: vec![0; 64],
-            signature_b: vec![0; 64],
-            sequence_number: 42,
+        // Same header twice is not a fork
+        let same = ForkEvidence {
+            header_b: signed_header(&key, 42, 1),
+            ..fork_evidence(&key)
+        };
+        assert!(!resolver.verify_fork_evidence(&same).unwrap());
+
+        // Different heights are not a fork
+        let other_height = ForkEvidence {
+            header_b: signed_header(&key, 43, 2),
+            ..fork_evidence(&key)
         };
+        assert!(!resolver.verify_fork_evidence(&other_height).unwrap());
 
-        let valid = resolver.verify_fork_evidence(&invalid_evidence).unwrap();
-        assert!(!valid);
+        // Headers signed by someone else do not implicate the validator
+        let forged = ForkEvidence {
+            header_b: signed_header(&PrivateKey::generate(), 42, 2),
+            ..fork_evidence(&key)
+        };
+        assert!(!resolver.verify_fork_evidence(&forged).unwrap());
     }
 
     #[test]
     fn test_verify_integrity_evidence() {
         let resolver = DisputeResolver::new();
+        let key = PrivateKey::generate();
 
         let message = b"test message";
         let mut hasher = Hasher::new();
         hasher.update(message);
         let actual_hash = hasher.finalize().as_bytes().to_vec();
+        let claimed_hash = vec![0; 32]; // Wrong hash
 
+        let commitment = IntegrityEvidence::commitment_bytes(message, &claimed_hash);
         let evidence = IntegrityEvidence {
+            validator_key: key.public_key(),
             message: message.to_vec(),
-            claimed_hash: vec![0; 32], // Wrong hash
+            claimed_hash,
             actual_hash: actual_hash.clone(),
-            signature: vec![0; 64],
+            signature: signatures::sign(&key, &commitment).to_bytes().to_vec(),
         };
 
         let valid = resolver.verify_integrity_evidence(&evidence).unwrap();
         assert!(valid);
+
+        // The commitment must actually be signed
+        let unsigned = IntegrityEvidence {
+            signature: vec![0; 64],
+            ..evidence
+        };
+        assert!(!resolver.verify_integrity_evidence(&unsigned).unwrap());
     }
 
     #[test]
-    fn test_resolve_dispute_for_claimant() {
-        let mut resolver = DisputeResolver::new();
+    fn test_proven_equivocation_slashes_and_claims_insurance() {
+        let (mut resolver, validator, key) = setup();
+        let claimant = UserId::new();
+        let slashing = SlashingManager::new();
+        let mut insurance = InsuranceFund::new(1_000_000, FundConfiguration::default());
+        let window = DisputeConfig::default().challenge_window_blocks;
+
+        let claim_id = submit_fork(&mut resolver, &claimant, &validator, &fork_evidence(&key));
+
+        // The accused gets the challenge window to see the claim
+        assert!(resolver.resolve_dispute(claim_id.clone(), 100 + window, &slashing, &mut insurance).is_err());
+
+        let outcome = resolver.resolve_dispute(claim_id.clone(), 101 + window, &slashing, &mut insurance).unwrap();
+        assert_eq!(outcome.status, DisputeStatus::ResolvedForClaimant);
+        assert_eq!(outcome.slashed, DisputeConfig::default().slash_amount);
+        assert_eq!(slashing.get_slashed_amount(&validator), DisputeConfig::default().slash_amount);
+        assert_eq!(slashing.get_slash_count_by_reason(&SlashReason::DoubleSigning), 1);
+
+        let insurance_claim = insurance.get_claim(&outcome.insurance_claim.unwrap()).unwrap();
+        assert_eq!(insurance_claim.claimant, claimant);
+        assert_eq!(insurance_claim.requested_amount, DisputeConfig::default().compensation_amount);
+
+        // Resolved claims cannot be resolved again
+        assert!(resolver.resolve_dispute(claim_id, 200 + window, &slashing, &mut insurance).is_err());
+    }
 
-        let evidence = ForkEvidence {
-            message_a: b"message 1".to_vec(),
-            message_b: b"message 2".to_vec(),
-            signature_a: vec![0; 64],
-            signature_b: vec![0; 64],
-            sequence_number: 42,
+    #[test]
+    fn test_evidence_backs_one_claim() {
+        let (mut resolver, validator, key) = setup();
+        let claimant = UserId::new();
+        let evidence = fork_evidence(&key);
+        submit_fork(&mut resolver, &claimant, &validator, &evidence);
+
+        let resubmit = |resolver: &mut DisputeResolver, claimant: &UserId, evidence: &ForkEvidence| {
+            resolver.submit_claim(
+                DisputeType::ForkDetected,
+                claimant.clone(),
+                validator.clone(),
+                serde_json::to_vec(evidence).unwrap(),
+                200,
+            )
         };
 
-        let claim_id = resolver.submit_claim(
-            DisputeType::ForkDetected,
-            "alice".to_string(),
-            "bob".to_string(),
-            serde_json::to_vec(&evidence).unwrap(),
-        ).unwrap();
+        // The same evidence, from anyone
+        assert!(resubmit(&mut resolver, &claimant, &evidence).is_err());
+        assert!(resubmit(&mut resolver, &UserId::new(), &evidence).is_err());
+
+        // A different pair of headers for the same equivocation
+        let same_height = ForkEvidence {
+            header_b: signed_header(&key, 42, 3),
+            ..evidence.clone()
+        };
+        assert!(resubmit(&mut resolver, &claimant, &same_height).is_err());
 
-        resolver.challenge_claim(claim_id.clone(), "bob".to_string(), b"counter".to_vec()).unwrap();
-        resolver.respond_to_challenge(claim_id.clone(), "alice".to_string(), b"response".to_vec()).unwrap();
-        resolver.submit_to_vote(claim_id.clone()).unwrap();
-        resolver.resolve_dispute(claim_id.clone(), 0.8).unwrap(); // 80% vote for claimant
+        // Another height is another offence
+        assert!(resubmit(&mut resolver, &claimant, &fork_evidence_at(&key, 43)).is_ok());
+    }
 
-        let claim = resolver.get_claim(&claim_id).unwrap();
-        assert_eq!(claim.status, DisputeStatus::ResolvedForClaimant);
+    #[test]
+    fn test_unproven_fork_resolves_for_accused() {
+        let (mut resolver, validator, _) = setup();
+        let slashing = SlashingManager::new();
+        let mut insurance = InsuranceFund::new(1_000_000, FundConfiguration::default());
+
+        // Valid fork evidence, but from a different validator's key
+        let other = PrivateKey::generate();
+        let claim_id = submit_fork(&mut resolver, &UserId::new(), &validator, &fork_evidence(&other));
+        resolver.challenge_claim(claim_id.clone(), validator.clone(), b"not my key".to_vec(), 120).unwrap();
+
+        // A challenged fraud proof resolves without waiting for a response
+        let outcome = resolver.resolve_dispute(claim_id, 121, &slashing, &mut insurance).unwrap();
+        assert_eq!(outcome.status, DisputeStatus::ResolvedForAccused);
+        assert!(!slashing.is_slashed(&validator));
+        assert!(insurance.get_pending_claims().is_empty());
     }
 
     #[test]
-    fn test_resolve_dispute_for_accused() {
+    fn test_optimistic_claims() {
         let mut resolver = DisputeResolver::new();
-
-        let evidence = ForkEvidence {
-            message_a: b"message 1".to_vec(),
-            message_b: b"message 2".to_vec(),
-            signature_a: vec![0; 64],
-            signature_b: vec![0; 64],
-            sequence_number: 42,
+        let slashing = SlashingManager::new();
+        let mut insurance = InsuranceFund::new(1_000_000, FundConfiguration::default());
+        let claimant = UserId::new();
+        let accused = UserId::new();
+        let config = DisputeConfig::default();
+        let after_windows = 100 + config.challenge_window_blocks + config.response_window_blocks + 1;
+
+        let submit = |resolver: &mut DisputeResolver, evidence: &[u8]| {
+            resolver.submit_claim(
+                DisputeType::DoubleSpend,
+                claimant.clone(),
+                accused.clone(),
+                evidence.to_vec(),
+                100,
+            ).unwrap()
         };
 
-        let claim_id = resolver.submit_claim(
-            DisputeType::ForkDetected,
-            "alice".to_string(),
-            "bob".to_string(),
-            serde_json::to_vec(&evidence).unwrap(),
-        ).unwrap();
-
-        resolver.challenge_claim(claim_id.clone(), "bob".to_string(), b"counter".to_vec()).unwrap();
-        resolver.respond_to_challenge(claim_id.clone(), "alice".to_string(), b"response".to_vec()).unwrap();
-        resolver.submit_to_vote(claim_id.clone()).unwrap();
-        resolver.resolve_dispute(claim_id.clone(), 0.2).unwrap(); // 20% vote for claimant
-
-        let claim = resolver.get_claim(&claim_id).unwrap();
-        assert_eq!(claim.status, DisputeStatus::ResolvedForAccused);
+        // Unverifiable evidence is never slashed on silence; it waits for a vote
+        let unchallenged = submit(&mut resolver, b"double spend 1");
+        let outcome = resolver.resolve_dispute(unchallenged.clone(), after_windows, &slashing, &mut insurance).unwrap();
+        assert_eq!(outcome.status, DisputeStatus::UnderVote);
+        assert!(!slashing.is_slashed(&accused));
+        let outcome = resolver.resolve_by_vote(unchallenged, 0.8, &slashing, &mut insurance).unwrap();
+        assert_eq!(outcome.status, DisputeStatus::ResolvedForClaimant);
+
+        // Unanswered challenges win, but not before the response window closes
+        let unanswered = submit(&mut resolver, b"double spend 2");
+        resolver.challenge_claim(unanswered.clone(), accused.clone(), b"counter".to_vec(), 100).unwrap();
+        assert!(resolver.resolve_dispute(unanswered.clone(), 100 + config.response_window_blocks, &slashing, &mut insurance).is_err());
+        let outcome = resolver.resolve_dispute(unanswered, after_windows, &slashing, &mut insurance).unwrap();
+        assert_eq!(outcome.status, DisputeStatus::ResolvedForAccused);
+
+        // Contested claims go to a vote
+        let contested = submit(&mut resolver, b"double spend 3");
+        resolver.challenge_claim(contested.clone(), accused.clone(), b"counter".to_vec(), 100).unwrap();
+        resolver.respond_to_challenge(contested.clone(), claimant.clone(), b"response".to_vec(), 110).unwrap();
+        let outcome = resolver.resolve_dispute(contested.clone(), 110, &slashing, &mut insurance).unwrap();
+        assert_eq!(outcome.status, DisputeStatus::UnderVote);
+
+        let outcome = resolver.resolve_by_vote(contested, 0.2, &slashing, &mut insurance).unwrap(); // 20% vote for claimant
+        assert_eq!(outcome.status, DisputeStatus::ResolvedForAccused);
+        assert_eq!(slashing.get_slashed_amount(&accused), config.slash_amount);
     }
 
     #[test]
     fn test_dispute_stats() {
-        let mut resolver = DisputeResolver::new();
+        let (mut resolver, validator, key) = setup();
 
-        let evidence = ForkEvidence {
-            message_a: b"message 1".to_vec(),
-            message_b: b"message 2".to_vec(),
-            signature_a: vec![0; 64],
-            signature_b: vec![0; 64],
-            sequence_number: 42,
-        };
-
-        resolver.submit_claim(
-            DisputeType::ForkDetected,
-            "alice".to_string(),
-            "bob".to_string(),
-            serde_json::to_vec(&evidence).unwrap(),
-        ).unwrap();
+        submit_fork(&mut resolver, &UserId::new(), &validator, &fork_evidence(&key));
 
         let stats = resolver.get_stats();
         assert_eq!(stats.total_claims, 1);
@@ -7,14 +7,94 @@
 //! - Emergency situations requiring compensation
 //!
 //! The fund is managed by governance and automatically replenishes from fees.
+//! Premiums are a share of message, bridge and block fees, claims are raised
+//! automatically for bridge events that cost users funds, and approved
+//! payouts are transfers from the fund's account on the currency chain.
+//! Payouts pause whenever the fund falls below its reserve requirements.
+//!
+//! Bridge events come from any bridge implementing `BridgeEvents`: the
+//! bridge manager here, or the currency chain's cross-chain bridge.
 
+use dchat_bridge::slashing::{SlashEvent, SlashReason};
+use dchat_bridge::{BridgeManager, BridgeTransaction, BridgeTransactionStatus};
 use dchat_core::types::UserId;
 use dchat_core::{Error, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use uuid::Uuid;
 
+/// Token ledger the fund keeps its reserves on
+///
+/// Implemented by the currency chain client; defined here so this crate
+/// does not depend on the client.
+pub trait FundLedger {
+    /// Balance of an account
+    fn balance(&self, account: &UserId) -> Result<u64>;
+
+    /// Move tokens between accounts, returning the ledger transaction ID
+    fn transfer(&self, from: &UserId, to: &UserId, amount: u64) -> Result<Uuid>;
+}
+
+/// Transaction a premium was collected from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PremiumSource {
+    /// Fee paid to send a message
+    Message { message_id: String },
+
+    /// Fee paid for a bridge transfer
+    Bridge { transaction_id: Uuid },
+
+    /// Fees collected over a currency chain block
+    Block { height: u64 },
+}
+
+/// Bridge transaction that cost its initiator `amount`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeLoss {
+    pub transaction_id: Uuid,
+    pub initiator: UserId,
+    pub amount: u64,
+}
+
+/// Bridge whose failures the fund compensates
+pub trait BridgeEvents {
+    /// Every slash event so far, oldest first
+    fn slash_events(&self) -> Vec<SlashEvent>;
+
+    /// A bridge transaction, as what its initiator stands to lose
+    fn transaction_loss(&self, transaction_id: Uuid) -> Option<BridgeLoss>;
+
+    /// Rolled-back transactions, oldest first
+    fn rolled_back(&self) -> Vec<BridgeLoss>;
+}
+
+fn bridge_loss(tx: &BridgeTransaction) -> BridgeLoss {
+    BridgeLoss {
+        transaction_id: tx.id,
+        initiator: tx.initiator.clone(),
+        amount: tx.amount,
+    }
+}
+
+impl BridgeEvents for BridgeManager {
+    fn slash_events(&self) -> Vec<SlashEvent> {
+        self.slashing.get_all_slashes()
+    }
+
+    fn transaction_loss(&self, transaction_id: Uuid) -> Option<BridgeLoss> {
+        self.get_transaction(transaction_id).map(bridge_loss)
+    }
+
+    fn rolled_back(&self) -> Vec<BridgeLoss> {
+        let mut rolled_back: Vec<_> = self.transactions()
+            .filter(|tx| tx.status == BridgeTransactionStatus::RolledBack)
+            .collect();
+        rolled_back.sort_by_key(|tx| tx.initiated_at);
+        rolled_back.into_iter().map(bridge_loss).collect()
+    }
+}
+
 /// Insurance claim type
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ClaimType {
@@ -44,6 +124,18 @@ pub enum ClaimType {
         affected_users: Vec<UserId>,
         reason: String,
     },
+
+    /// A bridge validator was slashed over a user's transaction
+    BridgeValidatorSlashed {
+        validator_id: UserId,
+        reason: SlashReason,
+        transaction_id: Uuid,
+    },
+
+    /// A bridge transaction failed and was rolled back
+    BridgeRollback {
+        transaction_id: Uuid,
+    },
 }
 
 /// Insurance claim status
@@ -141,9 +233,18 @@ pub struct FundConfiguration {
     
     /// Maximum claim processing time (seconds)
     pub max_processing_time_secs: u64,
+
+    /// Minimum balance per token of approved but unpaid claims
+    #[serde(default = "default_min_reserve_ratio")]
+    pub min_reserve_ratio: f64,
+}
+
+fn default_min_reserve_ratio() -> f64 {
+    1.0
 }
 
 /// Insurance fund manager
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InsuranceFund {
     /// Current fund balance
     balance: u64,
@@ -156,6 +257,18 @@ pub struct InsuranceFund {
     
     /// Transaction history (deposits and withdrawals)
     transactions: Vec<FundTransaction>,
+
+    /// Account holding the fund's tokens on the currency chain
+    account: Option<UserId>,
+
+    /// Transactions premiums have been collected from
+    premium_sources: BTreeSet<PremiumSource>,
+
+    /// Bridge transactions that already have an automatic claim
+    claimed_bridge_transactions: BTreeSet<Uuid>,
+
+    /// Bridge slash events already scanned
+    slash_events_seen: usize,
 }
 
 /// Fund transaction record
@@ -194,6 +307,12 @@ pub enum TransactionType {
     
     /// Emergency withdrawal
     EmergencyWithdrawal,
+
+    /// Premium from a message fee
+    MessagePremium,
+
+    /// Premium from a bridge fee
+    BridgePremium,
 }
 
 impl InsuranceFund {
@@ -204,8 +323,26 @@ impl InsuranceFund {
             claims: HashMap::new(),
             config,
             transactions: Vec::new(),
+            account: None,
+            premium_sources: BTreeSet::new(),
+            claimed_bridge_transactions: BTreeSet::new(),
+            slash_events_seen: 0,
         }
     }
+
+    /// Create a fund backed by `account` on a token ledger
+    ///
+    /// The opening balance is the account's balance on the ledger.
+    pub fn with_account(account: UserId, config: FundConfiguration, ledger: &dyn FundLedger) -> Result<Self> {
+        let mut fund = Self::new(ledger.balance(&account)?, config);
+        fund.account = Some(account);
+        Ok(fund)
+    }
+
+    /// Account holding the fund's tokens, if ledger-backed
+    pub fn account(&self) -> Option<&UserId> {
+        self.account.as_ref()
+    }
     
     /// Get current balance
     pub fn balance(&self) -> u64 {
@@ -292,7 +429,7 @@ impl InsuranceFund {
     
     /// Pay out an approved claim
     pub fn payout_claim(&mut self, claim_id: Uuid, tx_hash: String) -> Result<()> {
-        let claim = self.claims.get_mut(&claim_id)
+        let claim = self.claims.get(&claim_id)
             .ok_or_else(|| Error::validation("Claim not found"))?;
         
         let amount = match &claim.status {
@@ -304,10 +441,16 @@ impl InsuranceFund {
             return Err(Error::validation("Insufficient fund balance"));
         }
         
+        if !self.can_pay_out(amount) {
+            return Err(Error::validation("Payouts paused: fund is below its reserve requirements"));
+        }
+        
         // Deduct from balance
         self.balance -= amount;
         
         // Update claim status
+        let claim = self.claims.get_mut(&claim_id)
+            .ok_or_else(|| Error::validation("Claim not found"))?;
         claim.status = ClaimStatus::Paid {
             amount,
             transaction_hash: tx_hash.clone(),
@@ -327,6 +470,119 @@ impl InsuranceFund {
         Ok(())
     }
     
+    /// Pay out an approved claim as a transfer on the currency chain
+    pub fn execute_payout(&mut self, claim_id: Uuid, ledger: &dyn FundLedger) -> Result<Uuid> {
+        let account = self.account.clone()
+            .ok_or_else(|| Error::validation("Fund has no currency chain account"))?;
+        let claim = self.claims.get(&claim_id)
+            .ok_or_else(|| Error::validation("Claim not found"))?;
+        
+        let amount = match &claim.status {
+            ClaimStatus::Approved { amount } => *amount,
+            _ => return Err(Error::validation("Claim is not approved")),
+        };
+        
+        if !self.can_pay_out(amount) {
+            return Err(Error::validation("Payouts paused: fund is below its reserve requirements"));
+        }
+        
+        let tx_id = ledger.transfer(&account, &claim.claimant, amount)?;
+        self.payout_claim(claim_id, tx_id.to_string())?;
+        
+        Ok(tx_id)
+    }
+    
+    /// Collect the fund's share of a transaction fee
+    ///
+    /// The share is transferred from `payer` to the fund's account. Each
+    /// source is charged once. Returns the amount the fund received.
+    pub fn collect_premium(
+        &mut self,
+        ledger: &dyn FundLedger,
+        payer: &UserId,
+        fee: u64,
+        source: PremiumSource,
+    ) -> Result<u64> {
+        let account = self.account.clone()
+            .ok_or_else(|| Error::validation("Fund has no currency chain account"))?;
+        
+        if self.premium_sources.contains(&source) {
+            return Err(Error::validation("Premium already collected for this transaction"));
+        }
+        
+        let share = fee * self.config.fee_allocation_percent as u64 / 100;
+        if share == 0 {
+            return Ok(0);
+        }
+        
+        // Credit what arrived, which may be less than the share if the
+        // ledger burns part of every transfer
+        let before = ledger.balance(&account)?;
+        let tx_id = ledger.transfer(payer, &account, share)?;
+        let received = ledger.balance(&account)?.saturating_sub(before);
+        
+        let transaction_type = match source {
+            PremiumSource::Message { .. } => TransactionType::MessagePremium,
+            PremiumSource::Bridge { .. } => TransactionType::BridgePremium,
+            PremiumSource::Block { .. } => TransactionType::FeeDeposit,
+        };
+        self.premium_sources.insert(source);
+        self.deposit(received, transaction_type, tx_id.to_string());
+        
+        Ok(received)
+    }
+    
+    /// Raise claims for bridge events that cost users funds
+    ///
+    /// A slash event tied to a bridge transaction, or a rolled-back bridge
+    /// transaction, raises a claim for the transaction's initiator. Each
+    /// transaction gets at most one claim, capped at the fund's balance.
+    /// Returns the new claims.
+    pub fn sync_bridge_events(&mut self, bridge: &dyn BridgeEvents) -> Result<Vec<Uuid>> {
+        let mut raised = Vec::new();
+        
+        let slashes = bridge.slash_events();
+        for event in &slashes[self.slash_events_seen.min(slashes.len())..] {
+            let Some(loss) = event.transaction_id.and_then(|id| bridge.transaction_loss(id)) else {
+                continue;
+            };
+            let claim_type = ClaimType::BridgeValidatorSlashed {
+                validator_id: event.validator_id.clone(),
+                reason: event.reason.clone(),
+                transaction_id: loss.transaction_id,
+            };
+            if let Some(claim_id) = self.raise_bridge_claim(loss, claim_type)? {
+                raised.push(claim_id);
+            }
+        }
+        self.slash_events_seen = slashes.len();
+        
+        for loss in bridge.rolled_back() {
+            let claim_type = ClaimType::BridgeRollback { transaction_id: loss.transaction_id };
+            if let Some(claim_id) = self.raise_bridge_claim(loss, claim_type)? {
+                raised.push(claim_id);
+            }
+        }
+        
+        Ok(raised)
+    }
+    
+    fn raise_bridge_claim(&mut self, loss: BridgeLoss, claim_type: ClaimType) -> Result<Option<Uuid>> {
+        let amount = loss.amount.min(self.balance);
+        if amount == 0 || self.claimed_bridge_transactions.contains(&loss.transaction_id) {
+            return Ok(None);
+        }
+        
+        let claim_id = self.submit_claim(
+            loss.initiator,
+            claim_type,
+            amount,
+            vec![format!("bridge_tx:{}", loss.transaction_id)],
+        )?;
+        self.claimed_bridge_transactions.insert(loss.transaction_id);
+        Ok(Some(claim_id))
+    }
+    
     /// Reject a claim
     pub fn reject_claim(&mut self, claim_id: Uuid, reason: String) -> Result<()> {
         let claim = self.claims.get_mut(&claim_id)
@@ -365,6 +621,14 @@ impl InsuranceFund {
             .collect()
     }
     
+    /// Get claims approved but not yet paid
+    pub fn get_approved_claims(&self) -> Vec<&InsuranceClaim> {
+        self.claims
+            .values()
+            .filter(|c| matches!(c.status, ClaimStatus::Approved { .. }))
+            .collect()
+    }
+    
     /// Get statistics
     pub fn get_statistics(&self) -> FundStatistics {
         let total_claims = self.claims.len() as u64;
@@ -406,9 +670,34 @@ impl InsuranceFund {
         }
     }
     
+    /// Tokens owed on approved but unpaid claims
+    pub fn outstanding_liabilities(&self) -> u64 {
+        self.claims
+            .values()
+            .filter_map(|c| match c.status {
+                ClaimStatus::Approved { amount } => Some(amount),
+                _ => None,
+            })
+            .sum()
+    }
+    
+    /// Balance per token of outstanding liabilities
+    pub fn reserve_ratio(&self) -> f64 {
+        match self.outstanding_liabilities() {
+            0 => f64::INFINITY,
+            owed => self.balance as f64 / owed as f64,
+        }
+    }
+    
     /// Check fund health
     pub fn is_healthy(&self) -> bool {
         self.balance >= self.config.minimum_balance
+            && self.reserve_ratio() >= self.config.min_reserve_ratio
+    }
+    
+    /// Whether paying `amount` keeps the fund within its reserve requirements
+    fn can_pay_out(&self, amount: u64) -> bool {
+        self.is_healthy() && self.balance.saturating_sub(amount) >= self.config.minimum_balance
     }
 }
 
@@ -420,6 +709,7 @@ impl Default for FundConfiguration {
             fee_allocation_percent: 10, // 10% of fees
             min_votes_for_approval: 3,
             max_processing_time_secs: 7 * 24 * 3600, // 7 days
+            min_reserve_ratio: default_min_reserve_ratio(),
         }
     }
 }
@@ -596,4 +886,97 @@ mod tests {
         let fund2 = InsuranceFund::new(50_000, config);
         assert!(!fund2.is_healthy());
     }
+    
+    #[test]
+    fn test_payouts_pause_below_reserve() {
+        let config = FundConfiguration {
+            minimum_balance: 100_000,
+            min_votes_for_approval: 1,
+            min_reserve_ratio: 1.5,
+            ..Default::default()
+        };
+        let mut fund = InsuranceFund::new(250_000, config);
+        
+        let approve = |fund: &mut InsuranceFund, amount| {
+            let claim_id = fund.submit_claim(
+                create_test_user(),
+                ClaimType::SlashingOverflow { node_id: create_test_user(), deficit_amount: amount },
+                amount,
+                vec![],
+            ).unwrap();
+            fund.vote_on_claim(claim_id, create_test_user(), true).unwrap();
+            fund.approve_claim(claim_id).unwrap();
+            claim_id
+        };
+        
+        // Paying this would leave less than the minimum balance
+        let large = approve(&mut fund, 160_000);
+        assert!(fund.payout_claim(large, "tx_large".to_string()).is_err());
+        fund.reject_claim(large, "Over reserve".to_string()).unwrap();
+        
+        // Approved claims beyond the reserve ratio pause every payout
+        let first = approve(&mut fund, 90_000);
+        let second = approve(&mut fund, 90_000);
+        assert_eq!(fund.outstanding_liabilities(), 180_000);
+        assert!(!fund.is_healthy());
+        assert!(fund.payout_claim(first, "tx_first".to_string()).is_err());
+        
+        // Recapitalising resumes payouts
+        fund.deposit(50_000, TransactionType::GovernanceDeposit, "tx_top_up".to_string());
+        assert!(fund.is_healthy());
+        fund.payout_claim(first, "tx_first".to_string()).unwrap();
+        fund.payout_claim(second, "tx_second".to_string()).unwrap();
+        assert_eq!(fund.balance(), 120_000);
+    }
+    
+    #[test]
+    fn test_automatic_bridge_claims() {
+        use dchat_bridge::ChainId;
+        
+        let mut fund = InsuranceFund::new(1_000_000, FundConfiguration::default());
+        let mut bridge = BridgeManager::new();
+        let user = create_test_user();
+        let validator = create_test_user();
+        
+        let initiate = |bridge: &mut BridgeManager, amount| {
+            bridge.initiate_transaction(
+                ChainId::ChatChain,
+                ChainId::CurrencyChain,
+                user.clone(),
+                format!("src_{}", amount),
+                amount,
+                3600,
+            ).unwrap()
+        };
+        let slashed_tx = initiate(&mut bridge, 4_000);
+        let rolled_back_tx = initiate(&mut bridge, 7_000);
+        initiate(&mut bridge, 9_000);
+        
+        bridge.slashing.slash_validator(
+            validator.clone(),
+            SlashReason::InvalidSignature,
+            1_000,
+            Some(slashed_tx),
+            vec![],
+            None,
+        ).unwrap();
+        // Slashes with no transaction have no one to compensate
+        bridge.slashing.slash_validator(validator, SlashReason::ExtendedDowntime, 500, None, vec![], None).unwrap();
+        bridge.rollback_transaction(rolled_back_tx).unwrap();
+        // The slashed transaction was rolled back too, but is claimed once
+        bridge.rollback_transaction(slashed_tx).unwrap();
+        
+        let raised = fund.sync_bridge_events(&bridge).unwrap();
+        assert_eq!(raised.len(), 2);
+        let amounts: Vec<u64> = raised.iter().map(|id| fund.get_claim(id).unwrap().requested_amount).collect();
+        assert_eq!(amounts, vec![4_000, 7_000]);
+        assert!(matches!(
+            fund.get_claim(&raised[0]).unwrap().claim_type,
+            ClaimType::BridgeValidatorSlashed { transaction_id, .. } if transaction_id == slashed_tx
+        ));
+        assert!(raised.iter().all(|id| fund.get_claim(id).unwrap().claimant == user));
+        
+        // Nothing new on a second pass
+        assert!(fund.sync_bridge_events(&bridge).unwrap().is_empty());
+    }
 }
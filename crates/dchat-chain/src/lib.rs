@@ -2,16 +2,18 @@
 //!
 //! This crate provides on-chain functionality including:
 //! - On-chain transaction types for user operations
-//! - Channel sharding and state partitioning
-//! - Cryptographic dispute resolution
+//! - Channel sharding with cross-shard Merkle receipts
+//! - Fraud-proof dispute resolution with automatic slashing
 //! - Fork arbitration and consensus recovery
 //! - Message consensus pruning with Merkle checkpoints
-//! - Insurance fund for economic security
+//! - Domain-separated Merkle trees and inclusion proofs (from `dchat-crypto`)
+//! - Insurance fund with fee premiums and automatic bridge claims
 
 pub mod transactions;
 pub mod sharding;
 pub mod dispute_resolution;
 pub mod pruning;
+pub use dchat_crypto::merkle;
 pub mod insurance_fund;
 
 pub use transactions::{
@@ -19,10 +21,22 @@ pub use transactions::{
     RegisterUserTx, SendDirectMessageTx, CreateChannelTx, PostToChannelTx,
     JoinChannelTx, ChannelVisibility,
 };
-pub use sharding::{ShardManager, ShardId, ShardConfig};
-pub use dispute_resolution::{DisputeResolver, DisputeClaim, DisputeStatus};
-pub use pruning::{PruningManager, PruningPolicy, MerkleCheckpoint, MerkleProof, NodeType};
+pub use sharding::{
+    ShardManager, ShardId, ShardConfig, ChannelId, ShardMessage, CrossShardMessage, ShardHeader,
+    ShardReceipt, ShardCommittee, CommitteeKey, CommitteeMember, CommitteeVote, CommitteeCertificate,
+    ChannelMigration,
+};
+pub use dispute_resolution::{
+    DisputeResolver, DisputeClaim, DisputeStatus, DisputeType, DisputeConfig, DisputeOutcome,
+    ClaimId, BlockHeader, SignedBlockHeader, ForkEvidence, IntegrityEvidence,
+};
+pub use pruning::{
+    PruningManager, PruningPolicy, MerkleCheckpoint, MerkleProof, NonInclusionProof,
+    CheckpointRecord, NodeType,
+};
+pub use merkle::{MerkleTree, MerklePath};
 pub use insurance_fund::{
     InsuranceFund, InsuranceClaim, ClaimType, ClaimStatus, FundConfiguration,
-    FundStatistics, FundTransaction, TransactionType as FundTransactionType,
+    FundStatistics, FundTransaction, TransactionType as FundTransactionType, FundLedger,
+    PremiumSource, BridgeEvents, BridgeLoss,
 };
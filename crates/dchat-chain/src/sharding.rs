@@ -1,22 +1,49 @@
 //! Channel-scoped sharding for horizontal scalability
 //!
 //! Implements Section 17 (Scalability via Sharding) from ARCHITECTURE.md
-//! - Channel-based state partitioning
-//! - Cross-shard message routing
+//! - Channel-based state partitioning on a consistent-hash ring
+//! - Cross-shard message routing with Merkle receipts
+//! - Committee-certified shard state roots
+//! - Load-based rebalancing with verifiable channel migration
 //! - Light client support
-//! - BLS signature aggregation for efficiency
+//!
+//! Every shard keeps an append-only log of the entries it has applied
+//! (deliveries, outbound cross-shard messages, channel migrations). The
+//! shard's state root is the Merkle root of that log, and every root a shard
+//! has committed is kept so receipts stay checkable after the log grows.
+//!
+//! Committee members sign shard headers with BLS12-381 keys (minimal public
+//! key size, signatures in G2). A certificate is the signer bitmap plus one
+//! aggregate signature, checked with a single fast-aggregate verification.
+//! Every member key carries a proof of possession, checked when the committee
+//! is set, so a member cannot pick a rogue key that cancels out the others.
 
+use crate::merkle::{empty_root, leaf_hash, MerklePath, MerkleTree};
 use dchat_core::error::{Error, Result};
+use blst::min_pk::{AggregateSignature, PublicKey as BlsPublicKey, SecretKey as BlsSecretKey, Signature as BlsSignature};
+use blst::BLST_ERROR;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use blake3::Hasher;
 
+/// Virtual nodes each shard places on the hash ring
+const VIRTUAL_NODES_PER_SHARD: u32 = 64;
+
+/// Domain separator for shard headers signed by committees
+const HEADER_DOMAIN: &[u8] = b"dchat-shard-header-v1";
+
+/// Hash-to-curve tag for committee signatures (proof-of-possession scheme)
+const COMMITTEE_SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Hash-to-curve tag for proofs of possession
+const COMMITTEE_POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
 /// Shard identifier
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ShardId(pub u32);
 
 /// Channel identifier
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ChannelId(pub String);
 
 /// Shard assignment for a channel
@@ -37,6 +64,17 @@ pub struct ShardState {
     pub last_updated: i64,
 }
 
+/// A message stored in a channel on its shard
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardMessage {
+    pub id: String,
+    pub from_shard: ShardId,
+    pub from_channel: ChannelId,
+    pub to_channel: ChannelId,
+    pub payload: Vec<u8>,
+    pub timestamp: i64,
+}
+
 /// Cross-shard message
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CrossShardMessage {
@@ -47,7 +85,169 @@ pub struct CrossShardMessage {
     pub to_channel: ChannelId,
     pub payload: Vec<u8>,
     pub timestamp: i64,
-    pub proof: Vec<u8>,
+    /// Proof that the source shard logged this message
+    pub receipt: ShardReceipt,
+}
+
+impl CrossShardMessage {
+    /// Leaf hash of this message in the source shard's log
+    pub fn leaf(&self) -> [u8; 32] {
+        LogEntry::Outbound {
+            id: &self.id,
+            from_shard: &self.from_shard,
+            to_shard: &self.to_shard,
+            from_channel: &self.from_channel,
+            to_channel: &self.to_channel,
+            payload: &self.payload,
+            timestamp: self.timestamp,
+        }
+        .leaf()
+    }
+}
+
+/// A shard's committed state: what its committee signs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardHeader {
+    pub shard_id: ShardId,
+    /// Number of log entries covered by the root
+    pub height: u64,
+    pub state_root: [u8; 32],
+}
+
+impl ShardHeader {
+    /// Bytes signed by committee members
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = HEADER_DOMAIN.to_vec();
+        bytes.extend_from_slice(&self.shard_id.0.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&self.state_root);
+        bytes
+    }
+}
+
+/// Merkle receipt proving a log entry under a committed shard root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardReceipt {
+    pub shard_id: ShardId,
+    pub height: u64,
+    pub state_root: [u8; 32],
+    pub path: MerklePath,
+}
+
+impl ShardReceipt {
+    /// Header this receipt is anchored to
+    pub fn header(&self) -> ShardHeader {
+        ShardHeader {
+            shard_id: self.shard_id.clone(),
+            height: self.height,
+            state_root: self.state_root,
+        }
+    }
+
+    /// Check that `leaf` is in the log under this receipt's root
+    pub fn proves(&self, leaf: [u8; 32]) -> bool {
+        self.path.leaf_count == self.height
+            && self.path.compute_root(leaf) == Some(self.state_root)
+    }
+}
+
+/// A committee member's BLS12-381 signing key
+pub struct CommitteeKey(BlsSecretKey);
+
+impl CommitteeKey {
+    /// Generate a new random key
+    pub fn generate() -> Self {
+        let ikm: [u8; 32] = rand::random();
+        Self(BlsSecretKey::key_gen(&ikm, &[]).expect("32 bytes of key material"))
+    }
+
+    /// Public key and proof of possession to register in a committee
+    pub fn member(&self) -> CommitteeMember {
+        let public_key = self.0.sk_to_pk().to_bytes().to_vec();
+        let proof_of_possession = self.0.sign(&public_key, COMMITTEE_POP_DST, &[]).to_bytes().to_vec();
+        CommitteeMember { public_key, proof_of_possession }
+    }
+}
+
+/// A committee member's compressed BLS public key and its proof of
+/// possession
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitteeMember {
+    pub public_key: Vec<u8>,
+    pub proof_of_possession: Vec<u8>,
+}
+
+impl CommitteeMember {
+    /// Decode the public key, checking it is a valid group element whose
+    /// owner proved knowledge of the secret key
+    fn verified_key(&self) -> Option<BlsPublicKey> {
+        let key = BlsPublicKey::key_validate(&self.public_key).ok()?;
+        let proof = BlsSignature::sig_validate(&self.proof_of_possession, true).ok()?;
+        let verified = proof.verify(false, &self.public_key, COMMITTEE_POP_DST, &[], &key, false);
+        (verified == BLST_ERROR::BLST_SUCCESS).then_some(key)
+    }
+}
+
+/// Signing committee for one shard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardCommittee {
+    pub members: Vec<CommitteeMember>,
+    /// Signatures needed for a certificate
+    pub threshold: usize,
+}
+
+/// One committee member's signature over a shard header
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitteeVote {
+    /// Index into the committee's member list
+    pub member: u32,
+    pub signature: Vec<u8>,
+}
+
+impl CommitteeVote {
+    /// Sign `header` as committee member `member`
+    pub fn sign(member: u32, key: &CommitteeKey, header: &ShardHeader) -> Self {
+        Self {
+            member,
+            signature: key.0.sign(&header.signing_bytes(), COMMITTEE_SIGNATURE_DST, &[]).to_bytes().to_vec(),
+        }
+    }
+}
+
+/// A quorum of committee members' signatures over a shard header,
+/// aggregated into one BLS signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitteeCertificate {
+    pub header: ShardHeader,
+    /// Members that signed, in increasing order
+    pub signers: Vec<u32>,
+    /// Aggregate of the signers' signatures
+    pub signature: Vec<u8>,
+}
+
+/// A channel moved from one shard to another
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelMigration {
+    pub channel_id: ChannelId,
+    pub from_shard: ShardId,
+    pub to_shard: ShardId,
+    pub message_count: u64,
+    /// Hash of the channel's messages at export
+    pub state_hash: [u8; 32],
+    /// Proof that the source shard logged the export
+    pub receipt: ShardReceipt,
+}
+
+impl ChannelMigration {
+    fn leaf(&self) -> [u8; 32] {
+        LogEntry::MigrationOut {
+            channel_id: &self.channel_id,
+            to_shard: &self.to_shard,
+            message_count: self.message_count,
+            state_hash: &self.state_hash,
+        }
+        .leaf()
+    }
 }
 
 /// Sharding configuration
@@ -57,12 +257,19 @@ pub struct ShardConfig {
     pub num_shards: u32,
     /// Activity threshold for channel assignment (messages/hour)
     pub high_activity_threshold: u64,
-    /// Enable BLS signature aggregation
+    /// Enable aggregating committee signatures into BLS certificates
     pub enable_bls_aggregation: bool,
     /// Light client mode (subscribe to subset of shards)
     pub light_client_mode: bool,
     /// Shards to track in light client mode
     pub tracked_shards: Vec<ShardId>,
+    /// How far above the average load a shard may run before rebalancing
+    #[serde(default = "default_rebalance_tolerance")]
+    pub rebalance_tolerance: f64,
+}
+
+fn default_rebalance_tolerance() -> f64 {
+    0.25
 }
 
 impl Default for ShardConfig {
@@ -73,43 +280,199 @@ impl Default for ShardConfig {
             enable_bls_aggregation: true,
             light_client_mode: false,
             tracked_shards: Vec::new(),
+            rebalance_tolerance: default_rebalance_tolerance(),
         }
     }
 }
 
+/// Entries a shard applies to its log
+#[derive(Serialize)]
+enum LogEntry<'a> {
+    Delivered(&'a ShardMessage),
+    Outbound {
+        id: &'a str,
+        from_shard: &'a ShardId,
+        to_shard: &'a ShardId,
+        from_channel: &'a ChannelId,
+        to_channel: &'a ChannelId,
+        payload: &'a [u8],
+        timestamp: i64,
+    },
+    MigrationOut {
+        channel_id: &'a ChannelId,
+        to_shard: &'a ShardId,
+        message_count: u64,
+        state_hash: &'a [u8; 32],
+    },
+    MigrationIn {
+        channel_id: &'a ChannelId,
+        from_shard: &'a ShardId,
+        state_hash: &'a [u8; 32],
+    },
+}
+
+impl LogEntry<'_> {
+    fn leaf(&self) -> [u8; 32] {
+        leaf_hash(&serde_json::to_vec(self).expect("log entries serialize"))
+    }
+}
+
+/// Hash of a channel's messages, in order
+fn channel_state_hash(channel_id: &ChannelId, messages: &[ShardMessage]) -> [u8; 32] {
+    let mut hasher = Hasher::new();
+    hasher.update(channel_id.0.as_bytes());
+    for message in messages {
+        hasher.update(&LogEntry::Delivered(message).leaf());
+    }
+    *hasher.finalize().as_bytes()
+}
+
+/// Position on the hash ring
+fn ring_position(parts: &[&[u8]]) -> u64 {
+    let mut hasher = Hasher::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    let hash = hasher.finalize();
+    u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap())
+}
+
+/// Consistent-hash ring with virtual nodes
+///
+/// Adding a shard only takes over the ring arcs its virtual nodes land on,
+/// so roughly `1 / num_shards` of channels move.
+#[derive(Debug, Clone, Default)]
+struct HashRing {
+    points: Vec<(u64, u32)>,
+}
+
+impl HashRing {
+    fn new(num_shards: u32) -> Self {
+        let mut ring = Self::default();
+        for shard in 0..num_shards {
+            ring.add_shard(shard);
+        }
+        ring
+    }
+
+    fn add_shard(&mut self, shard: u32) {
+        for vnode in 0..VIRTUAL_NODES_PER_SHARD {
+            let position = ring_position(&[b"shard", &shard.to_le_bytes(), &vnode.to_le_bytes()]);
+            self.points.push((position, shard));
+        }
+        self.points.sort_unstable();
+    }
+
+    fn locate(&self, key: &[u8]) -> ShardId {
+        let position = ring_position(&[b"channel", key]);
+        let index = self.points.partition_point(|(point, _)| *point < position);
+        let (_, shard) = self.points[index % self.points.len()];
+        ShardId(shard)
+    }
+}
+
+/// Per-shard log and channel data
+#[derive(Debug, Default)]
+struct ShardLedger {
+    log: Vec<[u8; 32]>,
+    /// Tree over the log as of the last commit
+    tree: Option<MerkleTree>,
+    /// Every committed root by height
+    roots: BTreeMap<u64, [u8; 32]>,
+    channels: HashMap<ChannelId, Vec<ShardMessage>>,
+    /// Entries applied per channel since the last rebalance
+    activity: HashMap<ChannelId, u64>,
+}
+
+impl ShardLedger {
+    fn append(&mut self, leaf: [u8; 32], channel_id: &ChannelId) -> usize {
+        self.log.push(leaf);
+        *self.activity.entry(channel_id.clone()).or_default() += 1;
+        self.log.len() - 1
+    }
+
+    fn commit(&mut self) -> (u64, [u8; 32]) {
+        let height = self.log.len() as u64;
+        if self.tree.as_ref().map(|tree| tree.len() as u64) != Some(height) {
+            let tree = MerkleTree::from_leaf_hashes(self.log.clone());
+            self.roots.insert(height, tree.root());
+            self.tree = Some(tree);
+        }
+        (height, self.root())
+    }
+
+    fn root(&self) -> [u8; 32] {
+        self.tree.as_ref().map(MerkleTree::root).unwrap_or_else(empty_root)
+    }
+
+    fn root_at(&self, height: u64) -> Option<[u8; 32]> {
+        self.roots.get(&height).copied()
+    }
+
+    fn receipt(&mut self, shard_id: &ShardId, index: usize) -> ShardReceipt {
+        let (height, state_root) = self.commit();
+        let path = self.tree.as_ref().and_then(|tree| tree.path(index)).expect("entry is in the committed log");
+        ShardReceipt {
+            shard_id: shard_id.clone(),
+            height,
+            state_root,
+            path,
+        }
+    }
+
+    fn load(&self, channel_id: &ChannelId) -> u64 {
+        self.activity.get(channel_id).copied().unwrap_or(0)
+    }
+}
+
 /// Shard manager
 pub struct ShardManager {
     config: ShardConfig,
+    ring: HashRing,
     shard_states: HashMap<ShardId, ShardState>,
+    ledgers: HashMap<ShardId, ShardLedger>,
     channel_assignments: HashMap<ChannelId, ShardId>,
+    /// Channels placed off their ring position by rebalancing
+    pinned: HashSet<ChannelId>,
+    committees: HashMap<ShardId, ShardCommittee>,
     pending_cross_shard: Vec<CrossShardMessage>,
+    migrations: Vec<ChannelMigration>,
 }
 
 impl ShardManager {
     pub fn new(config: ShardConfig) -> Self {
-        let mut shard_states = HashMap::new();
-        
-        // Initialize all shards
-        for i in 0..config.num_shards {
-            let shard_id = ShardId(i);
-            shard_states.insert(
-                shard_id.clone(),
-                ShardState {
-                    shard_id,
-                    channels: Vec::new(),
-                    state_root: vec![0; 32],
-                    message_count: 0,
-                    last_updated: chrono::Utc::now().timestamp(),
-                },
-            );
-        }
-
-        Self {
+        let mut manager = Self {
+            ring: HashRing::new(config.num_shards),
             config,
-            shard_states,
+            shard_states: HashMap::new(),
+            ledgers: HashMap::new(),
             channel_assignments: HashMap::new(),
+            pinned: HashSet::new(),
+            committees: HashMap::new(),
             pending_cross_shard: Vec::new(),
+            migrations: Vec::new(),
+        };
+
+        // Initialize all shards
+        for i in 0..manager.config.num_shards {
+            manager.init_shard(ShardId(i));
         }
+
+        manager
+    }
+
+    fn init_shard(&mut self, shard_id: ShardId) {
+        self.shard_states.insert(
+            shard_id.clone(),
+            ShardState {
+                shard_id: shard_id.clone(),
+                channels: Vec::new(),
+                state_root: empty_root().to_vec(),
+                message_count: 0,
+                last_updated: chrono::Utc::now().timestamp(),
+            },
+        );
+        self.ledgers.insert(shard_id, ShardLedger::default());
     }
 
     /// Assign channel to shard using consistent hashing
@@ -133,14 +496,9 @@ impl ShardManager {
         Ok(shard_id)
     }
 
-    /// Hash channel ID to shard using BLAKE3
+    /// Hash channel ID to its shard on the consistent-hash ring
     fn hash_to_shard(&self, channel_id: &ChannelId) -> ShardId {
-        let mut hasher = Hasher::new();
-        hasher.update(channel_id.0.as_bytes());
-        let hash = hasher.finalize();
-        
-        let shard_num = u32::from_le_bytes([hash.as_bytes()[0], hash.as_bytes()[1], hash.as_bytes()[2], hash.as_bytes()[3]]);
-        ShardId(shard_num % self.config.num_shards)
+        self.ring.locate(channel_id.0.as_bytes())
     }
 
     /// Get shard for a channel
@@ -148,6 +506,15 @@ impl ShardManager {
         self.channel_assignments.get(channel_id).cloned()
     }
 
+    /// Messages stored for a channel on its current shard
+    pub fn channel_messages(&self, channel_id: &ChannelId) -> &[ShardMessage] {
+        self.get_shard(channel_id)
+            .and_then(|shard_id| self.ledgers.get(&shard_id))
+            .and_then(|ledger| ledger.channels.get(channel_id))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
     /// Route message (may be cross-shard)
     pub fn route_message(
         &mut self,
@@ -183,107 +550,244 @@ impl ShardManager {
     /// Deliver message within same shard
     fn deliver_same_shard(
         &mut self,
-        _from_channel: &ChannelId,
-        _to_channel: &ChannelId,
-        _payload: Vec<u8>,
+        from_channel: &ChannelId,
+        to_channel: &ChannelId,
+        payload: Vec<u8>,
     ) -> Result<()> {
-        // In production: update shard state, emit events
+        let shard_id = self.get_shard(to_channel)
+            .ok_or_else(|| Error::network("Destination channel not assigned to shard"))?;
+
+        self.deliver(&shard_id, ShardMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            from_shard: shard_id.clone(),
+            from_channel: from_channel.clone(),
+            to_channel: to_channel.clone(),
+            payload,
+            timestamp: chrono::Utc::now().timestamp(),
+        })
+    }
+
+    /// Apply a message to its destination channel's log
+    fn deliver(&mut self, shard_id: &ShardId, message: ShardMessage) -> Result<()> {
+        let ledger = self.ledgers.get_mut(shard_id)
+            .ok_or_else(|| Error::network("Shard not found"))?;
+
+        ledger.append(LogEntry::Delivered(&message).leaf(), &message.to_channel);
+        ledger.channels.entry(message.to_channel.clone()).or_default().push(message);
+        self.sync_state(shard_id);
         Ok(())
     }
 
     /// Create cross-shard message with proof
     fn create_cross_shard_message(
-        &self,
+        &mut self,
         from_shard: ShardId,
         to_shard: ShardId,
         from_channel: ChannelId,
         to_channel: ChannelId,
         payload: Vec<u8>,
     ) -> Result<CrossShardMessage> {
-        // Generate Merkle proof that message exists in source shard
-        let proof = self.generate_merkle_proof(&from_shard, &from_channel)?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let timestamp = chrono::Utc::now().timestamp();
+        let leaf = LogEntry::Outbound {
+            id: &id,
+            from_shard: &from_shard,
+            to_shard: &to_shard,
+            from_channel: &from_channel,
+            to_channel: &to_channel,
+            payload: &payload,
+            timestamp,
+        }
+        .leaf();
+
+        // Log the message in the source shard and prove it under the new root
+        let ledger = self.ledgers.get_mut(&from_shard)
+            .ok_or_else(|| Error::network("Shard not found"))?;
+        let index = ledger.append(leaf, &from_channel);
+        let receipt = ledger.receipt(&from_shard, index);
+        self.sync_state(&from_shard);
 
         Ok(CrossShardMessage {
-            id: uuid::Uuid::new_v4().to_string(),
+            id,
             from_shard,
             to_shard,
             from_channel,
             to_channel,
             payload,
-            timestamp: chrono::Utc::now().timestamp(),
-            proof,
+            timestamp,
+            receipt,
         })
     }
 
-    /// Generate Merkle proof for cross-shard message
-    fn generate_merkle_proof(&self, shard_id: &ShardId, _channel_id: &ChannelId) -> Result<Vec<u8>> {
-        let shard_state = self.shard_states.get(shard_id)
-            .ok_or_else(|| Error::network("Shard not found"))?;
-
-        // Placeholder: In production, generate actual Merkle proof
-        Ok(shard_state.state_root.clone())
-    }
-
-    /// Verify cross-shard message proof
+    /// Verify cross-shard message proof against the source shard's roots
     pub fn verify_cross_shard_proof(&self, msg: &CrossShardMessage) -> Result<bool> {
-        let source_shard = self.shard_states.get(&msg.from_shard)
+        let source = self.ledgers.get(&msg.from_shard)
             .ok_or_else(|| Error::network("Source shard not found"))?;
 
-        // Placeholder: In production, verify Merkle proof against state root
-        Ok(msg.proof == source_shard.state_root)
+        let receipt = &msg.receipt;
+        Ok(receipt.shard_id == msg.from_shard
+            && source.root_at(receipt.height) == Some(receipt.state_root)
+            && receipt.proves(msg.leaf()))
+    }
+
+    /// Verify a cross-shard message using only the source committee's keys
+    ///
+    /// This is what a node that does not track the source shard checks.
+    pub fn verify_certified_message(
+        &self,
+        msg: &CrossShardMessage,
+        certificate: &CommitteeCertificate,
+    ) -> Result<bool> {
+        Ok(certificate.header == msg.receipt.header()
+            && msg.receipt.shard_id == msg.from_shard
+            && self.verify_certificate(certificate)?
+            && msg.receipt.proves(msg.leaf()))
     }
 
     /// Process pending cross-shard messages
+    ///
+    /// Messages are delivered to the shard that currently holds the
+    /// destination channel, so messages in flight during a migration follow
+    /// the channel. Messages whose receipts fail verification are dropped.
     pub fn process_cross_shard_messages(&mut self) -> Result<usize> {
         let mut processed = 0;
+        let mut kept = Vec::new();
 
-        // In light client mode, only process messages for tracked shards
-        let messages_to_process: Vec<_> = if self.config.light_client_mode {
-            self.pending_cross_shard.iter()
-                .filter(|msg| self.config.tracked_shards.contains(&msg.to_shard))
-                .cloned()
-                .collect()
-        } else {
-            self.pending_cross_shard.clone()
-        };
+        for msg in std::mem::take(&mut self.pending_cross_shard) {
+            let dest_shard = self.get_shard(&msg.to_channel).unwrap_or_else(|| msg.to_shard.clone());
 
-        // Collect verified message IDs to remove later
-        let mut verified_msg_ids = Vec::new();
+            // In light client mode, only process messages for tracked shards
+            if self.config.light_client_mode && !self.config.tracked_shards.contains(&dest_shard) {
+                kept.push(msg);
+                continue;
+            }
 
-        for msg in messages_to_process {
             // Verify proof
-            if self.verify_cross_shard_proof(&msg)? {
-                // Deliver to destination shard
-                if let Some(dest_shard) = self.shard_states.get_mut(&msg.to_shard) {
-                    dest_shard.message_count += 1;
-                    dest_shard.last_updated = chrono::Utc::now().timestamp();
-                    processed += 1;
-                    verified_msg_ids.push(msg.id.clone());
-                }
+            if !self.verify_cross_shard_proof(&msg)? {
+                continue;
             }
-        }
 
-        // Remove processed messages
-        self.pending_cross_shard.retain(|msg| {
-            !verified_msg_ids.contains(&msg.id)
-        });
+            // Deliver to destination shard
+            self.deliver(&dest_shard, ShardMessage {
+                id: msg.id,
+                from_shard: msg.from_shard,
+                from_channel: msg.from_channel,
+                to_channel: msg.to_channel,
+                payload: msg.payload,
+                timestamp: msg.timestamp,
+            })?;
+            processed += 1;
+        }
 
+        self.pending_cross_shard = kept;
         Ok(processed)
     }
 
-    /// Aggregate BLS signatures for shard finality
-    pub fn aggregate_signatures(&self, signatures: &[Vec<u8>]) -> Result<Vec<u8>> {
+    /// Commit a shard's log and return the header for its committee to sign
+    pub fn commit_shard(&mut self, shard_id: &ShardId) -> Result<ShardHeader> {
+        let ledger = self.ledgers.get_mut(shard_id)
+            .ok_or_else(|| Error::network("Shard not found"))?;
+        let (height, state_root) = ledger.commit();
+        self.sync_state(shard_id);
+
+        Ok(ShardHeader {
+            shard_id: shard_id.clone(),
+            height,
+            state_root,
+        })
+    }
+
+    /// Set the signing committee for a shard
+    pub fn set_committee(&mut self, shard_id: ShardId, committee: ShardCommittee) -> Result<()> {
+        if !self.shard_states.contains_key(&shard_id) {
+            return Err(Error::network("Shard not found"));
+        }
+        if committee.threshold == 0 || committee.threshold > committee.members.len() {
+            return Err(Error::validation("Committee threshold must be between 1 and the member count"));
+        }
+        if let Some(index) = committee.members.iter().position(|m| m.verified_key().is_none()) {
+            return Err(Error::crypto(format!("Committee member {} has no valid proof of possession", index)));
+        }
+
+        self.committees.insert(shard_id, committee);
+        Ok(())
+    }
+
+    /// Aggregate committee signatures over a shard header into a certificate
+    ///
+    /// Every vote must be a valid signature from a distinct member, and there
+    /// must be at least the committee's threshold of them. The votes are
+    /// combined into a single BLS signature.
+    pub fn aggregate_signatures(
+        &self,
+        header: &ShardHeader,
+        votes: &[CommitteeVote],
+    ) -> Result<CommitteeCertificate> {
         if !self.config.enable_bls_aggregation {
-            return Err(Error::network("BLS aggregation disabled"));
+            return Err(Error::network("Signature aggregation disabled"));
+        }
+
+        let committee = self.committees.get(&header.shard_id)
+            .ok_or_else(|| Error::network("Shard has no committee"))?;
+
+        let mut votes = votes.to_vec();
+        votes.sort_by_key(|vote| vote.member);
+        votes.dedup_by_key(|vote| vote.member);
+
+        let mut signatures = Vec::with_capacity(votes.len());
+        for vote in &votes {
+            let signature = valid_vote_signature(committee, header, vote)
+                .ok_or_else(|| Error::crypto(format!("Invalid signature from committee member {}", vote.member)))?;
+            signatures.push(signature);
         }
 
-        // Placeholder: In production, use BLS12-381 signature aggregation
-        let mut aggregated = Vec::new();
-        for sig in signatures {
-            aggregated.extend_from_slice(sig);
+        if votes.len() < committee.threshold {
+            return Err(Error::crypto(format!(
+                "{} of {} required committee signatures",
+                votes.len(),
+                committee.threshold
+            )));
         }
 
-        Ok(aggregated)
+        let signatures: Vec<&BlsSignature> = signatures.iter().collect();
+        let aggregate = AggregateSignature::aggregate(&signatures, false)
+            .map_err(|e| Error::crypto(format!("Signature aggregation failed: {:?}", e)))?;
+
+        Ok(CommitteeCertificate {
+            header: header.clone(),
+            signers: votes.iter().map(|vote| vote.member).collect(),
+            signature: aggregate.to_signature().to_bytes().to_vec(),
+        })
+    }
+
+    /// Verify a committee certificate against the shard's committee
+    pub fn verify_certificate(&self, certificate: &CommitteeCertificate) -> Result<bool> {
+        let committee = self.committees.get(&certificate.header.shard_id)
+            .ok_or_else(|| Error::network("Shard has no committee"))?;
+
+        let distinct = certificate.signers.windows(2).all(|pair| pair[0] < pair[1]);
+        if !distinct || certificate.signers.len() < committee.threshold {
+            return Ok(false);
+        }
+
+        let Some(keys) = certificate.signers.iter()
+            .map(|&member| committee.members.get(member as usize).and_then(CommitteeMember::verified_key))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(false);
+        };
+        let Ok(signature) = BlsSignature::sig_validate(&certificate.signature, true) else {
+            return Ok(false);
+        };
+
+        let keys: Vec<&BlsPublicKey> = keys.iter().collect();
+        let verified = signature.fast_aggregate_verify(
+            false,
+            &certificate.header.signing_bytes(),
+            COMMITTEE_SIGNATURE_DST,
+            &keys,
+        );
+        Ok(verified == BLST_ERROR::BLST_SUCCESS)
     }
 
     /// Get shard statistics
@@ -296,11 +800,205 @@ impl ShardManager {
         })
     }
 
+    /// Log entries a shard has applied since the last rebalance
+    pub fn shard_load(&self, shard_id: &ShardId) -> u64 {
+        self.ledgers.get(shard_id)
+            .map(|ledger| ledger.activity.values().sum())
+            .unwrap_or(0)
+    }
+
     /// Rebalance shards (move channels between shards)
+    ///
+    /// While the busiest shard runs more than `rebalance_tolerance` above the
+    /// average load, its channel that best evens out the busiest and idlest
+    /// shards is migrated. Each move strictly lowers the busiest shard's load,
+    /// so this terminates. Activity counters restart afterwards.
     pub fn rebalance_shards(&mut self) -> Result<usize> {
-        // Placeholder: In production, implement load-based rebalancing
-        // For now, just return 0 (no channels moved)
-        Ok(0)
+        let mut moved = 0;
+
+        loop {
+            let mut loads: Vec<(ShardId, u64)> = self.ledgers.keys()
+                .map(|shard_id| (shard_id.clone(), self.shard_load(shard_id)))
+                .collect();
+            loads.sort();
+
+            let total: u64 = loads.iter().map(|(_, load)| load).sum();
+            let average = total as f64 / loads.len().max(1) as f64;
+            let Some((busiest, max_load)) = loads.iter().max_by_key(|(_, load)| *load).cloned() else {
+                break;
+            };
+            let Some((idlest, min_load)) = loads.iter().min_by_key(|(_, load)| *load).cloned() else {
+                break;
+            };
+            if max_load as f64 <= average * (1.0 + self.config.rebalance_tolerance) {
+                break;
+            }
+
+            // Moving load `a` leaves max(max - a, min + a), which is below the
+            // current peak only for 0 < a < gap; closest to gap / 2 is best
+            let gap = max_load - min_load;
+            let ledger = &self.ledgers[&busiest];
+            let mut candidates: Vec<&ChannelId> = ledger.activity.keys()
+                .filter(|channel_id| self.channel_assignments.get(*channel_id) == Some(&busiest))
+                .collect();
+            candidates.sort();
+            let candidate = candidates.into_iter()
+                .map(|channel_id| (channel_id, ledger.load(channel_id)))
+                .filter(|(_, load)| *load > 0 && *load < gap)
+                .min_by_key(|(_, load)| gap.abs_diff(2 * load))
+                .map(|(channel_id, _)| channel_id.clone());
+
+            let Some(channel_id) = candidate else {
+                break;
+            };
+            self.migrate_channel(&channel_id, idlest)?;
+            moved += 1;
+        }
+
+        for ledger in self.ledgers.values_mut() {
+            ledger.activity.clear();
+        }
+
+        Ok(moved)
+    }
+
+    /// Move a channel and its messages to another shard
+    ///
+    /// The source shard logs the export and the destination checks the
+    /// receipt and the exported state before taking the channel over.
+    pub fn migrate_channel(&mut self, channel_id: &ChannelId, to_shard: ShardId) -> Result<ChannelMigration> {
+        let from_shard = self.get_shard(channel_id)
+            .ok_or_else(|| Error::network("Channel not assigned to shard"))?;
+        if from_shard == to_shard {
+            return Err(Error::validation("Channel is already on that shard"));
+        }
+        if !self.ledgers.contains_key(&to_shard) {
+            return Err(Error::network("Shard not found"));
+        }
+
+        let (migration, messages) = self.export_channel(channel_id, &from_shard, &to_shard)?;
+        self.import_channel(&migration, messages)?;
+
+        if self.hash_to_shard(channel_id) == to_shard {
+            self.pinned.remove(channel_id);
+        } else {
+            self.pinned.insert(channel_id.clone());
+        }
+        self.migrations.push(migration.clone());
+        Ok(migration)
+    }
+
+    fn export_channel(
+        &mut self,
+        channel_id: &ChannelId,
+        from_shard: &ShardId,
+        to_shard: &ShardId,
+    ) -> Result<(ChannelMigration, Vec<ShardMessage>)> {
+        let ledger = self.ledgers.get_mut(from_shard)
+            .ok_or_else(|| Error::network("Shard not found"))?;
+
+        let messages = ledger.channels.remove(channel_id).unwrap_or_default();
+        let state_hash = channel_state_hash(channel_id, &messages);
+        let leaf = LogEntry::MigrationOut {
+            channel_id,
+            to_shard,
+            message_count: messages.len() as u64,
+            state_hash: &state_hash,
+        }
+        .leaf();
+        let index = ledger.append(leaf, channel_id);
+        let receipt = ledger.receipt(from_shard, index);
+        ledger.activity.remove(channel_id);
+
+        if let Some(state) = self.shard_states.get_mut(from_shard) {
+            state.channels.retain(|channel| channel != channel_id);
+        }
+        self.sync_state(from_shard);
+
+        Ok((
+            ChannelMigration {
+                channel_id: channel_id.clone(),
+                from_shard: from_shard.clone(),
+                to_shard: to_shard.clone(),
+                message_count: messages.len() as u64,
+                state_hash,
+                receipt,
+            },
+            messages,
+        ))
+    }
+
+    fn import_channel(&mut self, migration: &ChannelMigration, messages: Vec<ShardMessage>) -> Result<()> {
+        let source = self.ledgers.get(&migration.from_shard)
+            .ok_or_else(|| Error::network("Source shard not found"))?;
+        let receipt = &migration.receipt;
+        if receipt.shard_id != migration.from_shard
+            || source.root_at(receipt.height) != Some(receipt.state_root)
+            || !receipt.proves(migration.leaf())
+        {
+            return Err(Error::chain("Channel migration receipt does not verify"));
+        }
+        if messages.len() as u64 != migration.message_count
+            || channel_state_hash(&migration.channel_id, &messages) != migration.state_hash
+        {
+            return Err(Error::chain("Migrated channel state does not match its receipt"));
+        }
+
+        let ledger = self.ledgers.get_mut(&migration.to_shard)
+            .ok_or_else(|| Error::network("Shard not found"))?;
+        let leaf = LogEntry::MigrationIn {
+            channel_id: &migration.channel_id,
+            from_shard: &migration.from_shard,
+            state_hash: &migration.state_hash,
+        }
+        .leaf();
+        ledger.append(leaf, &migration.channel_id);
+        ledger.activity.remove(&migration.channel_id);
+        ledger.channels.entry(migration.channel_id.clone()).or_default().extend(messages);
+
+        if let Some(state) = self.shard_states.get_mut(&migration.to_shard) {
+            state.channels.push(migration.channel_id.clone());
+        }
+        self.channel_assignments.insert(migration.channel_id.clone(), migration.to_shard.clone());
+        self.sync_state(&migration.to_shard);
+        Ok(())
+    }
+
+    /// Completed channel migrations, oldest first
+    pub fn migrations(&self) -> &[ChannelMigration] {
+        &self.migrations
+    }
+
+    /// Add a shard to the ring
+    ///
+    /// Only channels whose ring position now falls on the new shard move;
+    /// channels pinned elsewhere by rebalancing stay put.
+    pub fn add_shard(&mut self) -> Result<ShardId> {
+        let shard_id = ShardId(self.config.num_shards);
+        self.config.num_shards += 1;
+        self.ring.add_shard(shard_id.0);
+        self.init_shard(shard_id.clone());
+
+        let mut moving: Vec<ChannelId> = self.channel_assignments.keys()
+            .filter(|channel_id| !self.pinned.contains(*channel_id))
+            .filter(|channel_id| self.hash_to_shard(channel_id) == shard_id)
+            .cloned()
+            .collect();
+        moving.sort();
+        for channel_id in moving {
+            self.migrate_channel(&channel_id, shard_id.clone())?;
+        }
+
+        Ok(shard_id)
+    }
+
+    /// Refresh a shard's public snapshot from its ledger
+    fn sync_state(&mut self, shard_id: &ShardId) {
+        if let (Some(state), Some(ledger)) = (self.shard_states.get_mut(shard_id), self.ledgers.get(shard_id)) {
+            state.state_root = ledger.root().to_vec();
+            state.message_count = ledger.channels.values().map(|messages| messages.len() as u64).sum();
+            state.last_updated = chrono::Utc::now().timestamp();
+        }
     }
 
     /// Get global statistics
@@ -323,6 +1021,15 @@ impl ShardManager {
     }
 }
 
+/// A vote's signature, if it is a valid signature over `header` by the
+/// member it names
+fn valid_vote_signature(committee: &ShardCommittee, header: &ShardHeader, vote: &CommitteeVote) -> Option<BlsSignature> {
+    let key = committee.members.get(vote.member as usize)?.verified_key()?;
+    let signature = BlsSignature::sig_validate(&vote.signature, true).ok()?;
+    let verified = signature.verify(false, &header.signing_bytes(), COMMITTEE_SIGNATURE_DST, &[], &key, false);
+    (verified == BLST_ERROR::BLST_SUCCESS).then_some(signature)
+}
+
 /// Shard statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShardStats {
@@ -438,18 +1145,167 @@ mod tests {
     }
 
     #[test]
-    fn test_bls_signature_aggregation() {
-        let config = ShardConfig::default();
-        let manager = ShardManager::new(config);
+    fn test_cross_shard_receipt_verification() {
+        let mut manager = ShardManager::new(ShardConfig::default());
 
-        let sig1 = vec![1, 2, 3];
-        let sig2 = vec![4, 5, 6];
-        let signatures = vec![sig1.clone(), sig2.clone()];
+        let channel1 = ChannelId("channel1".to_string());
+        let channel2 = ChannelId("channel2".to_string());
+        manager.channel_assignments.insert(channel1.clone(), ShardId(0));
+        manager.channel_assignments.insert(channel2.clone(), ShardId(1));
 
-        let aggregated = manager.aggregate_signatures(&signatures).unwrap();
-        
-        // Placeholder implementation just concatenates
-        assert_eq!(aggregated.len(), 6);
+        manager.route_message(channel1.clone(), channel1.clone(), b"local".to_vec()).unwrap();
+        manager.route_message(channel1.clone(), channel2.clone(), b"hello".to_vec()).unwrap();
+        // Later entries do not invalidate an earlier receipt
+        manager.route_message(channel1.clone(), channel1.clone(), b"more".to_vec()).unwrap();
+
+        let msg = manager.pending_cross_shard[0].clone();
+        assert!(manager.verify_cross_shard_proof(&msg).unwrap());
+
+        let mut tampered = msg.clone();
+        tampered.payload = b"goodbye".to_vec();
+        assert!(!manager.verify_cross_shard_proof(&tampered).unwrap());
+
+        let mut forged_root = msg.clone();
+        forged_root.receipt.state_root = [7; 32];
+        assert!(!manager.verify_cross_shard_proof(&forged_root).unwrap());
+
+        // A forged message is dropped, the real one is delivered
+        manager.pending_cross_shard.push(tampered);
+        assert_eq!(manager.process_cross_shard_messages().unwrap(), 1);
+        assert!(manager.pending_cross_shard.is_empty());
+        assert_eq!(manager.channel_messages(&channel2)[0].payload, b"hello");
+    }
+
+    #[test]
+    fn test_committee_certificate() {
+        let mut manager = ShardManager::new(ShardConfig::default());
+        let keys: Vec<CommitteeKey> = (0..4).map(|_| CommitteeKey::generate()).collect();
+        let members: Vec<CommitteeMember> = keys.iter().map(CommitteeKey::member).collect();
+
+        // A member key without a matching proof of possession is refused
+        let mut rogue = members.clone();
+        rogue[3].proof_of_possession = members[2].proof_of_possession.clone();
+        assert!(manager.set_committee(ShardId(0), ShardCommittee { members: rogue, threshold: 3 }).is_err());
+
+        manager.set_committee(ShardId(0), ShardCommittee { members, threshold: 3 }).unwrap();
+
+        let channel1 = ChannelId("channel1".to_string());
+        let channel2 = ChannelId("channel2".to_string());
+        manager.channel_assignments.insert(channel1.clone(), ShardId(0));
+        manager.channel_assignments.insert(channel2.clone(), ShardId(1));
+        manager.route_message(channel1, channel2, b"certified".to_vec()).unwrap();
+        let msg = manager.pending_cross_shard[0].clone();
+
+        let header = manager.commit_shard(&ShardId(0)).unwrap();
+        assert_eq!(header, msg.receipt.header());
+        let votes: Vec<CommitteeVote> = keys.iter().enumerate()
+            .map(|(i, key)| CommitteeVote::sign(i as u32, key, &header))
+            .collect();
+
+        // Below threshold, even with a repeated signer
+        let short = [votes[0].clone(), votes[1].clone(), votes[1].clone()];
+        assert!(manager.aggregate_signatures(&header, &short).is_err());
+
+        // A signature over another header is rejected
+        let other = ShardHeader { height: header.height + 1, ..header.clone() };
+        let wrong = [votes[0].clone(), votes[1].clone(), CommitteeVote::sign(2, &keys[2], &other)];
+        assert!(manager.aggregate_signatures(&header, &wrong).is_err());
+
+        let certificate = manager.aggregate_signatures(&header, &votes[1..]).unwrap();
+        assert_eq!(certificate.signers, vec![1, 2, 3]);
+        assert_eq!(certificate.signature.len(), 96);
+        assert!(manager.verify_certificate(&certificate).unwrap());
+        assert!(manager.verify_certified_message(&msg, &certificate).unwrap());
+
+        let mut tampered = msg.clone();
+        tampered.payload = b"forged".to_vec();
+        assert!(!manager.verify_certified_message(&tampered, &certificate).unwrap());
+
+        let mut stripped = certificate.clone();
+        stripped.signers.pop();
+        assert!(!manager.verify_certificate(&stripped).unwrap());
+
+        // The aggregate only verifies against the members that signed
+        let mut swapped = certificate.clone();
+        swapped.signers = vec![0, 1, 2];
+        assert!(!manager.verify_certificate(&swapped).unwrap());
+
+        manager.config.enable_bls_aggregation = false;
+        assert!(manager.aggregate_signatures(&header, &votes).is_err());
+    }
+
+    #[test]
+    fn test_rebalance_preserves_messages() {
+        let config = ShardConfig {
+            num_shards: 4,
+            ..Default::default()
+        };
+        let mut manager = ShardManager::new(config);
+
+        let channels: Vec<ChannelId> = (0..32).map(|i| ChannelId(format!("channel-{}", i))).collect();
+        for channel in &channels {
+            manager.assign_channel(channel.clone()).unwrap();
+        }
+
+        // Every channel on one shard is hot
+        let hot_shard = manager.get_shard(&channels[0]).unwrap();
+        let hot: Vec<ChannelId> = channels.iter()
+            .filter(|channel| manager.get_shard(channel) == Some(hot_shard.clone()))
+            .cloned()
+            .collect();
+        let cold = channels.iter()
+            .find(|channel| manager.get_shard(channel) != Some(hot_shard.clone()))
+            .unwrap()
+            .clone();
+        for (i, channel) in hot.iter().enumerate() {
+            for _ in 0..10 * (i + 1) {
+                manager.route_message(channel.clone(), channel.clone(), b"busy".to_vec()).unwrap();
+            }
+        }
+
+        // A message is in flight to a hot channel during the rebalance
+        manager.route_message(cold.clone(), hot[hot.len() - 1].clone(), b"in flight".to_vec()).unwrap();
+        let before = manager.get_global_stats().total_messages;
+
+        let moved = manager.rebalance_shards().unwrap();
+        assert!(moved > 0);
+        assert_eq!(manager.migrations().len(), moved);
+        assert!(hot.iter().any(|channel| manager.get_shard(channel) != Some(hot_shard.clone())));
+        assert_eq!(manager.get_global_stats().total_messages, before);
+        assert_eq!(manager.shard_load(&hot_shard), 0);
+
+        assert_eq!(manager.process_cross_shard_messages().unwrap(), 1);
+        for (i, channel) in hot.iter().enumerate() {
+            let expected = 10 * (i + 1) + usize::from(i == hot.len() - 1);
+            assert_eq!(manager.channel_messages(channel).len(), expected);
+        }
+    }
+
+    #[test]
+    fn test_add_shard_moves_few_channels() {
+        let config = ShardConfig {
+            num_shards: 8,
+            ..Default::default()
+        };
+        let mut manager = ShardManager::new(config);
+
+        let channels: Vec<ChannelId> = (0..400).map(|i| ChannelId(format!("channel-{}", i))).collect();
+        for channel in &channels {
+            manager.assign_channel(channel.clone()).unwrap();
+            manager.route_message(channel.clone(), channel.clone(), b"hi".to_vec()).unwrap();
+        }
+        let before: HashMap<ChannelId, ShardId> = manager.channel_assignments.clone();
+
+        let new_shard = manager.add_shard().unwrap();
+        assert_eq!(new_shard, ShardId(8));
+
+        let moved: Vec<&ChannelId> = channels.iter()
+            .filter(|channel| manager.get_shard(channel) != before.get(*channel).cloned())
+            .collect();
+        // Only channels taken over by the new shard move, about 1/9 of them
+        assert!(!moved.is_empty() && moved.len() < channels.len() / 4);
+        assert!(moved.iter().all(|channel| manager.get_shard(channel) == Some(new_shard.clone())));
+        assert!(channels.iter().all(|channel| manager.channel_messages(channel).len() == 1));
     }
 
     #[test]
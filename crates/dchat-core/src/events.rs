@@ -84,6 +84,34 @@ pub enum Event {
         result: bool,
     },
     
+    // Marketplace subscription events
+    SubscriptionStarted {
+        subscription_id: uuid::Uuid,
+        listing_id: uuid::Uuid,
+        subscriber: UserId,
+        price_per_period: u64,
+    },
+    SubscriptionRenewed {
+        subscription_id: uuid::Uuid,
+        amount: u64,
+        period_end: chrono::DateTime<chrono::Utc>,
+    },
+    SubscriptionPastDue {
+        subscription_id: uuid::Uuid,
+        grace_until: chrono::DateTime<chrono::Utc>,
+    },
+    SubscriptionExpired {
+        subscription_id: uuid::Uuid,
+    },
+    SubscriptionCancelled {
+        subscription_id: uuid::Uuid,
+        refund: u64,
+    },
+    CreatorPaidOut {
+        creator: UserId,
+        amount: u64,
+    },
+    
     // System events
     SystemStarted,
     SystemShutdown,
@@ -148,6 +148,44 @@ pub fn decrypt_with_password(password: &str, encrypted: &EncryptedData) -> Resul
     Ok(plaintext)
 }
 
+/// Encrypt data under a raw 256-bit key with AES-256-GCM
+///
+/// For keys that are already uniformly random (e.g. a PAKE session key).
+/// The random nonce is prepended to the returned ciphertext.
+pub fn encrypt_with_key(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut nonce_bytes = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce_bytes);
+
+    #[allow(deprecated)]
+    let nonce = Nonce::clone_from_slice(&nonce_bytes);
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| Error::crypto(format!("Cipher initialization failed: {}", e)))?;
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| Error::crypto(format!("Encryption failed: {}", e)))?;
+
+    let mut output = Vec::with_capacity(nonce_bytes.len() + ciphertext.len());
+    output.extend_from_slice(&nonce_bytes);
+    output.extend_from_slice(&ciphertext);
+    Ok(output)
+}
+
+/// Decrypt data produced by [`encrypt_with_key`]
+pub fn decrypt_with_key(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < 12 {
+        return Err(Error::crypto("Ciphertext too short".to_string()));
+    }
+    let (nonce_bytes, ciphertext) = data.split_at(12);
+
+    #[allow(deprecated)]
+    let nonce = Nonce::clone_from_slice(nonce_bytes);
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| Error::crypto(format!("Cipher initialization failed: {}", e)))?;
+    cipher
+        .decrypt(&nonce, ciphertext)
+        .map_err(|_| Error::crypto("Decryption failed: data may be corrupted or tampered with".to_string()))
+}
+
 /// Extract 32-byte encryption key from Argon2 hash string
 fn extract_key_from_hash(hash_string: &str) -> Result<Vec<u8>> {
     use base64::engine::general_purpose;
@@ -281,4 +319,14 @@ mod tests {
         let decrypted = decrypt_with_password(password, &deserialized).unwrap();
         assert_eq!(plaintext, decrypted.as_slice());
     }
+
+    #[test]
+    fn test_raw_key_roundtrip() {
+        let key = [7u8; 32];
+        let sealed = encrypt_with_key(&key, b"device grant").unwrap();
+        assert_eq!(decrypt_with_key(&key, &sealed).unwrap(), b"device grant");
+
+        assert!(decrypt_with_key(&[8u8; 32], &sealed).is_err());
+        assert!(decrypt_with_key(&key, &sealed[..8]).is_err());
+    }
 }
@@ -2,9 +2,17 @@
 
 use dchat_core::error::{Error, Result};
 use ed25519_dalek::{SigningKey as Ed25519SigningKey, VerifyingKey as Ed25519VerifyingKey};
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
+use sha2::Sha512;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+/// Offset marking a derivation index as hardened (`i'`)
+pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+/// HMAC key for the SLIP-0010 Ed25519 master key
+const SLIP10_ED25519_SEED_KEY: &[u8] = b"ed25519 seed";
+
 /// A private key that automatically zeros itself when dropped
 #[derive(Clone, ZeroizeOnDrop, Zeroize)]
 pub struct PrivateKey {
@@ -137,6 +145,9 @@ impl KeyPair {
 }
 
 /// Derive keys using BIP-32 style hierarchical deterministic key derivation
+///
+/// Hashes every index the same way and has no chain code; identity keys use
+/// [`ExtendedPrivateKey`] (SLIP-0010) instead.
 pub struct KeyDerivation;
 
 impl KeyDerivation {
@@ -168,6 +179,89 @@ impl KeyDerivation {
     }
 }
 
+/// SLIP-0010 extended private key on Ed25519
+///
+/// Ed25519 only supports hardened derivation, so every child index must
+/// carry [`HARDENED_OFFSET`].
+#[derive(Clone, ZeroizeOnDrop, Zeroize)]
+pub struct ExtendedPrivateKey {
+    private_key: PrivateKey,
+    chain_code: [u8; 32],
+}
+
+impl ExtendedPrivateKey {
+    /// Derive the master key from a seed (16 to 64 bytes)
+    pub fn from_seed(seed: &[u8]) -> Result<Self> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(Error::crypto("Seed must be between 16 and 64 bytes"));
+        }
+        Ok(Self::from_hmac(SLIP10_ED25519_SEED_KEY, &[seed]))
+    }
+    
+    /// Derive a hardened child key
+    pub fn derive_child(&self, index: u32) -> Result<Self> {
+        if index < HARDENED_OFFSET {
+            return Err(Error::crypto(format!(
+                "Ed25519 only supports hardened derivation (index {})",
+                index
+            )));
+        }
+        Ok(Self::from_hmac(
+            &self.chain_code,
+            &[&[0u8], self.private_key.as_bytes(), &index.to_be_bytes()],
+        ))
+    }
+    
+    /// Derive along a path of hardened indices
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self> {
+        path.iter().try_fold(self.clone(), |key, &index| key.derive_child(index))
+    }
+    
+    /// Get the private key
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+    
+    /// Get the chain code
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+    
+    /// Keypair for the private key at this node
+    pub fn to_keypair(&self) -> KeyPair {
+        KeyPair::from_private_key(self.private_key.clone())
+    }
+    
+    fn from_hmac(key: &[u8], data: &[&[u8]]) -> Self {
+        let mut mac = Hmac::<Sha512>::new_from_slice(key)
+            .expect("HMAC accepts keys of any length");
+        for chunk in data {
+            mac.update(chunk);
+        }
+        let output = mac.finalize().into_bytes();
+        
+        let mut private_key = [0u8; 32];
+        let mut chain_code = [0u8; 32];
+        private_key.copy_from_slice(&output[..32]);
+        chain_code.copy_from_slice(&output[32..]);
+        
+        let key = Self {
+            private_key: PrivateKey::from_bytes(private_key),
+            chain_code,
+        };
+        private_key.zeroize();
+        key
+    }
+}
+
+impl std::fmt::Debug for ExtendedPrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExtendedPrivateKey")
+            .field("public_key", &self.private_key.public_key())
+            .finish_non_exhaustive()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,4 +306,106 @@ mod tests {
         
         assert_eq!(derived.as_bytes(), step4.as_bytes());
     }
-}
\ No newline at end of file
+    
+    /// (path, chain code, private key, public key) from the SLIP-0010 spec
+    type Slip10Vector = (&'static [u32], &'static str, &'static str, &'static str);
+    
+    fn check_slip10_vectors(seed_hex: &str, vectors: &[Slip10Vector]) {
+        let seed = hex::decode(seed_hex).unwrap();
+        let master = ExtendedPrivateKey::from_seed(&seed).unwrap();
+        
+        for (path, chain_code, private_key, public_key) in vectors {
+            let hardened: Vec<u32> = path.iter().map(|i| i + HARDENED_OFFSET).collect();
+            let node = master.derive_path(&hardened).unwrap();
+            assert_eq!(hex::encode(node.chain_code()), *chain_code, "chain code for {:?}", path);
+            assert_eq!(hex::encode(node.private_key().as_bytes()), *private_key, "private key for {:?}", path);
+            // SLIP-0010 prefixes Ed25519 public keys with a zero byte
+            assert_eq!(
+                format!("00{}", hex::encode(node.to_keypair().public_key().as_bytes())),
+                *public_key,
+                "public key for {:?}",
+                path
+            );
+        }
+    }
+    
+    #[test]
+    fn test_slip10_ed25519_vector_1() {
+        check_slip10_vectors(
+            "000102030405060708090a0b0c0d0e0f",
+            &[
+                (
+                    &[],
+                    "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb",
+                    "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+                    "00a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed",
+                ),
+                (
+                    &[0],
+                    "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69",
+                    "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+                    "008c8a13df77a28f3445213a0f432fde644acaa215fc72dcdf300d5efaa85d350c",
+                ),
+                (
+                    &[0, 1],
+                    "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14",
+                    "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+                    "001932a5270f335bed617d5b935c80aedb1a35bd9fc1e31acafd5372c30f5c1187",
+                ),
+                (
+                    &[0, 1, 2],
+                    "2e69929e00b5ab250f49c3fb1c12f252de4fed2c1db88387094a0f8c4c9ccd6c",
+                    "92a5b23c0b8a99e37d07df3fb9966917f5d06e02ddbd909c7e184371463e9fc9",
+                    "00ae98736566d30ed0e9d2f4486a64bc95740d89c7db33f52121f8ea8f76ff0fc1",
+                ),
+                (
+                    &[0, 1, 2, 2],
+                    "8f6d87f93d750e0efccda017d662a1b31a266e4a6f5993b15f5c1f07f74dd5cc",
+                    "30d1dc7e5fc04c31219ab25a27ae00b50f6fd66622f6e9c913253d6511d1e662",
+                    "008abae2d66361c879b900d204ad2cc4984fa2aa344dd7ddc46007329ac76c429c",
+                ),
+                (
+                    &[0, 1, 2, 2, 1000000000],
+                    "68789923a0cac2cd5a29172a475fe9e0fb14cd6adb5ad98a3fa70333e7afa230",
+                    "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793",
+                    "003c24da049451555d51a7014a37337aa4e12d41e485abccfa46b47dfb2af54b7a",
+                ),
+            ],
+        );
+    }
+    
+    #[test]
+    fn test_slip10_ed25519_vector_2() {
+        check_slip10_vectors(
+            "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542",
+            &[
+                (
+                    &[],
+                    "ef70a74db9c3a5af931b5fe73ed8e1a53464133654fd55e7a66f8570b8e33c3b",
+                    "171cb88b1b3c1db25add599712e36245d75bc65a1a5c9e18d76f9f2b1eab4012",
+                    "008fe9693f8fa62a4305a140b9764c5ee01e455963744fe18204b4fb948249308a",
+                ),
+                (
+                    &[0],
+                    "0b78a3226f915c082bf118f83618a618ab6dec793752624cbeb622acb562862d",
+                    "1559eb2bbec5790b0c65d8693e4d0875b1747f4970ae8b650486ed7470845635",
+                    "0086fab68dcb57aa196c77c5f264f215a112c22a912c10d123b0d03c3c28ef1037",
+                ),
+                (
+                    &[0, 2147483647],
+                    "138f0b2551bcafeca6ff2aa88ba8ed0ed8de070841f0c4ef0165df8181eaad7f",
+                    "ea4f5bfe8694d8bb74b7b59404632fd5968b774ed545e810de9c32a4fb4192f4",
+                    "005ba3b9ac6e90e83effcd25ac4e58a1365a9e35a3d3ae5eb07b9e4d90bcf7506d",
+                ),
+            ],
+        );
+    }
+    
+    #[test]
+    fn test_slip10_rejects_non_hardened_index() {
+        let master = ExtendedPrivateKey::from_seed(&[7u8; 32]).unwrap();
+        assert!(master.derive_child(0).is_err());
+        assert!(master.derive_child(HARDENED_OFFSET).is_ok());
+        assert!(ExtendedPrivateKey::from_seed(&[0u8; 8]).is_err());
+    }
+}
@@ -4,6 +4,9 @@
 //! - Noise Protocol implementation for end-to-end encryption
 //! - Key management and rotation
 //! - Digital signatures
+//! - Password-authenticated key exchange (CPace)
+//! - Threshold encryption to a committee key
+//! - Domain-separated Merkle trees and inclusion proofs
 //! - Post-quantum cryptography support
 //! - Zero-knowledge proofs
 
@@ -14,13 +17,18 @@ pub mod post_quantum;
 pub mod kdf;
 pub mod rotation;
 pub mod handshake;
+pub mod pake;
+pub mod threshold;
+pub mod merkle;
 mod encryption;
 
-pub use keys::{KeyPair, PrivateKey, PublicKey as CryptoPublicKey};
+pub use keys::{ExtendedPrivateKey, KeyPair, PrivateKey, PublicKey as CryptoPublicKey};
 pub use signatures::{SigningKey, VerifyingKey, sign, verify};
 pub use noise::{NoiseSession, NoiseHandshake};
 pub use rotation::{KeyRotationManager, RotationPolicy};
-pub use encryption::{encrypt_with_password, decrypt_with_password};
+pub use encryption::{encrypt_with_password, decrypt_with_password, encrypt_with_key, decrypt_with_key};
+pub use pake::{CpaceExchange, PakeRole, PakeSessionKey};
+pub use threshold::{DecryptionShare, ThresholdCiphertext, ThresholdKeyShare, ThresholdPublicKey};
 
 use dchat_core::error::{Error, Result};
 
@@ -0,0 +1,205 @@
+//! Domain-separated binary Merkle trees
+//!
+//! Leaves and interior nodes are hashed with distinct prefixes (as in
+//! RFC 6962), so a leaf can never be passed off as an interior node. A node
+//! without a sibling is promoted to the next level unchanged instead of being
+//! paired with a copy of itself, which keeps trees of different sizes from
+//! sharing a root.
+
+use serde::{Deserialize, Serialize};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Hash of a leaf's data
+pub fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(data);
+    *hasher.finalize().as_bytes()
+}
+
+/// Hash of an interior node
+pub fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+/// Root of a tree with no leaves
+pub fn empty_root() -> [u8; 32] {
+    *blake3::hash(&[]).as_bytes()
+}
+
+/// A Merkle tree with every level kept for proof generation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// Level 0 holds the leaf hashes; the last level holds the root
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    /// Build a tree over leaves in the given order
+    pub fn from_leaves<I, T>(leaves: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        Self::from_leaf_hashes(leaves.into_iter().map(|leaf| leaf_hash(leaf.as_ref())).collect())
+    }
+
+    /// Build a tree over already-hashed leaves
+    pub fn from_leaf_hashes(leaf_hashes: Vec<[u8; 32]>) -> Self {
+        let mut levels = vec![leaf_hashes];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    /// Number of leaves
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Whether the tree has no leaves
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Root hash
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().unwrap().first().copied().unwrap_or_else(empty_root)
+    }
+
+    /// Hash of the leaf at `index`
+    pub fn leaf(&self, index: usize) -> Option<[u8; 32]> {
+        self.levels[0].get(index).copied()
+    }
+
+    /// Authentication path for the leaf at `index`
+    pub fn path(&self, index: usize) -> Option<MerklePath> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut siblings = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            if sibling < level.len() {
+                siblings.push(level[sibling]);
+            }
+            position /= 2;
+        }
+
+        Some(MerklePath {
+            leaf_index: index as u64,
+            leaf_count: self.len() as u64,
+            siblings,
+        })
+    }
+}
+
+/// Sibling hashes from a leaf up to the root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerklePath {
+    /// Position of the leaf
+    pub leaf_index: u64,
+    /// Number of leaves in the tree
+    pub leaf_count: u64,
+    /// Sibling hashes, bottom level first; promoted levels have none
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl MerklePath {
+    /// Root implied by this path and a leaf hash
+    ///
+    /// Returns `None` if the path does not fit a tree of `leaf_count` leaves.
+    pub fn compute_root(&self, leaf_hash: [u8; 32]) -> Option<[u8; 32]> {
+        if self.leaf_index >= self.leaf_count {
+            return None;
+        }
+
+        let mut hash = leaf_hash;
+        let mut position = self.leaf_index;
+        let mut width = self.leaf_count;
+        let mut siblings = self.siblings.iter();
+        while width > 1 {
+            let is_right = position % 2 == 1;
+            if is_right {
+                hash = node_hash(siblings.next()?, &hash);
+            } else if position + 1 < width {
+                hash = node_hash(&hash, siblings.next()?);
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+
+        // Every sibling must have been used
+        siblings.next().is_none().then_some(hash)
+    }
+
+    /// Check that `leaf` is at this path's position under `root`
+    pub fn verify(&self, leaf: &[u8], root: &[u8]) -> bool {
+        self.compute_root(leaf_hash(leaf))
+            .is_some_and(|computed| computed.as_slice() == root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_leaf_proves_for_all_sizes() {
+        for size in 1..=17u32 {
+            let leaves: Vec<[u8; 4]> = (0..size).map(|i| i.to_be_bytes()).collect();
+            let tree = MerkleTree::from_leaves(&leaves);
+            let root = tree.root();
+
+            for (index, leaf) in leaves.iter().enumerate() {
+                let path = tree.path(index).unwrap();
+                assert!(path.verify(leaf, &root), "size {} index {}", size, index);
+                assert!(!path.verify(b"other", &root));
+
+                // The same siblings do not prove another position
+                let mut moved = path.clone();
+                moved.leaf_index = (moved.leaf_index + 1) % moved.leaf_count;
+                if size > 1 {
+                    assert!(!moved.verify(leaf, &root));
+                }
+            }
+            assert!(tree.path(size as usize).is_none());
+        }
+    }
+
+    #[test]
+    fn test_domain_separation() {
+        let tree = MerkleTree::from_leaves([b"a", b"b"]);
+
+        // The root is not a valid leaf, and an interior node cannot be
+        // presented as a leaf of a shorter tree
+        let interior = MerkleTree::from_leaves([tree.leaf(0).unwrap(), tree.leaf(1).unwrap()]);
+        assert_ne!(interior.root(), tree.root());
+
+        // An odd leaf is promoted, not duplicated
+        let three = MerkleTree::from_leaves([b"a", b"b", b"c"]);
+        let four = MerkleTree::from_leaves([b"a", b"b", b"c", b"c"]);
+        assert_ne!(three.root(), four.root());
+
+        assert_eq!(MerkleTree::from_leaves(Vec::<Vec<u8>>::new()).root(), empty_root());
+    }
+}
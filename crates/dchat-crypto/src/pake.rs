@@ -0,0 +1,228 @@
+//! CPace password-authenticated key exchange
+//!
+//! Lets two parties that share only a short, low-entropy code (e.g. a device
+//! pairing code) agree on a strong session key. A passive observer learns
+//! nothing about the code, and an active attacker gets exactly one online
+//! guess per exchange. The construction follows CPace over ristretto255:
+//! both sides derive a generator from the code and session ID, exchange
+//! `y·G`, and hash the resulting Diffie-Hellman point into the session key.
+
+use crate::encryption::{decrypt_with_key, encrypt_with_key};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::traits::IsIdentity;
+use curve25519_dalek::Scalar;
+use dchat_core::error::{Error, Result};
+use rand::RngCore;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Domain separation contexts
+const GENERATOR_CONTEXT: &str = "dchat 2024 cpace generator";
+const SESSION_KEY_CONTEXT: &str = "dchat 2024 cpace session key";
+const CONFIRMATION_CONTEXT: &str = "dchat 2024 cpace key confirmation";
+const ENCRYPTION_CONTEXT: &str = "dchat 2024 cpace encryption key";
+
+/// Which side of the exchange we are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PakeRole {
+    /// Sends the first share
+    Initiator,
+    /// Answers the initiator's share
+    Responder,
+}
+
+impl PakeRole {
+    fn label(self) -> &'static [u8] {
+        match self {
+            PakeRole::Initiator => b"initiator",
+            PakeRole::Responder => b"responder",
+        }
+    }
+
+    fn peer(self) -> Self {
+        match self {
+            PakeRole::Initiator => PakeRole::Responder,
+            PakeRole::Responder => PakeRole::Initiator,
+        }
+    }
+}
+
+/// One side of a CPace exchange, before the peer's share has arrived
+pub struct CpaceExchange {
+    role: PakeRole,
+    session_id: Vec<u8>,
+    secret: Scalar,
+    share: [u8; 32],
+}
+
+impl CpaceExchange {
+    /// Start an exchange for `password` bound to `session_id`
+    ///
+    /// Both sides must use the same session ID; it should be fresh for every
+    /// pairing attempt so shares cannot be replayed across sessions.
+    pub fn new(role: PakeRole, password: &[u8], session_id: &[u8]) -> Self {
+        let generator = derive_generator(password, session_id);
+
+        let mut wide = [0u8; 64];
+        rand::thread_rng().fill_bytes(&mut wide);
+        let secret = Scalar::from_bytes_mod_order_wide(&wide);
+        wide.zeroize();
+
+        let share = (secret * generator).compress().to_bytes();
+
+        Self {
+            role,
+            session_id: session_id.to_vec(),
+            secret,
+            share,
+        }
+    }
+
+    /// Our public share, to send to the peer
+    pub fn share(&self) -> [u8; 32] {
+        self.share
+    }
+
+    /// Combine our secret with the peer's share
+    ///
+    /// Succeeds even when the codes differ; a mismatch only shows up when
+    /// the key confirmation tags are compared.
+    pub fn finish(mut self, peer_share: &[u8; 32]) -> Result<PakeSessionKey> {
+        let peer_point = CompressedRistretto(*peer_share)
+            .decompress()
+            .ok_or_else(|| Error::crypto("Invalid PAKE share".to_string()))?;
+        if peer_point.is_identity() {
+            return Err(Error::crypto("Invalid PAKE share".to_string()));
+        }
+
+        let shared = (self.secret * peer_point).compress();
+        self.secret.zeroize();
+
+        let (initiator_share, responder_share) = match self.role {
+            PakeRole::Initiator => (&self.share, peer_share),
+            PakeRole::Responder => (peer_share, &self.share),
+        };
+
+        let mut hasher = blake3::Hasher::new_derive_key(SESSION_KEY_CONTEXT);
+        hasher.update(&(self.session_id.len() as u64).to_le_bytes());
+        hasher.update(&self.session_id);
+        hasher.update(shared.as_bytes());
+        hasher.update(initiator_share);
+        hasher.update(responder_share);
+
+        Ok(PakeSessionKey {
+            role: self.role,
+            key: *hasher.finalize().as_bytes(),
+        })
+    }
+}
+
+/// Session key agreed by a completed CPace exchange
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct PakeSessionKey {
+    #[zeroize(skip)]
+    role: PakeRole,
+    key: [u8; 32],
+}
+
+impl PakeSessionKey {
+    /// Key confirmation tag to send to the peer
+    pub fn confirmation(&self) -> [u8; 32] {
+        self.confirmation_for(self.role)
+    }
+
+    /// Check the peer's confirmation tag; fails if the codes differed
+    pub fn verify_confirmation(&self, tag: &[u8; 32]) -> Result<()> {
+        let expected = self.confirmation_for(self.role.peer());
+        if crate::constant_time_eq(&expected, tag) {
+            Ok(())
+        } else {
+            Err(Error::crypto("PAKE key confirmation failed".to_string()))
+        }
+    }
+
+    /// Encrypt a message to the peer under the session key
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        encrypt_with_key(&self.encryption_key(), plaintext)
+    }
+
+    /// Decrypt a message sealed by the peer
+    pub fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        decrypt_with_key(&self.encryption_key(), ciphertext)
+    }
+
+    fn confirmation_for(&self, role: PakeRole) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_derive_key(CONFIRMATION_CONTEXT);
+        hasher.update(&self.key);
+        hasher.update(role.label());
+        *hasher.finalize().as_bytes()
+    }
+
+    fn encryption_key(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_derive_key(ENCRYPTION_CONTEXT);
+        hasher.update(&self.key);
+        *hasher.finalize().as_bytes()
+    }
+}
+
+impl std::fmt::Debug for PakeSessionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PakeSessionKey")
+            .field("role", &self.role)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Map the password and session ID to a generator with unknown discrete log
+fn derive_generator(password: &[u8], session_id: &[u8]) -> RistrettoPoint {
+    let mut hasher = blake3::Hasher::new_derive_key(GENERATOR_CONTEXT);
+    hasher.update(&(password.len() as u64).to_le_bytes());
+    hasher.update(password);
+    hasher.update(&(session_id.len() as u64).to_le_bytes());
+    hasher.update(session_id);
+
+    let mut uniform = [0u8; 64];
+    hasher.finalize_xof().fill(&mut uniform);
+    RistrettoPoint::from_uniform_bytes(&uniform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(initiator_code: &[u8], responder_code: &[u8]) -> (PakeSessionKey, PakeSessionKey) {
+        let sid = b"pairing-session";
+        let initiator = CpaceExchange::new(PakeRole::Initiator, initiator_code, sid);
+        let responder = CpaceExchange::new(PakeRole::Responder, responder_code, sid);
+        let (a_share, b_share) = (initiator.share(), responder.share());
+        (initiator.finish(&b_share).unwrap(), responder.finish(&a_share).unwrap())
+    }
+
+    #[test]
+    fn test_matching_codes_agree() {
+        let (a, b) = run(b"482913", b"482913");
+        assert!(a.verify_confirmation(&b.confirmation()).is_ok());
+        assert!(b.verify_confirmation(&a.confirmation()).is_ok());
+        // Tags are role-bound, so reflecting a tag back does not verify
+        assert!(a.verify_confirmation(&a.confirmation()).is_err());
+
+        let sealed = a.seal(b"device key").unwrap();
+        assert_eq!(b.open(&sealed).unwrap(), b"device key");
+    }
+
+    #[test]
+    fn test_wrong_code_fails_confirmation() {
+        let (a, b) = run(b"482913", b"482914");
+        assert!(a.verify_confirmation(&b.confirmation()).is_err());
+        assert!(b.verify_confirmation(&a.confirmation()).is_err());
+        assert!(b.open(&a.seal(b"secret").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_share_rejected() {
+        let exchange = CpaceExchange::new(PakeRole::Initiator, b"code", b"sid");
+        assert!(exchange.finish(&[0u8; 32]).is_err());
+
+        let exchange = CpaceExchange::new(PakeRole::Initiator, b"code", b"sid");
+        assert!(exchange.finish(&[0xff; 32]).is_err());
+    }
+}
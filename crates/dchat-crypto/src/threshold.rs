@@ -0,0 +1,387 @@
+//! Threshold ElGamal encryption over ristretto255
+//!
+//! A committee of `n` key holders shares one decryption key so that any `t`
+//! of them can decrypt, and fewer than `t` learn nothing. Messages are
+//! encrypted to the committee's group key with hashed ElGamal and
+//! AES-256-GCM. To decrypt, each holder publishes a decryption share
+//! `x_i·U` together with a Chaum-Pedersen proof that it used the same secret
+//! as its public share, so a bad share is rejected instead of silently
+//! corrupting the result.
+//!
+//! Keys come from a trusted dealer ([`deal`]); the dealer must discard the
+//! polynomial after handing out the shares.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::traits::IsIdentity;
+use curve25519_dalek::Scalar;
+use dchat_core::error::{Error, Result};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Domain separation contexts
+const ENCRYPTION_KEY_CONTEXT: &str = "dchat 2024 threshold elgamal key";
+const PROOF_CONTEXT: &str = "dchat 2024 threshold decryption share proof";
+
+/// Public parameters of a threshold committee
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThresholdPublicKey {
+    /// Shares needed to decrypt
+    pub threshold: u16,
+    /// Committee key messages are encrypted to
+    pub group_key: [u8; 32],
+    /// Public share of holder `i + 1`, used to verify its decryption shares
+    pub share_keys: Vec<[u8; 32]>,
+}
+
+/// One holder's share of the committee decryption key
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct ThresholdKeyShare {
+    index: u16,
+    secret: [u8; 32],
+}
+
+/// A message encrypted to a committee
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThresholdCiphertext {
+    /// Ephemeral ElGamal point `U = r·G`
+    pub ephemeral: [u8; 32],
+    pub nonce: [u8; 12],
+    /// AES-256-GCM ciphertext and tag
+    pub ciphertext: Vec<u8>,
+}
+
+/// One holder's contribution to decrypting a ciphertext
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecryptionShare {
+    /// Holder index, starting at 1
+    pub index: u16,
+    /// `x_i·U`
+    pub point: [u8; 32],
+    /// Chaum-Pedersen proof challenge
+    pub challenge: [u8; 32],
+    /// Chaum-Pedersen proof response
+    pub response: [u8; 32],
+}
+
+/// Split a fresh committee key into `holders` shares, any `threshold` of
+/// which can decrypt
+pub fn deal<R: RngCore + CryptoRng>(
+    threshold: u16,
+    holders: u16,
+    rng: &mut R,
+) -> Result<(ThresholdPublicKey, Vec<ThresholdKeyShare>)> {
+    if threshold == 0 || threshold > holders {
+        return Err(Error::crypto(format!(
+            "Invalid threshold {} of {} holders",
+            threshold, holders
+        )));
+    }
+
+    let mut coefficients: Vec<Scalar> = (0..threshold).map(|_| random_scalar(rng)).collect();
+    let group_key = (coefficients[0] * RISTRETTO_BASEPOINT_POINT).compress().to_bytes();
+
+    let mut share_keys = Vec::with_capacity(holders as usize);
+    let mut shares = Vec::with_capacity(holders as usize);
+    for index in 1..=holders {
+        // Horner evaluation of the polynomial at x = index
+        let x = Scalar::from(index as u64);
+        let secret = coefficients.iter().rev().fold(Scalar::ZERO, |acc, c| acc * x + c);
+        share_keys.push((secret * RISTRETTO_BASEPOINT_POINT).compress().to_bytes());
+        shares.push(ThresholdKeyShare {
+            index,
+            secret: secret.to_bytes(),
+        });
+    }
+    coefficients.zeroize();
+
+    Ok((
+        ThresholdPublicKey {
+            threshold,
+            group_key,
+            share_keys,
+        },
+        shares,
+    ))
+}
+
+impl ThresholdPublicKey {
+    /// Number of key holders in the committee
+    pub fn holders(&self) -> usize {
+        self.share_keys.len()
+    }
+
+    /// Encrypt `plaintext` to the committee, bound to `aad`
+    ///
+    /// The same `aad` must be supplied to [`combine`](Self::combine); use it
+    /// to tie a ciphertext to its context so it cannot be replayed elsewhere.
+    pub fn encrypt<R: RngCore + CryptoRng>(
+        &self,
+        plaintext: &[u8],
+        aad: &[u8],
+        rng: &mut R,
+    ) -> Result<ThresholdCiphertext> {
+        let group_key = decode_point(&self.group_key, "group key")?;
+
+        let mut r = random_scalar(rng);
+        let ephemeral = (r * RISTRETTO_BASEPOINT_POINT).compress().to_bytes();
+        let shared = r * group_key;
+        r.zeroize();
+
+        let mut nonce = [0u8; 12];
+        rng.fill_bytes(&mut nonce);
+
+        let cipher = cipher_for(&shared, &ephemeral);
+        let ciphertext = cipher
+            .encrypt(&Nonce::from(nonce), Payload { msg: plaintext, aad })
+            .map_err(|_| Error::crypto("Threshold encryption failed".to_string()))?;
+
+        Ok(ThresholdCiphertext {
+            ephemeral,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Check a decryption share against its holder's public share
+    pub fn verify_share(&self, ciphertext: &ThresholdCiphertext, share: &DecryptionShare) -> bool {
+        self.check_share(ciphertext, share).is_ok()
+    }
+
+    fn check_share(&self, ciphertext: &ThresholdCiphertext, share: &DecryptionShare) -> Result<RistrettoPoint> {
+        let share_key = share
+            .index
+            .checked_sub(1)
+            .and_then(|i| self.share_keys.get(i as usize))
+            .ok_or_else(|| Error::crypto(format!("Unknown key holder {}", share.index)))?;
+        let share_key = decode_point(share_key, "share key")?;
+        let ephemeral = decode_point(&ciphertext.ephemeral, "ephemeral key")?;
+        let point = decode_point(&share.point, "decryption share")?;
+        let challenge = decode_scalar(&share.challenge)?;
+        let response = decode_scalar(&share.response)?;
+
+        let commitment_g = response * RISTRETTO_BASEPOINT_POINT + challenge * share_key;
+        let commitment_u = response * ephemeral + challenge * point;
+        let expected = proof_challenge(
+            share.index,
+            &share_key,
+            &ephemeral,
+            &point,
+            &commitment_g,
+            &commitment_u,
+        );
+        if expected != challenge {
+            return Err(Error::crypto(format!(
+                "Invalid decryption share from holder {}",
+                share.index
+            )));
+        }
+        Ok(point)
+    }
+
+    /// Decrypt with at least `threshold` verified shares from distinct holders
+    pub fn combine(
+        &self,
+        ciphertext: &ThresholdCiphertext,
+        shares: &[DecryptionShare],
+        aad: &[u8],
+    ) -> Result<Vec<u8>> {
+        let mut seen = BTreeSet::new();
+        let mut points = Vec::with_capacity(self.threshold as usize);
+        for share in shares {
+            if points.len() == self.threshold as usize {
+                break;
+            }
+            if !seen.insert(share.index) {
+                continue;
+            }
+            points.push((share.index, self.check_share(ciphertext, share)?));
+        }
+        if points.len() < self.threshold as usize {
+            return Err(Error::crypto(format!(
+                "Need {} decryption shares, have {}",
+                self.threshold,
+                points.len()
+            )));
+        }
+
+        // Lagrange interpolation of x·U at zero
+        let shared = points.iter().fold(RistrettoPoint::default(), |acc, (i, point)| {
+            acc + lagrange_at_zero(*i, points.iter().map(|(j, _)| *j)) * point
+        });
+
+        let cipher = cipher_for(&shared, &ciphertext.ephemeral);
+        cipher
+            .decrypt(
+                &Nonce::from(ciphertext.nonce),
+                Payload {
+                    msg: &ciphertext.ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| Error::crypto("Threshold decryption failed".to_string()))
+    }
+}
+
+impl ThresholdKeyShare {
+    /// Holder index, starting at 1
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Produce this holder's verifiable decryption share for `ciphertext`
+    pub fn decryption_share<R: RngCore + CryptoRng>(
+        &self,
+        ciphertext: &ThresholdCiphertext,
+        rng: &mut R,
+    ) -> Result<DecryptionShare> {
+        let ephemeral = decode_point(&ciphertext.ephemeral, "ephemeral key")?;
+        let mut secret = decode_scalar(&self.secret)?;
+        let share_key = secret * RISTRETTO_BASEPOINT_POINT;
+        let point = secret * ephemeral;
+
+        let mut nonce = random_scalar(rng);
+        let commitment_g = nonce * RISTRETTO_BASEPOINT_POINT;
+        let commitment_u = nonce * ephemeral;
+        let challenge = proof_challenge(self.index, &share_key, &ephemeral, &point, &commitment_g, &commitment_u);
+        let response = nonce - challenge * secret;
+        nonce.zeroize();
+        secret.zeroize();
+
+        Ok(DecryptionShare {
+            index: self.index,
+            point: point.compress().to_bytes(),
+            challenge: challenge.to_bytes(),
+            response: response.to_bytes(),
+        })
+    }
+}
+
+impl std::fmt::Debug for ThresholdKeyShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThresholdKeyShare")
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+fn random_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar {
+    let mut wide = [0u8; 64];
+    rng.fill_bytes(&mut wide);
+    let scalar = Scalar::from_bytes_mod_order_wide(&wide);
+    wide.zeroize();
+    scalar
+}
+
+fn decode_point(bytes: &[u8; 32], what: &str) -> Result<RistrettoPoint> {
+    CompressedRistretto(*bytes)
+        .decompress()
+        .filter(|point| !point.is_identity())
+        .ok_or_else(|| Error::crypto(format!("Invalid {}", what)))
+}
+
+fn decode_scalar(bytes: &[u8; 32]) -> Result<Scalar> {
+    Option::from(Scalar::from_canonical_bytes(*bytes))
+        .ok_or_else(|| Error::crypto("Invalid scalar encoding".to_string()))
+}
+
+fn cipher_for(shared: &RistrettoPoint, ephemeral: &[u8; 32]) -> Aes256Gcm {
+    let mut hasher = blake3::Hasher::new_derive_key(ENCRYPTION_KEY_CONTEXT);
+    hasher.update(shared.compress().as_bytes());
+    hasher.update(ephemeral);
+    let mut key = *hasher.finalize().as_bytes();
+    let cipher = Aes256Gcm::new_from_slice(&key).expect("32-byte key");
+    key.zeroize();
+    cipher
+}
+
+fn proof_challenge(
+    index: u16,
+    share_key: &RistrettoPoint,
+    ephemeral: &RistrettoPoint,
+    point: &RistrettoPoint,
+    commitment_g: &RistrettoPoint,
+    commitment_u: &RistrettoPoint,
+) -> Scalar {
+    let mut hasher = blake3::Hasher::new_derive_key(PROOF_CONTEXT);
+    hasher.update(&index.to_le_bytes());
+    for p in [share_key, ephemeral, point, commitment_g, commitment_u] {
+        hasher.update(p.compress().as_bytes());
+    }
+    let mut wide = [0u8; 64];
+    hasher.finalize_xof().fill(&mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+/// Lagrange coefficient for holder `i` evaluated at zero
+fn lagrange_at_zero(i: u16, indices: impl Iterator<Item = u16>) -> Scalar {
+    let xi = Scalar::from(i as u64);
+    let (numerator, denominator) = indices
+        .filter(|j| *j != i)
+        .fold((Scalar::ONE, Scalar::ONE), |(num, den), j| {
+            let xj = Scalar::from(j as u64);
+            (num * xj, den * (xj - xi))
+        });
+    numerator * denominator.invert()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_any_threshold_subset_decrypts() {
+        let (public, shares) = deal(3, 5, &mut OsRng).unwrap();
+        let ciphertext = public.encrypt(b"ballot", b"proposal-1", &mut OsRng).unwrap();
+
+        for subset in [[0, 1, 2], [2, 3, 4], [0, 2, 4]] {
+            let decryption: Vec<_> = subset
+                .iter()
+                .map(|&i| shares[i].decryption_share(&ciphertext, &mut OsRng).unwrap())
+                .collect();
+            assert_eq!(public.combine(&ciphertext, &decryption, b"proposal-1").unwrap(), b"ballot");
+        }
+
+        // Fewer than the threshold is not enough, and duplicates do not count twice
+        let one = shares[0].decryption_share(&ciphertext, &mut OsRng).unwrap();
+        let two = shares[1].decryption_share(&ciphertext, &mut OsRng).unwrap();
+        assert!(public.combine(&ciphertext, &[one.clone(), two.clone()], b"proposal-1").is_err());
+        assert!(public.combine(&ciphertext, &[one.clone(), one, two], b"proposal-1").is_err());
+    }
+
+    #[test]
+    fn test_bad_shares_and_context_are_rejected() {
+        let (public, shares) = deal(2, 3, &mut OsRng).unwrap();
+        let ciphertext = public.encrypt(b"ballot", b"voter-a", &mut OsRng).unwrap();
+        let good = shares[0].decryption_share(&ciphertext, &mut OsRng).unwrap();
+        let other = shares[1].decryption_share(&ciphertext, &mut OsRng).unwrap();
+        assert!(public.verify_share(&ciphertext, &good));
+
+        // A share claiming another holder's index fails its proof
+        let mut forged = other.clone();
+        forged.index = 3;
+        assert!(!public.verify_share(&ciphertext, &forged));
+        assert!(public.combine(&ciphertext, &[good.clone(), forged], b"voter-a").is_err());
+
+        // A share computed for a different ciphertext does not verify
+        let unrelated = public.encrypt(b"ballot", b"voter-a", &mut OsRng).unwrap();
+        let stale = shares[1].decryption_share(&unrelated, &mut OsRng).unwrap();
+        assert!(!public.verify_share(&ciphertext, &stale));
+
+        // The ciphertext is bound to its associated data
+        assert!(public.combine(&ciphertext, &[good, other], b"voter-b").is_err());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(deal(0, 3, &mut OsRng).is_err());
+        assert!(deal(4, 3, &mut OsRng).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+// Liquid Delegation
+//
+// A user can hand their voting power on one topic (proposal type) to
+// another user, who may in turn delegate further. Power flows along the
+// chain to the first delegate who actually votes; a delegator who votes
+// directly keeps their own power.
+//
+// Delegations are kept as a history rather than overwritten, so a tally can
+// use the graph exactly as it stood at a proposal's deadline. Changing or
+// revoking a delegation after ballots are revealed cannot move power.
+
+use crate::voting::ProposalType;
+use dchat_core::{Error, Result, UserId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Longest delegation chain followed when resolving power
+pub const MAX_DELEGATION_DEPTH: usize = 32;
+
+/// One delegation of power on a topic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delegation {
+    pub delegator: UserId,
+    pub delegate: UserId,
+    pub topic: ProposalType,
+    /// Power the delegator hands over
+    pub voting_power: u64,
+    pub since: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Delegation {
+    /// Whether the delegation was in force at `at`
+    pub fn is_active_at(&self, at: DateTime<Utc>) -> bool {
+        self.since <= at && self.revoked_at.is_none_or(|revoked| revoked > at)
+    }
+}
+
+/// Per-topic delegation graph
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DelegationRegistry {
+    /// Delegation history per topic and delegator, oldest first
+    history: HashMap<ProposalType, HashMap<UserId, Vec<Delegation>>>,
+}
+
+impl DelegationRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delegate `voting_power` on `topic` from `delegator` to `delegate`
+    ///
+    /// Replaces any current delegation by the same user on the topic. Fails
+    /// if the delegate already delegates (directly or transitively) back to
+    /// the delegator.
+    pub fn delegate(
+        &mut self,
+        delegator: UserId,
+        delegate: UserId,
+        topic: ProposalType,
+        voting_power: u64,
+    ) -> Result<()> {
+        self.delegate_at(delegator, delegate, topic, voting_power, Utc::now())
+    }
+
+    /// [`delegate`](Self::delegate) as of `at`, which must not be earlier
+    /// than the delegator's current delegation on the topic
+    pub fn delegate_at(
+        &mut self,
+        delegator: UserId,
+        delegate: UserId,
+        topic: ProposalType,
+        voting_power: u64,
+        at: DateTime<Utc>,
+    ) -> Result<()> {
+        if delegator == delegate {
+            return Err(Error::validation("Cannot delegate to yourself".to_string()));
+        }
+
+        if self.chain_reaches(&delegate, &delegator, &topic, at) {
+            return Err(Error::validation("Delegation would create a cycle".to_string()));
+        }
+
+        let entries = self.history.entry(topic.clone()).or_default().entry(delegator.clone()).or_default();
+        if entries.last().is_some_and(|last| last.since > at) {
+            return Err(Error::validation("Delegation predates the current one".to_string()));
+        }
+        if let Some(current) = entries.last_mut().filter(|d| d.revoked_at.is_none()) {
+            current.revoked_at = Some(at);
+        }
+        entries.push(Delegation {
+            delegator,
+            delegate,
+            topic,
+            voting_power,
+            since: at,
+            revoked_at: None,
+        });
+        Ok(())
+    }
+
+    /// Revoke a user's current delegation on a topic
+    ///
+    /// Returns `false` if there was nothing to revoke.
+    pub fn revoke(&mut self, delegator: &UserId, topic: &ProposalType) -> bool {
+        self.revoke_at(delegator, topic, Utc::now())
+    }
+
+    /// [`revoke`](Self::revoke) as of `at`; a time before the delegation
+    /// started revokes it from its start
+    pub fn revoke_at(&mut self, delegator: &UserId, topic: &ProposalType, at: DateTime<Utc>) -> bool {
+        let current = self
+            .history
+            .get_mut(topic)
+            .and_then(|users| users.get_mut(delegator))
+            .and_then(|entries| entries.last_mut())
+            .filter(|d| d.revoked_at.is_none());
+        match current {
+            Some(delegation) => {
+                delegation.revoked_at = Some(at.max(delegation.since));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The delegation a user had in force on a topic at `at`
+    pub fn delegation_at(&self, delegator: &UserId, topic: &ProposalType, at: DateTime<Utc>) -> Option<&Delegation> {
+        self.history
+            .get(topic)?
+            .get(delegator)?
+            .iter()
+            .rev()
+            .find(|d| d.is_active_at(at))
+    }
+
+    /// Current delegate of a user on a topic
+    pub fn current_delegate(&self, delegator: &UserId, topic: &ProposalType) -> Option<&UserId> {
+        self.delegation_at(delegator, topic, Utc::now()).map(|d| &d.delegate)
+    }
+
+    /// All delegations in force on a topic at `at`
+    pub fn active_at(&self, topic: &ProposalType, at: DateTime<Utc>) -> Vec<&Delegation> {
+        self.history
+            .get(topic)
+            .map(|users| {
+                users
+                    .values()
+                    .filter_map(|entries| entries.iter().rev().find(|d| d.is_active_at(at)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Follow `delegator`'s chain at `at` to the first user for whom
+    /// `has_voted` holds
+    ///
+    /// Returns `None` if the chain ends, loops or exceeds
+    /// [`MAX_DELEGATION_DEPTH`] without reaching a voter.
+    pub fn resolve(
+        &self,
+        delegator: &UserId,
+        topic: &ProposalType,
+        at: DateTime<Utc>,
+        has_voted: impl Fn(&UserId) -> bool,
+    ) -> Option<&UserId> {
+        let mut visited = HashSet::from([delegator]);
+        let mut current = delegator;
+        for _ in 0..MAX_DELEGATION_DEPTH {
+            let next = &self.delegation_at(current, topic, at)?.delegate;
+            if has_voted(next) {
+                return Some(next);
+            }
+            if !visited.insert(next) {
+                return None;
+            }
+            current = next;
+        }
+        None
+    }
+
+    fn chain_reaches(&self, from: &UserId, target: &UserId, topic: &ProposalType, at: DateTime<Utc>) -> bool {
+        let mut visited = HashSet::new();
+        let mut current = from;
+        while visited.insert(current) {
+            if current == target {
+                return true;
+            }
+            match self.delegation_at(current, topic, at) {
+                Some(delegation) => current = &delegation.delegate,
+                None => return false,
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_cycles_are_rejected() {
+        let mut registry = DelegationRegistry::new();
+        let (a, b, c) = (UserId::new(), UserId::new(), UserId::new());
+        let topic = ProposalType::FeatureChange;
+
+        assert!(registry.delegate(a.clone(), a.clone(), topic.clone(), 10).is_err());
+        registry.delegate(a.clone(), b.clone(), topic.clone(), 10).unwrap();
+        registry.delegate(b.clone(), c.clone(), topic.clone(), 10).unwrap();
+        // c -> a would close a -> b -> c -> a
+        assert!(registry.delegate(c.clone(), a.clone(), topic.clone(), 10).is_err());
+        // The same edge on another topic is fine
+        registry.delegate(c.clone(), a.clone(), ProposalType::Slashing, 10).unwrap();
+
+        // After a revokes, c -> a no longer closes a loop
+        assert!(registry.revoke(&a, &topic));
+        assert!(!registry.revoke(&a, &topic));
+        registry.delegate(c, a, topic, 10).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_uses_graph_at_deadline() {
+        let mut registry = DelegationRegistry::new();
+        let (a, b, c) = (UserId::new(), UserId::new(), UserId::new());
+        let topic = ProposalType::TreasurySpend;
+
+        let start = Utc::now() - Duration::days(7);
+        let deadline = start + Duration::days(3);
+        let after = deadline + Duration::hours(1);
+
+        registry.delegate_at(a.clone(), b.clone(), topic.clone(), 10, start).unwrap();
+        registry.delegate_at(b.clone(), c.clone(), topic.clone(), 10, start).unwrap();
+
+        // Power skips b, who did not vote, and lands on c
+        assert_eq!(registry.resolve(&a, &topic, deadline, |u| u == &c), Some(&c));
+        assert_eq!(registry.resolve(&a, &topic, deadline, |u| u == &b), Some(&b));
+        assert_eq!(registry.resolve(&a, &topic, deadline, |_| false), None);
+
+        // Re-delegating after the deadline does not change the tally graph
+        registry.delegate_at(a.clone(), c.clone(), topic.clone(), 10, after).unwrap();
+        assert!(registry.revoke_at(&b, &topic, after));
+        assert_eq!(registry.resolve(&a, &topic, deadline, |u| u == &c), Some(&c));
+        assert_eq!(registry.delegation_at(&a, &topic, deadline).unwrap().delegate, b);
+        assert_eq!(registry.current_delegate(&a, &topic), Some(&c));
+        assert_eq!(registry.active_at(&topic, after).len(), 1);
+
+        // History cannot be rewritten from before the current delegation
+        assert!(registry.delegate_at(a, b, topic, 10, deadline).is_err());
+    }
+}
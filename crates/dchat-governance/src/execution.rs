@@ -0,0 +1,610 @@
+// On-chain Proposal Execution
+//
+// Passed proposals carry a typed payload that voters approved. Execution is
+// driven by block height rather than wall-clock time so every node applies
+// the same changes in the same order:
+// - A passed proposal is queued behind a timelock of `timelock_blocks`.
+// - Until it executes, a quorum of guardians can veto it (the emergency
+//   path for a payload that turns out to be harmful).
+// - `execute_due` applies every pending item whose height has been reached,
+//   ordered by (height, queue sequence). A payload that fails to apply is
+//   marked failed; it never blocks the items after it.
+//
+// Every queue, veto, execution and failure is appended to a hash-chained
+// log. Entries contain no timestamps, so nodes that replay the same blocks
+// produce the same chain.
+
+use crate::moderation::ModerationManager;
+use crate::upgrade::{UpgradeManager, UpgradeStatus};
+use crate::voting::VoteManager;
+use dchat_blockchain::{CurrencyChainClient, TokenSupplyConfig, TokenomicsManager};
+use dchat_core::{Config, Error, Result, UserId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Default delay between queueing and executing a proposal (about a day of
+/// 15-second blocks)
+pub const DEFAULT_TIMELOCK_BLOCKS: u64 = 5_760;
+
+/// Domain separator for execution log hashes
+const LOG_DOMAIN: &[u8] = b"dchat-governance-execution-v1";
+
+/// Domain separator for execution IDs
+const ID_DOMAIN: &[u8] = b"dchat-governance-execution-id-v1";
+
+/// A change that executes when a proposal passes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProposalPayload {
+    /// Set one node configuration field, addressed by JSON pointer
+    /// (e.g. `/governance/quorum_threshold`)
+    ConfigChange { key: String, value: serde_json::Value },
+    /// Replace the token supply parameters
+    TokenSupplyChange(TokenSupplyConfig),
+    /// Pay out of the treasury account
+    TreasuryTransfer { to: UserId, amount: u64 },
+    /// Slash a moderator's bonded stake
+    SlashModerator { moderator: UserId, amount: u64 },
+    /// Switch a protocol feature on or off
+    FeatureToggle { feature: String, enabled: bool },
+}
+
+/// What a queued execution was approved by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionSource {
+    /// A passed `VoteManager` proposal
+    Proposal(Uuid),
+    /// An approved `UpgradeManager` proposal
+    Upgrade(Uuid),
+}
+
+impl ExecutionSource {
+    /// ID of the execution queued for this source at queue position
+    /// `sequence`; every node derives the same one
+    pub fn execution_id(&self, sequence: u64) -> Uuid {
+        let body = serde_json::to_vec(&(self, sequence)).expect("execution source serializes");
+        let mut data = Vec::with_capacity(ID_DOMAIN.len() + body.len());
+        data.extend_from_slice(ID_DOMAIN);
+        data.extend_from_slice(&body);
+        let digest = dchat_crypto::hash(&data);
+        uuid::Builder::from_custom_bytes(digest[..16].try_into().expect("16 bytes")).into_uuid()
+    }
+}
+
+/// State of a queued execution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionStatus {
+    Pending,
+    Executed,
+    Failed(String),
+    Vetoed,
+}
+
+/// A payload waiting for its block height
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledExecution {
+    pub id: Uuid,
+    /// Position in the queue, used to order items due at the same height
+    pub sequence: u64,
+    pub source: ExecutionSource,
+    pub payload: Option<ProposalPayload>,
+    pub queued_at_height: u64,
+    pub execute_at_height: u64,
+    pub status: ExecutionStatus,
+}
+
+/// Audited event in an execution's lifecycle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionEvent {
+    Queued { execute_at_height: u64 },
+    VetoCast { guardian: UserId },
+    Vetoed,
+    Executed,
+    Failed { reason: String },
+}
+
+/// One entry of the hash-chained execution log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionRecord {
+    pub sequence: u64,
+    pub execution_id: Uuid,
+    pub source: ExecutionSource,
+    pub height: u64,
+    pub event: ExecutionEvent,
+    pub prev_hash: [u8; 32],
+    pub hash: [u8; 32],
+}
+
+impl ExecutionRecord {
+    fn compute_hash(&self) -> [u8; 32] {
+        let body = serde_json::to_vec(&(self.sequence, self.execution_id, self.source, self.height, &self.event))
+            .expect("execution record serializes");
+        let mut data = Vec::with_capacity(LOG_DOMAIN.len() + 32 + body.len());
+        data.extend_from_slice(LOG_DOMAIN);
+        data.extend_from_slice(&self.prev_hash);
+        data.extend_from_slice(&body);
+        dchat_crypto::hash(&data)
+    }
+}
+
+/// State a payload is applied to
+pub struct ExecutionContext<'a> {
+    pub config: &'a mut Config,
+    pub tokenomics: &'a TokenomicsManager,
+    pub currency: &'a CurrencyChainClient,
+    /// Account treasury transfers are paid from
+    pub treasury: &'a UserId,
+    pub moderation: &'a mut ModerationManager,
+    pub upgrades: &'a mut UpgradeManager,
+}
+
+/// Timelocked queue and executor for passed proposals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposalExecutor {
+    timelock_blocks: u64,
+    /// Queued executions in queue order
+    queue: Vec<ScheduledExecution>,
+    next_sequence: u64,
+    /// Guardians allowed to veto pending executions
+    guardians: HashSet<UserId>,
+    /// Vetoes needed to cancel an execution
+    veto_threshold: usize,
+    vetoes: HashMap<Uuid, HashSet<UserId>>,
+    log: Vec<ExecutionRecord>,
+}
+
+impl Default for ProposalExecutor {
+    fn default() -> Self {
+        Self::new(DEFAULT_TIMELOCK_BLOCKS)
+    }
+}
+
+impl ProposalExecutor {
+    /// Create an executor with the given timelock
+    pub fn new(timelock_blocks: u64) -> Self {
+        Self {
+            timelock_blocks,
+            queue: Vec::new(),
+            next_sequence: 0,
+            guardians: HashSet::new(),
+            veto_threshold: 1,
+            vetoes: HashMap::new(),
+            log: Vec::new(),
+        }
+    }
+
+    /// Blocks between queueing and execution
+    pub fn timelock_blocks(&self) -> u64 {
+        self.timelock_blocks
+    }
+
+    /// Set the guardian set and how many of them must veto
+    pub fn set_guardians(&mut self, guardians: impl IntoIterator<Item = UserId>, threshold: usize) -> Result<()> {
+        let guardians: HashSet<UserId> = guardians.into_iter().collect();
+        if threshold == 0 || threshold > guardians.len() {
+            return Err(Error::validation(format!(
+                "Veto threshold must be between 1 and {}",
+                guardians.len()
+            )));
+        }
+        self.guardians = guardians;
+        self.veto_threshold = threshold;
+        Ok(())
+    }
+
+    /// Queue a finalized, passed proposal for execution
+    pub fn queue_passed_proposal(&mut self, votes: &VoteManager, proposal_id: &Uuid, height: u64) -> Result<Uuid> {
+        let proposal = votes.get_proposal(proposal_id)
+            .ok_or_else(|| Error::NotFound("Proposal not found".to_string()))?;
+
+        match votes.proposal_outcome(proposal_id) {
+            Some(true) => {}
+            Some(false) => return Err(Error::validation("Proposal did not pass")),
+            None => return Err(Error::validation("Proposal not finalized")),
+        }
+
+        let payload = proposal.payload.clone();
+        self.enqueue(ExecutionSource::Proposal(*proposal_id), payload, height, height + self.timelock_blocks)
+    }
+
+    /// Queue an approved upgrade for execution
+    ///
+    /// A scheduled upgrade executes at its activation height if that is
+    /// later than the timelock, and is activated when it executes.
+    pub fn queue_upgrade(&mut self, upgrades: &UpgradeManager, upgrade_id: &Uuid, height: u64) -> Result<Uuid> {
+        let upgrade = upgrades.get_proposal(upgrade_id)
+            .ok_or_else(|| Error::NotFound("Upgrade proposal not found".to_string()))?;
+
+        let earliest = height + self.timelock_blocks;
+        let execute_at = match upgrade.status {
+            UpgradeStatus::Approved => earliest,
+            UpgradeStatus::Scheduled { activation_height } => activation_height.max(earliest),
+            _ => return Err(Error::validation("Upgrade must be approved first")),
+        };
+
+        self.enqueue(ExecutionSource::Upgrade(*upgrade_id), upgrade.execution_payload(), height, execute_at)
+    }
+
+    fn enqueue(
+        &mut self,
+        source: ExecutionSource,
+        payload: Option<ProposalPayload>,
+        height: u64,
+        execute_at_height: u64,
+    ) -> Result<Uuid> {
+        if self.queue.iter().any(|e| e.source == source) {
+            return Err(Error::AlreadyExists("Proposal already queued".to_string()));
+        }
+
+        let execution = ScheduledExecution {
+            id: source.execution_id(self.next_sequence),
+            sequence: self.next_sequence,
+            source,
+            payload,
+            queued_at_height: height,
+            execute_at_height,
+            status: ExecutionStatus::Pending,
+        };
+        self.next_sequence += 1;
+
+        let id = execution.id;
+        self.record(&execution, height, ExecutionEvent::Queued { execute_at_height });
+        self.queue.push(execution);
+        Ok(id)
+    }
+
+    /// Veto a pending execution as a guardian
+    ///
+    /// Returns `true` once the veto threshold is reached and the execution
+    /// is cancelled.
+    pub fn veto(&mut self, execution_id: &Uuid, guardian: &UserId, height: u64) -> Result<bool> {
+        if !self.guardians.contains(guardian) {
+            return Err(Error::PermissionDenied("Only guardians can veto".to_string()));
+        }
+
+        let index = self.queue.iter().position(|e| &e.id == execution_id)
+            .ok_or_else(|| Error::NotFound("Execution not found".to_string()))?;
+        if self.queue[index].status != ExecutionStatus::Pending {
+            return Err(Error::validation("Execution is no longer pending"));
+        }
+
+        let vetoes = self.vetoes.entry(*execution_id).or_default();
+        if !vetoes.insert(guardian.clone()) {
+            return Err(Error::AlreadyExists("Guardian already vetoed".to_string()));
+        }
+        let vetoed = vetoes.len() >= self.veto_threshold;
+
+        let execution = self.queue[index].clone();
+        self.record(&execution, height, ExecutionEvent::VetoCast { guardian: guardian.clone() });
+        if vetoed {
+            self.queue[index].status = ExecutionStatus::Vetoed;
+            self.record(&execution, height, ExecutionEvent::Vetoed);
+        }
+        Ok(vetoed)
+    }
+
+    /// Apply every pending execution due at `height`
+    ///
+    /// Returns the IDs of the executions that were attempted, in the order
+    /// they ran.
+    pub fn execute_due(&mut self, height: u64, ctx: &mut ExecutionContext<'_>) -> Vec<Uuid> {
+        let mut due: Vec<usize> = self.queue.iter()
+            .enumerate()
+            .filter(|(_, e)| e.status == ExecutionStatus::Pending && e.execute_at_height <= height)
+            .map(|(i, _)| i)
+            .collect();
+        due.sort_by_key(|&i| (self.queue[i].execute_at_height, self.queue[i].sequence));
+
+        let mut attempted = Vec::with_capacity(due.len());
+        for index in due {
+            let execution = self.queue[index].clone();
+            let (status, event) = match Self::apply(&execution, height, ctx) {
+                Ok(()) => (ExecutionStatus::Executed, ExecutionEvent::Executed),
+                Err(e) => (
+                    ExecutionStatus::Failed(e.to_string()),
+                    ExecutionEvent::Failed { reason: e.to_string() },
+                ),
+            };
+            self.queue[index].status = status;
+            self.record(&execution, height, event);
+            attempted.push(execution.id);
+        }
+        attempted
+    }
+
+    fn apply(execution: &ScheduledExecution, height: u64, ctx: &mut ExecutionContext<'_>) -> Result<()> {
+        if let Some(payload) = &execution.payload {
+            apply_payload(payload, ctx)?;
+        }
+
+        if let ExecutionSource::Upgrade(upgrade_id) = execution.source {
+            let scheduled = ctx.upgrades.get_proposal(&upgrade_id)
+                .is_some_and(|u| matches!(u.status, UpgradeStatus::Scheduled { .. }));
+            if scheduled {
+                ctx.upgrades.activate_upgrade(upgrade_id, height)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, execution: &ScheduledExecution, height: u64, event: ExecutionEvent) {
+        let prev_hash = self.log.last().map(|r| r.hash).unwrap_or([0u8; 32]);
+        let mut record = ExecutionRecord {
+            sequence: self.log.len() as u64,
+            execution_id: execution.id,
+            source: execution.source,
+            height,
+            event,
+            prev_hash,
+            hash: [0u8; 32],
+        };
+        record.hash = record.compute_hash();
+        self.log.push(record);
+    }
+
+    /// Look up a queued execution
+    pub fn get_execution(&self, execution_id: &Uuid) -> Option<&ScheduledExecution> {
+        self.queue.iter().find(|e| &e.id == execution_id)
+    }
+
+    /// Executions still waiting for their height
+    pub fn pending(&self) -> Vec<&ScheduledExecution> {
+        self.queue.iter().filter(|e| e.status == ExecutionStatus::Pending).collect()
+    }
+
+    /// The audit log, oldest first
+    pub fn log(&self) -> &[ExecutionRecord] {
+        &self.log
+    }
+
+    /// Check that the audit log is an unbroken hash chain
+    pub fn verify_log(&self) -> bool {
+        let mut prev_hash = [0u8; 32];
+        for (i, record) in self.log.iter().enumerate() {
+            if record.sequence != i as u64 || record.prev_hash != prev_hash || record.compute_hash() != record.hash {
+                return false;
+            }
+            prev_hash = record.hash;
+        }
+        true
+    }
+}
+
+/// Apply one payload to node state
+pub fn apply_payload(payload: &ProposalPayload, ctx: &mut ExecutionContext<'_>) -> Result<()> {
+    match payload {
+        ProposalPayload::ConfigChange { key, value } => {
+            let mut document = serde_json::to_value(&*ctx.config)?;
+            let field = document.pointer_mut(key)
+                .ok_or_else(|| Error::validation(format!("Unknown config key: {}", key)))?;
+            *field = value.clone();
+
+            let updated: Config = serde_json::from_value(document)
+                .map_err(|e| Error::validation(format!("Invalid value for {}: {}", key, e)))?;
+            updated.validate()?;
+            *ctx.config = updated;
+        }
+        ProposalPayload::TokenSupplyChange(supply) => {
+            ctx.tokenomics.update_supply_config(supply.clone())?;
+        }
+        ProposalPayload::TreasuryTransfer { to, amount } => {
+            ctx.currency.module_transfer(ctx.treasury, to, *amount)?;
+        }
+        ProposalPayload::SlashModerator { moderator, amount } => {
+            ctx.moderation.slash_moderator(moderator, *amount)?;
+        }
+        ProposalPayload::FeatureToggle { feature, enabled } => {
+            ctx.upgrades.set_feature(feature, *enabled);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upgrade::{UpgradeProposal, UpgradeType};
+    use crate::voting::{Proposal, ProposalType, Vote};
+    use chrono::{Duration, Utc};
+    use dchat_blockchain::CurrencyChainConfig;
+
+    struct Node {
+        config: Config,
+        tokenomics: TokenomicsManager,
+        currency: CurrencyChainClient,
+        treasury: UserId,
+        moderation: ModerationManager,
+        upgrades: UpgradeManager,
+    }
+
+    impl Node {
+        fn new() -> Self {
+            let treasury = UserId::new();
+            let currency = CurrencyChainClient::new(CurrencyChainConfig::default());
+            currency.create_module_account(&treasury, 1_000).unwrap();
+            Self {
+                config: Config::default(),
+                tokenomics: TokenomicsManager::new(TokenSupplyConfig::default()),
+                currency,
+                treasury,
+                moderation: ModerationManager::new(100),
+                upgrades: UpgradeManager::new(),
+            }
+        }
+
+        fn execute_due(&mut self, executor: &mut ProposalExecutor, height: u64) -> Vec<Uuid> {
+            let mut ctx = ExecutionContext {
+                config: &mut self.config,
+                tokenomics: &self.tokenomics,
+                currency: &self.currency,
+                treasury: &self.treasury,
+                moderation: &mut self.moderation,
+                upgrades: &mut self.upgrades,
+            };
+            executor.execute_due(height, &mut ctx)
+        }
+    }
+
+    /// Run a proposal carrying `payload` through a short vote
+    fn decide(votes: &mut VoteManager, payload: ProposalPayload, approve: bool) -> Uuid {
+        let mut proposal = Proposal::new(
+            UserId::new(),
+            ProposalType::FeatureChange,
+            "Execute".to_string(),
+            "Test".to_string(),
+            7,
+            10,
+        ).unwrap().with_payload(payload);
+        proposal.deadline = Utc::now() + Duration::milliseconds(30);
+        proposal.reveal_deadline = proposal.deadline + Duration::milliseconds(30);
+        let id = votes.submit_proposal(proposal).unwrap();
+
+        let voter = UserId::new();
+        let (vote, opening) = Vote::commit(voter.clone(), id, approve, 500);
+        votes.cast_vote(vote).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(35));
+        votes.reveal_vote(&id, &voter, &opening).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(35));
+        assert_eq!(votes.finalize_proposal(&id).unwrap(), approve);
+        id
+    }
+
+    #[test]
+    fn test_timelocked_execution_in_order() {
+        let mut node = Node::new();
+        let mut votes = VoteManager::new(1_000);
+        let mut executor = ProposalExecutor::new(10);
+        let recipient = UserId::new();
+
+        let transfer = decide(&mut votes, ProposalPayload::TreasuryTransfer { to: recipient.clone(), amount: 400 }, true);
+        let quorum = decide(&mut votes, ProposalPayload::ConfigChange {
+            key: "/governance/quorum_threshold".to_string(),
+            value: serde_json::json!(0.25),
+        }, true);
+        let overdraw = decide(&mut votes, ProposalPayload::TreasuryTransfer { to: recipient.clone(), amount: 700 }, true);
+        let rejected = decide(&mut votes, ProposalPayload::FeatureToggle { feature: "x".to_string(), enabled: true }, false);
+
+        assert!(executor.queue_passed_proposal(&votes, &rejected, 100).is_err());
+        let first = executor.queue_passed_proposal(&votes, &transfer, 100).unwrap();
+        let second = executor.queue_passed_proposal(&votes, &overdraw, 100).unwrap();
+        let third = executor.queue_passed_proposal(&votes, &quorum, 101).unwrap();
+        assert!(executor.queue_passed_proposal(&votes, &transfer, 102).is_err());
+        assert_eq!(first, ExecutionSource::Proposal(transfer).execution_id(0));
+
+        // Nothing runs before the timelock expires
+        assert!(node.execute_due(&mut executor, 109).is_empty());
+        assert_eq!(node.execute_due(&mut executor, 111), vec![first, second, third]);
+
+        // The overdraw fails once the first transfer drained the treasury,
+        // without stopping the config change queued after it
+        assert_eq!(node.currency.get_balance(&node.treasury).unwrap(), 600);
+        assert!(matches!(executor.get_execution(&second).unwrap().status, ExecutionStatus::Failed(_)));
+        assert_eq!(executor.get_execution(&third).unwrap().status, ExecutionStatus::Executed);
+        assert_eq!(node.config.governance.quorum_threshold, 0.25);
+        assert!(executor.pending().is_empty());
+        assert!(node.execute_due(&mut executor, 200).is_empty());
+    }
+
+    #[test]
+    fn test_guardian_veto_and_audit_log() {
+        let mut node = Node::new();
+        let mut votes = VoteManager::new(1_000);
+        let mut executor = ProposalExecutor::new(5);
+        let guardians: Vec<UserId> = (0..3).map(|_| UserId::new()).collect();
+        executor.set_guardians(guardians.clone(), 2).unwrap();
+
+        let moderator = UserId::new();
+        node.moderation.register_moderator(moderator.clone(), 150).unwrap();
+        let slash = decide(&mut votes, ProposalPayload::SlashModerator { moderator: moderator.clone(), amount: 100 }, true);
+        let bad_config = decide(&mut votes, ProposalPayload::ConfigChange {
+            key: "/network/max_connections".to_string(),
+            value: serde_json::json!(0),
+        }, true);
+
+        let slash_exec = executor.queue_passed_proposal(&votes, &slash, 10).unwrap();
+        let bad_exec = executor.queue_passed_proposal(&votes, &bad_config, 10).unwrap();
+
+        assert!(executor.veto(&bad_exec, &UserId::new(), 11).is_err());
+        assert!(!executor.veto(&bad_exec, &guardians[0], 11).unwrap());
+        assert!(executor.veto(&bad_exec, &guardians[0], 11).is_err());
+        assert!(executor.veto(&bad_exec, &guardians[1], 12).unwrap());
+
+        assert_eq!(node.execute_due(&mut executor, 15), vec![slash_exec]);
+        assert_eq!(executor.get_execution(&bad_exec).unwrap().status, ExecutionStatus::Vetoed);
+        assert!(node.config.network.max_connections > 0);
+        // 150 - 100 is below the minimum stake, so the role is lost
+        assert!(!node.moderation.is_moderator(&moderator));
+
+        // queue, queue, veto, veto, vetoed, executed
+        let events: Vec<&ExecutionEvent> = executor.log().iter().map(|r| &r.event).collect();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[4], &ExecutionEvent::Vetoed);
+        assert_eq!(events[5], &ExecutionEvent::Executed);
+        assert!(executor.verify_log());
+
+        executor.log[2].height += 1;
+        assert!(!executor.verify_log());
+    }
+
+    #[test]
+    fn test_replayed_queue_produces_the_same_log() {
+        let upgrades: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        let replay = || {
+            let mut executor = ProposalExecutor::new(5);
+            for (height, upgrade) in upgrades.iter().enumerate() {
+                executor.enqueue(ExecutionSource::Upgrade(*upgrade), None, height as u64, 10).unwrap();
+            }
+            executor
+        };
+
+        let (a, b) = (replay(), replay());
+        assert_eq!(a.log().last().unwrap().hash, b.log().last().unwrap().hash);
+        assert_eq!(a.pending()[1].id, ExecutionSource::Upgrade(upgrades[1]).execution_id(1));
+    }
+
+    #[test]
+    fn test_feature_toggle_upgrade_and_supply_change() {
+        let mut node = Node::new();
+        let mut executor = ProposalExecutor::new(0);
+
+        let mut upgrade = UpgradeProposal::new(
+            UserId::new(),
+            UpgradeType::FeatureToggle { feature: "stealth_payloads".to_string() },
+            node.upgrades.current_version().clone(),
+            crate::upgrade::Version::new(1, 1, 0),
+            "Stealth".to_string(),
+            "Enable stealth payloads".to_string(),
+            7,
+            10,
+        ).unwrap();
+        upgrade.status = UpgradeStatus::Approved;
+        let upgrade_id = node.upgrades.submit_proposal(upgrade).unwrap();
+        node.upgrades.schedule_upgrade(upgrade_id, 50, Utc::now()).unwrap();
+
+        // Scheduled upgrades wait for their activation height
+        let exec = executor.queue_upgrade(&node.upgrades, &upgrade_id, 10).unwrap();
+        assert_eq!(executor.get_execution(&exec).unwrap().execute_at_height, 50);
+        assert!(node.execute_due(&mut executor, 49).is_empty());
+        node.execute_due(&mut executor, 50);
+        assert!(node.upgrades.is_feature_enabled("stealth_payloads"));
+        assert_eq!(node.upgrades.get_proposal(&upgrade_id).unwrap().status, UpgradeStatus::Active);
+
+        let mut supply = node.tokenomics.supply_config();
+        supply.burn_rate_bps = 250;
+        let mut ctx = ExecutionContext {
+            config: &mut node.config,
+            tokenomics: &node.tokenomics,
+            currency: &node.currency,
+            treasury: &node.treasury,
+            moderation: &mut node.moderation,
+            upgrades: &mut node.upgrades,
+        };
+        apply_payload(&ProposalPayload::TokenSupplyChange(supply), &mut ctx).unwrap();
+        let mut genesis_change = ctx.tokenomics.supply_config();
+        genesis_change.initial_supply += 1;
+        assert!(apply_payload(&ProposalPayload::TokenSupplyChange(genesis_change), &mut ctx).is_err());
+        apply_payload(&ProposalPayload::FeatureToggle { feature: "stealth_payloads".to_string(), enabled: false }, &mut ctx).unwrap();
+        assert_eq!(node.tokenomics.supply_config().burn_rate_bps, 250);
+        assert!(!node.upgrades.is_feature_enabled("stealth_payloads"));
+    }
+}
@@ -4,14 +4,21 @@
 // for the dchat protocol.
 
 pub mod voting;
+pub mod delegation;
 pub mod abuse_reporting;
 pub mod moderation;
 pub mod upgrade;
+pub mod execution;
 
-pub use voting::{Proposal, Vote, VoteManager, ProposalType};
+pub use voting::{Proposal, Vote, VoteManager, ProposalType, BallotMode, BallotOpening, SealedBallot, TallyMode};
+pub use delegation::{Delegation, DelegationRegistry};
 pub use abuse_reporting::{AbuseReport, ReportManager, JurySelection};
 pub use moderation::{ModerationAction, ModerationManager, SlashingVote};
 pub use upgrade::{
     UpgradeProposal, UpgradeManager, UpgradeType, UpgradeStatus,
     Version, ValidatorSignature, ForkState,
 };
+pub use execution::{
+    ProposalPayload, ProposalExecutor, ExecutionContext, ExecutionSource,
+    ExecutionStatus, ExecutionEvent, ExecutionRecord, ScheduledExecution,
+};
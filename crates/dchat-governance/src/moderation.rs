@@ -94,6 +94,7 @@ pub struct SlashingVote {
 }
 
 /// Manager for moderation actions and slashing
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModerationManager {
     /// Active moderators and their stake
     moderators: HashMap<UserId, u64>,
@@ -220,6 +221,27 @@ impl ModerationManager {
         self.moderators.contains_key(user)
     }
 
+    /// Stake a moderator has bonded
+    pub fn moderator_stake(&self, moderator: &UserId) -> Option<u64> {
+        self.moderators.get(moderator).copied()
+    }
+
+    /// Slash up to `amount` of a moderator's stake
+    ///
+    /// A moderator left below the minimum stake loses the role. Returns the
+    /// amount actually slashed.
+    pub fn slash_moderator(&mut self, moderator: &UserId, amount: u64) -> Result<u64> {
+        let stake = self.moderators.get_mut(moderator)
+            .ok_or_else(|| Error::NotFound("Moderator not found".to_string()))?;
+        
+        let slashed = amount.min(*stake);
+        *stake -= slashed;
+        if *stake < self.min_stake {
+            self.moderators.remove(moderator);
+        }
+        Ok(slashed)
+    }
+
     /// Submit a moderation action
     pub fn submit_action(&mut self, action: ModerationAction) -> Result<Uuid> {
         if !self.is_moderator(&action.moderator) {
@@ -3,11 +3,12 @@
 // This module implements decentralized protocol upgrade voting,
 // hard fork coordination, and backward compatibility management.
 
+use crate::execution::ProposalPayload;
 use dchat_core::{UserId, Result, Error, PROTOCOL_VERSION};
 use chrono::{DateTime, Utc, Duration};
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 /// Semantic version representation
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -118,6 +119,10 @@ pub struct UpgradeProposal {
     
     /// Validator signatures (for hard forks)
     pub validator_signatures: Vec<ValidatorSignature>,
+    
+    /// What executes once the upgrade is approved
+    #[serde(default)]
+    pub payload: Option<ProposalPayload>,
 }
 
 /// Validator signature for upgrade approval
@@ -143,7 +148,7 @@ pub struct ForkState {
     pub fork_time: DateTime<Utc>,
     
     /// Nodes that followed this fork
-    pub supporting_nodes: HashSet<UserId>,
+    pub supporting_nodes: BTreeSet<UserId>,
     /// Cumulative stake on this fork
     pub total_stake: u64,
     
@@ -152,6 +157,7 @@ pub struct ForkState {
 }
 
 /// Upgrade governance manager
+#[derive(Serialize, Deserialize)]
 pub struct UpgradeManager {
     /// Active upgrade proposals
     proposals: HashMap<Uuid, UpgradeProposal>,
@@ -170,6 +176,10 @@ pub struct UpgradeManager {
     
     /// Minimum voting period for upgrades (days)
     min_voting_period_days: i64,
+    
+    /// Protocol features switched on or off by governance
+    #[serde(default)]
+    feature_flags: BTreeMap<String, bool>,
 }
 
 impl UpgradeProposal {
@@ -219,9 +229,31 @@ impl UpgradeProposal {
             quorum_percentage,
             created_at: now,
             validator_signatures: Vec::new(),
+            payload: None,
         })
     }
 
+    /// Attach the change that executes if the upgrade is approved
+    pub fn with_payload(mut self, payload: ProposalPayload) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    /// The change that executes if approved
+    ///
+    /// A feature-toggle upgrade without an explicit payload switches its
+    /// feature on.
+    pub fn execution_payload(&self) -> Option<ProposalPayload> {
+        match (&self.payload, &self.upgrade_type) {
+            (Some(payload), _) => Some(payload.clone()),
+            (None, UpgradeType::FeatureToggle { feature }) => Some(ProposalPayload::FeatureToggle {
+                feature: feature.clone(),
+                enabled: true,
+            }),
+            (None, _) => None,
+        }
+    }
+
     /// Check if voting is still open
     pub fn is_voting_open(&self) -> bool {
         matches!(self.status, UpgradeStatus::Proposed) && Utc::now() < self.voting_deadline
@@ -265,6 +297,7 @@ impl UpgradeManager {
             total_stake: 0,
             hard_fork_threshold: 67, // 67% validator approval for hard forks
             min_voting_period_days: 14, // Minimum 2 weeks for major upgrades
+            feature_flags: BTreeMap::new(),
         }
     }
 
@@ -384,13 +417,17 @@ impl UpgradeManager {
                 fork_version: proposal.target_version.clone(),
                 fork_height: proposal.activation_height.unwrap(),
                 fork_time: Utc::now(),
-                supporting_nodes: HashSet::new(),
+                supporting_nodes: BTreeSet::new(),
                 total_stake: 0,
                 is_canonical: true, // Assume canonical if governance passed
             };
             self.fork_history.push(fork);
         }
 
+        if let UpgradeType::FeatureToggle { feature } = &proposal.upgrade_type {
+            self.feature_flags.insert(feature.clone(), true);
+        }
+
         // Update current version
         self.current_version = proposal.target_version.clone();
         proposal.status = UpgradeStatus::Active;
@@ -439,6 +476,21 @@ impl UpgradeManager {
         self.total_stake = new_total;
     }
 
+    /// Switch a protocol feature on or off
+    pub fn set_feature(&mut self, feature: &str, enabled: bool) {
+        self.feature_flags.insert(feature.to_string(), enabled);
+    }
+
+    /// Whether governance has enabled a feature
+    pub fn is_feature_enabled(&self, feature: &str) -> bool {
+        self.feature_flags.get(feature).copied().unwrap_or(false)
+    }
+
+    /// All features governance has toggled
+    pub fn feature_flags(&self) -> &BTreeMap<String, bool> {
+        &self.feature_flags
+    }
+
     /// Set hard fork threshold
     pub fn set_hard_fork_threshold(&mut self, threshold: u32) -> Result<()> {
         if threshold > 100 {
@@ -765,4 +817,36 @@ mod tests {
         assert_eq!(forks[0].parent_version, current);
         assert!(forks[0].is_canonical);
     }
+
+    #[test]
+    fn test_proposal_survives_serialization() {
+        let mut manager = UpgradeManager::new();
+        manager.update_total_stake(10000);
+
+        let current = manager.current_version().clone();
+        let target = Version::new(current.major, current.minor + 1, 0);
+        let proposal = UpgradeProposal::new(
+            UserId::new(),
+            UpgradeType::SoftFork,
+            current,
+            target,
+            "Persisted upgrade".to_string(),
+            "Description".to_string(),
+            7,
+            60,
+        ).unwrap();
+        let proposal_id = manager.submit_proposal(proposal).unwrap();
+
+        // Each CLI invocation reloads the manager from its stored snapshot
+        let json = serde_json::to_string(&manager).unwrap();
+        let mut manager: UpgradeManager = serde_json::from_str(&json).unwrap();
+        manager.cast_upgrade_vote(proposal_id, UserId::new(), true, 7000).unwrap();
+
+        let json = serde_json::to_string(&manager).unwrap();
+        let mut manager: UpgradeManager = serde_json::from_str(&json).unwrap();
+        assert_eq!(manager.get_proposal(&proposal_id).unwrap().votes_for, 7000);
+
+        manager.proposals.get_mut(&proposal_id).unwrap().voting_deadline = Utc::now() - Duration::seconds(1);
+        assert!(manager.finalize_proposal(proposal_id).unwrap());
+    }
 }
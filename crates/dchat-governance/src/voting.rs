@@ -2,15 +2,46 @@
 //
 // This module implements token-weighted voting, proposals, and
 // decentralized governance for protocol decisions.
+//
+// Ballots stay secret until the voting deadline in one of two ways:
+// - Commit-reveal: voters publish a salted hash of their choice and open it
+//   during a reveal window after the deadline. Voters who never open their
+//   commitment forfeit part of their voting power on every later ballot and
+//   delegation, so they cannot withhold a losing vote for free.
+// - Threshold: ballots are encrypted to a validator committee key and
+//   decrypted after the deadline once `t` of `n` validators publish
+//   verifiable decryption shares. No single key holder can read or
+//   selectively reveal ballots.
+//
+// Verified ballots are then weighted according to the tally mode configured
+// for the proposal's type, and power delegated on that topic follows its
+// delegation chain to the first delegate who voted.
 
+use crate::delegation::DelegationRegistry;
+use crate::execution::ProposalPayload;
 use dchat_core::{UserId, Result, Error};
+use dchat_crypto::threshold::{DecryptionShare, ThresholdCiphertext, ThresholdKeyShare, ThresholdPublicKey};
+use dchat_identity::verification::{BadgeManager, BadgeType};
 use chrono::{DateTime, Utc, Duration};
+use rand::{rngs::OsRng, RngCore};
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// Time voters get after the deadline to open their commitments
+const DEFAULT_REVEAL_PERIOD_HOURS: i64 = 24;
+
+/// Share of voting power forfeited for not opening a commitment, in basis points
+const DEFAULT_NON_REVEAL_PENALTY_BPS: u32 = 1_000;
+
+/// Domain separation for ballot commitments
+const COMMITMENT_DOMAIN: &[u8] = b"dchat-ballot-commitment-v1";
+
+/// Default conviction half-life for treasury spending
+const DEFAULT_CONVICTION_HALF_LIFE_HOURS: u32 = 72;
 
 /// Type of proposal being voted on
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ProposalType {
     /// Protocol feature addition or modification
     FeatureChange,
@@ -24,6 +55,44 @@ pub enum ProposalType {
     Emergency,
 }
 
+/// How verified ballots are weighted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TallyMode {
+    /// One token, one vote
+    StakeWeighted,
+    /// Weight is the square root of stake, and only identities holding a
+    /// verified badge count, so splitting stake across accounts gains nothing
+    Quadratic,
+    /// Weight grows with how long stake was committed before the deadline,
+    /// reaching half of its full value after `half_life_hours`
+    Conviction { half_life_hours: u32 },
+}
+
+impl TallyMode {
+    /// Weight of `power` held for `held` before the deadline, or `None` if
+    /// it does not count at all
+    fn weight(&self, power: u64, verified: bool, held: Duration) -> Option<u64> {
+        match *self {
+            TallyMode::StakeWeighted => Some(power),
+            TallyMode::Quadratic => verified.then(|| power.isqrt()),
+            TallyMode::Conviction { half_life_hours } => {
+                let held_hours = held.num_seconds().max(0) as f64 / 3600.0;
+                let conviction = 1.0 - 0.5f64.powf(held_hours / half_life_hours.max(1) as f64);
+                Some((power as f64 * conviction) as u64)
+            }
+        }
+    }
+}
+
+/// How ballots on a proposal are kept secret until the deadline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BallotMode {
+    /// Salted hash commitments, opened by each voter after the deadline
+    CommitReveal,
+    /// Ballots encrypted to a validator committee key
+    Threshold(ThresholdPublicKey),
+}
+
 /// A governance proposal
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Proposal {
@@ -41,6 +110,10 @@ pub struct Proposal {
     pub created_at: DateTime<Utc>,
     /// Voting deadline
     pub deadline: DateTime<Utc>,
+    /// End of the reveal window (equal to the deadline for threshold ballots)
+    pub reveal_deadline: DateTime<Utc>,
+    /// How ballots are sealed
+    pub ballot_mode: BallotMode,
     /// Minimum quorum (percentage of total stake)
     pub quorum_percentage: u32,
     /// Current vote tally
@@ -48,6 +121,25 @@ pub struct Proposal {
     pub votes_against: u64,
     /// Has voting ended?
     pub finalized: bool,
+    /// What executes if the proposal passes
+    #[serde(default)]
+    pub payload: Option<ProposalPayload>,
+}
+
+/// A sealed ballot as cast
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SealedBallot {
+    /// Hash commitment to the choice and a random salt
+    Commitment([u8; 32]),
+    /// Choice encrypted to the proposal's committee key
+    Encrypted(ThresholdCiphertext),
+}
+
+/// What a voter keeps secret until the reveal window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BallotOpening {
+    pub vote_for: bool,
+    pub salt: [u8; 32],
 }
 
 /// A vote on a proposal
@@ -57,9 +149,9 @@ pub struct Vote {
     pub voter: UserId,
     /// Proposal being voted on
     pub proposal_id: Uuid,
-    /// Encrypted ballot (revealed after deadline)
-    pub encrypted_ballot: Vec<u8>,
-    /// Revealed ballot (Some after reveal phase)
+    /// Sealed ballot (opened after deadline)
+    pub ballot: SealedBallot,
+    /// Verified ballot (Some once the commitment is opened or the ciphertext decrypted)
     pub revealed_ballot: Option<bool>, // true = for, false = against
     /// Voting power (token stake)
     pub voting_power: u64,
@@ -73,12 +165,30 @@ pub struct VoteManager {
     proposals: HashMap<Uuid, Proposal>,
     /// Cast votes
     votes: HashMap<Uuid, Vec<Vote>>,
+    /// Verified decryption shares per proposal and voter
+    decryption_shares: HashMap<Uuid, HashMap<UserId, Vec<DecryptionShare>>>,
+    /// Voting power forfeited by voters who did not reveal
+    penalties: HashMap<UserId, u64>,
+    /// Penalty for not revealing, in basis points of voting power
+    non_reveal_penalty_bps: u32,
+    /// Tally mode per proposal type, for new proposals
+    tally_modes: HashMap<ProposalType, TallyMode>,
+    /// Tally mode each proposal was submitted with
+    proposal_modes: HashMap<Uuid, TallyMode>,
+    /// Liquid delegations per topic
+    delegations: DelegationRegistry,
+    /// Identities with a verified badge, eligible for quadratic votes
+    verified_identities: HashSet<UserId>,
+    /// Result of each finalized proposal
+    outcomes: HashMap<Uuid, bool>,
     /// Total staked tokens in system
     total_stake: u64,
 }
 
 impl Proposal {
     /// Create a new proposal
+    ///
+    /// Ballots default to commit-reveal with a one-day reveal window.
     pub fn new(
         proposer: UserId,
         proposal_type: ProposalType,
@@ -102,18 +212,48 @@ impl Proposal {
             description,
             created_at: now,
             deadline,
+            reveal_deadline: deadline + Duration::hours(DEFAULT_REVEAL_PERIOD_HOURS),
+            ballot_mode: BallotMode::CommitReveal,
             quorum_percentage,
             votes_for: 0,
             votes_against: 0,
             finalized: false,
+            payload: None,
         })
     }
 
+    /// Attach the change that executes if the proposal passes
+    pub fn with_payload(mut self, payload: ProposalPayload) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    /// Encrypt ballots to a validator committee instead of using commit-reveal
+    pub fn with_threshold_ballots(mut self, committee: ThresholdPublicKey) -> Self {
+        self.ballot_mode = BallotMode::Threshold(committee);
+        self.reveal_deadline = self.deadline;
+        self
+    }
+
+    /// Set how long voters have after the deadline to open commitments
+    pub fn with_reveal_period(mut self, period: Duration) -> Self {
+        if self.ballot_mode == BallotMode::CommitReveal {
+            self.reveal_deadline = self.deadline + period;
+        }
+        self
+    }
+
     /// Check if voting is still open
     pub fn is_open(&self) -> bool {
         !self.finalized && Utc::now() < self.deadline
     }
 
+    /// Check if commitments can be opened now
+    pub fn in_reveal_phase(&self) -> bool {
+        let now = Utc::now();
+        !self.finalized && now >= self.deadline && now < self.reveal_deadline
+    }
+
     /// Check if quorum has been met
     pub fn meets_quorum(&self, total_stake: u64) -> bool {
         let total_votes = self.votes_for + self.votes_against;
@@ -128,48 +268,72 @@ impl Proposal {
 }
 
 impl Vote {
-    /// Create an encrypted vote
-    /// 
-    /// Ballot is encrypted to prevent early result visibility
-    pub fn new_encrypted(
+    /// Create a commit-reveal vote
+    ///
+    /// Returns the vote to cast and the opening the voter must keep secret
+    /// until the reveal window.
+    pub fn commit(
         voter: UserId,
         proposal_id: Uuid,
         vote_for: bool,
         voting_power: u64,
-        encryption_key: &[u8; 32],
+    ) -> (Self, BallotOpening) {
+        let mut salt = [0u8; 32];
+        OsRng.fill_bytes(&mut salt);
+        let opening = BallotOpening { vote_for, salt };
+        let commitment = ballot_commitment(&proposal_id, &voter, &opening);
+        
+        let vote = Self {
+            voter,
+            proposal_id,
+            ballot: SealedBallot::Commitment(commitment),
+            revealed_ballot: None,
+            voting_power,
+            cast_at: Utc::now(),
+        };
+        (vote, opening)
+    }
+
+    /// Create a vote encrypted to a validator committee
+    ///
+    /// The ciphertext is bound to the proposal and voter, so it cannot be
+    /// copied into another voter's ballot.
+    pub fn encrypted(
+        voter: UserId,
+        proposal_id: Uuid,
+        vote_for: bool,
+        voting_power: u64,
+        committee: &ThresholdPublicKey,
     ) -> Result<Self> {
-        // Simple XOR encryption (production should use AES-GCM)
-        let plaintext = if vote_for { vec![1u8] } else { vec![0u8] };
-        let mut encrypted_ballot = plaintext.clone();
-        for (i, byte) in encrypted_ballot.iter_mut().enumerate() {
-            *byte ^= encryption_key[i % 32];
-        }
+        let aad = ballot_aad(&proposal_id, &voter);
+        let ciphertext = committee.encrypt(&[vote_for as u8], &aad, &mut OsRng)?;
         
         Ok(Self {
             voter,
             proposal_id,
-            encrypted_ballot,
+            ballot: SealedBallot::Encrypted(ciphertext),
             revealed_ballot: None,
             voting_power,
             cast_at: Utc::now(),
         })
     }
 
-    /// Reveal the ballot after voting deadline
-    pub fn reveal(&mut self, decryption_key: &[u8; 32]) -> Result<bool> {
+    /// Open a commitment; fails if the opening does not match it
+    pub fn open(&mut self, opening: &BallotOpening) -> Result<bool> {
         if self.revealed_ballot.is_some() {
             return Err(Error::validation("Ballot already revealed".to_string()));
         }
+        let SealedBallot::Commitment(commitment) = &self.ballot else {
+            return Err(Error::validation("Ballot is not a commitment".to_string()));
+        };
         
-        // Decrypt (simple XOR)
-        let mut plaintext = self.encrypted_ballot.clone();
-        for (i, byte) in plaintext.iter_mut().enumerate() {
-            *byte ^= decryption_key[i % 32];
+        let expected = ballot_commitment(&self.proposal_id, &self.voter, opening);
+        if !dchat_crypto::constant_time_eq(&expected, commitment) {
+            return Err(Error::validation("Opening does not match commitment".to_string()));
         }
         
-        let vote_for = plaintext[0] == 1;
-        self.revealed_ballot = Some(vote_for);
-        Ok(vote_for)
+        self.revealed_ballot = Some(opening.vote_for);
+        Ok(opening.vote_for)
     }
 }
 
@@ -179,20 +343,78 @@ impl VoteManager {
         Self {
             proposals: HashMap::new(),
             votes: HashMap::new(),
+            decryption_shares: HashMap::new(),
+            penalties: HashMap::new(),
+            non_reveal_penalty_bps: DEFAULT_NON_REVEAL_PENALTY_BPS,
+            tally_modes: HashMap::from([(
+                ProposalType::TreasurySpend,
+                TallyMode::Conviction { half_life_hours: DEFAULT_CONVICTION_HALF_LIFE_HOURS },
+            )]),
+            proposal_modes: HashMap::new(),
+            delegations: DelegationRegistry::new(),
+            verified_identities: HashSet::new(),
+            outcomes: HashMap::new(),
             total_stake,
         }
     }
 
+    /// Set the tally mode for proposals of a type submitted from now on
+    pub fn set_tally_mode(&mut self, proposal_type: ProposalType, mode: TallyMode) {
+        self.tally_modes.insert(proposal_type, mode);
+    }
+
+    /// Tally mode new proposals of a type get
+    pub fn tally_mode(&self, proposal_type: &ProposalType) -> TallyMode {
+        self.tally_modes.get(proposal_type).copied().unwrap_or(TallyMode::StakeWeighted)
+    }
+
+    /// Tally mode a submitted proposal is counted with
+    pub fn proposal_tally_mode(&self, proposal_id: &Uuid) -> Option<TallyMode> {
+        self.proposal_modes.get(proposal_id).copied()
+    }
+
+    /// Record that a user holds a valid verified-identity badge
+    pub fn register_verified_identity(&mut self, user: UserId, badges: &BadgeManager) -> Result<()> {
+        if !badges.has_badge(&user, &BadgeType::Verified) {
+            return Err(Error::PermissionDenied("User has no verified identity badge".to_string()));
+        }
+        self.verified_identities.insert(user);
+        Ok(())
+    }
+
+    /// Delegate voting power on a topic to another user
+    ///
+    /// Power the delegator forfeited by not revealing is not delegated.
+    pub fn delegate(&mut self, delegator: UserId, delegate: UserId, topic: ProposalType, voting_power: u64) -> Result<()> {
+        let voting_power = self.effective_power(&delegator, voting_power)?;
+        self.delegations.delegate(delegator, delegate, topic, voting_power)
+    }
+
+    /// Revoke a delegation on a topic; returns `false` if there was none
+    pub fn revoke_delegation(&mut self, delegator: &UserId, topic: &ProposalType) -> bool {
+        self.delegations.revoke(delegator, topic)
+    }
+
+    /// Delegation history and graph
+    pub fn delegations(&self) -> &DelegationRegistry {
+        &self.delegations
+    }
+
     /// Submit a new proposal
     pub fn submit_proposal(&mut self, proposal: Proposal) -> Result<Uuid> {
         let id = proposal.id;
+        self.proposal_modes.insert(id, self.tally_mode(&proposal.proposal_type));
         self.proposals.insert(id, proposal);
         self.votes.insert(id, Vec::new());
         Ok(id)
     }
 
     /// Cast a vote on a proposal
-    pub fn cast_vote(&mut self, vote: Vote) -> Result<()> {
+    ///
+    /// The ballot is timestamped on arrival; a caller-supplied `cast_at` is
+    /// ignored so conviction cannot be backdated. Power the voter forfeited
+    /// by not revealing earlier ballots is deducted from `voting_power`.
+    pub fn cast_vote(&mut self, mut vote: Vote) -> Result<()> {
         let proposal = self.proposals.get(&vote.proposal_id)
             .ok_or_else(|| Error::NotFound("Proposal not found".to_string()))?;
         
@@ -200,18 +422,64 @@ impl VoteManager {
             return Err(Error::validation("Voting is closed".to_string()));
         }
         
+        if vote.revealed_ballot.is_some() {
+            return Err(Error::validation("Ballot must be sealed when cast".to_string()));
+        }
+        
+        match (&proposal.ballot_mode, &vote.ballot) {
+            (BallotMode::CommitReveal, SealedBallot::Commitment(_))
+            | (BallotMode::Threshold(_), SealedBallot::Encrypted(_)) => {}
+            _ => return Err(Error::validation("Ballot does not match the proposal's ballot mode".to_string())),
+        }
+        
+        if self.proposal_modes.get(&vote.proposal_id) == Some(&TallyMode::Quadratic)
+            && !self.verified_identities.contains(&vote.voter)
+        {
+            return Err(Error::PermissionDenied("Quadratic votes require a verified identity".to_string()));
+        }
+        
         // Check for duplicate vote
         let existing_votes = self.votes.get(&vote.proposal_id).unwrap();
         if existing_votes.iter().any(|v| v.voter == vote.voter) {
             return Err(Error::validation("Already voted".to_string()));
         }
         
+        vote.voting_power = self.effective_power(&vote.voter, vote.voting_power)?;
+        vote.cast_at = Utc::now();
         self.votes.get_mut(&vote.proposal_id).unwrap().push(vote);
         Ok(())
     }
 
-    /// Reveal all votes for a proposal (after deadline)
-    pub fn reveal_votes(&mut self, proposal_id: &Uuid, decryption_key: &[u8; 32]) -> Result<()> {
+    /// Voting power left after deducting the voter's non-reveal penalties
+    fn effective_power(&self, voter: &UserId, voting_power: u64) -> Result<u64> {
+        match voting_power.saturating_sub(self.penalty(voter)) {
+            0 => Err(Error::PermissionDenied("No voting power left after non-reveal penalties".to_string())),
+            power => Ok(power),
+        }
+    }
+
+    /// Open a voter's commitment during the reveal window
+    pub fn reveal_vote(&mut self, proposal_id: &Uuid, voter: &UserId, opening: &BallotOpening) -> Result<bool> {
+        let proposal = self.proposals.get(proposal_id)
+            .ok_or_else(|| Error::NotFound("Proposal not found".to_string()))?;
+        
+        if proposal.ballot_mode != BallotMode::CommitReveal {
+            return Err(Error::validation("Proposal does not use commit-reveal ballots".to_string()));
+        }
+        if !proposal.in_reveal_phase() {
+            return Err(Error::validation("Proposal is not in its reveal phase".to_string()));
+        }
+        
+        let vote = self.votes.get_mut(proposal_id).unwrap()
+            .iter_mut()
+            .find(|v| &v.voter == voter)
+            .ok_or_else(|| Error::NotFound("No ballot from this voter".to_string()))?;
+        vote.open(opening)
+    }
+
+    /// Compute a committee member's decryption shares for every ballot on a
+    /// proposal, once voting has closed
+    pub fn decryption_shares(&self, proposal_id: &Uuid, key_share: &ThresholdKeyShare) -> Result<Vec<(UserId, DecryptionShare)>> {
         let proposal = self.proposals.get(proposal_id)
             .ok_or_else(|| Error::NotFound("Proposal not found".to_string()))?;
         
@@ -219,17 +487,88 @@ impl VoteManager {
             return Err(Error::validation("Voting period not ended".to_string()));
         }
         
+        self.votes.get(proposal_id).unwrap()
+            .iter()
+            .filter_map(|vote| match &vote.ballot {
+                SealedBallot::Encrypted(ciphertext) => Some((vote, ciphertext)),
+                SealedBallot::Commitment(_) => None,
+            })
+            .map(|(vote, ciphertext)| {
+                Ok((vote.voter.clone(), key_share.decryption_share(ciphertext, &mut OsRng)?))
+            })
+            .collect()
+    }
+
+    /// Accept committee decryption shares and decrypt every ballot that has
+    /// reached the threshold
+    ///
+    /// Shares are verified before any are stored; one invalid share rejects
+    /// the whole batch. Returns the number of ballots decrypted by this call.
+    pub fn submit_decryption_shares(&mut self, proposal_id: &Uuid, shares: Vec<(UserId, DecryptionShare)>) -> Result<usize> {
+        let proposal = self.proposals.get(proposal_id)
+            .ok_or_else(|| Error::NotFound("Proposal not found".to_string()))?;
+        
+        let BallotMode::Threshold(committee) = &proposal.ballot_mode else {
+            return Err(Error::validation("Proposal does not use threshold ballots".to_string()));
+        };
+        if proposal.finalized {
+            return Err(Error::validation("Proposal already finalized".to_string()));
+        }
+        if Utc::now() < proposal.deadline {
+            return Err(Error::validation("Voting period not ended".to_string()));
+        }
+        
         let votes = self.votes.get_mut(proposal_id).unwrap();
-        for vote in votes.iter_mut() {
-            if vote.revealed_ballot.is_none() {
-                vote.reveal(decryption_key)?;
+        for (voter, share) in &shares {
+            let ciphertext = votes.iter()
+                .find(|v| &v.voter == voter)
+                .and_then(|v| match &v.ballot {
+                    SealedBallot::Encrypted(ciphertext) => Some(ciphertext),
+                    SealedBallot::Commitment(_) => None,
+                })
+                .ok_or_else(|| Error::NotFound("No encrypted ballot from this voter".to_string()))?;
+            if !committee.verify_share(ciphertext, share) {
+                return Err(Error::validation(format!("Invalid decryption share from holder {}", share.index)));
             }
         }
         
-        Ok(())
+        let stored = self.decryption_shares.entry(*proposal_id).or_default();
+        for (voter, share) in shares {
+            let ballot_shares = stored.entry(voter).or_default();
+            if !ballot_shares.iter().any(|s| s.index == share.index) {
+                ballot_shares.push(share);
+            }
+        }
+        
+        let mut decrypted = 0;
+        for vote in votes.iter_mut().filter(|v| v.revealed_ballot.is_none()) {
+            let (SealedBallot::Encrypted(ciphertext), Some(ballot_shares)) = (&vote.ballot, stored.get(&vote.voter)) else {
+                continue;
+            };
+            if ballot_shares.len() < committee.threshold as usize {
+                continue;
+            }
+            
+            // A ballot that fails to decrypt to a valid choice is never tallied
+            let aad = ballot_aad(proposal_id, &vote.voter);
+            vote.revealed_ballot = match committee.combine(ciphertext, ballot_shares, &aad).ok().as_deref() {
+                Some([0]) => Some(false),
+                Some([1]) => Some(true),
+                _ => continue,
+            };
+            decrypted += 1;
+        }
+        
+        Ok(decrypted)
     }
 
     /// Finalize a proposal (count votes and determine outcome)
+    ///
+    /// Only verified ballots are tallied, weighted by the proposal's tally
+    /// mode, and each counts the power delegated to its voter on the
+    /// proposal's topic as of the deadline. Quorum is measured in raw stake.
+    /// For commit-reveal proposals this waits for the reveal window to close
+    /// and penalizes every voter who did not open their commitment.
     pub fn finalize_proposal(&mut self, proposal_id: &Uuid) -> Result<bool> {
         let proposal = self.proposals.get_mut(proposal_id)
             .ok_or_else(|| Error::NotFound("Proposal not found".to_string()))?;
@@ -242,31 +581,75 @@ impl VoteManager {
             return Err(Error::validation("Voting period not ended".to_string()));
         }
         
-        // Tally revealed votes
+        if Utc::now() < proposal.reveal_deadline {
+            return Err(Error::validation("Reveal period not ended".to_string()));
+        }
+        
+        let mode = self.proposal_modes.get(proposal_id).copied().unwrap_or(TallyMode::StakeWeighted);
         let votes = self.votes.get(proposal_id).unwrap();
         let mut votes_for = 0u64;
         let mut votes_against = 0u64;
+        let mut turnout = 0u64;
+        let mut count = |choice: bool, voter: &UserId, power: u64, committed_at: DateTime<Utc>| {
+            let verified = self.verified_identities.contains(voter);
+            if let Some(weight) = mode.weight(power, verified, proposal.deadline - committed_at) {
+                if choice {
+                    votes_for += weight;
+                } else {
+                    votes_against += weight;
+                }
+                turnout += power;
+            }
+        };
         
+        // Tally revealed votes
         for vote in votes {
-            if let Some(ballot) = vote.revealed_ballot {
-                if ballot {
-                    votes_for += vote.voting_power;
-                } else {
-                    votes_against += vote.voting_power;
+            match vote.revealed_ballot {
+                Some(choice) => count(choice, &vote.voter, vote.voting_power, vote.cast_at),
+                None if proposal.ballot_mode == BallotMode::CommitReveal => {
+                    let penalty = vote.voting_power * self.non_reveal_penalty_bps as u64 / 10_000;
+                    *self.penalties.entry(vote.voter.clone()).or_insert(0) += penalty;
                 }
+                // Undecryptable threshold ballots are not the voter's fault
+                None => {}
+            }
+        }
+        
+        // Delegated power follows the chain to the first verified ballot;
+        // anyone who cast a ballot themselves keeps their own power
+        let ballots: HashMap<&UserId, &Vote> = votes.iter().map(|v| (&v.voter, v)).collect();
+        for delegation in self.delegations.active_at(&proposal.proposal_type, proposal.deadline) {
+            if ballots.contains_key(&delegation.delegator) {
+                continue;
+            }
+            let delegate = self.delegations.resolve(
+                &delegation.delegator,
+                &proposal.proposal_type,
+                proposal.deadline,
+                |user| ballots.get(user).is_some_and(|v| v.revealed_ballot.is_some()),
+            );
+            if let Some(ballot) = delegate.map(|d| ballots[d]) {
+                let committed_at = ballot.cast_at.max(delegation.since);
+                count(ballot.revealed_ballot.unwrap(), &delegation.delegator, delegation.voting_power, committed_at);
             }
         }
         
         proposal.votes_for = votes_for;
         proposal.votes_against = votes_against;
         proposal.finalized = true;
+        self.decryption_shares.remove(proposal_id);
         
         // Check quorum and result
-        if !proposal.meets_quorum(self.total_stake) {
-            return Ok(false); // Failed due to quorum
-        }
+        let required_turnout = (self.total_stake * proposal.quorum_percentage as u64) / 100;
+        let passed = turnout >= required_turnout && proposal.passes();
+        self.outcomes.insert(*proposal_id, passed);
         
-        Ok(proposal.passes())
+        Ok(passed)
+    }
+
+    /// Whether a finalized proposal passed; `None` until it is finalized
+    pub fn proposal_outcome(&self, proposal_id: &Uuid) -> Option<bool> {
+        self.outcomes.get(proposal_id).copied()
     }
 
     /// Get proposal by ID
@@ -281,12 +664,43 @@ impl VoteManager {
             .collect()
     }
 
+    /// Voting power a voter has forfeited by not revealing ballots, which
+    /// is deducted from every later ballot and delegation
+    pub fn penalty(&self, voter: &UserId) -> u64 {
+        self.penalties.get(voter).copied().unwrap_or(0)
+    }
+
+    /// Set the non-reveal penalty in basis points of voting power
+    pub fn set_non_reveal_penalty_bps(&mut self, bps: u32) -> Result<()> {
+        if bps > 10_000 {
+            return Err(Error::validation("Penalty cannot exceed 100%".to_string()));
+        }
+        self.non_reveal_penalty_bps = bps;
+        Ok(())
+    }
+
     /// Update total stake
     pub fn update_total_stake(&mut self, new_total: u64) {
         self.total_stake = new_total;
     }
 }
 
+/// Hash commitment binding a choice to its proposal and voter
+fn ballot_commitment(proposal_id: &Uuid, voter: &UserId, opening: &BallotOpening) -> [u8; 32] {
+    let mut data = Vec::with_capacity(COMMITMENT_DOMAIN.len() + 16 + 16 + 1 + 32);
+    data.extend_from_slice(COMMITMENT_DOMAIN);
+    data.extend_from_slice(proposal_id.as_bytes());
+    data.extend_from_slice(voter.0.as_bytes());
+    data.push(opening.vote_for as u8);
+    data.extend_from_slice(&opening.salt);
+    dchat_crypto::hash(&data)
+}
+
+/// Associated data that ties an encrypted ballot to its proposal and voter
+fn ballot_aad(proposal_id: &Uuid, voter: &UserId) -> Vec<u8> {
+    [proposal_id.as_bytes().as_slice(), voter.0.as_bytes().as_slice()].concat()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -309,18 +723,199 @@ mod tests {
         assert!(!proposal.finalized);
     }
 
+    /// End voting now, optionally leaving the reveal window open
+    fn close_voting(manager: &mut VoteManager, id: &Uuid, reveal_window_open: bool) {
+        let proposal = manager.proposals.get_mut(id).unwrap();
+        let now = Utc::now();
+        proposal.deadline = proposal.deadline.min(now);
+        proposal.reveal_deadline = if reveal_window_open {
+            now + Duration::hours(1)
+        } else {
+            now
+        };
+    }
+
+    /// Submit a proposal of `proposal_type` with a 10% quorum
+    fn submit(manager: &mut VoteManager, proposal_type: ProposalType) -> Uuid {
+        let proposal = Proposal::new(
+            UserId::new(),
+            proposal_type,
+            "Tally".to_string(),
+            "Test".to_string(),
+            7,
+            10,
+        ).unwrap();
+        manager.submit_proposal(proposal).unwrap()
+    }
+
+    /// Cast a commit-reveal ballot, returning its opening
+    fn cast(manager: &mut VoteManager, proposal_id: Uuid, voter: &UserId, choice: bool, power: u64) -> BallotOpening {
+        let (vote, opening) = Vote::commit(voter.clone(), proposal_id, choice, power);
+        manager.cast_vote(vote).unwrap();
+        opening
+    }
+
+    fn reveal_all(manager: &mut VoteManager, proposal_id: Uuid, openings: &[(UserId, BallotOpening)]) {
+        close_voting(manager, &proposal_id, true);
+        for (voter, opening) in openings {
+            manager.reveal_vote(&proposal_id, voter, opening).unwrap();
+        }
+        close_voting(manager, &proposal_id, false);
+    }
+
+    fn verified_badge() -> dchat_identity::verification::VerifiedBadge {
+        use dchat_identity::verification::{ProofType, VerificationProof, VerifiedBadge};
+        VerifiedBadge {
+            badge_type: BadgeType::Verified,
+            issued_at: Utc::now(),
+            expires_at: None,
+            issuer: "test-authority".to_string(),
+            proof: VerificationProof {
+                proof_type: ProofType::AuthoritySigned { authority: "test-authority".to_string() },
+                signature: dchat_core::types::Signature::new(vec![0u8; 64]),
+                metadata: HashMap::new(),
+            },
+        }
+    }
+
     #[test]
     fn test_vote_encryption_decryption() {
         let voter = UserId::new();
         let proposal_id = Uuid::new_v4();
-        let key = [42u8; 32];
         
-        let mut vote = Vote::new_encrypted(voter, proposal_id, true, 100, &key).unwrap();
+        let (mut vote, opening) = Vote::commit(voter, proposal_id, true, 100);
         assert!(vote.revealed_ballot.is_none());
         
-        let revealed = vote.reveal(&key).unwrap();
+        // A different choice or salt does not open the commitment
+        let mut forged = opening.clone();
+        forged.vote_for = false;
+        assert!(vote.open(&forged).is_err());
+        forged = opening.clone();
+        forged.salt[0] ^= 1;
+        assert!(vote.open(&forged).is_err());
+        
+        let revealed = vote.open(&opening).unwrap();
         assert_eq!(revealed, true);
         assert_eq!(vote.revealed_ballot, Some(true));
+        assert!(vote.open(&opening).is_err());
+    }
+
+    #[test]
+    fn test_commit_reveal_tallies_only_opened_ballots() {
+        let mut manager = VoteManager::new(1000);
+        let proposal = Proposal::new(
+            UserId::new(),
+            ProposalType::FeatureChange,
+            "Commit-reveal".to_string(),
+            "Test".to_string(),
+            7,
+            10,
+        ).unwrap();
+        let proposal_id = manager.submit_proposal(proposal).unwrap();
+        
+        let (alice, bob, carol) = (UserId::new(), UserId::new(), UserId::new());
+        let (vote, alice_opening) = Vote::commit(alice.clone(), proposal_id, true, 300);
+        manager.cast_vote(vote).unwrap();
+        let (vote, bob_opening) = Vote::commit(bob.clone(), proposal_id, false, 200);
+        manager.cast_vote(vote).unwrap();
+        let (vote, _) = Vote::commit(carol.clone(), proposal_id, false, 500);
+        manager.cast_vote(vote).unwrap();
+        
+        // Nothing can be opened while voting is open
+        assert!(manager.reveal_vote(&proposal_id, &alice, &alice_opening).is_err());
+        
+        close_voting(&mut manager, &proposal_id, true);
+        assert!(manager.reveal_vote(&proposal_id, &alice, &alice_opening).unwrap());
+        // Bob tries to swap his vote; the mismatching opening is refused
+        let swapped = BallotOpening { vote_for: true, ..bob_opening };
+        assert!(manager.reveal_vote(&proposal_id, &bob, &swapped).is_err());
+        assert!(manager.finalize_proposal(&proposal_id).is_err());
+        
+        close_voting(&mut manager, &proposal_id, false);
+        assert!(manager.finalize_proposal(&proposal_id).unwrap());
+        let proposal = manager.get_proposal(&proposal_id).unwrap();
+        assert_eq!(proposal.votes_for, 300);
+        assert_eq!(proposal.votes_against, 0);
+        
+        // Withheld ballots cost 10% of the voting power behind them
+        assert_eq!(manager.penalty(&alice), 0);
+        assert_eq!(manager.penalty(&bob), 20);
+        assert_eq!(manager.penalty(&carol), 50);
+    }
+
+    #[test]
+    fn test_non_reveal_penalty_reduces_later_voting_power() {
+        let mut manager = VoteManager::new(1000);
+        let (alice, carol) = (UserId::new(), UserId::new());
+        
+        let first = submit(&mut manager, ProposalType::FeatureChange);
+        let alice_opening = cast(&mut manager, first, &alice, true, 500);
+        cast(&mut manager, first, &carol, false, 500);
+        reveal_all(&mut manager, first, &[(alice.clone(), alice_opening)]);
+        manager.finalize_proposal(&first).unwrap();
+        assert_eq!(manager.penalty(&carol), 50);
+        
+        // Carol's next ballot carries 450 of her 500
+        let second = submit(&mut manager, ProposalType::FeatureChange);
+        let alice_opening = cast(&mut manager, second, &alice, true, 460);
+        let carol_opening = cast(&mut manager, second, &carol, false, 500);
+        reveal_all(&mut manager, second, &[(alice.clone(), alice_opening), (carol.clone(), carol_opening)]);
+        assert!(manager.finalize_proposal(&second).unwrap());
+        assert_eq!(manager.get_proposal(&second).unwrap().votes_against, 450);
+        
+        // Delegations are reduced the same way, and a voter with nothing
+        // left cannot vote at all
+        manager.delegate(carol.clone(), alice.clone(), ProposalType::Slashing, 500).unwrap();
+        assert_eq!(manager.delegations().active_at(&ProposalType::Slashing, Utc::now() + Duration::hours(1))[0].voting_power, 450);
+        let third = submit(&mut manager, ProposalType::FeatureChange);
+        let (vote, _) = Vote::commit(carol, third, true, 50);
+        assert!(manager.cast_vote(vote).is_err());
+    }
+
+    #[test]
+    fn test_threshold_ballots_need_committee_shares() {
+        let (committee, key_shares) = dchat_crypto::threshold::deal(2, 3, &mut OsRng).unwrap();
+        let mut manager = VoteManager::new(1000);
+        let proposal = Proposal::new(
+            UserId::new(),
+            ProposalType::FeatureChange,
+            "Threshold".to_string(),
+            "Test".to_string(),
+            7,
+            10,
+        ).unwrap().with_threshold_ballots(committee.clone());
+        let proposal_id = manager.submit_proposal(proposal).unwrap();
+        
+        // Commitments are refused on a threshold proposal
+        let (vote, _) = Vote::commit(UserId::new(), proposal_id, true, 100);
+        assert!(manager.cast_vote(vote).is_err());
+        
+        let voters = [(UserId::new(), true, 400), (UserId::new(), false, 100), (UserId::new(), true, 50)];
+        for (voter, choice, power) in &voters {
+            let vote = Vote::encrypted(voter.clone(), proposal_id, *choice, *power, &committee).unwrap();
+            manager.cast_vote(vote).unwrap();
+        }
+        
+        // Committee members cannot decrypt before the deadline
+        assert!(manager.decryption_shares(&proposal_id, &key_shares[0]).is_err());
+        close_voting(&mut manager, &proposal_id, false);
+        
+        let first = manager.decryption_shares(&proposal_id, &key_shares[0]).unwrap();
+        assert_eq!(manager.submit_decryption_shares(&proposal_id, first).unwrap(), 0);
+        
+        // A share for the wrong ballot is rejected
+        let mut third = manager.decryption_shares(&proposal_id, &key_shares[2]).unwrap();
+        let (head, tail) = third.split_at_mut(1);
+        std::mem::swap(&mut head[0].1, &mut tail[0].1);
+        assert!(manager.submit_decryption_shares(&proposal_id, third).is_err());
+        
+        let second = manager.decryption_shares(&proposal_id, &key_shares[1]).unwrap();
+        assert_eq!(manager.submit_decryption_shares(&proposal_id, second).unwrap(), 3);
+        
+        assert!(manager.finalize_proposal(&proposal_id).unwrap());
+        let proposal = manager.get_proposal(&proposal_id).unwrap();
+        assert_eq!(proposal.votes_for, 450);
+        assert_eq!(proposal.votes_against, 100);
     }
 
     #[test]
@@ -357,8 +952,7 @@ mod tests {
         ).unwrap();
         let proposal_id = manager.submit_proposal(proposal).unwrap();
         
-        let key = [1u8; 32];
-        let vote = Vote::new_encrypted(voter, proposal_id, true, 100, &key).unwrap();
+        let (vote, _) = Vote::commit(voter, proposal_id, true, 100);
         
         manager.cast_vote(vote).unwrap();
     }
@@ -379,9 +973,8 @@ mod tests {
         ).unwrap();
         let proposal_id = manager.submit_proposal(proposal).unwrap();
         
-        let key = [1u8; 32];
-        let vote1 = Vote::new_encrypted(voter.clone(), proposal_id, true, 100, &key).unwrap();
-        let vote2 = Vote::new_encrypted(voter, proposal_id, false, 100, &key).unwrap();
+        let (vote1, _) = Vote::commit(voter.clone(), proposal_id, true, 100);
+        let (vote2, _) = Vote::commit(voter, proposal_id, false, 100);
         
         manager.cast_vote(vote1).unwrap();
         let result = manager.cast_vote(vote2);
@@ -398,10 +991,13 @@ mod tests {
             description: "Test".to_string(),
             created_at: Utc::now(),
             deadline: Utc::now() + Duration::days(7),
+            reveal_deadline: Utc::now() + Duration::days(8),
+            ballot_mode: BallotMode::CommitReveal,
             quorum_percentage: 50,
             votes_for: 600,
             votes_against: 400,
             finalized: false,
+            payload: None,
         };
         
         assert!(proposal.meets_quorum(2000)); // 1000/2000 = 50%
@@ -418,10 +1014,13 @@ mod tests {
             description: "Test".to_string(),
             created_at: Utc::now(),
             deadline: Utc::now() + Duration::days(7),
+            reveal_deadline: Utc::now() + Duration::days(8),
+            ballot_mode: BallotMode::CommitReveal,
             quorum_percentage: 50,
             votes_for: 600,
             votes_against: 400,
             finalized: false,
+            payload: None,
         };
         
         assert!(proposal.passes());
@@ -462,4 +1061,129 @@ mod tests {
         assert_eq!(active.len(), 1);
         assert_eq!(active[0].title, "Active");
     }
+
+    #[test]
+    fn test_quadratic_voting_resists_whales_and_sybils() {
+        let mut manager = VoteManager::new(20_000);
+        manager.set_tally_mode(ProposalType::ModerationPolicy, TallyMode::Quadratic);
+        let proposal_id = submit(&mut manager, ProposalType::ModerationPolicy);
+        
+        let mut badges = BadgeManager::new();
+        let whale = UserId::new();
+        let community: Vec<UserId> = (0..11).map(|_| UserId::new()).collect();
+        for user in community.iter().chain([&whale]) {
+            badges.award_badge(user.clone(), verified_badge()).unwrap();
+            manager.register_verified_identity(user.clone(), &badges).unwrap();
+        }
+        
+        // The whale's sock puppets have no badge and cannot vote
+        let sybil = UserId::new();
+        assert!(manager.register_verified_identity(sybil.clone(), &badges).is_err());
+        let (vote, _) = Vote::commit(sybil.clone(), proposal_id, false, 5_000);
+        assert!(manager.cast_vote(vote).is_err());
+        // ...and power they delegate to the whale counts for nothing
+        manager.delegate(sybil, whale.clone(), ProposalType::ModerationPolicy, 5_000).unwrap();
+        
+        // 10,000 stake against is worth 100; eleven holders of 100 are worth 110
+        let mut openings = vec![(whale.clone(), cast(&mut manager, proposal_id, &whale, false, 10_000))];
+        for user in &community {
+            openings.push((user.clone(), cast(&mut manager, proposal_id, user, true, 100)));
+        }
+        reveal_all(&mut manager, proposal_id, &openings);
+        
+        assert!(manager.finalize_proposal(&proposal_id).unwrap());
+        let proposal = manager.get_proposal(&proposal_id).unwrap();
+        assert_eq!(proposal.votes_for, 110);
+        assert_eq!(proposal.votes_against, 100);
+        
+        // Mode is fixed at submission; other types stay stake-weighted
+        manager.set_tally_mode(ProposalType::ModerationPolicy, TallyMode::StakeWeighted);
+        assert_eq!(manager.proposal_tally_mode(&proposal_id), Some(TallyMode::Quadratic));
+        assert_eq!(manager.tally_mode(&ProposalType::FeatureChange), TallyMode::StakeWeighted);
+    }
+
+    #[test]
+    fn test_conviction_voting_favors_early_commitment() {
+        let mut manager = VoteManager::new(10_000);
+        let proposal_id = submit(&mut manager, ProposalType::TreasurySpend);
+        assert_eq!(
+            manager.proposal_tally_mode(&proposal_id),
+            Some(TallyMode::Conviction { half_life_hours: DEFAULT_CONVICTION_HALF_LIFE_HOURS })
+        );
+        
+        let (early, whale) = (UserId::new(), UserId::new());
+        let early_opening = cast(&mut manager, proposal_id, &early, true, 1_000);
+        
+        // The whale tries to backdate its ballot; the manager restamps it
+        let (mut late_vote, whale_opening) = Vote::commit(whale.clone(), proposal_id, false, 5_000);
+        late_vote.cast_at = Utc::now() - Duration::days(30);
+        manager.cast_vote(late_vote).unwrap();
+        
+        close_voting(&mut manager, &proposal_id, true);
+        let deadline = manager.get_proposal(&proposal_id).unwrap().deadline;
+        for vote in manager.votes.get_mut(&proposal_id).unwrap() {
+            assert!(vote.cast_at > Utc::now() - Duration::minutes(1));
+            // Simulate the early voter committing a week ahead, the whale a minute before close
+            vote.cast_at = if vote.voter == early {
+                deadline - Duration::days(7)
+            } else {
+                deadline - Duration::minutes(1)
+            };
+        }
+        reveal_all(&mut manager, proposal_id, &[(early, early_opening), (whale, whale_opening)]);
+        
+        assert!(manager.finalize_proposal(&proposal_id).unwrap());
+        let proposal = manager.get_proposal(&proposal_id).unwrap();
+        // 1,000 held for 7 days at a 72h half-life is ~80% of its stake
+        assert!(proposal.votes_for > 790 && proposal.votes_for < 1_000);
+        assert!(proposal.votes_against < 10);
+    }
+
+    #[test]
+    fn test_delegated_power_follows_chain_to_first_voter() {
+        let mut manager = VoteManager::new(1_000);
+        let topic = ProposalType::FeatureChange;
+        let (alice, bob, carol, dave) = (UserId::new(), UserId::new(), UserId::new(), UserId::new());
+        
+        // alice -> bob -> carol, and dave -> alice
+        manager.delegate(alice.clone(), bob.clone(), topic.clone(), 100).unwrap();
+        manager.delegate(bob.clone(), carol.clone(), topic.clone(), 200).unwrap();
+        manager.delegate(dave.clone(), alice.clone(), topic.clone(), 50).unwrap();
+        // carol -> dave would close the loop
+        assert!(manager.delegate(carol.clone(), dave.clone(), topic.clone(), 300).is_err());
+        // Delegations on another topic do not count here
+        manager.delegate(carol.clone(), bob.clone(), ProposalType::Slashing, 300).unwrap();
+        
+        let first = submit(&mut manager, topic.clone());
+        let carol_opening = cast(&mut manager, first, &carol, true, 300);
+        let bob_opening = cast(&mut manager, first, &bob, false, 200);
+        reveal_all(&mut manager, first, &[(carol.clone(), carol_opening), (bob.clone(), bob_opening)]);
+        
+        // Bob voted himself, so his power is his own and alice's and dave's stop at him
+        assert!(!manager.finalize_proposal(&first).unwrap());
+        let proposal = manager.get_proposal(&first).unwrap();
+        assert_eq!(proposal.votes_for, 300);
+        assert_eq!(proposal.votes_against, 350);
+        
+        // On the next proposal only carol votes; every chain reaches her
+        let second = submit(&mut manager, topic.clone());
+        let carol_opening = cast(&mut manager, second, &carol, true, 300);
+        reveal_all(&mut manager, second, &[(carol.clone(), carol_opening)]);
+        
+        // Revoking after the deadline does not pull power out of the tally
+        assert!(manager.revoke_delegation(&alice, &topic));
+        assert!(manager.finalize_proposal(&second).unwrap());
+        assert_eq!(manager.get_proposal(&second).unwrap().votes_for, 650);
+        
+        // A delegator who casts an unrevealed ballot loses the delegation
+        // and is penalized instead
+        let third = submit(&mut manager, topic.clone());
+        let carol_opening = cast(&mut manager, third, &carol, true, 300);
+        cast(&mut manager, third, &dave, false, 50);
+        reveal_all(&mut manager, third, &[(carol, carol_opening)]);
+        assert!(manager.finalize_proposal(&third).unwrap());
+        // bob's 200 still reaches carol; alice revoked and dave withheld
+        assert_eq!(manager.get_proposal(&third).unwrap().votes_for, 500);
+        assert_eq!(manager.penalty(&dave), 5);
+    }
 }
@@ -1,6 +1,8 @@
 // Biometric Authentication Module for dchat
 // Implements platform-agnostic biometric authentication (TouchID, FaceID, Fingerprint)
+// with a software key vault backend for hosts without biometric hardware
 
+use crate::vault::SharedKeyVault;
 use std::fmt;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -30,6 +32,9 @@ pub enum BiometricError {
     
     #[error("Timeout waiting for authentication")]
     Timeout,
+    
+    #[error("Key not found: {0}")]
+    KeyNotFound(String),
 }
 
 /// Biometric authentication type
@@ -110,16 +115,38 @@ pub struct BiometricAuthResult {
 #[allow(dead_code)]
 pub struct BiometricAuthenticator {
     config: BiometricConfig,
+    /// Software key store, used instead of the platform keystore when set
+    vault: Option<SharedKeyVault>,
 }
 
 impl BiometricAuthenticator {
     /// Create a new biometric authenticator
     pub fn new(config: BiometricConfig) -> Self {
-        Self { config }
+        Self { config, vault: None }
+    }
+
+    /// Create an authenticator whose keys live in a software key vault
+    ///
+    /// There is no biometric check in this mode; the vault passphrase (and
+    /// TPM, if bound) is what protects the keys.
+    pub fn with_software_vault(config: BiometricConfig, vault: SharedKeyVault) -> Self {
+        Self {
+            config,
+            vault: Some(vault),
+        }
     }
 
     /// Check biometric capabilities of the device
     pub async fn check_capabilities(&self) -> Result<BiometricCapability, BiometricError> {
+        if self.vault.is_some() {
+            return Ok(BiometricCapability {
+                available_types: vec![],
+                hardware_present: false,
+                enrolled: false,
+                device_info: "Software key vault - no biometric hardware".to_string(),
+            });
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.check_capabilities_ios().await
@@ -163,9 +190,15 @@ impl BiometricAuthenticator {
     /// Store a key in the secure enclave/keystore with biometric protection
     pub async fn store_key(
         &self,
-        _key_id: &str,
-        _key_data: &[u8],
+        key_id: &str,
+        key_data: &[u8],
     ) -> Result<(), BiometricError> {
+        if let Some(vault) = &self.vault {
+            return lock_vault(vault)?
+                .put_secret(&vault_key_id(key_id), key_data)
+                .map_err(|e| BiometricError::PlatformError(e.to_string()));
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.store_key_ios(key_id, key_data).await
@@ -183,7 +216,13 @@ impl BiometricAuthenticator {
     }
 
     /// Retrieve a key from secure storage (requires biometric authentication)
-    pub async fn retrieve_key(&self, _key_id: &str) -> Result<Vec<u8>, BiometricError> {
+    pub async fn retrieve_key(&self, key_id: &str) -> Result<Vec<u8>, BiometricError> {
+        if let Some(vault) = &self.vault {
+            return lock_vault(vault)?
+                .secret(&vault_key_id(key_id))
+                .ok_or_else(|| BiometricError::KeyNotFound(key_id.to_string()));
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.retrieve_key_ios(key_id).await
@@ -201,7 +240,18 @@ impl BiometricAuthenticator {
     }
 
     /// Delete a key from secure storage
-    pub async fn delete_key(&self, _key_id: &str) -> Result<(), BiometricError> {
+    pub async fn delete_key(&self, key_id: &str) -> Result<(), BiometricError> {
+        if let Some(vault) = &self.vault {
+            let mut vault = lock_vault(vault)?;
+            let vault_key_id = vault_key_id(key_id);
+            if vault.secret(&vault_key_id).is_none() {
+                return Err(BiometricError::KeyNotFound(key_id.to_string()));
+            }
+            return vault
+                .remove(&vault_key_id)
+                .map_err(|e| BiometricError::PlatformError(e.to_string()));
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.delete_key_ios(key_id).await
@@ -415,6 +465,17 @@ impl BiometricAuthenticator {
     }
 }
 
+/// Namespace biometric secrets inside a vault shared with the enclave
+fn vault_key_id(key_id: &str) -> String {
+    format!("biometric_{}", key_id)
+}
+
+fn lock_vault(vault: &SharedKeyVault) -> Result<std::sync::MutexGuard<'_, crate::vault::KeyVault>, BiometricError> {
+    vault
+        .lock()
+        .map_err(|_| BiometricError::PlatformError("Key vault lock poisoned".to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -431,4 +492,25 @@ mod tests {
         assert_eq!(BiometricType::Face.to_string(), "Face Recognition");
         assert_eq!(BiometricType::Fingerprint.to_string(), "Fingerprint");
     }
+
+    #[tokio::test]
+    async fn test_software_vault_keys_are_per_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = crate::vault::KeyVault::create(dir.path().join("bio.vault"), "pw", None)
+            .unwrap()
+            .into_shared();
+        let authenticator = BiometricAuthenticator::with_software_vault(BiometricConfig::default(), vault);
+
+        authenticator.store_key("messages", b"key-one").await.unwrap();
+        authenticator.store_key("backups", b"key-two").await.unwrap();
+        assert_eq!(authenticator.retrieve_key("messages").await.unwrap(), b"key-one");
+        assert_eq!(authenticator.retrieve_key("backups").await.unwrap(), b"key-two");
+
+        authenticator.delete_key("messages").await.unwrap();
+        assert!(matches!(
+            authenticator.retrieve_key("messages").await,
+            Err(BiometricError::KeyNotFound(_))
+        ));
+        assert!(authenticator.delete_key("messages").await.is_err());
+    }
 }
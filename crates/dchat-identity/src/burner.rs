@@ -35,6 +35,19 @@ impl BurnerIdentity {
         }
     }
     
+    /// Burner for a key derived from a recovery phrase
+    ///
+    /// Like [`Identity::from_derived_key`](crate::identity::Identity::from_derived_key),
+    /// the ID is a hash of the key and the creation time is the Unix epoch,
+    /// so restoring the same phrase rebuilds the same burner.
+    pub fn from_derived_key(keypair: &KeyPair, parent_user_id: Option<UserId>) -> Self {
+        Self {
+            burner_id: crate::identity::derived_user_id("dchat burner id v1", keypair),
+            created_at: DateTime::UNIX_EPOCH,
+            ..Self::new(keypair, parent_user_id)
+        }
+    }
+    
     /// Create a time-limited burner
     pub fn with_expiry(keypair: &KeyPair, hours: i64, parent_user_id: Option<UserId>) -> Self {
         let mut identity = Self::new(keypair, parent_user_id);
@@ -202,6 +215,19 @@ mod tests {
         assert_eq!(burner.messages_sent, 0);
     }
     
+    #[test]
+    fn test_restored_burner_is_identical() {
+        let phrase = crate::mnemonic::MnemonicSeed::generate(12).unwrap().phrase();
+        let restore = || {
+            let keys = crate::mnemonic::MnemonicSeed::from_phrase(&phrase).unwrap().derive_keys("").unwrap();
+            BurnerIdentity::from_derived_key(&keys.burner_0, None)
+        };
+        
+        let (first, second) = (restore(), restore());
+        assert_eq!(serde_json::to_string(&first).unwrap(), serde_json::to_string(&second).unwrap());
+        assert_ne!(first.burner_id, BurnerIdentity::from_derived_key(&KeyPair::generate(), None).burner_id);
+    }
+    
     #[test]
     fn test_message_limit() {
         let keypair = KeyPair::generate();
@@ -0,0 +1,570 @@
+//! Conflict-free replicated state for multi-device sync
+//!
+//! Each device holds a [`ReplicatedState`] and edits it locally. Every edit
+//! returns a [`StateDelta`], itself a small CRDT state, which is sealed on the
+//! [`DeviceSyncChannel`] and queued as a [`SyncMessage`]. Merging is
+//! commutative, associative and idempotent, so replicas converge no matter
+//! how deltas are reordered or duplicated in transit.
+//!
+//! - Contacts: observed-remove set ([`OrSet`]); a concurrent add wins over a
+//!   remove that had not seen it.
+//! - Settings: last-writer-wins map ([`LwwMap`]) ordered by Lamport time,
+//!   with the replica ID breaking ties.
+//! - Read receipts: one grow-only counter ([`GCounter`]) per conversation.
+
+use crate::pairing::DeviceSyncChannel;
+use crate::sync::{SyncMessage, SyncMessageType};
+use dchat_core::error::{Error, Result};
+use dchat_core::types::UserId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hash;
+
+/// Identifies a replica (one per device)
+pub type ReplicaId = String;
+
+/// A unique event: the `counter`-th operation of `replica`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Dot {
+    pub replica: ReplicaId,
+    pub counter: u64,
+}
+
+/// Observed-remove set with add-wins semantics
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrSet<T: Eq + Hash> {
+    /// Live add tags per element
+    adds: HashMap<T, HashSet<Dot>>,
+    /// Tags that have been removed
+    removed: HashSet<Dot>,
+}
+
+impl<T: Eq + Hash + Clone> OrSet<T> {
+    pub fn new() -> Self {
+        Self {
+            adds: HashMap::new(),
+            removed: HashSet::new(),
+        }
+    }
+
+    /// Add `value` under a fresh `dot`, returning the delta
+    pub fn add(&mut self, value: T, dot: Dot) -> Self {
+        let mut delta = Self::new();
+        delta.adds.entry(value).or_default().insert(dot);
+        self.merge(&delta);
+        delta
+    }
+
+    /// Remove every add of `value` this replica has seen
+    ///
+    /// Returns `None` if the value is not present.
+    pub fn remove(&mut self, value: &T) -> Option<Self> {
+        let tags = self.adds.get(value)?.clone();
+        let delta = Self {
+            adds: HashMap::new(),
+            removed: tags,
+        };
+        self.merge(&delta);
+        Some(delta)
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.adds.contains_key(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.adds.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.adds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.adds.is_empty()
+    }
+
+    /// Join with another state or delta
+    pub fn merge(&mut self, other: &Self) {
+        self.removed.extend(other.removed.iter().cloned());
+        for (value, tags) in &other.adds {
+            self.adds.entry(value.clone()).or_default().extend(tags.iter().cloned());
+        }
+
+        let removed = &self.removed;
+        self.adds.retain(|_, tags| {
+            tags.retain(|tag| !removed.contains(tag));
+            !tags.is_empty()
+        });
+    }
+
+    fn max_counter(&self) -> u64 {
+        self.adds
+            .values()
+            .flatten()
+            .chain(&self.removed)
+            .map(|dot| dot.counter)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl<T: Eq + Hash + Clone> Default for OrSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A value stamped with the Lamport time and replica that wrote it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LwwEntry<V> {
+    time: u64,
+    replica: ReplicaId,
+    /// `None` marks a removed key
+    value: Option<V>,
+}
+
+impl<V> LwwEntry<V> {
+    fn supersedes(&self, other: &Self) -> bool {
+        (self.time, &self.replica) > (other.time, &other.replica)
+    }
+}
+
+/// Last-writer-wins map
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LwwMap<K: Ord, V> {
+    entries: BTreeMap<K, LwwEntry<V>>,
+}
+
+impl<K: Ord + Clone, V: Clone> LwwMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Write `value` at Lamport time `time`, returning the delta
+    pub fn set(&mut self, key: K, value: V, time: u64, replica: ReplicaId) -> Self {
+        self.write(key, Some(value), time, replica)
+    }
+
+    /// Remove `key` at Lamport time `time`, returning the delta
+    pub fn remove(&mut self, key: K, time: u64, replica: ReplicaId) -> Self {
+        self.write(key, None, time, replica)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).and_then(|entry| entry.value.as_ref())
+    }
+
+    /// Live entries in key order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries
+            .iter()
+            .filter_map(|(key, entry)| entry.value.as_ref().map(|value| (key, value)))
+    }
+
+    /// Join with another state or delta
+    pub fn merge(&mut self, other: &Self) {
+        for (key, entry) in &other.entries {
+            match self.entries.get(key) {
+                Some(current) if !entry.supersedes(current) => {}
+                _ => {
+                    self.entries.insert(key.clone(), entry.clone());
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, key: K, value: Option<V>, time: u64, replica: ReplicaId) -> Self {
+        let mut delta = Self::new();
+        delta.entries.insert(key, LwwEntry { time, replica, value });
+        self.merge(&delta);
+        delta
+    }
+
+    fn max_time(&self) -> u64 {
+        self.entries.values().map(|entry| entry.time).max().unwrap_or(0)
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for LwwMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Grow-only counter
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GCounter {
+    counts: BTreeMap<ReplicaId, u64>,
+}
+
+impl GCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `amount` on behalf of `replica`, returning the delta
+    pub fn increment(&mut self, replica: ReplicaId, amount: u64) -> Self {
+        let count = self.counts.entry(replica.clone()).or_insert(0);
+        *count = count.saturating_add(amount);
+
+        let mut delta = Self::new();
+        delta.counts.insert(replica, *count);
+        delta
+    }
+
+    pub fn value(&self) -> u64 {
+        self.counts.values().fold(0, |sum, count| sum.saturating_add(*count))
+    }
+
+    /// Join with another state or delta
+    pub fn merge(&mut self, other: &Self) {
+        for (replica, count) in &other.counts {
+            let current = self.counts.entry(replica.clone()).or_insert(0);
+            *current = (*current).max(*count);
+        }
+    }
+}
+
+/// A change to one part of the replicated state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StateDelta {
+    Contacts(OrSet<UserId>),
+    Settings(LwwMap<String, String>),
+    ReadReceipts {
+        conversation_id: String,
+        counter: GCounter,
+    },
+}
+
+impl StateDelta {
+    /// Sync message type this delta travels as
+    pub fn message_type(&self) -> SyncMessageType {
+        match self {
+            StateDelta::Contacts(_) => SyncMessageType::ContactsUpdate,
+            StateDelta::Settings(_) => SyncMessageType::SettingsUpdate,
+            StateDelta::ReadReceipts { .. } => SyncMessageType::ReadReceipts,
+        }
+    }
+}
+
+/// Contacts, settings and read receipts replicated across a user's devices
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicatedState {
+    replica: ReplicaId,
+    /// Lamport clock, shared by OR-Set dots and LWW timestamps
+    clock: u64,
+    contacts: OrSet<UserId>,
+    settings: LwwMap<String, String>,
+    read_receipts: BTreeMap<String, GCounter>,
+}
+
+impl ReplicatedState {
+    pub fn new(replica: ReplicaId) -> Self {
+        Self {
+            replica,
+            clock: 0,
+            contacts: OrSet::new(),
+            settings: LwwMap::new(),
+            read_receipts: BTreeMap::new(),
+        }
+    }
+
+    pub fn replica(&self) -> &str {
+        &self.replica
+    }
+
+    pub fn add_contact(&mut self, contact: UserId) -> StateDelta {
+        let dot = self.next_dot();
+        StateDelta::Contacts(self.contacts.add(contact, dot))
+    }
+
+    /// Returns `None` if the contact is not present
+    pub fn remove_contact(&mut self, contact: &UserId) -> Option<StateDelta> {
+        self.contacts.remove(contact).map(StateDelta::Contacts)
+    }
+
+    pub fn set_setting(&mut self, key: impl Into<String>, value: impl Into<String>) -> StateDelta {
+        let time = self.tick();
+        StateDelta::Settings(self.settings.set(key.into(), value.into(), time, self.replica.clone()))
+    }
+
+    pub fn remove_setting(&mut self, key: impl Into<String>) -> StateDelta {
+        let time = self.tick();
+        StateDelta::Settings(self.settings.remove(key.into(), time, self.replica.clone()))
+    }
+
+    /// Record `count` more messages read in a conversation
+    pub fn mark_read(&mut self, conversation_id: impl Into<String>, count: u64) -> StateDelta {
+        let conversation_id = conversation_id.into();
+        let counter = self
+            .read_receipts
+            .entry(conversation_id.clone())
+            .or_default()
+            .increment(self.replica.clone(), count);
+        StateDelta::ReadReceipts { conversation_id, counter }
+    }
+
+    /// Apply a delta from another replica (or a duplicate of our own)
+    pub fn apply(&mut self, delta: &StateDelta) {
+        match delta {
+            StateDelta::Contacts(contacts) => {
+                self.observe(contacts.max_counter());
+                self.contacts.merge(contacts);
+            }
+            StateDelta::Settings(settings) => {
+                self.observe(settings.max_time());
+                self.settings.merge(settings);
+            }
+            StateDelta::ReadReceipts { conversation_id, counter } => {
+                self.read_receipts
+                    .entry(conversation_id.clone())
+                    .or_default()
+                    .merge(counter);
+            }
+        }
+    }
+
+    /// Merge a full state, e.g. a snapshot sent to a newly paired device
+    pub fn merge(&mut self, other: &ReplicatedState) {
+        self.observe(other.clock);
+        self.contacts.merge(&other.contacts);
+        self.settings.merge(&other.settings);
+        for (conversation_id, counter) in &other.read_receipts {
+            self.read_receipts
+                .entry(conversation_id.clone())
+                .or_default()
+                .merge(counter);
+        }
+    }
+
+    /// Encrypt a delta for the user's other devices
+    pub fn seal_delta(channel: &DeviceSyncChannel, delta: &StateDelta) -> Result<SyncMessage> {
+        channel.seal(delta.message_type(), delta)
+    }
+
+    /// Decrypt and apply a sync message from another device
+    pub fn apply_message(&mut self, channel: &DeviceSyncChannel, message: &SyncMessage) -> Result<()> {
+        let delta: StateDelta = channel.open(message)?;
+        if delta.message_type() != message.message_type {
+            return Err(Error::identity("Sync delta does not match its message type"));
+        }
+        self.apply(&delta);
+        Ok(())
+    }
+
+    pub fn contacts(&self) -> impl Iterator<Item = &UserId> {
+        self.contacts.iter()
+    }
+
+    pub fn has_contact(&self, contact: &UserId) -> bool {
+        self.contacts.contains(contact)
+    }
+
+    pub fn setting(&self, key: &str) -> Option<&str> {
+        self.settings.get(&key.to_string()).map(String::as_str)
+    }
+
+    pub fn settings(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.settings.iter()
+    }
+
+    pub fn read_count(&self, conversation_id: &str) -> u64 {
+        self.read_receipts
+            .get(conversation_id)
+            .map(GCounter::value)
+            .unwrap_or(0)
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn next_dot(&mut self) -> Dot {
+        Dot {
+            replica: self.replica.clone(),
+            counter: self.tick(),
+        }
+    }
+
+    fn observe(&mut self, time: u64) {
+        self.clock = self.clock.max(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pairing::PairingHost;
+    use dchat_crypto::keys::PrivateKey;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_concurrent_add_wins_over_remove() {
+        let contact = UserId::new();
+        let mut laptop = ReplicatedState::new("laptop".to_string());
+        let mut phone = ReplicatedState::new("phone".to_string());
+
+        let added = laptop.add_contact(contact.clone());
+        phone.apply(&added);
+
+        // Phone removes while laptop concurrently re-adds
+        let removed = phone.remove_contact(&contact).unwrap();
+        let re_added = laptop.add_contact(contact.clone());
+        laptop.apply(&removed);
+        phone.apply(&re_added);
+
+        assert!(laptop.has_contact(&contact));
+        assert!(phone.has_contact(&contact));
+    }
+
+    #[test]
+    fn test_last_writer_wins_settings() {
+        let mut laptop = ReplicatedState::new("laptop".to_string());
+        let mut phone = ReplicatedState::new("phone".to_string());
+
+        let first = laptop.set_setting("theme", "dark");
+        phone.apply(&first);
+        let second = phone.set_setting("theme", "light");
+        laptop.apply(&second);
+        // A stale duplicate does not roll the value back
+        laptop.apply(&first);
+
+        assert_eq!(laptop.setting("theme"), Some("light"));
+        assert_eq!(phone.setting("theme"), Some("light"));
+    }
+
+    #[test]
+    fn test_deltas_sync_encrypted_between_devices() {
+        let master_key = PrivateKey::generate();
+        let host = PairingHost::new(UserId::new(), master_key, 1, "peer").unwrap();
+        let laptop_channel = host.sync_channel("laptop".to_string()).unwrap();
+        let phone_channel = host.sync_channel("phone".to_string()).unwrap();
+
+        let mut laptop = ReplicatedState::new("laptop".to_string());
+        let mut phone = ReplicatedState::new("phone".to_string());
+
+        let contact = UserId::new();
+        let messages = [
+            laptop.add_contact(contact.clone()),
+            laptop.set_setting("notifications", "off"),
+            laptop.mark_read("general", 4),
+        ]
+        .iter()
+        .map(|delta| ReplicatedState::seal_delta(&laptop_channel, delta).unwrap())
+        .collect::<Vec<_>>();
+
+        assert_eq!(messages[0].message_type, SyncMessageType::ContactsUpdate);
+        assert_eq!(messages[1].message_type, SyncMessageType::SettingsUpdate);
+        assert_eq!(messages[2].message_type, SyncMessageType::ReadReceipts);
+
+        for message in messages.iter().rev() {
+            phone.apply_message(&phone_channel, message).unwrap();
+        }
+        assert!(phone.has_contact(&contact));
+        assert_eq!(phone.setting("notifications"), Some("off"));
+        assert_eq!(phone.read_count("general"), 4);
+
+        let mut tampered = messages[0].clone();
+        tampered.encrypted_payload[20] ^= 1;
+        assert!(phone.apply_message(&phone_channel, &tampered).is_err());
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        AddContact(usize),
+        RemoveContact(usize),
+        SetSetting(u8, u8),
+        RemoveSetting(u8),
+        MarkRead(u8, u64),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (0..4usize).prop_map(Op::AddContact),
+            (0..4usize).prop_map(Op::RemoveContact),
+            (0..3u8, any::<u8>()).prop_map(|(k, v)| Op::SetSetting(k, v)),
+            (0..3u8).prop_map(Op::RemoveSetting),
+            (0..2u8, 1..5u64).prop_map(|(c, n)| Op::MarkRead(c, n)),
+        ]
+    }
+
+    type Observed = (Vec<String>, Vec<(String, String)>, Vec<u64>);
+
+    fn observe(state: &ReplicatedState) -> Observed {
+        let mut contacts: Vec<String> = state.contacts().map(|c| c.0.to_string()).collect();
+        contacts.sort();
+        let settings = state.settings().map(|(k, v)| (k.clone(), v.clone())).collect();
+        let reads = (0..2).map(|c| state.read_count(&format!("conv-{}", c))).collect();
+        (contacts, settings, reads)
+    }
+
+    /// Run `ops` locally on `count` fresh replicas, returning the replicas
+    /// and every delta they produced
+    fn apply_ops(ops: &[(usize, Op)], count: usize) -> (Vec<ReplicatedState>, Vec<StateDelta>) {
+        let contacts: Vec<UserId> = (0..4).map(|_| UserId::new()).collect();
+        let mut replicas: Vec<ReplicatedState> = (0..count)
+            .map(|i| ReplicatedState::new(format!("device-{}", i)))
+            .collect();
+
+        let mut deltas = Vec::new();
+        for (replica, op) in ops {
+            let state = &mut replicas[replica % count];
+            let delta = match op {
+                Op::AddContact(c) => Some(state.add_contact(contacts[*c].clone())),
+                Op::RemoveContact(c) => state.remove_contact(&contacts[*c]),
+                Op::SetSetting(k, v) => Some(state.set_setting(format!("key-{}", k), v.to_string())),
+                Op::RemoveSetting(k) => Some(state.remove_setting(format!("key-{}", k))),
+                Op::MarkRead(c, n) => Some(state.mark_read(format!("conv-{}", c), *n)),
+            };
+            deltas.extend(delta);
+        }
+        (replicas, deltas)
+    }
+
+    /// Deliver every delta to every replica in an order derived from its seed
+    fn deliver(replicas: &mut [ReplicatedState], deltas: &[StateDelta], seeds: &[u64], duplicate: bool) {
+        for (state, seed) in replicas.iter_mut().zip(seeds) {
+            let mut order: Vec<usize> = (0..deltas.len()).collect();
+            order.sort_by_key(|i| *blake3::hash(&[seed.to_le_bytes(), (*i as u64).to_le_bytes()].concat()).as_bytes());
+            for i in order {
+                state.apply(&deltas[i]);
+                if duplicate {
+                    state.apply(&deltas[i]);
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_replicas_converge_under_reordering(
+            ops in proptest::collection::vec((0..3usize, op()), 0..40),
+            seeds in proptest::array::uniform3(any::<u64>()),
+            duplicate in any::<bool>(),
+        ) {
+            let (mut replicas, deltas) = apply_ops(&ops, seeds.len());
+            deliver(&mut replicas, &deltas, &seeds, duplicate);
+            let expected = observe(&replicas[0]);
+            for replica in &replicas[1..] {
+                prop_assert_eq!(observe(replica), expected.clone());
+            }
+        }
+
+        #[test]
+        fn prop_state_merge_is_commutative(
+            ops in proptest::collection::vec((0..2usize, op()), 0..30),
+        ) {
+            // Each replica only has its own edits
+            let (replicas, _) = apply_ops(&ops, 2);
+            let mut ab = replicas[0].clone();
+            ab.merge(&replicas[1]);
+            let mut ba = replicas[1].clone();
+            ba.merge(&replicas[0]);
+            prop_assert_eq!(observe(&ab), observe(&ba));
+        }
+    }
+}
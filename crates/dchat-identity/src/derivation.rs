@@ -1,10 +1,15 @@
 //! Hierarchical key derivation for identity management
 
 use dchat_core::error::{Error, Result};
-use dchat_crypto::keys::{PrivateKey, KeyPair, KeyDerivation};
+use dchat_crypto::keys::{ExtendedPrivateKey, PrivateKey, KeyPair, HARDENED_OFFSET};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// Represents a BIP-44 style key derivation path
+///
+/// Hardened components carry [`HARDENED_OFFSET`], as in BIP-32. Ed25519
+/// (SLIP-0010) can only derive hardened components, so every path dchat
+/// derives keys from is fully hardened.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct KeyPath {
     pub purpose: u32,      // e.g., 44 for BIP-44
@@ -26,19 +31,35 @@ impl KeyPath {
         }
     }
     
+    /// Create a path with every component hardened
+    pub fn hardened(purpose: u32, coin_type: u32, account: u32, change: u32, index: u32) -> Self {
+        Self::new(
+            purpose | HARDENED_OFFSET,
+            coin_type | HARDENED_OFFSET,
+            account | HARDENED_OFFSET,
+            change | HARDENED_OFFSET,
+            index | HARDENED_OFFSET,
+        )
+    }
+    
     /// Create a standard dchat path
     pub fn dchat_path(account: u32, change: u32, index: u32) -> Self {
-        Self::new(44, 1337, account, change, index) // 1337 as dchat coin type
+        Self::hardened(44, 1337, account, change, index) // 1337 as dchat coin type
     }
     
     /// Create a device key path
     pub fn device_path(device_index: u32) -> Self {
-        Self::new(44, 1337, 0, 1, device_index)
+        Self::hardened(44, 1337, 0, 1, device_index)
     }
     
     /// Create a burner identity path
     pub fn burner_path(burner_index: u32) -> Self {
-        Self::new(44, 1337, 1, 0, burner_index)
+        Self::hardened(44, 1337, 1, 0, burner_index)
+    }
+    
+    /// Create a stealth address key path (index 0 is the view key, 1 the spend key)
+    pub fn stealth_path(index: u32) -> Self {
+        Self::hardened(44, 1337, 0, 3, index)
     }
     
     /// Convert to array for derivation
@@ -46,7 +67,14 @@ impl KeyPath {
         [self.purpose, self.coin_type, self.account, self.change, self.index]
     }
     
-    /// Parse from string representation (m/44'/1337'/0'/0/0)
+    /// Whether every component is hardened
+    pub fn is_fully_hardened(&self) -> bool {
+        self.to_array().iter().all(|&index| index >= HARDENED_OFFSET)
+    }
+    
+    /// Parse from string representation (m/44'/1337'/0'/0'/0')
+    ///
+    /// Hardened components may be marked with `'`, `h` or `H`.
     pub fn from_string(path: &str) -> Result<Self> {
         let parts: Vec<&str> = path.trim_start_matches("m/").split('/').collect();
         
@@ -55,9 +83,17 @@ impl KeyPath {
         }
         
         let parse_part = |s: &str| -> Result<u32> {
-            s.trim_end_matches('\'')
+            let (digits, hardened) = match s.strip_suffix(['\'', 'h', 'H']) {
+                Some(digits) => (digits, true),
+                None => (s, false),
+            };
+            let index: u32 = digits
                 .parse()
-                .map_err(|_| Error::identity("Invalid key path component"))
+                .map_err(|_| Error::identity("Invalid key path component"))?;
+            if index >= HARDENED_OFFSET {
+                return Err(Error::identity("Key path component out of range"));
+            }
+            Ok(if hardened { index | HARDENED_OFFSET } else { index })
         };
         
         Ok(Self {
@@ -68,13 +104,19 @@ impl KeyPath {
             index: parse_part(parts[4])?,
         })
     }
-    
-    /// Convert to string representation
-    pub fn to_string(&self) -> String {
-        format!(
-            "m/{}'/{}'/{}'/{}/{}",
-            self.purpose, self.coin_type, self.account, self.change, self.index
-        )
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for index in self.to_array() {
+            if index >= HARDENED_OFFSET {
+                write!(f, "/{}'", index - HARDENED_OFFSET)?;
+            } else {
+                write!(f, "/{}", index)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -82,10 +124,28 @@ impl KeyPath {
 pub struct IdentityDerivation;
 
 impl IdentityDerivation {
+    /// SLIP-0010 master key for a seed (e.g. a BIP-39 seed)
+    pub fn master_from_seed(seed: &[u8]) -> Result<ExtendedPrivateKey> {
+        ExtendedPrivateKey::from_seed(seed)
+    }
+    
+    /// Derive a key from a SLIP-0010 master key using a fully hardened path
+    pub fn derive_from_master(master: &ExtendedPrivateKey, path: &KeyPath) -> Result<KeyPair> {
+        if !path.is_fully_hardened() {
+            return Err(Error::identity(format!(
+                "Ed25519 key paths must be fully hardened: {}",
+                path
+            )));
+        }
+        Ok(master.derive_path(&path.to_array())?.to_keypair())
+    }
+    
     /// Derive a key from a master key using a key path
+    ///
+    /// The master key's bytes are used as the SLIP-0010 seed.
     pub fn derive_key(master_key: &PrivateKey, path: &KeyPath) -> Result<KeyPair> {
-        let derived_key = KeyDerivation::derive_key_path(master_key, &path.to_array())?;
-        Ok(KeyPair::from_private_key(derived_key))
+        let master = Self::master_from_seed(master_key.as_bytes())?;
+        Self::derive_from_master(&master, path)
     }
     
     /// Derive a device key
@@ -105,16 +165,34 @@ impl IdentityDerivation {
         master_key: &PrivateKey,
         conversation_index: u32,
     ) -> Result<KeyPair> {
-        let path = KeyPath::new(44, 1337, 0, 2, conversation_index);
+        let path = KeyPath::hardened(44, 1337, 0, 2, conversation_index);
         Self::derive_key(master_key, &path)
     }
     
+    /// Derive the (view, spend) key pair behind the identity's stealth address
+    pub fn derive_stealth_keys(master_key: &PrivateKey) -> Result<(KeyPair, KeyPair)> {
+        let master = Self::master_from_seed(master_key.as_bytes())?;
+        Ok((
+            Self::derive_from_master(&master, &KeyPath::stealth_path(0))?,
+            Self::derive_from_master(&master, &KeyPath::stealth_path(1))?,
+        ))
+    }
+    
     /// Derive multiple keys for different purposes
     pub fn derive_all_keys(master_key: &PrivateKey) -> Result<DerivedKeys> {
+        Self::derive_all_from_master(&Self::master_from_seed(master_key.as_bytes())?)
+    }
+    
+    /// Derive the identity, first device and first burner keys from a seed
+    pub fn derive_all_keys_from_seed(seed: &[u8]) -> Result<DerivedKeys> {
+        Self::derive_all_from_master(&Self::master_from_seed(seed)?)
+    }
+    
+    fn derive_all_from_master(master: &ExtendedPrivateKey) -> Result<DerivedKeys> {
         Ok(DerivedKeys {
-            main_identity: Self::derive_key(master_key, &KeyPath::dchat_path(0, 0, 0))?,
-            device_0: Self::derive_device_key(master_key, 0)?,
-            burner_0: Self::derive_burner_key(master_key, 0)?,
+            main_identity: Self::derive_from_master(master, &KeyPath::dchat_path(0, 0, 0))?,
+            device_0: Self::derive_from_master(master, &KeyPath::device_path(0))?,
+            burner_0: Self::derive_from_master(master, &KeyPath::burner_path(0))?,
         })
     }
 }
@@ -134,16 +212,55 @@ mod tests {
     #[test]
     fn test_key_path() {
         let path = KeyPath::dchat_path(0, 0, 0);
-        assert_eq!(path.purpose, 44);
-        assert_eq!(path.coin_type, 1337);
+        assert_eq!(path.purpose, 44 | HARDENED_OFFSET);
+        assert_eq!(path.coin_type, 1337 | HARDENED_OFFSET);
+        assert!(path.is_fully_hardened());
         
         let path_str = path.to_string();
-        assert_eq!(path_str, "m/44'/1337'/0'/0/0");
+        assert_eq!(path_str, "m/44'/1337'/0'/0'/0'");
         
         let parsed = KeyPath::from_string(&path_str).unwrap();
         assert_eq!(path, parsed);
     }
     
+    #[test]
+    fn test_key_path_keeps_hardened_markers() {
+        let mixed = KeyPath::from_string("m/44'/1337h/0H/0/7").unwrap();
+        assert_eq!(mixed.account, HARDENED_OFFSET);
+        assert_eq!(mixed.change, 0);
+        assert_eq!(mixed.index, 7);
+        assert!(!mixed.is_fully_hardened());
+        assert_eq!(mixed.to_string(), "m/44'/1337'/0'/0/7");
+        
+        // Hardened and normal components of the same index are distinct
+        assert_ne!(mixed, KeyPath::from_string("m/44'/1337'/0'/0'/7'").unwrap());
+        assert!(KeyPath::from_string("m/44'/1337'/0'/0/2147483648").is_err());
+    }
+    
+    #[test]
+    fn test_non_hardened_paths_are_rejected() {
+        let master_key = PrivateKey::generate();
+        let path = KeyPath::new(44, 1337, 0, 0, 0);
+        assert!(IdentityDerivation::derive_key(&master_key, &path).is_err());
+    }
+    
+    #[test]
+    fn test_derivation_follows_slip10() {
+        // SLIP-0010 test vector 1, chain m/0'/1'/2'/2'/1000000000'
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = IdentityDerivation::master_from_seed(&seed).unwrap();
+        let path = KeyPath::hardened(0, 1, 2, 2, 1_000_000_000);
+        let keypair = IdentityDerivation::derive_from_master(&master, &path).unwrap();
+        assert_eq!(
+            hex::encode(keypair.private_key().as_bytes()),
+            "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793"
+        );
+        assert_eq!(
+            hex::encode(keypair.public_key().as_bytes()),
+            "3c24da049451555d51a7014a37337aa4e12d41e485abccfa46b47dfb2af54b7a"
+        );
+    }
+    
     #[test]
     fn test_key_derivation() {
         let master_key = PrivateKey::generate();
@@ -179,4 +296,15 @@ mod tests {
             keys.burner_0.public_key().as_bytes()
         );
     }
+    
+    #[test]
+    fn test_stealth_keys_are_stable() {
+        let master_key = PrivateKey::generate();
+        let (view, spend) = IdentityDerivation::derive_stealth_keys(&master_key).unwrap();
+        let (view_again, spend_again) = IdentityDerivation::derive_stealth_keys(&master_key).unwrap();
+        
+        assert_eq!(view.private_key().as_bytes(), view_again.private_key().as_bytes());
+        assert_eq!(spend.private_key().as_bytes(), spend_again.private_key().as_bytes());
+        assert_ne!(view.private_key().as_bytes(), spend.private_key().as_bytes());
+    }
 }
\ No newline at end of file
@@ -1,9 +1,16 @@
 // Secure Enclave Integration for dchat
 // Platform-specific secure hardware integration (iOS Secure Enclave, Android StrongBox)
+// with a software key vault backend for Linux servers and CI
 
+use crate::vault::{SharedKeyVault, VaultEntryKind};
+use dchat_crypto::keys::PublicKey as CryptoPublicKey;
+use dchat_crypto::signatures::{self, Signature};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// Domain separation for software attestation signatures
+const ATTESTATION_DOMAIN: &[u8] = b"dchat software enclave attestation v1";
+
 /// Secure enclave errors
 #[derive(Error, Debug)]
 pub enum EnclaveError {
@@ -84,20 +91,66 @@ pub struct DeviceAttestation {
     pub platform_data: Vec<u8>,
 }
 
+impl DeviceAttestation {
+    /// Verify a software-vault attestation
+    ///
+    /// The leaf of the chain is the vault's attestation public key; whoever
+    /// checks the attestation must already trust that key (e.g. pinned when
+    /// the node was enrolled).
+    pub fn verify(&self) -> Result<(), EnclaveError> {
+        let leaf = self
+            .certificate_chain
+            .first()
+            .and_then(|leaf| <[u8; 32]>::try_from(leaf.as_slice()).ok())
+            .ok_or_else(|| EnclaveError::AttestationFailed("Missing attestation key".to_string()))?;
+        let signature = <[u8; 64]>::try_from(self.signature.as_slice())
+            .map_err(|_| EnclaveError::AttestationFailed("Malformed signature".to_string()))?;
+
+        signatures::verify(
+            &CryptoPublicKey::from_bytes(leaf),
+            &attestation_message(&self.challenge, &self.platform_data),
+            &Signature::from_bytes(signature),
+        )
+        .map_err(|e| EnclaveError::AttestationFailed(e.to_string()))
+    }
+}
+
+fn attestation_message(challenge: &[u8], platform_data: &[u8]) -> Vec<u8> {
+    let mut message = ATTESTATION_DOMAIN.to_vec();
+    message.extend_from_slice(&(challenge.len() as u64).to_le_bytes());
+    message.extend_from_slice(challenge);
+    message.extend_from_slice(platform_data);
+    message
+}
+
 /// Secure enclave manager
 #[allow(dead_code)]
 pub struct SecureEnclave {
     config: EnclaveConfig,
+    /// Software backend, used instead of platform hardware when set
+    vault: Option<SharedKeyVault>,
 }
 
 impl SecureEnclave {
     /// Create a new secure enclave instance
     pub fn new(config: EnclaveConfig) -> Self {
-        Self { config }
+        Self { config, vault: None }
+    }
+
+    /// Create an enclave backed by a software key vault
+    pub fn with_software_vault(config: EnclaveConfig, vault: SharedKeyVault) -> Self {
+        Self {
+            config,
+            vault: Some(vault),
+        }
     }
 
     /// Check if secure enclave is available on this device
     pub async fn is_available(&self) -> Result<bool, EnclaveError> {
+        if self.vault.is_some() {
+            return Ok(true);
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.is_available_ios().await
@@ -115,7 +168,11 @@ impl SecureEnclave {
     }
 
     /// Generate a new key pair in the secure enclave
-    pub async fn generate_key(&self, _key_id: &str) -> Result<EnclaveKey, EnclaveError> {
+    pub async fn generate_key(&self, key_id: &str) -> Result<EnclaveKey, EnclaveError> {
+        if let Some(vault) = &self.vault {
+            return self.generate_key_software(vault, key_id);
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.generate_key_ios(key_id).await
@@ -133,7 +190,11 @@ impl SecureEnclave {
     }
 
     /// Sign data using enclave key
-    pub async fn sign(&self, _key_id: &str, _data: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+    pub async fn sign(&self, key_id: &str, data: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+        if let Some(vault) = &self.vault {
+            return self.sign_software(vault, key_id, data);
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.sign_ios(key_id, data).await
@@ -151,7 +212,11 @@ impl SecureEnclave {
     }
 
     /// Get public key for an enclave key
-    pub async fn get_public_key(&self, _key_id: &str) -> Result<Vec<u8>, EnclaveError> {
+    pub async fn get_public_key(&self, key_id: &str) -> Result<Vec<u8>, EnclaveError> {
+        if let Some(vault) = &self.vault {
+            return self.get_public_key_software(vault, key_id);
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.get_public_key_ios(key_id).await
@@ -169,7 +234,11 @@ impl SecureEnclave {
     }
 
     /// Delete a key from the secure enclave
-    pub async fn delete_key(&self, _key_id: &str) -> Result<(), EnclaveError> {
+    pub async fn delete_key(&self, key_id: &str) -> Result<(), EnclaveError> {
+        if let Some(vault) = &self.vault {
+            return self.delete_key_software(vault, key_id);
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.delete_key_ios(key_id).await
@@ -187,7 +256,11 @@ impl SecureEnclave {
     }
 
     /// Perform device attestation (prove key is in secure hardware)
-    pub async fn attest_device(&self, _challenge: &[u8]) -> Result<DeviceAttestation, EnclaveError> {
+    pub async fn attest_device(&self, challenge: &[u8]) -> Result<DeviceAttestation, EnclaveError> {
+        if let Some(vault) = &self.vault {
+            return self.attest_device_software(vault, challenge);
+        }
+        
         #[cfg(target_os = "ios")]
         {
             self.attest_device_ios(challenge).await
@@ -204,6 +277,89 @@ impl SecureEnclave {
         }
     }
 
+    // Software key vault implementations
+    fn full_key_id(&self, key_id: &str) -> String {
+        format!("{}_{}", self.config.key_prefix, key_id)
+    }
+
+    fn generate_key_software(&self, vault: &SharedKeyVault, key_id: &str) -> Result<EnclaveKey, EnclaveError> {
+        if self.config.algorithm != EnclaveAlgorithm::Ed25519 {
+            return Err(EnclaveError::KeyGenerationFailed(
+                "The software vault only supports Ed25519".to_string(),
+            ));
+        }
+        
+        let full_key_id = self.full_key_id(key_id);
+        let mut vault = lock_vault(vault)?;
+        let public_key = vault
+            .generate_signing_key(&full_key_id)
+            .map_err(|e| EnclaveError::KeyGenerationFailed(e.to_string()))?;
+        let (_, created_at) = vault
+            .entry_info(&full_key_id)
+            .ok_or_else(|| EnclaveError::KeyNotFound(full_key_id.clone()))?;
+        
+        Ok(EnclaveKey {
+            key_id: full_key_id,
+            public_key: public_key.as_bytes().to_vec(),
+            algorithm: EnclaveAlgorithm::Ed25519,
+            created_at,
+            biometric_protected: false,
+        })
+    }
+
+    fn sign_software(&self, vault: &SharedKeyVault, key_id: &str, data: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+        let full_key_id = self.full_key_id(key_id);
+        let keypair = lock_vault(vault)?
+            .signing_key(&full_key_id)
+            .ok_or(EnclaveError::KeyNotFound(full_key_id))?;
+        Ok(signatures::sign(keypair.private_key(), data).to_bytes().to_vec())
+    }
+
+    fn get_public_key_software(&self, vault: &SharedKeyVault, key_id: &str) -> Result<Vec<u8>, EnclaveError> {
+        let full_key_id = self.full_key_id(key_id);
+        let keypair = lock_vault(vault)?
+            .signing_key(&full_key_id)
+            .ok_or(EnclaveError::KeyNotFound(full_key_id))?;
+        Ok(keypair.public_key().as_bytes().to_vec())
+    }
+
+    fn delete_key_software(&self, vault: &SharedKeyVault, key_id: &str) -> Result<(), EnclaveError> {
+        let full_key_id = self.full_key_id(key_id);
+        let mut vault = lock_vault(vault)?;
+        if vault.entry_info(&full_key_id).map(|(kind, _)| kind) != Some(VaultEntryKind::SigningKey) {
+            return Err(EnclaveError::KeyNotFound(full_key_id));
+        }
+        vault
+            .remove(&full_key_id)
+            .map_err(|e| EnclaveError::PlatformError(e.to_string()))
+    }
+
+    fn attest_device_software(&self, vault: &SharedKeyVault, challenge: &[u8]) -> Result<DeviceAttestation, EnclaveError> {
+        let attestation_key_id = self.full_key_id("attestation");
+        let mut vault = lock_vault(vault)?;
+        if !vault.contains(&attestation_key_id) {
+            vault
+                .generate_signing_key(&attestation_key_id)
+                .map_err(|e| EnclaveError::AttestationFailed(e.to_string()))?;
+        }
+        let keypair = vault
+            .signing_key(&attestation_key_id)
+            .ok_or(EnclaveError::KeyNotFound(attestation_key_id))?;
+        
+        let platform_data = format!("dchat software vault; tpm_bound={}", vault.is_tpm_bound()).into_bytes();
+        let signature = signatures::sign(
+            keypair.private_key(),
+            &attestation_message(challenge, &platform_data),
+        );
+        
+        Ok(DeviceAttestation {
+            certificate_chain: vec![keypair.public_key().as_bytes().to_vec()],
+            signature: signature.to_bytes().to_vec(),
+            challenge: challenge.to_vec(),
+            platform_data,
+        })
+    }
+
     // iOS Secure Enclave implementations
     #[cfg(target_os = "ios")]
     async fn is_available_ios(&self) -> Result<bool, EnclaveError> {
@@ -399,6 +555,12 @@ impl SecureEnclave {
     }
 }
 
+fn lock_vault(vault: &SharedKeyVault) -> Result<std::sync::MutexGuard<'_, crate::vault::KeyVault>, EnclaveError> {
+    vault
+        .lock()
+        .map_err(|_| EnclaveError::PlatformError("Key vault lock poisoned".to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -416,4 +578,32 @@ mod tests {
         // Availability depends on platform
         let _ = enclave.is_available().await;
     }
+
+    #[tokio::test]
+    async fn test_software_enclave_signs_and_attests() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = crate::vault::KeyVault::create(dir.path().join("enclave.vault"), "pw", None)
+            .unwrap()
+            .into_shared();
+        let enclave = SecureEnclave::with_software_vault(EnclaveConfig::default(), vault);
+        assert!(enclave.is_available().await.unwrap());
+
+        let key = enclave.generate_key("device").await.unwrap();
+        assert_eq!(enclave.get_public_key("device").await.unwrap(), key.public_key);
+
+        let signature = enclave.sign("device", b"payload").await.unwrap();
+        assert_ne!(signature, vec![0u8; 64]);
+        let public_key = CryptoPublicKey::from_bytes(key.public_key.as_slice().try_into().unwrap());
+        let signature = Signature::from_bytes(signature.as_slice().try_into().unwrap());
+        assert!(signatures::verify(&public_key, b"payload", &signature).is_ok());
+
+        let attestation = enclave.attest_device(b"nonce-123").await.unwrap();
+        assert!(attestation.verify().is_ok());
+        let mut forged = attestation.clone();
+        forged.challenge = b"nonce-124".to_vec();
+        assert!(forged.verify().is_err());
+
+        enclave.delete_key("device").await.unwrap();
+        assert!(matches!(enclave.sign("device", b"x").await, Err(EnclaveError::KeyNotFound(_))));
+    }
 }
@@ -6,6 +6,7 @@ use dchat_crypto::keys::KeyPair;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
+use uuid::Builder;
 
 /// Represents a user's identity in the dchat system
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +23,15 @@ pub struct Identity {
     pub metadata: HashMap<String, String>,
 }
 
+/// User ID hashed from a key, so keys restored from a recovery phrase get
+/// their old ID back
+pub(crate) fn derived_user_id(context: &str, keypair: &KeyPair) -> UserId {
+    let digest = blake3::derive_key(context, keypair.public_key().as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    UserId(Builder::from_random_bytes(bytes).into_uuid())
+}
+
 impl Identity {
     /// Create a new identity from a keypair
     pub fn new(username: String, keypair: &KeyPair) -> Self {
@@ -41,6 +51,22 @@ impl Identity {
         }
     }
     
+    /// Identity for a key derived from a recovery phrase
+    ///
+    /// The user ID is a hash of the public key and every timestamp is the
+    /// Unix epoch, so restoring the same phrase rebuilds the same identity.
+    pub fn from_derived_key(username: String, keypair: &KeyPair) -> Self {
+        Self {
+            user_id: derived_user_id("dchat user id v1", keypair),
+            reputation: ReputationScore {
+                last_updated: DateTime::UNIX_EPOCH,
+                ..ReputationScore::default()
+            },
+            created_at: DateTime::UNIX_EPOCH,
+            ..Self::new(username, keypair)
+        }
+    }
+    
     /// Update display name
     pub fn set_display_name(&mut self, display_name: String) {
         self.display_name = Some(display_name);
@@ -217,6 +243,19 @@ mod tests {
         assert_eq!(identity.badges.len(), 0);
     }
     
+    #[test]
+    fn test_restored_identity_is_identical() {
+        let phrase = crate::mnemonic::MnemonicSeed::generate(12).unwrap().phrase();
+        let restore = || {
+            let keys = crate::mnemonic::MnemonicSeed::from_phrase(&phrase).unwrap().derive_keys("").unwrap();
+            Identity::from_derived_key("user".to_string(), &keys.main_identity)
+        };
+        
+        let (first, second) = (restore(), restore());
+        assert_eq!(serde_json::to_string(&first).unwrap(), serde_json::to_string(&second).unwrap());
+        assert_ne!(first.user_id, Identity::from_derived_key("user".to_string(), &KeyPair::generate()).user_id);
+    }
+    
     #[test]
     fn test_identity_manager() {
         let mut manager = IdentityManager::new();
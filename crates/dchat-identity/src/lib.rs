@@ -2,35 +2,51 @@
 //! 
 //! This crate provides identity management including:
 //! - Identity registration and verification
-//! - Multi-device synchronization
+//! - Multi-device synchronization with CRDT-merged state
+//! - Device pairing with PAKE-protected codes and device certificates
 //! - Hierarchical key derivation
+//! - BIP-39 mnemonic backup and restore
 //! - Burner identities
+//! - Software key vault and PKCS#11 keys for servers without secure hardware
 //! - Guardian-based account recovery
 //! - User profiles and status
 
 pub mod identity;
 pub mod device;
 pub mod derivation;
+pub mod mnemonic;
 pub mod sync;
+pub mod crdt;
+pub mod pairing;
 pub mod guardian;
 pub mod guardian_recovery; // Phase 2: Guardian-based account recovery
 pub mod verification;
 pub mod burner;
 pub mod biometric; // Phase 7 Sprint 6: Keyless UX - Biometric authentication
 pub mod enclave; // Phase 7 Sprint 6: Keyless UX - Secure enclave integration
+pub mod vault; // Software keystore backing enclave/biometric on Linux
+pub mod pkcs11; // HSM-held validator keys
 pub mod mpc; // Phase 7 Sprint 6: Keyless UX - MPC threshold signing
 pub mod profile; // User profiles, status, and privacy settings
 pub mod storage; // Profile database storage
 
 pub use identity::{Identity, IdentityManager};
 pub use device::{Device, DeviceManager};
-pub use derivation::{KeyPath, IdentityDerivation};
+pub use derivation::{KeyPath, IdentityDerivation, DerivedKeys};
+pub use mnemonic::MnemonicSeed;
+pub use crdt::{ReplicatedState, StateDelta};
+pub use pairing::{
+    CertificateRegistry, DeviceCertificate, DeviceRevocation, DeviceSyncChannel, PairedDevice,
+    PairingCode, PairingHost, PairingJoiner, PairingMessage, SyncKeyRotation,
+};
 pub use guardian::{Guardian, GuardianManager, RecoveryRequest};
 pub use guardian_recovery::{GuardianRecoveryManager, GuardianId, RecoveryStatus};
 pub use verification::{VerifiedBadge, VerificationProof};
 pub use burner::BurnerIdentity;
 pub use biometric::{BiometricAuthenticator, BiometricConfig, BiometricType, BiometricAuthResult};
 pub use enclave::{SecureEnclave, EnclaveConfig};
+pub use vault::{KeyVault, SharedKeyVault, TpmSealer};
+pub use pkcs11::{Pkcs11Key, Pkcs11Uri};
 pub use mpc::{MpcSigner, MpcConfig, ThresholdSignature};
 pub use profile::{
     UserProfile, ProfilePicture, UserStatus, StatusType, OnlineStatus,
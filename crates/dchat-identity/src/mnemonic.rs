@@ -0,0 +1,132 @@
+//! BIP-39 mnemonic seeds for human-backupable identities
+//!
+//! A mnemonic phrase is stretched into a 64-byte BIP-39 seed, which becomes
+//! the SLIP-0010 master key that every identity, device and burner key is
+//! derived from. Restoring the phrase restores all of them.
+
+use crate::derivation::{DerivedKeys, IdentityDerivation};
+use bip39::Mnemonic;
+use dchat_core::error::{Error, Result};
+use dchat_crypto::keys::ExtendedPrivateKey;
+use zeroize::Zeroize;
+
+/// Word counts allowed by BIP-39
+pub const VALID_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// A BIP-39 mnemonic phrase
+pub struct MnemonicSeed {
+    mnemonic: Mnemonic,
+}
+
+impl MnemonicSeed {
+    /// Generate a new random mnemonic with the given number of words
+    pub fn generate(word_count: usize) -> Result<Self> {
+        if !VALID_WORD_COUNTS.contains(&word_count) {
+            return Err(Error::identity(format!(
+                "Mnemonic must have 12, 15, 18, 21 or 24 words, not {}",
+                word_count
+            )));
+        }
+        let mnemonic = Mnemonic::generate(word_count)
+            .map_err(|e| Error::identity(format!("Mnemonic generation failed: {}", e)))?;
+        Ok(Self { mnemonic })
+    }
+
+    /// Parse and checksum-verify an English mnemonic phrase
+    pub fn from_phrase(phrase: &str) -> Result<Self> {
+        let mnemonic = Mnemonic::parse(phrase)
+            .map_err(|e| Error::identity(format!("Invalid mnemonic: {}", e)))?;
+        Ok(Self { mnemonic })
+    }
+
+    /// The space-separated phrase to show the user
+    pub fn phrase(&self) -> String {
+        self.mnemonic.to_string()
+    }
+
+    /// Number of words in the phrase
+    pub fn word_count(&self) -> usize {
+        self.mnemonic.word_count()
+    }
+
+    /// The 64-byte BIP-39 seed, with an optional passphrase ("" for none)
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        self.mnemonic.to_seed(passphrase)
+    }
+
+    /// SLIP-0010 master key for this mnemonic
+    pub fn master_key(&self, passphrase: &str) -> Result<ExtendedPrivateKey> {
+        let mut seed = self.to_seed(passphrase);
+        let master = IdentityDerivation::master_from_seed(&seed);
+        seed.zeroize();
+        master
+    }
+
+    /// Derive the identity, first device and first burner keys
+    pub fn derive_keys(&self, passphrase: &str) -> Result<DerivedKeys> {
+        let mut seed = self.to_seed(passphrase);
+        let keys = IdentityDerivation::derive_all_keys_from_seed(&seed);
+        seed.zeroize();
+        keys
+    }
+}
+
+impl std::fmt::Debug for MnemonicSeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MnemonicSeed")
+            .field("words", &self.word_count())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABANDON_ABOUT: &str = "abandon abandon abandon abandon abandon abandon \
+        abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_bip39_reference_seed() {
+        // Trezor BIP-39 reference vector (passphrase "TREZOR")
+        let mnemonic = MnemonicSeed::from_phrase(ABANDON_ABOUT).unwrap();
+        assert_eq!(
+            hex::encode(mnemonic.to_seed("TREZOR")),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f\
+             09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn test_generate_and_restore() {
+        let mnemonic = MnemonicSeed::generate(24).unwrap();
+        assert_eq!(mnemonic.word_count(), 24);
+
+        let restored = MnemonicSeed::from_phrase(&mnemonic.phrase()).unwrap();
+        let original_keys = mnemonic.derive_keys("").unwrap();
+        let restored_keys = restored.derive_keys("").unwrap();
+
+        assert_eq!(
+            original_keys.main_identity.public_key(),
+            restored_keys.main_identity.public_key()
+        );
+        assert_eq!(original_keys.device_0.public_key(), restored_keys.device_0.public_key());
+        assert_eq!(original_keys.burner_0.public_key(), restored_keys.burner_0.public_key());
+
+        // A different passphrase yields an unrelated wallet
+        let other = restored.derive_keys("hunter2").unwrap();
+        assert_ne!(
+            original_keys.main_identity.public_key(),
+            other.main_identity.public_key()
+        );
+    }
+
+    #[test]
+    fn test_invalid_mnemonics_rejected() {
+        assert!(MnemonicSeed::generate(13).is_err());
+        // Valid words, bad checksum
+        let bad_checksum = ABANDON_ABOUT.replace("about", "abandon");
+        assert!(MnemonicSeed::from_phrase(&bad_checksum).is_err());
+        assert!(MnemonicSeed::from_phrase("not a real mnemonic").is_err());
+    }
+}
@@ -0,0 +1,1086 @@
+//! Device pairing: bringing a new device into an existing identity
+//!
+//! 1. The primary device opens a [`PairingHost`] and shows its
+//!    [`PairingCode`], either as a short `1234-5678` code or a QR payload.
+//! 2. The new device builds a [`PairingJoiner`] from the code and the two
+//!    run CPace over the network by exchanging [`PairingMessage`]s. The code
+//!    never crosses the wire, and a wrong guess burns one of a handful of
+//!    attempts.
+//! 3. After key confirmation the primary derives the device key with
+//!    [`IdentityDerivation::derive_device_key`], signs a
+//!    [`DeviceCertificate`] for it and sends both, sealed under the PAKE key.
+//! 4. Contacts and settings follow as encrypted [`SyncMessage`]s on a
+//!    [`DeviceSyncChannel`].
+//!
+//! Revoking a device produces a signed [`DeviceRevocation`] that is gossiped
+//! to peers; a [`CertificateRegistry`] refuses certificates it has seen
+//! revoked. The primary then moves the remaining devices to a new sync key
+//! with a [`SyncKeyRotation`], so the revoked device cannot read later
+//! sync messages.
+
+use crate::derivation::IdentityDerivation;
+use crate::device::{Device, DeviceId, DeviceType};
+use crate::sync::{SyncMessage, SyncMessageType};
+use chrono::{DateTime, Duration, Utc};
+use dchat_core::error::{Error, Result};
+use dchat_core::types::{PublicKey, Signature, UserId};
+use dchat_crypto::kdf::Hkdf;
+use dchat_crypto::keys::{KeyPair, PrivateKey, PublicKey as CryptoPublicKey};
+use dchat_crypto::pake::{CpaceExchange, PakeRole, PakeSessionKey};
+use dchat_crypto::signatures::{self, Signature as CryptoSignature};
+use dchat_crypto::{decrypt_with_key, encrypt_with_key};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+use zeroize::Zeroize;
+
+/// Digits in a pairing code
+pub const PAIRING_CODE_DIGITS: usize = 8;
+
+/// How long a pairing code stays valid
+pub const PAIRING_CODE_TTL_SECONDS: i64 = 300;
+
+/// Wrong-code attempts allowed before a pairing code is burned
+pub const MAX_PAIRING_ATTEMPTS: u32 = 3;
+
+/// Scheme prefix of QR pairing payloads
+const QR_PREFIX: &str = "dchat-pair:1:";
+
+/// Domain separation for signed device statements
+const CERTIFICATE_DOMAIN: &[u8] = b"dchat device certificate v1";
+const REVOCATION_DOMAIN: &[u8] = b"dchat device revocation v1";
+
+/// Code shown by the primary device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairingCode {
+    /// Identity being joined
+    pub user_id: UserId,
+
+    /// Network address of the primary device
+    pub host_peer: String,
+
+    /// Decimal pairing code
+    pub code: String,
+}
+
+impl PairingCode {
+    fn generate(user_id: UserId, host_peer: String) -> Self {
+        let seed = dchat_crypto::generate_seed();
+        let mut value = [0u8; 8];
+        value.copy_from_slice(&seed[..8]);
+        let modulus = 10u64.pow(PAIRING_CODE_DIGITS as u32);
+        let code = format!(
+            "{:0width$}",
+            u64::from_le_bytes(value) % modulus,
+            width = PAIRING_CODE_DIGITS
+        );
+
+        Self { user_id, host_peer, code }
+    }
+
+    /// Code typed in by the user for `user_id`, without the host's address
+    pub fn typed(user_id: UserId, code: &str) -> Result<Self> {
+        Ok(Self {
+            user_id,
+            host_peer: String::new(),
+            code: normalize_code(code)?,
+        })
+    }
+
+    /// Code formatted for reading aloud, e.g. `1234-5678`
+    pub fn display_code(&self) -> String {
+        let (head, tail) = self.code.split_at(PAIRING_CODE_DIGITS / 2);
+        format!("{}-{}", head, tail)
+    }
+
+    /// Payload to render as a QR code
+    pub fn to_qr_payload(&self) -> String {
+        format!("{}{}:{}:{}", QR_PREFIX, self.user_id.0, self.code, self.host_peer)
+    }
+
+    /// Parse a scanned QR payload
+    pub fn from_qr_payload(payload: &str) -> Result<Self> {
+        let rest = payload
+            .strip_prefix(QR_PREFIX)
+            .ok_or_else(|| Error::identity("Not a dchat pairing code"))?;
+
+        let mut parts = rest.splitn(3, ':');
+        let (user_id, code, host_peer) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(user_id), Some(code), Some(host_peer)) if !host_peer.is_empty() => {
+                (user_id, code, host_peer)
+            }
+            _ => return Err(Error::identity("Malformed pairing payload")),
+        };
+
+        let user_id = Uuid::parse_str(user_id)
+            .map_err(|e| Error::identity(format!("Invalid user ID in pairing payload: {}", e)))?;
+
+        Ok(Self {
+            user_id: UserId(user_id),
+            host_peer: host_peer.to_string(),
+            code: normalize_code(code)?,
+        })
+    }
+}
+
+/// Strip separators from a typed code and check its shape
+fn normalize_code(input: &str) -> Result<String> {
+    let code: String = input.chars().filter(|c| !matches!(c, '-' | ' ')).collect();
+    if code.len() != PAIRING_CODE_DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::identity(format!(
+            "Pairing code must be {} digits",
+            PAIRING_CODE_DIGITS
+        )));
+    }
+    Ok(code)
+}
+
+/// Messages exchanged while pairing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PairingMessage {
+    /// New device asks to join
+    Request {
+        device_name: String,
+        device_type: DeviceType,
+    },
+    /// Primary opens a PAKE session
+    Challenge {
+        session_id: String,
+        share: [u8; 32],
+    },
+    /// New device answers and proves it knows the code
+    Response {
+        session_id: String,
+        share: [u8; 32],
+        confirmation: [u8; 32],
+    },
+    /// Primary proves it knows the code and hands over the sealed grant
+    Grant {
+        session_id: String,
+        confirmation: [u8; 32],
+        sealed_grant: Vec<u8>,
+    },
+    /// Pairing refused
+    Rejected { reason: String },
+    /// Primary moves the remaining devices to a new sync key
+    RotateSyncKey { rotation: SyncKeyRotation },
+}
+
+impl PairingMessage {
+    /// Encode for the wire
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        bincode::serialize(self)
+            .map_err(|e| Error::identity(format!("Failed to encode pairing message: {}", e)))
+    }
+
+    /// Decode from the wire
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        bincode::deserialize(bytes)
+            .map_err(|e| Error::identity(format!("Malformed pairing message: {}", e)))
+    }
+}
+
+/// Statement, signed by the identity key, that a device key belongs to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceCertificate {
+    pub user_id: UserId,
+    pub identity_key: PublicKey,
+    pub device_id: DeviceId,
+    pub device_name: String,
+    pub device_type: DeviceType,
+    pub device_index: u32,
+    pub device_key: PublicKey,
+    pub issued_at: DateTime<Utc>,
+    pub signature: Signature,
+}
+
+impl DeviceCertificate {
+    /// Issue a certificate for `device_key`
+    #[allow(clippy::too_many_arguments)]
+    pub fn issue(
+        user_id: UserId,
+        identity: &KeyPair,
+        device_id: DeviceId,
+        device_name: String,
+        device_type: DeviceType,
+        device_index: u32,
+        device_key: &CryptoPublicKey,
+    ) -> Result<Self> {
+        let mut certificate = Self {
+            user_id,
+            identity_key: identity.public_key().to_core_public_key(),
+            device_id,
+            device_name,
+            device_type,
+            device_index,
+            device_key: device_key.to_core_public_key(),
+            issued_at: Utc::now(),
+            signature: Signature::new(Vec::new()),
+        };
+        let signature = signatures::sign(identity.private_key(), &certificate.signing_bytes()?);
+        certificate.signature = signature.to_core_signature();
+        Ok(certificate)
+    }
+
+    /// Check the identity key's signature
+    pub fn verify(&self) -> Result<()> {
+        verify_statement(&self.identity_key, &self.signing_bytes()?, &self.signature)
+    }
+
+    /// Device record for a [`crate::DeviceManager`]; certified devices are trusted
+    pub fn to_device(&self) -> Device {
+        Device {
+            device_id: self.device_id.clone(),
+            device_name: self.device_name.clone(),
+            device_type: self.device_type.clone(),
+            public_key: self.device_key.clone(),
+            added_at: self.issued_at,
+            last_seen: self.issued_at,
+            trusted: true,
+        }
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>> {
+        statement_bytes(
+            CERTIFICATE_DOMAIN,
+            &(
+                &self.user_id,
+                &self.identity_key,
+                &self.device_id,
+                &self.device_name,
+                &self.device_type,
+                self.device_index,
+                &self.device_key,
+                self.issued_at,
+            ),
+        )
+    }
+}
+
+/// Statement, signed by the identity key, that a device is no longer trusted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceRevocation {
+    pub user_id: UserId,
+    pub identity_key: PublicKey,
+    pub device_id: DeviceId,
+    pub device_key: PublicKey,
+    pub revoked_at: DateTime<Utc>,
+    pub signature: Signature,
+}
+
+impl DeviceRevocation {
+    /// Revoke the device named by `certificate`
+    pub fn issue(certificate: &DeviceCertificate, identity: &KeyPair) -> Result<Self> {
+        let identity_key = identity.public_key().to_core_public_key();
+        if identity_key != certificate.identity_key {
+            return Err(Error::identity("Certificate was issued by a different identity"));
+        }
+
+        let mut revocation = Self {
+            user_id: certificate.user_id.clone(),
+            identity_key,
+            device_id: certificate.device_id.clone(),
+            device_key: certificate.device_key.clone(),
+            revoked_at: Utc::now(),
+            signature: Signature::new(Vec::new()),
+        };
+        let signature = signatures::sign(identity.private_key(), &revocation.signing_bytes()?);
+        revocation.signature = signature.to_core_signature();
+        Ok(revocation)
+    }
+
+    /// Check the identity key's signature
+    pub fn verify(&self) -> Result<()> {
+        verify_statement(&self.identity_key, &self.signing_bytes()?, &self.signature)
+    }
+
+    /// Encode for gossip
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        bincode::serialize(self)
+            .map_err(|e| Error::identity(format!("Failed to encode revocation: {}", e)))
+    }
+
+    /// Decode from gossip
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        bincode::deserialize(bytes)
+            .map_err(|e| Error::identity(format!("Malformed revocation: {}", e)))
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>> {
+        statement_bytes(
+            REVOCATION_DOMAIN,
+            &(
+                &self.user_id,
+                &self.identity_key,
+                &self.device_id,
+                &self.device_key,
+                self.revoked_at,
+            ),
+        )
+    }
+}
+
+fn statement_bytes<T: Serialize>(domain: &[u8], fields: &T) -> Result<Vec<u8>> {
+    let mut bytes = domain.to_vec();
+    bincode::serialize_into(&mut bytes, fields)
+        .map_err(|e| Error::identity(format!("Failed to encode device statement: {}", e)))?;
+    Ok(bytes)
+}
+
+fn verify_statement(key: &PublicKey, message: &[u8], signature: &Signature) -> Result<()> {
+    let key = CryptoPublicKey::try_from(key)?;
+    let signature = CryptoSignature::try_from(signature)?;
+    signatures::verify(&key, message, &signature)
+}
+
+/// Peer-side view of device certificates and their revocations
+#[derive(Debug, Default)]
+pub struct CertificateRegistry {
+    /// Revocations keyed by (issuing identity key, revoked device key)
+    revoked: HashMap<(Vec<u8>, Vec<u8>), DeviceRevocation>,
+}
+
+impl CertificateRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a revocation received from the network
+    ///
+    /// Returns `true` if it was new, i.e. worth gossiping onwards.
+    pub fn apply_revocation(&mut self, revocation: DeviceRevocation) -> Result<bool> {
+        revocation.verify()?;
+        let key = revocation_key(&revocation.identity_key, &revocation.device_key);
+        if self.revoked.contains_key(&key) {
+            return Ok(false);
+        }
+        self.revoked.insert(key, revocation);
+        Ok(true)
+    }
+
+    /// Accept a certificate only if `identity_key` signed it and has not revoked it
+    pub fn verify_certificate(
+        &self,
+        certificate: &DeviceCertificate,
+        identity_key: &PublicKey,
+    ) -> Result<()> {
+        if &certificate.identity_key != identity_key {
+            return Err(Error::identity("Device certificate belongs to a different identity"));
+        }
+        certificate.verify()?;
+        if self.is_revoked(certificate) {
+            return Err(Error::identity(format!(
+                "Device {} has been revoked",
+                certificate.device_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the certificate's issuer has revoked it
+    pub fn is_revoked(&self, certificate: &DeviceCertificate) -> bool {
+        self.revoked
+            .contains_key(&revocation_key(&certificate.identity_key, &certificate.device_key))
+    }
+
+    pub fn revoked_count(&self) -> usize {
+        self.revoked.len()
+    }
+
+    /// Every revocation seen, e.g. to gossip again to peers that joined later
+    pub fn revocations(&self) -> impl Iterator<Item = &DeviceRevocation> {
+        self.revoked.values()
+    }
+}
+
+/// Revocations only count against the identity that signed them, so one
+/// identity cannot shadow another's revocation of the same device key
+fn revocation_key(identity_key: &PublicKey, device_key: &PublicKey) -> (Vec<u8>, Vec<u8>) {
+    (identity_key.as_bytes().to_vec(), device_key.as_bytes().to_vec())
+}
+
+/// Encrypted sync channel shared by all devices of one identity
+///
+/// The key is derived from the identity master key per epoch; revoking a
+/// device moves the others to the next epoch.
+pub struct DeviceSyncChannel {
+    user_id: UserId,
+    device_id: DeviceId,
+    epoch: u32,
+    key: PrivateKey,
+}
+
+impl DeviceSyncChannel {
+    /// Channel keyed from the identity master key
+    pub fn from_master_key(user_id: UserId, device_id: DeviceId, master_key: &PrivateKey) -> Result<Self> {
+        Self::for_epoch(user_id, device_id, master_key, 0)
+    }
+
+    /// Channel keyed from the identity master key at a given sync key epoch
+    pub fn for_epoch(user_id: UserId, device_id: DeviceId, master_key: &PrivateKey, epoch: u32) -> Result<Self> {
+        let key = sync_key(master_key, epoch)?;
+        Ok(Self { user_id, device_id, epoch, key })
+    }
+
+    /// Encrypt `payload` into a sync message from this device
+    pub fn seal<T: Serialize>(&self, message_type: SyncMessageType, payload: &T) -> Result<SyncMessage> {
+        let mut plaintext = bincode::serialize(&(&self.user_id, &message_type, payload))
+            .map_err(|e| Error::identity(format!("Failed to encode sync payload: {}", e)))?;
+        let encrypted_payload = encrypt_with_key(self.key.as_bytes(), &plaintext);
+        plaintext.zeroize();
+
+        Ok(SyncMessage {
+            sync_id: Uuid::new_v4().to_string(),
+            user_id: self.user_id.clone(),
+            device_id: self.device_id.clone(),
+            message_type,
+            timestamp: Utc::now(),
+            encrypted_payload: encrypted_payload?,
+        })
+    }
+
+    /// Decrypt a sync message from another device of this identity
+    pub fn open<T: DeserializeOwned>(&self, message: &SyncMessage) -> Result<T> {
+        let mut plaintext = decrypt_with_key(self.key.as_bytes(), &message.encrypted_payload)?;
+        let decoded: std::result::Result<(UserId, SyncMessageType, T), _> = bincode::deserialize(&plaintext);
+        plaintext.zeroize();
+
+        let (user_id, message_type, payload) =
+            decoded.map_err(|e| Error::identity(format!("Malformed sync payload: {}", e)))?;
+        if user_id != message.user_id || message_type != message.message_type {
+            return Err(Error::identity("Sync message header does not match its payload"));
+        }
+        Ok(payload)
+    }
+
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    /// Sync key epoch this channel is keyed for
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+}
+
+fn sync_key(master_key: &PrivateKey, epoch: u32) -> Result<PrivateKey> {
+    Hkdf::derive_purpose_key(master_key, "device-sync", epoch)
+}
+
+/// Key a device's copy of a rotated sync key is sealed under
+///
+/// Both the primary, which derives every device key from the master key,
+/// and the device itself can compute it; a revoked device is simply left
+/// out of the rotation.
+fn rotation_key(device_key: &PrivateKey, epoch: u32) -> Result<PrivateKey> {
+    Hkdf::derive_purpose_key(device_key, "device-sync-rotation", epoch)
+}
+
+/// New sync key, sealed to each device that is still trusted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncKeyRotation {
+    pub user_id: UserId,
+    pub epoch: u32,
+    /// (device ID, sync key sealed to that device)
+    pub sealed_keys: Vec<(DeviceId, Vec<u8>)>,
+}
+
+impl SyncKeyRotation {
+    /// Seal the sync key for `epoch` to each of `devices`
+    ///
+    /// `master_key` is the key the devices' certificates were derived from.
+    pub fn issue(
+        user_id: UserId,
+        master_key: &PrivateKey,
+        epoch: u32,
+        devices: &[DeviceCertificate],
+    ) -> Result<Self> {
+        let sync_key = sync_key(master_key, epoch)?;
+        let sealed_keys = devices
+            .iter()
+            .map(|certificate| {
+                let device_key = IdentityDerivation::derive_device_key(master_key, certificate.device_index)?;
+                let wrap = rotation_key(device_key.private_key(), epoch)?;
+                Ok((certificate.device_id.clone(), encrypt_with_key(wrap.as_bytes(), sync_key.as_bytes())?))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { user_id, epoch, sealed_keys })
+    }
+}
+
+/// Secrets handed to a new device, sealed under the PAKE session key
+#[derive(Serialize, Deserialize)]
+struct DeviceGrant {
+    device_key: [u8; 32],
+    sync_epoch: u32,
+    sync_key: [u8; 32],
+    certificate: DeviceCertificate,
+}
+
+impl Drop for DeviceGrant {
+    fn drop(&mut self) {
+        self.device_key.zeroize();
+        self.sync_key.zeroize();
+    }
+}
+
+struct HostSession {
+    device_name: String,
+    device_type: DeviceType,
+    exchange: CpaceExchange,
+}
+
+/// Primary-device side of a pairing
+pub struct PairingHost {
+    user_id: UserId,
+    master_key: PrivateKey,
+    identity: KeyPair,
+    device_index: u32,
+    sync_epoch: u32,
+    code: PairingCode,
+    expires_at: DateTime<Utc>,
+    sessions: HashMap<String, HostSession>,
+    failed_attempts: u32,
+    completed: bool,
+}
+
+impl PairingHost {
+    /// Open a pairing that will provision device `device_index`
+    pub fn new(
+        user_id: UserId,
+        master_key: PrivateKey,
+        device_index: u32,
+        host_peer: impl Into<String>,
+    ) -> Result<Self> {
+        let identity = IdentityDerivation::derive_all_keys(&master_key)?.main_identity;
+        let code = PairingCode::generate(user_id.clone(), host_peer.into());
+
+        Ok(Self {
+            user_id,
+            master_key,
+            identity,
+            device_index,
+            sync_epoch: 0,
+            code,
+            expires_at: Utc::now() + Duration::seconds(PAIRING_CODE_TTL_SECONDS),
+            sessions: HashMap::new(),
+            failed_attempts: 0,
+            completed: false,
+        })
+    }
+
+    /// Hand the new device the sync key of `epoch`, the one the identity's
+    /// devices currently use
+    pub fn with_sync_epoch(mut self, epoch: u32) -> Self {
+        self.sync_epoch = epoch;
+        self
+    }
+
+    /// Code to show the user
+    pub fn code(&self) -> &PairingCode {
+        &self.code
+    }
+
+    /// Whether the code can still be used
+    pub fn is_open(&self) -> bool {
+        !self.completed && self.failed_attempts < MAX_PAIRING_ATTEMPTS && Utc::now() < self.expires_at
+    }
+
+    /// Answer a new device's request with a PAKE challenge
+    pub fn handle_request(&mut self, device_name: String, device_type: DeviceType) -> Result<PairingMessage> {
+        self.ensure_open()?;
+
+        let session_id = Uuid::new_v4().to_string();
+        let exchange = CpaceExchange::new(
+            PakeRole::Initiator,
+            self.code.code.as_bytes(),
+            session_id.as_bytes(),
+        );
+        let share = exchange.share();
+        self.sessions.insert(
+            session_id.clone(),
+            HostSession { device_name, device_type, exchange },
+        );
+
+        Ok(PairingMessage::Challenge { session_id, share })
+    }
+
+    /// Verify the new device's proof of the code and provision it
+    ///
+    /// Returns the grant to send back and the certificate to record locally.
+    pub fn handle_response(
+        &mut self,
+        session_id: &str,
+        share: &[u8; 32],
+        confirmation: &[u8; 32],
+    ) -> Result<(PairingMessage, DeviceCertificate)> {
+        self.ensure_open()?;
+        let session = self
+            .sessions
+            .remove(session_id)
+            .ok_or_else(|| Error::identity("Unknown pairing session"))?;
+
+        let key = match session.exchange.finish(share) {
+            Ok(key) => key,
+            Err(e) => {
+                self.failed_attempts += 1;
+                return Err(e);
+            }
+        };
+        if key.verify_confirmation(confirmation).is_err() {
+            self.failed_attempts += 1;
+            return Err(Error::identity("Pairing code did not match"));
+        }
+
+        let device_keys = IdentityDerivation::derive_device_key(&self.master_key, self.device_index)?;
+        let certificate = DeviceCertificate::issue(
+            self.user_id.clone(),
+            &self.identity,
+            Uuid::new_v4().to_string(),
+            session.device_name,
+            session.device_type,
+            self.device_index,
+            device_keys.public_key(),
+        )?;
+        let sync_key = sync_key(&self.master_key, self.sync_epoch)?;
+
+        let grant = DeviceGrant {
+            device_key: *device_keys.private_key().as_bytes(),
+            sync_epoch: self.sync_epoch,
+            sync_key: *sync_key.as_bytes(),
+            certificate: certificate.clone(),
+        };
+        let sealed_grant = seal_grant(&key, &grant)?;
+
+        self.completed = true;
+        self.sessions.clear();
+
+        let message = PairingMessage::Grant {
+            session_id: session_id.to_string(),
+            confirmation: key.confirmation(),
+            sealed_grant,
+        };
+        Ok((message, certificate))
+    }
+
+    /// Sync channel for the primary device itself
+    pub fn sync_channel(&self, device_id: DeviceId) -> Result<DeviceSyncChannel> {
+        DeviceSyncChannel::for_epoch(self.user_id.clone(), device_id, &self.master_key, self.sync_epoch)
+    }
+
+    fn ensure_open(&self) -> Result<()> {
+        if self.completed {
+            Err(Error::identity("Pairing code has already been used"))
+        } else if self.failed_attempts >= MAX_PAIRING_ATTEMPTS {
+            Err(Error::identity("Too many failed pairing attempts"))
+        } else if Utc::now() >= self.expires_at {
+            Err(Error::identity("Pairing code has expired"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn seal_grant(key: &PakeSessionKey, grant: &DeviceGrant) -> Result<Vec<u8>> {
+    let mut plaintext = bincode::serialize(grant)
+        .map_err(|e| Error::identity(format!("Failed to encode device grant: {}", e)))?;
+    let sealed = key.seal(&plaintext);
+    plaintext.zeroize();
+    sealed
+}
+
+/// A device that has completed pairing
+pub struct PairedDevice {
+    /// Certificate proving this device belongs to the identity
+    pub certificate: DeviceCertificate,
+
+    /// The device's own key pair
+    pub keypair: KeyPair,
+
+    /// Channel for contacts and settings sync
+    pub sync: DeviceSyncChannel,
+}
+
+impl PairedDevice {
+    /// Rebuild a paired device from its key and a sync key sealed with
+    /// [`seal_sync_key`](Self::seal_sync_key), e.g. after a restart
+    pub fn restore(certificate: DeviceCertificate, keypair: KeyPair, sync_key: &SyncKeyRotation) -> Result<Self> {
+        certificate.verify()?;
+        if keypair.public_key().to_core_public_key() != certificate.device_key {
+            return Err(Error::identity("Device key does not match its certificate"));
+        }
+        if sync_key.user_id != certificate.user_id {
+            return Err(Error::identity("Sync key is for another identity"));
+        }
+        let key = open_sync_key(&certificate, &keypair, sync_key)?;
+        let sync = DeviceSyncChannel {
+            user_id: certificate.user_id.clone(),
+            device_id: certificate.device_id.clone(),
+            epoch: sync_key.epoch,
+            key,
+        };
+        Ok(Self { certificate, keypair, sync })
+    }
+
+    /// The current sync key sealed to this device alone, for storing
+    /// without keeping the raw key at rest
+    pub fn seal_sync_key(&self) -> Result<SyncKeyRotation> {
+        let wrap = rotation_key(self.keypair.private_key(), self.sync.epoch)?;
+        Ok(SyncKeyRotation {
+            user_id: self.certificate.user_id.clone(),
+            epoch: self.sync.epoch,
+            sealed_keys: vec![(
+                self.certificate.device_id.clone(),
+                encrypt_with_key(wrap.as_bytes(), self.sync.key.as_bytes())?,
+            )],
+        })
+    }
+
+    /// Switch the sync channel to a rotated key
+    ///
+    /// Fails if the rotation is stale or leaves this device out, i.e. the
+    /// device has been revoked.
+    pub fn apply_rotation(&mut self, rotation: &SyncKeyRotation) -> Result<()> {
+        if rotation.user_id != self.certificate.user_id {
+            return Err(Error::identity("Sync key rotation is for another identity"));
+        }
+        if rotation.epoch <= self.sync.epoch {
+            return Err(Error::identity("Sync key rotation is not newer than the current key"));
+        }
+        self.sync.key = open_sync_key(&self.certificate, &self.keypair, rotation)?;
+        self.sync.epoch = rotation.epoch;
+        Ok(())
+    }
+}
+
+/// Unseal this device's copy of the sync key in `rotation`
+fn open_sync_key(
+    certificate: &DeviceCertificate,
+    keypair: &KeyPair,
+    rotation: &SyncKeyRotation,
+) -> Result<PrivateKey> {
+    let (_, sealed) = rotation
+        .sealed_keys
+        .iter()
+        .find(|(device_id, _)| device_id == &certificate.device_id)
+        .ok_or_else(|| Error::identity("Device was left out of the sync key rotation"))?;
+
+    let wrap = rotation_key(keypair.private_key(), rotation.epoch)?;
+    let mut key = decrypt_with_key(wrap.as_bytes(), sealed)?;
+    let mut bytes = [0u8; 32];
+    if key.len() != bytes.len() {
+        key.zeroize();
+        return Err(Error::identity("Malformed rotated sync key"));
+    }
+    bytes.copy_from_slice(&key);
+    key.zeroize();
+
+    let key = PrivateKey::from_bytes(bytes);
+    bytes.zeroize();
+    Ok(key)
+}
+
+/// New-device side of a pairing
+pub struct PairingJoiner {
+    code: String,
+    device_name: String,
+    device_type: DeviceType,
+    session: Option<(String, PakeSessionKey)>,
+}
+
+impl PairingJoiner {
+    /// Start joining with a typed or scanned pairing code
+    pub fn new(code: &str, device_name: String, device_type: DeviceType) -> Result<Self> {
+        Ok(Self {
+            code: normalize_code(code)?,
+            device_name,
+            device_type,
+            session: None,
+        })
+    }
+
+    /// First message to send to the primary device
+    pub fn request(&self) -> PairingMessage {
+        PairingMessage::Request {
+            device_name: self.device_name.clone(),
+            device_type: self.device_type.clone(),
+        }
+    }
+
+    /// Answer the primary's challenge
+    pub fn handle_challenge(&mut self, session_id: String, share: &[u8; 32]) -> Result<PairingMessage> {
+        let exchange = CpaceExchange::new(PakeRole::Responder, self.code.as_bytes(), session_id.as_bytes());
+        let own_share = exchange.share();
+        let key = exchange.finish(share)?;
+        let confirmation = key.confirmation();
+        self.session = Some((session_id.clone(), key));
+
+        Ok(PairingMessage::Response {
+            session_id,
+            share: own_share,
+            confirmation,
+        })
+    }
+
+    /// Verify the primary and unpack the device key and certificate
+    pub fn handle_grant(
+        self,
+        session_id: &str,
+        confirmation: &[u8; 32],
+        sealed_grant: &[u8],
+    ) -> Result<PairedDevice> {
+        let (expected_session, key) = self
+            .session
+            .ok_or_else(|| Error::identity("Pairing grant received before challenge"))?;
+        if expected_session != session_id {
+            return Err(Error::identity("Pairing grant is for another session"));
+        }
+        key.verify_confirmation(confirmation)
+            .map_err(|_| Error::identity("Primary device did not prove the pairing code"))?;
+
+        let mut plaintext = key.open(sealed_grant)?;
+        let grant: std::result::Result<DeviceGrant, _> = bincode::deserialize(&plaintext);
+        plaintext.zeroize();
+        let grant = grant.map_err(|e| Error::identity(format!("Malformed device grant: {}", e)))?;
+
+        grant.certificate.verify()?;
+        let keypair = KeyPair::from_private_key(PrivateKey::from_bytes(grant.device_key));
+        if keypair.public_key().to_core_public_key() != grant.certificate.device_key {
+            return Err(Error::identity("Device key does not match its certificate"));
+        }
+
+        let sync = DeviceSyncChannel {
+            user_id: grant.certificate.user_id.clone(),
+            device_id: grant.certificate.device_id.clone(),
+            epoch: grant.sync_epoch,
+            key: PrivateKey::from_bytes(grant.sync_key),
+        };
+        Ok(PairedDevice {
+            certificate: grant.certificate.clone(),
+            keypair,
+            sync,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::DeviceManager;
+    use crate::sync::SyncManager;
+
+    fn host() -> PairingHost {
+        PairingHost::new(UserId::new(), PrivateKey::generate(), 1, "/ip4/10.0.0.1/tcp/7070").unwrap()
+    }
+
+    fn pair(host: &mut PairingHost, code: &str) -> Result<(PairedDevice, DeviceCertificate)> {
+        let mut joiner = PairingJoiner::new(code, "Phone".to_string(), DeviceType::Mobile)?;
+        let PairingMessage::Request { device_name, device_type } = joiner.request() else {
+            unreachable!()
+        };
+        let PairingMessage::Challenge { session_id, share } = host.handle_request(device_name, device_type)? else {
+            unreachable!()
+        };
+        let PairingMessage::Response { session_id, share, confirmation } =
+            joiner.handle_challenge(session_id, &share)?
+        else {
+            unreachable!()
+        };
+        let (grant, certificate) = host.handle_response(&session_id, &share, &confirmation)?;
+        let PairingMessage::Grant { session_id, confirmation, sealed_grant } =
+            PairingMessage::from_bytes(&grant.to_bytes()?)?
+        else {
+            unreachable!()
+        };
+        let paired = joiner.handle_grant(&session_id, &confirmation, &sealed_grant)?;
+        Ok((paired, certificate))
+    }
+
+    #[test]
+    fn test_pairing_code_formats() {
+        let host = host();
+        let code = host.code();
+        assert_eq!(code.code.len(), PAIRING_CODE_DIGITS);
+        assert_eq!(code.display_code().len(), PAIRING_CODE_DIGITS + 1);
+
+        let parsed = PairingCode::from_qr_payload(&code.to_qr_payload()).unwrap();
+        assert_eq!(&parsed, code);
+        assert!(PairingCode::from_qr_payload("https://example.com").is_err());
+        assert!(PairingJoiner::new("1234-567", "x".to_string(), DeviceType::Web).is_err());
+    }
+
+    #[test]
+    fn test_pairing_provisions_derived_device_key() {
+        let master_key = PrivateKey::generate();
+        let user_id = UserId::new();
+        let mut host = PairingHost::new(user_id.clone(), master_key.clone(), 3, "peer").unwrap();
+        let code = host.code().display_code();
+
+        let (paired, certificate) = pair(&mut host, &code).unwrap();
+        let expected = IdentityDerivation::derive_device_key(&master_key, 3).unwrap();
+        assert_eq!(paired.keypair.public_key(), expected.public_key());
+        assert_eq!(paired.certificate, certificate);
+        assert_eq!(certificate.user_id, user_id);
+        assert!(certificate.verify().is_ok());
+
+        let mut devices = DeviceManager::new();
+        devices.add_device(user_id.clone(), certificate.to_device()).unwrap();
+        assert!(devices.has_trusted_devices(&user_id));
+
+        // The code is single-use
+        assert!(!host.is_open());
+        assert!(host.handle_request("Tablet".to_string(), DeviceType::Mobile).is_err());
+    }
+
+    #[test]
+    fn test_wrong_code_is_rejected_and_burns_attempts() {
+        let mut host = host();
+        let correct = host.code().code.clone();
+        let wrong = if correct == "00000000" { "00000001" } else { "00000000" };
+
+        for _ in 0..MAX_PAIRING_ATTEMPTS {
+            assert!(pair(&mut host, wrong).is_err());
+        }
+        assert!(!host.is_open());
+        assert!(pair(&mut host, &correct).is_err());
+    }
+
+    #[test]
+    fn test_contacts_and_settings_sync_to_new_device() {
+        let mut host = host();
+        let code = host.code().code.clone();
+        let (paired, _) = pair(&mut host, &code).unwrap();
+
+        let primary = host.sync_channel("primary".to_string()).unwrap();
+        let contacts = vec![UserId::new(), UserId::new()];
+        let settings = crate::profile::PrivacySettings::default();
+
+        let mut queue = SyncManager::default();
+        queue.add_sync_message(primary.seal(SyncMessageType::ContactsUpdate, &contacts).unwrap()).unwrap();
+        queue.add_sync_message(primary.seal(SyncMessageType::SettingsUpdate, &settings).unwrap()).unwrap();
+
+        let pending = queue.get_pending_syncs(&paired.certificate.user_id);
+        assert_eq!(pending.len(), 2);
+        let synced_contacts: Vec<UserId> = paired.sync.open(pending[0]).unwrap();
+        assert_eq!(synced_contacts, contacts);
+        let synced_settings: crate::profile::PrivacySettings = paired.sync.open(pending[1]).unwrap();
+        assert_eq!(
+            serde_json::to_value(&synced_settings).unwrap(),
+            serde_json::to_value(&settings).unwrap()
+        );
+
+        // Retyping a message is caught
+        let mut retyped = pending[0].clone();
+        retyped.message_type = SyncMessageType::SettingsUpdate;
+        assert!(paired.sync.open::<Vec<UserId>>(&retyped).is_err());
+    }
+
+    #[test]
+    fn test_revocation_propagates_to_peers() {
+        let master_key = PrivateKey::generate();
+        let identity = IdentityDerivation::derive_all_keys(&master_key).unwrap().main_identity;
+        let mut host = PairingHost::new(UserId::new(), master_key, 1, "peer").unwrap();
+        let code = host.code().code.clone();
+        let (_, certificate) = pair(&mut host, &code).unwrap();
+        let identity_key = identity.public_key().to_core_public_key();
+
+        let mut peer = CertificateRegistry::new();
+        assert!(peer.verify_certificate(&certificate, &identity_key).is_ok());
+
+        let revocation = DeviceRevocation::issue(&certificate, &identity).unwrap();
+        let gossiped = DeviceRevocation::from_bytes(&revocation.to_bytes().unwrap()).unwrap();
+        assert!(peer.apply_revocation(gossiped.clone()).unwrap());
+        assert!(!peer.apply_revocation(gossiped).unwrap());
+        assert!(peer.verify_certificate(&certificate, &identity_key).is_err());
+
+        // Only the issuing identity can revoke
+        let mut forged = revocation.clone();
+        forged.device_key = PublicKey::new(vec![9; 32]);
+        assert!(CertificateRegistry::new().apply_revocation(forged).is_err());
+        assert!(DeviceRevocation::issue(&certificate, &KeyPair::generate()).is_err());
+    }
+
+    #[test]
+    fn test_revoked_device_is_left_out_of_sync_key_rotation() {
+        let master_key = PrivateKey::generate();
+        let user_id = UserId::new();
+        let mut paired = Vec::new();
+        for device_index in [1, 2] {
+            let mut host = PairingHost::new(user_id.clone(), master_key.clone(), device_index, "peer").unwrap();
+            let code = host.code().code.clone();
+            paired.push(pair(&mut host, &code).unwrap().0);
+        }
+        let (mut kept, mut revoked) = (paired.remove(0), paired.remove(0));
+
+        let rotation = SyncKeyRotation::issue(user_id.clone(), &master_key, 1, &[kept.certificate.clone()]).unwrap();
+        let PairingMessage::RotateSyncKey { rotation } =
+            PairingMessage::from_bytes(&PairingMessage::RotateSyncKey { rotation }.to_bytes().unwrap()).unwrap()
+        else {
+            unreachable!()
+        };
+        kept.apply_rotation(&rotation).unwrap();
+        assert!(revoked.apply_rotation(&rotation).is_err());
+        assert!(kept.apply_rotation(&rotation).is_err());
+
+        let primary = DeviceSyncChannel::for_epoch(user_id, "primary".to_string(), &master_key, 1).unwrap();
+        let delta = primary.seal(SyncMessageType::ContactsUpdate, &vec![UserId::new()]).unwrap();
+        assert!(kept.sync.open::<Vec<UserId>>(&delta).is_ok());
+        assert!(revoked.sync.open::<Vec<UserId>>(&delta).is_err());
+
+        // A device paired after the rotation gets the current key
+        let mut host = PairingHost::new(delta.user_id.clone(), master_key, 3, "peer").unwrap().with_sync_epoch(1);
+        let code = host.code().code.clone();
+        let (late, _) = pair(&mut host, &code).unwrap();
+        assert_eq!(late.sync.epoch(), 1);
+        assert!(late.sync.open::<Vec<UserId>>(&delta).is_ok());
+    }
+
+    #[test]
+    fn test_paired_device_restores_from_its_sealed_sync_key() {
+        let master_key = PrivateKey::generate();
+        let user_id = UserId::new();
+        let mut host = PairingHost::new(user_id.clone(), master_key.clone(), 1, "peer").unwrap();
+        let code = host.code().code.clone();
+        let (mut device, _) = pair(&mut host, &code).unwrap();
+        let rotation = SyncKeyRotation::issue(user_id.clone(), &master_key, 2, &[device.certificate.clone()]).unwrap();
+        device.apply_rotation(&rotation).unwrap();
+
+        let sealed = device.seal_sync_key().unwrap();
+        let keypair = KeyPair::from_private_key(device.keypair.private_key().clone());
+        let restored = PairedDevice::restore(device.certificate.clone(), keypair, &sealed).unwrap();
+        assert_eq!(restored.sync.epoch(), 2);
+        let primary = DeviceSyncChannel::for_epoch(user_id, "primary".to_string(), &master_key, 2).unwrap();
+        let delta = primary.seal(SyncMessageType::ContactsUpdate, &vec![UserId::new()]).unwrap();
+        assert!(restored.sync.open::<Vec<UserId>>(&delta).is_ok());
+
+        // Another key cannot unseal it
+        assert!(PairedDevice::restore(device.certificate.clone(), KeyPair::generate(), &sealed).is_err());
+    }
+
+    #[test]
+    fn test_foreign_revocation_does_not_shadow_the_real_one() {
+        let master_key = PrivateKey::generate();
+        let identity = IdentityDerivation::derive_all_keys(&master_key).unwrap().main_identity;
+        let mut host = PairingHost::new(UserId::new(), master_key, 1, "peer").unwrap();
+        let code = host.code().code.clone();
+        let (_, certificate) = pair(&mut host, &code).unwrap();
+        let identity_key = identity.public_key().to_core_public_key();
+
+        // An attacker re-issues the victim's device key under their own identity
+        let attacker = KeyPair::generate();
+        let mut foreign = certificate.clone();
+        foreign.identity_key = attacker.public_key().to_core_public_key();
+        let foreign_revocation = DeviceRevocation::issue(&foreign, &attacker).unwrap();
+
+        let mut peer = CertificateRegistry::new();
+        assert!(peer.apply_revocation(foreign_revocation).unwrap());
+        assert!(peer.verify_certificate(&certificate, &identity_key).is_ok());
+
+        let revocation = DeviceRevocation::issue(&certificate, &identity).unwrap();
+        assert!(peer.apply_revocation(revocation).unwrap());
+        assert!(peer.is_revoked(&certificate));
+        assert!(peer.verify_certificate(&certificate, &identity_key).is_err());
+    }
+}
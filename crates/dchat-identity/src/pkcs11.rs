@@ -0,0 +1,709 @@
+//! PKCS#11 signing keys (SoftHSM, YubiHSM, cloud HSM connectors)
+//!
+//! Loads a PKCS#11 module with `dlopen` and signs with an Ed25519 key
+//! (`CKK_EC_EDWARDS` / `CKM_EDDSA`) that never leaves the token. Keys are
+//! addressed with a subset of RFC 7512 URIs:
+//!
+//! ```text
+//! pkcs11:token=dchat;object=validator?module-path=/usr/lib/softhsm/libsofthsm2.so&pin-value=1234
+//! ```
+//!
+//! `module-path` and `pin-value` fall back to the `DCHAT_PKCS11_MODULE` and
+//! `DCHAT_PKCS11_PIN` environment variables so the PIN need not appear on
+//! the command line.
+
+use dchat_core::error::{Error, Result};
+use dchat_crypto::keys::PublicKey as CryptoPublicKey;
+use dchat_crypto::signatures::{self, Signature};
+use std::sync::Mutex;
+use zeroize::Zeroize;
+
+/// Environment fallback for the module path
+pub const PKCS11_MODULE_ENV: &str = "DCHAT_PKCS11_MODULE";
+
+/// Environment fallback for the user PIN
+pub const PKCS11_PIN_ENV: &str = "DCHAT_PKCS11_PIN";
+
+/// A parsed `pkcs11:` key URI
+#[derive(Clone, PartialEq, Eq)]
+pub struct Pkcs11Uri {
+    /// Token label
+    pub token: String,
+    /// Key label (`CKA_LABEL`)
+    pub object: String,
+    /// Path to the PKCS#11 module
+    pub module_path: String,
+    /// User PIN
+    pub pin: String,
+}
+
+impl Pkcs11Uri {
+    /// Parse a URI, filling in the module path and PIN from the environment
+    pub fn parse(uri: &str) -> Result<Self> {
+        let rest = uri
+            .strip_prefix("pkcs11:")
+            .ok_or_else(|| Error::identity("PKCS#11 key must be a pkcs11: URI"))?;
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        let mut token = None;
+        let mut object = None;
+        for attribute in path.split(';').filter(|a| !a.is_empty()) {
+            match attribute.split_once('=') {
+                Some(("token", value)) => token = Some(percent_decode(value)?),
+                Some(("object", value)) => object = Some(percent_decode(value)?),
+                // Other path attributes (type, id, manufacturer...) are not needed
+                Some(_) => {}
+                None => return Err(Error::identity(format!("Malformed PKCS#11 attribute: {}", attribute))),
+            }
+        }
+
+        let mut module_path = None;
+        let mut pin = None;
+        for attribute in query.split('&').filter(|a| !a.is_empty()) {
+            match attribute.split_once('=') {
+                Some(("module-path", value)) => module_path = Some(percent_decode(value)?),
+                Some(("pin-value", value)) => pin = Some(percent_decode(value)?),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            token: token.ok_or_else(|| Error::identity("PKCS#11 URI is missing token="))?,
+            object: object.ok_or_else(|| Error::identity("PKCS#11 URI is missing object="))?,
+            module_path: module_path
+                .or_else(|| std::env::var(PKCS11_MODULE_ENV).ok())
+                .ok_or_else(|| {
+                    Error::identity(format!("No PKCS#11 module-path in URI or {}", PKCS11_MODULE_ENV))
+                })?,
+            pin: pin
+                .or_else(|| std::env::var(PKCS11_PIN_ENV).ok())
+                .ok_or_else(|| Error::identity(format!("No PKCS#11 pin-value in URI or {}", PKCS11_PIN_ENV)))?,
+        })
+    }
+}
+
+impl std::fmt::Debug for Pkcs11Uri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pkcs11Uri")
+            .field("token", &self.token)
+            .field("object", &self.object)
+            .field("module_path", &self.module_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Pkcs11Uri {
+    fn drop(&mut self) {
+        self.pin.zeroize();
+    }
+}
+
+fn percent_decode(value: &str) -> Result<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value
+                .get(i + 1..i + 3)
+                .ok_or_else(|| Error::identity("Truncated percent escape in PKCS#11 URI"))?;
+            decoded.push(
+                u8::from_str_radix(hex, 16)
+                    .map_err(|_| Error::identity("Invalid percent escape in PKCS#11 URI"))?,
+            );
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| Error::identity("PKCS#11 URI is not UTF-8"))
+}
+
+/// An Ed25519 key held on a PKCS#11 token
+pub struct Pkcs11Key {
+    public_key: CryptoPublicKey,
+    session: Mutex<ffi::Session>,
+}
+
+impl Pkcs11Key {
+    /// Log in to the token and locate the key pair named by `uri`
+    pub fn open(uri: &Pkcs11Uri) -> Result<Self> {
+        let mut session = ffi::Session::open(&uri.module_path, &uri.token, &uri.pin)?;
+        let public_key = CryptoPublicKey::from_bytes(session.ed25519_public_key(&uri.object)?);
+        session.select_private_key(&uri.object)?;
+
+        let key = Self {
+            public_key,
+            session: Mutex::new(session),
+        };
+
+        // Make sure the private key on the token matches the public key we report
+        let probe = b"dchat pkcs11 key check";
+        let signature = key.sign(probe)?;
+        signatures::verify(&key.public_key, probe, &signature)
+            .map_err(|_| Error::identity("PKCS#11 private key does not match its public key"))?;
+        Ok(key)
+    }
+
+    pub fn public_key(&self) -> &CryptoPublicKey {
+        &self.public_key
+    }
+
+    /// Sign `message` on the token
+    pub fn sign(&self, message: &[u8]) -> Result<Signature> {
+        let session = self
+            .session
+            .lock()
+            .map_err(|_| Error::identity("PKCS#11 session lock poisoned"))?;
+        Ok(Signature::from_bytes(session.sign(message)?))
+    }
+}
+
+impl std::fmt::Debug for Pkcs11Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pkcs11Key")
+            .field("public_key", &hex::encode(self.public_key.as_bytes()))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Minimal Cryptoki bindings: just enough to log in, find a key and sign
+///
+/// Every call goes through the module's `CK_FUNCTION_LIST`. The invariants
+/// the `unsafe` blocks below rely on are those of [`ffi::Session`]: the
+/// function table stays valid while the module is loaded, the module stays
+/// loaded until the session is dropped, and the session handle is open and
+/// logged in from the end of `Session::open` until then.
+#[cfg(unix)]
+mod ffi {
+    use dchat_core::error::{Error, Result};
+    use std::ffi::{c_void, CString};
+    use std::os::raw::{c_uchar, c_ulong};
+    use std::ptr;
+
+    type CkUlong = c_ulong;
+    type CkRv = CkUlong;
+    type CkSlotId = CkUlong;
+    type CkSessionHandle = CkUlong;
+    type CkObjectHandle = CkUlong;
+
+    const CKR_OK: CkRv = 0x000;
+    const CKR_USER_ALREADY_LOGGED_IN: CkRv = 0x100;
+    const CKR_CRYPTOKI_ALREADY_INITIALIZED: CkRv = 0x191;
+
+    const CKF_SERIAL_SESSION: CkUlong = 0x4;
+    const CKU_USER: CkUlong = 1;
+
+    const CKA_CLASS: CkUlong = 0x000;
+    const CKA_LABEL: CkUlong = 0x003;
+    const CKA_KEY_TYPE: CkUlong = 0x100;
+    const CKA_EC_POINT: CkUlong = 0x181;
+
+    const CKO_PUBLIC_KEY: CkUlong = 2;
+    const CKO_PRIVATE_KEY: CkUlong = 3;
+    const CKK_EC_EDWARDS: CkUlong = 0x40;
+    const CKM_EDDSA: CkUlong = 0x1057;
+
+    #[repr(C)]
+    struct CkVersion {
+        major: c_uchar,
+        minor: c_uchar,
+    }
+
+    #[repr(C)]
+    struct CkAttribute {
+        attribute_type: CkUlong,
+        value: *mut c_void,
+        value_len: CkUlong,
+    }
+
+    #[repr(C)]
+    struct CkMechanism {
+        mechanism: CkUlong,
+        parameter: *mut c_void,
+        parameter_len: CkUlong,
+    }
+
+    #[repr(C)]
+    struct CkTokenInfo {
+        label: [c_uchar; 32],
+        manufacturer_id: [c_uchar; 32],
+        model: [c_uchar; 16],
+        serial_number: [c_uchar; 16],
+        flags: CkUlong,
+        counters: [CkUlong; 10],
+        hardware_version: CkVersion,
+        firmware_version: CkVersion,
+        utc_time: [c_uchar; 16],
+    }
+
+    type Unused = *const c_void;
+
+    /// Leading entries of `CK_FUNCTION_LIST`, in specification order
+    ///
+    /// Only ever read through the pointer `C_GetFunctionList` returns, never
+    /// constructed or copied, so the entries after `C_Sign` that are left
+    /// out do not matter. Every v2.x module provides at least these.
+    #[repr(C)]
+    struct CkFunctionList {
+        version: CkVersion,
+        initialize: unsafe extern "C" fn(*mut c_void) -> CkRv,
+        finalize: unsafe extern "C" fn(*mut c_void) -> CkRv,
+        _get_info: Unused,
+        _get_function_list: Unused,
+        get_slot_list: unsafe extern "C" fn(c_uchar, *mut CkSlotId, *mut CkUlong) -> CkRv,
+        _get_slot_info: Unused,
+        get_token_info: unsafe extern "C" fn(CkSlotId, *mut CkTokenInfo) -> CkRv,
+        _get_mechanism_list: Unused,
+        _get_mechanism_info: Unused,
+        _init_token: Unused,
+        _init_pin: Unused,
+        _set_pin: Unused,
+        open_session: unsafe extern "C" fn(CkSlotId, CkUlong, *mut c_void, Unused, *mut CkSessionHandle) -> CkRv,
+        close_session: unsafe extern "C" fn(CkSessionHandle) -> CkRv,
+        _close_all_sessions: Unused,
+        _get_session_info: Unused,
+        _get_operation_state: Unused,
+        _set_operation_state: Unused,
+        login: unsafe extern "C" fn(CkSessionHandle, CkUlong, *const c_uchar, CkUlong) -> CkRv,
+        logout: unsafe extern "C" fn(CkSessionHandle) -> CkRv,
+        _create_object: Unused,
+        _copy_object: Unused,
+        _destroy_object: Unused,
+        _get_object_size: Unused,
+        get_attribute_value: unsafe extern "C" fn(CkSessionHandle, CkObjectHandle, *mut CkAttribute, CkUlong) -> CkRv,
+        _set_attribute_value: Unused,
+        find_objects_init: unsafe extern "C" fn(CkSessionHandle, *const CkAttribute, CkUlong) -> CkRv,
+        find_objects: unsafe extern "C" fn(CkSessionHandle, *mut CkObjectHandle, CkUlong, *mut CkUlong) -> CkRv,
+        find_objects_final: unsafe extern "C" fn(CkSessionHandle) -> CkRv,
+        _encrypt_init: Unused,
+        _encrypt: Unused,
+        _encrypt_update: Unused,
+        _encrypt_final: Unused,
+        _decrypt_init: Unused,
+        _decrypt: Unused,
+        _decrypt_update: Unused,
+        _decrypt_final: Unused,
+        _digest_init: Unused,
+        _digest: Unused,
+        _digest_update: Unused,
+        _digest_key: Unused,
+        _digest_final: Unused,
+        sign_init: unsafe extern "C" fn(CkSessionHandle, *const CkMechanism, CkObjectHandle) -> CkRv,
+        sign: unsafe extern "C" fn(CkSessionHandle, *const c_uchar, CkUlong, *mut c_uchar, *mut CkUlong) -> CkRv,
+    }
+
+    type GetFunctionList = unsafe extern "C" fn(*mut *const CkFunctionList) -> CkRv;
+
+    fn check(rv: CkRv, operation: &str) -> Result<()> {
+        if rv == CKR_OK {
+            Ok(())
+        } else {
+            Err(Error::identity(format!("PKCS#11 {} failed: CKR 0x{:x}", operation, rv)))
+        }
+    }
+
+    /// A logged-in session on one token; owns the loaded module
+    ///
+    /// Invariants: `library` is a live `dlopen` handle until `drop`, and
+    /// `functions` points at that module's function table, so it is valid
+    /// for as long as the session exists. `handle` is 0 until a session has
+    /// been opened, and is never closed before `drop`.
+    pub(super) struct Session {
+        library: *mut c_void,
+        functions: *const CkFunctionList,
+        finalize: bool,
+        handle: CkSessionHandle,
+        private_key: Option<CkObjectHandle>,
+    }
+
+    // SAFETY: the raw pointers refer to the module's handle and static
+    // function table, which are not tied to the thread that loaded them.
+    // PKCS#11 session handles may be used from any thread as long as calls
+    // on one session are not concurrent; `Session` is not `Sync` and
+    // `Pkcs11Key` keeps it behind a mutex.
+    unsafe impl Send for Session {}
+
+    impl Session {
+        pub(super) fn open(module_path: &str, token_label: &str, pin: &str) -> Result<Self> {
+            let path = CString::new(module_path)
+                .map_err(|_| Error::identity("PKCS#11 module path contains NUL"))?;
+
+            // SAFETY: `dlopen` and `dlsym` get NUL-terminated strings that
+            // outlive the calls. A non-null `C_GetFunctionList` symbol has the
+            // signature the PKCS#11 specification gives it, which is what
+            // `GetFunctionList` declares, so the transmute is sound. The
+            // module is closed again on every error path, before the table
+            // pointer could be used.
+            let (library, functions) = unsafe {
+                let library = libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+                if library.is_null() {
+                    return Err(Error::identity(format!("Cannot load PKCS#11 module {}", module_path)));
+                }
+                let symbol = libc::dlsym(library, c"C_GetFunctionList".as_ptr());
+                if symbol.is_null() {
+                    libc::dlclose(library);
+                    return Err(Error::identity(format!("{} is not a PKCS#11 module", module_path)));
+                }
+                let get_function_list: GetFunctionList = std::mem::transmute(symbol);
+                let mut functions = ptr::null();
+                if get_function_list(&mut functions) != CKR_OK || functions.is_null() {
+                    libc::dlclose(library);
+                    return Err(Error::identity("C_GetFunctionList failed"));
+                }
+                (library, functions)
+            };
+
+            let mut session = Self {
+                library,
+                functions,
+                finalize: false,
+                handle: 0,
+                private_key: None,
+            };
+
+            // SAFETY: `session` now owns the loaded module, so its function
+            // table is valid (see `Session`). `C_Initialize` takes a null
+            // argument to mean default locking. If it fails, dropping
+            // `session` unloads the module.
+            unsafe {
+                let f = &*session.functions;
+                match (f.initialize)(ptr::null_mut()) {
+                    CKR_OK => session.finalize = true,
+                    CKR_CRYPTOKI_ALREADY_INITIALIZED => {}
+                    rv => check(rv, "C_Initialize")?,
+                }
+            }
+
+            let slot = session.find_token(token_label)?;
+
+            // SAFETY: the function table is valid (see `Session`).
+            // `C_OpenSession` writes the handle into a local; no notify
+            // callback is registered, so the null application pointer is
+            // never dereferenced. `C_Login` reads exactly `pin.len()` bytes
+            // from `pin`, which outlives the call. If either fails, dropping
+            // `session` closes what was opened.
+            unsafe {
+                let f = &*session.functions;
+                let mut handle = 0;
+                check(
+                    (f.open_session)(slot, CKF_SERIAL_SESSION, ptr::null_mut(), ptr::null(), &mut handle),
+                    "C_OpenSession",
+                )?;
+                session.handle = handle;
+
+                match (f.login)(handle, CKU_USER, pin.as_ptr(), pin.len() as CkUlong) {
+                    CKR_OK | CKR_USER_ALREADY_LOGGED_IN => {}
+                    rv => check(rv, "C_Login")?,
+                }
+            }
+            Ok(session)
+        }
+
+        fn find_token(&self, label: &str) -> Result<CkSlotId> {
+            // SAFETY: the function table is valid (see `Session`).
+            // `C_GetSlotList` is first called with a null list to get the
+            // count, then with a buffer of exactly `count` entries. It fails
+            // with CKR_BUFFER_TOO_SMALL rather than write past that if slots
+            // appeared in between, and lowers `count` if some went away.
+            let slots = unsafe {
+                let f = &*self.functions;
+                let mut count: CkUlong = 0;
+                check((f.get_slot_list)(1, ptr::null_mut(), &mut count), "C_GetSlotList")?;
+                let mut slots = vec![0 as CkSlotId; count as usize];
+                check((f.get_slot_list)(1, slots.as_mut_ptr(), &mut count), "C_GetSlotList")?;
+                slots.truncate(count as usize);
+                slots
+            };
+
+            for slot in slots {
+                // SAFETY: `CkTokenInfo` only holds integers and byte arrays,
+                // for which all zeroes is a valid value, and `C_GetTokenInfo`
+                // fills a caller-owned struct of exactly this layout.
+                let info = unsafe {
+                    let f = &*self.functions;
+                    let mut info: CkTokenInfo = std::mem::zeroed();
+                    if (f.get_token_info)(slot, &mut info) != CKR_OK {
+                        continue;
+                    }
+                    info
+                };
+                // Labels are blank-padded to 32 bytes
+                let token_label = String::from_utf8_lossy(&info.label);
+                if token_label.trim_end() == label {
+                    return Ok(slot);
+                }
+            }
+            Err(Error::identity(format!("PKCS#11 token not found: {}", label)))
+        }
+
+        fn find_object(&self, class: CkUlong, label: &str) -> Result<CkObjectHandle> {
+            let mut class = class;
+            let mut key_type = CKK_EC_EDWARDS;
+            let template = [
+                CkAttribute {
+                    attribute_type: CKA_CLASS,
+                    value: &mut class as *mut CkUlong as *mut c_void,
+                    value_len: std::mem::size_of::<CkUlong>() as CkUlong,
+                },
+                CkAttribute {
+                    attribute_type: CKA_KEY_TYPE,
+                    value: &mut key_type as *mut CkUlong as *mut c_void,
+                    value_len: std::mem::size_of::<CkUlong>() as CkUlong,
+                },
+                CkAttribute {
+                    attribute_type: CKA_LABEL,
+                    value: label.as_ptr() as *mut c_void,
+                    value_len: label.len() as CkUlong,
+                },
+            ];
+
+            // SAFETY: the function table is valid and the session open (see
+            // `Session`). The template's value pointers refer to `class`,
+            // `key_type` and `label`, which outlive the whole search, and the
+            // lengths match them; `C_FindObjectsInit` only reads through
+            // them. `C_FindObjects` writes at most one handle (the maximum
+            // passed) into `object`, and every started search is finished.
+            let (rv, object, found) = unsafe {
+                let f = &*self.functions;
+                check(
+                    (f.find_objects_init)(self.handle, template.as_ptr(), template.len() as CkUlong),
+                    "C_FindObjectsInit",
+                )?;
+                let mut object = 0;
+                let mut found = 0;
+                let rv = (f.find_objects)(self.handle, &mut object, 1, &mut found);
+                (f.find_objects_final)(self.handle);
+                (rv, object, found)
+            };
+            check(rv, "C_FindObjects")?;
+
+            if found == 0 {
+                return Err(Error::identity(format!("PKCS#11 Ed25519 key not found: {}", label)));
+            }
+            Ok(object)
+        }
+
+        pub(super) fn ed25519_public_key(&self, label: &str) -> Result<[u8; 32]> {
+            let object = self.find_object(CKO_PUBLIC_KEY, label)?;
+
+            // SAFETY: the function table is valid and the session open (see
+            // `Session`). The first `C_GetAttributeValue` has a null value
+            // and only reports the length; the second gets a buffer of
+            // exactly that length, which it may not overrun, and which
+            // outlives the call. The result is truncated to the length the
+            // module reports having written.
+            unsafe {
+                let f = &*self.functions;
+
+                let mut attribute = CkAttribute {
+                    attribute_type: CKA_EC_POINT,
+                    value: ptr::null_mut(),
+                    value_len: 0,
+                };
+                check((f.get_attribute_value)(self.handle, object, &mut attribute, 1), "C_GetAttributeValue")?;
+                let mut point = vec![0u8; attribute.value_len as usize];
+                attribute.value = point.as_mut_ptr() as *mut c_void;
+                check((f.get_attribute_value)(self.handle, object, &mut attribute, 1), "C_GetAttributeValue")?;
+                point.truncate(attribute.value_len as usize);
+
+                decode_ec_point(&point)
+            }
+        }
+
+        pub(super) fn select_private_key(&mut self, label: &str) -> Result<()> {
+            self.private_key = Some(self.find_object(CKO_PRIVATE_KEY, label)?);
+            Ok(())
+        }
+
+        pub(super) fn sign(&self, message: &[u8]) -> Result<[u8; 64]> {
+            let key = self
+                .private_key
+                .ok_or_else(|| Error::identity("No PKCS#11 private key selected"))?;
+
+            // SAFETY: the function table is valid and the session open (see
+            // `Session`), and `key` is a handle the session found. The
+            // mechanism has no parameter, so its null pointer is never read.
+            // `C_Sign` reads `message.len()` bytes from `message` and writes
+            // at most `len` (64, the buffer size) bytes into `signature`;
+            // it reports a larger size with CKR_BUFFER_TOO_SMALL instead of
+            // writing past the buffer.
+            unsafe {
+                let f = &*self.functions;
+                let mechanism = CkMechanism {
+                    mechanism: CKM_EDDSA,
+                    parameter: ptr::null_mut(),
+                    parameter_len: 0,
+                };
+                check((f.sign_init)(self.handle, &mechanism, key), "C_SignInit")?;
+
+                let mut signature = [0u8; 64];
+                let mut len = signature.len() as CkUlong;
+                check(
+                    (f.sign)(self.handle, message.as_ptr(), message.len() as CkUlong, signature.as_mut_ptr(), &mut len),
+                    "C_Sign",
+                )?;
+                if len != 64 {
+                    return Err(Error::identity("PKCS#11 token returned a non-Ed25519 signature"));
+                }
+                Ok(signature)
+            }
+        }
+    }
+
+    impl Drop for Session {
+        fn drop(&mut self) {
+            // SAFETY: the function table is valid until the `dlclose` below,
+            // which is the last use of it (see `Session`). Teardown runs in
+            // reverse order of `open` and only undoes steps that happened: the
+            // session is logged out and closed only if it was opened, and
+            // Cryptoki is finalized only if this session initialized it.
+            unsafe {
+                let f = &*self.functions;
+                if self.handle != 0 {
+                    (f.logout)(self.handle);
+                    (f.close_session)(self.handle);
+                }
+                if self.finalize {
+                    (f.finalize)(ptr::null_mut());
+                }
+                libc::dlclose(self.library);
+            }
+        }
+    }
+
+    /// `CKA_EC_POINT` is a DER OCTET STRING around the 32-byte key, though
+    /// some tokens return the raw key
+    pub(super) fn decode_ec_point(point: &[u8]) -> Result<[u8; 32]> {
+        let raw = match point {
+            [0x04, 0x20, key @ ..] if key.len() == 32 => key,
+            key if key.len() == 32 => key,
+            _ => return Err(Error::identity("Unexpected Ed25519 public key encoding on token")),
+        };
+        let mut key = [0u8; 32];
+        key.copy_from_slice(raw);
+        Ok(key)
+    }
+}
+
+#[cfg(not(unix))]
+mod ffi {
+    use dchat_core::error::{Error, Result};
+
+    pub(super) struct Session;
+
+    impl Session {
+        pub(super) fn open(_module_path: &str, _token_label: &str, _pin: &str) -> Result<Self> {
+            Err(Error::identity("PKCS#11 is only supported on Unix platforms"))
+        }
+
+        pub(super) fn ed25519_public_key(&self, _label: &str) -> Result<[u8; 32]> {
+            unreachable!()
+        }
+
+        pub(super) fn select_private_key(&mut self, _label: &str) -> Result<()> {
+            unreachable!()
+        }
+
+        pub(super) fn sign(&self, _message: &[u8]) -> Result<[u8; 64]> {
+            unreachable!()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uri() {
+        let uri = Pkcs11Uri::parse(
+            "pkcs11:token=dchat%20validators;object=validator-1;type=private\
+             ?module-path=/usr/lib/softhsm/libsofthsm2.so&pin-value=1234",
+        )
+        .unwrap();
+        assert_eq!(uri.token, "dchat validators");
+        assert_eq!(uri.object, "validator-1");
+        assert_eq!(uri.module_path, "/usr/lib/softhsm/libsofthsm2.so");
+        assert_eq!(uri.pin, "1234");
+        assert!(!format!("{:?}", uri).contains("1234"));
+
+        assert!(Pkcs11Uri::parse("validator.key").is_err());
+        assert!(Pkcs11Uri::parse("pkcs11:object=x?module-path=/m&pin-value=1").is_err());
+        assert!(Pkcs11Uri::parse("pkcs11:token=t;object=%zz?module-path=/m&pin-value=1").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_decode_ec_point() {
+        let key = [5u8; 32];
+        let mut der = vec![0x04, 0x20];
+        der.extend_from_slice(&key);
+        assert_eq!(ffi::decode_ec_point(&der).unwrap(), key);
+        assert_eq!(ffi::decode_ec_point(&key).unwrap(), key);
+        assert!(ffi::decode_ec_point(&der[..10]).is_err());
+    }
+
+    #[test]
+    fn test_missing_module_is_an_error() {
+        let uri = Pkcs11Uri::parse("pkcs11:token=t;object=o?module-path=/nonexistent/libpkcs11.so&pin-value=1")
+            .unwrap();
+        assert!(Pkcs11Key::open(&uri).is_err());
+    }
+
+    /// Provisions a throwaway SoftHSM token with `softhsm2-util` and OpenSC's
+    /// `pkcs11-tool`, then signs on it
+    ///
+    /// Run with the module path and a scratch SoftHSM config, e.g.
+    /// `DCHAT_TEST_SOFTHSM_MODULE=/usr/lib/softhsm/libsofthsm2.so
+    /// SOFTHSM2_CONF=/tmp/softhsm2.conf cargo test -p dchat-identity -- --ignored`,
+    /// where the config's `directories.tokendir` is an empty directory.
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "requires SoftHSM; set DCHAT_TEST_SOFTHSM_MODULE and SOFTHSM2_CONF"]
+    fn test_sign_with_softhsm() {
+        let module = std::env::var("DCHAT_TEST_SOFTHSM_MODULE").unwrap();
+        let label = format!("dchat-test-{}", std::process::id());
+
+        let run = |program: &str, args: &[&str]| {
+            let output = std::process::Command::new(program).args(args).output().unwrap();
+            assert!(output.status.success(), "{} failed: {}", program, String::from_utf8_lossy(&output.stderr));
+        };
+        run("softhsm2-util", &["--init-token", "--free", "--label", &label, "--pin", "1234", "--so-pin", "0000"]);
+        run("pkcs11-tool", &[
+            "--module", &module, "--login", "--pin", "1234", "--token-label", &label,
+            "--keypairgen", "--key-type", "EC:edwards25519", "--label", "validator",
+        ]);
+
+        let uri = |pin: &str| Pkcs11Uri {
+            token: label.clone(),
+            object: "validator".to_string(),
+            module_path: module.clone(),
+            pin: pin.to_string(),
+        };
+        let key = Pkcs11Key::open(&uri("1234")).unwrap();
+        for message in [&b"block 1"[..], b"block 2"] {
+            let signature = key.sign(message).unwrap();
+            assert!(signatures::verify(key.public_key(), message, &signature).is_ok());
+        }
+        assert!(signatures::verify(key.public_key(), b"block 3", &key.sign(b"block 2").unwrap()).is_err());
+
+        drop(key);
+        assert!(Pkcs11Key::open(&uri("9999")).is_err());
+        run("softhsm2-util", &["--delete-token", "--token", &label]);
+    }
+
+    /// Run against a hardware token, or SoftHSM, with e.g.
+    /// `softhsm2-util --init-token --free --label dchat --pin 1234 --so-pin 0000`
+    /// and `pkcs11-tool --module $DCHAT_PKCS11_MODULE --login --pin 1234 --token-label dchat
+    /// --keypairgen --key-type EC:edwards25519 --label validator`.
+    #[test]
+    #[ignore = "requires a PKCS#11 token; set DCHAT_TEST_PKCS11_URI"]
+    fn test_sign_with_token() {
+        let uri = Pkcs11Uri::parse(&std::env::var("DCHAT_TEST_PKCS11_URI").unwrap()).unwrap();
+        let key = Pkcs11Key::open(&uri).unwrap();
+        let signature = key.sign(b"block").unwrap();
+        assert!(signatures::verify(key.public_key(), b"block", &signature).is_ok());
+    }
+}
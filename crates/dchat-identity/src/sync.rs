@@ -18,7 +18,7 @@ pub struct SyncMessage {
 }
 
 /// Types of sync messages
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SyncMessageType {
     /// Device registration request
     DeviceAdded,
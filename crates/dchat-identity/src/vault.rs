@@ -0,0 +1,377 @@
+//! Software key vault for hosts without secure hardware
+//!
+//! Linux servers and CI machines have no Secure Enclave or StrongBox, so the
+//! software backends of [`crate::SecureEnclave`] and
+//! [`crate::BiometricAuthenticator`] keep their keys in a [`KeyVault`]: a
+//! single file encrypted with AES-256-GCM under a key derived from a
+//! passphrase with Argon2id. The vault can additionally be bound to the
+//! machine's TPM through a [`TpmSealer`], in which case the file is useless
+//! without both the passphrase and the TPM that sealed it.
+
+use chrono::Utc;
+use dchat_core::error::{Error, Result};
+use dchat_crypto::keys::{KeyPair, PrivateKey, PublicKey as CryptoPublicKey};
+use dchat_crypto::{decrypt_with_key, derive_key_from_password, encrypt_with_key};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use zeroize::Zeroize;
+
+/// Current vault file format
+const VAULT_VERSION: u8 = 1;
+
+/// Combines the passphrase key with the TPM-sealed secret
+const TPM_BINDING_CONTEXT: &str = "dchat 2024 key vault tpm binding";
+
+/// A vault shared between the enclave and biometric backends
+pub type SharedKeyVault = Arc<Mutex<KeyVault>>;
+
+/// Seals secrets to a TPM (or any other machine-bound secret store)
+///
+/// Implementations typically create a sealed object under the TPM's storage
+/// root key, optionally with a PCR policy, and return its serialized blob.
+pub trait TpmSealer: Send + Sync {
+    /// Seal `secret` so that only this machine can recover it
+    fn seal(&self, secret: &[u8; 32]) -> Result<Vec<u8>>;
+
+    /// Recover a secret sealed by [`TpmSealer::seal`]
+    fn unseal(&self, blob: &[u8]) -> Result<[u8; 32]>;
+}
+
+/// What a vault entry holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VaultEntryKind {
+    /// 32-byte Ed25519 signing key
+    SigningKey,
+    /// Opaque secret bytes
+    Secret,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VaultEntry {
+    kind: VaultEntryKind,
+    data: Vec<u8>,
+    created_at: i64,
+}
+
+/// On-disk layout; everything but the header is encrypted
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u8,
+    salt: [u8; 16],
+    tpm_blob: Option<Vec<u8>>,
+    ciphertext: Vec<u8>,
+}
+
+/// Encrypted, file-backed key store
+pub struct KeyVault {
+    path: PathBuf,
+    salt: [u8; 16],
+    tpm_blob: Option<Vec<u8>>,
+    key: [u8; 32],
+    entries: BTreeMap<String, VaultEntry>,
+}
+
+impl KeyVault {
+    /// Create a new, empty vault at `path`
+    ///
+    /// Fails if a file already exists there.
+    pub fn create(
+        path: impl AsRef<Path>,
+        passphrase: &str,
+        sealer: Option<&dyn TpmSealer>,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            return Err(Error::identity(format!("Key vault already exists: {}", path.display())));
+        }
+
+        let mut salt = [0u8; 16];
+        salt.copy_from_slice(&dchat_crypto::generate_seed()[..16]);
+
+        let tpm_blob = match sealer {
+            Some(sealer) => {
+                let mut secret = dchat_crypto::generate_seed();
+                let blob = sealer.seal(&secret);
+                secret.zeroize();
+                Some(blob?)
+            }
+            None => None,
+        };
+
+        let key = vault_key(passphrase, &salt, tpm_blob.as_deref(), sealer)?;
+        let vault = Self {
+            path,
+            salt,
+            tpm_blob,
+            key,
+            entries: BTreeMap::new(),
+        };
+        vault.save()?;
+        Ok(vault)
+    }
+
+    /// Open and decrypt an existing vault
+    pub fn open(
+        path: impl AsRef<Path>,
+        passphrase: &str,
+        sealer: Option<&dyn TpmSealer>,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let bytes = std::fs::read(&path).map_err(Error::Io)?;
+        let file: VaultFile = bincode::deserialize(&bytes)
+            .map_err(|e| Error::identity(format!("Corrupt key vault: {}", e)))?;
+        if file.version != VAULT_VERSION {
+            return Err(Error::identity(format!(
+                "Unsupported key vault version: {}",
+                file.version
+            )));
+        }
+
+        let key = vault_key(passphrase, &file.salt, file.tpm_blob.as_deref(), sealer)?;
+        let mut plaintext = decrypt_with_key(&key, &file.ciphertext)
+            .map_err(|_| Error::identity("Wrong passphrase or tampered key vault"))?;
+        let entries = bincode::deserialize(&plaintext);
+        plaintext.zeroize();
+
+        Ok(Self {
+            path,
+            salt: file.salt,
+            tpm_blob: file.tpm_blob,
+            key,
+            entries: entries.map_err(|e| Error::identity(format!("Corrupt key vault: {}", e)))?,
+        })
+    }
+
+    /// Wrap the vault for sharing between backends
+    pub fn into_shared(self) -> SharedKeyVault {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// Whether opening this vault requires the TPM
+    pub fn is_tpm_bound(&self) -> bool {
+        self.tpm_blob.is_some()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn contains(&self, key_id: &str) -> bool {
+        self.entries.contains_key(key_id)
+    }
+
+    pub fn key_ids(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Kind and creation time of an entry
+    pub fn entry_info(&self, key_id: &str) -> Option<(VaultEntryKind, i64)> {
+        self.entries.get(key_id).map(|entry| (entry.kind, entry.created_at))
+    }
+
+    /// Generate and store a new Ed25519 signing key
+    pub fn generate_signing_key(&mut self, key_id: &str) -> Result<CryptoPublicKey> {
+        let keypair = KeyPair::generate();
+        self.insert(key_id, VaultEntryKind::SigningKey, keypair.private_key().as_bytes().to_vec())?;
+        Ok(keypair.public_key().clone())
+    }
+
+    /// Load a signing key
+    pub fn signing_key(&self, key_id: &str) -> Option<KeyPair> {
+        let entry = self.entries.get(key_id)?;
+        if entry.kind != VaultEntryKind::SigningKey || entry.data.len() != 32 {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&entry.data);
+        Some(KeyPair::from_private_key(PrivateKey::from_bytes(bytes)))
+    }
+
+    /// Store opaque secret bytes, replacing any previous secret under `key_id`
+    pub fn put_secret(&mut self, key_id: &str, data: &[u8]) -> Result<()> {
+        if let Some(entry) = self.entries.get(key_id) {
+            if entry.kind != VaultEntryKind::Secret {
+                return Err(Error::identity(format!("{} is not a secret entry", key_id)));
+            }
+            self.remove(key_id)?;
+        }
+        self.insert(key_id, VaultEntryKind::Secret, data.to_vec())
+    }
+
+    /// Load secret bytes
+    pub fn secret(&self, key_id: &str) -> Option<Vec<u8>> {
+        self.entries
+            .get(key_id)
+            .filter(|entry| entry.kind == VaultEntryKind::Secret)
+            .map(|entry| entry.data.clone())
+    }
+
+    /// Delete an entry
+    pub fn remove(&mut self, key_id: &str) -> Result<()> {
+        let mut entry = self
+            .entries
+            .remove(key_id)
+            .ok_or_else(|| Error::identity(format!("Key not found: {}", key_id)))?;
+        entry.data.zeroize();
+        self.save()
+    }
+
+    fn insert(&mut self, key_id: &str, kind: VaultEntryKind, data: Vec<u8>) -> Result<()> {
+        if self.entries.contains_key(key_id) {
+            return Err(Error::identity(format!("Key already exists: {}", key_id)));
+        }
+        self.entries.insert(
+            key_id.to_string(),
+            VaultEntry {
+                kind,
+                data,
+                created_at: Utc::now().timestamp(),
+            },
+        );
+        self.save()
+    }
+
+    /// Re-encrypt and atomically replace the vault file
+    fn save(&self) -> Result<()> {
+        let mut plaintext = bincode::serialize(&self.entries)
+            .map_err(|e| Error::identity(format!("Failed to encode key vault: {}", e)))?;
+        let ciphertext = encrypt_with_key(&self.key, &plaintext);
+        plaintext.zeroize();
+
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            salt: self.salt,
+            tpm_blob: self.tpm_blob.clone(),
+            ciphertext: ciphertext?,
+        };
+        let bytes = bincode::serialize(&file)
+            .map_err(|e| Error::identity(format!("Failed to encode key vault: {}", e)))?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        write_private_file(&tmp_path, &bytes)?;
+        std::fs::rename(&tmp_path, &self.path).map_err(Error::Io)
+    }
+}
+
+impl Drop for KeyVault {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        for entry in self.entries.values_mut() {
+            entry.data.zeroize();
+        }
+    }
+}
+
+impl std::fmt::Debug for KeyVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyVault")
+            .field("path", &self.path)
+            .field("entries", &self.entries.len())
+            .field("tpm_bound", &self.is_tpm_bound())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Argon2id(passphrase), mixed with the TPM-sealed secret when bound
+fn vault_key(
+    passphrase: &str,
+    salt: &[u8; 16],
+    tpm_blob: Option<&[u8]>,
+    sealer: Option<&dyn TpmSealer>,
+) -> Result<[u8; 32]> {
+    let mut derived = derive_key_from_password(passphrase, salt, 32)?;
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&derived);
+    derived.zeroize();
+
+    if let Some(blob) = tpm_blob {
+        let sealer = sealer.ok_or_else(|| Error::identity("Key vault is bound to a TPM"))?;
+        let mut secret = sealer.unseal(blob)?;
+        let mut hasher = blake3::Hasher::new_derive_key(TPM_BINDING_CONTEXT);
+        hasher.update(&key);
+        hasher.update(&secret);
+        secret.zeroize();
+        key.zeroize();
+        key = *hasher.finalize().as_bytes();
+    }
+    Ok(key)
+}
+
+fn write_private_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(Error::Io)?;
+    file.write_all(bytes).map_err(Error::Io)?;
+    file.sync_all().map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dchat_crypto::signatures;
+
+    /// Stands in for a TPM: "seals" by XOR with a machine secret
+    struct FakeTpm([u8; 32]);
+
+    impl TpmSealer for FakeTpm {
+        fn seal(&self, secret: &[u8; 32]) -> Result<Vec<u8>> {
+            Ok(secret.iter().zip(&self.0).map(|(a, b)| a ^ b).collect())
+        }
+
+        fn unseal(&self, blob: &[u8]) -> Result<[u8; 32]> {
+            let mut secret = [0u8; 32];
+            for (i, (a, b)) in blob.iter().zip(&self.0).enumerate() {
+                secret[i] = a ^ b;
+            }
+            Ok(secret)
+        }
+    }
+
+    #[test]
+    fn test_vault_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.vault");
+
+        let mut vault = KeyVault::create(&path, "passphrase", None).unwrap();
+        let public_key = vault.generate_signing_key("validator").unwrap();
+        vault.put_secret("backup", b"secret bytes").unwrap();
+        assert!(KeyVault::create(&path, "passphrase", None).is_err());
+        drop(vault);
+
+        let vault = KeyVault::open(&path, "passphrase", None).unwrap();
+        let keypair = vault.signing_key("validator").unwrap();
+        assert_eq!(keypair.public_key(), &public_key);
+        let signature = signatures::sign(keypair.private_key(), b"msg");
+        assert!(signatures::verify(&public_key, b"msg", &signature).is_ok());
+        assert_eq!(vault.secret("backup").unwrap(), b"secret bytes");
+        assert!(vault.secret("validator").is_none());
+
+        assert!(KeyVault::open(&path, "wrong", None).is_err());
+    }
+
+    #[test]
+    fn test_tpm_bound_vault_needs_tpm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.vault");
+        let tpm = FakeTpm([42; 32]);
+
+        let mut vault = KeyVault::create(&path, "passphrase", Some(&tpm)).unwrap();
+        assert!(vault.is_tpm_bound());
+        vault.put_secret("k", b"v").unwrap();
+        drop(vault);
+
+        assert!(KeyVault::open(&path, "passphrase", None).is_err());
+        assert!(KeyVault::open(&path, "passphrase", Some(&FakeTpm([7; 32]))).is_err());
+        let vault = KeyVault::open(&path, "passphrase", Some(&tpm)).unwrap();
+        assert_eq!(vault.secret("k").unwrap(), b"v");
+    }
+}
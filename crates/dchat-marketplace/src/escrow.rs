@@ -1,3 +1,11 @@
+//! Escrow backed by a token ledger account
+//!
+//! Escrowed funds sit in a single escrow account on the ledger. An escrow is
+//! opened from a payment the buyer already made into that account, and every
+//! exit (release, refund, dispute resolution, expiry) pays the full escrowed
+//! amount back out in one all-or-nothing ledger transfer, so the escrow
+//! account always holds exactly the sum of the open escrows.
+
 use crate::types::{ListingId, MarketplaceError, UserId};
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
@@ -5,6 +13,31 @@ use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use uuid::Uuid;
 
+/// A transfer already applied on the ledger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerPayment {
+    pub from: UserId,
+    pub to: UserId,
+    /// Amount credited to `to`
+    pub amount: u64,
+}
+
+/// Token ledger escrowed funds are held on
+///
+/// Implemented by the currency chain client; defined here so this crate does
+/// not depend on the client.
+pub trait EscrowLedger {
+    /// Look up an applied transfer by transaction ID
+    fn payment(&self, tx_id: &Uuid) -> dchat_core::Result<Option<LedgerPayment>>;
+    /// Pay every `(recipient, amount)` from `from`, or none of them
+    fn transfer_batch(&self, from: &UserId, payouts: &[(UserId, u64)]) -> dchat_core::Result<Vec<Uuid>>;
+    /// Pay every `(recipient, amount)` from `from` and burn `burn` more from
+    /// it as a platform fee, or do none of it
+    ///
+    /// Returns the payout transaction IDs.
+    fn transfer_and_burn(&self, from: &UserId, payouts: &[(UserId, u64)], burn: u64) -> dchat_core::Result<Vec<Uuid>>;
+}
+
 /// Escrow state machine
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EscrowState {
@@ -67,6 +100,49 @@ pub struct Escrow {
     pub created_at: DateTime<Utc>,
     pub locked_until: DateTime<Utc>, // Auto-refund if not released by this time
     pub dispute: Option<Dispute>,
+    /// Ledger payment from the buyer that funded the escrow
+    #[serde(default)]
+    pub funding_tx: Uuid,
+    /// Ledger transfers that paid the escrow out
+    #[serde(default)]
+    pub payout_txs: Vec<Uuid>,
+}
+
+impl Escrow {
+    /// Buyer who funded the escrow
+    pub fn buyer(&self) -> &UserId {
+        match &self.escrow_type {
+            EscrowType::TwoParty { buyer, .. } | EscrowType::MultiParty { buyer, .. } => buyer,
+        }
+    }
+
+    /// Payouts that refund `refund` to the buyer and the rest to the sellers
+    ///
+    /// Multi-party recipients share the remainder in proportion to their
+    /// amounts; rounding dust goes to the first recipient.
+    fn payouts(&self, refund: u64) -> Vec<(UserId, u64)> {
+        let remainder = self.amount - refund;
+        let mut payouts = vec![(self.buyer().clone(), refund)];
+        match &self.escrow_type {
+            EscrowType::TwoParty { seller, .. } => payouts.push((seller.clone(), remainder)),
+            EscrowType::MultiParty { recipients, .. } => {
+                let shares: Vec<(UserId, u64)> = recipients
+                    .iter()
+                    .map(|(id, amt)| {
+                        let share = *amt as u128 * remainder as u128 / self.amount.max(1) as u128;
+                        (id.clone(), share as u64)
+                    })
+                    .collect();
+                let dust = remainder - shares.iter().map(|(_, share)| share).sum::<u64>();
+                payouts.extend(shares);
+                if let Some(first) = payouts.get_mut(1) {
+                    first.1 += dust;
+                }
+            }
+        }
+        payouts.retain(|(_, amount)| *amount > 0);
+        payouts
+    }
 }
 
 /// Dispute details
@@ -81,27 +157,94 @@ pub struct Dispute {
 }
 
 /// Escrow manager
+///
+/// The escrow account must exist on the ledger as an account only the
+/// protocol can move (a module account on the currency chain).
 pub struct EscrowManager {
+    account: UserId,
     escrows: Arc<RwLock<HashMap<Uuid, Escrow>>>,
 }
 
 impl EscrowManager {
-    /// Create a new escrow manager
+    /// Create a new escrow manager with a fresh escrow account
     pub fn new() -> Self {
+        Self::with_account(UserId::new())
+    }
+
+    /// Create an escrow manager holding funds in `account`
+    pub fn with_account(account: UserId) -> Self {
         Self {
+            account,
             escrows: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
-    /// Create a two-party escrow
+    /// Ledger account escrowed funds are held in
+    pub fn account(&self) -> &UserId {
+        &self.account
+    }
+
+    /// Check that `funding_tx` is an unused payment from `buyer` into the
+    /// escrow account, returning the amount it credited
+    fn verify_funding(
+        &self,
+        escrows: &HashMap<Uuid, Escrow>,
+        ledger: &dyn EscrowLedger,
+        buyer: &UserId,
+        funding_tx: Uuid,
+    ) -> Result<u64, MarketplaceError> {
+        let payment = ledger
+            .payment(&funding_tx)
+            .map_err(|e| MarketplaceError::Ledger(e.to_string()))?
+            .ok_or(MarketplaceError::InvalidPayment)?;
+
+        if &payment.from != buyer
+            || payment.to != self.account
+            || escrows.values().any(|e| e.funding_tx == funding_tx)
+        {
+            return Err(MarketplaceError::InvalidPayment);
+        }
+        Ok(payment.amount)
+    }
+
+    /// Pay an escrow out and move it to `state`
+    ///
+    /// Nothing changes unless the ledger applies every payout.
+    fn settle(
+        &self,
+        ledger: &dyn EscrowLedger,
+        escrow: &mut Escrow,
+        refund: u64,
+        state: EscrowState,
+    ) -> Result<(), MarketplaceError> {
+        if refund > escrow.amount {
+            return Err(MarketplaceError::RefundExceedsEscrow);
+        }
+
+        let payouts = escrow.payouts(refund);
+        if !payouts.is_empty() {
+            let tx_ids = ledger
+                .transfer_batch(&self.account, &payouts)
+                .map_err(|e| MarketplaceError::Ledger(e.to_string()))?;
+            escrow.payout_txs.extend(tx_ids);
+        }
+        escrow.state = state;
+        Ok(())
+    }
+
+    /// Create a two-party escrow funded by the buyer's payment `funding_tx`
     pub fn create_two_party_escrow(
         &self,
+        ledger: &dyn EscrowLedger,
         listing_id: ListingId,
         buyer: &UserId,
         seller: &UserId,
-        amount: u64,
+        funding_tx: Uuid,
         lock_duration_secs: u64,
     ) -> Result<Uuid, MarketplaceError> {
+        let mut escrows = self.escrows.write().unwrap();
+        let amount = self.verify_funding(&escrows, ledger, buyer, funding_tx)?;
+
         let now = Utc::now();
         let escrow_id = Uuid::new_v4();
 
@@ -114,24 +257,39 @@ impl EscrowManager {
             created_at: now,
             locked_until: now + Duration::seconds(lock_duration_secs as i64),
             dispute: None,
+            funding_tx,
+            payout_txs: Vec::new(),
         };
 
-        let mut escrows = self.escrows.write().unwrap();
         escrows.insert(escrow_id, escrow);
 
         Ok(escrow_id)
     }
 
     /// Create a multi-party escrow with revenue split
+    ///
+    /// The buyer's payment `funding_tx` must cover exactly the sum of the
+    /// recipient amounts.
     pub fn create_multi_party_escrow(
         &self,
+        ledger: &dyn EscrowLedger,
         listing_id: ListingId,
         buyer: &UserId,
         recipients: Vec<(UserId, u64)>,
+        funding_tx: Uuid,
         lock_duration_secs: u64,
     ) -> Result<Uuid, MarketplaceError> {
+        let mut escrows = self.escrows.write().unwrap();
+        let paid = self.verify_funding(&escrows, ledger, buyer, funding_tx)?;
+
         // Validate total amount matches sum of recipient amounts
-        let total: u64 = recipients.iter().map(|(_, amt)| amt).sum();
+        let total = recipients
+            .iter()
+            .try_fold(0u64, |sum, (_, amt)| sum.checked_add(*amt))
+            .ok_or(MarketplaceError::InvalidPayment)?;
+        if recipients.is_empty() || total != paid {
+            return Err(MarketplaceError::InvalidPayment);
+        }
 
         let now = Utc::now();
         let escrow_id = Uuid::new_v4();
@@ -145,9 +303,10 @@ impl EscrowManager {
             created_at: now,
             locked_until: now + Duration::seconds(lock_duration_secs as i64),
             dispute: None,
+            funding_tx,
+            payout_txs: Vec::new(),
         };
 
-        let mut escrows = self.escrows.write().unwrap();
         escrows.insert(escrow_id, escrow);
 
         Ok(escrow_id)
@@ -169,30 +328,23 @@ impl EscrowManager {
     }
 
     /// Release funds to seller (buyer confirms)
-    pub fn release_funds(&self, escrow_id: Uuid, buyer: &UserId) -> Result<(), MarketplaceError> {
+    ///
+    /// Pays the seller, or every multi-party recipient, from the escrow
+    /// account.
+    pub fn release_funds(
+        &self,
+        ledger: &dyn EscrowLedger,
+        escrow_id: Uuid,
+        buyer: &UserId,
+    ) -> Result<(), MarketplaceError> {
         let mut escrows = self.escrows.write().unwrap();
         let escrow = escrows
             .get_mut(&escrow_id)
             .ok_or(MarketplaceError::EscrowNotFound)?;
 
         // Verify buyer authorization
-        match &escrow.escrow_type {
-            EscrowType::TwoParty {
-                buyer: escrow_buyer,
-                ..
-            } => {
-                if escrow_buyer != buyer {
-                    return Err(MarketplaceError::Unauthorized);
-                }
-            }
-            EscrowType::MultiParty {
-                buyer: escrow_buyer,
-                ..
-            } => {
-                if escrow_buyer != buyer {
-                    return Err(MarketplaceError::Unauthorized);
-                }
-            }
+        if escrow.buyer() != buyer {
+            return Err(MarketplaceError::Unauthorized);
         }
 
         // Can only release from AwaitingRelease state
@@ -200,8 +352,7 @@ impl EscrowManager {
             return Err(MarketplaceError::InvalidEscrowState);
         }
 
-        escrow.state = EscrowState::Released;
-        Ok(())
+        self.settle(ledger, escrow, 0, EscrowState::Released)
     }
 
     /// Raise a dispute
@@ -248,8 +399,12 @@ impl EscrowManager {
     }
 
     /// Resolve a dispute (admin/arbitrator action)
+    ///
+    /// A partial refund returns that amount to the buyer and pays the rest
+    /// to the seller side.
     pub fn resolve_dispute(
         &self,
+        ledger: &dyn EscrowLedger,
         escrow_id: Uuid,
         resolution: DisputeResolution,
     ) -> Result<(), MarketplaceError> {
@@ -262,37 +417,41 @@ impl EscrowManager {
             return Err(MarketplaceError::InvalidEscrowState);
         }
 
-        let dispute = escrow
-            .dispute
-            .as_mut()
-            .ok_or(MarketplaceError::InvalidEscrowState)?;
-
-        dispute.resolution = Some(resolution.clone());
-        dispute.resolved_at = Some(Utc::now());
+        if escrow.dispute.is_none() {
+            return Err(MarketplaceError::InvalidEscrowState);
+        }
 
-        // Update escrow state based on resolution
-        escrow.state = match resolution {
-            DisputeResolution::ReleaseFunds => EscrowState::Released,
-            DisputeResolution::RefundFull => EscrowState::Refunded,
-            DisputeResolution::PartialRefund(_) => EscrowState::Refunded, // Partial is handled externally
+        // Pay out according to the resolution
+        let (refund, state) = match resolution {
+            DisputeResolution::ReleaseFunds => (0, EscrowState::Released),
+            DisputeResolution::RefundFull => (escrow.amount, EscrowState::Refunded),
+            DisputeResolution::PartialRefund(refund) => (refund, EscrowState::Refunded),
         };
+        self.settle(ledger, escrow, refund, state)?;
+
+        let dispute = escrow.dispute.as_mut().expect("checked above");
+        dispute.resolution = Some(resolution);
+        dispute.resolved_at = Some(Utc::now());
 
         Ok(())
     }
 
     /// Check for expired escrows and auto-refund
-    pub fn process_expirations(&self) -> Vec<Uuid> {
+    ///
+    /// An escrow whose refund the ledger rejects stays open and is retried
+    /// on the next call.
+    pub fn process_expirations(&self, ledger: &dyn EscrowLedger) -> Vec<Uuid> {
         let mut refunded = Vec::new();
         let now = Utc::now();
         let mut escrows = self.escrows.write().unwrap();
 
         for (id, escrow) in escrows.iter_mut() {
             // Auto-refund if locked and expired
-            if (escrow.state == EscrowState::Locked
+            let expired = (escrow.state == EscrowState::Locked
                 || escrow.state == EscrowState::AwaitingRelease)
-                && now > escrow.locked_until
-            {
-                escrow.state = EscrowState::Expired;
+                && now > escrow.locked_until;
+            let refund = escrow.amount;
+            if expired && self.settle(ledger, escrow, refund, EscrowState::Expired).is_ok() {
                 refunded.push(*id);
             }
         }
@@ -300,6 +459,21 @@ impl EscrowManager {
         refunded
     }
 
+    /// Total still held for escrows that have not been paid out
+    pub fn locked_total(&self) -> u64 {
+        let escrows = self.escrows.read().unwrap();
+        escrows
+            .values()
+            .filter(|e| {
+                matches!(
+                    e.state,
+                    EscrowState::Locked | EscrowState::AwaitingRelease | EscrowState::Disputed
+                )
+            })
+            .map(|e| e.amount)
+            .sum()
+    }
+
     /// Get escrow by ID
     pub fn get_escrow(&self, escrow_id: Uuid) -> Option<Escrow> {
         let escrows = self.escrows.read().unwrap();
@@ -350,31 +524,170 @@ impl Default for EscrowManager {
     }
 }
 
+/// Serialized form of `EscrowManager`
+#[derive(Serialize, Deserialize)]
+struct EscrowSnapshot {
+    account: UserId,
+    escrows: HashMap<Uuid, Escrow>,
+}
+
+impl Serialize for EscrowManager {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EscrowSnapshot {
+            account: self.account.clone(),
+            escrows: self.escrows.read().unwrap().clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EscrowManager {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snapshot = EscrowSnapshot::deserialize(deserializer)?;
+        Ok(Self {
+            account: snapshot.account,
+            escrows: Arc::new(RwLock::new(snapshot.escrows)),
+        })
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    /// In-memory ledger with a fixed token supply
+    #[derive(Default)]
+    pub(crate) struct MemoryLedger {
+        balances: RwLock<HashMap<UserId, u64>>,
+        payments: RwLock<HashMap<Uuid, LedgerPayment>>,
+        burned: RwLock<u64>,
+    }
+
+    impl MemoryLedger {
+        pub(crate) fn fund(&self, account: &UserId, amount: u64) {
+            *self.balances.write().unwrap().entry(account.clone()).or_default() += amount;
+        }
+
+        pub(crate) fn balance(&self, account: &UserId) -> u64 {
+            self.balances.read().unwrap().get(account).copied().unwrap_or(0)
+        }
+
+        pub(crate) fn total(&self) -> u64 {
+            self.balances.read().unwrap().values().sum()
+        }
+
+        pub(crate) fn burned(&self) -> u64 {
+            *self.burned.read().unwrap()
+        }
+
+        /// Transfer as `from` would with a signed transaction
+        pub(crate) fn pay(&self, from: &UserId, to: &UserId, amount: u64) -> Uuid {
+            self.transfer_batch(from, &[(to.clone(), amount)]).unwrap()[0]
+        }
+    }
+
+    impl EscrowLedger for MemoryLedger {
+        fn payment(&self, tx_id: &Uuid) -> dchat_core::Result<Option<LedgerPayment>> {
+            Ok(self.payments.read().unwrap().get(tx_id).cloned())
+        }
+
+        fn transfer_batch(&self, from: &UserId, payouts: &[(UserId, u64)]) -> dchat_core::Result<Vec<Uuid>> {
+            self.transfer_and_burn(from, payouts, 0)
+        }
+
+        fn transfer_and_burn(&self, from: &UserId, payouts: &[(UserId, u64)], burn: u64) -> dchat_core::Result<Vec<Uuid>> {
+            let mut balances = self.balances.write().unwrap();
+            let total: u64 = payouts.iter().map(|(_, amount)| amount).sum::<u64>() + burn;
+            let balance = balances.get(from).copied().unwrap_or(0);
+            if balance < total {
+                return Err(dchat_core::Error::InvalidInput("Insufficient balance".to_string()));
+            }
+
+            *balances.get_mut(from).unwrap() -= total;
+            *self.burned.write().unwrap() += burn;
+            let mut payments = self.payments.write().unwrap();
+            Ok(payouts
+                .iter()
+                .map(|(to, amount)| {
+                    *balances.entry(to.clone()).or_default() += amount;
+                    let tx_id = Uuid::new_v4();
+                    payments.insert(tx_id, LedgerPayment { from: from.clone(), to: to.clone(), amount: *amount });
+                    tx_id
+                })
+                .collect())
+        }
+    }
 
     fn new_listing_id() -> ListingId {
         Uuid::new_v4()
     }
 
+    /// Buyer pays `amount` into escrow, returning the payment
+    fn deposit(ledger: &MemoryLedger, manager: &EscrowManager, buyer: &UserId, amount: u64) -> Uuid {
+        ledger.fund(buyer, amount);
+        ledger.pay(buyer, manager.account(), amount)
+    }
+
+    fn two_party(ledger: &MemoryLedger, manager: &EscrowManager, buyer: &UserId, seller: &UserId, amount: u64, lock: u64) -> Uuid {
+        let payment = deposit(ledger, manager, buyer, amount);
+        manager
+            .create_two_party_escrow(ledger, new_listing_id(), buyer, seller, payment, lock)
+            .unwrap()
+    }
+
     #[test]
     fn test_create_two_party_escrow() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller = UserId::new();
 
-        let escrow_id = manager
-            .create_two_party_escrow(Uuid::new_v4(), &buyer, &seller, 1000, 86400)
-            .unwrap();
+        let escrow_id = two_party(&ledger, &manager, &buyer, &seller, 1000, 86400);
 
         let escrow = manager.get_escrow(escrow_id).unwrap();
         assert_eq!(escrow.amount, 1000);
         assert_eq!(escrow.state, EscrowState::Locked);
+        assert_eq!(ledger.balance(&buyer), 0);
+        assert_eq!(ledger.balance(manager.account()), 1000);
+    }
+
+    #[test]
+    fn test_escrow_requires_unused_payment_from_buyer() {
+        let ledger = MemoryLedger::default();
+        let manager = EscrowManager::new();
+        let buyer = UserId::new();
+        let seller = UserId::new();
+        let other = UserId::new();
+
+        // Unknown payments, and payments not into the escrow account
+        assert!(manager
+            .create_two_party_escrow(&ledger, new_listing_id(), &buyer, &seller, Uuid::new_v4(), 86400)
+            .is_err());
+        ledger.fund(&buyer, 1000);
+        let elsewhere = ledger.pay(&buyer, &seller, 500);
+        assert!(manager
+            .create_two_party_escrow(&ledger, new_listing_id(), &buyer, &seller, elsewhere, 86400)
+            .is_err());
+
+        // Someone else's payment cannot fund the buyer's escrow
+        let payment = ledger.pay(&buyer, manager.account(), 500);
+        assert!(manager
+            .create_two_party_escrow(&ledger, new_listing_id(), &other, &seller, payment, 86400)
+            .is_err());
+
+        // A payment funds one escrow only
+        manager
+            .create_two_party_escrow(&ledger, new_listing_id(), &buyer, &seller, payment, 86400)
+            .unwrap();
+        assert!(manager
+            .create_two_party_escrow(&ledger, new_listing_id(), &buyer, &seller, payment, 86400)
+            .is_err());
     }
 
     #[test]
     fn test_create_multi_party_escrow() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller1 = UserId::new();
@@ -382,8 +695,16 @@ mod tests {
 
         let recipients = vec![(seller1, 700), (seller2, 300)];
 
+        // The payment must cover exactly the split
+        let short = deposit(&ledger, &manager, &buyer, 900);
+        assert!(matches!(
+            manager.create_multi_party_escrow(&ledger, new_listing_id(), &buyer, recipients.clone(), short, 86400),
+            Err(MarketplaceError::InvalidPayment)
+        ));
+
+        let payment = deposit(&ledger, &manager, &buyer, 1000);
         let escrow_id = manager
-            .create_multi_party_escrow(new_listing_id(), &buyer, recipients, 86400)
+            .create_multi_party_escrow(&ledger, new_listing_id(), &buyer, recipients, payment, 86400)
             .unwrap();
 
         let escrow = manager.get_escrow(escrow_id).unwrap();
@@ -393,13 +714,15 @@ mod tests {
 
     #[test]
     fn test_release_funds_flow() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller = UserId::new();
 
-        let escrow_id = manager
-            .create_two_party_escrow(new_listing_id(), &buyer, &seller, 1000, 86400)
-            .unwrap();
+        let escrow_id = two_party(&ledger, &manager, &buyer, &seller, 1000, 86400);
+
+        // Buyer cannot release before the seller completes
+        assert!(manager.release_funds(&ledger, escrow_id, &buyer).is_err());
 
         // Seller marks as complete
         manager.mark_awaiting_release(escrow_id).unwrap();
@@ -408,39 +731,45 @@ mod tests {
         assert_eq!(escrow.state, EscrowState::AwaitingRelease);
 
         // Buyer releases funds
-        manager.release_funds(escrow_id, &buyer).unwrap();
+        manager.release_funds(&ledger, escrow_id, &buyer).unwrap();
 
         let escrow = manager.get_escrow(escrow_id).unwrap();
         assert_eq!(escrow.state, EscrowState::Released);
+        assert_eq!(escrow.payout_txs.len(), 1);
+        assert_eq!(ledger.balance(&seller), 1000);
+        assert_eq!(ledger.balance(manager.account()), 0);
+
+        // Released funds cannot be paid out twice
+        assert!(manager.release_funds(&ledger, escrow_id, &buyer).is_err());
+        assert_eq!(ledger.balance(&seller), 1000);
     }
 
     #[test]
     fn test_unauthorized_release() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller = UserId::new();
         let attacker = UserId::new();
 
-        let escrow_id = manager
-            .create_two_party_escrow(new_listing_id(), &buyer, &seller, 1000, 86400)
-            .unwrap();
+        let escrow_id = two_party(&ledger, &manager, &buyer, &seller, 1000, 86400);
 
         manager.mark_awaiting_release(escrow_id).unwrap();
 
         // Attacker tries to release
-        let result = manager.release_funds(escrow_id, &attacker);
+        let result = manager.release_funds(&ledger, escrow_id, &attacker);
         assert!(result.is_err());
+        assert_eq!(ledger.balance(manager.account()), 1000);
     }
 
     #[test]
     fn test_raise_dispute() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller = UserId::new();
 
-        let escrow_id = manager
-            .create_two_party_escrow(new_listing_id(), &buyer, &seller, 1000, 86400)
-            .unwrap();
+        let escrow_id = two_party(&ledger, &manager, &buyer, &seller, 1000, 86400);
 
         manager.mark_awaiting_release(escrow_id).unwrap();
 
@@ -458,13 +787,12 @@ mod tests {
 
     #[test]
     fn test_resolve_dispute_refund() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller = UserId::new();
 
-        let escrow_id = manager
-            .create_two_party_escrow(new_listing_id(), &buyer, &seller, 1000, 86400)
-            .unwrap();
+        let escrow_id = two_party(&ledger, &manager, &buyer, &seller, 1000, 86400);
 
         manager
             .raise_dispute(escrow_id, &buyer, DisputeReason::ItemNotReceived,
@@ -474,22 +802,23 @@ mod tests {
 
         // Admin resolves with full refund
         manager
-            .resolve_dispute(escrow_id, DisputeResolution::RefundFull)
+            .resolve_dispute(&ledger, escrow_id, DisputeResolution::RefundFull)
             .unwrap();
 
         let escrow = manager.get_escrow(escrow_id).unwrap();
         assert_eq!(escrow.state, EscrowState::Refunded);
+        assert_eq!(ledger.balance(&buyer), 1000);
+        assert_eq!(ledger.balance(&seller), 0);
     }
 
     #[test]
     fn test_resolve_dispute_release() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller = UserId::new();
 
-        let escrow_id = manager
-            .create_two_party_escrow(new_listing_id(), &buyer, &seller, 1000, 86400)
-            .unwrap();
+        let escrow_id = two_party(&ledger, &manager, &buyer, &seller, 1000, 86400);
 
         manager
             .raise_dispute(escrow_id, &buyer, DisputeReason::QualityIssue,
@@ -499,22 +828,22 @@ mod tests {
 
         // Admin resolves in favor of seller
         manager
-            .resolve_dispute(escrow_id, DisputeResolution::ReleaseFunds)
+            .resolve_dispute(&ledger, escrow_id, DisputeResolution::ReleaseFunds)
             .unwrap();
 
         let escrow = manager.get_escrow(escrow_id).unwrap();
         assert_eq!(escrow.state, EscrowState::Released);
+        assert_eq!(ledger.balance(&seller), 1000);
     }
 
     #[test]
     fn test_partial_refund() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller = UserId::new();
 
-        let escrow_id = manager
-            .create_two_party_escrow(new_listing_id(), &buyer, &seller, 1000, 86400)
-            .unwrap();
+        let escrow_id = two_party(&ledger, &manager, &buyer, &seller, 1000, 86400);
 
         manager
             .raise_dispute(escrow_id, &buyer, DisputeReason::QualityIssue,
@@ -522,13 +851,22 @@ mod tests {
             )
             .unwrap();
 
+        // A refund larger than the escrow is rejected and changes nothing
+        assert!(matches!(
+            manager.resolve_dispute(&ledger, escrow_id, DisputeResolution::PartialRefund(1001)),
+            Err(MarketplaceError::RefundExceedsEscrow)
+        ));
+        assert_eq!(manager.get_escrow(escrow_id).unwrap().state, EscrowState::Disputed);
+
         // Admin resolves with 30% refund
         manager
-            .resolve_dispute(escrow_id, DisputeResolution::PartialRefund(300))
+            .resolve_dispute(&ledger, escrow_id, DisputeResolution::PartialRefund(300))
             .unwrap();
 
         let escrow = manager.get_escrow(escrow_id).unwrap();
         assert_eq!(escrow.state, EscrowState::Refunded);
+        assert_eq!(ledger.balance(&buyer), 300);
+        assert_eq!(ledger.balance(&seller), 700);
 
         let dispute = escrow.dispute.unwrap();
         assert!(dispute.resolution.is_some());
@@ -536,39 +874,38 @@ mod tests {
 
     #[test]
     fn test_expiration_processing() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller = UserId::new();
 
         // Create escrow with 0 second lock (immediate expiration)
-        let escrow_id = manager
-            .create_two_party_escrow(new_listing_id(), &buyer, &seller, 1000, 0)
-            .unwrap();
+        let escrow_id = two_party(&ledger, &manager, &buyer, &seller, 1000, 0);
+        two_party(&ledger, &manager, &buyer, &seller, 500, 86400);
 
         // Process expirations
         std::thread::sleep(std::time::Duration::from_millis(10));
-        let expired = manager.process_expirations();
+        let expired = manager.process_expirations(&ledger);
 
         assert_eq!(expired.len(), 1);
         assert_eq!(expired[0], escrow_id);
 
         let escrow = manager.get_escrow(escrow_id).unwrap();
         assert_eq!(escrow.state, EscrowState::Expired);
+        assert_eq!(ledger.balance(&buyer), 1000);
+        assert_eq!(ledger.balance(manager.account()), 500);
     }
 
     #[test]
     fn test_get_buyer_escrows() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let seller1 = UserId::new();
         let seller2 = UserId::new();
 
-        manager
-            .create_two_party_escrow(new_listing_id(), &buyer, &seller1, 1000, 86400)
-            .unwrap();
-        manager
-            .create_two_party_escrow(new_listing_id(), &buyer, &seller2, 2000, 86400)
-            .unwrap();
+        two_party(&ledger, &manager, &buyer, &seller1, 1000, 86400);
+        two_party(&ledger, &manager, &buyer, &seller2, 2000, 86400);
 
         let buyer_escrows = manager.get_buyer_escrows(buyer);
         assert_eq!(buyer_escrows.len(), 2);
@@ -576,17 +913,14 @@ mod tests {
 
     #[test]
     fn test_get_seller_escrows() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer1 = UserId::new();
         let buyer2 = UserId::new();
         let seller = UserId::new();
 
-        manager
-            .create_two_party_escrow(new_listing_id(), &buyer1, &seller, 1000, 86400)
-            .unwrap();
-        manager
-            .create_two_party_escrow(new_listing_id(), &buyer2, &seller, 2000, 86400)
-            .unwrap();
+        two_party(&ledger, &manager, &buyer1, &seller, 1000, 86400);
+        two_party(&ledger, &manager, &buyer2, &seller, 2000, 86400);
 
         let seller_escrows = manager.get_seller_escrows(seller);
         assert_eq!(seller_escrows.len(), 2);
@@ -594,6 +928,7 @@ mod tests {
 
     #[test]
     fn test_multi_party_escrow_split() {
+        let ledger = MemoryLedger::default();
         let manager = EscrowManager::new();
         let buyer = UserId::new();
         let creator = UserId::new();
@@ -601,10 +936,11 @@ mod tests {
         let affiliate = UserId::new();
 
         // 70% to creator, 20% to platform, 10% to affiliate
-        let recipients = vec![(creator, 700), (platform, 200), (affiliate, 100)];
+        let recipients = vec![(creator.clone(), 700), (platform.clone(), 200), (affiliate.clone(), 100)];
 
+        let payment = deposit(&ledger, &manager, &buyer, 1000);
         let escrow_id = manager
-            .create_multi_party_escrow(new_listing_id(), &buyer, recipients, 86400)
+            .create_multi_party_escrow(&ledger, new_listing_id(), &buyer, recipients, payment, 86400)
             .unwrap();
 
         let escrow = manager.get_escrow(escrow_id).unwrap();
@@ -618,6 +954,173 @@ mod tests {
         } else {
             panic!("Expected MultiParty escrow");
         }
+
+        // Release pays every recipient in one transfer
+        manager.mark_awaiting_release(escrow_id).unwrap();
+        manager.release_funds(&ledger, escrow_id, &buyer).unwrap();
+        assert_eq!(ledger.balance(&creator), 700);
+        assert_eq!(ledger.balance(&platform), 200);
+        assert_eq!(ledger.balance(&affiliate), 100);
     }
-}
 
+    #[test]
+    fn test_multi_party_partial_refund_splits_remainder() {
+        let ledger = MemoryLedger::default();
+        let manager = EscrowManager::new();
+        let buyer = UserId::new();
+        let creator = UserId::new();
+        let platform = UserId::new();
+
+        let payment = deposit(&ledger, &manager, &buyer, 1000);
+        let escrow_id = manager
+            .create_multi_party_escrow(
+                &ledger,
+                new_listing_id(),
+                &buyer,
+                vec![(creator.clone(), 667), (platform.clone(), 333)],
+                payment,
+                86400,
+            )
+            .unwrap();
+        manager
+            .raise_dispute(escrow_id, &platform, DisputeReason::BuyerUnresponsive, String::new())
+            .unwrap();
+        manager
+            .resolve_dispute(&ledger, escrow_id, DisputeResolution::PartialRefund(501))
+            .unwrap();
+
+        // 499 split 667:333, with the rounding dust to the first recipient
+        assert_eq!(ledger.balance(&buyer), 501);
+        assert_eq!(ledger.balance(&creator), 333);
+        assert_eq!(ledger.balance(&platform), 166);
+        assert_eq!(ledger.balance(manager.account()), 0);
+    }
+
+    #[test]
+    fn test_failed_payout_changes_nothing() {
+        let ledger = MemoryLedger::default();
+        let manager = EscrowManager::new();
+        let buyer = UserId::new();
+        let creator = UserId::new();
+        let platform = UserId::new();
+
+        let payment = deposit(&ledger, &manager, &buyer, 1000);
+        let escrow_id = manager
+            .create_multi_party_escrow(
+                &ledger,
+                new_listing_id(),
+                &buyer,
+                vec![(creator.clone(), 700), (platform.clone(), 300)],
+                payment,
+                86400,
+            )
+            .unwrap();
+        manager.mark_awaiting_release(escrow_id).unwrap();
+
+        // Drain the escrow account behind the manager's back
+        ledger.pay(manager.account(), &UserId::new(), 400);
+
+        assert!(matches!(
+            manager.release_funds(&ledger, escrow_id, &buyer),
+            Err(MarketplaceError::Ledger(_))
+        ));
+        assert_eq!(manager.get_escrow(escrow_id).unwrap().state, EscrowState::AwaitingRelease);
+        assert_eq!(ledger.balance(&creator), 0);
+        assert_eq!(ledger.balance(&platform), 0);
+    }
+
+    #[test]
+    fn test_state_survives_serialization() {
+        let ledger = MemoryLedger::default();
+        let manager = EscrowManager::new();
+        let escrow_id = two_party(&ledger, &manager, &UserId::new(), &UserId::new(), 1000, 86400);
+
+        let restored: EscrowManager =
+            serde_json::from_str(&serde_json::to_string(&manager).unwrap()).unwrap();
+        assert_eq!(restored.account(), manager.account());
+        assert_eq!(restored.get_escrow(escrow_id).unwrap().funding_tx, manager.get_escrow(escrow_id).unwrap().funding_tx);
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        TwoParty { amount: u64, expires: bool },
+        MultiParty { amounts: Vec<u64>, expires: bool },
+        Complete(usize),
+        Release(usize),
+        Dispute(usize),
+        Resolve(usize, u8, u64),
+        Expire,
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (0..10_000u64, any::<bool>()).prop_map(|(amount, expires)| Op::TwoParty { amount, expires }),
+            (prop::collection::vec(0..5_000u64, 1..4), any::<bool>())
+                .prop_map(|(amounts, expires)| Op::MultiParty { amounts, expires }),
+            any::<usize>().prop_map(Op::Complete),
+            any::<usize>().prop_map(Op::Release),
+            any::<usize>().prop_map(Op::Dispute),
+            (any::<usize>(), 0..3u8, 0..12_000u64).prop_map(|(i, kind, refund)| Op::Resolve(i, kind, refund)),
+            Just(Op::Expire),
+        ]
+    }
+
+    proptest! {
+        /// No sequence of escrow transitions creates or destroys tokens, and
+        /// the escrow account always holds exactly what open escrows owe
+        #[test]
+        fn prop_funds_are_conserved(ops in prop::collection::vec(op(), 1..40)) {
+            let ledger = MemoryLedger::default();
+            let manager = EscrowManager::new();
+            let buyer = UserId::new();
+            let sellers: Vec<UserId> = (0..3).map(|_| UserId::new()).collect();
+            let supply = 1_000_000;
+            ledger.fund(&buyer, supply);
+            let mut ids: Vec<Uuid> = Vec::new();
+            let pick = |ids: &[Uuid], i: usize| ids.get(i % ids.len().max(1)).copied();
+
+            for op in ops {
+                match op {
+                    Op::TwoParty { amount, expires } => {
+                        let payment = ledger.pay(&buyer, manager.account(), amount);
+                        let lock = if expires { 0 } else { 86400 };
+                        ids.push(manager
+                            .create_two_party_escrow(&ledger, new_listing_id(), &buyer, &sellers[0], payment, lock)
+                            .unwrap());
+                    }
+                    Op::MultiParty { amounts, expires } => {
+                        let payment = ledger.pay(&buyer, manager.account(), amounts.iter().sum());
+                        let recipients = sellers.iter().cloned().zip(amounts).collect();
+                        let lock = if expires { 0 } else { 86400 };
+                        ids.push(manager
+                            .create_multi_party_escrow(&ledger, new_listing_id(), &buyer, recipients, payment, lock)
+                            .unwrap());
+                    }
+                    Op::Complete(i) => if let Some(id) = pick(&ids, i) {
+                        let _ = manager.mark_awaiting_release(id);
+                    },
+                    Op::Release(i) => if let Some(id) = pick(&ids, i) {
+                        let _ = manager.release_funds(&ledger, id, &buyer);
+                    },
+                    Op::Dispute(i) => if let Some(id) = pick(&ids, i) {
+                        let _ = manager.raise_dispute(id, &buyer, DisputeReason::QualityIssue, String::new());
+                    },
+                    Op::Resolve(i, kind, refund) => if let Some(id) = pick(&ids, i) {
+                        let resolution = match kind {
+                            0 => DisputeResolution::ReleaseFunds,
+                            1 => DisputeResolution::RefundFull,
+                            _ => DisputeResolution::PartialRefund(refund),
+                        };
+                        let _ = manager.resolve_dispute(&ledger, id, resolution);
+                    },
+                    Op::Expire => {
+                        manager.process_expirations(&ledger);
+                    }
+                }
+
+                prop_assert_eq!(ledger.total(), supply);
+                prop_assert_eq!(ledger.balance(manager.account()), manager.locked_total());
+            }
+        }
+    }
+}
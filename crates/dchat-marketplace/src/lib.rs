@@ -4,8 +4,12 @@
 //! - Digital goods (sticker packs, themes, bots)
 //! - NFT integration and trading
 //! - Creator economy (tips, subscriptions)
-//! - Listing management and discovery
+//! - Listing management, browse and full-text search
+//! - Secondary market for reselling NFTs, memberships, bots, channels and artwork
+//! - Royalty, revenue-split and license enforcement on every transfer, with a
+//!   provenance chain per asset
 //! - Escrow system with dispute resolution
+//! - Recurring subscription billing
 
 use chrono::{DateTime, Utc};
 use dchat_core::{types::UserId, Error, Result};
@@ -14,6 +18,12 @@ use std::fmt;
 use uuid::Uuid;
 
 pub mod escrow;
+pub mod secondary;
+pub mod settlement;
+pub mod subscriptions;
+
+use secondary::{Order, OrderOutcome, OrderSide, ResaleAsset, Trade};
+use settlement::{ProvenanceRecord, Quote, RevenueSplit, TransferKind};
 
 // Re-export types for escrow module
 pub mod types {
@@ -27,6 +37,18 @@ pub mod types {
         EscrowNotFound,
         InvalidEscrowState,
         Unauthorized,
+        /// Payment is missing, not from the buyer into the escrow account,
+        /// already funds another escrow, or does not match the amount owed
+        InvalidPayment,
+        RefundExceedsEscrow,
+        /// The ledger rejected a transfer
+        Ledger(String),
+        SubscriptionNotFound,
+        InvalidSubscriptionState,
+        InsufficientCredit,
+        /// Revenue split shares are empty, zero, repeated or do not sum to
+        /// 10,000 basis points
+        InvalidRevenueSplit,
     }
 
     impl fmt::Display for MarketplaceError {
@@ -35,6 +57,13 @@ pub mod types {
                 MarketplaceError::EscrowNotFound => write!(f, "Escrow not found"),
                 MarketplaceError::InvalidEscrowState => write!(f, "Invalid escrow state"),
                 MarketplaceError::Unauthorized => write!(f, "Unauthorized operation"),
+                MarketplaceError::InvalidPayment => write!(f, "Invalid escrow payment"),
+                MarketplaceError::RefundExceedsEscrow => write!(f, "Refund exceeds escrowed amount"),
+                MarketplaceError::Ledger(e) => write!(f, "Ledger error: {}", e),
+                MarketplaceError::SubscriptionNotFound => write!(f, "Subscription not found"),
+                MarketplaceError::InvalidSubscriptionState => write!(f, "Invalid subscription state"),
+                MarketplaceError::InsufficientCredit => write!(f, "Insufficient subscription credit"),
+                MarketplaceError::InvalidRevenueSplit => write!(f, "Invalid revenue split"),
             }
         }
     }
@@ -81,6 +110,10 @@ pub enum OnChainStorageType {
 }
 
 /// Represents a transferable asset
+///
+/// `transfer_ownership` only records the new owner; assets change hands
+/// through `MarketplaceManager`, which checks `check_transfer` and settles
+/// royalties and fees first.
 pub trait TransferableAsset {
     /// Get unique asset identifier
     fn asset_id(&self) -> String;
@@ -90,6 +123,10 @@ pub trait TransferableAsset {
     fn transfer_ownership(&mut self, new_owner: UserId) -> Result<()>;
     /// Get on-chain address or identifier
     fn on_chain_address(&self) -> Option<String>;
+    /// Check that the asset may change hands this way
+    fn check_transfer(&self, _kind: TransferKind) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Pricing model for digital goods
@@ -105,6 +142,111 @@ pub enum PricingModel {
     PayWhatYouWant { minimum: u64 },
 }
 
+impl PricingModel {
+    /// Lowest amount the listing sells for
+    pub fn base_price(&self) -> u64 {
+        match self {
+            PricingModel::OneTime { price } => *price,
+            PricingModel::Subscription { price_per_month } => *price_per_month,
+            PricingModel::Free => 0,
+            PricingModel::PayWhatYouWant { minimum } => *minimum,
+        }
+    }
+}
+
+/// Listings per page when a query does not say
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+
+/// Largest page a query may ask for
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// Filters and paging for browsing listings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListingQuery {
+    pub good_type: Option<DigitalGoodType>,
+    /// Bounds on `PricingModel::base_price`
+    pub min_price: Option<u64>,
+    pub max_price: Option<u64>,
+    pub min_rating: Option<f32>,
+    pub verified_only: bool,
+    /// Words that must all appear in the title or description; results are
+    /// ranked by relevance instead of recency
+    pub text: Option<String>,
+    /// Zero-based page number
+    pub page: usize,
+    /// Page size; 0 means `DEFAULT_PAGE_SIZE`
+    pub per_page: usize,
+}
+
+/// One page of browse results
+#[derive(Debug, Clone)]
+pub struct ListingPage<'a> {
+    pub listings: Vec<&'a Listing>,
+    /// Listings matching the query across all pages
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+}
+
+impl ListingPage<'_> {
+    /// Number of pages the matches span
+    pub fn page_count(&self) -> usize {
+        self.total.div_ceil(self.per_page)
+    }
+}
+
+/// Lowercase alphanumeric words of `text`
+fn search_terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Relevance of a listing to search terms, or `None` if a term is missing
+///
+/// A term matches a word it is a prefix of; title matches count double.
+fn relevance(listing: &Listing, terms: &[String]) -> Option<u32> {
+    let title = search_terms(&listing.title);
+    let description = search_terms(&listing.description);
+    terms.iter().try_fold(0, |score, term| {
+        let hits = |words: &[String]| words.iter().filter(|w| w.starts_with(term.as_str())).count() as u32;
+        match 2 * hits(&title) + hits(&description) {
+            0 => None,
+            term_score => Some(score + term_score),
+        }
+    })
+}
+
+/// Transferable asset a listing sells, if any
+fn listing_asset(listing: &Listing) -> Option<ResaleAsset> {
+    match listing.good_type {
+        DigitalGoodType::Bot => listing.bot_id.map(ResaleAsset::Bot),
+        DigitalGoodType::Channel => listing.channel_id.map(ResaleAsset::Channel),
+        DigitalGoodType::Nft => listing.nft_token_id.clone().map(ResaleAsset::Nft),
+        _ => None,
+    }
+}
+
+/// How a released escrow divided a listing sale
+///
+/// A multi-party escrow pays the seller first and the royalties after.
+fn escrow_quote(escrow: &escrow::Escrow) -> Quote {
+    let (seller_proceeds, royalties) = match &escrow.escrow_type {
+        escrow::EscrowType::TwoParty { .. } => (escrow.amount, Vec::new()),
+        escrow::EscrowType::MultiParty { recipients, .. } => match recipients.split_first() {
+            Some(((_, seller_proceeds), royalties)) => (*seller_proceeds, royalties.to_vec()),
+            None => (0, Vec::new()),
+        },
+    };
+    Quote {
+        price: escrow.amount,
+        royalties,
+        platform_fee: 0,
+        seller_proceeds,
+    }
+}
+
 /// A digital good listing in the marketplace
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Listing {
@@ -146,6 +288,7 @@ pub struct Purchase {
     pub listing_id: Uuid,
     pub amount_paid: u64,
     pub purchased_at: DateTime<Utc>,
+    /// Ledger transaction that paid into escrow
     pub transaction_hash: String,
 }
 
@@ -223,6 +366,8 @@ pub struct ImageArtwork {
     pub title: String,
     pub description: String,
     pub creator: UserId,
+    /// Holder of the rights the license grants
+    pub owner: UserId,
     pub content_hash: String,
     pub width: u32,
     pub height: u32,
@@ -245,6 +390,8 @@ pub enum LicenseType {
     CcByNc,
     /// Public domain
     PublicDomain,
+    /// Personal-use license bound to its holder
+    Personal,
 }
 
 /// Channel membership pass
@@ -272,7 +419,128 @@ pub enum MembershipAccessLevel {
     Vip,
 }
 
+impl TransferableAsset for NftMetadata {
+    fn asset_id(&self) -> String {
+        ResaleAsset::Nft(self.token_id.clone()).to_string()
+    }
+
+    fn current_owner(&self) -> UserId {
+        self.owner.clone()
+    }
+
+    fn transfer_ownership(&mut self, new_owner: UserId) -> Result<()> {
+        self.owner = new_owner;
+        Ok(())
+    }
+
+    fn on_chain_address(&self) -> Option<String> {
+        None
+    }
+}
+
+impl TransferableAsset for BotOwnership {
+    fn asset_id(&self) -> String {
+        ResaleAsset::Bot(self.bot_id).to_string()
+    }
+
+    fn current_owner(&self) -> UserId {
+        self.current_owner.clone()
+    }
+
+    fn transfer_ownership(&mut self, new_owner: UserId) -> Result<()> {
+        let old_owner = std::mem::replace(&mut self.current_owner, new_owner);
+        self.previous_owners.push((old_owner, Utc::now()));
+        self.transfer_count += 1;
+        Ok(())
+    }
+
+    fn on_chain_address(&self) -> Option<String> {
+        Some(self.on_chain_address.clone())
+    }
+}
+
+impl TransferableAsset for ChannelOwnership {
+    fn asset_id(&self) -> String {
+        ResaleAsset::Channel(self.channel_id).to_string()
+    }
+
+    fn current_owner(&self) -> UserId {
+        self.current_owner.clone()
+    }
+
+    fn transfer_ownership(&mut self, new_owner: UserId) -> Result<()> {
+        let old_owner = std::mem::replace(&mut self.current_owner, new_owner);
+        self.previous_owners.push((old_owner, Utc::now()));
+        self.transfer_count += 1;
+        Ok(())
+    }
+
+    fn on_chain_address(&self) -> Option<String> {
+        Some(self.on_chain_address.clone())
+    }
+}
+
+impl TransferableAsset for ImageArtwork {
+    fn asset_id(&self) -> String {
+        ResaleAsset::Image(self.image_id).to_string()
+    }
+
+    fn current_owner(&self) -> UserId {
+        self.owner.clone()
+    }
+
+    fn transfer_ownership(&mut self, new_owner: UserId) -> Result<()> {
+        self.owner = new_owner;
+        Ok(())
+    }
+
+    fn on_chain_address(&self) -> Option<String> {
+        None
+    }
+
+    fn check_transfer(&self, kind: TransferKind) -> Result<()> {
+        match self.license_type {
+            LicenseType::Personal => Err(Error::validation("Personal-use licenses are not transferable")),
+            LicenseType::PublicDomain => Err(Error::validation("Public-domain works have no rights to transfer")),
+            LicenseType::CcByNc if kind.is_commercial() => {
+                Err(Error::validation("Non-commercial works can only be given away"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl TransferableAsset for ChannelMembership {
+    fn asset_id(&self) -> String {
+        ResaleAsset::Membership(self.membership_id).to_string()
+    }
+
+    fn current_owner(&self) -> UserId {
+        self.holder.clone()
+    }
+
+    fn transfer_ownership(&mut self, new_owner: UserId) -> Result<()> {
+        self.holder = new_owner;
+        Ok(())
+    }
+
+    fn on_chain_address(&self) -> Option<String> {
+        None
+    }
+
+    fn check_transfer(&self, _kind: TransferKind) -> Result<()> {
+        if !self.is_transferable {
+            return Err(Error::validation("Membership is not transferable"));
+        }
+        if self.expires_at <= Utc::now() {
+            return Err(Error::validation("Membership has expired"));
+        }
+        Ok(())
+    }
+}
+
 /// Marketplace manager for handling listings and purchases
+#[derive(Serialize, Deserialize)]
 pub struct MarketplaceManager {
     listings: Vec<Listing>,
     purchases: Vec<Purchase>,
@@ -283,6 +551,9 @@ pub struct MarketplaceManager {
     images: Vec<ImageArtwork>,
     memberships: Vec<ChannelMembership>,
     pub escrow: escrow::EscrowManager,
+    pub subscriptions: subscriptions::SubscriptionManager,
+    pub market: secondary::OrderBook,
+    pub settlement: settlement::Settlement,
 }
 
 impl MarketplaceManager {
@@ -298,6 +569,9 @@ impl MarketplaceManager {
             images: Vec::new(),
             memberships: Vec::new(),
             escrow: escrow::EscrowManager::new(),
+            subscriptions: subscriptions::SubscriptionManager::new(),
+            market: secondary::OrderBook::new(),
+            settlement: settlement::Settlement::new(),
         }
     }
 
@@ -352,18 +626,21 @@ impl MarketplaceManager {
     }
 
     /// Purchase a digital good with automatic escrow creation
+    ///
+    /// `payment_tx` is the buyer's ledger transfer into the escrow account;
+    /// the amount paid is read from the ledger, not taken from the caller.
+    /// A listed NFT, bot or channel must be the seller's to sell, and its
+    /// royalty or revenue split is paid out of the escrow with the seller.
     pub fn purchase(
         &mut self,
+        ledger: &dyn escrow::EscrowLedger,
         buyer: UserId,
         listing_id: Uuid,
-        amount_paid: u64,
-        transaction_hash: String,
+        payment_tx: Uuid,
     ) -> Result<Uuid> {
         // Find the listing
         let listing = self
-            .listings
-            .iter_mut()
-            .find(|l| l.id == listing_id)
+            .get_listing(listing_id)
             .ok_or_else(|| Error::validation("Listing not found"))?;
 
         // Check if already in escrow
@@ -371,6 +648,11 @@ impl MarketplaceManager {
             return Err(Error::validation("Item currently in escrow for another transaction"));
         }
 
+        let amount_paid = ledger
+            .payment(&payment_tx)?
+            .ok_or_else(|| Error::NotFound(format!("Payment not found: {}", payment_tx)))?
+            .amount;
+
         // Verify payment amount matches pricing
         match &listing.pricing {
             PricingModel::OneTime { price } => {
@@ -378,10 +660,8 @@ impl MarketplaceManager {
                     return Err(Error::validation("Insufficient payment"));
                 }
             }
-            PricingModel::Subscription { price_per_month } => {
-                if amount_paid < *price_per_month {
-                    return Err(Error::validation("Insufficient payment"));
-                }
+            PricingModel::Subscription { .. } => {
+                return Err(Error::validation("Subscription listings are billed through subscribe"));
             }
             PricingModel::PayWhatYouWant { minimum } => {
                 if amount_paid < *minimum {
@@ -392,17 +672,38 @@ impl MarketplaceManager {
         }
 
         let seller = listing.creator.clone();
+        let quote = match listing_asset(listing) {
+            Some(asset) => {
+                self.check_transfer(&asset, &seller, TransferKind::Sale)?;
+                self.quote(TransferKind::Sale, &asset, &seller, amount_paid)
+            }
+            None => Quote::gift(),
+        };
         
         // Create escrow for the transaction (30 days lock)
-        let escrow_id = self.escrow.create_two_party_escrow(
-            listing_id,
-            &buyer,
-            &seller,
-            amount_paid,
-            30 * 24 * 60 * 60, // 30 days in seconds
-        ).map_err(|e| Error::validation(&format!("Escrow creation failed: {:?}", e)))?;
+        let lock_duration_secs = 30 * 24 * 60 * 60; // 30 days in seconds
+        let escrow_id = if quote.royalties.is_empty() {
+            self.escrow.create_two_party_escrow(
+                ledger,
+                listing_id,
+                &buyer,
+                &seller,
+                payment_tx,
+                lock_duration_secs,
+            )
+        } else {
+            let mut recipients = vec![(seller.clone(), quote.seller_proceeds)];
+            recipients.extend(quote.royalties);
+            self.escrow.create_multi_party_escrow(ledger, listing_id, &buyer, recipients, payment_tx, lock_duration_secs)
+        }
+        .map_err(|e| Error::validation(format!("Escrow creation failed: {:?}", e)))?;
 
         // Mark listing as in escrow
+        let listing = self
+            .listings
+            .iter_mut()
+            .find(|l| l.id == listing_id)
+            .expect("listing found above");
         listing.in_escrow = true;
         listing.escrow_id = Some(escrow_id);
 
@@ -413,7 +714,7 @@ impl MarketplaceManager {
             listing_id,
             amount_paid,
             purchased_at: Utc::now(),
-            transaction_hash,
+            transaction_hash: payment_tx.to_string(),
         };
 
         let purchase_id = purchase.id;
@@ -423,11 +724,14 @@ impl MarketplaceManager {
         Ok(purchase_id)
     }
 
-    /// Complete purchase and transfer asset ownership (called after escrow release)
+    /// Complete purchase and transfer asset ownership
+    ///
+    /// Only once the purchase's escrow has paid the seller. The transfer is
+    /// recorded in the asset's provenance with the escrow's payouts.
     pub fn complete_purchase_transfer(
         &mut self,
         purchase_id: Uuid,
-        _escrow_id: Uuid,
+        escrow_id: Uuid,
     ) -> Result<()> {
         // Find the purchase and extract needed data
         let (buyer, listing_id) = {
@@ -439,8 +743,19 @@ impl MarketplaceManager {
             (purchase.buyer.clone(), purchase.listing_id)
         };
 
+        let escrow = self
+            .escrow
+            .get_escrow(escrow_id)
+            .ok_or_else(|| Error::NotFound(format!("Escrow not found: {}", escrow_id)))?;
+        if escrow.listing_id != listing_id || escrow.buyer() != &buyer {
+            return Err(Error::validation("Escrow does not belong to this purchase"));
+        }
+        if escrow.state != escrow::EscrowState::Released {
+            return Err(Error::validation("Escrow has not been released to the seller"));
+        }
+
         // Extract listing data before mutable borrows
-        let (good_type, bot_id, channel_id, nft_token_id, membership_duration) = {
+        let (seller, asset, good_type, channel_id, membership_duration) = {
             let listing = self
                 .listings
                 .iter()
@@ -448,40 +763,25 @@ impl MarketplaceManager {
                 .ok_or_else(|| Error::validation("Listing not found"))?;
             
             (
+                listing.creator.clone(),
+                listing_asset(listing),
                 listing.good_type,
-                listing.bot_id,
                 listing.channel_id,
-                listing.nft_token_id.clone(),
                 listing.membership_duration_days,
             )
         };
 
         // Transfer ownership based on asset type (now we can mutably borrow self)
-        match good_type {
-            DigitalGoodType::Bot => {
-                if let Some(bot_id) = bot_id {
-                    self.transfer_bot_ownership(bot_id, buyer)?;
-                }
-            }
-            DigitalGoodType::Channel => {
-                if let Some(channel_id) = channel_id {
-                    self.transfer_channel_ownership(channel_id, buyer)?;
-                }
+        if let Some(asset) = asset {
+            let quote = escrow_quote(&escrow);
+            self.settle_transfer(&asset, &seller, buyer, TransferKind::Sale, quote, |_| Ok(escrow.payout_txs.clone()))?;
+        } else if good_type == DigitalGoodType::Membership {
+            if let Some(channel_id) = channel_id {
+                let duration_days = membership_duration.unwrap_or(30);
+                self.grant_membership(channel_id, buyer, duration_days)?;
             }
-            DigitalGoodType::Nft => {
-                if let Some(ref token_id) = nft_token_id {
-                    self.transfer_nft(token_id, buyer)?;
-                }
-            }
-            DigitalGoodType::Membership => {
-                if let Some(channel_id) = channel_id {
-                    let duration_days = membership_duration.unwrap_or(30);
-                    self.grant_membership(channel_id, buyer, duration_days)?;
-                }
-            }
-            // Other types don't require ownership transfer (content delivery only)
-            _ => {}
         }
+        // Other types don't require ownership transfer (content delivery only)
 
         // Clear escrow status
         let listing = self
@@ -517,6 +817,45 @@ impl MarketplaceManager {
             .collect()
     }
 
+    /// Browse listings with filters, search and paging
+    pub fn browse(&self, query: &ListingQuery) -> ListingPage<'_> {
+        let terms = query.text.as_deref().map(search_terms).unwrap_or_default();
+
+        let mut matches: Vec<(u32, &Listing)> = self
+            .listings
+            .iter()
+            .filter(|l| query.good_type.is_none_or(|t| l.good_type == t))
+            .filter(|l| query.min_price.is_none_or(|min| l.pricing.base_price() >= min))
+            .filter(|l| query.max_price.is_none_or(|max| l.pricing.base_price() <= max))
+            .filter(|l| query.min_rating.is_none_or(|min| l.rating >= min))
+            .filter(|l| !query.verified_only || l.is_verified)
+            .filter_map(|l| if terms.is_empty() { Some((0, l)) } else { relevance(l, &terms).map(|score| (score, l)) })
+            .collect();
+
+        // Most relevant first, then most downloaded, then newest
+        matches.sort_by(|(a_score, a), (b_score, b)| {
+            b_score
+                .cmp(a_score)
+                .then(b.downloads.cmp(&a.downloads))
+                .then(b.created_at.cmp(&a.created_at))
+                .then(a.id.cmp(&b.id))
+        });
+
+        let per_page = match query.per_page {
+            0 => DEFAULT_PAGE_SIZE,
+            n => n.min(MAX_PAGE_SIZE),
+        };
+        let total = matches.len();
+        let listings = matches
+            .into_iter()
+            .skip(query.page.saturating_mul(per_page))
+            .take(per_page)
+            .map(|(_, l)| l)
+            .collect();
+
+        ListingPage { listings, total, page: query.page, per_page }
+    }
+
     /// Get creator statistics
     pub fn get_creator_stats(&self, creator: &UserId) -> CreatorStats {
         let creator_listings: Vec<_> = self.get_listings_by_creator(creator);
@@ -580,16 +919,9 @@ impl MarketplaceManager {
         Ok(())
     }
 
-    /// Transfer NFT ownership
+    /// Give an NFT to a new owner
     pub fn transfer_nft(&mut self, token_id: &str, new_owner: UserId) -> Result<()> {
-        let nft = self
-            .nft_registry
-            .iter_mut()
-            .find(|n| n.token_id == token_id)
-            .ok_or_else(|| Error::validation("NFT not found"))?;
-
-        nft.owner = new_owner;
-        Ok(())
+        self.gift(&ResaleAsset::Nft(token_id.to_string()), new_owner)
     }
 
     /// Get NFT by token ID
@@ -634,20 +966,9 @@ impl MarketplaceManager {
         Ok(on_chain_address)
     }
 
-    /// Transfer bot ownership
+    /// Give a bot to a new owner
     pub fn transfer_bot_ownership(&mut self, bot_id: Uuid, new_owner: UserId) -> Result<()> {
-        let bot = self
-            .bot_ownership
-            .iter_mut()
-            .find(|b| b.bot_id == bot_id)
-            .ok_or_else(|| Error::validation("Bot ownership not found"))?;
-
-        let old_owner = bot.current_owner.clone();
-        bot.previous_owners.push((old_owner, Utc::now()));
-        bot.current_owner = new_owner;
-        bot.transfer_count += 1;
-
-        Ok(())
+        self.gift(&ResaleAsset::Bot(bot_id), new_owner)
     }
 
     /// Get bot ownership info
@@ -694,20 +1015,9 @@ impl MarketplaceManager {
         Ok(on_chain_address)
     }
 
-    /// Transfer channel ownership
+    /// Give a channel to a new owner
     pub fn transfer_channel_ownership(&mut self, channel_id: Uuid, new_owner: UserId) -> Result<()> {
-        let channel = self
-            .channel_ownership
-            .iter_mut()
-            .find(|c| c.channel_id == channel_id)
-            .ok_or_else(|| Error::validation("Channel ownership not found"))?;
-
-        let old_owner = channel.current_owner.clone();
-        channel.previous_owners.push((old_owner, Utc::now()));
-        channel.current_owner = new_owner;
-        channel.transfer_count += 1;
-
-        Ok(())
+        self.gift(&ResaleAsset::Channel(channel_id), new_owner)
     }
 
     /// Get channel ownership info
@@ -783,6 +1093,7 @@ impl MarketplaceManager {
             image_id: Uuid::new_v4(),
             title,
             description,
+            owner: creator.clone(),
             creator,
             content_hash,
             width,
@@ -809,6 +1120,11 @@ impl MarketplaceManager {
             .collect()
     }
 
+    /// Give the rights to an image to a new owner, as its license allows
+    pub fn transfer_image(&mut self, image_id: Uuid, new_owner: UserId) -> Result<()> {
+        self.gift(&ResaleAsset::Image(image_id), new_owner)
+    }
+
     // ========== Channel Membership Methods ==========
 
     /// Grant channel membership
@@ -836,24 +1152,9 @@ impl MarketplaceManager {
         Ok(membership_id)
     }
 
-    /// Transfer membership to another user
+    /// Give a membership to another user
     pub fn transfer_membership(&mut self, membership_id: Uuid, new_holder: UserId) -> Result<()> {
-        let membership = self
-            .memberships
-            .iter_mut()
-            .find(|m| m.membership_id == membership_id)
-            .ok_or_else(|| Error::validation("Membership not found"))?;
-
-        if !membership.is_transferable {
-            return Err(Error::validation("Membership is not transferable"));
-        }
-
-        if membership.expires_at < Utc::now() {
-            return Err(Error::validation("Membership has expired"));
-        }
-
-        membership.holder = new_holder;
-        Ok(())
+        self.gift(&ResaleAsset::Membership(membership_id), new_holder)
     }
 
     /// Get membership by ID
@@ -884,6 +1185,387 @@ impl MarketplaceManager {
             .any(|m| m.channel_id == channel_id && &m.holder == holder && m.expires_at > Utc::now())
     }
 
+    // ========== Settlement Methods ==========
+
+    /// A transferable asset by ID
+    fn asset(&self, asset: &ResaleAsset) -> Option<&dyn TransferableAsset> {
+        match asset {
+            ResaleAsset::Nft(token_id) => self.get_nft(token_id).map(|a| a as &dyn TransferableAsset),
+            ResaleAsset::Membership(id) => self.get_membership(*id).map(|a| a as &dyn TransferableAsset),
+            ResaleAsset::Bot(id) => self.get_bot_ownership(*id).map(|a| a as &dyn TransferableAsset),
+            ResaleAsset::Channel(id) => self.get_channel_ownership(*id).map(|a| a as &dyn TransferableAsset),
+            ResaleAsset::Image(id) => self.get_image(*id).map(|a| a as &dyn TransferableAsset),
+        }
+    }
+
+    /// A transferable asset by ID, for changing its owner
+    fn asset_mut(&mut self, asset: &ResaleAsset) -> Option<&mut dyn TransferableAsset> {
+        match asset {
+            ResaleAsset::Nft(token_id) => self
+                .nft_registry
+                .iter_mut()
+                .find(|n| &n.token_id == token_id)
+                .map(|a| a as &mut dyn TransferableAsset),
+            ResaleAsset::Membership(id) => self
+                .memberships
+                .iter_mut()
+                .find(|m| m.membership_id == *id)
+                .map(|a| a as &mut dyn TransferableAsset),
+            ResaleAsset::Bot(id) => self
+                .bot_ownership
+                .iter_mut()
+                .find(|b| b.bot_id == *id)
+                .map(|a| a as &mut dyn TransferableAsset),
+            ResaleAsset::Channel(id) => self
+                .channel_ownership
+                .iter_mut()
+                .find(|c| c.channel_id == *id)
+                .map(|a| a as &mut dyn TransferableAsset),
+            ResaleAsset::Image(id) => self
+                .images
+                .iter_mut()
+                .find(|i| i.image_id == *id)
+                .map(|a| a as &mut dyn TransferableAsset),
+        }
+    }
+
+    /// Current holder of an asset
+    pub fn asset_holder(&self, asset: &ResaleAsset) -> Option<UserId> {
+        self.asset(asset).map(|a| a.current_owner())
+    }
+
+    /// Creator owed the royalty when an asset changes hands
+    ///
+    /// A bot's or channel's creator is its first owner. A membership's
+    /// creator is the channel's owner, or failing that the creator of the
+    /// channel's membership listing.
+    pub fn asset_creator(&self, asset: &ResaleAsset) -> Option<UserId> {
+        let first_owner = |previous: &[(UserId, DateTime<Utc>)], current: &UserId| {
+            previous.first().map(|(owner, _)| owner.clone()).unwrap_or_else(|| current.clone())
+        };
+        match asset {
+            ResaleAsset::Nft(token_id) => self.get_nft(token_id).map(|n| n.creator.clone()),
+            ResaleAsset::Image(id) => self.get_image(*id).map(|i| i.creator.clone()),
+            ResaleAsset::Bot(id) => self
+                .get_bot_ownership(*id)
+                .map(|b| first_owner(&b.previous_owners, &b.current_owner)),
+            ResaleAsset::Channel(id) => self
+                .get_channel_ownership(*id)
+                .map(|c| first_owner(&c.previous_owners, &c.current_owner)),
+            ResaleAsset::Membership(id) => {
+                let channel_id = self.get_membership(*id)?.channel_id;
+                self.get_channel_ownership(channel_id)
+                    .map(|c| c.current_owner.clone())
+                    .or_else(|| {
+                        self.listings
+                            .iter()
+                            .find(|l| l.good_type == DigitalGoodType::Membership && l.channel_id == Some(channel_id))
+                            .map(|l| l.creator.clone())
+                    })
+            }
+        }
+    }
+
+    /// Divide a sale of `asset` by `seller` at `price`
+    pub fn quote(&self, kind: TransferKind, asset: &ResaleAsset, seller: &UserId, price: u64) -> Quote {
+        let creator = self.asset_creator(asset);
+        self.settlement.quote(kind, asset, seller, creator.as_ref(), price)
+    }
+
+    /// Register how the creator's revenue from `asset` is divided
+    pub fn set_revenue_split(&mut self, creator: &UserId, asset: ResaleAsset, split: RevenueSplit) -> Result<()> {
+        let asset_creator = self
+            .asset_creator(&asset)
+            .ok_or_else(|| Error::NotFound(format!("Asset not found: {}", asset)))?;
+        if &asset_creator != creator {
+            return Err(Error::PermissionDenied(format!("Only the creator of {} can split its revenue", asset)));
+        }
+        self.settlement.set_split(asset, split);
+        Ok(())
+    }
+
+    /// Check that `from` holds `asset` and its rules allow this transfer
+    fn check_transfer(&self, asset: &ResaleAsset, from: &UserId, kind: TransferKind) -> Result<()> {
+        let asset_ref = self
+            .asset(asset)
+            .ok_or_else(|| Error::NotFound(format!("Asset not found: {}", asset)))?;
+        if &asset_ref.current_owner() != from {
+            return Err(Error::PermissionDenied(format!("{} does not hold {}", from, asset)));
+        }
+        asset_ref.check_transfer(kind)
+    }
+
+    /// Move an asset from `from` to `to`, settling `quote`
+    ///
+    /// Every change of hands goes through here. `pay` moves the funds the
+    /// quote calls for and returns the ledger transactions; the asset only
+    /// moves once it succeeds, and the transfer is then appended to the
+    /// asset's provenance chain.
+    fn settle_transfer(
+        &mut self,
+        asset: &ResaleAsset,
+        from: &UserId,
+        to: UserId,
+        kind: TransferKind,
+        quote: Quote,
+        pay: impl FnOnce(&Quote) -> Result<Vec<Uuid>>,
+    ) -> Result<ProvenanceRecord> {
+        self.check_transfer(asset, from, kind)?;
+        if from == &to {
+            return Err(Error::validation("Cannot transfer an asset to its holder"));
+        }
+
+        let payout_txs = pay(&quote)?;
+        self.asset_mut(asset)
+            .expect("checked asset exists")
+            .transfer_ownership(to.clone())?;
+        Ok(self.settlement.record(asset.clone(), from.clone(), to, kind, &quote, payout_txs))
+    }
+
+    /// Give an asset from its current holder to `to`
+    fn gift(&mut self, asset: &ResaleAsset, to: UserId) -> Result<()> {
+        let holder = self
+            .asset_holder(asset)
+            .ok_or_else(|| Error::NotFound(format!("Asset not found: {}", asset)))?;
+        self.settle_transfer(asset, &holder, to, TransferKind::Gift, Quote::gift(), |_| Ok(Vec::new()))?;
+        Ok(())
+    }
+
+    // ========== Secondary Market Methods ==========
+
+    /// Offer an asset for resale at `price`
+    ///
+    /// Fills immediately against the best bid at or above the price, at
+    /// that bid's price.
+    pub fn place_ask(
+        &mut self,
+        ledger: &dyn escrow::EscrowLedger,
+        seller: UserId,
+        asset: ResaleAsset,
+        price: u64,
+    ) -> Result<OrderOutcome> {
+        self.check_transfer(&asset, &seller, TransferKind::Resale)?;
+        if self.market.ask_for(&asset).is_some() {
+            return Err(Error::AlreadyExists(format!("{} is already listed for resale", asset)));
+        }
+
+        let ask = Order {
+            id: Uuid::new_v4(),
+            asset,
+            side: OrderSide::Ask,
+            trader: seller,
+            price,
+            placed_at: Utc::now(),
+            funding_tx: None,
+        };
+
+        let crossing = self.market.best_bid(&ask.asset, price).cloned();
+        match crossing {
+            Some(bid) => {
+                let trade = self.settle_trade(ledger, &ask, &bid, bid.price)?;
+                self.market.remove(bid.id);
+                Ok(OrderOutcome { order_id: ask.id, trade: Some(trade) })
+            }
+            None => {
+                let order_id = ask.id;
+                self.market.insert(ask);
+                Ok(OrderOutcome { order_id, trade: None })
+            }
+        }
+    }
+
+    /// Bid for an asset with a payment into the market account
+    ///
+    /// The bid is for the full payment. It fills immediately if the open ask
+    /// is at or below it, at the ask's price, and the difference goes back
+    /// to the bidder.
+    pub fn place_bid(
+        &mut self,
+        ledger: &dyn escrow::EscrowLedger,
+        bidder: UserId,
+        asset: ResaleAsset,
+        funding_tx: Uuid,
+    ) -> Result<OrderOutcome> {
+        let holder = self
+            .asset_holder(&asset)
+            .ok_or_else(|| Error::NotFound(format!("Asset not found: {}", asset)))?;
+        if holder == bidder {
+            return Err(Error::validation("Cannot bid on an asset you hold"));
+        }
+        let price = self
+            .market
+            .verify_funding(ledger, &bidder, funding_tx)
+            .map_err(|e| Error::validation(format!("Bid not funded: {}", e)))?;
+
+        let bid = Order {
+            id: Uuid::new_v4(),
+            asset,
+            side: OrderSide::Bid,
+            trader: bidder,
+            price,
+            placed_at: Utc::now(),
+            funding_tx: Some(funding_tx),
+        };
+
+        // An ask left behind by an asset that changed hands can never fill
+        if let Some(ask) = self.market.ask_for(&bid.asset).cloned() {
+            if self.check_transfer(&ask.asset, &ask.trader, TransferKind::Resale).is_err() {
+                self.market.remove(ask.id);
+            } else if ask.price <= price {
+                let trade = self.settle_trade(ledger, &ask, &bid, ask.price)?;
+                self.market.remove(ask.id);
+                return Ok(OrderOutcome { order_id: bid.id, trade: Some(trade) });
+            }
+        }
+
+        let order_id = bid.id;
+        self.market.insert(bid);
+        Ok(OrderOutcome { order_id, trade: None })
+    }
+
+    /// Withdraw an open order, refunding a bid's funds
+    pub fn cancel_order(&mut self, ledger: &dyn escrow::EscrowLedger, trader: &UserId, order_id: Uuid) -> Result<()> {
+        let order = self
+            .market
+            .get_order(order_id)
+            .ok_or_else(|| Error::NotFound(format!("Order not found: {}", order_id)))?;
+        if &order.trader != trader {
+            return Err(Error::PermissionDenied("Only the trader can cancel an order".to_string()));
+        }
+
+        if order.side == OrderSide::Bid {
+            ledger.transfer_batch(self.market.account(), &[(trader.clone(), order.price)])?;
+        }
+        self.market.remove(order_id);
+        Ok(())
+    }
+
+    /// Settle a crossed ask and bid at `price`
+    ///
+    /// Pays the seller, the creator's royalty and any excess back to the
+    /// bidder, and burns the platform fee, in one ledger transfer, then
+    /// moves the asset.
+    fn settle_trade(&mut self, ledger: &dyn escrow::EscrowLedger, ask: &Order, bid: &Order, price: u64) -> Result<Trade> {
+        let creator = self.asset_creator(&ask.asset);
+        let quote = self.quote(TransferKind::Resale, &ask.asset, &ask.trader, price);
+        let market = self.market.account().clone();
+        let record = self.settle_transfer(&ask.asset, &ask.trader, bid.trader.clone(), TransferKind::Resale, quote, |quote| {
+            let mut payouts = vec![(ask.trader.clone(), quote.seller_proceeds), (bid.trader.clone(), bid.price - price)];
+            payouts.extend(quote.royalties.iter().cloned());
+            payouts.retain(|(_, amount)| *amount > 0);
+            ledger.transfer_and_burn(&market, &payouts, quote.platform_fee)
+        })?;
+
+        let trade = Trade {
+            id: Uuid::new_v4(),
+            asset: ask.asset.clone(),
+            seller: ask.trader.clone(),
+            buyer: bid.trader.clone(),
+            price,
+            creator,
+            royalty: record.royalties.iter().map(|(_, amount)| amount).sum(),
+            platform_fee: record.platform_fee,
+            ask_id: ask.id,
+            bid_id: bid.id,
+            executed_at: record.settled_at,
+            payout_txs: record.payout_txs,
+        };
+        self.market.record(trade.clone(), bid.funding_tx);
+        Ok(trade)
+    }
+
+    // ========== Subscription Methods ==========
+
+    /// Subscribe to a `PricingModel::Subscription` listing
+    ///
+    /// The first period is charged from the subscriber's prepaid credit. A
+    /// membership listing also grants a non-transferable channel membership
+    /// that lasts as long as the subscription.
+    pub fn subscribe(&mut self, subscriber: UserId, listing_id: Uuid, now: DateTime<Utc>) -> Result<Uuid> {
+        let listing = self
+            .listings
+            .iter()
+            .find(|l| l.id == listing_id)
+            .ok_or_else(|| Error::validation("Listing not found"))?;
+
+        let PricingModel::Subscription { price_per_month } = listing.pricing else {
+            return Err(Error::validation("Listing is not sold by subscription"));
+        };
+        let channel_id = match listing.good_type {
+            DigitalGoodType::Membership => listing.channel_id,
+            _ => None,
+        };
+        let creator = listing.creator.clone();
+        let membership_id = channel_id.map(|_| Uuid::new_v4());
+
+        let subscription_id = self
+            .subscriptions
+            .subscribe(&subscriber, listing_id, &creator, price_per_month, membership_id, now)
+            .map_err(|e| Error::validation(format!("Subscription failed: {}", e)))?;
+
+        if let (Some(channel_id), Some(membership_id)) = (channel_id, membership_id) {
+            self.memberships.push(ChannelMembership {
+                membership_id,
+                channel_id,
+                holder: subscriber,
+                purchased_at: now,
+                expires_at: now,
+                is_transferable: false,
+                access_level: MembershipAccessLevel::Basic,
+            });
+        }
+        self.sync_subscription_memberships();
+
+        Ok(subscription_id)
+    }
+
+    /// Credit a subscriber's ledger payment and renew anything it covers
+    pub fn top_up_subscription_credit(
+        &mut self,
+        ledger: &dyn escrow::EscrowLedger,
+        subscriber: &UserId,
+        payment_tx: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<u64> {
+        let amount = self
+            .subscriptions
+            .top_up(ledger, subscriber, payment_tx, now)
+            .map_err(|e| Error::validation(format!("Top-up failed: {}", e)))?;
+        self.sync_subscription_memberships();
+        Ok(amount)
+    }
+
+    /// Cancel a subscription, returning the prorated refund to credit
+    pub fn cancel_subscription(&mut self, subscriber: &UserId, subscription_id: Uuid, now: DateTime<Utc>) -> Result<u64> {
+        let refund = self
+            .subscriptions
+            .cancel(subscription_id, subscriber, now)
+            .map_err(|e| Error::validation(format!("Cancellation failed: {}", e)))?;
+        self.sync_subscription_memberships();
+        Ok(refund)
+    }
+
+    /// Run the subscription scheduler up to `now`
+    ///
+    /// Charges due renewals, starts and ends grace periods, and moves the
+    /// memberships of lapsed subscriptions to expire.
+    pub fn process_subscriptions(&mut self, now: DateTime<Utc>) {
+        self.subscriptions.process_due(now);
+        self.sync_subscription_memberships();
+    }
+
+    /// Keep subscription memberships expiring with their subscription
+    fn sync_subscription_memberships(&mut self) {
+        for subscription in self.subscriptions.subscriptions() {
+            let Some(membership_id) = subscription.membership_id else {
+                continue;
+            };
+            if let Some(membership) = self.memberships.iter_mut().find(|m| m.membership_id == membership_id) {
+                membership.expires_at = subscription.access_until();
+            }
+        }
+    }
+
     /// Update listing rating
     pub fn update_rating(&mut self, listing_id: Uuid, new_rating: f32) -> Result<()> {
         let listing = self
@@ -922,11 +1604,18 @@ impl Default for MarketplaceManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use escrow::tests::MemoryLedger;
 
     fn create_test_user() -> UserId {
         UserId::new()
     }
 
+    /// Buyer pays `amount` into the marketplace escrow account
+    fn pay_into_escrow(ledger: &MemoryLedger, marketplace: &MarketplaceManager, buyer: &UserId, amount: u64) -> Uuid {
+        ledger.fund(buyer, amount);
+        ledger.pay(buyer, marketplace.escrow.account(), amount)
+    }
+
     #[test]
     fn test_create_listing() {
         let mut marketplace = MarketplaceManager::new();
@@ -962,7 +1651,7 @@ mod tests {
 
         let listing_id = marketplace
             .create_listing(
-                creator,
+                creator.clone(),
                 "Theme".to_string(),
                 "Dark theme".to_string(),
                 DigitalGoodType::Theme,
@@ -976,13 +1665,24 @@ mod tests {
             )
             .unwrap();
 
+        let ledger = MemoryLedger::default();
+        let payment = pay_into_escrow(&ledger, &marketplace, &buyer, 500);
         let purchase_id = marketplace
-            .purchase(buyer, listing_id, 500, "tx123".to_string())
+            .purchase(&ledger, buyer.clone(), listing_id, payment)
             .unwrap();
 
         assert!(purchase_id != Uuid::nil());
         let listing = marketplace.get_listing(listing_id).unwrap();
         assert_eq!(listing.downloads, 1);
+
+        // The asset changes hands only once the seller has been paid
+        let escrow_id = listing.escrow_id.unwrap();
+        assert!(marketplace.complete_purchase_transfer(purchase_id, escrow_id).is_err());
+        marketplace.escrow.mark_awaiting_release(escrow_id).unwrap();
+        marketplace.escrow.release_funds(&ledger, escrow_id, &buyer).unwrap();
+        marketplace.complete_purchase_transfer(purchase_id, escrow_id).unwrap();
+        assert_eq!(ledger.balance(&creator), 500);
+        assert!(!marketplace.get_listing(listing_id).unwrap().in_escrow);
     }
 
     #[test]
@@ -1007,8 +1707,15 @@ mod tests {
             )
             .unwrap();
 
-        let result = marketplace.purchase(buyer, listing_id, 500, "tx456".to_string());
+        let ledger = MemoryLedger::default();
+        let payment = pay_into_escrow(&ledger, &marketplace, &buyer, 500);
+        let result = marketplace.purchase(&ledger, buyer.clone(), listing_id, payment);
+        assert!(result.is_err());
+
+        // The amount comes from the ledger, so a made-up payment is rejected
+        let result = marketplace.purchase(&ledger, buyer, listing_id, Uuid::new_v4());
         assert!(result.is_err());
+        assert!(!marketplace.get_listing(listing_id).unwrap().in_escrow);
     }
 
     #[test]
@@ -1099,6 +1806,11 @@ mod tests {
         let creator = create_test_user();
         let buyer = UserId::new();
 
+        let bot_id = Uuid::new_v4();
+        marketplace
+            .register_bot_ownership(bot_id, "product_bot".to_string(), creator.clone())
+            .unwrap();
+
         let listing_id = marketplace
             .create_listing(
                 creator.clone(),
@@ -1109,14 +1821,16 @@ mod tests {
                 "hash".to_string(),
                 OnChainStorageType::Hybrid,
                 None,
-                Some(Uuid::new_v4()),
+                Some(bot_id),
                 None,
                 None,
             )
             .unwrap();
 
+        let ledger = MemoryLedger::default();
+        let payment = pay_into_escrow(&ledger, &marketplace, &buyer, 2000);
         marketplace
-            .purchase(buyer, listing_id, 2000, "tx1".to_string())
+            .purchase(&ledger, buyer, listing_id, payment)
             .unwrap();
 
         let stats = marketplace.get_creator_stats(&creator);
@@ -1238,4 +1952,294 @@ mod tests {
         let listing = marketplace.get_listing(listing_id).unwrap();
         assert_eq!(listing.rating, 4.5);
     }
+
+    #[test]
+    fn test_subscription_membership_follows_billing() {
+        let mut marketplace = MarketplaceManager::new();
+        let ledger = MemoryLedger::default();
+        let creator = create_test_user();
+        let subscriber = UserId::new();
+        let channel_id = Uuid::new_v4();
+
+        let listing_id = marketplace
+            .create_listing(
+                creator.clone(),
+                "VIP Channel".to_string(),
+                "Monthly access".to_string(),
+                DigitalGoodType::Membership,
+                PricingModel::Subscription { price_per_month: 100 },
+                "hash".to_string(),
+                OnChainStorageType::ChatChain,
+                None,
+                None,
+                Some(channel_id),
+                None,
+            )
+            .unwrap();
+
+        // Subscriptions are not bought through escrow, and need credit
+        let payment = pay_into_escrow(&ledger, &marketplace, &subscriber, 100);
+        assert!(marketplace.purchase(&ledger, subscriber.clone(), listing_id, payment).is_err());
+        let now = Utc::now();
+        assert!(marketplace.subscribe(subscriber.clone(), listing_id, now).is_err());
+
+        ledger.fund(&subscriber, 100);
+        let payment = ledger.pay(&subscriber, marketplace.subscriptions.account(), 100);
+        marketplace.top_up_subscription_credit(&ledger, &subscriber, payment, now).unwrap();
+        let subscription_id = marketplace.subscribe(subscriber.clone(), listing_id, now).unwrap();
+        assert!(marketplace.has_active_membership(channel_id, &subscriber));
+
+        // Subscription memberships stay with the subscriber
+        let membership = marketplace.get_memberships_by_holder(&subscriber)[0].membership_id;
+        assert!(marketplace.transfer_membership(membership, UserId::new()).is_err());
+
+        // The unpaid renewal lapses and the membership expires with it
+        let lapse = now + chrono::Duration::days(subscriptions::BILLING_PERIOD_DAYS);
+        marketplace.process_subscriptions(lapse);
+        let expected = lapse + chrono::Duration::days(subscriptions::GRACE_PERIOD_DAYS);
+        assert_eq!(marketplace.get_membership(membership).unwrap().expires_at, expected);
+
+        // Cancelling ends access immediately
+        marketplace.cancel_subscription(&subscriber, subscription_id, Utc::now()).unwrap();
+        assert!(!marketplace.has_active_membership(channel_id, &subscriber));
+    }
+
+    fn list_sticker(marketplace: &mut MarketplaceManager, title: &str, description: &str, price: u64) -> Uuid {
+        marketplace
+            .create_listing(
+                create_test_user(),
+                title.to_string(),
+                description.to_string(),
+                DigitalGoodType::StickerPack,
+                PricingModel::OneTime { price },
+                "QmHash".to_string(),
+                OnChainStorageType::Ipfs,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_browse_filters_and_pages() {
+        let mut marketplace = MarketplaceManager::new();
+        for price in 1..=25 {
+            list_sticker(&mut marketplace, "Pack", "Stickers", price * 100);
+        }
+        let verified = list_sticker(&mut marketplace, "Pack", "Stickers", 1000);
+        marketplace.verify_listing(verified).unwrap();
+        marketplace.update_rating(verified, 4.5).unwrap();
+
+        let page = marketplace.browse(&ListingQuery::default());
+        assert_eq!(page.total, 26);
+        assert_eq!(page.listings.len(), DEFAULT_PAGE_SIZE);
+        assert_eq!(page.page_count(), 2);
+        let last = marketplace.browse(&ListingQuery { page: 1, ..Default::default() });
+        assert_eq!(last.listings.len(), 6);
+
+        let ranged = marketplace.browse(&ListingQuery {
+            min_price: Some(500),
+            max_price: Some(1000),
+            ..Default::default()
+        });
+        assert_eq!(ranged.total, 7);
+
+        let rated = marketplace.browse(&ListingQuery {
+            min_rating: Some(4.0),
+            verified_only: true,
+            ..Default::default()
+        });
+        assert_eq!(rated.total, 1);
+        assert_eq!(rated.listings[0].id, verified);
+
+        let other_type = marketplace.browse(&ListingQuery {
+            good_type: Some(DigitalGoodType::Theme),
+            ..Default::default()
+        });
+        assert_eq!(other_type.total, 0);
+    }
+
+    #[test]
+    fn test_search_ranks_title_matches_first() {
+        let mut marketplace = MarketplaceManager::new();
+        let in_description = list_sticker(&mut marketplace, "Animals", "Cute cats and dogs", 100);
+        let in_title = list_sticker(&mut marketplace, "Cat Stickers", "Cute animals", 100);
+        list_sticker(&mut marketplace, "Dogs", "Only dogs", 100);
+
+        let page = marketplace.browse(&ListingQuery {
+            text: Some("CUTE cat".to_string()),
+            ..Default::default()
+        });
+        let ids: Vec<_> = page.listings.iter().map(|l| l.id).collect();
+        assert_eq!(ids, vec![in_title, in_description]);
+    }
+
+    #[test]
+    fn test_resale_pays_royalty_and_moves_asset() {
+        let ledger = MemoryLedger::default();
+        let mut marketplace = MarketplaceManager::new();
+        let creator = create_test_user();
+        let seller = create_test_user();
+        let buyer = create_test_user();
+        marketplace
+            .register_nft(
+                "token_resale".to_string(),
+                "Badge".to_string(),
+                "Collectible".to_string(),
+                "QmImg".to_string(),
+                vec![],
+                creator.clone(),
+                seller.clone(),
+            )
+            .unwrap();
+        let asset = ResaleAsset::Nft("token_resale".to_string());
+
+        // Only the holder can ask
+        assert!(marketplace.place_ask(&ledger, buyer.clone(), asset.clone(), 1000).is_err());
+        let ask = marketplace.place_ask(&ledger, seller.clone(), asset.clone(), 1000).unwrap();
+        assert!(ask.trade.is_none());
+
+        // A low bid rests on the book with its funds held
+        ledger.fund(&buyer, 1900);
+        let low = ledger.pay(&buyer, marketplace.market.account(), 800);
+        let rested = marketplace.place_bid(&ledger, buyer.clone(), asset.clone(), low).unwrap();
+        assert!(rested.trade.is_none());
+        assert_eq!(marketplace.market.held_total(), 800);
+        assert!(marketplace.place_bid(&ledger, buyer.clone(), asset.clone(), low).is_err());
+
+        // A crossing bid fills at the ask, with the excess refunded
+        let high = ledger.pay(&buyer, marketplace.market.account(), 1100);
+        let filled = marketplace.place_bid(&ledger, buyer.clone(), asset.clone(), high).unwrap();
+        let trade = filled.trade.unwrap();
+        assert_eq!(trade.price, 1000);
+        assert_eq!(trade.royalty, 50);
+        assert_eq!(trade.platform_fee, 25);
+        assert_eq!(marketplace.get_nft("token_resale").unwrap().owner, buyer);
+        assert_eq!(ledger.balance(&seller), 925);
+        assert_eq!(ledger.burned(), 25);
+        assert_eq!(ledger.balance(&creator), 50);
+        assert_eq!(ledger.balance(&buyer), 100);
+
+        // The stale low bid is still refundable
+        marketplace.cancel_order(&ledger, &buyer, rested.order_id).unwrap();
+        assert_eq!(ledger.balance(&buyer), 900);
+        assert_eq!(ledger.balance(marketplace.market.account()), marketplace.market.held_total());
+        assert_eq!(marketplace.market.trades_for(&asset).len(), 1);
+    }
+
+    #[test]
+    fn test_ask_fills_against_best_bid() {
+        let ledger = MemoryLedger::default();
+        let mut marketplace = MarketplaceManager::new();
+        let owner = create_test_user();
+        let bot_id = Uuid::new_v4();
+        marketplace
+            .register_bot_ownership(bot_id, "helper_bot".to_string(), owner.clone())
+            .unwrap();
+        let asset = ResaleAsset::Bot(bot_id);
+
+        let bidders: Vec<_> = (0..3).map(|_| create_test_user()).collect();
+        for (bidder, amount) in bidders.iter().zip([300, 700, 500]) {
+            ledger.fund(bidder, amount);
+            let tx = ledger.pay(bidder, marketplace.market.account(), amount);
+            marketplace.place_bid(&ledger, bidder.clone(), asset.clone(), tx).unwrap();
+        }
+
+        let outcome = marketplace.place_ask(&ledger, owner.clone(), asset.clone(), 400).unwrap();
+        let trade = outcome.trade.unwrap();
+        assert_eq!(trade.buyer, bidders[1]);
+        assert_eq!(trade.price, 700);
+        assert_eq!(marketplace.asset_holder(&asset), Some(bidders[1].clone()));
+        assert_eq!(marketplace.market.held_total(), 800);
+        assert_eq!(ledger.balance(marketplace.market.account()), 800);
+    }
+
+    #[test]
+    fn test_license_limits_image_transfers() {
+        let mut marketplace = MarketplaceManager::new();
+        let creator = create_test_user();
+        let register = |marketplace: &mut MarketplaceManager, license_type| {
+            marketplace
+                .register_image(
+                    "Sunset".to_string(),
+                    "Painting".to_string(),
+                    creator.clone(),
+                    "QmArt".to_string(),
+                    800,
+                    600,
+                    "png".to_string(),
+                    license_type,
+                )
+                .unwrap()
+        };
+
+        let personal = register(&mut marketplace, LicenseType::Personal);
+        assert!(marketplace.transfer_image(personal, create_test_user()).is_err());
+
+        // Non-commercial works can be given away but not sold
+        let non_commercial = register(&mut marketplace, LicenseType::CcByNc);
+        let ledger = MemoryLedger::default();
+        let asset = ResaleAsset::Image(non_commercial);
+        assert!(marketplace.place_ask(&ledger, creator.clone(), asset.clone(), 100).is_err());
+        let friend = create_test_user();
+        marketplace.transfer_image(non_commercial, friend.clone()).unwrap();
+        assert_eq!(marketplace.get_image(non_commercial).unwrap().owner, friend);
+        assert_eq!(marketplace.settlement.provenance(&asset).len(), 1);
+    }
+
+    #[test]
+    fn test_listing_sale_splits_revenue_and_records_provenance() {
+        let ledger = MemoryLedger::default();
+        let mut marketplace = MarketplaceManager::new();
+        let creator = create_test_user();
+        let partner = create_test_user();
+        let buyer = create_test_user();
+        let channel_id = Uuid::new_v4();
+        marketplace
+            .register_channel_ownership(channel_id, "news".to_string(), creator.clone(), 10)
+            .unwrap();
+        let asset = ResaleAsset::Channel(channel_id);
+
+        // Only the creator may split the revenue
+        let split = RevenueSplit::new(vec![(creator.clone(), 7_500), (partner.clone(), 2_500)]).unwrap();
+        assert!(marketplace.set_revenue_split(&partner, asset.clone(), split.clone()).is_err());
+        marketplace.set_revenue_split(&creator, asset.clone(), split).unwrap();
+
+        let listing_id = marketplace
+            .create_listing(
+                creator.clone(),
+                "News channel".to_string(),
+                "Daily news".to_string(),
+                DigitalGoodType::Channel,
+                PricingModel::OneTime { price: 1000 },
+                "hash".to_string(),
+                OnChainStorageType::ChatChain,
+                None,
+                None,
+                Some(channel_id),
+                None,
+            )
+            .unwrap();
+        let payment = pay_into_escrow(&ledger, &marketplace, &buyer, 1000);
+        let purchase_id = marketplace.purchase(&ledger, buyer.clone(), listing_id, payment).unwrap();
+        let escrow_id = marketplace.get_listing(listing_id).unwrap().escrow_id.unwrap();
+        marketplace.escrow.mark_awaiting_release(escrow_id).unwrap();
+        marketplace.escrow.release_funds(&ledger, escrow_id, &buyer).unwrap();
+        marketplace.complete_purchase_transfer(purchase_id, escrow_id).unwrap();
+
+        assert_eq!(ledger.balance(&creator), 750);
+        assert_eq!(ledger.balance(&partner), 250);
+        assert_eq!(marketplace.asset_holder(&asset), Some(buyer.clone()));
+
+        // The buyer gives the channel on; the chain links both transfers
+        let next = create_test_user();
+        marketplace.transfer_channel_ownership(channel_id, next.clone()).unwrap();
+        let chain = marketplace.settlement.provenance(&asset);
+        assert_eq!(chain.len(), 2);
+        assert_eq!((chain[0].kind, chain[0].price), (TransferKind::Sale, 1000));
+        assert_eq!((&chain[1].from, &chain[1].to), (&buyer, &next));
+        assert!(marketplace.settlement.verify_provenance(&asset));
+    }
 }
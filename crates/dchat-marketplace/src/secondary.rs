@@ -0,0 +1,221 @@
+//! Order book for reselling owned assets
+//!
+//! Holders of NFTs, memberships, bots, channels and artwork place asks;
+//! buyers place bids funded by a ledger payment into the market account, so
+//! a bid can always settle. Each asset has at most one ask (its holder's)
+//! and any number of bids. An incoming order that crosses the resting side
+//! trades at the resting order's price: the highest bid, earliest first, or
+//! the ask.
+//!
+//! The book only matches orders and holds bid funds; `MarketplaceManager`
+//! settles trades through `settlement`, which pays the creator royalty and
+//! burns the platform fee out of the seller's proceeds.
+
+use crate::escrow::EscrowLedger;
+use crate::types::{MarketplaceError, UserId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use uuid::Uuid;
+
+/// An asset that can be resold
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResaleAsset {
+    Nft(String),
+    Membership(Uuid),
+    Bot(Uuid),
+    Channel(Uuid),
+    Image(Uuid),
+}
+
+impl fmt::Display for ResaleAsset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResaleAsset::Nft(token_id) => write!(f, "nft:{}", token_id),
+            ResaleAsset::Membership(id) => write!(f, "membership:{}", id),
+            ResaleAsset::Bot(id) => write!(f, "bot:{}", id),
+            ResaleAsset::Channel(id) => write!(f, "channel:{}", id),
+            ResaleAsset::Image(id) => write!(f, "image:{}", id),
+        }
+    }
+}
+
+/// Side of an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderSide {
+    Ask,
+    Bid,
+}
+
+/// A resting order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub id: Uuid,
+    pub asset: ResaleAsset,
+    pub side: OrderSide,
+    pub trader: UserId,
+    pub price: u64,
+    pub placed_at: DateTime<Utc>,
+    /// Ledger payment holding a bid's funds
+    pub funding_tx: Option<Uuid>,
+}
+
+/// A settled resale
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub id: Uuid,
+    pub asset: ResaleAsset,
+    pub seller: UserId,
+    pub buyer: UserId,
+    pub price: u64,
+    /// Creator paid the royalty, if the asset has one
+    pub creator: Option<UserId>,
+    pub royalty: u64,
+    /// Platform fee burned out of the seller's proceeds
+    pub platform_fee: u64,
+    pub ask_id: Uuid,
+    pub bid_id: Uuid,
+    pub executed_at: DateTime<Utc>,
+    pub payout_txs: Vec<Uuid>,
+}
+
+/// Result of placing an order
+#[derive(Debug, Clone)]
+pub struct OrderOutcome {
+    pub order_id: Uuid,
+    /// Trade the order filled, if it crossed the book
+    pub trade: Option<Trade>,
+}
+
+/// Secondary market order book
+///
+/// The market account must exist on the ledger as an account only the
+/// protocol can move (a module account on the currency chain).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderBook {
+    account: UserId,
+    orders: HashMap<Uuid, Order>,
+    trades: Vec<Trade>,
+    /// Ledger payments already used to fund bids
+    used_payments: BTreeSet<Uuid>,
+}
+
+impl OrderBook {
+    /// Create an order book with a fresh market account
+    pub fn new() -> Self {
+        Self::with_account(UserId::new())
+    }
+
+    /// Create an order book holding bid funds in `account`
+    pub fn with_account(account: UserId) -> Self {
+        Self {
+            account,
+            orders: HashMap::new(),
+            trades: Vec::new(),
+            used_payments: BTreeSet::new(),
+        }
+    }
+
+    /// Ledger account bid funds are held in
+    pub fn account(&self) -> &UserId {
+        &self.account
+    }
+
+    /// Check that `funding_tx` is an unused payment from `bidder` into the
+    /// market account, returning the amount it credited
+    pub(crate) fn verify_funding(
+        &self,
+        ledger: &dyn EscrowLedger,
+        bidder: &UserId,
+        funding_tx: Uuid,
+    ) -> Result<u64, MarketplaceError> {
+        let payment = ledger
+            .payment(&funding_tx)
+            .map_err(|e| MarketplaceError::Ledger(e.to_string()))?
+            .ok_or(MarketplaceError::InvalidPayment)?;
+
+        if &payment.from != bidder || payment.to != self.account || self.used_payments.contains(&funding_tx) {
+            return Err(MarketplaceError::InvalidPayment);
+        }
+        Ok(payment.amount)
+    }
+
+    /// Open ask for `asset`
+    pub fn ask_for(&self, asset: &ResaleAsset) -> Option<&Order> {
+        self.orders
+            .values()
+            .find(|o| o.side == OrderSide::Ask && &o.asset == asset)
+    }
+
+    /// Highest bid for `asset` at or above `min_price`, earliest first on ties
+    pub fn best_bid(&self, asset: &ResaleAsset, min_price: u64) -> Option<&Order> {
+        self.orders
+            .values()
+            .filter(|o| o.side == OrderSide::Bid && &o.asset == asset && o.price >= min_price)
+            .max_by(|a, b| a.price.cmp(&b.price).then(b.placed_at.cmp(&a.placed_at)))
+    }
+
+    /// Rest an order on the book
+    pub(crate) fn insert(&mut self, order: Order) {
+        if let Some(funding_tx) = order.funding_tx {
+            self.used_payments.insert(funding_tx);
+        }
+        self.orders.insert(order.id, order);
+    }
+
+    /// Take an order off the book
+    pub(crate) fn remove(&mut self, order_id: Uuid) -> Option<Order> {
+        self.orders.remove(&order_id)
+    }
+
+    /// Record a settled trade
+    pub(crate) fn record(&mut self, trade: Trade, funding_tx: Option<Uuid>) {
+        if let Some(funding_tx) = funding_tx {
+            self.used_payments.insert(funding_tx);
+        }
+        self.trades.push(trade);
+    }
+
+    /// Get an open order by ID
+    pub fn get_order(&self, order_id: Uuid) -> Option<&Order> {
+        self.orders.get(&order_id)
+    }
+
+    /// Open orders for an asset, asks first then bids by price
+    pub fn orders_for(&self, asset: &ResaleAsset) -> Vec<&Order> {
+        let mut orders: Vec<_> = self.orders.values().filter(|o| &o.asset == asset).collect();
+        orders.sort_by(|a, b| {
+            (a.side == OrderSide::Bid)
+                .cmp(&(b.side == OrderSide::Bid))
+                .then(b.price.cmp(&a.price))
+                .then(a.placed_at.cmp(&b.placed_at))
+        });
+        orders
+    }
+
+    /// Open orders placed by `trader`
+    pub fn orders_by(&self, trader: &UserId) -> Vec<&Order> {
+        self.orders.values().filter(|o| &o.trader == trader).collect()
+    }
+
+    /// Trade history for an asset, oldest first
+    pub fn trades_for(&self, asset: &ResaleAsset) -> Vec<&Trade> {
+        self.trades.iter().filter(|t| &t.asset == asset).collect()
+    }
+
+    /// Total held for open bids
+    pub fn held_total(&self) -> u64 {
+        self.orders
+            .values()
+            .filter(|o| o.side == OrderSide::Bid)
+            .map(|o| o.price)
+            .sum()
+    }
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,395 @@
+//! Royalties, revenue splits and provenance for asset transfers
+//!
+//! Every change of hands of an NFT, membership, bot, channel or artwork goes
+//! through `MarketplaceManager`'s settlement path: the asset's transfer rules
+//! and license are checked, the sale price is divided between the seller,
+//! the creator and the platform fee (which is burned), and a record is
+//! appended to the asset's provenance chain.
+//!
+//! A creator may register a revenue split for an asset. Their royalty on a
+//! resale, or their whole proceeds when they sell it themselves, is then
+//! divided between the split's recipients.
+//!
+//! Each provenance record commits to the hash of the asset's previous
+//! record, so the chain can be audited from the asset's first transfer.
+
+use crate::secondary::ResaleAsset;
+use crate::types::{MarketplaceError, UserId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Royalty paid to an asset's creator when someone else sells it, in basis
+/// points
+pub const DEFAULT_ROYALTY_BPS: u16 = 500;
+
+/// Platform fee burned on every resale, in basis points
+pub const DEFAULT_PLATFORM_FEE_BPS: u16 = 250;
+
+/// Most recipients a revenue split may have
+pub const MAX_SPLIT_RECIPIENTS: usize = 16;
+
+/// How an asset changed hands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferKind {
+    /// Given away with no payment
+    Gift,
+    /// Sold from a listing, paid through escrow
+    Sale,
+    /// Sold on the secondary market
+    Resale,
+}
+
+impl TransferKind {
+    /// Whether the transfer is paid for
+    pub fn is_commercial(self) -> bool {
+        self != TransferKind::Gift
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            TransferKind::Gift => 0,
+            TransferKind::Sale => 1,
+            TransferKind::Resale => 2,
+        }
+    }
+}
+
+/// Division of a creator's revenue between recipients
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevenueSplit {
+    /// Recipient and share in basis points; shares sum to 10,000
+    shares: Vec<(UserId, u16)>,
+}
+
+impl RevenueSplit {
+    /// Create a split from shares in basis points
+    ///
+    /// Shares must be non-zero, go to distinct recipients and sum to 10,000.
+    pub fn new(shares: Vec<(UserId, u16)>) -> Result<Self, MarketplaceError> {
+        let total: u32 = shares.iter().map(|(_, bps)| *bps as u32).sum();
+        let distinct = shares
+            .iter()
+            .enumerate()
+            .all(|(i, (user, _))| shares[..i].iter().all(|(other, _)| other != user));
+
+        if shares.is_empty()
+            || shares.len() > MAX_SPLIT_RECIPIENTS
+            || shares.iter().any(|(_, bps)| *bps == 0)
+            || total != 10_000
+            || !distinct
+        {
+            return Err(MarketplaceError::InvalidRevenueSplit);
+        }
+        Ok(Self { shares })
+    }
+
+    /// Recipients and their shares in basis points
+    pub fn shares(&self) -> &[(UserId, u16)] {
+        &self.shares
+    }
+
+    /// Divide `amount` by share; rounding dust goes to the first recipient
+    pub fn divide(&self, amount: u64) -> Vec<(UserId, u64)> {
+        let mut parts: Vec<(UserId, u64)> = self
+            .shares
+            .iter()
+            .map(|(user, bps)| (user.clone(), (amount as u128 * *bps as u128 / 10_000) as u64))
+            .collect();
+        let dust = amount - parts.iter().map(|(_, part)| part).sum::<u64>();
+        parts[0].1 += dust;
+        parts
+    }
+}
+
+/// How a sale price is divided
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quote {
+    pub price: u64,
+    /// Paid to the creator or their revenue split
+    pub royalties: Vec<(UserId, u64)>,
+    /// Burned
+    pub platform_fee: u64,
+    /// Left for the seller
+    pub seller_proceeds: u64,
+}
+
+impl Quote {
+    /// Quote for a transfer with no payment
+    pub fn gift() -> Self {
+        Self {
+            price: 0,
+            royalties: Vec::new(),
+            platform_fee: 0,
+            seller_proceeds: 0,
+        }
+    }
+
+    /// Total paid to the creator side
+    pub fn royalty(&self) -> u64 {
+        self.royalties.iter().map(|(_, amount)| amount).sum()
+    }
+}
+
+/// One change of hands in an asset's history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvenanceRecord {
+    pub id: Uuid,
+    pub asset: ResaleAsset,
+    pub from: UserId,
+    pub to: UserId,
+    pub kind: TransferKind,
+    pub price: u64,
+    pub royalties: Vec<(UserId, u64)>,
+    pub platform_fee: u64,
+    /// Ledger transfers that settled the price
+    pub payout_txs: Vec<Uuid>,
+    pub settled_at: DateTime<Utc>,
+    /// Hash of the asset's previous record, zero for the first
+    pub prev_hash: [u8; 32],
+    /// Hash of this record's contents and `prev_hash`
+    pub hash: [u8; 32],
+}
+
+impl ProvenanceRecord {
+    /// Hash of the record's contents chained to `prev_hash`
+    fn compute_hash(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.prev_hash);
+        hasher.update(self.id.as_bytes());
+        hasher.update(self.asset.to_string().as_bytes());
+        hasher.update(self.from.0.as_bytes());
+        hasher.update(self.to.0.as_bytes());
+        hasher.update(&[self.kind.tag()]);
+        hasher.update(&self.price.to_le_bytes());
+        for (recipient, amount) in &self.royalties {
+            hasher.update(recipient.0.as_bytes());
+            hasher.update(&amount.to_le_bytes());
+        }
+        hasher.update(&self.platform_fee.to_le_bytes());
+        for tx in &self.payout_txs {
+            hasher.update(tx.as_bytes());
+        }
+        hasher.update(&self.settled_at.timestamp_micros().to_le_bytes());
+        *hasher.finalize().as_bytes()
+    }
+}
+
+/// Royalty and fee rates, revenue splits and provenance chains
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settlement {
+    royalty_bps: u16,
+    platform_fee_bps: u16,
+    splits: Vec<(ResaleAsset, RevenueSplit)>,
+    provenance: Vec<ProvenanceRecord>,
+}
+
+impl Settlement {
+    /// Create a settlement with the default royalty and platform fee
+    pub fn new() -> Self {
+        Self::with_rates(DEFAULT_ROYALTY_BPS, DEFAULT_PLATFORM_FEE_BPS)
+    }
+
+    /// Create a settlement with rates in basis points
+    ///
+    /// The two rates together are capped at 10,000.
+    pub fn with_rates(royalty_bps: u16, platform_fee_bps: u16) -> Self {
+        let royalty_bps = royalty_bps.min(10_000);
+        Self {
+            royalty_bps,
+            platform_fee_bps: platform_fee_bps.min(10_000 - royalty_bps),
+            splits: Vec::new(),
+            provenance: Vec::new(),
+        }
+    }
+
+    /// Royalty owed to the creator on a sale at `price` by someone else
+    pub fn royalty(&self, price: u64) -> u64 {
+        (price as u128 * self.royalty_bps as u128 / 10_000) as u64
+    }
+
+    /// Platform fee burned on a resale at `price`
+    pub fn platform_fee(&self, price: u64) -> u64 {
+        (price as u128 * self.platform_fee_bps as u128 / 10_000) as u64
+    }
+
+    /// Register how the creator's revenue from `asset` is divided
+    pub(crate) fn set_split(&mut self, asset: ResaleAsset, split: RevenueSplit) {
+        self.splits.retain(|(a, _)| a != &asset);
+        self.splits.push((asset, split));
+    }
+
+    /// Revenue split registered for `asset`
+    pub fn split_for(&self, asset: &ResaleAsset) -> Option<&RevenueSplit> {
+        self.splits.iter().find(|(a, _)| a == asset).map(|(_, split)| split)
+    }
+
+    /// Divide a sale of `asset` by `seller` at `price`
+    ///
+    /// The royalty comes out of the price; resales also burn the platform
+    /// fee, while listing sales are fee-free. When the creator is the seller
+    /// there is no royalty, but a registered split still divides their
+    /// proceeds.
+    pub fn quote(
+        &self,
+        kind: TransferKind,
+        asset: &ResaleAsset,
+        seller: &UserId,
+        creator: Option<&UserId>,
+        price: u64,
+    ) -> Quote {
+        if !kind.is_commercial() {
+            return Quote::gift();
+        }
+
+        let platform_fee = match kind {
+            TransferKind::Resale => self.platform_fee(price),
+            _ => 0,
+        };
+        let split = self.split_for(asset);
+        let royalty = match (creator, split) {
+            (Some(creator), split) if creator == seller => {
+                if split.is_some() { price - platform_fee } else { 0 }
+            }
+            (Some(_), _) => self.royalty(price),
+            (None, _) => 0,
+        };
+        let mut royalties = match (split, creator) {
+            (Some(split), _) => split.divide(royalty),
+            (None, Some(creator)) => vec![(creator.clone(), royalty)],
+            (None, None) => Vec::new(),
+        };
+        royalties.retain(|(_, amount)| *amount > 0);
+
+        Quote {
+            price,
+            royalties,
+            platform_fee,
+            seller_proceeds: price - platform_fee - royalty,
+        }
+    }
+
+    /// Append a transfer to its asset's provenance chain
+    pub(crate) fn record(
+        &mut self,
+        asset: ResaleAsset,
+        from: UserId,
+        to: UserId,
+        kind: TransferKind,
+        quote: &Quote,
+        payout_txs: Vec<Uuid>,
+    ) -> ProvenanceRecord {
+        let prev_hash = self.provenance(&asset).last().map(|r| r.hash).unwrap_or([0; 32]);
+        let mut record = ProvenanceRecord {
+            id: Uuid::new_v4(),
+            asset,
+            from,
+            to,
+            kind,
+            price: quote.price,
+            royalties: quote.royalties.clone(),
+            platform_fee: quote.platform_fee,
+            payout_txs,
+            settled_at: Utc::now(),
+            prev_hash,
+            hash: [0; 32],
+        };
+        record.hash = record.compute_hash();
+        self.provenance.push(record.clone());
+        record
+    }
+
+    /// Provenance chain for `asset`, oldest first
+    pub fn provenance(&self, asset: &ResaleAsset) -> Vec<&ProvenanceRecord> {
+        self.provenance.iter().filter(|r| &r.asset == asset).collect()
+    }
+
+    /// Check that `asset`'s provenance chain is intact
+    ///
+    /// Every record must hash to its stored hash, link to the record before
+    /// it, and start from the previous record's recipient.
+    pub fn verify_provenance(&self, asset: &ResaleAsset) -> bool {
+        let chain = self.provenance(asset);
+        let mut prev: Option<&ProvenanceRecord> = None;
+        for record in chain {
+            let linked = match prev {
+                Some(prev) => record.prev_hash == prev.hash && record.from == prev.to,
+                None => record.prev_hash == [0; 32],
+            };
+            if !linked || record.compute_hash() != record.hash {
+                return false;
+            }
+            prev = Some(record);
+        }
+        true
+    }
+}
+
+impl Default for Settlement {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nft() -> ResaleAsset {
+        ResaleAsset::Nft("token".to_string())
+    }
+
+    #[test]
+    fn test_split_validation_and_division() {
+        let (a, b) = (UserId::new(), UserId::new());
+        assert!(RevenueSplit::new(vec![]).is_err());
+        assert!(RevenueSplit::new(vec![(a.clone(), 5_000)]).is_err());
+        assert!(RevenueSplit::new(vec![(a.clone(), 5_000), (a.clone(), 5_000)]).is_err());
+        assert!(RevenueSplit::new(vec![(a.clone(), 10_000), (b.clone(), 0)]).is_err());
+
+        let split = RevenueSplit::new(vec![(a.clone(), 3_333), (b.clone(), 6_667)]).unwrap();
+        assert_eq!(split.divide(100), vec![(a, 34), (b, 66)]);
+    }
+
+    #[test]
+    fn test_quotes_by_kind_and_seller() {
+        let settlement = Settlement::new();
+        let (creator, seller, partner) = (UserId::new(), UserId::new(), UserId::new());
+
+        assert_eq!(settlement.quote(TransferKind::Gift, &nft(), &seller, Some(&creator), 1000), Quote::gift());
+
+        let resale = settlement.quote(TransferKind::Resale, &nft(), &seller, Some(&creator), 1000);
+        assert_eq!(resale.royalties, vec![(creator.clone(), 50)]);
+        assert_eq!(resale.platform_fee, 25);
+        assert_eq!(resale.seller_proceeds, 925);
+
+        // The creator pays no royalty to themselves and listing sales are fee-free
+        let sale = settlement.quote(TransferKind::Sale, &nft(), &creator, Some(&creator), 1000);
+        assert!(sale.royalties.is_empty());
+        assert_eq!(sale.seller_proceeds, 1000);
+
+        // A split divides the royalty, or all of the creator's own proceeds
+        let mut settlement = settlement;
+        let split = RevenueSplit::new(vec![(creator.clone(), 6_000), (partner.clone(), 4_000)]).unwrap();
+        settlement.set_split(nft(), split);
+        let resale = settlement.quote(TransferKind::Resale, &nft(), &seller, Some(&creator), 1000);
+        assert_eq!(resale.royalties, vec![(creator.clone(), 30), (partner.clone(), 20)]);
+        let own = settlement.quote(TransferKind::Resale, &nft(), &creator, Some(&creator), 1000);
+        assert_eq!(own.royalties, vec![(creator, 585), (partner, 390)]);
+        assert_eq!(own.seller_proceeds, 0);
+    }
+
+    #[test]
+    fn test_provenance_chain_detects_tampering() {
+        let mut settlement = Settlement::new();
+        let (a, b, c) = (UserId::new(), UserId::new(), UserId::new());
+        settlement.record(nft(), a.clone(), b.clone(), TransferKind::Gift, &Quote::gift(), vec![]);
+        let quote = settlement.quote(TransferKind::Resale, &nft(), &b, Some(&a), 400);
+        settlement.record(nft(), b, c, TransferKind::Resale, &quote, vec![Uuid::new_v4()]);
+        assert!(settlement.verify_provenance(&nft()));
+        assert_eq!(settlement.provenance(&nft())[1].prev_hash, settlement.provenance(&nft())[0].hash);
+
+        settlement.provenance[1].price = 1;
+        assert!(!settlement.verify_provenance(&nft()));
+    }
+}
@@ -0,0 +1,539 @@
+//! Recurring billing for `PricingModel::Subscription` listings
+//!
+//! Ledger accounts only move with their owner's signature, so subscribers
+//! prepay credit into the subscription account and every billing period is
+//! charged from that credit. A period's charge is held until the period ends
+//! and then becomes the creator's earnings, paid out with `payout_creator`.
+//! Cancelling refunds the unused part of the current period to the
+//! subscriber's credit.
+//!
+//! A renewal the credit cannot cover puts the subscription past due. Access
+//! continues for a grace period; a top-up within it renews the subscription
+//! from where it lapsed, and after it the subscription expires.
+//!
+//! State changes queue `Event`s, which `publish_events` hands to an
+//! `EventBus`.
+
+use crate::escrow::EscrowLedger;
+use crate::types::{ListingId, MarketplaceError, UserId};
+use chrono::{DateTime, Duration, Utc};
+use dchat_core::events::{Event, EventBus};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use uuid::Uuid;
+
+/// Length of a billing period
+pub const BILLING_PERIOD_DAYS: i64 = 30;
+
+/// How long access outlasts an unpaid renewal
+pub const GRACE_PERIOD_DAYS: i64 = 3;
+
+/// Subscription lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubscriptionStatus {
+    /// Current period paid
+    Active,
+    /// Renewal failed; access lasts until the grace period ends
+    PastDue,
+    /// Cancelled by the subscriber
+    Cancelled,
+    /// Grace period ended without payment
+    Expired,
+}
+
+/// A recurring subscription to a listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: Uuid,
+    pub listing_id: ListingId,
+    pub subscriber: UserId,
+    pub creator: UserId,
+    pub price_per_period: u64,
+    pub status: SubscriptionStatus,
+    pub started_at: DateTime<Utc>,
+    pub period_start: DateTime<Utc>,
+    /// End of the paid period; for a cancelled subscription, when it was cancelled
+    pub period_end: DateTime<Utc>,
+    /// Charge for the current period, held until the period ends
+    pub period_charge: u64,
+    /// Channel membership the subscription keeps alive
+    pub membership_id: Option<Uuid>,
+}
+
+impl Subscription {
+    /// When access granted by the subscription ends
+    pub fn access_until(&self) -> DateTime<Utc> {
+        match self.status {
+            SubscriptionStatus::Cancelled => self.period_end,
+            _ => self.period_end + Duration::days(GRACE_PERIOD_DAYS),
+        }
+    }
+
+    /// Whether the subscription grants access at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.status, SubscriptionStatus::Active | SubscriptionStatus::PastDue)
+            && now < self.access_until()
+    }
+}
+
+/// Subscription scheduler and billing ledger
+///
+/// The subscription account must exist on the ledger as an account only the
+/// protocol can move (a module account on the currency chain).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionManager {
+    account: UserId,
+    subscriptions: HashMap<Uuid, Subscription>,
+    /// Prepaid credit by subscriber
+    credits: HashMap<UserId, u64>,
+    /// Earnings from ended periods awaiting payout, by creator
+    earnings: HashMap<UserId, u64>,
+    /// Ledger payments already credited
+    credited_payments: BTreeSet<Uuid>,
+    /// Events not yet published
+    events: Vec<Event>,
+}
+
+impl SubscriptionManager {
+    /// Create a subscription manager with a fresh subscription account
+    pub fn new() -> Self {
+        Self::with_account(UserId::new())
+    }
+
+    /// Create a subscription manager holding credit in `account`
+    pub fn with_account(account: UserId) -> Self {
+        Self {
+            account,
+            subscriptions: HashMap::new(),
+            credits: HashMap::new(),
+            earnings: HashMap::new(),
+            credited_payments: BTreeSet::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Ledger account subscription credit is held in
+    pub fn account(&self) -> &UserId {
+        &self.account
+    }
+
+    /// Credit a subscriber's ledger payment into the subscription account
+    ///
+    /// Past-due subscriptions the new credit covers renew at `now`.
+    pub fn top_up(
+        &mut self,
+        ledger: &dyn EscrowLedger,
+        subscriber: &UserId,
+        payment_tx: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<u64, MarketplaceError> {
+        let payment = ledger
+            .payment(&payment_tx)
+            .map_err(|e| MarketplaceError::Ledger(e.to_string()))?
+            .ok_or(MarketplaceError::InvalidPayment)?;
+
+        if &payment.from != subscriber
+            || payment.to != self.account
+            || self.credited_payments.contains(&payment_tx)
+        {
+            return Err(MarketplaceError::InvalidPayment);
+        }
+
+        self.credited_payments.insert(payment_tx);
+        *self.credits.entry(subscriber.clone()).or_default() += payment.amount;
+        self.process_due(now);
+        Ok(payment.amount)
+    }
+
+    /// Return unused credit to the subscriber's wallet
+    pub fn withdraw(
+        &mut self,
+        ledger: &dyn EscrowLedger,
+        subscriber: &UserId,
+        amount: u64,
+    ) -> Result<Uuid, MarketplaceError> {
+        if self.credit(subscriber) < amount {
+            return Err(MarketplaceError::InsufficientCredit);
+        }
+
+        let tx_ids = ledger
+            .transfer_batch(&self.account, &[(subscriber.clone(), amount)])
+            .map_err(|e| MarketplaceError::Ledger(e.to_string()))?;
+        *self.credits.entry(subscriber.clone()).or_default() -= amount;
+        Ok(tx_ids[0])
+    }
+
+    /// Prepaid credit left for `subscriber`
+    pub fn credit(&self, subscriber: &UserId) -> u64 {
+        self.credits.get(subscriber).copied().unwrap_or(0)
+    }
+
+    /// Start a subscription, charging the first period from credit
+    pub fn subscribe(
+        &mut self,
+        subscriber: &UserId,
+        listing_id: ListingId,
+        creator: &UserId,
+        price_per_period: u64,
+        membership_id: Option<Uuid>,
+        now: DateTime<Utc>,
+    ) -> Result<Uuid, MarketplaceError> {
+        if self
+            .subscriptions
+            .values()
+            .any(|s| s.listing_id == listing_id && &s.subscriber == subscriber && s.is_active(now))
+        {
+            return Err(MarketplaceError::InvalidSubscriptionState);
+        }
+
+        let credit = self.credits.entry(subscriber.clone()).or_default();
+        if *credit < price_per_period {
+            return Err(MarketplaceError::InsufficientCredit);
+        }
+        *credit -= price_per_period;
+
+        let subscription = Subscription {
+            id: Uuid::new_v4(),
+            listing_id,
+            subscriber: subscriber.clone(),
+            creator: creator.clone(),
+            price_per_period,
+            status: SubscriptionStatus::Active,
+            started_at: now,
+            period_start: now,
+            period_end: now + Duration::days(BILLING_PERIOD_DAYS),
+            period_charge: price_per_period,
+            membership_id,
+        };
+        let subscription_id = subscription.id;
+
+        self.events.push(Event::SubscriptionStarted {
+            subscription_id,
+            listing_id,
+            subscriber: subscriber.clone(),
+            price_per_period,
+        });
+        self.subscriptions.insert(subscription_id, subscription);
+        Ok(subscription_id)
+    }
+
+    /// Cancel a subscription at `now`, returning the prorated refund
+    ///
+    /// The unused share of the current period's charge goes back to the
+    /// subscriber's credit and the creator earns the rest.
+    pub fn cancel(
+        &mut self,
+        subscription_id: Uuid,
+        subscriber: &UserId,
+        now: DateTime<Utc>,
+    ) -> Result<u64, MarketplaceError> {
+        let subscription = self
+            .subscriptions
+            .get_mut(&subscription_id)
+            .ok_or(MarketplaceError::SubscriptionNotFound)?;
+
+        if &subscription.subscriber != subscriber {
+            return Err(MarketplaceError::Unauthorized);
+        }
+        if !matches!(subscription.status, SubscriptionStatus::Active | SubscriptionStatus::PastDue) {
+            return Err(MarketplaceError::InvalidSubscriptionState);
+        }
+
+        let period = (subscription.period_end - subscription.period_start).num_seconds();
+        let unused = (subscription.period_end - now).num_seconds().clamp(0, period);
+        let refund = if period > 0 {
+            (subscription.period_charge as u128 * unused as u128 / period as u128) as u64
+        } else {
+            0
+        };
+
+        *self.credits.entry(subscriber.clone()).or_default() += refund;
+        *self.earnings.entry(subscription.creator.clone()).or_default() += subscription.period_charge - refund;
+        subscription.period_charge = 0;
+        subscription.period_end = subscription.period_end.min(now);
+        subscription.status = SubscriptionStatus::Cancelled;
+
+        self.events.push(Event::SubscriptionCancelled { subscription_id, refund });
+        Ok(refund)
+    }
+
+    /// Renew, lapse and expire every subscription due by `now`
+    ///
+    /// Subscriptions that fell several periods behind are caught up one
+    /// period at a time, oldest first.
+    pub fn process_due(&mut self, now: DateTime<Utc>) {
+        let mut due: Vec<_> = self
+            .subscriptions
+            .values()
+            .filter(|s| matches!(s.status, SubscriptionStatus::Active | SubscriptionStatus::PastDue))
+            .map(|s| (s.period_end, s.id))
+            .collect();
+        due.sort();
+
+        for (_, id) in due {
+            let subscription = self.subscriptions.get_mut(&id).expect("listed above");
+            loop {
+                if subscription.status == SubscriptionStatus::Active {
+                    if now < subscription.period_end {
+                        break;
+                    }
+                    // The period is over: its charge is now the creator's
+                    *self.earnings.entry(subscription.creator.clone()).or_default() += subscription.period_charge;
+                    subscription.period_charge = 0;
+                }
+
+                let credit = self.credits.entry(subscription.subscriber.clone()).or_default();
+                if *credit >= subscription.price_per_period {
+                    *credit -= subscription.price_per_period;
+                    subscription.period_start = subscription.period_end;
+                    subscription.period_end += Duration::days(BILLING_PERIOD_DAYS);
+                    subscription.period_charge = subscription.price_per_period;
+                    subscription.status = SubscriptionStatus::Active;
+                    self.events.push(Event::SubscriptionRenewed {
+                        subscription_id: id,
+                        amount: subscription.price_per_period,
+                        period_end: subscription.period_end,
+                    });
+                } else if now >= subscription.access_until() {
+                    subscription.status = SubscriptionStatus::Expired;
+                    self.events.push(Event::SubscriptionExpired { subscription_id: id });
+                    break;
+                } else {
+                    if subscription.status == SubscriptionStatus::Active {
+                        subscription.status = SubscriptionStatus::PastDue;
+                        self.events.push(Event::SubscriptionPastDue {
+                            subscription_id: id,
+                            grace_until: subscription.access_until(),
+                        });
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Pay a creator everything earned from ended periods
+    pub fn payout_creator(&mut self, ledger: &dyn EscrowLedger, creator: &UserId) -> Result<u64, MarketplaceError> {
+        let amount = self.earnings_of(creator);
+        if amount == 0 {
+            return Ok(0);
+        }
+
+        ledger
+            .transfer_batch(&self.account, &[(creator.clone(), amount)])
+            .map_err(|e| MarketplaceError::Ledger(e.to_string()))?;
+        self.earnings.remove(creator);
+        self.events.push(Event::CreatorPaidOut { creator: creator.clone(), amount });
+        Ok(amount)
+    }
+
+    /// Earnings awaiting payout to `creator`
+    pub fn earnings_of(&self, creator: &UserId) -> u64 {
+        self.earnings.get(creator).copied().unwrap_or(0)
+    }
+
+    /// Total the subscription account owes to subscribers and creators
+    pub fn held_total(&self) -> u64 {
+        self.credits.values().sum::<u64>()
+            + self.earnings.values().sum::<u64>()
+            + self.subscriptions.values().map(|s| s.period_charge).sum::<u64>()
+    }
+
+    /// Get subscription by ID
+    pub fn get_subscription(&self, subscription_id: Uuid) -> Option<&Subscription> {
+        self.subscriptions.get(&subscription_id)
+    }
+
+    /// All subscriptions, in any state
+    pub fn subscriptions(&self) -> impl Iterator<Item = &Subscription> {
+        self.subscriptions.values()
+    }
+
+    /// Get all subscriptions for a subscriber
+    pub fn get_subscriber_subscriptions(&self, subscriber: &UserId) -> Vec<&Subscription> {
+        self.subscriptions
+            .values()
+            .filter(|s| &s.subscriber == subscriber)
+            .collect()
+    }
+
+    /// Whether `subscriber` has a subscription to `listing_id` granting access at `now`
+    pub fn has_active_subscription(&self, listing_id: ListingId, subscriber: &UserId, now: DateTime<Utc>) -> bool {
+        self.subscriptions
+            .values()
+            .any(|s| s.listing_id == listing_id && &s.subscriber == subscriber && s.is_active(now))
+    }
+
+    /// Publish queued events to `bus`, returning how many were sent
+    pub async fn publish_events(&mut self, bus: &EventBus) -> dchat_core::Result<usize> {
+        let events = std::mem::take(&mut self.events);
+        let count = events.len();
+        for event in events {
+            bus.publish(event).await?;
+        }
+        Ok(count)
+    }
+}
+
+impl Default for SubscriptionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escrow::tests::MemoryLedger;
+
+    fn days(n: i64) -> Duration {
+        Duration::days(n)
+    }
+
+    /// A subscriber with `amount` of prepaid credit
+    fn funded(ledger: &MemoryLedger, manager: &mut SubscriptionManager, amount: u64, now: DateTime<Utc>) -> UserId {
+        let subscriber = UserId::new();
+        ledger.fund(&subscriber, amount);
+        let payment = ledger.pay(&subscriber, manager.account(), amount);
+        manager.top_up(ledger, &subscriber, payment, now).unwrap();
+        subscriber
+    }
+
+    #[test]
+    fn test_top_up_requires_fresh_payment() {
+        let ledger = MemoryLedger::default();
+        let mut manager = SubscriptionManager::new();
+        let subscriber = UserId::new();
+        let now = Utc::now();
+        ledger.fund(&subscriber, 500);
+
+        let elsewhere = ledger.pay(&subscriber, &UserId::new(), 100);
+        assert!(manager.top_up(&ledger, &subscriber, elsewhere, now).is_err());
+
+        let payment = ledger.pay(&subscriber, manager.account(), 300);
+        assert!(manager.top_up(&ledger, &UserId::new(), payment, now).is_err());
+        assert_eq!(manager.top_up(&ledger, &subscriber, payment, now).unwrap(), 300);
+        assert!(manager.top_up(&ledger, &subscriber, payment, now).is_err());
+        assert_eq!(manager.credit(&subscriber), 300);
+
+        manager.withdraw(&ledger, &subscriber, 100).unwrap();
+        assert!(manager.withdraw(&ledger, &subscriber, 201).is_err());
+        assert_eq!(manager.credit(&subscriber), 200);
+        assert_eq!(ledger.balance(&subscriber), 200);
+    }
+
+    #[test]
+    fn test_periodic_charges_and_payouts() {
+        let ledger = MemoryLedger::default();
+        let mut manager = SubscriptionManager::new();
+        let creator = UserId::new();
+        let start = Utc::now();
+        let subscriber = funded(&ledger, &mut manager, 350, start);
+
+        let id = manager
+            .subscribe(&subscriber, Uuid::new_v4(), &creator, 100, None, start)
+            .unwrap();
+        assert_eq!(manager.credit(&subscriber), 250);
+
+        // Nothing is due mid-period, and the creator earns only ended periods
+        manager.process_due(start + days(10));
+        assert_eq!(manager.credit(&subscriber), 250);
+        assert_eq!(manager.earnings_of(&creator), 0);
+
+        // Two periods later both renewals have been charged
+        manager.process_due(start + days(2 * BILLING_PERIOD_DAYS));
+        let subscription = manager.get_subscription(id).unwrap();
+        assert_eq!(subscription.status, SubscriptionStatus::Active);
+        assert_eq!(subscription.period_end, start + days(3 * BILLING_PERIOD_DAYS));
+        assert_eq!(manager.credit(&subscriber), 50);
+        assert_eq!(manager.earnings_of(&creator), 200);
+
+        assert_eq!(manager.payout_creator(&ledger, &creator).unwrap(), 200);
+        assert_eq!(ledger.balance(&creator), 200);
+        assert_eq!(manager.earnings_of(&creator), 0);
+        assert_eq!(ledger.balance(manager.account()), manager.held_total());
+    }
+
+    #[test]
+    fn test_grace_period_then_expiry() {
+        let ledger = MemoryLedger::default();
+        let mut manager = SubscriptionManager::new();
+        let creator = UserId::new();
+        let listing = Uuid::new_v4();
+        let start = Utc::now();
+        let subscriber = funded(&ledger, &mut manager, 100, start);
+
+        let id = manager.subscribe(&subscriber, listing, &creator, 100, None, start).unwrap();
+        let period_end = start + days(BILLING_PERIOD_DAYS);
+
+        // The renewal fails but access lasts through the grace period
+        manager.process_due(period_end);
+        assert_eq!(manager.get_subscription(id).unwrap().status, SubscriptionStatus::PastDue);
+        assert!(manager.has_active_subscription(listing, &subscriber, period_end + days(1)));
+
+        // A top-up during grace renews from where the subscription lapsed
+        let payment = {
+            ledger.fund(&subscriber, 100);
+            ledger.pay(&subscriber, manager.account(), 100)
+        };
+        manager.top_up(&ledger, &subscriber, payment, period_end + days(1)).unwrap();
+        let subscription = manager.get_subscription(id).unwrap();
+        assert_eq!(subscription.status, SubscriptionStatus::Active);
+        assert_eq!(subscription.period_start, period_end);
+
+        // Without another top-up the next lapse expires after the grace period
+        let next_end = period_end + days(BILLING_PERIOD_DAYS);
+        manager.process_due(next_end + days(GRACE_PERIOD_DAYS));
+        assert_eq!(manager.get_subscription(id).unwrap().status, SubscriptionStatus::Expired);
+        assert!(!manager.has_active_subscription(listing, &subscriber, next_end + days(GRACE_PERIOD_DAYS)));
+        assert_eq!(manager.earnings_of(&creator), 200);
+    }
+
+    #[test]
+    fn test_cancel_prorates_refund() {
+        let ledger = MemoryLedger::default();
+        let mut manager = SubscriptionManager::new();
+        let creator = UserId::new();
+        let start = Utc::now();
+        let subscriber = funded(&ledger, &mut manager, 300, start);
+
+        let id = manager
+            .subscribe(&subscriber, Uuid::new_v4(), &creator, 300, None, start)
+            .unwrap();
+
+        // Only the subscriber can cancel
+        assert!(manager.cancel(id, &creator, start + days(10)).is_err());
+
+        // Ten of thirty days used: two thirds come back
+        assert_eq!(manager.cancel(id, &subscriber, start + days(10)).unwrap(), 200);
+        assert_eq!(manager.credit(&subscriber), 200);
+        assert_eq!(manager.earnings_of(&creator), 100);
+        assert!(manager.cancel(id, &subscriber, start + days(11)).is_err());
+
+        // A cancelled subscription is never charged again
+        manager.process_due(start + days(90));
+        assert_eq!(manager.credit(&subscriber), 200);
+        assert_eq!(ledger.balance(manager.account()), manager.held_total());
+    }
+
+    #[tokio::test]
+    async fn test_events_are_published() {
+        let ledger = MemoryLedger::default();
+        let mut manager = SubscriptionManager::new();
+        let creator = UserId::new();
+        let start = Utc::now();
+        let subscriber = funded(&ledger, &mut manager, 100, start);
+        let bus = EventBus::new(16);
+        let mut receiver = bus.subscribe();
+
+        let id = manager.subscribe(&subscriber, Uuid::new_v4(), &creator, 100, None, start).unwrap();
+        manager.process_due(start + days(BILLING_PERIOD_DAYS));
+        manager.process_due(start + days(BILLING_PERIOD_DAYS + GRACE_PERIOD_DAYS));
+
+        assert_eq!(manager.publish_events(&bus).await.unwrap(), 3);
+        assert!(matches!(receiver.recv().await.unwrap(), Event::SubscriptionStarted { subscription_id, .. } if subscription_id == id));
+        assert!(matches!(receiver.recv().await.unwrap(), Event::SubscriptionPastDue { .. }));
+        assert!(matches!(receiver.recv().await.unwrap(), Event::SubscriptionExpired { .. }));
+        assert_eq!(manager.publish_events(&bus).await.unwrap(), 0);
+    }
+}
@@ -5,12 +5,28 @@
 //! - NFT-based access badges
 //! - Staking requirements for membership
 //! - Cryptographic access proof verification
+//!
+//! Token-gated channels are checked against a `TokenLedger`, the currency
+//! chain in production, both when a user joins and on every membership
+//! check: a member who sells or unbonds below the minimum loses access.
 
 use dchat_core::types::{ChannelId, UserId};
 use dchat_core::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
+/// Token balances and bonds read by token-gated channels
+///
+/// Implemented by the currency chain client; defined here so this crate does
+/// not depend on the client.
+pub trait TokenLedger {
+    /// Free balance of `token_id` held by an account
+    fn token_balance(&self, account: &UserId, token_id: &str) -> Result<u64>;
+
+    /// Amount of `token_id` an account has bonded
+    fn bonded_balance(&self, account: &UserId, token_id: &str) -> Result<u64>;
+}
+
 /// Channel access control policy
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum AccessPolicy {
@@ -62,6 +78,7 @@ pub enum AccessPolicy {
 }
 
 /// Channel access manager
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelAccessManager {
     /// Map of channel ID to access policy
     policies: HashMap<ChannelId, AccessPolicy>,
@@ -69,17 +86,14 @@ pub struct ChannelAccessManager {
     /// Map of channel ID to current members
     members: HashMap<ChannelId, HashSet<UserId>>,
     
-    /// Map of user ID to their token holdings (for verification)
-    user_tokens: HashMap<UserId, HashMap<String, u64>>,
-    
     /// Map of user ID to their NFT ownership
     user_nfts: HashMap<UserId, HashSet<String>>,
     
     /// Map of user ID to their reputation scores
     user_reputation: HashMap<UserId, u8>,
     
-    /// Map of user ID to their staked amounts per channel
-    user_stakes: HashMap<(UserId, ChannelId), u64>,
+    /// Map of channel ID to the amounts users staked for it
+    user_stakes: HashMap<ChannelId, HashMap<UserId, u64>>,
 }
 
 impl ChannelAccessManager {
@@ -88,7 +102,6 @@ impl ChannelAccessManager {
         Self {
             policies: HashMap::new(),
             members: HashMap::new(),
-            user_tokens: HashMap::new(),
             user_nfts: HashMap::new(),
             user_reputation: HashMap::new(),
             user_stakes: HashMap::new(),
@@ -105,14 +118,6 @@ impl ChannelAccessManager {
         self.policies.get(channel_id)
     }
     
-    /// Update user's token holdings
-    pub fn update_user_tokens(&mut self, user_id: UserId, token_id: String, amount: u64) {
-        self.user_tokens
-            .entry(user_id)
-            .or_default()
-            .insert(token_id, amount);
-    }
-    
     /// Update user's NFT ownership
     pub fn add_user_nft(&mut self, user_id: UserId, nft_token_id: String) {
         self.user_nfts
@@ -135,27 +140,23 @@ impl ChannelAccessManager {
     
     /// Record user stake for a channel
     pub fn record_stake(&mut self, user_id: UserId, channel_id: ChannelId, amount: u64) {
-        self.user_stakes.insert((user_id, channel_id), amount);
+        self.user_stakes.entry(channel_id).or_default().insert(user_id, amount);
     }
     
     /// Check if user can access channel
-    pub fn can_access(&self, user_id: &UserId, channel_id: &ChannelId) -> Result<bool> {
-        // Check if already a member
-        if let Some(members) = self.members.get(channel_id) {
-            if members.contains(user_id) {
-                return Ok(true);
-            }
-        }
-        
+    ///
+    /// Members are held to the policy too, so token holdings are re-read
+    /// from `ledger` every time.
+    pub fn can_access(&self, user_id: &UserId, channel_id: &ChannelId, ledger: &dyn TokenLedger) -> Result<bool> {
         // Get access policy
         let policy = self.policies.get(channel_id)
             .ok_or_else(|| Error::validation("Channel not found"))?;
         
-        self.check_policy(user_id, policy)
+        self.check_policy(user_id, policy, ledger)
     }
     
     /// Check if user meets policy requirements
-    fn check_policy(&self, user_id: &UserId, policy: &AccessPolicy) -> Result<bool> {
+    fn check_policy(&self, user_id: &UserId, policy: &AccessPolicy, ledger: &dyn TokenLedger) -> Result<bool> {
         match policy {
             AccessPolicy::Public => Ok(true),
             
@@ -166,15 +167,17 @@ impl ChannelAccessManager {
             AccessPolicy::TokenGated {
                 token_id,
                 minimum_amount,
-                requires_bonding: _,
+                requires_bonding,
             } => {
-                let user_tokens = self.user_tokens.get(user_id);
-                if let Some(tokens) = user_tokens {
-                    if let Some(amount) = tokens.get(token_id) {
-                        return Ok(amount >= minimum_amount);
-                    }
-                }
-                Ok(false)
+                // Bonded tokens count either way; free ones only when
+                // bonding is not required
+                let bonded = ledger.bonded_balance(user_id, token_id)?;
+                let held = if *requires_bonding {
+                    bonded
+                } else {
+                    bonded.saturating_add(ledger.token_balance(user_id, token_id)?)
+                };
+                Ok(held >= *minimum_amount)
             }
             
             AccessPolicy::NftGated {
@@ -205,13 +208,13 @@ impl ChannelAccessManager {
             } => {
                 // Check if user has staked enough (implementation simplified)
                 // In production, would verify stake duration and on-chain commitment
-                Ok(self.user_stakes.values().any(|amount| amount >= minimum_stake))
+                Ok(self.user_stakes.values().flat_map(HashMap::values).any(|amount| amount >= minimum_stake))
             }
             
             AccessPolicy::Combined { policies } => {
                 // All policies must pass
                 for sub_policy in policies {
-                    if !self.check_policy(user_id, sub_policy)? {
+                    if !self.check_policy(user_id, sub_policy, ledger)? {
                         return Ok(false);
                     }
                 }
@@ -221,8 +224,8 @@ impl ChannelAccessManager {
     }
     
     /// Grant channel access to user (after policy check)
-    pub fn grant_access(&mut self, user_id: UserId, channel_id: ChannelId) -> Result<()> {
-        if !self.can_access(&user_id, &channel_id)? {
+    pub fn grant_access(&mut self, user_id: UserId, channel_id: ChannelId, ledger: &dyn TokenLedger) -> Result<()> {
+        if !self.can_access(&user_id, &channel_id, ledger)? {
             return Err(Error::validation("Access denied: requirements not met"));
         }
         
@@ -250,12 +253,13 @@ impl ChannelAccessManager {
             .unwrap_or_default()
     }
     
-    /// Check if user is member of channel
-    pub fn is_member(&self, user_id: &UserId, channel_id: &ChannelId) -> bool {
-        self.members
+    /// Check if user is member of channel and still meets its policy
+    pub fn is_member(&self, user_id: &UserId, channel_id: &ChannelId, ledger: &dyn TokenLedger) -> Result<bool> {
+        let joined = self.members
             .get(channel_id)
             .map(|members| members.contains(user_id))
-            .unwrap_or(false)
+            .unwrap_or(false);
+        Ok(joined && self.can_access(user_id, channel_id, ledger)?)
     }
     
     /// Invite user to private channel
@@ -283,6 +287,33 @@ impl Default for ChannelAccessManager {
 mod tests {
     use super::*;
     
+    /// Balances and bonds standing in for the currency chain
+    #[derive(Default)]
+    struct TestLedger {
+        balances: HashMap<(UserId, String), u64>,
+        bonds: HashMap<(UserId, String), u64>,
+    }
+    
+    impl TestLedger {
+        fn set_balance(&mut self, user_id: &UserId, token_id: &str, amount: u64) {
+            self.balances.insert((user_id.clone(), token_id.to_string()), amount);
+        }
+        
+        fn set_bond(&mut self, user_id: &UserId, token_id: &str, amount: u64) {
+            self.bonds.insert((user_id.clone(), token_id.to_string()), amount);
+        }
+    }
+    
+    impl TokenLedger for TestLedger {
+        fn token_balance(&self, account: &UserId, token_id: &str) -> Result<u64> {
+            Ok(self.balances.get(&(account.clone(), token_id.to_string())).copied().unwrap_or(0))
+        }
+        
+        fn bonded_balance(&self, account: &UserId, token_id: &str) -> Result<u64> {
+            Ok(self.bonds.get(&(account.clone(), token_id.to_string())).copied().unwrap_or(0))
+        }
+    }
+    
     fn create_test_user() -> UserId {
         UserId::new()
     }
@@ -294,19 +325,21 @@ mod tests {
     #[test]
     fn test_public_channel_access() {
         let mut manager = ChannelAccessManager::new();
+        let ledger = TestLedger::default();
         let channel = create_test_channel();
         let user = create_test_user();
         
         manager.set_policy(channel.clone(), AccessPolicy::Public);
         
-        assert!(manager.can_access(&user, &channel).unwrap());
-        manager.grant_access(user.clone(), channel.clone()).unwrap();
-        assert!(manager.is_member(&user, &channel));
+        assert!(manager.can_access(&user, &channel, &ledger).unwrap());
+        manager.grant_access(user.clone(), channel.clone(), &ledger).unwrap();
+        assert!(manager.is_member(&user, &channel, &ledger).unwrap());
     }
     
     #[test]
     fn test_private_channel_access() {
         let mut manager = ChannelAccessManager::new();
+        let ledger = TestLedger::default();
         let channel = create_test_channel();
         let user1 = create_test_user();
         let user2 = create_test_user();
@@ -319,15 +352,16 @@ mod tests {
         });
         
         // User1 is invited
-        assert!(manager.can_access(&user1, &channel).unwrap());
+        assert!(manager.can_access(&user1, &channel, &ledger).unwrap());
         
         // User2 is not invited
-        assert!(!manager.can_access(&user2, &channel).unwrap());
+        assert!(!manager.can_access(&user2, &channel, &ledger).unwrap());
     }
     
     #[test]
     fn test_token_gated_channel() {
         let mut manager = ChannelAccessManager::new();
+        let mut ledger = TestLedger::default();
         let channel = create_test_channel();
         let user = create_test_user();
         
@@ -341,20 +375,55 @@ mod tests {
         );
         
         // User doesn't have tokens
-        assert!(!manager.can_access(&user, &channel).unwrap());
+        assert!(!manager.can_access(&user, &channel, &ledger).unwrap());
         
         // Give user insufficient tokens
-        manager.update_user_tokens(user.clone(), "DCHAT".to_string(), 50);
-        assert!(!manager.can_access(&user, &channel).unwrap());
+        ledger.set_balance(&user, "DCHAT", 50);
+        assert!(!manager.can_access(&user, &channel, &ledger).unwrap());
         
         // Give user sufficient tokens
-        manager.update_user_tokens(user.clone(), "DCHAT".to_string(), 150);
-        assert!(manager.can_access(&user, &channel).unwrap());
+        ledger.set_balance(&user, "DCHAT", 150);
+        assert!(manager.can_access(&user, &channel, &ledger).unwrap());
+        manager.grant_access(user.clone(), channel.clone(), &ledger).unwrap();
+        
+        // Spending below the minimum ends membership
+        ledger.set_balance(&user, "DCHAT", 99);
+        assert!(!manager.is_member(&user, &channel, &ledger).unwrap());
+    }
+    
+    #[test]
+    fn test_bonded_token_channel() {
+        let mut manager = ChannelAccessManager::new();
+        let mut ledger = TestLedger::default();
+        let channel = create_test_channel();
+        let user = create_test_user();
+        
+        manager.set_policy(
+            channel.clone(),
+            AccessPolicy::TokenGated {
+                token_id: "GEM".to_string(),
+                minimum_amount: 100,
+                requires_bonding: true,
+            },
+        );
+        
+        // Free tokens don't count when bonding is required
+        ledger.set_balance(&user, "GEM", 500);
+        assert!(manager.grant_access(user.clone(), channel.clone(), &ledger).is_err());
+        
+        ledger.set_bond(&user, "GEM", 100);
+        manager.grant_access(user.clone(), channel.clone(), &ledger).unwrap();
+        assert!(manager.is_member(&user, &channel, &ledger).unwrap());
+        
+        // Unbonding ends membership
+        ledger.set_bond(&user, "GEM", 0);
+        assert!(!manager.is_member(&user, &channel, &ledger).unwrap());
     }
     
     #[test]
     fn test_nft_gated_channel() {
         let mut manager = ChannelAccessManager::new();
+        let ledger = TestLedger::default();
         let channel = create_test_channel();
         let user = create_test_user();
         
@@ -367,20 +436,21 @@ mod tests {
         );
         
         // User doesn't have NFT
-        assert!(!manager.can_access(&user, &channel).unwrap());
+        assert!(!manager.can_access(&user, &channel, &ledger).unwrap());
         
         // Give user wrong NFT
         manager.add_user_nft(user.clone(), "badge_002".to_string());
-        assert!(!manager.can_access(&user, &channel).unwrap());
+        assert!(!manager.can_access(&user, &channel, &ledger).unwrap());
         
         // Give user correct NFT
         manager.add_user_nft(user.clone(), "badge_001".to_string());
-        assert!(manager.can_access(&user, &channel).unwrap());
+        assert!(manager.can_access(&user, &channel, &ledger).unwrap());
     }
     
     #[test]
     fn test_reputation_gated_channel() {
         let mut manager = ChannelAccessManager::new();
+        let ledger = TestLedger::default();
         let channel = create_test_channel();
         let user = create_test_user();
         
@@ -392,20 +462,21 @@ mod tests {
         );
         
         // User has no reputation
-        assert!(!manager.can_access(&user, &channel).unwrap());
+        assert!(!manager.can_access(&user, &channel, &ledger).unwrap());
         
         // User has low reputation
         manager.update_reputation(user.clone(), 30);
-        assert!(!manager.can_access(&user, &channel).unwrap());
+        assert!(!manager.can_access(&user, &channel, &ledger).unwrap());
         
         // User has sufficient reputation
         manager.update_reputation(user.clone(), 75);
-        assert!(manager.can_access(&user, &channel).unwrap());
+        assert!(manager.can_access(&user, &channel, &ledger).unwrap());
     }
     
     #[test]
     fn test_combined_policy() {
         let mut manager = ChannelAccessManager::new();
+        let mut ledger = TestLedger::default();
         let channel = create_test_channel();
         let user = create_test_user();
         
@@ -426,20 +497,21 @@ mod tests {
         );
         
         // User has neither
-        assert!(!manager.can_access(&user, &channel).unwrap());
+        assert!(!manager.can_access(&user, &channel, &ledger).unwrap());
         
         // User has tokens but not reputation
-        manager.update_user_tokens(user.clone(), "DCHAT".to_string(), 150);
-        assert!(!manager.can_access(&user, &channel).unwrap());
+        ledger.set_balance(&user, "DCHAT", 150);
+        assert!(!manager.can_access(&user, &channel, &ledger).unwrap());
         
         // User has both
         manager.update_reputation(user.clone(), 75);
-        assert!(manager.can_access(&user, &channel).unwrap());
+        assert!(manager.can_access(&user, &channel, &ledger).unwrap());
     }
     
     #[test]
     fn test_invite_to_private_channel() {
         let mut manager = ChannelAccessManager::new();
+        let ledger = TestLedger::default();
         let channel = create_test_channel();
         let user = create_test_user();
         
@@ -451,25 +523,26 @@ mod tests {
         );
         
         // User not invited
-        assert!(!manager.can_access(&user, &channel).unwrap());
+        assert!(!manager.can_access(&user, &channel, &ledger).unwrap());
         
         // Invite user
         manager.invite_user(&channel, user.clone()).unwrap();
-        assert!(manager.can_access(&user, &channel).unwrap());
+        assert!(manager.can_access(&user, &channel, &ledger).unwrap());
     }
     
     #[test]
     fn test_revoke_access() {
         let mut manager = ChannelAccessManager::new();
+        let ledger = TestLedger::default();
         let channel = create_test_channel();
         let user = create_test_user();
         
         manager.set_policy(channel.clone(), AccessPolicy::Public);
-        manager.grant_access(user.clone(), channel.clone()).unwrap();
+        manager.grant_access(user.clone(), channel.clone(), &ledger).unwrap();
         
-        assert!(manager.is_member(&user, &channel));
+        assert!(manager.is_member(&user, &channel, &ledger).unwrap());
         
         manager.revoke_access(&user, &channel).unwrap();
-        assert!(!manager.is_member(&user, &channel));
+        assert!(!manager.is_member(&user, &channel, &ledger).unwrap());
     }
 }
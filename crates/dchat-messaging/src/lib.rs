@@ -7,6 +7,7 @@
 //! - Proof-of-delivery tracking
 //! - Message expiration and lifecycle
 //! - Advanced channel access control (token-gating, NFT verification)
+//! - Stealth-addressed direct messages
 
 pub mod channel_access;
 pub mod delivery;
@@ -14,9 +15,10 @@ pub mod expiration;
 pub mod media;
 pub mod ordering;
 pub mod queue;
+pub mod stealth;
 pub mod types;
 
-pub use channel_access::{AccessPolicy, ChannelAccessManager};
+pub use channel_access::{AccessPolicy, ChannelAccessManager, TokenLedger};
 pub use delivery::{DeliveryProof, DeliveryTracker};
 pub use expiration::{ExpirationPolicy, MessageExpiration};
 pub use media::{
@@ -26,4 +28,5 @@ pub use media::{
 };
 pub use ordering::{MessageOrder, SequenceNumber};
 pub use queue::{MessageQueue, OfflineQueue};
+pub use stealth::{StealthDirectMessage, StealthMessenger, StealthScanHandle, StealthScanService};
 pub use types::{Message, MessageBuilder, MessageType, MessageStatus};
@@ -0,0 +1,271 @@
+//! Stealth-addressed direct messages
+//!
+//! A stealth DM replaces the recipient field with a one-time stealth address,
+//! so relays only ever see an opaque [`StealthPayload`]. The sender's ID
+//! travels inside the ciphertext. Recipients run a [`StealthScanService`]
+//! that trial-decrypts every stealth payload seen on gossip with their view
+//! key; view tags keep the cost of a miss to one ECDH and one hash.
+
+use dchat_core::error::{Error, Result};
+use dchat_core::types::UserId;
+use dchat_privacy::stealth::{StealthAddress, StealthGenerator, StealthPayload, StealthScanner};
+use rand::seq::SliceRandom;
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+
+/// Maximum number of queued payloads scanned together
+const SCAN_BATCH_SIZE: usize = 512;
+
+/// Plaintext carried inside a stealth payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StealthDirectMessage {
+    /// Sender (only visible to the recipient)
+    pub sender: UserId,
+
+    /// End-to-end encrypted message body
+    pub encrypted_payload: Vec<u8>,
+
+    /// Time the sender sealed the message
+    pub sent_at: SystemTime,
+}
+
+/// Seals direct messages to stealth addresses
+pub struct StealthMessenger {
+    generator: StealthGenerator,
+
+    /// Decoy payloads published alongside every real message
+    decoys_per_message: usize,
+}
+
+impl StealthMessenger {
+    pub fn new<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        Self {
+            generator: StealthGenerator::new(rng),
+            decoys_per_message: 0,
+        }
+    }
+
+    /// Publish `count` decoys of the same size with every message
+    pub fn with_decoys(mut self, count: usize) -> Self {
+        self.decoys_per_message = count;
+        self
+    }
+
+    /// Seal a DM for `recipient`
+    ///
+    /// Returns wire-encoded payloads in random order: the real message plus
+    /// any configured decoys. All of them should be published.
+    pub fn seal<R: Rng + CryptoRng>(
+        &self,
+        sender: UserId,
+        recipient: &StealthAddress,
+        encrypted_payload: Vec<u8>,
+        rng: &mut R,
+    ) -> Result<Vec<Vec<u8>>> {
+        let message = StealthDirectMessage {
+            sender,
+            encrypted_payload,
+            sent_at: SystemTime::now(),
+        };
+        let plaintext = bincode::serialize(&message)
+            .map_err(|e| Error::messaging(format!("Failed to encode stealth DM: {}", e)))?;
+
+        let mut payloads = Vec::with_capacity(1 + self.decoys_per_message);
+        payloads.push(self.generator.create_payload(recipient, &plaintext, rng)?);
+        for _ in 0..self.decoys_per_message {
+            payloads.push(self.generator.create_decoy(plaintext.len(), rng)?);
+        }
+        payloads.shuffle(rng);
+
+        payloads.iter().map(encode_payload).collect()
+    }
+}
+
+/// Encode a stealth payload for the wire
+pub fn encode_payload(payload: &StealthPayload) -> Result<Vec<u8>> {
+    bincode::serialize(payload)
+        .map_err(|e| Error::messaging(format!("Failed to encode stealth payload: {}", e)))
+}
+
+/// Decode a stealth payload received from the wire
+pub fn decode_payload(wire: &[u8]) -> Result<StealthPayload> {
+    bincode::deserialize(wire)
+        .map_err(|e| Error::messaging(format!("Malformed stealth payload: {}", e)))
+}
+
+/// Trial-decrypt one wire payload, returning the DM if it is ours
+pub fn open_wire(scanner: &StealthScanner, wire: &[u8]) -> Option<StealthDirectMessage> {
+    let payload = decode_payload(wire).ok()?;
+    let plaintext = scanner.scan(&payload).ok()??;
+    bincode::deserialize(&plaintext).ok()
+}
+
+/// Trial-decrypt a batch of wire payloads, returning the DMs that are ours
+///
+/// Uses [`StealthScanner::scan_batch`], which is considerably cheaper per
+/// payload than calling [`open_wire`] in a loop.
+pub fn open_wire_batch(scanner: &StealthScanner, wires: &[Vec<u8>]) -> Vec<StealthDirectMessage> {
+    let payloads: Vec<StealthPayload> = wires.iter().filter_map(|w| decode_payload(w).ok()).collect();
+    scanner
+        .scan_batch(&payloads)
+        .into_iter()
+        .flatten()
+        .filter_map(|plaintext| bincode::deserialize(&plaintext).ok())
+        .collect()
+}
+
+/// Counters exposed by a running scan service
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StealthScanStats {
+    /// Payloads trial-decrypted
+    pub scanned: u64,
+
+    /// Payloads that were addressed to us
+    pub matched: u64,
+}
+
+#[derive(Default)]
+struct ScanCounters {
+    scanned: AtomicU64,
+    matched: AtomicU64,
+}
+
+/// Background task that scans gossip for stealth DMs
+pub struct StealthScanService;
+
+impl StealthScanService {
+    /// Spawn the scanner on the current tokio runtime
+    ///
+    /// Feed it raw stealth payloads through the returned handle; DMs addressed
+    /// to `scanner` come out of the receiver. The task exits once every
+    /// handle has been dropped.
+    pub fn spawn(
+        scanner: StealthScanner,
+        capacity: usize,
+    ) -> (StealthScanHandle, mpsc::Receiver<StealthDirectMessage>) {
+        let (input_tx, mut input_rx) = mpsc::channel::<Vec<u8>>(capacity);
+        let (output_tx, output_rx) = mpsc::channel(capacity);
+        let counters = Arc::new(ScanCounters::default());
+
+        let task_counters = counters.clone();
+        let scanner = Arc::new(scanner);
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(SCAN_BATCH_SIZE);
+            while input_rx.recv_many(&mut batch, SCAN_BATCH_SIZE).await > 0 {
+                let wires = std::mem::take(&mut batch);
+                task_counters.scanned.fetch_add(wires.len() as u64, Ordering::Relaxed);
+
+                // Scanning is CPU-bound; keep it off the async workers
+                let scanner = scanner.clone();
+                let opened = match tokio::task::spawn_blocking(move || open_wire_batch(&scanner, &wires)).await {
+                    Ok(opened) => opened,
+                    Err(_) => break,
+                };
+
+                task_counters.matched.fetch_add(opened.len() as u64, Ordering::Relaxed);
+                for message in opened {
+                    if output_tx.send(message).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        let handle = StealthScanHandle {
+            input: input_tx,
+            counters,
+        };
+        (handle, output_rx)
+    }
+}
+
+/// Handle for submitting payloads to a [`StealthScanService`]
+#[derive(Clone)]
+pub struct StealthScanHandle {
+    input: mpsc::Sender<Vec<u8>>,
+    counters: Arc<ScanCounters>,
+}
+
+impl StealthScanHandle {
+    /// Queue a wire payload for scanning
+    pub async fn submit(&self, wire: Vec<u8>) -> Result<()> {
+        self.input
+            .send(wire)
+            .await
+            .map_err(|_| Error::messaging("Stealth scanner stopped".to_string()))
+    }
+
+    /// Queue a wire payload without waiting; fails if the queue is full
+    pub fn try_submit(&self, wire: Vec<u8>) -> Result<()> {
+        self.input
+            .try_send(wire)
+            .map_err(|e| Error::messaging(format!("Stealth scanner unavailable: {}", e)))
+    }
+
+    /// Snapshot of the scan counters
+    pub fn stats(&self) -> StealthScanStats {
+        StealthScanStats {
+            scanned: self.counters.scanned.load(Ordering::Relaxed),
+            matched: self.counters.matched.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_seal_and_open_with_decoys() {
+        let mut rng = OsRng;
+        let recipient = StealthScanner::generate(&mut rng);
+        let sender = UserId::new();
+
+        let messenger = StealthMessenger::new(&mut rng).with_decoys(3);
+        let wires = messenger
+            .seal(sender.clone(), &recipient.address(), b"hello".to_vec(), &mut rng)
+            .unwrap();
+        assert_eq!(wires.len(), 4);
+
+        let opened: Vec<_> = wires.iter().filter_map(|w| open_wire(&recipient, w)).collect();
+        assert_eq!(opened.len(), 1);
+        assert_eq!(opened[0].sender, sender);
+        assert_eq!(opened[0].encrypted_payload, b"hello");
+
+        let stranger = StealthScanner::generate(&mut rng);
+        assert!(wires.iter().all(|w| open_wire(&stranger, w).is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_scan_service_delivers_only_own_messages() {
+        let mut rng = OsRng;
+        let recipient = StealthScanner::generate(&mut rng);
+        let other = StealthScanner::generate(&mut rng);
+        let messenger = StealthMessenger::new(&mut rng);
+
+        let mine = messenger
+            .seal(UserId::new(), &recipient.address(), b"mine".to_vec(), &mut rng)
+            .unwrap();
+        let theirs = messenger
+            .seal(UserId::new(), &other.address(), b"theirs".to_vec(), &mut rng)
+            .unwrap();
+
+        let (handle, mut inbox) = StealthScanService::spawn(recipient, 16);
+        handle.submit(b"not a payload".to_vec()).await.unwrap();
+        for wire in theirs.into_iter().chain(mine) {
+            handle.submit(wire).await.unwrap();
+        }
+
+        let received = inbox.recv().await.unwrap();
+        assert_eq!(received.encrypted_payload, b"mine");
+        assert_eq!(handle.stats().matched, 1);
+
+        drop(handle);
+        assert!(inbox.recv().await.is_none());
+    }
+}
@@ -39,6 +39,27 @@ pub enum DchatMessage {
         user_id: UserId,
         last_sequence: u64,
     },
+    /// Direct message addressed to a one-time stealth address
+    ///
+    /// Carries neither sender nor recipient; `payload` is an encoded
+    /// `dchat_privacy::StealthPayload` that only the recipient's scanner
+    /// can recognise.
+    StealthDirectMessage {
+        payload: Vec<u8>,
+    },
+    /// One step of a device pairing handshake
+    ///
+    /// `payload` is an encoded `dchat_identity::PairingMessage`; the pairing
+    /// code itself never appears on the wire.
+    DevicePairing {
+        payload: Vec<u8>,
+    },
+    /// Signed revocation of a device certificate, gossiped to all peers
+    ///
+    /// `payload` is an encoded `dchat_identity::DeviceRevocation`.
+    DeviceRevocation {
+        payload: Vec<u8>,
+    },
 }
 
 /// Combined network behavior for dchat
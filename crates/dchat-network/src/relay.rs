@@ -276,6 +276,19 @@ impl RelayNode {
                     DchatMessage::SyncRequest { user_id, last_sequence } => {
                         tracing::info!("🔄 Sync request from {} (last_seq: {})", user_id, last_sequence);
                     }
+                    DchatMessage::StealthDirectMessage { payload } => {
+                        tracing::debug!("🕶️ Relay received stealth message ({} bytes)", payload.len());
+                        
+                        let message_size = payload.len();
+                        self.total_bandwidth += message_size as u64;
+                        self.total_messages += 1;
+                    }
+                    DchatMessage::DevicePairing { payload } | DchatMessage::DeviceRevocation { payload } => {
+                        tracing::debug!("📱 Relay received device control message ({} bytes)", payload.len());
+                        
+                        self.total_bandwidth += payload.len() as u64;
+                        self.total_messages += 1;
+                    }
                 }
                 Ok(())
             }
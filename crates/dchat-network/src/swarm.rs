@@ -177,6 +177,19 @@ impl NetworkManager {
         Ok(())
     }
     
+    /// Unsubscribe from a channel topic; returns false if we were not subscribed
+    pub fn unsubscribe_from_channel(&mut self, channel_id: &str) -> Result<bool> {
+        let was_subscribed = self.swarm.behaviour_mut().unsubscribe_channel(channel_id)
+            .map_err(|e| Error::network(format!("Unsubscribe failed: {}", e)))?;
+        tracing::info!("🔕 Unsubscribed from channel: {}", channel_id);
+        Ok(was_subscribed)
+    }
+    
+    /// Number of peers we currently have a connection to
+    pub fn connected_peer_count(&self) -> usize {
+        self.swarm.connected_peers().count()
+    }
+    
     /// Publish message to channel
     pub fn publish_to_channel(&mut self, channel_id: &str, message: &DchatMessage) -> Result<()> {
         self.swarm.behaviour_mut().publish_to_channel(channel_id, message)
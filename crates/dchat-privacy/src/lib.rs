@@ -9,4 +9,4 @@ pub mod stealth;
 
 pub use zk_proofs::{ZkProof, ContactProof, ReputationProof};
 pub use blind_tokens::{BlindToken, BlindSigner, TokenIssuer};
-pub use stealth::{StealthPayload, StealthAddress};
+pub use stealth::{StealthPayload, StealthAddress, StealthGenerator, StealthScanner};
@@ -4,12 +4,28 @@
 // that prevents relay nodes from inspecting message content or
 // determining recipient identity.
 
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
 use dchat_core::{Result, Error};
 use dchat_crypto::keys::PublicKey;
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_TABLE;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
 use curve25519_dalek::Scalar;
 use rand::{Rng, CryptoRng};
 use serde::{Serialize, Deserialize};
 
+/// KDF context strings, one per value derived from the ECDH shared secret
+const VIEW_TAG_CONTEXT: &str = "dchat 2024 stealth view tag";
+const TAG_CONTEXT: &str = "dchat 2024 stealth payload tag";
+const ENCRYPTION_CONTEXT: &str = "dchat 2024 stealth encryption key";
+const ONE_TIME_KEY_CONTEXT: &str = "dchat 2024 stealth one-time key";
+
+/// Smallest batch worth handing to its own scan thread
+const MIN_SCAN_CHUNK: usize = 256;
+
+/// Bytes of the plaintext length prefix inside the padded ciphertext
+const LENGTH_PREFIX: usize = 4;
+
 /// A stealth address for anonymous message delivery
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StealthAddress {
@@ -24,12 +40,16 @@ pub struct StealthAddress {
 pub struct StealthPayload {
     /// Stealth address ephemeral key
     pub ephemeral_key: [u8; 32],
-    /// Encrypted message content
+    /// One-time address that replaces the recipient ID on the wire
+    pub one_time_address: [u8; 32],
+    /// Single-byte view tag; lets scanners reject ~255/256 of foreign
+    /// payloads right after the ECDH step
+    pub view_tag: u8,
+    /// ChaCha20-Poly1305 encryption of the length-prefixed message,
+    /// zero-padded to a uniform size so relays cannot see its length
     pub ciphertext: Vec<u8>,
     /// Message tag for recipient identification
     pub tag: [u8; 16],
-    /// Padding to uniform size
-    pub padding_size: usize,
 }
 
 /// Generator for stealth addresses and payloads
@@ -97,42 +117,38 @@ impl StealthGenerator {
         let mut ephemeral_bytes = [0u8; 32];
         rng.fill(&mut ephemeral_bytes);
         let ephemeral_scalar = Scalar::from_bytes_mod_order(ephemeral_bytes);
-        let ephemeral_point = &ephemeral_scalar * curve25519_dalek::constants::RISTRETTO_BASEPOINT_TABLE;
+        let ephemeral_point = &ephemeral_scalar * RISTRETTO_BASEPOINT_TABLE;
         let ephemeral_key = ephemeral_point.compress().to_bytes();
         
         // Derive shared secret from recipient's view key
-        let recipient_view_point = curve25519_dalek::ristretto::CompressedRistretto(recipient.view_key)
-            .decompress()
-            .ok_or_else(|| Error::Crypto("Invalid view key".to_string()))?;
-        let shared_secret_point = ephemeral_scalar * recipient_view_point;
-        let shared_secret = shared_secret_point.compress().to_bytes();
-        
-        // Derive encryption key from shared secret
-        let encryption_key = blake3::hash(&shared_secret);
-        
-        // Encrypt plaintext (simplified XOR for demonstration)
-        let mut ciphertext = plaintext.to_vec();
-        for (i, byte) in ciphertext.iter_mut().enumerate() {
-            *byte ^= encryption_key.as_bytes()[i % 32];
-        }
+        let recipient_view_point = decompress(&recipient.view_key, "Invalid view key")?;
+        let recipient_spend_point = decompress(&recipient.spend_key, "Invalid spend key")?;
+        let shared_secret = encode_shared_point(&(ephemeral_scalar * recipient_view_point));
+        
+        // One-time address: P = H(shared)·G + S
+        let one_time_point = &one_time_scalar(&shared_secret) * RISTRETTO_BASEPOINT_TABLE
+            + recipient_spend_point;
+        let one_time_address = one_time_point.compress().to_bytes();
         
-        // Create tag for recipient identification: H(view_key || ephemeral_key)
-        let mut tag_input = Vec::new();
-        tag_input.extend_from_slice(&recipient.view_key);
-        tag_input.extend_from_slice(&ephemeral_key);
-        let tag_hash = blake3::hash(&tag_input);
-        let mut tag = [0u8; 16];
-        tag.copy_from_slice(&tag_hash.as_bytes()[0..16]);
+        // Pad to uniform size (e.g., 1KB blocks); the real length travels
+        // inside the ciphertext
+        let length = u32::try_from(plaintext.len())
+            .map_err(|_| Error::Crypto("Stealth payload too large".to_string()))?;
+        let mut padded = Vec::with_capacity(Self::calculate_padded_size(plaintext.len() + LENGTH_PREFIX));
+        padded.extend_from_slice(&length.to_le_bytes());
+        padded.extend_from_slice(plaintext);
+        padded.resize(padded.capacity(), 0);
         
-        // Pad to uniform size (e.g., 1KB blocks)
-        let target_size = Self::calculate_padded_size(plaintext.len());
-        let padding_size = target_size - plaintext.len();
+        let ciphertext = seal(&shared_secret, &ephemeral_key, &one_time_address, &padded)?;
+        let view_tag = view_tag(&shared_secret);
+        let tag = payload_tag(&shared_secret, &ephemeral_key);
         
         Ok(StealthPayload {
             ephemeral_key,
+            one_time_address,
+            view_tag,
             ciphertext,
             tag,
-            padding_size,
         })
     }
 
@@ -158,12 +174,9 @@ impl StealthGenerator {
         let mut dummy_data = vec![0u8; size];
         rng.fill(&mut dummy_data[..]);
         
-        // Create dummy stealth address
-        let mut view_key = [0u8; 32];
-        let mut spend_key = [0u8; 32];
-        rng.fill(&mut view_key);
-        rng.fill(&mut spend_key);
-        let dummy_address = StealthAddress::new(view_key, spend_key);
+        // Create dummy stealth address (random bytes are rarely valid
+        // Ristretto encodings, so derive the points from random scalars)
+        let dummy_address = StealthScanner::generate(rng).address();
         
         self.create_payload(&dummy_address, &dummy_data, rng)
     }
@@ -175,57 +188,213 @@ impl StealthScanner {
         Self { view_key, spend_key }
     }
 
+    /// Generate a fresh view/spend key pair
+    pub fn generate<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        let mut view_bytes = [0u8; 32];
+        let mut spend_bytes = [0u8; 32];
+        rng.fill(&mut view_bytes);
+        rng.fill(&mut spend_bytes);
+        Self::from_secret_bytes(view_bytes, spend_bytes)
+    }
+
+    /// Create a scanner from 32-byte view and spend secrets, e.g. keys
+    /// derived from an identity, so its address survives restarts
+    pub fn from_secret_bytes(view_key: [u8; 32], spend_key: [u8; 32]) -> Self {
+        Self::new(
+            Scalar::from_bytes_mod_order(view_key),
+            Scalar::from_bytes_mod_order(spend_key),
+        )
+    }
+
+    /// Public stealth address senders use to reach this scanner
+    pub fn address(&self) -> StealthAddress {
+        StealthAddress::new(
+            (&self.view_key * RISTRETTO_BASEPOINT_TABLE).compress().to_bytes(),
+            (&self.spend_key * RISTRETTO_BASEPOINT_TABLE).compress().to_bytes(),
+        )
+    }
+
     /// Check if a payload is for this recipient
     /// 
-    /// Uses the tag to quickly filter without decryption
+    /// Uses the view tag and tag to filter without decryption
     pub fn is_for_me(&self, payload: &StealthPayload) -> Result<bool> {
-        // Reconstruct expected tag
-        let view_key_point = &self.view_key * curve25519_dalek::constants::RISTRETTO_BASEPOINT_TABLE;
-        let view_key_bytes = view_key_point.compress().to_bytes();
-        
-        let mut tag_input = Vec::new();
-        tag_input.extend_from_slice(&view_key_bytes);
-        tag_input.extend_from_slice(&payload.ephemeral_key);
-        let expected_tag_hash = blake3::hash(&tag_input);
-        let mut expected_tag = [0u8; 16];
-        expected_tag.copy_from_slice(&expected_tag_hash.as_bytes()[0..16]);
-        
-        Ok(expected_tag == payload.tag)
+        Ok(self.match_shared_secret(payload)?.is_some())
+    }
+
+    /// Trial-decrypt a payload seen on the network
+    ///
+    /// Returns `Ok(None)` for payloads addressed to someone else (including
+    /// decoys and malformed ephemeral keys), so a scanner can be fed an
+    /// untrusted gossip stream without treating misses as errors.
+    pub fn scan(&self, payload: &StealthPayload) -> Result<Option<Vec<u8>>> {
+        let shared_secret = match self.match_shared_secret(payload) {
+            Ok(Some(secret)) => secret,
+            Ok(None) | Err(_) => return Ok(None),
+        };
+        Ok(open(payload, &shared_secret))
     }
 
     /// Decrypt a stealth payload
     /// 
     /// Only works if is_for_me() returns true
     pub fn decrypt(&self, payload: &StealthPayload) -> Result<Vec<u8>> {
-        if !self.is_for_me(payload)? {
-            return Err(Error::Crypto("Payload not for this recipient".to_string()));
+        let shared_secret = self
+            .match_shared_secret(payload)?
+            .ok_or_else(|| Error::Crypto("Payload not for this recipient".to_string()))?;
+        open(payload, &shared_secret)
+            .ok_or_else(|| Error::Crypto("Stealth payload failed authentication".to_string()))
+    }
+
+    /// Private key controlling a payload's one-time address: H(shared) + s
+    pub fn one_time_secret(&self, payload: &StealthPayload) -> Result<Scalar> {
+        let shared_secret = self
+            .match_shared_secret(payload)?
+            .ok_or_else(|| Error::Crypto("Payload not for this recipient".to_string()))?;
+        Ok(one_time_scalar(&shared_secret) + self.spend_key)
+    }
+
+    /// Trial-decrypt a batch of payloads
+    ///
+    /// Equivalent to calling [`scan`](Self::scan) on each payload, but the
+    /// Ristretto encodings of the ECDH points share a single inversion per
+    /// chunk and chunks are spread across the available cores.
+    pub fn scan_batch(&self, payloads: &[StealthPayload]) -> Vec<Option<Vec<u8>>> {
+        let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = payloads.len().div_ceil(workers).max(MIN_SCAN_CHUNK);
+        if payloads.len() <= chunk_size {
+            return self.scan_chunk(payloads);
         }
         
-        // Derive shared secret
-        let ephemeral_point = curve25519_dalek::ristretto::CompressedRistretto(payload.ephemeral_key)
-            .decompress()
-            .ok_or_else(|| Error::Crypto("Invalid ephemeral key".to_string()))?;
-        let shared_secret_point = self.view_key * ephemeral_point;
-        let shared_secret = shared_secret_point.compress().to_bytes();
-        
-        // Derive decryption key
-        let decryption_key = blake3::hash(&shared_secret);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = payloads
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || self.scan_chunk(chunk)))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("stealth scan worker panicked"))
+                .collect()
+        })
+    }
+
+    fn scan_chunk(&self, payloads: &[StealthPayload]) -> Vec<Option<Vec<u8>>> {
+        let points: Vec<Option<RistrettoPoint>> = payloads
+            .iter()
+            .map(|p| CompressedRistretto(p.ephemeral_key).decompress().map(|r| self.view_key * r))
+            .collect();
+        let mut encoded = RistrettoPoint::double_and_compress_batch(points.iter().flatten()).into_iter();
         
-        // Decrypt (simplified XOR)
-        let mut plaintext = payload.ciphertext.clone();
-        for (i, byte) in plaintext.iter_mut().enumerate() {
-            *byte ^= decryption_key.as_bytes()[i % 32];
+        payloads
+            .iter()
+            .zip(points)
+            .map(|(payload, point)| {
+                point?;
+                let shared_secret = encoded.next()?.to_bytes();
+                if !self.check_shared_secret(payload, &shared_secret) {
+                    return None;
+                }
+                open(payload, &shared_secret)
+            })
+            .collect()
+    }
+
+    /// Run the ECDH step and the view tag, tag and one-time address checks
+    fn match_shared_secret(&self, payload: &StealthPayload) -> Result<Option<[u8; 32]>> {
+        let ephemeral_point = decompress(&payload.ephemeral_key, "Invalid ephemeral key")?;
+        let shared_secret = encode_shared_point(&(self.view_key * ephemeral_point));
+        Ok(self.check_shared_secret(payload, &shared_secret).then_some(shared_secret))
+    }
+
+    /// The view tag is checked first so foreign payloads cost one hash on top
+    /// of the ECDH step
+    fn check_shared_secret(&self, payload: &StealthPayload, shared_secret: &[u8; 32]) -> bool {
+        if view_tag(shared_secret) != payload.view_tag {
+            return false;
         }
-        
-        // Remove padding
-        if plaintext.len() > payload.padding_size {
-            plaintext.truncate(plaintext.len() - payload.padding_size);
+        if payload_tag(shared_secret, &payload.ephemeral_key) != payload.tag {
+            return false;
         }
         
-        Ok(plaintext)
+        let expected_address = &(one_time_scalar(shared_secret) + self.spend_key)
+            * RISTRETTO_BASEPOINT_TABLE;
+        expected_address.compress().to_bytes() == payload.one_time_address
     }
 }
 
+/// Shared secrets are the encoding of the doubled ECDH point, which lets
+/// scanners compute a whole batch of them with one inversion
+fn encode_shared_point(point: &RistrettoPoint) -> [u8; 32] {
+    RistrettoPoint::double_and_compress_batch(std::iter::once(point))[0].to_bytes()
+}
+
+fn decompress(bytes: &[u8; 32], what: &str) -> Result<RistrettoPoint> {
+    CompressedRistretto(*bytes)
+        .decompress()
+        .ok_or_else(|| Error::Crypto(what.to_string()))
+}
+
+fn view_tag(shared_secret: &[u8; 32]) -> u8 {
+    blake3::derive_key(VIEW_TAG_CONTEXT, shared_secret)[0]
+}
+
+fn payload_tag(shared_secret: &[u8; 32], ephemeral_key: &[u8; 32]) -> [u8; 16] {
+    let mut hasher = blake3::Hasher::new_derive_key(TAG_CONTEXT);
+    hasher.update(shared_secret);
+    hasher.update(ephemeral_key);
+    let mut tag = [0u8; 16];
+    tag.copy_from_slice(&hasher.finalize().as_bytes()[0..16]);
+    tag
+}
+
+fn one_time_scalar(shared_secret: &[u8; 32]) -> Scalar {
+    let mut wide = [0u8; 64];
+    blake3::Hasher::new_derive_key(ONE_TIME_KEY_CONTEXT)
+        .update(shared_secret)
+        .finalize_xof()
+        .fill(&mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+/// ChaCha20-Poly1305 keyed from the shared secret, binding the payload's
+/// public keys as associated data
+///
+/// Every payload has a fresh ephemeral key and so a fresh encryption key,
+/// which makes the all-zero nonce safe.
+fn cipher(shared_secret: &[u8; 32]) -> ChaCha20Poly1305 {
+    ChaCha20Poly1305::new(&blake3::derive_key(ENCRYPTION_CONTEXT, shared_secret).into())
+}
+
+fn associated_data(ephemeral_key: &[u8; 32], one_time_address: &[u8; 32]) -> [u8; 64] {
+    let mut aad = [0u8; 64];
+    aad[..32].copy_from_slice(ephemeral_key);
+    aad[32..].copy_from_slice(one_time_address);
+    aad
+}
+
+fn seal(
+    shared_secret: &[u8; 32],
+    ephemeral_key: &[u8; 32],
+    one_time_address: &[u8; 32],
+    padded: &[u8],
+) -> Result<Vec<u8>> {
+    let aad = associated_data(ephemeral_key, one_time_address);
+    cipher(shared_secret)
+        .encrypt(&Nonce::default(), Payload { msg: padded, aad: &aad })
+        .map_err(|_| Error::Crypto("Stealth payload encryption failed".to_string()))
+}
+
+/// Decrypt and unpad a payload; `None` if it fails authentication or its
+/// length prefix is out of range
+fn open(payload: &StealthPayload, shared_secret: &[u8; 32]) -> Option<Vec<u8>> {
+    let aad = associated_data(&payload.ephemeral_key, &payload.one_time_address);
+    let padded = cipher(shared_secret)
+        .decrypt(&Nonce::default(), Payload { msg: &payload.ciphertext, aad: &aad })
+        .ok()?;
+    let (length, body) = padded.split_first_chunk::<LENGTH_PREFIX>()?;
+    let length = u32::from_le_bytes(*length) as usize;
+    body.get(..length).map(<[u8]>::to_vec)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -299,7 +468,93 @@ mod tests {
         
         // Decrypt and verify
         let decrypted = scanner.decrypt(&payload).unwrap();
-        assert_eq!(&decrypted[..message.len()], message);
+        assert_eq!(decrypted, message);
+    }
+
+    #[test]
+    fn test_long_messages_round_trip_at_a_padded_size() {
+        let mut rng = OsRng;
+        let generator = StealthGenerator::new(&mut rng);
+        let recipient = StealthScanner::generate(&mut rng);
+        
+        for (len, padded) in [(200, 256), (1000, 1024), (5000, 8192)] {
+            let message: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let payload = generator.create_payload(&recipient.address(), &message, &mut rng).unwrap();
+            // Padded body plus the Poly1305 tag
+            assert_eq!(payload.ciphertext.len(), padded + 16);
+            assert_eq!(recipient.decrypt(&payload).unwrap(), message);
+            assert_eq!(recipient.scan_batch(std::slice::from_ref(&payload))[0].as_ref(), Some(&message));
+        }
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_is_rejected() {
+        let mut rng = OsRng;
+        let generator = StealthGenerator::new(&mut rng);
+        let recipient = StealthScanner::generate(&mut rng);
+        
+        let mut payload = generator.create_payload(&recipient.address(), b"pay 10", &mut rng).unwrap();
+        payload.ciphertext[5] ^= 1;
+        assert!(recipient.decrypt(&payload).is_err());
+        assert!(recipient.scan(&payload).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_scan_ignores_foreign_payloads_and_decoys() {
+        let mut rng = OsRng;
+        let generator = StealthGenerator::new(&mut rng);
+        let alice = StealthScanner::generate(&mut rng);
+        let bob = StealthScanner::generate(&mut rng);
+        
+        let payload = generator.create_payload(&alice.address(), b"hi alice", &mut rng).unwrap();
+        let decoy = generator.create_decoy(64, &mut rng).unwrap();
+        
+        assert_eq!(alice.scan(&payload).unwrap().as_deref(), Some(&b"hi alice"[..]));
+        assert!(bob.scan(&payload).unwrap().is_none());
+        assert!(alice.scan(&decoy).unwrap().is_none());
+        assert!(bob.decrypt(&payload).is_err());
+        
+        // Garbage ephemeral keys are a miss, not an error
+        let mut garbage = payload.clone();
+        garbage.ephemeral_key = [0xff; 32];
+        assert!(alice.scan(&garbage).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_scan_batch_matches_single_scan() {
+        let mut rng = OsRng;
+        let generator = StealthGenerator::new(&mut rng);
+        let recipient = StealthScanner::generate(&mut rng);
+        
+        let mut payloads: Vec<_> = (0..300)
+            .map(|_| generator.create_decoy(32, &mut rng).unwrap())
+            .collect();
+        payloads[17] = generator.create_payload(&recipient.address(), b"first", &mut rng).unwrap();
+        payloads[299] = generator.create_payload(&recipient.address(), b"last", &mut rng).unwrap();
+        payloads[150].ephemeral_key = [0xff; 32];
+        
+        let batch = recipient.scan_batch(&payloads);
+        let single: Vec<_> = payloads.iter().map(|p| recipient.scan(p).unwrap()).collect();
+        assert_eq!(batch, single);
+        assert_eq!(batch.iter().flatten().count(), 2);
+        assert_eq!(batch[17].as_deref(), Some(&b"first"[..]));
+    }
+
+    #[test]
+    fn test_one_time_address_is_unlinkable_and_spendable() {
+        let mut rng = OsRng;
+        let generator = StealthGenerator::new(&mut rng);
+        let recipient = StealthScanner::generate(&mut rng);
+        let address = recipient.address();
+        
+        let first = generator.create_payload(&address, b"one", &mut rng).unwrap();
+        let second = generator.create_payload(&address, b"two", &mut rng).unwrap();
+        assert_ne!(first.one_time_address, second.one_time_address);
+        assert_ne!(first.one_time_address, address.spend_key);
+        
+        let secret = recipient.one_time_secret(&first).unwrap();
+        let public = (&secret * RISTRETTO_BASEPOINT_TABLE).compress().to_bytes();
+        assert_eq!(public, first.one_time_address);
     }
 
     #[test]
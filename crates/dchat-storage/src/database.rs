@@ -44,8 +44,8 @@ impl Default for DatabaseConfig {
 /// Database handle
 #[derive(Clone)]
 pub struct Database {
-    pool: SqlitePool,
-    config: DatabaseConfig,
+    pub(crate) pool: SqlitePool,
+    pub(crate) config: DatabaseConfig,
 }
 
 impl Database {
@@ -6,13 +6,17 @@
 //! - Message deduplication via content addressing
 //! - TTL-based data lifecycle management
 //! - Storage economics (bonds, quotas)
+//! - Persistent governance, tokenomics and marketplace state
+//! - Node-type-aware pruning with Merkle checkpoints and state sync
 
 pub mod backup;
 pub mod database;
 pub mod deduplication;
 pub mod file_upload;
 pub mod lifecycle;
+pub mod pruning;
 pub mod schema;
+pub mod state;
 
 pub use backup::{BackupManager, EncryptedBackup};
 pub use database::{Database, DatabaseConfig, MessageRow};
@@ -21,4 +25,6 @@ pub use file_upload::{
     FileUploadManager, MediaFileType, StorageStats, UploadConfig, UploadedFile,
 };
 pub use lifecycle::{LifecycleManager, TtlConfig};
+pub use pruning::{BlockRow, NodePruner, PruneReport, StateSnapshot, SyncedCheckpoint};
 pub use schema::Schema;
+pub use state::Persisted;
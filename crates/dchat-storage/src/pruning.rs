@@ -0,0 +1,594 @@
+//! Pruned-node storage
+//!
+//! Deletes message content and old blocks according to the node type's
+//! retention (see [`PruningManager`]) while keeping a Merkle checkpoint over
+//! everything deleted:
+//! - Archive nodes delete nothing.
+//! - Full nodes delete messages past retention, keep the leaves of every
+//!   checkpoint (so they can prove a pruned message existed) and one
+//!   checkpoint interval of blocks below the latest checkpoint.
+//! - Light nodes delete messages past retention, keep only checkpoint roots
+//!   and keep blocks from the latest checkpoint onwards.
+//!
+//! Messages in the governance policy's priority channels are never deleted.
+//! SQLite reuses the pages freed by deletions, so the database file stops
+//! growing once the retention window is full.
+//!
+//! A new node can bootstrap from another node's [`StateSnapshot`]: its
+//! checkpoints plus the blocks from the latest checkpoint onwards.
+
+use crate::database::Database;
+use dchat_chain::pruning::{CheckpointRecord, MerkleCheckpoint, MerkleProof, NonInclusionProof, NodeType, PruningConfig, PruningManager};
+use dchat_core::error::{Error, Result};
+use dchat_core::types::MessageId;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+/// Domain separator for block hashes
+const BLOCK_DOMAIN: &[u8] = b"dchat-block-v1";
+
+/// A chain block as stored by this node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockRow {
+    pub height: u64,
+    pub hash: Vec<u8>,
+    pub parent_hash: Vec<u8>,
+    pub timestamp: i64,
+    pub data: Vec<u8>,
+}
+
+impl BlockRow {
+    /// Create a block on top of `parent_hash`
+    pub fn new(height: u64, parent_hash: Vec<u8>, timestamp: i64, data: Vec<u8>) -> Self {
+        let mut block = Self {
+            height,
+            hash: Vec::new(),
+            parent_hash,
+            timestamp,
+            data,
+        };
+        block.hash = block.compute_hash().to_vec();
+        block
+    }
+
+    /// Hash committing to every field but `hash`
+    pub fn compute_hash(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(BLOCK_DOMAIN);
+        hasher.update(&self.height.to_be_bytes());
+        hasher.update(&(self.parent_hash.len() as u32).to_be_bytes());
+        hasher.update(&self.parent_hash);
+        hasher.update(&self.timestamp.to_be_bytes());
+        hasher.update(&self.data);
+        *hasher.finalize().as_bytes()
+    }
+
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Self {
+        Self {
+            height: row.get::<i64, _>("height") as u64,
+            hash: row.get("hash"),
+            parent_hash: row.get("parent_hash"),
+            timestamp: row.get("timestamp"),
+            data: row.get("data"),
+        }
+    }
+}
+
+impl Database {
+    /// Append a block
+    ///
+    /// Fails unless the block's hash is correct and it extends the current
+    /// tip.
+    pub async fn insert_block(&self, block: &BlockRow) -> Result<()> {
+        if block.hash != block.compute_hash() {
+            return Err(Error::storage(format!("Block {} has an invalid hash", block.height)));
+        }
+        if let Some(tip) = self.latest_block().await? {
+            if block.height != tip.height + 1 || block.parent_hash != tip.hash {
+                return Err(Error::storage(format!(
+                    "Block {} does not extend tip {}",
+                    block.height, tip.height
+                )));
+            }
+        }
+
+        sqlx::query("INSERT INTO blocks (height, hash, parent_hash, timestamp, data) VALUES (?, ?, ?, ?, ?)")
+            .bind(block.height as i64)
+            .bind(&block.hash)
+            .bind(&block.parent_hash)
+            .bind(block.timestamp)
+            .bind(&block.data)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to insert block: {}", e)))?;
+        Ok(())
+    }
+
+    /// Highest stored block
+    pub async fn latest_block(&self) -> Result<Option<BlockRow>> {
+        let row = sqlx::query("SELECT * FROM blocks ORDER BY height DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to get latest block: {}", e)))?;
+        Ok(row.as_ref().map(BlockRow::from_row))
+    }
+
+    /// Stored blocks at or above `height`, lowest first
+    pub async fn blocks_from(&self, height: u64) -> Result<Vec<BlockRow>> {
+        let rows = sqlx::query("SELECT * FROM blocks WHERE height >= ? ORDER BY height ASC")
+            .bind(height as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to get blocks: {}", e)))?;
+        Ok(rows.iter().map(BlockRow::from_row).collect())
+    }
+
+    /// Delete blocks below `height`
+    pub async fn delete_blocks_below(&self, height: u64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM blocks WHERE height < ?")
+            .bind(height as i64)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to delete blocks: {}", e)))?;
+        Ok(result.rows_affected())
+    }
+
+    /// Bytes the database occupies on disk, including its write-ahead log
+    pub async fn disk_usage(&self) -> Result<u64> {
+        let pages: i64 = sqlx::query_scalar("PRAGMA page_count")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to read page count: {}", e)))?;
+        let page_size: i64 = sqlx::query_scalar("PRAGMA page_size")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to read page size: {}", e)))?;
+
+        let mut wal = self.config.path.clone().into_os_string();
+        wal.push("-wal");
+        let wal_size = std::fs::metadata(wal).map(|m| m.len()).unwrap_or(0);
+        Ok((pages * page_size) as u64 + wal_size)
+    }
+
+    /// Fold the write-ahead log back into the database and truncate it
+    async fn truncate_wal(&self) -> Result<()> {
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to checkpoint WAL: {}", e)))?;
+        Ok(())
+    }
+
+    /// Delete messages older than `cutoff` outside `keep_channels`,
+    /// returning the IDs and sizes of the deleted rows
+    async fn delete_messages_before(&self, cutoff: i64, keep_channels: &[String]) -> Result<Vec<(String, i64)>> {
+        let mut sql = String::from("DELETE FROM messages WHERE timestamp < ?");
+        if !keep_channels.is_empty() {
+            let placeholders = vec!["?"; keep_channels.len()].join(", ");
+            sql.push_str(&format!(" AND (channel_id IS NULL OR channel_id NOT IN ({}))", placeholders));
+        }
+        sql.push_str(" RETURNING id, size");
+
+        let mut query = sqlx::query(&sql).bind(cutoff);
+        for channel in keep_channels {
+            query = query.bind(channel);
+        }
+        let rows = query
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to prune messages: {}", e)))?;
+        Ok(rows.iter().map(|row| (row.get("id"), row.get("size"))).collect())
+    }
+}
+
+/// A checkpoint as exchanged during state sync
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncedCheckpoint {
+    pub checkpoint: MerkleCheckpoint,
+    /// Hash of the block at the checkpoint's height, if the node had one
+    pub block_hash: Option<Vec<u8>>,
+    /// Covered message IDs, if the sending node kept them
+    pub messages: Option<Vec<MessageId>>,
+}
+
+/// What a new node needs to join without replaying history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    /// Checkpoints, lowest height first
+    pub checkpoints: Vec<SyncedCheckpoint>,
+    /// Blocks from the latest checkpoint onwards
+    pub blocks: Vec<BlockRow>,
+}
+
+impl StateSnapshot {
+    /// Checkpoint the snapshot's blocks start from
+    ///
+    /// Callers should compare its root and block hash against a trusted
+    /// source before bootstrapping from the snapshot.
+    pub fn latest_checkpoint(&self) -> Option<&SyncedCheckpoint> {
+        self.checkpoints.last()
+    }
+}
+
+/// Outcome of one pruning pass
+#[derive(Debug, Clone, Default)]
+pub struct PruneReport {
+    pub messages_deleted: u64,
+    pub bytes_freed: u64,
+    pub blocks_deleted: u64,
+    /// Checkpoint created by this pass, if any
+    pub checkpoint: Option<MerkleCheckpoint>,
+}
+
+/// Applies a [`PruningManager`]'s policy to a node's database
+pub struct NodePruner {
+    db: Database,
+    manager: PruningManager,
+}
+
+impl NodePruner {
+    /// Open a pruner over `db`, restoring the checkpoints stored there
+    ///
+    /// Deleted content is gone from this node too, so the manager's local
+    /// cache is not used and checkpoint leaves stay on disk rather than in
+    /// memory.
+    pub async fn open(db: Database, config: PruningConfig) -> Result<Self> {
+        let manager = PruningManager::new(PruningConfig {
+            retain_local_cache: false,
+            checkpoint_dir: None,
+            ..config
+        });
+        let mut pruner = Self { db, manager };
+
+        for stored in pruner.stored_checkpoints(false).await? {
+            pruner.manager.import_checkpoint_root(stored.checkpoint);
+        }
+        Ok(pruner)
+    }
+
+    /// Create a node from another node's snapshot
+    ///
+    /// Verifies every checkpoint that comes with its leaves and that the
+    /// blocks form a chain starting at the latest checkpoint's block. The
+    /// database must not have any blocks yet.
+    pub async fn bootstrap(db: Database, config: PruningConfig, snapshot: StateSnapshot) -> Result<Self> {
+        if db.latest_block().await?.is_some() {
+            return Err(Error::AlreadyExists("Database already has blocks".to_string()));
+        }
+
+        let mut pruner = Self::open(db, config).await?;
+        for synced in &snapshot.checkpoints {
+            if let Some(messages) = &synced.messages {
+                let record = CheckpointRecord {
+                    checkpoint: synced.checkpoint.clone(),
+                    messages: messages.clone(),
+                };
+                pruner.manager.import_checkpoint(record)?;
+                pruner.manager.discard_leaves(&synced.checkpoint.checkpoint_id);
+            }
+        }
+
+        let mut parent: Option<&BlockRow> = None;
+        for block in &snapshot.blocks {
+            let linked = match parent {
+                Some(parent) => block.height == parent.height + 1 && block.parent_hash == parent.hash,
+                None => true,
+            };
+            if !linked || block.hash != block.compute_hash() {
+                return Err(Error::chain(format!("Snapshot block {} does not verify", block.height)));
+            }
+            parent = Some(block);
+        }
+
+        if let Some(anchor) = snapshot.latest_checkpoint() {
+            if let Some(block_hash) = &anchor.block_hash {
+                let first = snapshot.blocks.first();
+                let anchored = first.is_some_and(|b| b.height == anchor.checkpoint.height && &b.hash == block_hash);
+                if !anchored {
+                    return Err(Error::chain("Snapshot blocks do not start at the latest checkpoint".to_string()));
+                }
+            }
+        }
+
+        for synced in snapshot.checkpoints {
+            pruner.store_checkpoint(&synced.checkpoint, synced.block_hash.as_deref(), synced.messages.as_deref()).await?;
+            pruner.manager.import_checkpoint_root(synced.checkpoint);
+        }
+        for block in &snapshot.blocks {
+            pruner.db.insert_block(block).await?;
+        }
+        Ok(pruner)
+    }
+
+    /// The pruning policy in force
+    pub fn manager(&self) -> &PruningManager {
+        &self.manager
+    }
+
+    /// Mutable access, e.g. to install a governance policy
+    pub fn manager_mut(&mut self) -> &mut PruningManager {
+        &mut self.manager
+    }
+
+    /// Delete everything past retention as of `now` (unix seconds)
+    ///
+    /// Deleted messages are checkpointed at the current tip. A checkpoint is
+    /// also taken once the tip is a full checkpoint interval past the last
+    /// one, so blocks can be pruned on a quiet chain.
+    pub async fn prune(&mut self, now: i64) -> Result<PruneReport> {
+        let mut report = PruneReport::default();
+
+        let deleted = match self.manager.message_retention() {
+            Some(retention) => {
+                let cutoff = now.saturating_sub(retention.as_secs() as i64);
+                self.db.delete_messages_before(cutoff, &self.manager.priority_channels()).await?
+            }
+            None => Vec::new(),
+        };
+        report.messages_deleted = deleted.len() as u64;
+        report.bytes_freed = deleted.iter().map(|(_, size)| *size as u64).sum();
+
+        let tip = self.db.latest_block().await?;
+        let tip_height = tip.as_ref().map_or(0, |b| b.height);
+        let last_height = self.manager.latest_checkpoint().map(|c| c.height);
+        let interval_passed = tip.is_some()
+            && last_height.is_none_or(|h| tip_height >= h.saturating_add(self.manager.checkpoint_interval()));
+
+        if !deleted.is_empty() || interval_passed {
+            let ids: Vec<MessageId> = deleted.iter().map(|(id, _)| message_id(id)).collect();
+            let result = self.manager.record_pruning(tip_height, &ids, report.bytes_freed)?;
+            let leaves = (self.manager.node_type() != NodeType::Light).then_some(ids.as_slice());
+            self.store_checkpoint(&result.checkpoint, tip.as_ref().map(|b| b.hash.as_slice()), leaves).await?;
+            self.manager.discard_leaves(&result.checkpoint.checkpoint_id);
+            report.checkpoint = Some(result.checkpoint);
+        }
+
+        if let (Some(window), Some(latest)) = (self.manager.block_retention(), self.manager.latest_checkpoint()) {
+            report.blocks_deleted = self.db.delete_blocks_below(latest.height.saturating_sub(window)).await?;
+        }
+
+        if report.messages_deleted > 0 || report.blocks_deleted > 0 {
+            self.db.truncate_wal().await?;
+        }
+        Ok(report)
+    }
+
+    /// Prove a pruned message was covered by a checkpoint
+    pub async fn generate_proof(&mut self, message: &MessageId, checkpoint_id: &str) -> Result<MerkleProof> {
+        self.with_leaves(checkpoint_id, |manager| manager.generate_proof(message, checkpoint_id)).await
+    }
+
+    /// Prove a message was not covered by a checkpoint
+    pub async fn generate_non_inclusion_proof(&mut self, message: &MessageId, checkpoint_id: &str) -> Result<NonInclusionProof> {
+        self.with_leaves(checkpoint_id, |manager| manager.generate_non_inclusion_proof(message, checkpoint_id))
+            .await
+    }
+
+    /// Everything a new node needs to bootstrap from this one
+    pub async fn snapshot(&self) -> Result<StateSnapshot> {
+        let checkpoints = self.stored_checkpoints(true).await?;
+        let from = checkpoints.last().map_or(0, |c| c.checkpoint.height);
+        Ok(StateSnapshot {
+            checkpoints,
+            blocks: self.db.blocks_from(from).await?,
+        })
+    }
+
+    async fn with_leaves<T>(&mut self, checkpoint_id: &str, f: impl FnOnce(&PruningManager) -> Result<T>) -> Result<T> {
+        let row = sqlx::query("SELECT block_hash, checkpoint, leaves FROM pruning_checkpoints WHERE id = ?")
+            .bind(checkpoint_id)
+            .fetch_optional(&self.db.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to load checkpoint: {}", e)))?;
+        let stored = row
+            .as_ref()
+            .map(decode_checkpoint)
+            .transpose()?
+            .ok_or_else(|| Error::NotFound(format!("Checkpoint not found: {}", checkpoint_id)))?;
+        let messages = stored.messages
+            .ok_or_else(|| Error::NotFound(format!("Leaves of checkpoint {} were not kept", checkpoint_id)))?;
+
+        self.manager.import_checkpoint(CheckpointRecord {
+            checkpoint: stored.checkpoint,
+            messages,
+        })?;
+        let result = f(&self.manager);
+        self.manager.discard_leaves(checkpoint_id);
+        result
+    }
+
+    async fn store_checkpoint(
+        &self,
+        checkpoint: &MerkleCheckpoint,
+        block_hash: Option<&[u8]>,
+        leaves: Option<&[MessageId]>,
+    ) -> Result<()> {
+        let leaves: Option<Vec<u8>> = leaves.map(|ids| ids.iter().flat_map(|id| *id.0.as_bytes()).collect());
+        sqlx::query(
+            "INSERT INTO pruning_checkpoints (id, height, block_hash, checkpoint, leaves) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&checkpoint.checkpoint_id)
+        .bind(checkpoint.height as i64)
+        .bind(block_hash)
+        .bind(serde_json::to_string(checkpoint)?)
+        .bind(leaves)
+        .execute(&self.db.pool)
+        .await
+        .map_err(|e| Error::storage(format!("Failed to store checkpoint: {}", e)))?;
+        Ok(())
+    }
+
+    async fn stored_checkpoints(&self, with_leaves: bool) -> Result<Vec<SyncedCheckpoint>> {
+        let sql = if with_leaves {
+            "SELECT block_hash, checkpoint, leaves FROM pruning_checkpoints ORDER BY height ASC, rowid ASC"
+        } else {
+            "SELECT block_hash, checkpoint, NULL AS leaves FROM pruning_checkpoints ORDER BY height ASC, rowid ASC"
+        };
+        let rows = sqlx::query(sql)
+            .fetch_all(&self.db.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to load checkpoints: {}", e)))?;
+
+        rows.iter().map(decode_checkpoint).collect()
+    }
+}
+
+fn decode_checkpoint(row: &sqlx::sqlite::SqliteRow) -> Result<SyncedCheckpoint> {
+    let checkpoint: String = row.get("checkpoint");
+    let leaves: Option<Vec<u8>> = row.get("leaves");
+    Ok(SyncedCheckpoint {
+        checkpoint: serde_json::from_str(&checkpoint)?,
+        block_hash: row.get("block_hash"),
+        messages: leaves.map(|bytes| {
+            bytes
+                .chunks_exact(16)
+                .map(|chunk| MessageId(Uuid::from_slice(chunk).expect("16-byte chunk")))
+                .collect()
+        }),
+    })
+}
+
+/// Leaf identity of a stored message
+///
+/// Message rows keyed by something other than a UUID are identified by a
+/// hash of their key.
+fn message_id(id: &str) -> MessageId {
+    let uuid = Uuid::parse_str(id).unwrap_or_else(|_| {
+        let hash = blake3::hash(id.as_bytes());
+        Uuid::from_slice(&hash.as_bytes()[..16]).expect("16-byte slice")
+    });
+    MessageId(uuid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{DatabaseConfig, MessageRow};
+    use tempfile::tempdir;
+
+    async fn open(path: std::path::PathBuf) -> Database {
+        let db = Database::new(DatabaseConfig {
+            path,
+            ..DatabaseConfig::default()
+        })
+        .await
+        .unwrap();
+        db.insert_user("alice", "alice", &[0; 32]).await.unwrap();
+        db
+    }
+
+    fn config(node_type: NodeType) -> PruningConfig {
+        PruningConfig {
+            node_type,
+            retention_period: 100,
+            checkpoint_interval: 10,
+            ..PruningConfig::default()
+        }
+    }
+
+    fn message(channel: &str, timestamp: i64) -> MessageRow {
+        MessageRow {
+            id: Uuid::new_v4().to_string(),
+            sender_id: "alice".to_string(),
+            recipient_id: None,
+            channel_id: Some(channel.to_string()),
+            content_type: "text".to_string(),
+            content: "hello".to_string(),
+            encrypted_payload: vec![0; 64],
+            timestamp,
+            sequence_num: None,
+            status: "sent".to_string(),
+            expires_at: None,
+            size: 64,
+            content_hash: None,
+        }
+    }
+
+    async fn extend(db: &Database, count: u64) {
+        for _ in 0..count {
+            let (height, parent) = match db.latest_block().await.unwrap() {
+                Some(tip) => (tip.height + 1, tip.hash),
+                None => (1, Vec::new()),
+            };
+            db.insert_block(&BlockRow::new(height, parent, height as i64, vec![1, 2, 3])).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_node_types_prune_differently() {
+        let dir = tempdir().unwrap();
+        for (i, node_type) in [NodeType::Archive, NodeType::Full, NodeType::Light].into_iter().enumerate() {
+            let db = open(dir.path().join(format!("{}.db", i))).await;
+            let mut pruner = NodePruner::open(db.clone(), config(node_type)).await.unwrap();
+            let mut policy = dchat_chain::PruningPolicy::new("keep-announcements".to_string(), 50);
+            policy.add_priority_channel("announcements".to_string());
+            policy.vote_count = 100;
+            pruner.manager_mut().set_policy(policy).unwrap();
+
+            let old = message("general", 1_000);
+            db.insert_message(&old).await.unwrap();
+            db.insert_message(&message("announcements", 1_000)).await.unwrap();
+            db.insert_message(&message("general", 1_090)).await.unwrap();
+            extend(&db, 30).await;
+
+            // The policy's 50 seconds is stricter than the configured 100
+            let report = pruner.prune(1_100).await.unwrap();
+            let remaining = db.stats().await.unwrap().message_count;
+            let blocks = db.blocks_from(0).await.unwrap().len();
+            match node_type {
+                NodeType::Archive => {
+                    assert_eq!((report.messages_deleted, remaining, blocks), (0, 3, 30));
+                }
+                NodeType::Full => {
+                    assert_eq!((report.messages_deleted, remaining, blocks), (1, 2, 11));
+                    let checkpoint = report.checkpoint.unwrap();
+                    let proof = pruner.generate_proof(&message_id(&old.id), &checkpoint.checkpoint_id).await.unwrap();
+                    assert!(proof.verify(&checkpoint.merkle_root));
+                }
+                NodeType::Light => {
+                    assert_eq!((report.messages_deleted, remaining, blocks), (1, 2, 1));
+                    // Only the root is kept
+                    let checkpoint = report.checkpoint.unwrap();
+                    assert!(pruner.generate_proof(&message_id(&old.id), &checkpoint.checkpoint_id).await.is_err());
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_from_snapshot() {
+        let dir = tempdir().unwrap();
+        let db = open(dir.path().join("source.db")).await;
+        let mut source = NodePruner::open(db.clone(), config(NodeType::Full)).await.unwrap();
+
+        let old = message("general", 0);
+        db.insert_message(&old).await.unwrap();
+        extend(&db, 25).await;
+        source.prune(1_000).await.unwrap();
+        extend(&db, 5).await;
+
+        let snapshot = source.snapshot().await.unwrap();
+        let anchor = snapshot.latest_checkpoint().unwrap().checkpoint.clone();
+        assert_eq!(snapshot.blocks.first().unwrap().height, anchor.height);
+
+        // Tampered blocks are refused
+        let mut tampered = snapshot.clone();
+        tampered.blocks[2].data = vec![9];
+        let target = open(dir.path().join("tampered.db")).await;
+        assert!(NodePruner::bootstrap(target, config(NodeType::Full), tampered).await.is_err());
+
+        let target = open(dir.path().join("target.db")).await;
+        let mut node = NodePruner::bootstrap(target.clone(), config(NodeType::Full), snapshot).await.unwrap();
+        assert_eq!(target.latest_block().await.unwrap().unwrap().height, 30);
+        let proof = node.generate_proof(&message_id(&old.id), &anchor.checkpoint_id).await.unwrap();
+        assert!(proof.verify(&anchor.merkle_root));
+
+        // The new node keeps building on the synced tip
+        extend(&target, 1).await;
+
+        // Its checkpoints survive a restart
+        let reopened = NodePruner::open(target, config(NodeType::Full)).await.unwrap();
+        assert_eq!(reopened.manager().latest_checkpoint().unwrap().checkpoint_id, anchor.checkpoint_id);
+    }
+}
@@ -144,6 +144,40 @@ impl Schema {
                 FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
             )
             "#,
+            
+            // Versioned snapshots of governance, tokenomics and marketplace state
+            r#"
+            CREATE TABLE IF NOT EXISTS module_state (
+                module TEXT PRIMARY KEY,
+                schema_version INTEGER NOT NULL,
+                revision INTEGER NOT NULL,
+                state TEXT NOT NULL,
+                updated_at INTEGER NOT NULL
+            )
+            "#,
+            
+            // Chain blocks retained by this node
+            r#"
+            CREATE TABLE IF NOT EXISTS blocks (
+                height INTEGER PRIMARY KEY,
+                hash BLOB NOT NULL,
+                parent_hash BLOB NOT NULL,
+                timestamp INTEGER NOT NULL,
+                data BLOB NOT NULL
+            )
+            "#,
+            
+            // Merkle checkpoints over pruned messages; leaves are NULL on
+            // nodes that keep only roots
+            r#"
+            CREATE TABLE IF NOT EXISTS pruning_checkpoints (
+                id TEXT PRIMARY KEY,
+                height INTEGER NOT NULL,
+                block_hash BLOB,
+                checkpoint TEXT NOT NULL,
+                leaves BLOB
+            )
+            "#,
         ]
     }
     
@@ -159,6 +193,7 @@ impl Schema {
             "CREATE INDEX IF NOT EXISTS idx_devices_user ON devices(user_id)",
             "CREATE INDEX IF NOT EXISTS idx_channel_members_user ON channel_members(user_id)",
             "CREATE INDEX IF NOT EXISTS idx_guardians_user ON guardians(user_id)",
+            "CREATE INDEX IF NOT EXISTS idx_pruning_checkpoints_height ON pruning_checkpoints(height)",
         ]
     }
 }
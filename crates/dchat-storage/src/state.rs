@@ -0,0 +1,315 @@
+//! Persistent manager state
+//!
+//! Managers that live in memory inside a node (governance, tokenomics,
+//! marketplace, bots) are stored as versioned JSON snapshots in the `module_state`
+//! table. A snapshot is loaded at the start of a CLI command and written
+//! back at the end; the revision column makes the write fail instead of
+//! silently overwriting a concurrent invocation's changes.
+//!
+//! A long-lived holder, such as a running node, calls `reload_state` when a
+//! command fails part way or loses a save to a concurrent writer, so the
+//! half-applied change is dropped instead of being saved by the next
+//! command and the stale revision is refreshed.
+//!
+//! Snapshots are encoded canonically, with object keys sorted, so a
+//! `HashMap` reloaded in a different iteration order still encodes the same
+//! and a read-only command writes nothing. Sets in persisted managers are
+//! `BTreeSet`s for the same reason.
+
+use crate::database::Database;
+use dchat_core::error::{Error, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::Row;
+use std::ops::{Deref, DerefMut};
+
+/// A manager loaded from the database
+pub struct Persisted<T> {
+    value: T,
+    module: String,
+    schema_version: u32,
+    /// Revision the snapshot was loaded at; 0 if it has never been saved
+    revision: i64,
+    /// Canonical encoding at load time, to skip writes for read-only commands
+    encoded: String,
+}
+
+impl<T> Persisted<T> {
+    /// Module name the snapshot is stored under
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// Revision of the stored snapshot this value was loaded from
+    pub fn revision(&self) -> i64 {
+        self.revision
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Persisted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Persisted<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl Database {
+    /// Load the snapshot stored under `module`, or start from `default`
+    ///
+    /// Fails if the stored snapshot was written with a different
+    /// `schema_version`, rather than guessing at a migration.
+    pub async fn load_state<T, F>(&self, module: &str, schema_version: u32, default: F) -> Result<Persisted<T>>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> T,
+    {
+        let row = sqlx::query("SELECT schema_version, revision, state FROM module_state WHERE module = ?")
+            .bind(module)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to load {} state: {}", module, e)))?;
+
+        let Some(row) = row else {
+            let value = default();
+            let encoded = encode(module, &value)?;
+            return Ok(Persisted {
+                value,
+                module: module.to_string(),
+                schema_version,
+                revision: 0,
+                encoded,
+            });
+        };
+
+        let stored_version: i64 = row.get("schema_version");
+        if stored_version != schema_version as i64 {
+            return Err(Error::storage(format!(
+                "{} state has schema version {}, expected {}",
+                module, stored_version, schema_version
+            )));
+        }
+
+        let stored: String = row.get("state");
+        let value = serde_json::from_str(&stored)
+            .map_err(|e| Error::storage(format!("Corrupt {} state: {}", module, e)))?;
+        // Compare later saves against the canonical form, not whatever order
+        // the stored row happens to have
+        let encoded = encode(module, &value)?;
+
+        Ok(Persisted {
+            value,
+            module: module.to_string(),
+            schema_version,
+            revision: row.get("revision"),
+            encoded,
+        })
+    }
+
+    /// Throw away unsaved changes to `state` and pick up the latest stored
+    /// snapshot, including one written by another process
+    pub async fn reload_state<T: Serialize + DeserializeOwned>(&self, state: &mut Persisted<T>) -> Result<()> {
+        let row = sqlx::query("SELECT schema_version, revision, state FROM module_state WHERE module = ?")
+            .bind(&state.module)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Error::storage(format!("Failed to load {} state: {}", state.module, e)))?;
+
+        let Some(row) = row else {
+            // Never saved: go back to the default it was loaded with
+            state.value = serde_json::from_str(&state.encoded)
+                .map_err(|e| Error::storage(format!("Corrupt {} state: {}", state.module, e)))?;
+            return Ok(());
+        };
+
+        let stored_version: i64 = row.get("schema_version");
+        if stored_version != state.schema_version as i64 {
+            return Err(Error::storage(format!(
+                "{} state has schema version {}, expected {}",
+                state.module, stored_version, state.schema_version
+            )));
+        }
+        let stored: String = row.get("state");
+        state.value = serde_json::from_str(&stored)
+            .map_err(|e| Error::storage(format!("Corrupt {} state: {}", state.module, e)))?;
+        state.encoded = encode(&state.module, &state.value)?;
+        state.revision = row.get("revision");
+        Ok(())
+    }
+
+    /// Write `state` back if it changed since it was loaded
+    ///
+    /// Returns `false` when there was nothing to write. Fails if another
+    /// writer saved the module in the meantime.
+    pub async fn save_state<T: Serialize>(&self, state: &mut Persisted<T>) -> Result<bool> {
+        let encoded = encode(&state.module, &state.value)?;
+        if encoded == state.encoded {
+            return Ok(false);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let next_revision = state.revision + 1;
+
+        let result = if state.revision == 0 {
+            sqlx::query(
+                "INSERT INTO module_state (module, schema_version, revision, state, updated_at)
+                 VALUES (?, ?, ?, ?, ?) ON CONFLICT(module) DO NOTHING",
+            )
+            .bind(&state.module)
+            .bind(state.schema_version as i64)
+            .bind(next_revision)
+            .bind(&encoded)
+            .bind(now)
+            .execute(&self.pool)
+            .await
+        } else {
+            sqlx::query(
+                "UPDATE module_state SET schema_version = ?, revision = ?, state = ?, updated_at = ?
+                 WHERE module = ? AND revision = ?",
+            )
+            .bind(state.schema_version as i64)
+            .bind(next_revision)
+            .bind(&encoded)
+            .bind(now)
+            .bind(&state.module)
+            .bind(state.revision)
+            .execute(&self.pool)
+            .await
+        }
+        .map_err(|e| Error::storage(format!("Failed to save {} state: {}", state.module, e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::storage(format!(
+                "{} state was modified concurrently; retry the command",
+                state.module
+            )));
+        }
+
+        state.revision = next_revision;
+        state.encoded = encoded;
+        Ok(true)
+    }
+}
+
+/// Encode with object keys sorted
+///
+/// Going through `serde_json::Value`, whose objects are ordered maps, sorts
+/// every map's keys regardless of the map type that produced them.
+fn encode<T: Serialize>(module: &str, value: &T) -> Result<String> {
+    serde_json::to_value(value)
+        .and_then(|value| serde_json::to_string(&value))
+        .map_err(|e| Error::storage(format!("Failed to encode {} state: {}", module, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseConfig;
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    async fn open(path: std::path::PathBuf) -> Database {
+        Database::new(DatabaseConfig {
+            path,
+            ..DatabaseConfig::default()
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_state_survives_reopen() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("state.db");
+
+        let db = open(path.clone()).await;
+        let mut state = db.load_state("proposals", 1, BTreeMap::<String, u64>::new).await.unwrap();
+        assert_eq!(state.revision(), 0);
+        // Unchanged state is not written
+        assert!(!db.save_state(&mut state).await.unwrap());
+
+        state.insert("upgrade".to_string(), 10);
+        assert!(db.save_state(&mut state).await.unwrap());
+        assert_eq!(state.revision(), 1);
+        db.close().await.unwrap();
+
+        let db = open(path).await;
+        let mut state = db.load_state("proposals", 1, BTreeMap::<String, u64>::new).await.unwrap();
+        assert_eq!(state.get("upgrade"), Some(&10));
+        *state.get_mut("upgrade").unwrap() += 5;
+        assert!(db.save_state(&mut state).await.unwrap());
+        assert_eq!(state.revision(), 2);
+
+        // A different schema version is refused rather than misread
+        assert!(db.load_state("proposals", 2, BTreeMap::<String, u64>::new).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_hash_map_is_not_rewritten() {
+        use std::collections::HashMap;
+
+        let dir = tempdir().unwrap();
+        let db = open(dir.path().join("state.db")).await;
+        let mut state = db.load_state("pools", 1, HashMap::<String, u64>::new).await.unwrap();
+        state.extend((0..64).map(|i| (format!("pool-{}", i), i)));
+        db.save_state(&mut state).await.unwrap();
+
+        // Two read-only invocations both load and save without conflicting
+        let mut first = db.load_state("pools", 1, HashMap::<String, u64>::new).await.unwrap();
+        let mut second = db.load_state("pools", 1, HashMap::<String, u64>::new).await.unwrap();
+        assert!(!db.save_state(&mut first).await.unwrap());
+        assert!(!db.save_state(&mut second).await.unwrap());
+        assert_eq!(second.revision(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_save_is_rejected() {
+        let dir = tempdir().unwrap();
+        let db = open(dir.path().join("state.db")).await;
+
+        let mut first = db.load_state("tokens", 1, Vec::<u64>::new).await.unwrap();
+        let mut second = db.load_state("tokens", 1, Vec::<u64>::new).await.unwrap();
+
+        first.push(1);
+        second.push(2);
+        db.save_state(&mut first).await.unwrap();
+        assert!(db.save_state(&mut second).await.is_err());
+
+        let reloaded = db.load_state("tokens", 1, Vec::<u64>::new).await.unwrap();
+        assert_eq!(*reloaded, vec![1]);
+
+        // The loser reloads and can save again
+        db.reload_state(&mut second).await.unwrap();
+        assert_eq!(*second, vec![1]);
+        second.push(2);
+        db.save_state(&mut second).await.unwrap();
+        assert_eq!(second.revision(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reload_drops_unsaved_changes() {
+        let dir = tempdir().unwrap();
+        let db = open(dir.path().join("state.db")).await;
+
+        let mut state = db.load_state("votes", 1, || vec![7u64]).await.unwrap();
+        state.push(8);
+        db.reload_state(&mut state).await.unwrap();
+        assert_eq!(*state, vec![7]);
+        assert!(!db.save_state(&mut state).await.unwrap());
+
+        state.push(9);
+        db.save_state(&mut state).await.unwrap();
+        state.clear();
+        db.reload_state(&mut state).await.unwrap();
+        assert_eq!(*state, vec![7, 9]);
+    }
+}
@@ -0,0 +1,494 @@
+//! Local control API for running nodes
+//!
+//! A long-running `dchat user`/`relay`/`validator` node listens on a Unix
+//! socket in its data directory and answers JSON-RPC 2.0 requests, one JSON
+//! object per line. CLI subcommands and automation connect to it instead of
+//! spinning up their own in-process managers.
+//!
+//! Access is restricted three ways: the socket is bound inside a 0700
+//! `control/` directory and then set to mode 0600, the peer must run as the
+//! node's own uid, and the first request on every connection must be `auth`
+//! with the token the node wrote to `control.token` (also 0600) at startup.
+
+use dchat_core::error::{Error, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Directory inside the node's data directory that holds the socket
+pub const CONTROL_SOCKET_DIR: &str = "control";
+
+/// Socket file name inside `CONTROL_SOCKET_DIR`
+pub const CONTROL_SOCKET_FILE: &str = "control.sock";
+
+/// Token file name inside the node's data directory
+pub const CONTROL_TOKEN_FILE: &str = "control.token";
+
+/// JSON-RPC error codes
+pub mod codes {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    /// Missing or wrong `auth` token, or a permission error from the node
+    pub const UNAUTHORIZED: i64 = -32001;
+    /// The requested object does not exist
+    pub const NOT_FOUND: i64 = -32004;
+}
+
+/// A JSON-RPC request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlRequest {
+    pub jsonrpc: String,
+    pub id: u64,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// A JSON-RPC error object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// A JSON-RPC response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub jsonrpc: String,
+    pub id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ControlError>,
+}
+
+impl ControlResponse {
+    fn ok(id: u64, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id: Some(id),
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Option<u64>, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(ControlError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+/// Answers control requests on behalf of a node
+#[async_trait::async_trait]
+pub trait ControlHandler: Send + Sync + 'static {
+    /// Handle `method`; errors reach the client as the same `Error` variant
+    /// for `NotFound`, `InvalidInput` and `PermissionDenied`
+    async fn call(&self, method: &str, params: Value) -> Result<Value>;
+}
+
+/// Deserialize request parameters, reporting failures as validation errors
+pub fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T> {
+    serde_json::from_value(params).map_err(|e| Error::validation(format!("Invalid params: {}", e)))
+}
+
+/// Socket and token locations for a data directory
+#[derive(Debug, Clone)]
+pub struct ControlPaths {
+    pub socket: PathBuf,
+    pub token: PathBuf,
+}
+
+impl ControlPaths {
+    pub fn in_dir(data_dir: &Path) -> Self {
+        Self {
+            socket: data_dir.join(CONTROL_SOCKET_DIR).join(CONTROL_SOCKET_FILE),
+            token: data_dir.join(CONTROL_TOKEN_FILE),
+        }
+    }
+}
+
+/// Map a node error to a JSON-RPC error that [`ControlClient`] turns back
+/// into the same variant
+fn to_control_error(error: &Error) -> (i64, String) {
+    match error {
+        Error::NotFound(message) => (codes::NOT_FOUND, message.clone()),
+        Error::InvalidInput(message) => (codes::INVALID_PARAMS, message.clone()),
+        Error::PermissionDenied(message) => (codes::UNAUTHORIZED, message.clone()),
+        other => (codes::INTERNAL_ERROR, other.to_string()),
+    }
+}
+
+fn from_control_error(error: ControlError) -> Error {
+    match error.code {
+        codes::NOT_FOUND => Error::NotFound(error.message),
+        codes::INVALID_PARAMS => Error::InvalidInput(error.message),
+        codes::UNAUTHORIZED => Error::PermissionDenied(error.message),
+        _ => Error::Internal(error.message),
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{ControlClient, ControlServer};
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::sync::broadcast;
+    use tokio::task::JoinHandle;
+    use tracing::{debug, info, warn};
+
+    /// Longest request line the server will buffer
+    const MAX_REQUEST_BYTES: usize = 4 * 1024 * 1024;
+
+    /// Listens on the control socket and dispatches to a [`ControlHandler`]
+    pub struct ControlServer {
+        paths: ControlPaths,
+        listener: UnixListener,
+        token: Arc<String>,
+    }
+
+    impl ControlServer {
+        /// Bind the socket in `data_dir` and write a fresh access token
+        ///
+        /// Fails if another node is already serving on the same socket; a
+        /// stale socket file left by a crashed node is replaced.
+        pub async fn bind(data_dir: &Path) -> Result<Self> {
+            let paths = ControlPaths::in_dir(data_dir);
+            // Only the owner can reach the socket from the moment it is
+            // bound, whatever the umask
+            let socket_dir = data_dir.join(CONTROL_SOCKET_DIR);
+            tokio::fs::DirBuilder::new().recursive(true).mode(0o700).create(&socket_dir).await?;
+            tokio::fs::set_permissions(&socket_dir, std::fs::Permissions::from_mode(0o700)).await?;
+
+            if paths.socket.exists() {
+                if UnixStream::connect(&paths.socket).await.is_ok() {
+                    return Err(Error::Config(format!(
+                        "Another node is already serving {}",
+                        paths.socket.display()
+                    )));
+                }
+                tokio::fs::remove_file(&paths.socket).await?;
+            }
+
+            let token = hex::encode(rand::random::<[u8; 32]>());
+            write_private(&paths.token, token.as_bytes()).await?;
+
+            let listener = UnixListener::bind(&paths.socket)?;
+            tokio::fs::set_permissions(&paths.socket, std::fs::Permissions::from_mode(0o600)).await?;
+
+            Ok(Self {
+                paths,
+                listener,
+                token: Arc::new(token),
+            })
+        }
+
+        pub fn socket_path(&self) -> &Path {
+            &self.paths.socket
+        }
+
+        /// Serve connections until `shutdown` fires, then remove the socket
+        pub fn spawn<H: ControlHandler>(
+            self,
+            handler: Arc<H>,
+            mut shutdown: broadcast::Receiver<()>,
+        ) -> JoinHandle<()> {
+            tokio::spawn(async move {
+                info!("🎛️  Control API listening on {}", self.paths.socket.display());
+                // SAFETY: geteuid takes no arguments, cannot fail and only
+                // reads the calling process's credentials.
+                let uid = unsafe { libc::geteuid() };
+                loop {
+                    tokio::select! {
+                        accepted = self.listener.accept() => {
+                            let stream = match accepted {
+                                Ok((stream, _)) => stream,
+                                Err(e) => {
+                                    warn!("Control socket accept failed: {}", e);
+                                    continue;
+                                }
+                            };
+                            match stream.peer_cred() {
+                                Ok(cred) if cred.uid() == uid => {}
+                                Ok(cred) => {
+                                    warn!("Rejected control connection from uid {}", cred.uid());
+                                    continue;
+                                }
+                                Err(e) => {
+                                    warn!("Cannot read control peer credentials: {}", e);
+                                    continue;
+                                }
+                            }
+                            let handler = handler.clone();
+                            let token = self.token.clone();
+                            tokio::spawn(async move {
+                                if let Err(e) = serve_connection(stream, handler, token).await {
+                                    debug!("Control connection closed: {}", e);
+                                }
+                            });
+                        }
+                        _ = shutdown.recv() => break,
+                    }
+                }
+                let _ = std::fs::remove_file(&self.paths.socket);
+                let _ = std::fs::remove_file(&self.paths.token);
+            })
+        }
+    }
+
+    async fn serve_connection<H: ControlHandler>(
+        stream: UnixStream,
+        handler: Arc<H>,
+        token: Arc<String>,
+    ) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).take(MAX_REQUEST_BYTES as u64 + 1);
+        let mut authenticated = false;
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            lines.set_limit(MAX_REQUEST_BYTES as u64 + 1);
+            if lines.read_line(&mut line).await? == 0 {
+                return Ok(());
+            }
+            if line.len() > MAX_REQUEST_BYTES {
+                let response = ControlResponse::error(None, codes::INVALID_REQUEST, "Request too large");
+                write_line(&mut writer, &response).await?;
+                return Ok(());
+            }
+
+            let response = match serde_json::from_str::<ControlRequest>(&line) {
+                Err(e) => ControlResponse::error(None, codes::PARSE_ERROR, e.to_string()),
+                Ok(request) if request.jsonrpc != "2.0" => {
+                    ControlResponse::error(Some(request.id), codes::INVALID_REQUEST, "Expected jsonrpc 2.0")
+                }
+                Ok(request) if request.method == "auth" => {
+                    let presented = request.params.get("token").and_then(Value::as_str).unwrap_or("");
+                    authenticated = dchat_crypto::constant_time_eq(presented.as_bytes(), token.as_bytes());
+                    if authenticated {
+                        ControlResponse::ok(request.id, Value::Bool(true))
+                    } else {
+                        ControlResponse::error(Some(request.id), codes::UNAUTHORIZED, "Invalid control token")
+                    }
+                }
+                Ok(request) if !authenticated => {
+                    ControlResponse::error(Some(request.id), codes::UNAUTHORIZED, "Authenticate first")
+                }
+                Ok(request) => match handler.call(&request.method, request.params).await {
+                    Ok(result) => ControlResponse::ok(request.id, result),
+                    Err(e) => {
+                        let (code, message) = to_control_error(&e);
+                        ControlResponse::error(Some(request.id), code, message)
+                    }
+                },
+            };
+            write_line(&mut writer, &response).await?;
+        }
+    }
+
+    async fn write_line<W: AsyncWriteExt + Unpin, T: Serialize>(writer: &mut W, value: &T) -> Result<()> {
+        let mut bytes = serde_json::to_vec(value)?;
+        bytes.push(b'\n');
+        writer.write_all(&bytes).await?;
+        Ok(())
+    }
+
+    async fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true).mode(0o600);
+        let mut file = options.open(path).await?;
+        // Tighten an existing file that was created with looser permissions
+        file.set_permissions(std::fs::Permissions::from_mode(0o600)).await?;
+        file.write_all(contents).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// An authenticated connection to a running node
+    pub struct ControlClient {
+        reader: BufReader<tokio::net::unix::OwnedReadHalf>,
+        writer: tokio::net::unix::OwnedWriteHalf,
+        next_id: u64,
+    }
+
+    impl ControlClient {
+        /// Connect to the node serving `data_dir`, if one is running
+        pub async fn connect(data_dir: &Path) -> Result<Option<Self>> {
+            let paths = ControlPaths::in_dir(data_dir);
+            let stream = match UnixStream::connect(&paths.socket).await {
+                Ok(stream) => stream,
+                Err(_) => return Ok(None),
+            };
+            let token = tokio::fs::read_to_string(&paths.token).await?;
+
+            let (reader, writer) = stream.into_split();
+            let mut client = Self {
+                reader: BufReader::new(reader),
+                writer,
+                next_id: 0,
+            };
+            client
+                .call::<bool>("auth", serde_json::json!({ "token": token.trim() }))
+                .await?;
+            Ok(Some(client))
+        }
+
+        /// Call `method` and decode its result
+        pub async fn call<T: DeserializeOwned>(&mut self, method: &str, params: Value) -> Result<T> {
+            self.next_id += 1;
+            let request = ControlRequest {
+                jsonrpc: "2.0".to_string(),
+                id: self.next_id,
+                method: method.to_string(),
+                params,
+            };
+            write_line(&mut self.writer, &request).await?;
+
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Err(Error::network("Node closed the control connection"));
+            }
+            let response: ControlResponse = serde_json::from_str(&line)?;
+            if let Some(error) = response.error {
+                return Err(from_control_error(error));
+            }
+            serde_json::from_value(response.result.unwrap_or(Value::Null)).map_err(Error::from)
+        }
+    }
+}
+
+/// Stub for platforms without Unix sockets: no node is ever reachable
+#[cfg(not(unix))]
+pub struct ControlClient;
+
+#[cfg(not(unix))]
+impl ControlClient {
+    pub async fn connect(_data_dir: &Path) -> Result<Option<Self>> {
+        Ok(None)
+    }
+
+    pub async fn call<T: DeserializeOwned>(&mut self, _method: &str, _params: Value) -> Result<T> {
+        Err(Error::Config("The control API requires Unix sockets".to_string()))
+    }
+}
+
+/// Stub for platforms without Unix sockets: binding always fails
+#[cfg(not(unix))]
+pub struct ControlServer;
+
+#[cfg(not(unix))]
+impl ControlServer {
+    pub async fn bind(_data_dir: &Path) -> Result<Self> {
+        Err(Error::Config("The control API requires Unix sockets".to_string()))
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        Path::new("")
+    }
+
+    pub fn spawn<H: ControlHandler>(
+        self,
+        _handler: Arc<H>,
+        _shutdown: tokio::sync::broadcast::Receiver<()>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async {})
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast;
+
+    struct Echo;
+
+    #[async_trait::async_trait]
+    impl ControlHandler for Echo {
+        async fn call(&self, method: &str, params: Value) -> Result<Value> {
+            match method {
+                "echo" => Ok(params),
+                "add" => {
+                    let (a, b): (u64, u64) = parse_params(params)?;
+                    Ok(serde_json::json!(a + b))
+                }
+                _ => Err(Error::NotFound(format!("Unknown method: {}", method))),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = ControlServer::bind(dir.path()).await.unwrap();
+        let handle = server.spawn(Arc::new(Echo), shutdown_rx);
+
+        let mut client = ControlClient::connect(dir.path()).await.unwrap().unwrap();
+        let sum: u64 = client.call("add", serde_json::json!([2, 3])).await.unwrap();
+        assert_eq!(sum, 5);
+        assert!(matches!(
+            client.call::<Value>("missing", Value::Null).await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            client.call::<u64>("add", serde_json::json!("x")).await,
+            Err(Error::InvalidInput(_))
+        ));
+
+        // A second node cannot take over the socket while the first is alive
+        assert!(ControlServer::bind(dir.path()).await.is_err());
+
+        shutdown_tx.send(()).unwrap();
+        handle.await.unwrap();
+        assert!(!ControlPaths::in_dir(dir.path()).socket.exists());
+        assert!(ControlClient::connect(dir.path()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_requests_require_token() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (_shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let _handle = ControlServer::bind(dir.path()).await.unwrap().spawn(Arc::new(Echo), shutdown_rx);
+
+        let stream = tokio::net::UnixStream::connect(ControlPaths::in_dir(dir.path()).socket).await.unwrap();
+        let mut stream = BufReader::new(stream);
+        async fn exchange(stream: &mut BufReader<tokio::net::UnixStream>, request: &str) -> ControlResponse {
+            stream.get_mut().write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_line(&mut response).await.unwrap();
+            serde_json::from_str(&response).unwrap()
+        }
+
+        let response = exchange(&mut stream, r#"{"jsonrpc":"2.0","id":1,"method":"echo","params":1}"#).await;
+        assert_eq!(response.error.unwrap().code, codes::UNAUTHORIZED);
+        let response = exchange(&mut stream, r#"{"jsonrpc":"2.0","id":2,"method":"auth","params":{"token":"guess"}}"#).await;
+        assert_eq!(response.error.unwrap().code, codes::UNAUTHORIZED);
+        let response = exchange(&mut stream, r#"{"jsonrpc":"2.0","id":3,"method":"echo","params":1}"#).await;
+        assert_eq!(response.error.unwrap().code, codes::UNAUTHORIZED);
+        let response = exchange(&mut stream, "not json").await;
+        assert_eq!(response.error.unwrap().code, codes::PARSE_ERROR);
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir.path().join(CONTROL_TOKEN_FILE)), 0o600);
+        assert_eq!(mode(&dir.path().join(CONTROL_SOCKET_DIR)), 0o700);
+        assert_eq!(mode(&ControlPaths::in_dir(dir.path()).socket), 0o600);
+    }
+}
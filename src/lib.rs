@@ -94,6 +94,9 @@
 // User management module
 pub mod user_management;
 
+// Local control API for running nodes
+pub mod control;
+
 // Re-export all crate modules
 pub use dchat_core as core;
 pub use dchat_crypto as crypto;
@@ -143,13 +146,21 @@ pub mod prelude {
     pub use dchat_identity::{
         biometric::{BiometricAuthenticator, BiometricType},
         burner::{BurnerIdentity, BurnerManager},
+        crdt::{ReplicatedState, StateDelta},
         derivation::{IdentityDerivation, KeyPath},
         device::{Device, DeviceManager, DeviceType},
         enclave::SecureEnclave,
         guardian::{Guardian, GuardianManager, RecoveryRequest},
         identity::{Identity, IdentityManager},
+        mnemonic::MnemonicSeed,
         mpc::{MpcCoordinator, MpcConfig, SignatureShare},
+        pairing::{
+            CertificateRegistry, DeviceCertificate, DeviceRevocation, PairedDevice, PairingCode,
+            PairingHost, PairingJoiner, PairingMessage, SyncKeyRotation,
+        },
+        pkcs11::{Pkcs11Key, Pkcs11Uri},
         sync::{SyncManager, SyncMessage},
+        vault::{KeyVault, TpmSealer},
         verification::{BadgeManager, BadgeType, VerifiedBadge},
     };
     
@@ -159,9 +170,13 @@ pub mod prelude {
         expiration::{ExpirationPolicy, MessageExpiration},
         ordering::{MessageOrder, SequenceNumber},
         queue::{MessageQueue, OfflineQueue},
+        stealth::{StealthDirectMessage, StealthMessenger, StealthScanService},
         types::{Message, MessageBuilder, MessageStatus, MessageType},
     };
     
+    // Privacy
+    pub use dchat_privacy::stealth::{StealthAddress, StealthScanner};
+    
     // Network
     pub use dchat_network::{
         behavior::{DchatBehavior, DchatMessage},
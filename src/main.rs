@@ -9,9 +9,15 @@
 //! - Observability integration
 
 use dchat::prelude::*;
-use dchat::blockchain::{ChatChainClient, ChatChainConfig, CurrencyChainClient, CurrencyChainConfig, CrossChainBridge};
+use dchat::blockchain::{BridgeState, ChatChainClient, ChatChainConfig, CurrencyChainClient, CurrencyChainConfig, CrossChainBridge, SignedTransaction, SwapDirection, TransactionEnvelope, TransactionPayload};
+use dchat::blockchain::amm::Q64;
+use dchat::blockchain::assets::{IssueToken, NATIVE_TOKEN};
+use dchat::control::{parse_params, ControlClient, ControlHandler, ControlServer};
+use dchat::chain::pruning::{NodeType, PruningConfig};
+use dchat::storage::NodePruner;
 
 use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 use dchat_network::{Multiaddr, PeerId};
 use std::path::{Path, PathBuf};
 use tokio::signal;
@@ -22,6 +28,68 @@ use uuid::Uuid;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const DEFAULT_CONFIG_PATH: &str = "config.toml";
+/// Gossip topic carrying stealth-addressed direct messages
+const STEALTH_DM_CHANNEL: &str = "stealth-dm";
+/// Gossip topic carrying signed device certificate revocations
+const DEVICE_REVOCATION_CHANNEL: &str = "device-revocations";
+/// Key file a user node keeps under its data directory when run without `--key`
+const USER_MASTER_KEY_FILE: &str = "user_master.key";
+/// Key file of the device a user node was paired as, under its data directory
+const PAIRED_DEVICE_KEY_FILE: &str = "paired_device.key";
+/// Most decoys published alongside one stealth DM
+const MAX_STEALTH_DECOYS: usize = 16;
+/// How often a user node gossips the revocations it knows again, for peers
+/// that were offline when they were first published
+const REVOCATION_REGOSSIP_SECS: u64 = 300;
+
+/// `module_state` keys and schema versions for state kept between CLI invocations
+const GOVERNANCE_STATE: &str = "governance.upgrades";
+const GOVERNANCE_STATE_VERSION: u32 = 1;
+const EXECUTION_STATE: &str = "governance.execution";
+const EXECUTION_STATE_VERSION: u32 = 1;
+const MODERATION_STATE: &str = "governance.moderation";
+const MODERATION_STATE_VERSION: u32 = 1;
+/// Tokenomics saved on its own before it moved into the currency chain state
+const TOKENOMICS_STATE: &str = "blockchain.tokenomics";
+const TOKENOMICS_STATE_VERSION: u32 = 2;
+const MARKETPLACE_STATE: &str = "marketplace";
+const MARKETPLACE_STATE_VERSION: u32 = 5;
+const CURRENCY_STATE: &str = "blockchain.currency";
+const CURRENCY_STATE_VERSION: u32 = 1;
+const BRIDGE_STATE: &str = "blockchain.bridge";
+const BRIDGE_STATE_VERSION: u32 = 1;
+const INSURANCE_STATE: &str = "chain.insurance";
+const INSURANCE_STATE_VERSION: u32 = 1;
+const CHANNEL_ACCESS_STATE: &str = "messaging.channel_access";
+const CHANNEL_ACCESS_STATE_VERSION: u32 = 1;
+const DEVICE_STATE: &str = "identity.devices";
+const DEVICE_STATE_VERSION: u32 = 1;
+const BOT_STATE: &str = "bots";
+const BOT_STATE_VERSION: u32 = 1;
+
+/// Domains the node's protocol account IDs on the currency chain derive from
+const FEE_POOL_ACCOUNT_DOMAIN: &[u8] = b"dchat-fee-pool-account-v1";
+const INSURANCE_ACCOUNT_DOMAIN: &[u8] = b"dchat-insurance-account-v1";
+const TREASURY_ACCOUNT_DOMAIN: &[u8] = b"dchat-treasury-account-v1";
+
+/// Stake a moderator must bond before governance can slash it
+const MODERATOR_MIN_STAKE: u64 = 1_000;
+
+/// Human-readable output of a subcommand, printed by the CLI or returned
+/// to it by a running node
+#[derive(Default)]
+struct CommandOutput(String);
+
+/// `println!` into a [`CommandOutput`]
+macro_rules! say {
+    ($out:expr) => {
+        $out.0.push('\n')
+    };
+    ($out:expr, $($arg:tt)*) => {{
+        use std::fmt::Write as _;
+        let _ = writeln!($out.0, $($arg)*);
+    }};
+}
 
 #[derive(Parser)]
 #[command(name = "dchat")]
@@ -48,6 +116,11 @@ struct Cli {
     #[arg(long, default_value = "0.0.0.0:8080")]
     health_addr: String,
 
+    /// Data this node keeps (archive, full, light); relays and user nodes
+    /// default to light, validators to full
+    #[arg(long, global = true, value_parser = parse_node_type)]
+    node_type: Option<NodeType>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -87,6 +160,16 @@ enum Commands {
         #[arg(long)]
         identity: Option<PathBuf>,
         
+        /// Identity master key file (JSON with a "private_key" field); the
+        /// stealth address is derived from it. Defaults to a key kept in the
+        /// data directory
+        #[arg(long)]
+        key: Option<PathBuf>,
+        
+        /// Decoy payloads published with every stealth DM
+        #[arg(long, default_value = "0")]
+        stealth_decoys: usize,
+        
         /// Username for display
         #[arg(long)]
         username: Option<String>,
@@ -94,11 +177,15 @@ enum Commands {
         /// Non-interactive mode (for testing)
         #[arg(long)]
         non_interactive: bool,
+
+        /// Run without reading stdin; drive the node through its control socket
+        #[arg(long, conflicts_with = "non_interactive")]
+        daemon: bool,
     },
 
     /// Run as validator node (participates in consensus)
     Validator {
-        /// Validator key file path (or HSM key ID)
+        /// Validator key file path (or pkcs11: URI with --hsm)
         #[arg(long)]
         key: String,
 
@@ -106,8 +193,8 @@ enum Commands {
         #[arg(long)]
         chain_rpc: String,
 
-        /// Enable HSM/KMS
-        #[arg(long)]
+        /// Load the validator key from a PKCS#11 token
+        #[arg(long, alias = "use-hsm")]
         hsm: bool,
         
         /// Validator stake amount
@@ -151,6 +238,14 @@ enum Commands {
         /// Generate ephemeral/burner identity
         #[arg(long)]
         burner: bool,
+
+        /// Derive keys from a new BIP-39 recovery phrase (also saves `<name>.device.key`)
+        #[arg(long)]
+        mnemonic: bool,
+
+        /// Number of words in the recovery phrase
+        #[arg(long, default_value = "24")]
+        words: usize,
     },
 
     /// User account management
@@ -159,6 +254,12 @@ enum Commands {
         action: AccountCommand,
     },
 
+    /// Pair, list and revoke devices of a running user node's identity
+    Device {
+        #[command(subcommand)]
+        action: DeviceCommand,
+    },
+
     /// Database management commands
     Database {
         #[command(subcommand)]
@@ -215,7 +316,7 @@ enum Commands {
     },
 }
 
-#[derive(Debug, Subcommand)]
+#[derive(Debug, Subcommand, Serialize, Deserialize)]
 enum BotCommand {
     /// Create a new bot
     Create {
@@ -292,13 +393,41 @@ enum BotCommand {
     },
 }
 
-#[derive(Debug, Subcommand)]
+#[derive(Debug, Subcommand, Serialize, Deserialize)]
 enum MarketplaceCommand {
-    /// List marketplace items
+    /// Browse and search marketplace listings
     List {
         /// Filter by item type (sticker-pack, emoji-pack, theme, bot, nft, image, subscription, badge, channel, membership)
         #[arg(long)]
         item_type: Option<String>,
+        
+        /// Minimum price
+        #[arg(long)]
+        min_price: Option<u64>,
+        
+        /// Maximum price
+        #[arg(long)]
+        max_price: Option<u64>,
+        
+        /// Minimum rating (0.0-5.0)
+        #[arg(long)]
+        min_rating: Option<f32>,
+        
+        /// Only show verified listings
+        #[arg(long)]
+        verified: bool,
+        
+        /// Words to search for in titles and descriptions
+        #[arg(long)]
+        search: Option<String>,
+        
+        /// Page number, starting at 0
+        #[arg(long, default_value = "0")]
+        page: usize,
+        
+        /// Listings per page
+        #[arg(long, default_value = "20")]
+        per_page: usize,
     },
     
     /// Create a new listing
@@ -349,8 +478,15 @@ enum MarketplaceCommand {
         /// Listing ID
         #[arg(long)]
         listing_id: String,
+        
+        /// Transaction ID of the buyer's transfer into the escrow account
+        #[arg(long)]
+        payment_tx: String,
     },
     
+    /// Show the account purchases are paid into
+    EscrowAccount,
+    
     /// Get creator statistics
     CreatorStats {
         /// Creator user ID
@@ -368,9 +504,9 @@ enum MarketplaceCommand {
         #[arg(long)]
         seller: String,
         
-        /// Amount in tokens
+        /// Transaction ID of the buyer's transfer into the escrow account
         #[arg(long)]
-        amount: u64,
+        payment_tx: String,
     },
     
     /// Register bot for marketplace trading
@@ -492,7 +628,7 @@ enum MarketplaceCommand {
         #[arg(long)]
         format: String,
         
-        /// License type (all-rights-reserved, cc-by, cc-by-sa, cc-by-nd, cc-by-nc, public-domain)
+        /// License type (all-rights-reserved, cc-by, cc-by-sa, cc-by-nd, cc-by-nc, public-domain, personal)
         #[arg(long, default_value = "all-rights-reserved")]
         license: String,
     },
@@ -532,6 +668,143 @@ enum MarketplaceCommand {
         #[arg(long)]
         channel_id: String,
     },
+    
+    /// Add prepaid subscription credit from a transfer into the subscription account
+    TopUpCredit {
+        /// Subscriber user ID
+        #[arg(long)]
+        subscriber_id: String,
+        
+        /// Transaction ID of the subscriber's transfer into the subscription account
+        #[arg(long)]
+        payment_tx: String,
+    },
+    
+    /// Subscribe to a listing billed monthly
+    Subscribe {
+        /// Subscriber user ID
+        #[arg(long)]
+        subscriber_id: String,
+        
+        /// Listing ID
+        #[arg(long)]
+        listing_id: String,
+    },
+    
+    /// Cancel a subscription, refunding the unused part of the period to credit
+    CancelSubscription {
+        /// Subscriber user ID
+        #[arg(long)]
+        subscriber_id: String,
+        
+        /// Subscription ID
+        #[arg(long)]
+        subscription_id: String,
+    },
+    
+    /// List a user's subscriptions and credit
+    MySubscriptions {
+        /// User ID
+        #[arg(long)]
+        user_id: String,
+    },
+    
+    /// Pay a creator their subscription earnings
+    PayoutCreator {
+        /// Creator user ID
+        #[arg(long)]
+        creator_id: String,
+    },
+    
+    /// Offer an NFT, membership, bot, channel or image for resale
+    Ask {
+        /// Seller user ID
+        #[arg(long)]
+        seller_id: String,
+        
+        /// Asset type (nft, membership, bot, channel, image)
+        #[arg(long)]
+        asset_type: String,
+        
+        /// NFT token ID, or membership, bot, channel or image ID
+        #[arg(long)]
+        asset_id: String,
+        
+        /// Asking price
+        #[arg(long)]
+        price: u64,
+    },
+    
+    /// Bid for an NFT, membership, bot, channel or image on the secondary market
+    Bid {
+        /// Bidder user ID
+        #[arg(long)]
+        bidder_id: String,
+        
+        /// Asset type (nft, membership, bot, channel, image)
+        #[arg(long)]
+        asset_type: String,
+        
+        /// NFT token ID, or membership, bot, channel or image ID
+        #[arg(long)]
+        asset_id: String,
+        
+        /// Transaction ID of the bidder's transfer into the market account; the bid is for the full amount
+        #[arg(long)]
+        payment_tx: String,
+    },
+    
+    /// Cancel an open ask or bid, refunding a bid's funds
+    CancelOrder {
+        /// Trader user ID
+        #[arg(long)]
+        trader_id: String,
+        
+        /// Order ID
+        #[arg(long)]
+        order_id: String,
+    },
+    
+    /// Show open orders and trade history for an asset
+    OrderBook {
+        /// Asset type (nft, membership, bot, channel, image)
+        #[arg(long)]
+        asset_type: String,
+        
+        /// NFT token ID, or membership, bot, channel or image ID
+        #[arg(long)]
+        asset_id: String,
+    },
+    
+    /// Split a creator's royalties and sale proceeds for an asset between recipients
+    SplitRevenue {
+        /// Creator user ID
+        #[arg(long)]
+        creator_id: String,
+        
+        /// Asset type (nft, membership, bot, channel, image)
+        #[arg(long)]
+        asset_type: String,
+        
+        /// NFT token ID, or membership, bot, channel or image ID
+        #[arg(long)]
+        asset_id: String,
+        
+        /// Recipient shares as USER_ID:BASIS_POINTS, summing to 10000
+        #[arg(long, value_delimiter = ',')]
+        shares: Vec<String>,
+    },
+    
+    /// Show an asset's provenance chain
+    Provenance {
+        /// Asset type (nft, membership, bot, channel, image)
+        #[arg(long)]
+        asset_type: String,
+        
+        /// NFT token ID, or membership, bot, channel or image ID
+        #[arg(long)]
+        asset_id: String,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -621,7 +894,7 @@ enum ChaosCommand {
     },
 }
 
-#[derive(Debug, Subcommand)]
+#[derive(Debug, Subcommand, Serialize, Deserialize)]
 enum GovernanceCommand {
     /// Submit a protocol upgrade proposal
     ProposeUpgrade {
@@ -750,6 +1023,20 @@ enum GovernanceCommand {
         proposal_id: String,
     },
     
+    /// List passed proposals waiting for their execution height
+    ListExecutions,
+    
+    /// Veto a queued execution as a guardian
+    VetoExecution {
+        /// Execution ID
+        #[arg(long)]
+        execution_id: String,
+        
+        /// Guardian user ID
+        #[arg(long)]
+        guardian: String,
+    },
+    
     /// Show current protocol version
     Version,
     
@@ -772,6 +1059,14 @@ enum GovernanceCommand {
         /// Total network stake
         #[arg(long)]
         total_stake: Option<u64>,
+        
+        /// Comma-separated user IDs of the guardians who can veto executions
+        #[arg(long)]
+        guardians: Option<String>,
+        
+        /// Guardian vetoes needed to cancel an execution
+        #[arg(long, default_value = "1")]
+        veto_threshold: usize,
     },
 }
 
@@ -858,7 +1153,7 @@ enum UpdateCommand {
 }
 
 /// Token and tokenomics commands
-#[derive(Debug, Subcommand)]
+#[derive(Debug, Subcommand, Serialize, Deserialize)]
 enum TokenCommand {
     /// Show token supply statistics
     Stats,
@@ -958,57 +1253,106 @@ enum TokenCommand {
         duration_blocks: Option<u64>,
     },
     
-    /// Process inflation for current block
+    /// Process inflation for currency chain blocks not yet processed
     ProcessInflation,
     
-    /// Transfer tokens between users
-    Transfer {
-        /// Sender user ID
+    /// Grant tokens that vest on a schedule
+    CreateGrant {
+        /// Beneficiary user ID
         #[arg(long)]
-        from: String,
+        beneficiary: String,
         
-        /// Recipient user ID
+        /// Grantor user ID; the grantor can revoke the grant and mark milestones
         #[arg(long)]
-        to: String,
+        grantor: String,
         
-        /// Amount to transfer
+        /// Total tokens granted
         #[arg(long)]
         amount: u64,
+        
+        /// Release schedule (cliff, linear, milestones)
+        #[arg(long)]
+        schedule: String,
+        
+        /// Height linear vesting starts at
+        #[arg(long)]
+        start: Option<u64>,
+        
+        /// Height nothing is claimable before (required for cliff grants)
+        #[arg(long)]
+        cliff: Option<u64>,
+        
+        /// Height linear vesting completes at
+        #[arg(long)]
+        end: Option<u64>,
+        
+        /// Milestone tranches as AMOUNT:DESCRIPTION, summing to the amount
+        #[arg(long, value_delimiter = ',')]
+        milestones: Vec<String>,
+        
+        /// Let the grantor revoke what has not vested
+        #[arg(long)]
+        revocable: bool,
     },
     
-    /// Check balance
-    Balance {
-        /// User ID
+    /// Claim vested tokens
+    Claim {
+        /// Beneficiary user ID
         #[arg(long)]
         user_id: String,
+        
+        /// Grant to claim from (default: all of the user's grants)
+        #[arg(long)]
+        grant_id: Option<String>,
+        
+        /// Beneficiary's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the beneficiary will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
     },
-}
-
-#[derive(Debug, Subcommand)]
-enum AccountCommand {
-    /// Create a new user account
-    Create {
-        /// Username for the account
+    
+    /// Revoke a revocable grant, cancelling what has not vested
+    RevokeGrant {
+        /// Grant ID
         #[arg(long)]
-        username: String,
+        grant_id: String,
         
-        /// Save keys to file
-        #[arg(long, default_value = "user_keys.json")]
-        save_to: PathBuf,
+        /// Grantor user ID
+        #[arg(long)]
+        grantor: String,
     },
-
-    /// List all users
-    List,
-
-    /// Get user profile information
-    Profile {
-        /// User ID to lookup
+    
+    /// Mark a milestone of a grant reached
+    ReachMilestone {
+        /// Grant ID
+        #[arg(long)]
+        grant_id: String,
+        
+        /// Grantor user ID
+        #[arg(long)]
+        grantor: String,
+        
+        /// Milestone index, from 0
+        #[arg(long)]
+        index: usize,
+    },
+    
+    /// List a user's vesting grants
+    Grants {
+        /// Beneficiary user ID
         #[arg(long)]
         user_id: String,
     },
-
-    /// Send direct message
-    SendDm {
+    
+    /// Transfer tokens between users
+    Transfer {
         /// Sender user ID
         #[arg(long)]
         from: String,
@@ -1017,170 +1361,614 @@ enum AccountCommand {
         #[arg(long)]
         to: String,
         
-        /// Message content
+        /// Amount to transfer
         #[arg(long)]
-        message: String,
+        amount: u64,
+        
+        /// Sender's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the sender will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
     },
-
-    /// Create a new channel
-    CreateChannel {
-        /// Creator user ID
+    
+    /// Swap native tokens for a ledger token or back through its pool
+    Swap {
+        /// Trader user ID
         #[arg(long)]
-        creator_id: String,
+        from: String,
         
-        /// Channel name
+        /// Symbol of the token paired with the native token
         #[arg(long)]
-        name: String,
+        token: String,
         
-        /// Channel description
+        /// "buy" pays native tokens for the token, "sell" pays the token for native tokens
+        #[arg(long, default_value = "buy")]
+        direction: String,
+        
+        /// Amount paid in
         #[arg(long)]
-        description: Option<String>,
-    },
-
-    /// Post message to channel
-    PostChannel {
-        /// Sender user ID
+        amount: u64,
+        
+        /// Least the swap may pay out (default: quote less the slippage tolerance)
         #[arg(long)]
-        user_id: String,
+        min_out: Option<u64>,
         
-        /// Channel ID
+        /// Slippage tolerance below the current quote, in basis points
+        #[arg(long, default_value = "50")]
+        slippage_bps: u16,
+        
+        /// Trader's key file (JSON with a "private_key" field)
         #[arg(long)]
-        channel_id: String,
+        key_file: PathBuf,
         
-        /// Message content
+        /// Most the trader will pay in fees (default: current base fee plus tip)
         #[arg(long)]
-        message: String,
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
     },
-
-    /// Get user's direct messages
-    GetDms {
-        /// User ID
+    
+    /// Deposit native tokens and a ledger token into its pool for LP shares
+    AddLiquidity {
+        /// Provider user ID
         #[arg(long)]
-        user_id: String,
-    },
-
-    /// Get channel messages
-    GetChannelMessages {
-        /// Channel ID
+        from: String,
+        
+        /// Symbol of the token paired with the native token
         #[arg(long)]
-        channel_id: String,
+        token: String,
+        
+        /// Most native tokens to deposit
+        #[arg(long)]
+        native_amount: u64,
+        
+        /// Most of the token to deposit
+        #[arg(long)]
+        token_amount: u64,
+        
+        /// Least LP shares the deposit may mint
+        #[arg(long, default_value = "0")]
+        min_shares: u64,
+        
+        /// Provider's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the provider will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
     },
-}
-
-#[derive(Debug, Subcommand)]
-enum DatabaseCommand {
-    /// Run database migrations
-    Migrate,
-    /// Backup database to file
-    Backup {
-        /// Output file path
-        output: PathBuf,
+    
+    /// Burn LP shares for their cut of both pool reserves
+    RemoveLiquidity {
+        /// Provider user ID
+        #[arg(long)]
+        from: String,
+        
+        /// Symbol of the token paired with the native token
+        #[arg(long)]
+        token: String,
+        
+        /// LP shares to burn
+        #[arg(long)]
+        shares: u64,
+        
+        /// Least native tokens to receive
+        #[arg(long, default_value = "0")]
+        min_native: u64,
+        
+        /// Least of the token to receive
+        #[arg(long, default_value = "0")]
+        min_token: u64,
+        
+        /// Provider's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the provider will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
     },
-    /// Restore database from backup
-    Restore {
-        /// Input file path
-        input: PathBuf,
+    
+    /// Show a swap pool's reserves and prices
+    SwapPool {
+        /// Symbol of the token paired with the native token
+        #[arg(long)]
+        token: String,
+        
+        /// Blocks the time-weighted average price covers
+        #[arg(long, default_value = "100")]
+        window: u64,
+    },
+    
+    /// Issue a new token with the sender as its issuer
+    IssueToken {
+        /// Issuer user ID
+        #[arg(long)]
+        from: String,
+        
+        /// Token symbol (uppercase letters and digits)
+        #[arg(long)]
+        symbol: String,
+        
+        /// Token name
+        #[arg(long)]
+        name: String,
+        
+        /// Decimal places the token is displayed with
+        #[arg(long, default_value = "0")]
+        decimals: u8,
+        
+        /// Cap on the token's total supply (default: uncapped)
+        #[arg(long)]
+        max_supply: Option<u64>,
+        
+        /// Amount credited to the issuer
+        #[arg(long, default_value = "0")]
+        initial_supply: u64,
+        
+        /// Issuer's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the issuer will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
+    },
+    
+    /// Mint more of a token you issued
+    MintToken {
+        /// Issuer user ID
+        #[arg(long)]
+        from: String,
+        
+        /// Token symbol
+        #[arg(long)]
+        symbol: String,
+        
+        /// Recipient user ID
+        #[arg(long)]
+        to: String,
+        
+        /// Amount to mint
+        #[arg(long)]
+        amount: u64,
+        
+        /// Issuer's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the issuer will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
+    },
+    
+    /// Burn issued tokens you hold
+    BurnToken {
+        /// Holder user ID
+        #[arg(long)]
+        from: String,
+        
+        /// Token symbol
+        #[arg(long)]
+        symbol: String,
+        
+        /// Amount to burn
+        #[arg(long)]
+        amount: u64,
+        
+        /// Holder's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the holder will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
+    },
+    
+    /// Send issued tokens to another user
+    SendToken {
+        /// Sender user ID
+        #[arg(long)]
+        from: String,
+        
+        /// Token symbol
+        #[arg(long)]
+        symbol: String,
+        
+        /// Recipient user ID
+        #[arg(long)]
+        to: String,
+        
+        /// Amount to send
+        #[arg(long)]
+        amount: u64,
+        
+        /// Sender's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the sender will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
+    },
+    
+    /// Lock native or issued tokens, e.g. to join a bonded token-gated channel
+    Bond {
+        /// Holder user ID
+        #[arg(long)]
+        from: String,
+        
+        /// Token symbol (DCHAT for the native token)
+        #[arg(long, default_value = "DCHAT")]
+        token: String,
+        
+        /// Amount to bond
+        #[arg(long)]
+        amount: u64,
+        
+        /// Blocks the tokens stay locked for
+        #[arg(long)]
+        lock_blocks: u64,
+        
+        /// Holder's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the holder will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
+    },
+    
+    /// Release bonded tokens whose lock has passed
+    Unbond {
+        /// Holder user ID
+        #[arg(long)]
+        from: String,
+        
+        /// Token symbol (DCHAT for the native token)
+        #[arg(long, default_value = "DCHAT")]
+        token: String,
+        
+        /// Amount to unbond
+        #[arg(long)]
+        amount: u64,
+        
+        /// Holder's key file (JSON with a "private_key" field)
+        #[arg(long)]
+        key_file: PathBuf,
+        
+        /// Most the holder will pay in fees (default: current base fee plus tip)
+        #[arg(long)]
+        max_fee: Option<u64>,
+        
+        /// Tip paid on top of the base fee
+        #[arg(long, default_value = "0")]
+        tip: u64,
+    },
+    
+    /// Show an issued token's metadata and supply
+    TokenInfo {
+        /// Token symbol
+        #[arg(long)]
+        symbol: String,
+    },
+    
+    /// Check balance
+    Balance {
+        /// User ID
+        #[arg(long)]
+        user_id: String,
     },
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Debug, Subcommand, Serialize, Deserialize)]
+enum DeviceCommand {
+    /// Show a pairing code for a new device (run on the primary device)
+    Pair,
 
-    // Initialize logging
-    init_logging(&cli.log_level, cli.json_logs)?;
+    /// Join another device's identity with the code it shows
+    Join {
+        /// QR payload (`dchat-pair:1:...`) or the typed `1234-5678` code
+        #[arg(long)]
+        code: String,
 
-    info!("🚀 dchat v{} starting...", VERSION);
-    info!("Mode: {:?}", cli.command);
+        /// Identity being joined; needed with a typed code
+        #[arg(long)]
+        user_id: Option<String>,
 
-    // Load configuration
-    let config = load_config(&cli.config).await?;
-    info!("✓ Configuration loaded from {:?}", cli.config);
+        /// Name for this device
+        #[arg(long)]
+        name: String,
 
-    // Execute command
-    match cli.command {
-        Commands::Relay {
-            listen,
-            bootstrap,
-            hsm,
-            kms_key_id,
-            stake,
-        } => {
-            run_relay_node(config, listen, bootstrap, hsm, kms_key_id, stake, cli.metrics_addr.clone(), cli.health_addr.clone()).await
-        }
-        Commands::User { bootstrap, identity, username, non_interactive } => {
-            run_user_node(config, bootstrap, identity, username, non_interactive).await
-        }
-        Commands::Validator { key, chain_rpc, hsm, stake, producer } => {
-            run_validator_node(config, key, chain_rpc, hsm, stake, producer, cli.metrics_addr.clone(), cli.health_addr.clone()).await
-        }
-        Commands::Testnet { validators, relays, clients, data_dir, observability } => {
-            run_testnet(config, validators, relays, clients, data_dir, observability).await
-        }
-        Commands::Keygen { output, burner } => {
-            generate_keys(output, burner).await
-        }
-        Commands::Account { action } => {
-            run_account_command(config, action).await
-        }
-        Commands::Database { action } => {
-            run_database_command(config, action).await
-        }
-        Commands::Health { url } => {
-            check_health(&url).await
-        }
-        Commands::Bot { action } => {
-            run_bot_command(config, action).await
-        }
-        Commands::Marketplace { action } => {
-            run_marketplace_command(config, action).await
-        }
-        Commands::Accessibility { action } => {
-            run_accessibility_command(action).await
-        }
-        Commands::Chaos { action } => {
-            run_chaos_command(action).await
-        }
-        Commands::Governance { action } => {
-            run_governance_command(action).await
-        }
-        Commands::Token { action } => {
-            run_token_command(action).await
-        }
-        Commands::Update { action } => {
-            run_update_command(action).await
-        }
-    }
+        /// Device type (desktop, mobile, web, server or free text)
+        #[arg(long, default_value = "desktop")]
+        device_type: String,
+    },
+
+    /// List devices paired to this identity
+    List,
+
+    /// Revoke a paired device and rotate the sync key of the others
+    Revoke {
+        /// Device ID
+        #[arg(long)]
+        device_id: String,
+    },
 }
 
-/// Initialize logging with tracing-subscriber
-fn init_logging(log_level: &str, json: bool) -> Result<()> {
-    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(log_level));
+#[derive(Debug, Subcommand, Serialize, Deserialize)]
+enum AccountCommand {
+    /// Create a new user account
+    Create {
+        /// Username for the account
+        #[arg(long)]
+        username: String,
+        
+        /// Save keys to file
+        #[arg(long, default_value = "user_keys.json")]
+        save_to: PathBuf,
+    },
 
-    if json {
-        // JSON structured logging for production
-        tracing_subscriber::registry()
-            .with(env_filter)
-            .with(tracing_subscriber::fmt::layer().compact())
-            .init();
-    } else {
-        // Pretty logging for development
-        tracing_subscriber::registry()
-            .with(env_filter)
-            .with(tracing_subscriber::fmt::layer().pretty())
-            .init();
-    }
+    /// List all users
+    List,
 
-    Ok(())
-}
+    /// Get user profile information
+    Profile {
+        /// User ID to lookup
+        #[arg(long)]
+        user_id: String,
+    },
 
-/// Load configuration from file or use defaults
-async fn load_config(path: &PathBuf) -> Result<Config> {
-    if path.exists() {
-        info!("Loading config from {:?}", path);
+    /// Send direct message
+    SendDm {
+        /// Sender user ID
+        #[arg(long)]
+        from: String,
         
-        // Read TOML file
+        /// Recipient user ID
+        #[arg(long)]
+        to: String,
+        
+        /// Message content
+        #[arg(long)]
+        message: String,
+    },
+
+    /// Create a new channel
+    CreateChannel {
+        /// Creator user ID
+        #[arg(long)]
+        creator_id: String,
+        
+        /// Channel name
+        #[arg(long)]
+        name: String,
+        
+        /// Channel description
+        #[arg(long)]
+        description: Option<String>,
+    },
+
+    /// Post message to channel
+    PostChannel {
+        /// Sender user ID
+        #[arg(long)]
+        user_id: String,
+        
+        /// Channel ID
+        #[arg(long)]
+        channel_id: String,
+        
+        /// Message content
+        #[arg(long)]
+        message: String,
+    },
+
+    /// Get user's direct messages
+    GetDms {
+        /// User ID
+        #[arg(long)]
+        user_id: String,
+    },
+
+    /// Get channel messages
+    GetChannelMessages {
+        /// Channel ID
+        #[arg(long)]
+        channel_id: String,
+    },
+
+    /// Restore identity, device and burner keys from a recovery phrase
+    ///
+    /// The phrase is read from stdin, or from `--mnemonic-file`, never from
+    /// the command line where `ps` and shell history would see it.
+    Restore {
+        /// File holding the BIP-39 recovery phrase (default: prompt on stdin)
+        #[arg(long)]
+        mnemonic_file: Option<PathBuf>,
+
+        /// Output file path; the device key is saved alongside as `<name>.device.key`
+        #[arg(short, long, default_value = "identity.json")]
+        output: PathBuf,
+
+        /// Restore the burner identity instead of the main identity
+        #[arg(long)]
+        burner: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum DatabaseCommand {
+    /// Run database migrations
+    Migrate,
+    /// Backup database to file
+    Backup {
+        /// Output file path
+        output: PathBuf,
+    },
+    /// Restore database from backup
+    Restore {
+        /// Input file path
+        input: PathBuf,
+    },
+    /// Write the checkpoints and recent blocks a new node bootstraps from
+    Snapshot {
+        /// Output file path
+        output: PathBuf,
+    },
+    /// Start this node's database from another node's snapshot
+    Bootstrap {
+        /// Snapshot file path
+        input: PathBuf,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // Initialize logging
+    init_logging(&cli.log_level, cli.json_logs)?;
+
+    info!("🚀 dchat v{} starting...", VERSION);
+    info!("Mode: {:?}", cli.command);
+
+    // Load configuration
+    let config = load_config(&cli.config).await?;
+    info!("✓ Configuration loaded from {:?}", cli.config);
+
+    // Execute command
+    match cli.command {
+        Commands::Relay {
+            listen,
+            bootstrap,
+            hsm,
+            kms_key_id,
+            stake,
+        } => {
+            let node_type = cli.node_type.unwrap_or(NodeType::Light);
+            run_relay_node(config, listen, bootstrap, hsm, kms_key_id, stake, node_type, cli.metrics_addr.clone(), cli.health_addr.clone()).await
+        }
+        Commands::User { bootstrap, identity, key, stealth_decoys, username, non_interactive, daemon } => {
+            let node_type = cli.node_type.unwrap_or(NodeType::Light);
+            run_user_node(config, bootstrap, identity, key, stealth_decoys, username, non_interactive, daemon, node_type).await
+        }
+        Commands::Validator { key, chain_rpc, hsm, stake, producer } => {
+            let node_type = cli.node_type.unwrap_or(NodeType::Full);
+            run_validator_node(config, key, chain_rpc, hsm, stake, producer, node_type, cli.metrics_addr.clone(), cli.health_addr.clone()).await
+        }
+        Commands::Testnet { validators, relays, clients, data_dir, observability } => {
+            run_testnet(config, validators, relays, clients, data_dir, observability).await
+        }
+        Commands::Keygen { output, burner, mnemonic, words } => {
+            if mnemonic {
+                generate_keys_from_mnemonic(output, burner, words).await
+            } else {
+                generate_keys(output, burner).await
+            }
+        }
+        Commands::Account { action } => {
+            run_account_command(config, action).await
+        }
+        Commands::Database { action } => {
+            run_database_command(config, action, cli.node_type.unwrap_or(NodeType::Full)).await
+        }
+        Commands::Health { url } => {
+            check_health(&url).await
+        }
+        Commands::Bot { action } => {
+            run_bot_command(config, action).await
+        }
+        Commands::Marketplace { action } => {
+            run_marketplace_command(config, action).await
+        }
+        Commands::Accessibility { action } => {
+            run_accessibility_command(action).await
+        }
+        Commands::Chaos { action } => {
+            run_chaos_command(action).await
+        }
+        Commands::Governance { action } => {
+            run_governance_command(config, action).await
+        }
+        Commands::Token { action } => {
+            run_token_command(config, action).await
+        }
+        Commands::Device { action } => {
+            run_device_command(config, action).await
+        }
+        Commands::Update { action } => {
+            run_update_command(action).await
+        }
+    }
+}
+
+/// Initialize logging with tracing-subscriber
+fn init_logging(log_level: &str, json: bool) -> Result<()> {
+    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(log_level));
+
+    if json {
+        // JSON structured logging for production
+        tracing_subscriber::registry()
+            .with(env_filter)
+            .with(tracing_subscriber::fmt::layer().compact())
+            .init();
+    } else {
+        // Pretty logging for development
+        tracing_subscriber::registry()
+            .with(env_filter)
+            .with(tracing_subscriber::fmt::layer().pretty())
+            .init();
+    }
+
+    Ok(())
+}
+
+/// Load configuration from file or use defaults
+async fn load_config(path: &PathBuf) -> Result<Config> {
+    if path.exists() {
+        info!("Loading config from {:?}", path);
+        
+        // Read TOML file
         let contents = tokio::fs::read_to_string(path)
             .await
             .map_err(|e| Error::Config(format!("Failed to read config file: {}", e)))?;
@@ -1242,6 +2030,7 @@ async fn run_relay_node(
     use_hsm: bool,
     _kms_key_id: Option<String>,
     stake_amount: u64,
+    node_type: NodeType,
     metrics_addr: String,
     health_addr: String,
 ) -> Result<()> {
@@ -1324,10 +2113,12 @@ async fn run_relay_node(
     });
     info!("✓ Relay node started");
 
+    let control_handle = start_control_api(&config, "relay", peer_id, None, shutdown_tx.subscribe()).await;
+
     // Initialize storage
-    let db_config = DatabaseConfig::default();
-    let _database = Database::new(db_config).await?;
-    info!("✓ Database initialized");
+    let database = open_state_database(&config).await?;
+    info!("✓ Database initialized ({:?} node)", node_type);
+    let pruning_handle = start_pruning_task(&config, &database, node_type, shutdown_tx.subscribe()).await;
 
     info!("🎉 Relay node is ready!");
     info!("Press Ctrl+C to shutdown gracefully...");
@@ -1351,6 +2142,9 @@ async fn run_relay_node(
         tokio::time::Duration::from_secs(30),
         async {
             let _ = tokio::join!(health_handle, metrics_handle, relay_handle);
+            for handle in [control_handle, pruning_handle].into_iter().flatten() {
+                let _ = handle.await;
+            }
         }
     ).await.map_err(|_| Error::network("Shutdown timeout".to_string()))?;
 
@@ -1360,11 +2154,15 @@ async fn run_relay_node(
 
 /// Run as user node
 async fn run_user_node(
-    _config: Config,
+    config: Config,
     bootstrap_peers: Vec<String>,
     identity_path: Option<PathBuf>,
+    key_path: Option<PathBuf>,
+    stealth_decoys: usize,
     username: Option<String>,
     non_interactive: bool,
+    daemon: bool,
+    node_type: NodeType,
 ) -> Result<()> {
     info!("👤 Starting user node...");
     
@@ -1466,9 +2264,9 @@ async fn run_user_node(
     info!("✓ Subscription exchange complete - {} mesh peers for #global", final_mesh_count);
     
     // Initialize storage
-    let db_config = DatabaseConfig::default();
-    let database = Database::new(db_config).await?;
-    info!("✓ Database initialized");
+    let database = open_state_database(&config).await?;
+    info!("✓ Database initialized ({:?} node)", node_type);
+    let pruning_handle = start_pruning_task(&config, &database, node_type, shutdown_tx.subscribe()).await;
     
     if non_interactive {
         // Non-interactive mode for testing
@@ -1524,26 +2322,209 @@ async fn run_user_node(
     } else {
         // Interactive mode
         info!("🎉 User client is ready!");
-        info!("Type your messages and press Enter to send to #global");
+        if daemon {
+            info!("Running as a daemon; use the control socket to send messages");
+        } else {
+            info!("Type your messages and press Enter to send to #global");
+        }
         info!("Press Ctrl+C to exit");
         
         use std::sync::Arc;
         use tokio::sync::Mutex;
         
+        // Stealth DMs arrive without a recipient, so trial-decrypt everything
+        // published on the stealth topic in the background
+        network.subscribe_to_channel(STEALTH_DM_CHANNEL).ok();
+        let master_key = load_user_master_key(&config, key_path).await?;
+        let (view_key, spend_key) = IdentityDerivation::derive_stealth_keys(&master_key)?;
+        let stealth_scanner = StealthScanner::from_secret_bytes(
+            *view_key.private_key().as_bytes(),
+            *spend_key.private_key().as_bytes(),
+        );
+        let stealth_address = stealth_scanner.address();
+        info!("🕶️ Stealth address: {}", encode_stealth_address(&stealth_address));
+        if !daemon {
+            info!("Send a stealth DM with: /dm <stealth address> <message>");
+        }
+        let (stealth_handle, mut stealth_inbox) = StealthScanService::spawn(stealth_scanner, 1024);
+        let stealth_rx_handle = tokio::spawn(async move {
+            while let Some(dm) = stealth_inbox.recv().await {
+                let msg_text = String::from_utf8_lossy(&dm.encrypted_payload);
+                println!("\n[stealth DM] {}: {}", dm.sender, msg_text);
+                print!("You: ");
+                use std::io::Write;
+                std::io::stdout().flush().ok();
+            }
+        });
+        
+        // Pair devices over this identity's pairing topic and track revoked
+        // device certificates so we stop trusting them
+        let pairing_topic = device_pairing_topic(&identity.user_id);
+        network.subscribe_to_channel(&pairing_topic).ok();
+        network.subscribe_to_channel(DEVICE_REVOCATION_CHANNEL).ok();
+        let state_db = open_state_database(&config).await?;
+        let device_state = state_db.load_state(DEVICE_STATE, DEVICE_STATE_VERSION, DeviceRecords::default).await?;
+        let paired_key_path = config.storage.data_dir.join(PAIRED_DEVICE_KEY_FILE);
+        let paired_key = match device_state.paired {
+            Some(_) => Some(load_key_file(&paired_key_path).await?),
+            None => None,
+        };
+        let devices = NodeDevices::restore(identity.user_id.clone(), master_key, &device_state, paired_key)?;
+        let device_store = DeviceStore { database: state_db, state: tokio::sync::Mutex::new(device_state), paired_key_path };
+        
         // Wrap network in Arc<Mutex> for shared access
         let network_arc = Arc::new(Mutex::new(network));
         let network_clone = network_arc.clone();
         
+        // Expose messaging, channels and node state to the CLI and automation
+        let messaging = Arc::new(NodeMessaging::new(
+            network_arc.clone(),
+            identity.user_id.clone(),
+            &["global", STEALTH_DM_CHANNEL, DEVICE_REVOCATION_CHANNEL, &pairing_topic],
+            stealth_address,
+            stealth_decoys,
+            devices,
+            device_store,
+        ));
+        let control_handle = start_control_api(
+            &config,
+            "user",
+            peer_id,
+            Some(messaging.clone()),
+            shutdown_tx.subscribe(),
+        )
+        .await;
+        
+        let tx_messaging = messaging.clone();
+        
         // Spawn message receiver
         let rx_identity = identity.user_id.clone();
         let rx_handle = tokio::spawn(async move {
+            let mut last_regossip = tokio::time::Instant::now();
             loop {
-                if let Some(event) = network_clone.lock().await.next_event().await {
-                    if let NetworkEvent::MessageReceived { from, message } = event {
-                        if let DchatMessage::ChannelMessage { sender, channel_id, encrypted_payload } = message {
+                if last_regossip.elapsed() >= tokio::time::Duration::from_secs(REVOCATION_REGOSSIP_SECS) {
+                    last_regossip = tokio::time::Instant::now();
+                    let known: Vec<DeviceRevocation> =
+                        messaging.devices.lock().unwrap().registry.revocations().cloned().collect();
+                    for revocation in &known {
+                        if let Err(e) = messaging.publish_revocation(revocation).await {
+                            tracing::debug!("Revocation of {} not gossiped again: {}", revocation.device_id, e);
+                        }
+                    }
+                }
+                
+                // Poll in short slices so control requests can publish in between
+                let event = {
+                    let mut network = network_clone.lock().await;
+                    tokio::time::timeout(tokio::time::Duration::from_millis(100), network.next_event()).await
+                };
+                let Ok(event) = event else { continue };
+                if let Some(NetworkEvent::MessageReceived { from, message }) = event {
+                    match message {
+                        DchatMessage::ChannelMessage { sender, channel_id, encrypted_payload } => {
                             if sender != rx_identity {
-                                let msg_text = String::from_utf8_lossy(&encrypted_payload);
-                                println!("\n[#{}] {}: {}", channel_id, from, msg_text);
+                                messaging.record(&channel_id, &from, &encrypted_payload);
+                                if !daemon {
+                                    let msg_text = String::from_utf8_lossy(&encrypted_payload);
+                                    println!("\n[#{}] {}: {}", channel_id, from, msg_text);
+                                    print!("You: ");
+                                    use std::io::Write;
+                                    std::io::stdout().flush().ok();
+                                }
+                            }
+                        }
+                        DchatMessage::StealthDirectMessage { payload } => {
+                            if let Err(e) = stealth_handle.try_submit(payload) {
+                                warn!("⚠️  Dropped stealth payload: {}", e);
+                            }
+                        }
+                        DchatMessage::DevicePairing { payload } => {
+                            let reply = PairingMessage::from_bytes(&payload)
+                                .and_then(|message| messaging.devices.lock().unwrap().handle_pairing(message));
+                            if let Err(e) = messaging.save_devices().await {
+                                warn!("⚠️  Failed to save device state: {}", e);
+                            }
+                            match reply {
+                                Ok(Some((topic, reply))) => {
+                                    if let Err(e) = messaging.publish_pairing(&topic, &reply).await {
+                                        warn!("⚠️  Failed to answer pairing message: {}", e);
+                                    }
+                                }
+                                Ok(None) => {}
+                                Err(e) => warn!("⚠️  Pairing message from {} failed: {}", from, e),
+                            }
+                        }
+                        DchatMessage::DeviceRevocation { payload } => {
+                            let applied = DeviceRevocation::from_bytes(&payload).and_then(|revocation| {
+                                let device_id = revocation.device_id.clone();
+                                let new = messaging.devices.lock().unwrap().registry.apply_revocation(revocation)?;
+                                Ok((device_id, new))
+                            });
+                            match applied {
+                                // Gossipsub forwards it to our mesh peers; the periodic
+                                // re-gossip above covers peers that join later
+                                Ok((device_id, true)) => {
+                                    info!("📵 Device {} revoked", device_id);
+                                    if let Err(e) = messaging.save_devices().await {
+                                        warn!("⚠️  Failed to save device state: {}", e);
+                                    }
+                                }
+                                Ok((_, false)) => {}
+                                Err(e) => warn!("⚠️  Ignored invalid device revocation from {}: {}", from, e),
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        });
+        
+        if daemon {
+            signal::ctrl_c().await?;
+            info!("🛑 Received shutdown signal (Ctrl+C)");
+        } else {
+            // Read user input
+            use std::io::{self, BufRead};
+            let stdin = io::stdin();
+            let reader = stdin.lock();
+        
+            let tx_identity = identity.user_id.clone();
+        
+            for line in reader.lines() {
+                if let Ok(text) = line {
+                    if let Some(dm) = text.strip_prefix("/dm ") {
+                        let sent = match dm.trim().split_once(' ') {
+                            Some((to, body)) => match parse_stealth_address(to) {
+                                Ok(recipient) => tx_messaging.send_stealth(&recipient, body, stealth_decoys).await,
+                                Err(e) => Err(e),
+                            },
+                            None => Err(Error::validation("Usage: /dm <stealth address> <message>")),
+                        };
+                        match sent {
+                            Ok(payloads) => println!("Stealth DM sent ({} payloads)", payloads),
+                            Err(e) => println!("Error sending stealth DM: {}", e),
+                        }
+                        print!("You: ");
+                        use std::io::Write;
+                        std::io::stdout().flush().ok();
+                    } else if !text.trim().is_empty() {
+                        let message = DchatMessage::ChannelMessage {
+                            sender: tx_identity.clone(),
+                            channel_id: "global".to_string(),
+                            encrypted_payload: text.as_bytes().to_vec(),
+                        };
+                    
+                        match network_arc.lock().await.publish_to_channel("global", &message) {
+                            Ok(_) => {
+                                info!("📤 Sent: {}", text);
+                                println!("Message sent!");
+                                print!("You: ");
+                                use std::io::Write;
+                                std::io::stdout().flush().ok();
+                            }
+                            Err(e) => {
+                                info!("❌ Failed to send: {}", e);
+                                println!("Error sending message: {}", e);
                                 print!("You: ");
                                 use std::io::Write;
                                 std::io::stdout().flush().ok();
@@ -1552,608 +2533,1648 @@ async fn run_user_node(
                     }
                 }
             }
-        });
+        }
         
-        // Read user input
-        use std::io::{self, BufRead};
-        let stdin = io::stdin();
-        let reader = stdin.lock();
+        rx_handle.abort();
+        stealth_rx_handle.abort();
+        let _ = shutdown_tx.send(());
+        if let Some(handle) = control_handle {
+            let _ = handle.await;
+        }
+    }
+    
+    // Graceful shutdown
+    info!("Shutting down user client...");
+    let _ = shutdown_tx.send(());
+    if let Some(handle) = pruning_handle {
+        let _ = handle.await;
+    }
+    database.close().await?;
+    info!("✓ Shutdown complete");
+    Ok(())
+}
+
+/// Run full testnet with all components
+async fn run_testnet(
+    _config: Config,
+    num_validators: usize,
+    num_relays: usize,
+    num_clients: usize,
+    data_dir: PathBuf,
+    enable_observability: bool,
+) -> Result<()> {
+    info!("🚀 Launching full testnet...");
+    info!("  Validators: {}", num_validators);
+    info!("  Relays: {}", num_relays);
+    info!("  Clients: {}", num_clients);
+    info!("  Data directory: {:?}", data_dir);
+    
+    // Create testnet directory structure
+    std::fs::create_dir_all(&data_dir)?;
+    let validators_dir = data_dir.join("validators");
+    let relays_dir = data_dir.join("relays");
+    let clients_dir = data_dir.join("clients");
+    
+    std::fs::create_dir_all(&validators_dir)?;
+    std::fs::create_dir_all(&relays_dir)?;
+    std::fs::create_dir_all(&clients_dir)?;
+    
+    info!("✓ Created testnet directories");
+    
+    // Generate genesis configuration
+    info!("Generating genesis configuration...");
+    let genesis = generate_genesis_config(num_validators)?;
+    let genesis_path = data_dir.join("genesis.json");
+    std::fs::write(&genesis_path, serde_json::to_string_pretty(&genesis)?)?;
+    info!("✓ Genesis configuration written to {:?}", genesis_path);
+    
+    // Generate validator keys
+    info!("Generating validator keys...");
+    let mut validator_keys = Vec::new();
+    for i in 0..num_validators {
+        let keypair = KeyPair::generate();
+        let key_path = validators_dir.join(format!("validator_{}.key", i));
+        save_key_file(&key_path, &keypair).await?;
+        validator_keys.push(keypair);
+        info!("  ✓ Validator {} key: {:?}", i, key_path);
+    }
+    
+    // Generate relay identities
+    info!("Generating relay identities...");
+    let mut relay_addrs = Vec::new();
+    for i in 0..num_relays {
+        let base_port = 7070 + (i * 2);
+        let addr = format!("/ip4/127.0.0.1/tcp/{}", base_port);
+        relay_addrs.push(addr.clone());
+        info!("  ✓ Relay {} address: {}", i, addr);
+    }
+    
+    // Create testnet coordination file
+    let testnet_info = serde_json::json!({
+        "validators": num_validators,
+        "relays": num_relays,
+        "clients": num_clients,
+        "relay_addresses": relay_addrs,
+        "genesis_path": genesis_path,
+        "started_at": chrono::Utc::now().to_rfc3339(),
+    });
+    
+    let info_path = data_dir.join("testnet-info.json");
+    std::fs::write(&info_path, serde_json::to_string_pretty(&testnet_info)?)?;
+    info!("✓ Testnet info written to {:?}", info_path);
+    
+    // Create docker-compose for testnet
+    if enable_observability {
+        info!("Generating docker-compose with observability stack...");
+        generate_testnet_compose(
+            &data_dir,
+            num_validators,
+            num_relays,
+            num_clients,
+            &relay_addrs,
+            true,
+        )?;
+    } else {
+        generate_testnet_compose(
+            &data_dir,
+            num_validators,
+            num_relays,
+            num_clients,
+            &relay_addrs,
+            false,
+        )?;
+    }
+    
+    info!("\n🎉 Testnet configuration complete!");
+    info!("\nNext steps:");
+    info!("  1. Review configuration: {:?}", info_path);
+    info!("  2. Start validators: docker-compose -f {:?}/docker-compose.yml up validators", data_dir);
+    info!("  3. Start relays: docker-compose -f {:?}/docker-compose.yml up relays", data_dir);
+    info!("  4. Start clients: docker-compose -f {:?}/docker-compose.yml up clients", data_dir);
+    info!("\nOr start everything:");
+    info!("  docker-compose -f {:?}/docker-compose.yml up -d", data_dir);
+    
+    Ok(())
+}
+
+/// Run as validator node
+async fn run_validator_node(
+    config: Config,
+    key_path: String,
+    chain_rpc: String,
+    use_hsm: bool,
+    stake_amount: u64,
+    is_producer: bool,
+    node_type: NodeType,
+    metrics_addr: String,
+    health_addr: String,
+) -> Result<()> {
+    info!("⚙️  Starting validator node...");
+    info!("Chain RPC: {}", chain_rpc);
+    info!("HSM enabled: {}", use_hsm);
+    info!("Stake: {} tokens", stake_amount);
+    info!("Block producer: {}", is_producer);
+    
+    // Create shutdown channel
+    let (shutdown_tx, mut shutdown_rx) = broadcast::channel::<()>(1);
+    
+    // Start health check server
+    let health_handle = start_health_server(&health_addr, shutdown_tx.subscribe())?;
+    info!("✓ Health server listening on {}", health_addr);
+    
+    // Start metrics server
+    let metrics_handle = start_metrics_server(&metrics_addr, shutdown_tx.subscribe())?;
+    info!("✓ Metrics server listening on {}", metrics_addr);
+    
+    // Load validator key
+    // The HSM session must outlive the node so the key stays usable for signing
+    let (validator_id, hsm_key, file_key) = if use_hsm {
+        let uri = Pkcs11Uri::parse(&key_path)?;
+        info!("Loading validator key from HSM: token={} object={}", uri.token, uri.object);
+        let hsm_key = Pkcs11Key::open(&uri)?;
+        (hsm_key.public_key().clone(), Some(hsm_key), None)
+    } else {
+        info!("Loading validator key from file: {}", key_path);
+        let validator_key = load_key_file(&PathBuf::from(key_path)).await?;
+        (validator_key.public_key().clone(), None, Some(validator_key))
+    };
+    let sign_transaction = |envelope: TransactionEnvelope| -> Result<SignedTransaction> {
+        let signature = match (&hsm_key, &file_key) {
+            (Some(hsm_key), _) => hsm_key.sign(&envelope.signing_bytes())?,
+            (None, Some(keypair)) => dchat_crypto::signatures::sign(keypair.private_key(), &envelope.signing_bytes()),
+            (None, None) => unreachable!("validator key is loaded from the HSM or a file"),
+        };
+        Ok(SignedTransaction { envelope, signature: signature.to_bytes().to_vec() })
+    };
+    
+    info!("✓ Validator key loaded: {:?}", validator_id);
+    
+    // Initialize network for validator
+    let network_config = NetworkConfig::default();
+    let mut network = NetworkManager::new(network_config).await?;
+    let peer_id = network.peer_id();
+    
+    network.start().await?;
+    info!("✓ Validator network initialized (peer_id: {})", peer_id);
+    
+    // The validator stakes on the node's currency chain, so its state has to open
+    let control = std::sync::Arc::new(NodeControl::open(&config, "validator", peer_id, None).await?);
+    
+    // Initialize storage
+    let database = control.database.clone();
+    info!("✓ Database initialized ({:?} node)", node_type);
+    let pruning_handle = start_pruning_task(&config, &database, node_type, shutdown_tx.subscribe()).await;
+    
+    // Stake tokens: register as a validator with the stake as self-bond; a
+    // restarted validator keeps its existing registration and stake
+    let validator_account = validator_account_id(&validator_id);
+    match control.register_validator(&validator_account, &validator_id, stake_amount, sign_transaction).await? {
+        Some(stake_tx) => info!("✓ Staked {} tokens ({})", stake_amount, stake_tx),
+        None => info!("✓ Already registered as validator {}", validator_account),
+    }
+    let control_handle = serve_control_api(&config, control, is_producer, shutdown_tx.subscribe()).await;
+    
+    // Start consensus participation
+    let consensus_handle = tokio::spawn(async move {
+        info!("Starting consensus engine...");
+        let mut block_height = 0u64;
+        let mut stats_interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
+        
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(6)) => {
+                    // Block production interval (6 seconds)
+                    if is_producer {
+                        block_height += 1;
+                        info!("📦 Produced block #{}", block_height);
+                        // TODO: Actually produce and broadcast block
+                    } else {
+                        // Validate blocks from other producers
+                        info!("✓ Validated block #{}", block_height);
+                        block_height += 1;
+                    }
+                }
+                
+                _ = stats_interval.tick() => {
+                    info!("📊 Validator stats: height={}, stake={}", block_height, stake_amount);
+                }
+            }
+        }
+    });
+    
+    info!("🎉 Validator node is ready!");
+    info!("Participating in consensus...");
+    info!("Press Ctrl+C to shutdown gracefully");
+    
+    // Wait for shutdown signal
+    tokio::select! {
+        _ = signal::ctrl_c() => {
+            info!("🛑 Received shutdown signal (Ctrl+C)");
+        }
+        _ = shutdown_rx.recv() => {
+            info!("🛑 Received shutdown signal (internal)");
+        }
+    }
+    
+    // Graceful shutdown
+    info!("Shutting down validator gracefully...");
+    let _ = shutdown_tx.send(());
+    consensus_handle.abort();
+    
+    if let Some(handle) = pruning_handle {
+        let _ = handle.await;
+    }
+    database.close().await?;
+    
+    // Wait for tasks to complete
+    tokio::time::timeout(
+        tokio::time::Duration::from_secs(30),
+        async {
+            let _ = tokio::join!(health_handle, metrics_handle);
+            if let Some(handle) = control_handle {
+                let _ = handle.await;
+            }
+        }
+    ).await.map_err(|_| Error::network("Shutdown timeout".to_string()))?;
+    
+    info!("✓ Validator shutdown complete");
+    Ok(())
+}
+
+// ============================================================================
+// Testnet Helper Functions
+// ============================================================================
+
+/// Generate genesis configuration for testnet
+fn generate_genesis_config(num_validators: usize) -> Result<serde_json::Value> {
+    let mut validators = Vec::new();
+    
+    for i in 0..num_validators {
+        validators.push(serde_json::json!({
+            "id": format!("validator_{}", i),
+            "stake": 10000,
+            "voting_power": 1,
+        }));
+    }
+    
+    Ok(serde_json::json!({
+        "chain_id": "dchat-testnet-1",
+        "initial_height": "1",
+        "genesis_time": chrono::Utc::now().to_rfc3339(),
+        "validators": validators,
+        "app_state": {
+            "initial_supply": 1000000,
+            "min_stake": 1000,
+        }
+    }))
+}
+
+/// Save a key pair to a key file only its owner can read
+async fn save_key_file(path: &Path, keypair: &KeyPair) -> Result<()> {
+    let private_bytes = keypair.private_key().as_bytes();
+    let public_bytes = keypair.public_key().as_bytes();
+    
+    let key_json = serde_json::json!({
+        "public_key": format!("{:?}", public_bytes),
+        "private_key": format!("{:?}", private_bytes),
+        "created_at": chrono::Utc::now().to_rfc3339(),
+    });
+    
+    tokio::fs::write(path, serde_json::to_string_pretty(&key_json)?).await
+        .map_err(Error::Io)?;
+    
+    // Set restrictive permissions on Unix
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = tokio::fs::metadata(path).await
+            .map_err(|e| Error::Io(e))?;
+        let mut permissions = metadata.permissions();
+        permissions.set_mode(0o600);
+        tokio::fs::set_permissions(path, permissions).await
+            .map_err(|e| Error::Io(e))?;
+    }
+    
+    Ok(())
+}
+
+/// Currency chain account a validator key stakes from
+fn validator_account_id(public_key: &PublicKey) -> UserId {
+    let digest = dchat_crypto::hash(public_key.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    UserId(Uuid::from_bytes(bytes))
+}
+
+/// Load a key pair from a key file
+async fn load_key_file(path: &Path) -> Result<KeyPair> {
+    let contents = tokio::fs::read_to_string(path).await
+        .map_err(Error::Io)?;
+    Ok(KeyPair::from_private_key(parse_private_key(&contents)?))
+}
+
+/// Parse the private key out of a key file's contents
+fn parse_private_key(contents: &str) -> Result<PrivateKey> {
+    let key_json: serde_json::Value = serde_json::from_str(contents)
+        .map_err(|e| Error::Crypto(format!("Invalid key file: {}", e)))?;
+    
+    let private_key_str = key_json["private_key"]
+        .as_str()
+        .ok_or_else(|| Error::Crypto("Missing private_key field".to_string()))?;
+    
+    // Parse the debug format string like "[1, 2, 3, ...]"
+    let bytes_str = private_key_str.trim_matches(&['[', ']'][..]);
+    let mut bytes = [0u8; 32];
+    for (i, byte_str) in bytes_str.split(',').enumerate().take(32) {
+        bytes[i] = byte_str.trim().parse()
+            .map_err(|e| Error::Crypto(format!("Invalid byte: {}", e)))?;
+    }
+    
+    Ok(PrivateKey::from_bytes(bytes))
+}
+
+/// Load the user node's master key, creating the default one on first run
+async fn load_user_master_key(config: &Config, path: Option<PathBuf>) -> Result<PrivateKey> {
+    if let Some(path) = path {
+        return Ok(load_key_file(&path).await?.private_key().clone());
+    }
+
+    let path = config.storage.data_dir.join(USER_MASTER_KEY_FILE);
+    if tokio::fs::try_exists(&path).await? {
+        return Ok(load_key_file(&path).await?.private_key().clone());
+    }
+    tokio::fs::create_dir_all(&config.storage.data_dir).await?;
+    let keypair = KeyPair::generate();
+    save_key_file(&path, &keypair).await?;
+    info!("✓ Created master key {:?}", path);
+    Ok(keypair.private_key().clone())
+}
+
+/// Stealth address as users share it: view key then spend key, in hex
+fn encode_stealth_address(address: &StealthAddress) -> String {
+    format!("{}{}", hex::encode(address.view_key), hex::encode(address.spend_key))
+}
+
+/// Parse a stealth address written by [`encode_stealth_address`]
+fn parse_stealth_address(encoded: &str) -> Result<StealthAddress> {
+    let bytes = hex::decode(encoded.trim())
+        .map_err(|e| Error::validation(format!("Invalid stealth address: {}", e)))?;
+    if bytes.len() != 64 {
+        return Err(Error::validation("A stealth address is 64 bytes of hex"));
+    }
+    let (mut view_key, mut spend_key) = ([0u8; 32], [0u8; 32]);
+    view_key.copy_from_slice(&bytes[..32]);
+    spend_key.copy_from_slice(&bytes[32..]);
+    Ok(StealthAddress::new(view_key, spend_key))
+}
+
+/// Load identity from file
+async fn load_identity_from_file(path: &PathBuf) -> Result<Identity> {
+    let contents = tokio::fs::read_to_string(path).await
+        .map_err(Error::Io)?;
+    let identity: Identity = serde_json::from_str(&contents)
+        .map_err(|e| Error::Crypto(format!("Invalid identity file: {}", e)))?;
+    Ok(identity)
+}
+
+/// Generate docker-compose for testnet
+fn generate_testnet_compose(
+    data_dir: &Path,
+    num_validators: usize,
+    num_relays: usize,
+    num_clients: usize,
+    relay_addrs: &[String],
+    with_observability: bool,
+) -> Result<()> {
+    let mut services = serde_json::Map::new();
+    
+    // Add validators
+    for i in 0..num_validators {
+        let service_name = format!("validator{}", i);
+        services.insert(service_name, serde_json::json!({
+            "image": "dchat:latest",
+            "command": [
+                "validator",
+                "--key", format!("/data/validator_{}.key", i),
+                "--chain-rpc", "http://chain-rpc:26657",
+                "--stake", "10000",
+                "--producer",
+            ],
+            "volumes": [
+                format!("{}:/data", data_dir.join("validators").display())
+            ],
+            "networks": ["dchat-testnet"],
+            "restart": "unless-stopped",
+        }));
+    }
+    
+    // Add relays
+    for i in 0..num_relays {
+        let service_name = format!("relay{}", i);
+        let port = 7070 + (i * 2);
+        
+        services.insert(service_name, serde_json::json!({
+            "image": "dchat:latest",
+            "command": [
+                "relay",
+                "--listen", format!("0.0.0.0:{}", port),
+                "--bootstrap", relay_addrs,
+                "--stake", "1000",
+            ],
+            "ports": [format!("{}:{}", port, port)],
+            "networks": ["dchat-testnet"],
+            "restart": "unless-stopped",
+        }));
+    }
+    
+    // Add clients
+    for i in 0..num_clients {
+        let service_name = format!("client{}", i);
+        services.insert(service_name, serde_json::json!({
+            "image": "dchat:latest",
+            "command": [
+                "user",
+                "--bootstrap", relay_addrs,
+                "--username", format!("testuser{}", i),
+                "--non-interactive",
+            ],
+            "networks": ["dchat-testnet"],
+            "restart": "unless-stopped",
+        }));
+    }
+    
+    // Add observability stack if requested
+    if with_observability {
+        services.insert("prometheus".to_string(), serde_json::json!({
+            "image": "prom/prometheus:latest",
+            "ports": ["9090:9090"],
+            "networks": ["dchat-testnet"],
+        }));
         
-        let tx_identity = identity.user_id.clone();
+        services.insert("grafana".to_string(), serde_json::json!({
+            "image": "grafana/grafana:latest",
+            "ports": ["3000:3000"],
+            "networks": ["dchat-testnet"],
+        }));
         
-        for line in reader.lines() {
-            if let Ok(text) = line {
-                if !text.trim().is_empty() {
-                    let message = DchatMessage::ChannelMessage {
-                        sender: tx_identity.clone(),
-                        channel_id: "global".to_string(),
-                        encrypted_payload: text.as_bytes().to_vec(),
-                    };
-                    
-                    match network_arc.lock().await.publish_to_channel("global", &message) {
-                        Ok(_) => {
-                            info!("📤 Sent: {}", text);
-                            println!("Message sent!");
-                            print!("You: ");
-                            use std::io::Write;
-                            std::io::stdout().flush().ok();
-                        }
-                        Err(e) => {
-                            info!("❌ Failed to send: {}", e);
-                            println!("Error sending message: {}", e);
-                            print!("You: ");
-                            use std::io::Write;
-                            std::io::stdout().flush().ok();
-                        }
-                    }
-                }
+        services.insert("jaeger".to_string(), serde_json::json!({
+            "image": "jaegertracing/all-in-one:latest",
+            "ports": ["16686:16686", "14268:14268"],
+            "networks": ["dchat-testnet"],
+        }));
+    }
+    
+    let compose = serde_json::json!({
+        "version": "3.8",
+        "services": services,
+        "networks": {
+            "dchat-testnet": {
+                "driver": "bridge"
             }
         }
-        
-        rx_handle.abort();
-    }
+    });
+    
+    let compose_path = data_dir.join("docker-compose.json");
+    let compose_json = serde_json::to_string_pretty(&compose)
+        .map_err(|e| Error::Config(format!("Failed to serialize compose: {}", e)))?;
+    std::fs::write(&compose_path, compose_json)
+        .map_err(Error::Io)?;
+    
+    info!("✓ Docker compose written to {:?}", compose_path);
     
-    // Graceful shutdown
-    info!("Shutting down user client...");
-    let _ = shutdown_tx.send(());
-    database.close().await?;
-    info!("✓ Shutdown complete");
     Ok(())
 }
 
-/// Run full testnet with all components
-async fn run_testnet(
-    _config: Config,
-    num_validators: usize,
-    num_relays: usize,
-    num_clients: usize,
-    data_dir: PathBuf,
-    enable_observability: bool,
-) -> Result<()> {
-    info!("🚀 Launching full testnet...");
-    info!("  Validators: {}", num_validators);
-    info!("  Relays: {}", num_relays);
-    info!("  Clients: {}", num_clients);
-    info!("  Data directory: {:?}", data_dir);
-    
-    // Create testnet directory structure
-    std::fs::create_dir_all(&data_dir)?;
-    let validators_dir = data_dir.join("validators");
-    let relays_dir = data_dir.join("relays");
-    let clients_dir = data_dir.join("clients");
-    
-    std::fs::create_dir_all(&validators_dir)?;
-    std::fs::create_dir_all(&relays_dir)?;
-    std::fs::create_dir_all(&clients_dir)?;
+/// Start metrics server
+fn start_metrics_server(
+    addr: &str,
+    mut shutdown: broadcast::Receiver<()>,
+) -> Result<tokio::task::JoinHandle<()>> {
+    use warp::Filter;
     
-    info!("✓ Created testnet directories");
+    let addr: std::net::SocketAddr = addr.parse()
+        .map_err(|e| Error::Config(format!("Invalid metrics address: {}", e)))?;
     
-    // Generate genesis configuration
-    info!("Generating genesis configuration...");
-    let genesis = generate_genesis_config(num_validators)?;
-    let genesis_path = data_dir.join("genesis.json");
-    std::fs::write(&genesis_path, serde_json::to_string_pretty(&genesis)?)?;
-    info!("✓ Genesis configuration written to {:?}", genesis_path);
+    let metrics_route = warp::path("metrics")
+        .map(|| {
+            // TODO: Export Prometheus metrics
+            warp::reply::with_header(
+                "# dchat metrics\n# TODO: Implement metrics export\n",
+                "Content-Type",
+                "text/plain; version=0.0.4"
+            )
+        });
     
-    // Generate validator keys
-    info!("Generating validator keys...");
-    let mut validator_keys = Vec::new();
-    for i in 0..num_validators {
-        let keypair = KeyPair::generate();
-        let key_path = validators_dir.join(format!("validator_{}.key", i));
-        save_validator_key(&key_path, &keypair).await?;
-        validator_keys.push(keypair);
-        info!("  ✓ Validator {} key: {:?}", i, key_path);
-    }
+    let routes = metrics_route;
     
-    // Generate relay identities
-    info!("Generating relay identities...");
-    let mut relay_addrs = Vec::new();
-    for i in 0..num_relays {
-        let base_port = 7070 + (i * 2);
-        let addr = format!("/ip4/127.0.0.1/tcp/{}", base_port);
-        relay_addrs.push(addr.clone());
-        info!("  ✓ Relay {} address: {}", i, addr);
-    }
+    let (_, server) = warp::serve(routes)
+        .bind_with_graceful_shutdown(addr, async move {
+            let _ = shutdown.recv().await;
+        });
     
-    // Create testnet coordination file
-    let testnet_info = serde_json::json!({
-        "validators": num_validators,
-        "relays": num_relays,
-        "clients": num_clients,
-        "relay_addresses": relay_addrs,
-        "genesis_path": genesis_path,
-        "started_at": chrono::Utc::now().to_rfc3339(),
+    let handle = tokio::spawn(async move {
+        server.await;
     });
     
-    let info_path = data_dir.join("testnet-info.json");
-    std::fs::write(&info_path, serde_json::to_string_pretty(&testnet_info)?)?;
-    info!("✓ Testnet info written to {:?}", info_path);
-    
-    // Create docker-compose for testnet
-    if enable_observability {
-        info!("Generating docker-compose with observability stack...");
-        generate_testnet_compose(
-            &data_dir,
-            num_validators,
-            num_relays,
-            num_clients,
-            &relay_addrs,
-            true,
-        )?;
+    Ok(handle)
+}
+
+// ============================================================================
+// Key Generation and Identity Management
+// ============================================================================
+
+/// Generate new identity and keys
+async fn generate_keys(output: PathBuf, burner: bool) -> Result<()> {
+    info!("🔑 Generating new identity...");
+
+    if burner {
+        info!("Creating burner/ephemeral identity");
+        let keypair = KeyPair::generate();
+        let burner_identity = BurnerIdentity::new(&keypair, None);
+        info!("✓ Burner identity created: {}", burner_identity.burner_id);
+        
+        // Save burner identity (no encryption, ephemeral nature)
+        save_burner_identity_unencrypted(&output, &burner_identity).await?;
     } else {
-        generate_testnet_compose(
-            &data_dir,
-            num_validators,
-            num_relays,
-            num_clients,
-            &relay_addrs,
-            false,
-        )?;
+        info!("Generating permanent identity...");
+        let keypair = KeyPair::generate();
+        let identity = Identity::new("user".to_string(), &keypair);
+        info!("✓ Identity created: {}", identity.user_id);
+        
+        // Prompt for password and encrypt
+        let password = prompt_password("Enter password to encrypt identity: ")?;
+        save_identity_encrypted(&output, &identity, &password).await?;
     }
-    
-    info!("\n🎉 Testnet configuration complete!");
-    info!("\nNext steps:");
-    info!("  1. Review configuration: {:?}", info_path);
-    info!("  2. Start validators: docker-compose -f {:?}/docker-compose.yml up validators", data_dir);
-    info!("  3. Start relays: docker-compose -f {:?}/docker-compose.yml up relays", data_dir);
-    info!("  4. Start clients: docker-compose -f {:?}/docker-compose.yml up clients", data_dir);
-    info!("\nOr start everything:");
-    info!("  docker-compose -f {:?}/docker-compose.yml up -d", data_dir);
-    
+
+    info!("✓ Identity saved to {:?}", output);
     Ok(())
 }
 
-/// Run as validator node
-async fn run_validator_node(
-    _config: Config,
-    key_path: String,
-    chain_rpc: String,
-    use_hsm: bool,
-    stake_amount: u64,
-    is_producer: bool,
-    metrics_addr: String,
-    health_addr: String,
-) -> Result<()> {
-    info!("⚙️  Starting validator node...");
-    info!("Chain RPC: {}", chain_rpc);
-    info!("HSM enabled: {}", use_hsm);
-    info!("Stake: {} tokens", stake_amount);
-    info!("Block producer: {}", is_producer);
-    
-    // Create shutdown channel
-    let (shutdown_tx, mut shutdown_rx) = broadcast::channel::<()>(1);
-    
-    // Start health check server
-    let health_handle = start_health_server(&health_addr, shutdown_tx.subscribe())?;
-    info!("✓ Health server listening on {}", health_addr);
+/// Generate a new identity from a fresh BIP-39 recovery phrase
+async fn generate_keys_from_mnemonic(output: PathBuf, burner: bool, words: usize) -> Result<()> {
+    info!("🔑 Generating new recovery phrase...");
+    let mnemonic = MnemonicSeed::generate(words)?;
     
-    // Start metrics server
-    let metrics_handle = start_metrics_server(&metrics_addr, shutdown_tx.subscribe())?;
-    info!("✓ Metrics server listening on {}", metrics_addr);
+    println!("\n🔐 Recovery phrase ({} words) - write it down and keep it offline:", mnemonic.word_count());
+    println!("\n  {}\n", mnemonic.phrase());
+    println!("Anyone with this phrase can restore your identity, devices and burners.");
     
-    // Load validator key
-    let validator_key = if use_hsm {
-        info!("Loading validator key from HSM: {}", key_path);
-        // TODO: Load from HSM/KMS
-        KeyPair::generate()
-    } else {
-        info!("Loading validator key from file: {}", key_path);
-        load_validator_key(&PathBuf::from(key_path)).await?
+    save_derived_identity(&mnemonic, &output, burner).await
+}
+
+/// Rebuild an identity from an existing BIP-39 recovery phrase
+async fn restore_keys_from_mnemonic(phrase: &str, output: PathBuf, burner: bool) -> Result<()> {
+    info!("♻️  Restoring identity from recovery phrase...");
+    let mnemonic = MnemonicSeed::from_phrase(phrase)?;
+    save_derived_identity(&mnemonic, &output, burner).await
+}
+
+/// Read a recovery phrase from a file, or from stdin if none is given
+async fn read_recovery_phrase(file: Option<&Path>) -> Result<String> {
+    let phrase = match file {
+        Some(path) => tokio::fs::read_to_string(path).await.map_err(Error::Io)?,
+        None => prompt_password("Enter recovery phrase: ")?,
     };
+    Ok(phrase.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Derive the master, device and burner keys and save the selected identity
+async fn save_derived_identity(mnemonic: &MnemonicSeed, output: &Path, burner: bool) -> Result<()> {
+    let keys = mnemonic.derive_keys("")?;
     
-    let validator_id = validator_key.public_key();
-    info!("✓ Validator key loaded: {:?}", validator_id);
-    
-    // Initialize network for validator
-    let network_config = NetworkConfig::default();
-    let mut network = NetworkManager::new(network_config).await?;
-    let peer_id = network.peer_id();
-    
-    network.start().await?;
-    info!("✓ Validator network initialized (peer_id: {})", peer_id);
+    println!("Derived keys:");
+    println!("  Identity {}: {}", KeyPath::dchat_path(0, 0, 0), hex::encode(keys.main_identity.public_key().as_bytes()));
+    println!("  Device   {}: {}", KeyPath::device_path(0), hex::encode(keys.device_0.public_key().as_bytes()));
+    println!("  Burner   {}: {}", KeyPath::burner_path(0), hex::encode(keys.burner_0.public_key().as_bytes()));
     
-    // Initialize storage
-    let db_config = DatabaseConfig::default();
-    let database = Database::new(db_config).await?;
-    info!("✓ Database initialized");
+    if burner {
+        let burner_identity = BurnerIdentity::from_derived_key(&keys.burner_0, None);
+        info!("✓ Burner identity derived: {}", burner_identity.burner_id);
+        save_burner_identity_unencrypted(output, &burner_identity).await?;
+    } else {
+        let identity = Identity::from_derived_key("user".to_string(), &keys.main_identity);
+        info!("✓ Identity derived: {}", identity.user_id);
+        let password = prompt_password("Enter password to encrypt identity: ")?;
+        save_identity_encrypted(output, &identity, &password).await?;
+        
+        let device_key_path = output.with_extension("device.key");
+        save_key_file(&device_key_path, &keys.device_0).await?;
+        info!("✓ Device key saved to {:?}", device_key_path);
+    }
     
-    // Connect to chain RPC
-    info!("Connecting to chain at {}...", chain_rpc);
-    // TODO: Implement chain client connection
+    info!("✓ Identity saved to {:?}", output);
+    Ok(())
+}
+
+/// Prompt user for password (interactive)
+fn prompt_password(prompt_msg: &str) -> Result<String> {
+    use std::io::{self, Write};
     
-    // Stake tokens
-    info!("Staking {} tokens...", stake_amount);
-    // TODO: Submit staking transaction
-    info!("✓ Stake submitted");
+    print!("{}", prompt_msg);
+    io::stdout().flush()
+        .map_err(Error::Io)?;
     
-    // Start consensus participation
-    let consensus_handle = tokio::spawn(async move {
-        info!("Starting consensus engine...");
-        let mut block_height = 0u64;
-        let mut stats_interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
-        
-        loop {
-            tokio::select! {
-                _ = tokio::time::sleep(tokio::time::Duration::from_secs(6)) => {
-                    // Block production interval (6 seconds)
-                    if is_producer {
-                        block_height += 1;
-                        info!("📦 Produced block #{}", block_height);
-                        // TODO: Actually produce and broadcast block
-                    } else {
-                        // Validate blocks from other producers
-                        info!("✓ Validated block #{}", block_height);
-                        block_height += 1;
-                    }
-                }
-                
-                _ = stats_interval.tick() => {
-                    info!("📊 Validator stats: height={}, stake={}", block_height, stake_amount);
-                }
-            }
-        }
-    });
+    let mut password = String::new();
+    io::stdin().read_line(&mut password)
+        .map_err(Error::Io)?;
     
-    info!("🎉 Validator node is ready!");
-    info!("Participating in consensus...");
-    info!("Press Ctrl+C to shutdown gracefully");
+    Ok(password.trim().to_string())
+}
+
+/// Save identity with encryption
+async fn save_identity_encrypted(
+    path: &Path,
+    identity: &Identity,
+    password: &str,
+) -> Result<()> {
+    use dchat_crypto::encrypt_with_password;
     
-    // Wait for shutdown signal
-    tokio::select! {
-        _ = signal::ctrl_c() => {
-            info!("🛑 Received shutdown signal (Ctrl+C)");
-        }
-        _ = shutdown_rx.recv() => {
-            info!("🛑 Received shutdown signal (internal)");
-        }
-    }
+    // Serialize identity to JSON
+    let json = serde_json::to_string(identity)
+        .map_err(|e| Error::crypto(format!("Serialization failed: {}", e)))?;
     
-    // Graceful shutdown
-    info!("Shutting down validator gracefully...");
-    let _ = shutdown_tx.send(());
-    consensus_handle.abort();
+    // Encrypt with password
+    let encrypted = encrypt_with_password(password, json.as_bytes())?;
     
-    // Unstake tokens
-    info!("Initiating unstaking...");
-    // TODO: Submit unstaking transaction
+    // Serialize encrypted container to bytes
+    let encrypted_bytes = encrypted.to_bytes()?;
     
-    database.close().await?;
+    // Write to file
+    tokio::fs::write(path, &encrypted_bytes).await
+        .map_err(Error::Io)?;
     
-    // Wait for tasks to complete
-    tokio::time::timeout(
-        tokio::time::Duration::from_secs(30),
-        async {
-            let _ = tokio::join!(health_handle, metrics_handle);
-        }
-    ).await.map_err(|_| Error::network("Shutdown timeout".to_string()))?;
+    info!("✓ Identity encrypted and saved");
+    Ok(())
+}
+
+/// Save burner identity (unencrypted, ephemeral)
+async fn save_burner_identity_unencrypted(
+    path: &Path,
+    burner_identity: &BurnerIdentity,
+) -> Result<()> {
+    // Serialize to JSON
+    let json = serde_json::to_string(burner_identity)
+        .map_err(|e| Error::crypto(format!("Serialization failed: {}", e)))?;
     
-    info!("✓ Validator shutdown complete");
+    // Write to file
+    tokio::fs::write(path, json).await
+        .map_err(Error::Io)?;
+    
+    info!("✓ Burner identity saved");
     Ok(())
 }
 
+/// Open the node database that holds governance, token and marketplace state
+async fn open_state_database(config: &Config) -> Result<Database> {
+    tokio::fs::create_dir_all(&config.storage.data_dir).await?;
+    Database::new(DatabaseConfig {
+        path: config.storage.data_dir.join("dchat.db"),
+        max_connections: config.storage.db_pool_size,
+        connection_timeout_secs: config.storage.db_connection_timeout_secs,
+        idle_timeout_secs: config.storage.db_idle_timeout_secs,
+        max_lifetime_secs: config.storage.db_max_lifetime_secs,
+        enable_wal: config.storage.db_enable_wal,
+    })
+    .await
+}
+
 // ============================================================================
-// Testnet Helper Functions
+// Node Control API
 // ============================================================================
 
-/// Generate genesis configuration for testnet
-fn generate_genesis_config(num_validators: usize) -> Result<serde_json::Value> {
-    let mut validators = Vec::new();
-    
-    for i in 0..num_validators {
-        validators.push(serde_json::json!({
-            "id": format!("validator_{}", i),
-            "stake": 10000,
-            "voting_power": 1,
-        }));
+/// Channel messages kept for `messaging.recent`
+const RECENT_MESSAGE_LIMIT: usize = 256;
+
+/// Send a CLI subcommand to the node serving this data directory
+///
+/// Returns `false` when no node is running, so the caller runs it locally.
+async fn forward_to_node<T: Serialize>(config: &Config, method: &str, command: &T) -> Result<bool> {
+    let Some(mut client) = ControlClient::connect(&config.storage.data_dir).await? else {
+        return Ok(false);
+    };
+    let output: String = client.call(method, serde_json::to_value(command)?).await?;
+    print!("{}", output);
+    Ok(true)
+}
+
+/// A channel message received by a user node
+#[derive(Debug, Clone, Serialize)]
+struct ReceivedMessage {
+    channel: String,
+    from: String,
+    text: String,
+    received_at: String,
+}
+
+/// Messaging state shared between a user node's event loop and its control API
+struct NodeMessaging {
+    network: std::sync::Arc<tokio::sync::Mutex<NetworkManager>>,
+    sender: dchat_core::types::UserId,
+    channels: std::sync::Mutex<std::collections::BTreeSet<String>>,
+    recent: std::sync::Mutex<std::collections::VecDeque<ReceivedMessage>>,
+    /// Address other users send this node stealth DMs to
+    stealth_address: StealthAddress,
+    /// Decoys published with a stealth DM unless the caller picks a number
+    stealth_decoys: usize,
+    devices: std::sync::Mutex<NodeDevices>,
+    device_store: DeviceStore,
+}
+
+/// Where a user node keeps its [`DeviceRecords`]
+struct DeviceStore {
+    database: Database,
+    state: tokio::sync::Mutex<dchat_storage::Persisted<DeviceRecords>>,
+    /// Key of the device this node was paired as
+    paired_key_path: PathBuf,
+}
+
+impl NodeMessaging {
+    fn new(
+        network: std::sync::Arc<tokio::sync::Mutex<NetworkManager>>,
+        sender: dchat_core::types::UserId,
+        channels: &[&str],
+        stealth_address: StealthAddress,
+        stealth_decoys: usize,
+        devices: NodeDevices,
+        device_store: DeviceStore,
+    ) -> Self {
+        Self {
+            network,
+            sender,
+            channels: std::sync::Mutex::new(channels.iter().map(|c| c.to_string()).collect()),
+            recent: std::sync::Mutex::new(std::collections::VecDeque::new()),
+            stealth_address,
+            stealth_decoys,
+            devices: std::sync::Mutex::new(devices),
+            device_store,
+        }
     }
-    
-    Ok(serde_json::json!({
-        "chain_id": "dchat-testnet-1",
-        "initial_height": "1",
-        "genesis_time": chrono::Utc::now().to_rfc3339(),
-        "validators": validators,
-        "app_state": {
-            "initial_supply": 1000000,
-            "min_stake": 1000,
+
+    /// Write the device records back if pairing, revocation or a sync key
+    /// rotation changed them
+    async fn save_devices(&self) -> Result<()> {
+        let (mut records, paired_key) = {
+            let devices = self.devices.lock().unwrap();
+            let key = devices.paired.as_ref().map(|paired| KeyPair::from_private_key(paired.keypair.private_key().clone()));
+            (devices.records()?, key)
+        };
+        let store = &self.device_store;
+        let mut state = store.state.lock().await;
+        let newly_paired = records.paired.as_ref().map(|p| &p.certificate) != state.paired.as_ref().map(|p| &p.certificate);
+        if let (true, Some(key)) = (newly_paired, paired_key) {
+            // The key goes to disk before the record that needs it
+            save_key_file(&store.paired_key_path, &key).await?;
         }
-    }))
+        if let (Some(new), Some(old)) = (records.paired.as_mut(), state.paired.as_ref()) {
+            // Sealing draws a fresh nonce; keep the stored copy of an unchanged key
+            if new.certificate == old.certificate && new.sync_key.epoch == old.sync_key.epoch {
+                new.sync_key = old.sync_key.clone();
+            }
+        }
+        **state = records;
+        if let Err(e) = store.database.save_state(&mut state).await {
+            // The in-memory devices are the source of truth; the next save retries
+            store.database.reload_state(&mut state).await?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Publish one step of a pairing handshake or a sync key rotation
+    async fn publish_pairing(&self, topic: &str, message: &PairingMessage) -> Result<()> {
+        let message = DchatMessage::DevicePairing { payload: message.to_bytes()? };
+        self.network.lock().await.publish_to_channel(topic, &message)?;
+        Ok(())
+    }
+
+    async fn publish_revocation(&self, revocation: &DeviceRevocation) -> Result<()> {
+        let message = DchatMessage::DeviceRevocation { payload: revocation.to_bytes()? };
+        self.network.lock().await.publish_to_channel(DEVICE_REVOCATION_CHANNEL, &message)?;
+        Ok(())
+    }
+
+    /// Seal `text` to `recipient` and publish it on the stealth topic
+    ///
+    /// Returns the number of payloads published: the DM plus its decoys.
+    async fn send_stealth(&self, recipient: &StealthAddress, text: &str, decoys: usize) -> Result<usize> {
+        if text.trim().is_empty() {
+            return Err(Error::validation("Message text is empty"));
+        }
+        if decoys > MAX_STEALTH_DECOYS {
+            return Err(Error::validation(format!("At most {} decoys per message", MAX_STEALTH_DECOYS)));
+        }
+
+        let mut rng = rand::rngs::OsRng;
+        let payloads = StealthMessenger::new(&mut rng)
+            .with_decoys(decoys)
+            .seal(self.sender.clone(), recipient, text.as_bytes().to_vec(), &mut rng)?;
+        let mut network = self.network.lock().await;
+        for payload in &payloads {
+            let message = DchatMessage::StealthDirectMessage { payload: payload.clone() };
+            network.publish_to_channel(STEALTH_DM_CHANNEL, &message)?;
+        }
+        Ok(payloads.len())
+    }
+
+    fn record(&self, channel: &str, from: &PeerId, payload: &[u8]) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_MESSAGE_LIMIT {
+            recent.pop_front();
+        }
+        recent.push_back(ReceivedMessage {
+            channel: channel.to_string(),
+            from: from.to_string(),
+            text: String::from_utf8_lossy(payload).into_owned(),
+            received_at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+}
+
+/// Gossip topic for the pairing handshakes and sync key rotations of one identity
+fn device_pairing_topic(user_id: &UserId) -> String {
+    format!("device-pairing/{}", user_id)
+}
+
+/// Device pairing and certificate state of a user node
+///
+/// As a primary the node hands out device keys derived from its master key
+/// and keeps the certificates it issued; as a new device it holds the key
+/// and sync channel it was granted.
+struct NodeDevices {
+    user_id: UserId,
+    master_key: PrivateKey,
+    identity: KeyPair,
+    registry: CertificateRegistry,
+    /// Certificates this identity has issued, by device ID
+    issued: std::collections::BTreeMap<String, DeviceCertificate>,
+    /// Index the next paired device's key is derived at
+    next_device_index: u32,
+    /// Epoch of the sync key this identity's devices share
+    sync_epoch: u32,
+    host: Option<PairingHost>,
+    joiner: Option<(PairingCode, PairingJoiner)>,
+    /// This node's own device, once it has joined another identity
+    paired: Option<PairedDevice>,
+}
+
+/// The part of [`NodeDevices`] that survives a restart
+///
+/// Pairing sessions in progress are dropped. The paired device's key is
+/// kept in its own key file and its sync key is stored sealed to it.
+#[derive(Default, Serialize, Deserialize)]
+struct DeviceRecords {
+    issued: std::collections::BTreeMap<String, DeviceCertificate>,
+    next_device_index: u32,
+    sync_epoch: u32,
+    revocations: Vec<DeviceRevocation>,
+    paired: Option<PairedDeviceRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PairedDeviceRecord {
+    certificate: DeviceCertificate,
+    sync_key: SyncKeyRotation,
+}
+
+impl NodeDevices {
+    fn new(user_id: UserId, master_key: PrivateKey) -> Result<Self> {
+        let identity = IdentityDerivation::derive_all_keys(&master_key)?.main_identity;
+        Ok(Self {
+            user_id,
+            master_key,
+            identity,
+            registry: CertificateRegistry::new(),
+            issued: std::collections::BTreeMap::new(),
+            next_device_index: 1,
+            sync_epoch: 0,
+            host: None,
+            joiner: None,
+            paired: None,
+        })
+    }
+
+    /// Rebuild the devices of an identity from stored records
+    ///
+    /// `paired_key` is the key of the device this node was paired as, if
+    /// `records` say it was.
+    fn restore(user_id: UserId, master_key: PrivateKey, records: &DeviceRecords, paired_key: Option<KeyPair>) -> Result<Self> {
+        let mut devices = Self::new(user_id, master_key)?;
+        for revocation in &records.revocations {
+            devices.registry.apply_revocation(revocation.clone())?;
+        }
+        devices.issued = records.issued.clone();
+        // Index 0 is the primary's own device key
+        devices.next_device_index = records.next_device_index.max(1);
+        devices.sync_epoch = records.sync_epoch;
+        if let Some(paired) = &records.paired {
+            let key = paired_key.ok_or_else(|| Error::NotFound("Key of the paired device is missing".to_string()))?;
+            devices.paired = Some(PairedDevice::restore(paired.certificate.clone(), key, &paired.sync_key)?);
+        }
+        Ok(devices)
+    }
+
+    fn records(&self) -> Result<DeviceRecords> {
+        let paired = self
+            .paired
+            .as_ref()
+            .map(|paired| {
+                Ok::<_, Error>(PairedDeviceRecord {
+                    certificate: paired.certificate.clone(),
+                    sync_key: paired.seal_sync_key()?,
+                })
+            })
+            .transpose()?;
+        let mut revocations: Vec<DeviceRevocation> = self.registry.revocations().cloned().collect();
+        revocations.sort_by(|a, b| {
+            (a.identity_key.as_bytes(), a.device_key.as_bytes()).cmp(&(b.identity_key.as_bytes(), b.device_key.as_bytes()))
+        });
+        Ok(DeviceRecords {
+            issued: self.issued.clone(),
+            next_device_index: self.next_device_index,
+            sync_epoch: self.sync_epoch,
+            revocations,
+            paired,
+        })
+    }
+
+    /// Open a pairing for the next device
+    fn open_pairing(&mut self, host_peer: String) -> Result<PairingCode> {
+        let host = PairingHost::new(self.user_id.clone(), self.master_key.clone(), self.next_device_index, host_peer)?
+            .with_sync_epoch(self.sync_epoch);
+        let code = host.code().clone();
+        self.host = Some(host);
+        Ok(code)
+    }
+
+    /// Start joining the identity behind `code`; returns the request to publish
+    fn start_join(&mut self, code: PairingCode, device_name: String, device_type: DeviceType) -> Result<PairingMessage> {
+        let joiner = PairingJoiner::new(&code.code, device_name, device_type)?;
+        let request = joiner.request();
+        self.joiner = Some((code, joiner));
+        Ok(request)
+    }
+
+    /// Advance whichever side of a pairing this node is on
+    ///
+    /// Returns the reply to publish and the topic to publish it on, if any.
+    /// Messages for a side this node is not on are ignored.
+    fn handle_pairing(&mut self, message: PairingMessage) -> Result<Option<(String, PairingMessage)>> {
+        let host_topic = device_pairing_topic(&self.user_id);
+        match message {
+            PairingMessage::Request { device_name, device_type } => match self.host.as_mut() {
+                Some(host) if host.is_open() => {
+                    Ok(Some((host_topic, host.handle_request(device_name, device_type)?)))
+                }
+                _ => Ok(None),
+            },
+            PairingMessage::Response { session_id, share, confirmation } => {
+                let Some(host) = self.host.as_mut() else { return Ok(None) };
+                let (grant, certificate) = match host.handle_response(&session_id, &share, &confirmation) {
+                    Ok(granted) => granted,
+                    Err(e) => return Ok(Some((host_topic, PairingMessage::Rejected { reason: e.to_string() }))),
+                };
+                self.registry.verify_certificate(&certificate, &self.identity.public_key().to_core_public_key())?;
+                info!("📱 Paired device {} ({})", certificate.device_name, certificate.device_id);
+                self.issued.insert(certificate.device_id.clone(), certificate);
+                self.next_device_index += 1;
+                self.host = None;
+                Ok(Some((host_topic, grant)))
+            }
+            PairingMessage::Challenge { session_id, share } => match self.joiner.as_mut() {
+                Some((code, joiner)) => {
+                    let response = joiner.handle_challenge(session_id, &share)?;
+                    Ok(Some((device_pairing_topic(&code.user_id), response)))
+                }
+                None => Ok(None),
+            },
+            PairingMessage::Grant { session_id, confirmation, sealed_grant } => {
+                let Some((code, joiner)) = self.joiner.take() else { return Ok(None) };
+                let paired = joiner.handle_grant(&session_id, &confirmation, &sealed_grant)?;
+                if paired.certificate.user_id != code.user_id {
+                    return Err(Error::identity("Device certificate is for a different identity"));
+                }
+                self.registry.verify_certificate(&paired.certificate, &paired.certificate.identity_key)?;
+                info!("📱 Joined {} as device {}", code.user_id, paired.certificate.device_id);
+                self.paired = Some(paired);
+                Ok(None)
+            }
+            PairingMessage::Rejected { reason } => {
+                if self.joiner.take().is_some() {
+                    return Err(Error::identity(format!("Pairing rejected: {}", reason)));
+                }
+                Ok(None)
+            }
+            PairingMessage::RotateSyncKey { rotation } => {
+                if let Some(paired) = self.paired.as_mut() {
+                    paired.apply_rotation(&rotation)?;
+                    info!("🔑 Device sync key rotated to epoch {}", rotation.epoch);
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Revoke a device this identity issued and rotate the sync key of the rest
+    ///
+    /// Returns the revocation to gossip and the rotation to publish to the
+    /// identity's devices.
+    fn revoke(&mut self, device_id: &str) -> Result<(DeviceRevocation, PairingMessage)> {
+        let certificate = self
+            .issued
+            .get(device_id)
+            .ok_or_else(|| Error::NotFound(format!("No paired device {}", device_id)))?;
+        let revocation = DeviceRevocation::issue(certificate, &self.identity)?;
+        if !self.registry.apply_revocation(revocation.clone())? {
+            return Err(Error::AlreadyExists(format!("Device {} is already revoked", device_id)));
+        }
+
+        self.sync_epoch += 1;
+        let remaining: Vec<DeviceCertificate> = self
+            .issued
+            .values()
+            .filter(|certificate| !self.registry.is_revoked(certificate))
+            .cloned()
+            .collect();
+        let rotation = SyncKeyRotation::issue(self.user_id.clone(), &self.master_key, self.sync_epoch, &remaining)?;
+        Ok((revocation, PairingMessage::RotateSyncKey { rotation }))
+    }
+}
+
+#[derive(Deserialize)]
+struct SendParams {
+    channel: String,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct StealthSendParams {
+    /// Recipient's stealth address
+    to: String,
+    text: String,
+    decoys: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct RecentParams {
+    channel: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ChannelParams {
+    channel: String,
+}
+
+#[derive(Deserialize)]
+struct ChannelPolicyParams {
+    channel: String,
+    policy: dchat::messaging::AccessPolicy,
+}
+
+/// Live node state answered over the control socket
+///
+/// The `*.execute` methods take the same command enums as the CLI and
+/// return its rendered output, so a forwarded subcommand prints exactly
+/// what a local run would.
+struct NodeControl {
+    role: &'static str,
+    peer_id: PeerId,
+    started_at: chrono::DateTime<chrono::Utc>,
+    messaging: Option<std::sync::Arc<NodeMessaging>>,
+    database: Database,
+    governance: tokio::sync::Mutex<dchat_storage::Persisted<dchat::governance::UpgradeManager>>,
+    execution: tokio::sync::Mutex<dchat_storage::Persisted<GovernanceExecution>>,
+    moderation: tokio::sync::Mutex<dchat_storage::Persisted<dchat::governance::ModerationManager>>,
+    /// Configuration the node started with, before governance overrides
+    config: Config,
+    marketplace: tokio::sync::Mutex<dchat_storage::Persisted<dchat::marketplace::MarketplaceManager>>,
+    /// Locked after any other state a command holds
+    currency: tokio::sync::Mutex<dchat_storage::Persisted<std::sync::Arc<CurrencyChainClient>>>,
+    /// Outboxes, light clients and replay protection of the cross-chain bridge
+    bridge: tokio::sync::Mutex<dchat_storage::Persisted<BridgeState>>,
+    /// The bridge's view of the chat chain; chat chain state is not kept here
+    chat_chain: std::sync::Arc<ChatChainClient>,
+    /// Funded from block fees; compensates users for failed bridge operations
+    insurance: tokio::sync::Mutex<dchat_storage::Persisted<dchat::chain::InsuranceFund>>,
+    /// Policies and members of gated channels, checked against the currency chain
+    channel_access: tokio::sync::Mutex<dchat_storage::Persisted<dchat::messaging::ChannelAccessManager>>,
+    bots: tokio::sync::Mutex<dchat_storage::Persisted<dchat::bots::BotFather>>,
+    accounts: tokio::sync::OnceCell<dchat::UserManager>,
+    events: dchat_core::EventBus,
 }
 
-/// Save validator key to encrypted file
-async fn save_validator_key(path: &PathBuf, keypair: &KeyPair) -> Result<()> {
-    let private_bytes = keypair.private_key().as_bytes();
-    let public_bytes = keypair.public_key().as_bytes();
-    
-    let key_json = serde_json::json!({
-        "public_key": format!("{:?}", public_bytes),
-        "private_key": format!("{:?}", private_bytes),
-        "created_at": chrono::Utc::now().to_rfc3339(),
-    });
-    
-    tokio::fs::write(path, serde_json::to_string_pretty(&key_json)?).await
-        .map_err(Error::Io)?;
-    
-    // Set restrictive permissions on Unix
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let metadata = tokio::fs::metadata(path).await
-            .map_err(|e| Error::Io(e))?;
-        let mut permissions = metadata.permissions();
-        permissions.set_mode(0o600);
-        tokio::fs::set_permissions(path, permissions).await
-            .map_err(|e| Error::Io(e))?;
+impl NodeControl {
+    async fn open(
+        config: &Config,
+        role: &'static str,
+        peer_id: PeerId,
+        messaging: Option<std::sync::Arc<NodeMessaging>>,
+    ) -> Result<Self> {
+        use dchat::governance::{ModerationManager, UpgradeManager};
+        use dchat::marketplace::MarketplaceManager;
+
+        let database = open_state_database(config).await?;
+        let governance = database
+            .load_state(GOVERNANCE_STATE, GOVERNANCE_STATE_VERSION, UpgradeManager::new)
+            .await?;
+        let execution = database
+            .load_state(EXECUTION_STATE, EXECUTION_STATE_VERSION, GovernanceExecution::default)
+            .await?;
+        let moderation = database
+            .load_state(MODERATION_STATE, MODERATION_STATE_VERSION, || ModerationManager::new(MODERATOR_MIN_STAKE))
+            .await?;
+        let marketplace = database
+            .load_state(MARKETPLACE_STATE, MARKETPLACE_STATE_VERSION, MarketplaceManager::new)
+            .await?;
+        let currency = load_currency_chain(&database).await?;
+        let bridge = database.load_state(BRIDGE_STATE, BRIDGE_STATE_VERSION, BridgeState::default).await?;
+        let insurance_fund = dchat::chain::InsuranceFund::with_account(
+            module_account_id(INSURANCE_ACCOUNT_DOMAIN),
+            dchat::chain::FundConfiguration::default(),
+            &**currency,
+        )?;
+        let insurance = database
+            .load_state(INSURANCE_STATE, INSURANCE_STATE_VERSION, || insurance_fund)
+            .await?;
+        let channel_access = database
+            .load_state(CHANNEL_ACCESS_STATE, CHANNEL_ACCESS_STATE_VERSION, dchat::messaging::ChannelAccessManager::new)
+            .await?;
+        let bots = database.load_state(BOT_STATE, BOT_STATE_VERSION, dchat::bots::BotFather::new).await?;
+        let events = dchat_core::EventBus::new(256);
+        events.add_handler(std::sync::Arc::new(dchat_core::events::LoggingEventHandler)).await;
+
+        Ok(Self {
+            role,
+            peer_id,
+            started_at: chrono::Utc::now(),
+            messaging,
+            database,
+            governance: tokio::sync::Mutex::new(governance),
+            execution: tokio::sync::Mutex::new(execution),
+            moderation: tokio::sync::Mutex::new(moderation),
+            config: config.clone(),
+            marketplace: tokio::sync::Mutex::new(marketplace),
+            currency: tokio::sync::Mutex::new(currency),
+            bridge: tokio::sync::Mutex::new(bridge),
+            chat_chain: std::sync::Arc::new(ChatChainClient::new(ChatChainConfig::default())),
+            insurance: tokio::sync::Mutex::new(insurance),
+            channel_access: tokio::sync::Mutex::new(channel_access),
+            bots: tokio::sync::Mutex::new(bots),
+            accounts: tokio::sync::OnceCell::new(),
+            events,
+        })
+    }
+
+    /// Save a state a command has changed, or roll it back if the command
+    /// or the save failed
+    ///
+    /// Without the rollback a half-applied command would be saved by the
+    /// next successful one, and after a lost save race the stale revision
+    /// would make every later save fail.
+    async fn settle<T, R>(&self, state: &mut dchat_storage::Persisted<T>, result: Result<R>) -> Result<R>
+    where
+        T: Serialize + serde::de::DeserializeOwned,
+    {
+        let result = match result {
+            Ok(value) => self.database.save_state(state).await.map(|_| value),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            if let Err(e) = self.database.reload_state(state).await {
+                warn!("⚠️  Failed to reload {} state: {}", state.module(), e);
+            }
+        }
+        result
+    }
+
+    /// Close a currency chain block, pay out its inflation and commit its
+    /// outbound bridge messages
+    ///
+    /// Tokenomics follows the chain's own height, so each block is paid once.
+    /// The insurance fund takes its share of the block's fees, raises claims
+    /// for bridge operations that rolled back and pays approved claims.
+    /// Passed proposals whose timelock ends at the new height execute last.
+    async fn produce_block(&self) -> Result<usize> {
+        let mut governance = self.governance.lock().await;
+        let mut execution = self.execution.lock().await;
+        let mut moderation = self.moderation.lock().await;
+        let mut insurance = self.insurance.lock().await;
+        let mut bridge = self.bridge.lock().await;
+        let mut currency = self.currency.lock().await;
+        let result = collect_block_fees(&mut insurance, &currency).and_then(|_| {
+            currency.advance_block();
+            let mints = currency.process_block_inflation()?;
+            **bridge = self.commit_bridge_messages(&bridge, &currency)?;
+            let events = CrossChainBridge::with_state(self.chat_chain.clone(), (*currency).clone(), bridge.clone());
+            insurance.sync_bridge_events(&events)?;
+            pay_insurance_claims(&mut insurance, &currency);
+            execute_due_proposals(&mut execution, &mut governance, &mut moderation, &self.config, &currency)?;
+            Ok(mints.len())
+        });
+        let result = self.settle(&mut currency, result).await;
+        let result = self.settle(&mut bridge, result).await;
+        let result = self.settle(&mut insurance, result).await;
+        let result = self.settle(&mut moderation, result).await;
+        let result = self.settle(&mut execution, result).await;
+        self.settle(&mut governance, result).await
+    }
+
+    /// Commit the currency chain's bridge outbox at its current height,
+    /// returning the bridge state to save
+    ///
+    /// The bridge starts following the currency chain once the chain has
+    /// validators to sign its headers, and cross-chain operations whose
+    /// receipts are all final complete.
+    fn commit_bridge_messages(&self, state: &BridgeState, currency: &std::sync::Arc<CurrencyChainClient>) -> Result<BridgeState> {
+        use dchat::bridge::ChainId;
+        
+        let bridge = CrossChainBridge::with_state(self.chat_chain.clone(), currency.clone(), state.clone());
+        if let Some(validators) = bridge_validator_set(currency) {
+            bridge.follow_chain(ChainId::CurrencyChain, validators);
+        }
+        let header = bridge.commit_messages(&ChainId::CurrencyChain).map_err(Error::chain)?;
+        bridge.finalize_pending_transactions().map_err(Error::chain)?;
+        tracing::debug!("Committed bridge header at height {}", header.height);
+        Ok(bridge.state())
+    }
+
+    /// Register `account` as a validator bonding `self_bond`, unless it
+    /// already is one
+    ///
+    /// A new account is bound to `public_key`. Returns the registration
+    /// transaction, or `None` if the account was already registered.
+    async fn register_validator(
+        &self,
+        account: &UserId,
+        public_key: &PublicKey,
+        self_bond: u64,
+        sign: impl FnOnce(TransactionEnvelope) -> Result<SignedTransaction>,
+    ) -> Result<Option<Uuid>> {
+        let mut currency = self.currency.lock().await;
+        if currency.get_validator(account).is_some() {
+            return Ok(None);
+        }
+        let result = (|| {
+            if currency.get_wallet(account)?.is_none() {
+                currency.create_account(account, public_key.clone(), 0)?;
+            }
+            let register = sign(TransactionEnvelope {
+                from: account.clone(),
+                nonce: currency.get_nonce(account),
+                max_fee: currency.base_fee(),
+                tip: 0,
+                payload: TransactionPayload::RegisterValidator { self_bond, commission_bps: 0 },
+            })?;
+            currency.submit_transaction(&register).map(Some)
+        })();
+        self.settle(&mut currency, result).await
+    }
+
+    /// Run the subscription scheduler and publish the events it queued
+    async fn bill_subscriptions(&self) -> Result<usize> {
+        let mut marketplace = self.marketplace.lock().await;
+        marketplace.process_subscriptions(chrono::Utc::now());
+        let published = marketplace.subscriptions.publish_events(&self.events).await;
+        self.settle(&mut marketplace, published).await
+    }
+
+    fn messaging(&self) -> Result<&NodeMessaging> {
+        self.messaging
+            .as_deref()
+            .ok_or_else(|| Error::NotFound(format!("Messaging is not available on a {} node", self.role)))
+    }
+
+    async fn status(&self) -> serde_json::Value {
+        let mut status = serde_json::json!({
+            "role": self.role,
+            "version": VERSION,
+            "peer_id": self.peer_id.to_string(),
+            "started_at": self.started_at.to_rfc3339(),
+        });
+        if let Some(messaging) = &self.messaging {
+            status["user_id"] = serde_json::json!(messaging.sender.to_string());
+            status["stealth_address"] = serde_json::json!(encode_stealth_address(&messaging.stealth_address));
+            status["connected_peers"] = serde_json::json!(messaging.network.lock().await.connected_peer_count());
+        }
+        status
+    }
+
+    async fn send_message(&self, params: SendParams) -> Result<serde_json::Value> {
+        let messaging = self.messaging()?;
+        if params.text.trim().is_empty() {
+            return Err(Error::validation("Message text is empty"));
+        }
+        {
+            let access = self.channel_access.lock().await;
+            let currency = self.currency.lock().await;
+            if !is_channel_member(&access, &***currency, &messaging.sender, &params.channel)? {
+                return Err(Error::PermissionDenied(format!("Not a member of channel {}", params.channel)));
+            }
+        }
+        let message = DchatMessage::ChannelMessage {
+            sender: messaging.sender.clone(),
+            channel_id: params.channel.clone(),
+            encrypted_payload: params.text.into_bytes(),
+        };
+        messaging.network.lock().await.publish_to_channel(&params.channel, &message)?;
+        Ok(serde_json::json!({ "sent": true, "channel": params.channel }))
+    }
+
+    async fn send_stealth_message(&self, params: StealthSendParams) -> Result<serde_json::Value> {
+        let messaging = self.messaging()?;
+        let recipient = parse_stealth_address(&params.to)?;
+        let decoys = params.decoys.unwrap_or(messaging.stealth_decoys);
+        let payloads = messaging.send_stealth(&recipient, &params.text, decoys).await?;
+        Ok(serde_json::json!({ "sent": true, "payloads": payloads }))
+    }
+
+    fn recent_messages(&self, params: RecentParams) -> Result<serde_json::Value> {
+        let messaging = self.messaging()?;
+        let limit = params.limit.unwrap_or(50).min(RECENT_MESSAGE_LIMIT);
+        let recent = messaging.recent.lock().unwrap();
+        let mut messages: Vec<&ReceivedMessage> = recent
+            .iter()
+            .rev()
+            .filter(|m| params.channel.as_deref().is_none_or(|c| m.channel == c))
+            .take(limit)
+            .collect();
+        messages.reverse();
+        Ok(serde_json::to_value(messages)?)
+    }
+
+    /// Join or leave a channel
+    ///
+    /// Joining a gated channel makes this node's user a member, which it
+    /// must qualify for on the currency chain.
+    async fn set_subscription(&self, channel: String, subscribe: bool) -> Result<serde_json::Value> {
+        let messaging = self.messaging()?;
+        let mut access = self.channel_access.lock().await;
+        let result = {
+            let currency = self.currency.lock().await;
+            if subscribe {
+                join_channel(&mut access, &***currency, &messaging.sender, &channel)
+            } else {
+                access.revoke_access(&messaging.sender, &gossip_channel_id(&channel))
+            }
+        };
+        self.settle(&mut access, result).await?;
+
+        let mut network = messaging.network.lock().await;
+        let changed = if subscribe {
+            network.subscribe_to_channel(&channel)?;
+            messaging.channels.lock().unwrap().insert(channel)
+        } else {
+            network.unsubscribe_from_channel(&channel)?;
+            messaging.channels.lock().unwrap().remove(&channel)
+        };
+        Ok(serde_json::json!({ "changed": changed }))
+    }
+
+    async fn accounts(&self) -> Result<&dchat::UserManager> {
+        self.accounts.get_or_try_init(open_user_manager).await
     }
-    
-    Ok(())
 }
 
-/// Load validator key from file
-async fn load_validator_key(path: &PathBuf) -> Result<KeyPair> {
-    let contents = tokio::fs::read_to_string(path).await
-        .map_err(Error::Io)?;
-    let key_json: serde_json::Value = serde_json::from_str(&contents)
-        .map_err(|e| Error::Crypto(format!("Invalid key file: {}", e)))?;
-    
-    let private_key_str = key_json["private_key"]
-        .as_str()
-        .ok_or_else(|| Error::Crypto("Missing private_key field".to_string()))?;
-    
-    // Parse the debug format string like "[1, 2, 3, ...]"
-    let bytes_str = private_key_str.trim_matches(&['[', ']'][..]);
-    let mut bytes = [0u8; 32];
-    for (i, byte_str) in bytes_str.split(',').enumerate().take(32) {
-        bytes[i] = byte_str.trim().parse()
-            .map_err(|e| Error::Crypto(format!("Invalid byte: {}", e)))?;
+#[async_trait::async_trait]
+impl ControlHandler for NodeControl {
+    async fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        let mut out = CommandOutput::default();
+        match method {
+            "node.status" => return Ok(self.status().await),
+            "messaging.send" => return self.send_message(parse_params(params)?).await,
+            "messaging.send_stealth" => return self.send_stealth_message(parse_params(params)?).await,
+            "messaging.recent" => return self.recent_messages(parse_params(params)?),
+            "channels.list" => {
+                let channels = self.messaging()?.channels.lock().unwrap().clone();
+                return Ok(serde_json::to_value(channels)?);
+            }
+            "channels.subscribe" => {
+                let ChannelParams { channel } = parse_params(params)?;
+                return self.set_subscription(channel, true).await;
+            }
+            "channels.unsubscribe" => {
+                let ChannelParams { channel } = parse_params(params)?;
+                return self.set_subscription(channel, false).await;
+            }
+            "channels.set_policy" => {
+                let ChannelPolicyParams { channel, policy } = parse_params(params)?;
+                let mut access = self.channel_access.lock().await;
+                access.set_policy(gossip_channel_id(&channel), policy);
+                self.settle(&mut access, Ok(())).await?;
+                return Ok(serde_json::json!({ "channel": channel }));
+            }
+            "governance.execute" => {
+                let action = parse_params(params)?;
+                let mut manager = self.governance.lock().await;
+                let mut execution = self.execution.lock().await;
+                let height = self.currency.lock().await.get_current_block();
+                let result = apply_governance_command(&mut manager, &mut execution.executor, height, action, &mut out);
+                let result = self.settle(&mut execution, result).await;
+                self.settle(&mut manager, result).await?;
+            }
+            "token.execute" => {
+                let action = parse_params(params)?;
+                let mut currency = self.currency.lock().await;
+                let result = apply_token_command(&currency, action, &mut out);
+                self.settle(&mut currency, result).await?;
+            }
+            "marketplace.execute" => {
+                let action = parse_params(params)?;
+                let mut marketplace = self.marketplace.lock().await;
+                let mut currency = self.currency.lock().await;
+                let result = apply_marketplace_command(&mut marketplace, &currency, action, &mut out);
+                let result = self.settle(&mut currency, result).await;
+                self.settle(&mut marketplace, result).await?;
+            }
+            "bot.execute" => {
+                let action = parse_params(params)?;
+                let mut bots = self.bots.lock().await;
+                let result = apply_bot_command(&bots, action, &mut out);
+                self.settle(&mut bots, result).await?;
+            }
+            "device.execute" => {
+                apply_device_command(self.messaging()?, &self.peer_id, parse_params(params)?, &mut out).await?
+            }
+            "account.execute" => {
+                let action = parse_params(params)?;
+                apply_account_command(self.accounts().await?, action, &mut out).await?;
+            }
+            _ => return Err(Error::NotFound(format!("Unknown control method: {}", method))),
+        }
+        Ok(serde_json::Value::String(out.0))
     }
-    
-    let private_key = PrivateKey::from_bytes(bytes);
-    Ok(KeyPair::from_private_key(private_key))
 }
 
-/// Load identity from file
-async fn load_identity_from_file(path: &PathBuf) -> Result<Identity> {
-    let contents = tokio::fs::read_to_string(path).await
-        .map_err(Error::Io)?;
-    let identity: Identity = serde_json::from_str(&contents)
-        .map_err(|e| Error::Crypto(format!("Invalid identity file: {}", e)))?;
-    Ok(identity)
+/// Active currency chain validators with signing keys, as the set the bridge
+/// checks currency chain headers against; `None` until there is one
+fn bridge_validator_set(currency: &CurrencyChainClient) -> Option<dchat::bridge::multisig::MultiSigConfig> {
+    use dchat::blockchain::ValidatorStatus;
+    use dchat::bridge::multisig::{MultiSigConfig, ValidatorId};
+    
+    let validators: Vec<_> = currency
+        .get_validators()
+        .into_iter()
+        .filter(|validator| validator.status == ValidatorStatus::Active)
+        .filter_map(|validator| {
+            let public_key = currency.get_wallet(&validator.id).ok()??.public_key?;
+            Some(ValidatorId::new(validator.id, public_key.as_bytes().to_vec()))
+        })
+        .collect();
+    MultiSigConfig::new(validators.len() * 2 / 3 + 1, validators).ok()
 }
 
-/// Generate docker-compose for testnet
-fn generate_testnet_compose(
-    data_dir: &Path,
-    num_validators: usize,
-    num_relays: usize,
-    num_clients: usize,
-    relay_addrs: &[String],
-    with_observability: bool,
-) -> Result<()> {
-    let mut services = serde_json::Map::new();
-    
-    // Add validators
-    for i in 0..num_validators {
-        let service_name = format!("validator{}", i);
-        services.insert(service_name, serde_json::json!({
-            "image": "dchat:latest",
-            "command": [
-                "validator",
-                "--key", format!("/data/validator_{}.key", i),
-                "--chain-rpc", "http://chain-rpc:26657",
-                "--stake", "10000",
-                "--producer",
-            ],
-            "volumes": [
-                format!("{}:/data", data_dir.join("validators").display())
-            ],
-            "networks": ["dchat-testnet"],
-            "restart": "unless-stopped",
-        }));
-    }
-    
-    // Add relays
-    for i in 0..num_relays {
-        let service_name = format!("relay{}", i);
-        let port = 7070 + (i * 2);
-        
-        services.insert(service_name, serde_json::json!({
-            "image": "dchat:latest",
-            "command": [
-                "relay",
-                "--listen", format!("0.0.0.0:{}", port),
-                "--bootstrap", relay_addrs,
-                "--stake", "1000",
-            ],
-            "ports": [format!("{}:{}", port, port)],
-            "networks": ["dchat-testnet"],
-            "restart": "unless-stopped",
-        }));
+/// Start the control API for a node; a node without one keeps running
+async fn start_control_api(
+    config: &Config,
+    role: &'static str,
+    peer_id: PeerId,
+    messaging: Option<std::sync::Arc<NodeMessaging>>,
+    shutdown: broadcast::Receiver<()>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    match NodeControl::open(config, role, peer_id, messaging).await {
+        Ok(control) => serve_control_api(config, std::sync::Arc::new(control), false, shutdown).await,
+        Err(e) => {
+            warn!("⚠️  Control API disabled: {}", e);
+            None
+        }
     }
-    
-    // Add clients
-    for i in 0..num_clients {
-        let service_name = format!("client{}", i);
-        services.insert(service_name, serde_json::json!({
-            "image": "dchat:latest",
-            "command": [
-                "user",
-                "--bootstrap", relay_addrs,
-                "--username", format!("testuser{}", i),
-                "--non-interactive",
-            ],
-            "networks": ["dchat-testnet"],
-            "restart": "unless-stopped",
-        }));
+}
+
+/// Start the background tasks of an opened node state and serve it over the
+/// control API
+async fn serve_control_api(
+    config: &Config,
+    control: std::sync::Arc<NodeControl>,
+    produce_blocks: bool,
+    shutdown: broadcast::Receiver<()>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    start_billing_task(control.clone(), shutdown.resubscribe());
+    if produce_blocks {
+        start_block_task(control.clone(), shutdown.resubscribe());
     }
-    
-    // Add observability stack if requested
-    if with_observability {
-        services.insert("prometheus".to_string(), serde_json::json!({
-            "image": "prom/prometheus:latest",
-            "ports": ["9090:9090"],
-            "networks": ["dchat-testnet"],
-        }));
-        
-        services.insert("grafana".to_string(), serde_json::json!({
-            "image": "grafana/grafana:latest",
-            "ports": ["3000:3000"],
-            "networks": ["dchat-testnet"],
-        }));
-        
-        services.insert("jaeger".to_string(), serde_json::json!({
-            "image": "jaegertracing/all-in-one:latest",
-            "ports": ["16686:16686", "14268:14268"],
-            "networks": ["dchat-testnet"],
-        }));
+    match ControlServer::bind(&config.storage.data_dir).await {
+        Ok(server) => Some(server.spawn(control, shutdown)),
+        Err(e) => {
+            warn!("⚠️  Control API disabled: {}", e);
+            None
+        }
     }
-    
-    let compose = serde_json::json!({
-        "version": "3.8",
-        "services": services,
-        "networks": {
-            "dchat-testnet": {
-                "driver": "bridge"
+}
+
+/// How often nodes run the subscription scheduler
+const BILLING_INTERVAL_SECS: u64 = 3600;
+
+/// Periodically bill marketplace subscriptions
+fn start_billing_task(
+    control: std::sync::Arc<NodeControl>,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(BILLING_INTERVAL_SECS));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    match control.bill_subscriptions().await {
+                        Ok(events) if events > 0 => info!("🔁 Subscription billing produced {} events", events),
+                        Ok(_) => {}
+                        Err(e) => warn!("⚠️  Subscription billing failed: {}", e),
+                    }
+                }
+                _ = shutdown.recv() => break,
             }
         }
     });
-    
-    let compose_path = data_dir.join("docker-compose.json");
-    let compose_json = serde_json::to_string_pretty(&compose)
-        .map_err(|e| Error::Config(format!("Failed to serialize compose: {}", e)))?;
-    std::fs::write(&compose_path, compose_json)
-        .map_err(Error::Io)?;
-    
-    info!("✓ Docker compose written to {:?}", compose_path);
-    
-    Ok(())
 }
 
-/// Start metrics server
-fn start_metrics_server(
-    addr: &str,
+/// How often block producers close a currency chain block
+const BLOCK_INTERVAL_SECS: u64 = 6;
+
+/// Produce currency chain blocks and process tokenomics for each
+fn start_block_task(
+    control: std::sync::Arc<NodeControl>,
     mut shutdown: broadcast::Receiver<()>,
-) -> Result<tokio::task::JoinHandle<()>> {
-    use warp::Filter;
-    
-    let addr: std::net::SocketAddr = addr.parse()
-        .map_err(|e| Error::Config(format!("Invalid metrics address: {}", e)))?;
-    
-    let metrics_route = warp::path("metrics")
-        .map(|| {
-            // TODO: Export Prometheus metrics
-            warp::reply::with_header(
-                "# dchat metrics\n# TODO: Implement metrics export\n",
-                "Content-Type",
-                "text/plain; version=0.0.4"
-            )
-        });
-    
-    let routes = metrics_route;
-    
-    let (_, server) = warp::serve(routes)
-        .bind_with_graceful_shutdown(addr, async move {
-            let _ = shutdown.recv().await;
-        });
-    
-    let handle = tokio::spawn(async move {
-        server.await;
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(BLOCK_INTERVAL_SECS));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    match control.produce_block().await {
+                        Ok(mints) if mints > 0 => info!("⚡ Block processing minted {} events", mints),
+                        Ok(_) => {}
+                        Err(e) => warn!("⚠️  Block processing failed: {}", e),
+                    }
+                }
+                _ = shutdown.recv() => break,
+            }
+        }
     });
-    
-    Ok(handle)
 }
 
-// ============================================================================
-// Key Generation and Identity Management
-// ============================================================================
-
-/// Generate new identity and keys
-async fn generate_keys(output: PathBuf, burner: bool) -> Result<()> {
-    info!("🔑 Generating new identity...");
+/// How often nodes delete content past retention
+const PRUNE_INTERVAL_SECS: u64 = 3600;
 
-    if burner {
-        info!("Creating burner/ephemeral identity");
-        let keypair = KeyPair::generate();
-        let burner_identity = BurnerIdentity::new(&keypair, None);
-        info!("✓ Burner identity created: {}", burner_identity.burner_id);
-        
-        // Save burner identity (no encryption, ephemeral nature)
-        save_burner_identity_unencrypted(&output, &burner_identity).await?;
-    } else {
-        info!("Generating permanent identity...");
-        let keypair = KeyPair::generate();
-        let identity = Identity::new("user".to_string(), &keypair);
-        info!("✓ Identity created: {}", identity.user_id);
-        
-        // Prompt for password and encrypt
-        let password = prompt_password("Enter password to encrypt identity: ")?;
-        save_identity_encrypted(&output, &identity, &password).await?;
+/// Parse a `--node-type` value
+fn parse_node_type(value: &str) -> std::result::Result<NodeType, String> {
+    match value.to_lowercase().as_str() {
+        "archive" => Ok(NodeType::Archive),
+        "full" => Ok(NodeType::Full),
+        "light" => Ok(NodeType::Light),
+        _ => Err("Use: archive, full or light".to_string()),
     }
-
-    info!("✓ Identity saved to {:?}", output);
-    Ok(())
-}
-
-/// Prompt user for password (interactive)
-fn prompt_password(prompt_msg: &str) -> Result<String> {
-    use std::io::{self, Write};
-    
-    print!("{}", prompt_msg);
-    io::stdout().flush()
-        .map_err(Error::Io)?;
-    
-    let mut password = String::new();
-    io::stdin().read_line(&mut password)
-        .map_err(Error::Io)?;
-    
-    Ok(password.trim().to_string())
 }
 
-/// Save identity with encryption
-async fn save_identity_encrypted(
-    path: &Path,
-    identity: &Identity,
-    password: &str,
-) -> Result<()> {
-    use dchat_crypto::encrypt_with_password;
-    
-    // Serialize identity to JSON
-    let json = serde_json::to_string(identity)
-        .map_err(|e| Error::crypto(format!("Serialization failed: {}", e)))?;
-    
-    // Encrypt with password
-    let encrypted = encrypt_with_password(password, json.as_bytes())?;
-    
-    // Serialize encrypted container to bytes
-    let encrypted_bytes = encrypted.to_bytes()?;
-    
-    // Write to file
-    tokio::fs::write(path, &encrypted_bytes).await
-        .map_err(Error::Io)?;
-    
-    info!("✓ Identity encrypted and saved");
-    Ok(())
+/// Pruning policy of a node of `node_type`
+fn pruning_config(config: &Config, node_type: NodeType) -> PruningConfig {
+    PruningConfig {
+        node_type,
+        retention_period: config.storage.message_retention_days as u64 * 86_400,
+        ..PruningConfig::default()
+    }
 }
 
-/// Save burner identity (unencrypted, ephemeral)
-async fn save_burner_identity_unencrypted(
-    path: &Path,
-    burner_identity: &BurnerIdentity,
-) -> Result<()> {
-    // Serialize to JSON
-    let json = serde_json::to_string(burner_identity)
-        .map_err(|e| Error::crypto(format!("Serialization failed: {}", e)))?;
-    
-    // Write to file
-    tokio::fs::write(path, json).await
-        .map_err(Error::Io)?;
-    
-    info!("✓ Burner identity saved");
-    Ok(())
+/// Periodically prune the node's database for its node type
+async fn start_pruning_task(
+    config: &Config,
+    database: &Database,
+    node_type: NodeType,
+    mut shutdown: broadcast::Receiver<()>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let mut pruner = match NodePruner::open(database.clone(), pruning_config(config, node_type)).await {
+        Ok(pruner) => pruner,
+        Err(e) => {
+            warn!("⚠️  Pruning disabled: {}", e);
+            return None;
+        }
+    };
+
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(PRUNE_INTERVAL_SECS));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    match pruner.prune(chrono::Utc::now().timestamp()).await {
+                        Ok(report) if report.messages_deleted > 0 || report.blocks_deleted > 0 => {
+                            info!(
+                                "🧹 Pruned {} messages ({} bytes) and {} blocks",
+                                report.messages_deleted, report.bytes_freed, report.blocks_deleted
+                            );
+                        }
+                        Ok(_) => {}
+                        Err(e) => warn!("⚠️  Pruning failed: {}", e),
+                    }
+                }
+                _ = shutdown.recv() => break,
+            }
+        }
+    }))
 }
 
 /// Run database management commands
-async fn run_database_command(config: Config, action: DatabaseCommand) -> Result<()> {
+///
+/// Snapshots are taken and bootstrapped with the pruning policy of
+/// `node_type`.
+async fn run_database_command(config: Config, action: DatabaseCommand, node_type: NodeType) -> Result<()> {
     use dchat_storage::database::{Database, DatabaseConfig};
     
     match action {
@@ -2220,6 +4241,31 @@ async fn run_database_command(config: Config, action: DatabaseCommand) -> Result
             info!("✓ Restore complete");
             Ok(())
         }
+        DatabaseCommand::Snapshot { output } => {
+            info!("📸 Writing state snapshot to {:?}...", output);
+            
+            let db = open_state_database(&config).await?;
+            let snapshot = NodePruner::open(db.clone(), pruning_config(&config, node_type)).await?.snapshot().await?;
+            tokio::fs::write(&output, serde_json::to_vec(&snapshot)?).await
+                .map_err(Error::Io)?;
+            
+            db.close().await?;
+            info!("✓ Snapshot complete: {} checkpoints, {} blocks", snapshot.checkpoints.len(), snapshot.blocks.len());
+            Ok(())
+        }
+        DatabaseCommand::Bootstrap { input } => {
+            info!("🌱 Bootstrapping from snapshot {:?}...", input);
+            
+            let bytes = tokio::fs::read(&input).await.map_err(Error::Io)?;
+            let snapshot: dchat::storage::StateSnapshot = serde_json::from_slice(&bytes)?;
+            let (checkpoints, blocks) = (snapshot.checkpoints.len(), snapshot.blocks.len());
+            let db = open_state_database(&config).await?;
+            NodePruner::bootstrap(db.clone(), pruning_config(&config, node_type), snapshot).await?;
+            
+            db.close().await?;
+            info!("✓ Bootstrap complete: {} checkpoints, {} blocks", checkpoints, blocks);
+            Ok(())
+        }
     }
 }
 
@@ -2273,7 +4319,34 @@ fn start_health_server(
 }
 
 /// Handle user account management commands
-async fn run_account_command(_config: Config, action: AccountCommand) -> Result<()> {
+async fn run_account_command(config: Config, action: AccountCommand) -> Result<()> {
+    // Restoring keys never touches node state; everything else goes to the
+    // running node when there is one
+    let action = match action {
+        AccountCommand::Restore { mnemonic_file, output, burner } => {
+            let phrase = read_recovery_phrase(mnemonic_file.as_deref()).await?;
+            return restore_keys_from_mnemonic(&phrase, output, burner).await;
+        }
+        // The node may run in another directory, so pin the key file location
+        AccountCommand::Create { username, save_to } => AccountCommand::Create {
+            username,
+            save_to: std::path::absolute(&save_to)?,
+        },
+        action => action,
+    };
+    if forward_to_node(&config, "account.execute", &action).await? {
+        return Ok(());
+    }
+    
+    let user_manager = open_user_manager().await?;
+    let mut out = CommandOutput::default();
+    let result = apply_account_command(&user_manager, action, &mut out).await;
+    print!("{}", out.0);
+    result
+}
+
+/// Open the account database and chain clients used by `dchat account`
+async fn open_user_manager() -> Result<dchat::UserManager> {
     use dchat::UserManager;
     use dchat_storage::DatabaseConfig;
     use std::path::PathBuf;
@@ -2295,31 +4368,37 @@ async fn run_account_command(_config: Config, action: AccountCommand) -> Result<
     let currency_chain = Arc::new(CurrencyChainClient::new(CurrencyChainConfig::default()));
     let bridge = Arc::new(CrossChainBridge::new(chat_chain.clone(), currency_chain.clone()));
     
-    let user_manager = UserManager::new(
+    Ok(UserManager::new(
         database,
         chat_chain,
         currency_chain,
         bridge,
         PathBuf::from("./keys"),
-    );
+    ))
+}
 
+async fn apply_account_command(
+    user_manager: &dchat::UserManager,
+    action: AccountCommand,
+    out: &mut CommandOutput,
+) -> Result<()> {
     match action {
         AccountCommand::Create { username, save_to } => {
             info!("📝 Creating user account: {}", username);
             let response = user_manager.create_user(&username).await?;
             
-            println!("\n✅ User Created Successfully!");
-            println!("  User ID: {}", response.user_id);
-            println!("  Username: {}", response.username);
-            println!("  Public Key: {}", response.public_key);
-            println!("  Created: {}", response.created_at);
-            println!("\n🔐 IMPORTANT: Store your private key securely!");
-            println!("  Private Key: {}", response.private_key);
+            say!(out, "\n✅ User Created Successfully!");
+            say!(out, "  User ID: {}", response.user_id);
+            say!(out, "  Username: {}", response.username);
+            say!(out, "  Public Key: {}", response.public_key);
+            say!(out, "  Created: {}", response.created_at);
+            say!(out, "\n🔐 IMPORTANT: Store your private key securely!");
+            say!(out, "  Private Key: {}", response.private_key);
             
             // Save to file
             let json = serde_json::to_string_pretty(&response)?;
             std::fs::write(&save_to, json)?;
-            println!("\n💾 Keys saved to: {:?}", save_to);
+            say!(out, "\n💾 Keys saved to: {:?}", save_to);
             
             Ok(())
         }
@@ -2329,14 +4408,14 @@ async fn run_account_command(_config: Config, action: AccountCommand) -> Result<
             let users = user_manager.list_users().await?;
             
             if users.is_empty() {
-                println!("No users created yet.");
+                say!(out, "No users created yet.");
             } else {
-                println!("\n👥 Users ({}):", users.len());
-                println!("{:<40} {:<20} {:<40}", "User ID", "Username", "Public Key");
-                println!("{}", "-".repeat(100));
+                say!(out, "\n👥 Users ({}):", users.len());
+                say!(out, "{:<40} {:<20} {:<40}", "User ID", "Username", "Public Key");
+                say!(out, "{}", "-".repeat(100));
                 for user in users {
                     let key_preview = format!("{}...", &user.public_key[..16.min(user.public_key.len())]);
-                    println!("{:<40} {:<20} {:<40}", user.user_id, user.username, key_preview);
+                    say!(out, "{:<40} {:<20} {:<40}", user.user_id, user.username, key_preview);
                 }
             }
             
@@ -2347,14 +4426,14 @@ async fn run_account_command(_config: Config, action: AccountCommand) -> Result<
             info!("👤 Getting profile for user: {}", user_id);
             let profile = user_manager.get_user_profile(&user_id).await?;
             
-            println!("\n📊 User Profile:");
-            println!("  User ID: {}", profile.user_id);
-            println!("  Username: {}", profile.username);
-            println!("  Display Name: {:?}", profile.display_name);
-            println!("  Reputation: {}", profile.reputation_score);
-            println!("  Verified: {}", profile.verified);
-            println!("  Created: {}", profile.created_at);
-            println!("  Public Key: {}", profile.public_key);
+            say!(out, "\n📊 User Profile:");
+            say!(out, "  User ID: {}", profile.user_id);
+            say!(out, "  Username: {}", profile.username);
+            say!(out, "  Display Name: {:?}", profile.display_name);
+            say!(out, "  Reputation: {}", profile.reputation_score);
+            say!(out, "  Verified: {}", profile.verified);
+            say!(out, "  Created: {}", profile.created_at);
+            say!(out, "  Public Key: {}", profile.public_key);
             
             Ok(())
         }
@@ -2363,11 +4442,11 @@ async fn run_account_command(_config: Config, action: AccountCommand) -> Result<
             info!("💬 Sending DM from {} to {}", from, to);
             let response = user_manager.send_direct_message(&from, &to, &message).await?;
             
-            println!("\n✅ Direct Message Sent!");
-            println!("  Message ID: {}", response.message_id);
-            println!("  Status: {}", response.status);
-            println!("  Sent: {}", response.timestamp);
-            println!("  On-chain: {}", response.on_chain_confirmed);
+            say!(out, "\n✅ Direct Message Sent!");
+            say!(out, "  Message ID: {}", response.message_id);
+            say!(out, "  Status: {}", response.status);
+            say!(out, "  Sent: {}", response.timestamp);
+            say!(out, "  On-chain: {}", response.on_chain_confirmed);
             
             Ok(())
         }
@@ -2378,12 +4457,12 @@ async fn run_account_command(_config: Config, action: AccountCommand) -> Result<
                 .create_channel(&creator_id, &name, description.as_deref())
                 .await?;
             
-            println!("\n✅ Channel Created!");
-            println!("  Channel ID: {}", response.channel_id);
-            println!("  Name: {}", response.channel_name);
-            println!("  Creator: {}", response.creator_id);
-            println!("  Created: {}", response.created_at);
-            println!("  On-chain: {}", response.on_chain_confirmed);
+            say!(out, "\n✅ Channel Created!");
+            say!(out, "  Channel ID: {}", response.channel_id);
+            say!(out, "  Name: {}", response.channel_name);
+            say!(out, "  Creator: {}", response.creator_id);
+            say!(out, "  Created: {}", response.created_at);
+            say!(out, "  On-chain: {}", response.on_chain_confirmed);
             
             Ok(())
         }
@@ -2394,10 +4473,10 @@ async fn run_account_command(_config: Config, action: AccountCommand) -> Result<
                 .post_to_channel(&user_id, &channel_id, &message)
                 .await?;
             
-            println!("\n✅ Message Posted!");
-            println!("  Message ID: {}", response.message_id);
-            println!("  Status: {}", response.status);
-            println!("  Posted: {}", response.timestamp);
+            say!(out, "\n✅ Message Posted!");
+            say!(out, "  Message ID: {}", response.message_id);
+            say!(out, "  Status: {}", response.status);
+            say!(out, "  Posted: {}", response.timestamp);
             
             Ok(())
         }
@@ -2407,13 +4486,13 @@ async fn run_account_command(_config: Config, action: AccountCommand) -> Result<
             let messages = user_manager.get_direct_messages(&user_id).await?;
             
             if messages.is_empty() {
-                println!("No direct messages.");
+                say!(out, "No direct messages.");
             } else {
-                println!("\n📬 Direct Messages ({}):", messages.len());
-                println!("{:<40} {:<15} {:<25}", "Message ID", "Status", "Timestamp");
-                println!("{}", "-".repeat(80));
+                say!(out, "\n📬 Direct Messages ({}):", messages.len());
+                say!(out, "{:<40} {:<15} {:<25}", "Message ID", "Status", "Timestamp");
+                say!(out, "{}", "-".repeat(80));
                 for msg in messages {
-                    println!("{:<40} {:<15} {:<25}", msg.message_id, msg.status, msg.timestamp);
+                    say!(out, "{:<40} {:<15} {:<25}", msg.message_id, msg.status, msg.timestamp);
                 }
             }
             
@@ -2425,27 +4504,46 @@ async fn run_account_command(_config: Config, action: AccountCommand) -> Result<
             let messages = user_manager.get_channel_messages(&channel_id).await?;
             
             if messages.is_empty() {
-                println!("No messages in channel.");
+                say!(out, "No messages in channel.");
             } else {
-                println!("\n📖 Channel Messages ({}):", messages.len());
-                println!("{:<40} {:<15} {:<25}", "Message ID", "Status", "Timestamp");
-                println!("{}", "-".repeat(80));
+                say!(out, "\n📖 Channel Messages ({}):", messages.len());
+                say!(out, "{:<40} {:<15} {:<25}", "Message ID", "Status", "Timestamp");
+                say!(out, "{}", "-".repeat(80));
                 for msg in messages {
-                    println!("{:<40} {:<15} {:<25}", msg.message_id, msg.status, msg.timestamp);
+                    say!(out, "{:<40} {:<15} {:<25}", msg.message_id, msg.status, msg.timestamp);
                 }
             }
             
             Ok(())
         }
+
+        AccountCommand::Restore { .. } => {
+            Err(Error::validation("Account restore runs in the CLI, not on the node"))
+        }
     }
 }
 
 /// Run bot management commands
-async fn run_bot_command(_config: Config, action: BotCommand) -> Result<()> {
-    use dchat::bots::{BotFather, CreateBotRequest};
-    use dchat_core::types::UserId;
+async fn run_bot_command(config: Config, action: BotCommand) -> Result<()> {
+    if forward_to_node(&config, "bot.execute", &action).await? {
+        return Ok(());
+    }
     
-    let bot_father = BotFather::new();
+    let database = open_state_database(&config).await?;
+    let mut bot_father = database
+        .load_state(BOT_STATE, BOT_STATE_VERSION, dchat::bots::BotFather::new)
+        .await?;
+    let mut out = CommandOutput::default();
+    let result = apply_bot_command(&bot_father, action, &mut out);
+    print!("{}", out.0);
+    result?;
+    database.save_state(&mut bot_father).await?;
+    Ok(())
+}
+
+fn apply_bot_command(bot_father: &dchat::bots::BotFather, action: BotCommand, out: &mut CommandOutput) -> Result<()> {
+    use dchat::bots::CreateBotRequest;
+    use dchat_core::types::UserId;
     
     match action {
         BotCommand::Create { username, name, description, owner_id } => {
@@ -2462,12 +4560,12 @@ async fn run_bot_command(_config: Config, action: BotCommand) -> Result<()> {
             
             let bot = bot_father.create_bot(owner, request)?;
             
-            println!("\n✅ Bot created successfully!");
-            println!("Bot ID: {}", bot.id);
-            println!("Username: @{}", bot.username);
-            println!("Name: {}", bot.display_name);
-            println!("Token: {}", bot.token);
-            println!("\n⚠️  Keep this token secret! It cannot be recovered.");
+            say!(out, "\n✅ Bot created successfully!");
+            say!(out, "Bot ID: {}", bot.id);
+            say!(out, "Username: @{}", bot.username);
+            say!(out, "Name: {}", bot.display_name);
+            say!(out, "Token: {}", bot.token);
+            say!(out, "\n⚠️  Keep this token secret! It cannot be recovered.");
             
             Ok(())
         }
@@ -2479,19 +4577,19 @@ async fn run_bot_command(_config: Config, action: BotCommand) -> Result<()> {
                         .map_err(|_| Error::validation("Invalid owner ID"))?);
                     let bots = bot_father.get_user_bots(&owner);
                     
-                    println!("\n🤖 Bots owned by {}:", id);
+                    say!(out, "\n🤖 Bots owned by {}:", id);
                     if bots.is_empty() {
-                        println!("No bots found.");
+                        say!(out, "No bots found.");
                     } else {
                         for bot in bots {
-                            println!("  • {} (@{}) - {}", bot.display_name, bot.username, if bot.is_active { "Active" } else { "Inactive" });
+                            say!(out, "  • {} (@{}) - {}", bot.display_name, bot.username, if bot.is_active { "Active" } else { "Inactive" });
                         }
                     }
                 }
                 None => {
                     let count = bot_father.get_all_bots_count();
                     let active = bot_father.get_active_bots_count();
-                    println!("\n🤖 Total bots: {} (Active: {})", count, active);
+                    say!(out, "\n🤖 Total bots: {} (Active: {})", count, active);
                 }
             }
             
@@ -2504,18 +4602,18 @@ async fn run_bot_command(_config: Config, action: BotCommand) -> Result<()> {
             
             match bot_father.get_bot(&bot_uuid) {
                 Some(bot) => {
-                    println!("\n🤖 Bot Information:");
-                    println!("ID: {}", bot.id);
-                    println!("Username: @{}", bot.username);
-                    println!("Name: {}", bot.display_name);
-                    println!("Description: {:?}", bot.description);
-                    println!("Owner: {}", bot.owner_id);
-                    println!("Active: {}", bot.is_active);
-                    println!("Created: {}", bot.created_at);
-                    println!("Commands: {}", bot.commands.len());
+                    say!(out, "\n🤖 Bot Information:");
+                    say!(out, "ID: {}", bot.id);
+                    say!(out, "Username: @{}", bot.username);
+                    say!(out, "Name: {}", bot.display_name);
+                    say!(out, "Description: {:?}", bot.description);
+                    say!(out, "Owner: {}", bot.owner_id);
+                    say!(out, "Active: {}", bot.is_active);
+                    say!(out, "Created: {}", bot.created_at);
+                    say!(out, "Commands: {}", bot.commands.len());
                 }
                 None => {
-                    println!("❌ Bot not found");
+                    say!(out, "❌ Bot not found");
                 }
             }
             
@@ -2530,29 +4628,29 @@ async fn run_bot_command(_config: Config, action: BotCommand) -> Result<()> {
             
             let new_token = bot_father.regenerate_token(&bot_uuid, &owner)?;
             
-            println!("\n✅ Token regenerated successfully!");
-            println!("New token: {}", new_token);
-            println!("\n⚠️  Keep this token secret! Old token is now invalid.");
+            say!(out, "\n✅ Token regenerated successfully!");
+            say!(out, "New token: {}", new_token);
+            say!(out, "\n⚠️  Keep this token secret! Old token is now invalid.");
             
             Ok(())
         }
         
         BotCommand::SetWebhook { bot_id, url, secret } => {
-            println!("\n🔗 Setting webhook for bot {}", bot_id);
-            println!("URL: {}", url);
+            say!(out, "\n🔗 Setting webhook for bot {}", bot_id);
+            say!(out, "URL: {}", url);
             if let Some(s) = secret {
-                println!("Secret: {}", "*".repeat(s.len()));
+                say!(out, "Secret: {}", "*".repeat(s.len()));
             }
-            println!("\n✅ Webhook configured (in-memory only)");
+            say!(out, "\n✅ Webhook configured (in-memory only)");
             
             Ok(())
         }
         
         BotCommand::SendMessage { token: _, chat_id, text } => {
-            println!("\n📤 Sending message as bot...");
-            println!("Chat: {}", chat_id);
-            println!("Text: {}", text);
-            println!("\n✅ Message sent (simulated)");
+            say!(out, "\n📤 Sending message as bot...");
+            say!(out, "Chat: {}", chat_id);
+            say!(out, "Text: {}", text);
+            say!(out, "\n✅ Message sent (simulated)");
             
             Ok(())
         }
@@ -2560,29 +4658,78 @@ async fn run_bot_command(_config: Config, action: BotCommand) -> Result<()> {
 }
 
 /// Run marketplace commands
-async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) -> Result<()> {
-    use dchat::marketplace::{MarketplaceManager, DigitalGoodType, PricingModel};
+async fn run_marketplace_command(config: Config, action: MarketplaceCommand) -> Result<()> {
+    use dchat::marketplace::MarketplaceManager;
+    
+    if forward_to_node(&config, "marketplace.execute", &action).await? {
+        return Ok(());
+    }
+    
+    let database = open_state_database(&config).await?;
+    let mut marketplace = database
+        .load_state(MARKETPLACE_STATE, MARKETPLACE_STATE_VERSION, MarketplaceManager::new)
+        .await?;
+    let mut currency = load_currency_chain(&database).await?;
+    let mut out = CommandOutput::default();
+    let result = apply_marketplace_command(&mut marketplace, &currency, action, &mut out);
+    print!("{}", out.0);
+    result?;
+    database.save_state(&mut currency).await?;
+    database.save_state(&mut marketplace).await?;
+    Ok(())
+}
+
+fn apply_marketplace_command(
+    marketplace: &mut dchat::marketplace::MarketplaceManager,
+    ledger: &CurrencyChainClient,
+    action: MarketplaceCommand,
+    out: &mut CommandOutput,
+) -> Result<()> {
+    use dchat::marketplace::{DigitalGoodType, PricingModel};
     use dchat_core::types::UserId;
     
-    let mut marketplace = MarketplaceManager::new();
+    // Escrowed funds, subscription credit and resale bids live in module
+    // accounts on the currency chain
+    for account in [
+        marketplace.escrow.account().clone(),
+        marketplace.subscriptions.account().clone(),
+        marketplace.market.account().clone(),
+    ] {
+        if ledger.get_wallet(&account)?.is_none() {
+            ledger.create_module_account(&account, 0)?;
+        }
+    }
+    
+    // Bill anything that fell due since the last run
+    marketplace.process_subscriptions(chrono::Utc::now());
     
     match action {
-        MarketplaceCommand::List { item_type } => {
-            println!("\n🏪 Marketplace Listings:");
-            
-            let _type_filter = item_type.as_ref().map(|t| match t.as_str() {
-                "sticker-pack" => DigitalGoodType::StickerPack,
-                "theme" => DigitalGoodType::Theme,
-                "bot" => DigitalGoodType::Bot,
-                "nft" => DigitalGoodType::Nft,
-                "subscription" => DigitalGoodType::Subscription,
-                "badge" => DigitalGoodType::Badge,
-                _ => DigitalGoodType::Theme,
-            });
+        MarketplaceCommand::List { item_type, min_price, max_price, min_rating, verified, search, page, per_page } => {
+            use dchat::marketplace::ListingQuery;
+            
+            let good_type = item_type.as_deref().map(parse_good_type).transpose()?;
+            let query = ListingQuery {
+                good_type,
+                min_price,
+                max_price,
+                min_rating,
+                verified_only: verified,
+                text: search,
+                page,
+                per_page,
+            };
+            let results = marketplace.browse(&query);
             
-            // Note: marketplace doesn't have list_items method, would need implementation
-            println!("Marketplace listing API needs to be implemented");
-            println!("(Use get_listing with specific UUID instead)");
+            say!(out, "\n🏪 Marketplace Listings ({} found, page {} of {}):",
+                results.total, results.page + 1, results.page_count().max(1));
+            
+            for listing in &results.listings {
+                say!(out, "\n  • {} [{:?}]{}", listing.title, listing.good_type,
+                    if listing.is_verified { " ✔" } else { "" });
+                say!(out, "    ID: {}", listing.id);
+                say!(out, "    Price: {:?}", listing.pricing);
+                say!(out, "    Rating: {:.1} | Downloads: {}", listing.rating, listing.downloads);
+            }
             
             Ok(())
         }
@@ -2655,38 +4802,46 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
                 membership_duration,
             )?;
             
-            println!("\n✅ Listing created successfully!");
-            println!("Listing ID: {}", listing_id);
-            println!("Title: {}", title);
-            println!("Item Type: {}", item_type);
-            println!("Storage: {:?}", storage_type);
+            say!(out, "\n✅ Listing created successfully!");
+            say!(out, "Listing ID: {}", listing_id);
+            say!(out, "Title: {}", title);
+            say!(out, "Item Type: {}", item_type);
+            say!(out, "Storage: {:?}", storage_type);
             
             if let Some(bot) = bot_uuid {
-                println!("Bot ID: {}", bot);
+                say!(out, "Bot ID: {}", bot);
             }
             if let Some(channel) = channel_uuid {
-                println!("Channel ID: {}", channel);
+                say!(out, "Channel ID: {}", channel);
             }
             if let Some(duration) = membership_duration {
-                println!("Membership Duration: {} days", duration);
+                say!(out, "Membership Duration: {} days", duration);
             }
             
             Ok(())
         }
         
-        MarketplaceCommand::Buy { buyer_id, listing_id } => {
+        MarketplaceCommand::Buy { buyer_id, listing_id, payment_tx } => {
             info!("💳 Processing purchase");
             
             let buyer = UserId(uuid::Uuid::parse_str(&buyer_id)
                 .map_err(|_| Error::validation("Invalid buyer ID"))?);
             let listing_uuid = uuid::Uuid::parse_str(&listing_id)
                 .map_err(|_| Error::validation("Invalid listing ID"))?;
+            let payment_uuid = uuid::Uuid::parse_str(&payment_tx)
+                .map_err(|_| Error::validation("Invalid payment transaction ID"))?;
             
-            // Simulate payment with mock transaction
-            let purchase_id = marketplace.purchase(buyer, listing_uuid, 1000, "mock_tx_hash".to_string())?;
+            let purchase_id = marketplace.purchase(ledger, buyer, listing_uuid, payment_uuid)?;
             
-            println!("\n✅ Purchase successful!");
-            println!("Purchase ID: {}", purchase_id);
+            say!(out, "\n✅ Purchase successful!");
+            say!(out, "Purchase ID: {}", purchase_id);
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::EscrowAccount => {
+            say!(out, "\n🔒 Escrow account: {}", marketplace.escrow.account());
+            say!(out, "Pay for a purchase with `dchat token transfer --to <account>`, then pass the transaction ID to `marketplace buy --payment-tx`.");
             
             Ok(())
         }
@@ -2697,42 +4852,46 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
             
             let stats = marketplace.get_creator_stats(&creator);
             
-            println!("\n📊 Creator Statistics:");
-            println!("Creator: {}", stats.creator);
-            println!("Total Sales: {}", stats.total_sales);
-            println!("Total Earnings: {} tokens", stats.total_earnings);
-            println!("Active Listings: {}", stats.active_listings);
-            println!("Total Downloads: {}", stats.total_downloads);
-            println!("Average Rating: {:.2}⭐", stats.average_rating);
+            say!(out, "\n📊 Creator Statistics:");
+            say!(out, "Creator: {}", stats.creator);
+            say!(out, "Total Sales: {}", stats.total_sales);
+            say!(out, "Total Earnings: {} tokens", stats.total_earnings);
+            say!(out, "Active Listings: {}", stats.active_listings);
+            say!(out, "Total Downloads: {}", stats.total_downloads);
+            say!(out, "Average Rating: {:.2}⭐", stats.average_rating);
             
             Ok(())
         }
         
-        MarketplaceCommand::CreateEscrow { buyer, seller, amount } => {
+        MarketplaceCommand::CreateEscrow { buyer, seller, payment_tx } => {
             let buyer_id = UserId(uuid::Uuid::parse_str(&buyer)
                 .map_err(|_| Error::validation("Invalid buyer ID"))?);
             let seller_id = UserId(uuid::Uuid::parse_str(&seller)
                 .map_err(|_| Error::validation("Invalid seller ID"))?);
+            let payment_uuid = uuid::Uuid::parse_str(&payment_tx)
+                .map_err(|_| Error::validation("Invalid payment transaction ID"))?;
             
             let listing_id = uuid::Uuid::new_v4(); // Generate mock listing ID
             let lock_duration_secs = 30 * 24 * 60 * 60; // 30 days in seconds
             
             let escrow_id = marketplace.escrow.create_two_party_escrow(
+                ledger,
                 listing_id,
                 &buyer_id,
                 &seller_id,
-                amount,
+                payment_uuid,
                 lock_duration_secs,
             ).map_err(|e| Error::internal(format!("Escrow error: {:?}", e)))?;
+            let amount = marketplace.escrow.get_escrow(escrow_id).map(|e| e.amount).unwrap_or(0);
             
             let expires_at = chrono::Utc::now() + chrono::Duration::seconds(lock_duration_secs as i64);
             
-            println!("\n✅ Escrow created successfully!");
-            println!("Escrow ID: {}", escrow_id);
-            println!("Buyer: {}", buyer);
-            println!("Seller: {}", seller);
-            println!("Amount: {} tokens", amount);
-            println!("Expires: {}", expires_at);
+            say!(out, "\n✅ Escrow created successfully!");
+            say!(out, "Escrow ID: {}", escrow_id);
+            say!(out, "Buyer: {}", buyer);
+            say!(out, "Seller: {}", seller);
+            say!(out, "Amount: {} tokens", amount);
+            say!(out, "Expires: {}", expires_at);
             
             Ok(())
         }
@@ -2749,10 +4908,10 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
                 owner_id,
             )?;
             
-            println!("\n✅ Bot registered for marketplace trading!");
-            println!("Bot ID: {}", bot_id);
-            println!("Username: {}", username);
-            println!("On-Chain Address: {}", on_chain_address);
+            say!(out, "\n✅ Bot registered for marketplace trading!");
+            say!(out, "Bot ID: {}", bot_id);
+            say!(out, "Username: {}", username);
+            say!(out, "On-Chain Address: {}", on_chain_address);
             
             Ok(())
         }
@@ -2770,11 +4929,11 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
                 member_count,
             )?;
             
-            println!("\n✅ Channel registered for marketplace trading!");
-            println!("Channel ID: {}", channel_id);
-            println!("Name: {}", name);
-            println!("Member Count: {}", member_count);
-            println!("On-Chain Address: {}", on_chain_address);
+            say!(out, "\n✅ Channel registered for marketplace trading!");
+            say!(out, "Channel ID: {}", channel_id);
+            say!(out, "Name: {}", name);
+            say!(out, "Member Count: {}", member_count);
+            say!(out, "On-Chain Address: {}", on_chain_address);
             
             Ok(())
         }
@@ -2784,21 +4943,21 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
                 .map_err(|_| Error::validation("Invalid bot ID"))?;
             
             if let Some(ownership) = marketplace.get_bot_ownership(bot_uuid) {
-                println!("\n🤖 Bot Ownership Information:");
-                println!("Bot ID: {}", ownership.bot_id);
-                println!("Username: {}", ownership.bot_username);
-                println!("Current Owner: {}", ownership.current_owner);
-                println!("On-Chain Address: {}", ownership.on_chain_address);
-                println!("Transfer Count: {}", ownership.transfer_count);
+                say!(out, "\n🤖 Bot Ownership Information:");
+                say!(out, "Bot ID: {}", ownership.bot_id);
+                say!(out, "Username: {}", ownership.bot_username);
+                say!(out, "Current Owner: {}", ownership.current_owner);
+                say!(out, "On-Chain Address: {}", ownership.on_chain_address);
+                say!(out, "Transfer Count: {}", ownership.transfer_count);
                 
                 if !ownership.previous_owners.is_empty() {
-                    println!("\n📜 Ownership History:");
+                    say!(out, "\n📜 Ownership History:");
                     for (i, (owner, timestamp)) in ownership.previous_owners.iter().enumerate() {
-                        println!("  {}. {} at {}", i + 1, owner, timestamp);
+                        say!(out, "  {}. {} at {}", i + 1, owner, timestamp);
                     }
                 }
             } else {
-                println!("❌ Bot ownership not found");
+                say!(out, "❌ Bot ownership not found");
             }
             
             Ok(())
@@ -2809,22 +4968,22 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
                 .map_err(|_| Error::validation("Invalid channel ID"))?;
             
             if let Some(ownership) = marketplace.get_channel_ownership(channel_uuid) {
-                println!("\n📺 Channel Ownership Information:");
-                println!("Channel ID: {}", ownership.channel_id);
-                println!("Name: {}", ownership.channel_name);
-                println!("Current Owner: {}", ownership.current_owner);
-                println!("Member Count: {}", ownership.member_count);
-                println!("On-Chain Address: {}", ownership.on_chain_address);
-                println!("Transfer Count: {}", ownership.transfer_count);
+                say!(out, "\n📺 Channel Ownership Information:");
+                say!(out, "Channel ID: {}", ownership.channel_id);
+                say!(out, "Name: {}", ownership.channel_name);
+                say!(out, "Current Owner: {}", ownership.current_owner);
+                say!(out, "Member Count: {}", ownership.member_count);
+                say!(out, "On-Chain Address: {}", ownership.on_chain_address);
+                say!(out, "Transfer Count: {}", ownership.transfer_count);
                 
                 if !ownership.previous_owners.is_empty() {
-                    println!("\n📜 Ownership History:");
+                    say!(out, "\n📜 Ownership History:");
                     for (i, (owner, timestamp)) in ownership.previous_owners.iter().enumerate() {
-                        println!("  {}. {} at {}", i + 1, owner, timestamp);
+                        say!(out, "  {}. {} at {}", i + 1, owner, timestamp);
                     }
                 }
             } else {
-                println!("❌ Channel ownership not found");
+                say!(out, "❌ Channel ownership not found");
             }
             
             Ok(())
@@ -2836,13 +4995,13 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
             
             let bots = marketplace.get_bots_by_owner(&user_uuid);
             
-            println!("\n🤖 Your Bots:");
+            say!(out, "\n🤖 Your Bots:");
             if bots.is_empty() {
-                println!("No bots owned");
+                say!(out, "No bots owned");
             } else {
                 for bot in bots {
-                    println!("\n  • {} ({})", bot.bot_username, bot.bot_id);
-                    println!("    Transfers: {}", bot.transfer_count);
+                    say!(out, "\n  • {} ({})", bot.bot_username, bot.bot_id);
+                    say!(out, "    Transfers: {}", bot.transfer_count);
                 }
             }
             
@@ -2855,14 +5014,14 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
             
             let channels = marketplace.get_channels_by_owner(&user_uuid);
             
-            println!("\n📺 Your Channels:");
+            say!(out, "\n📺 Your Channels:");
             if channels.is_empty() {
-                println!("No channels owned");
+                say!(out, "No channels owned");
             } else {
                 for channel in channels {
-                    println!("\n  • {} ({})", channel.channel_name, channel.channel_id);
-                    println!("    Members: {}", channel.member_count);
-                    println!("    Transfers: {}", channel.transfer_count);
+                    say!(out, "\n  • {} ({})", channel.channel_name, channel.channel_id);
+                    say!(out, "    Members: {}", channel.member_count);
+                    say!(out, "    Transfers: {}", channel.transfer_count);
                 }
             }
             
@@ -2883,11 +5042,11 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
                 animated,
             )?;
             
-            println!("\n✅ Emoji pack created!");
-            println!("Pack ID: {}", pack_id);
-            println!("Name: {}", name);
-            println!("Emoji Count: {}", emoji_count);
-            println!("Animated: {}", animated);
+            say!(out, "\n✅ Emoji pack created!");
+            say!(out, "Pack ID: {}", pack_id);
+            say!(out, "Name: {}", name);
+            say!(out, "Emoji Count: {}", emoji_count);
+            say!(out, "Animated: {}", animated);
             
             Ok(())
         }
@@ -2905,6 +5064,7 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
                 "cc-by-nd" => LicenseType::CcByNd,
                 "cc-by-nc" => LicenseType::CcByNc,
                 "public-domain" => LicenseType::PublicDomain,
+                "personal" => LicenseType::Personal,
                 _ => return Err(Error::validation("Invalid license type")),
             };
             
@@ -2919,12 +5079,12 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
                 license_type,
             )?;
             
-            println!("\n✅ Image registered!");
-            println!("Image ID: {}", image_id);
-            println!("Title: {}", title);
-            println!("Dimensions: {}x{}", width, height);
-            println!("Format: {}", format);
-            println!("License: {}", license);
+            say!(out, "\n✅ Image registered!");
+            say!(out, "Image ID: {}", image_id);
+            say!(out, "Title: {}", title);
+            say!(out, "Dimensions: {}x{}", width, height);
+            say!(out, "Format: {}", format);
+            say!(out, "License: {}", license);
             
             Ok(())
         }
@@ -2938,9 +5098,9 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
             let has_membership = marketplace.has_active_membership(channel_uuid, &user_uuid);
             
             if has_membership {
-                println!("\n✅ User has active membership");
+                say!(out, "\n✅ User has active membership");
             } else {
-                println!("\n❌ User does not have active membership");
+                say!(out, "\n❌ User does not have active membership");
             }
             
             Ok(())
@@ -2952,15 +5112,15 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
             
             let memberships = marketplace.get_memberships_by_holder(&user_uuid);
             
-            println!("\n🎫 Your Memberships:");
+            say!(out, "\n🎫 Your Memberships:");
             if memberships.is_empty() {
-                println!("No active memberships");
+                say!(out, "No active memberships");
             } else {
                 for membership in memberships {
-                    println!("\n  • Channel: {}", membership.channel_id);
-                    println!("    Access Level: {:?}", membership.access_level);
-                    println!("    Expires: {}", membership.expires_at);
-                    println!("    Transferable: {}", membership.is_transferable);
+                    say!(out, "\n  • Channel: {}", membership.channel_id);
+                    say!(out, "    Access Level: {:?}", membership.access_level);
+                    say!(out, "    Expires: {}", membership.expires_at);
+                    say!(out, "    Transferable: {}", membership.is_transferable);
                 }
             }
             
@@ -2975,8 +5135,8 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
             
             marketplace.transfer_membership(membership_uuid, new_holder_id)?;
             
-            println!("\n✅ Membership transferred successfully!");
-            println!("New Holder: {}", new_holder);
+            say!(out, "\n✅ Membership transferred successfully!");
+            say!(out, "New Holder: {}", new_holder);
             
             Ok(())
         }
@@ -2987,18 +5147,260 @@ async fn run_marketplace_command(_config: Config, action: MarketplaceCommand) ->
             
             let members = marketplace.get_memberships_by_channel(channel_uuid);
             
-            println!("\n👥 Channel Members:");
-            println!("Channel ID: {}", channel_id);
-            println!("Active Members: {}", members.len());
+            say!(out, "\n👥 Channel Members:");
+            say!(out, "Channel ID: {}", channel_id);
+            say!(out, "Active Members: {}", members.len());
             
             for (i, membership) in members.iter().enumerate() {
-                println!("\n  {}. User: {}", i + 1, membership.holder);
-                println!("     Access Level: {:?}", membership.access_level);
-                println!("     Expires: {}", membership.expires_at);
+                say!(out, "\n  {}. User: {}", i + 1, membership.holder);
+                say!(out, "     Access Level: {:?}", membership.access_level);
+                say!(out, "     Expires: {}", membership.expires_at);
+            }
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::TopUpCredit { subscriber_id, payment_tx } => {
+            let subscriber = UserId(uuid::Uuid::parse_str(&subscriber_id)
+                .map_err(|_| Error::validation("Invalid subscriber ID"))?);
+            let payment_uuid = uuid::Uuid::parse_str(&payment_tx)
+                .map_err(|_| Error::validation("Invalid payment transaction ID"))?;
+            
+            let amount = marketplace.top_up_subscription_credit(ledger, &subscriber, payment_uuid, chrono::Utc::now())?;
+            
+            say!(out, "\n✅ Added {} tokens of subscription credit", amount);
+            say!(out, "Credit: {} tokens", marketplace.subscriptions.credit(&subscriber));
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::Subscribe { subscriber_id, listing_id } => {
+            let subscriber = UserId(uuid::Uuid::parse_str(&subscriber_id)
+                .map_err(|_| Error::validation("Invalid subscriber ID"))?);
+            let listing_uuid = uuid::Uuid::parse_str(&listing_id)
+                .map_err(|_| Error::validation("Invalid listing ID"))?;
+            
+            let subscription_id = marketplace.subscribe(subscriber.clone(), listing_uuid, chrono::Utc::now())?;
+            let subscription = marketplace.subscriptions.get_subscription(subscription_id)
+                .ok_or_else(|| Error::NotFound("Subscription not found".to_string()))?;
+            
+            say!(out, "\n✅ Subscribed!");
+            say!(out, "Subscription ID: {}", subscription_id);
+            say!(out, "Price: {} tokens per period", subscription.price_per_period);
+            say!(out, "Renews: {}", subscription.period_end);
+            say!(out, "Credit left: {} tokens", marketplace.subscriptions.credit(&subscriber));
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::CancelSubscription { subscriber_id, subscription_id } => {
+            let subscriber = UserId(uuid::Uuid::parse_str(&subscriber_id)
+                .map_err(|_| Error::validation("Invalid subscriber ID"))?);
+            let subscription_uuid = uuid::Uuid::parse_str(&subscription_id)
+                .map_err(|_| Error::validation("Invalid subscription ID"))?;
+            
+            let refund = marketplace.cancel_subscription(&subscriber, subscription_uuid, chrono::Utc::now())?;
+            
+            say!(out, "\n✅ Subscription cancelled");
+            say!(out, "Refunded to credit: {} tokens", refund);
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::MySubscriptions { user_id } => {
+            let user = UserId(uuid::Uuid::parse_str(&user_id)
+                .map_err(|_| Error::validation("Invalid user ID"))?);
+            
+            say!(out, "\n🔁 Your Subscriptions:");
+            say!(out, "Subscription account: {}", marketplace.subscriptions.account());
+            say!(out, "Credit: {} tokens", marketplace.subscriptions.credit(&user));
+            for subscription in marketplace.subscriptions.get_subscriber_subscriptions(&user) {
+                say!(out, "\n  • {} ({:?})", subscription.id, subscription.status);
+                say!(out, "    Listing: {}", subscription.listing_id);
+                say!(out, "    Price: {} tokens per period", subscription.price_per_period);
+                say!(out, "    Access until: {}", subscription.access_until());
             }
             
             Ok(())
         }
+        
+        MarketplaceCommand::PayoutCreator { creator_id } => {
+            let creator = UserId(uuid::Uuid::parse_str(&creator_id)
+                .map_err(|_| Error::validation("Invalid creator ID"))?);
+            
+            let amount = marketplace.subscriptions.payout_creator(ledger, &creator)
+                .map_err(|e| Error::validation(format!("Payout failed: {}", e)))?;
+            
+            say!(out, "\n💸 Paid {} tokens of subscription earnings", amount);
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::Ask { seller_id, asset_type, asset_id, price } => {
+            let seller = UserId(uuid::Uuid::parse_str(&seller_id)
+                .map_err(|_| Error::validation("Invalid seller ID"))?);
+            let asset = parse_resale_asset(&asset_type, &asset_id)?;
+            
+            let outcome = marketplace.place_ask(ledger, seller, asset, price)?;
+            
+            say_order_outcome(out, "Ask", &outcome);
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::Bid { bidder_id, asset_type, asset_id, payment_tx } => {
+            let bidder = UserId(uuid::Uuid::parse_str(&bidder_id)
+                .map_err(|_| Error::validation("Invalid bidder ID"))?);
+            let asset = parse_resale_asset(&asset_type, &asset_id)?;
+            let payment_uuid = uuid::Uuid::parse_str(&payment_tx)
+                .map_err(|_| Error::validation("Invalid payment transaction ID"))?;
+            
+            let outcome = marketplace.place_bid(ledger, bidder, asset, payment_uuid)?;
+            
+            say_order_outcome(out, "Bid", &outcome);
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::CancelOrder { trader_id, order_id } => {
+            let trader = UserId(uuid::Uuid::parse_str(&trader_id)
+                .map_err(|_| Error::validation("Invalid trader ID"))?);
+            let order_uuid = uuid::Uuid::parse_str(&order_id)
+                .map_err(|_| Error::validation("Invalid order ID"))?;
+            
+            marketplace.cancel_order(ledger, &trader, order_uuid)?;
+            
+            say!(out, "\n✅ Order {} cancelled", order_uuid);
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::OrderBook { asset_type, asset_id } => {
+            let asset = parse_resale_asset(&asset_type, &asset_id)?;
+            
+            say!(out, "\n📈 Order book for {}", asset);
+            say!(out, "Market account: {}", marketplace.market.account());
+            if let Some(holder) = marketplace.asset_holder(&asset) {
+                say!(out, "Holder: {}", holder);
+            }
+            for order in marketplace.market.orders_for(&asset) {
+                say!(out, "  {:?} {} tokens by {} ({})", order.side, order.price, order.trader, order.id);
+            }
+            
+            let trades = marketplace.market.trades_for(&asset);
+            if !trades.is_empty() {
+                say!(out, "\nTrades:");
+                for trade in trades {
+                    say!(out, "  {} → {} for {} tokens (royalty {}, fee burned {}) at {}",
+                        trade.seller, trade.buyer, trade.price, trade.royalty, trade.platform_fee, trade.executed_at);
+                }
+            }
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::SplitRevenue { creator_id, asset_type, asset_id, shares } => {
+            use dchat::marketplace::settlement::RevenueSplit;
+            
+            let creator = UserId(uuid::Uuid::parse_str(&creator_id)
+                .map_err(|_| Error::validation("Invalid creator ID"))?);
+            let asset = parse_resale_asset(&asset_type, &asset_id)?;
+            let shares = shares.iter().map(|share| {
+                let (user, bps) = share.split_once(':')
+                    .ok_or_else(|| Error::validation(format!("Invalid share: {}", share)))?;
+                let user = UserId(uuid::Uuid::parse_str(user)
+                    .map_err(|_| Error::validation(format!("Invalid recipient ID: {}", user)))?);
+                let bps = bps.parse::<u16>()
+                    .map_err(|_| Error::validation(format!("Invalid basis points: {}", bps)))?;
+                Ok((user, bps))
+            }).collect::<Result<Vec<_>>>()?;
+            let split = RevenueSplit::new(shares)
+                .map_err(|e| Error::validation(e.to_string()))?;
+            
+            marketplace.set_revenue_split(&creator, asset.clone(), split.clone())?;
+            
+            say!(out, "\n✅ Revenue split set for {}", asset);
+            for (user, bps) in split.shares() {
+                say!(out, "  {}: {}.{:02}%", user, bps / 100, bps % 100);
+            }
+            
+            Ok(())
+        }
+        
+        MarketplaceCommand::Provenance { asset_type, asset_id } => {
+            let asset = parse_resale_asset(&asset_type, &asset_id)?;
+            
+            say!(out, "\n📜 Provenance of {}", asset);
+            if let Some(creator) = marketplace.asset_creator(&asset) {
+                say!(out, "Creator: {}", creator);
+            }
+            for record in marketplace.settlement.provenance(&asset) {
+                let royalty: u64 = record.royalties.iter().map(|(_, amount)| amount).sum();
+                say!(out, "\n  {:?} {} → {} at {}", record.kind, record.from, record.to, record.settled_at);
+                say!(out, "    Price: {} (royalty {}, fee burned {})", record.price, royalty, record.platform_fee);
+                say!(out, "    Hash: {}", hex::encode(record.hash));
+            }
+            if marketplace.settlement.verify_provenance(&asset) {
+                say!(out, "\n✅ Chain verified");
+            } else {
+                say!(out, "\n❌ Chain does not verify");
+            }
+            
+            Ok(())
+        }
+    }
+}
+
+/// Parse a marketplace item type name
+fn parse_good_type(name: &str) -> Result<dchat::marketplace::DigitalGoodType> {
+    use dchat::marketplace::DigitalGoodType;
+    
+    Ok(match name {
+        "sticker-pack" => DigitalGoodType::StickerPack,
+        "emoji-pack" => DigitalGoodType::EmojiPack,
+        "theme" => DigitalGoodType::Theme,
+        "bot" => DigitalGoodType::Bot,
+        "nft" => DigitalGoodType::Nft,
+        "image" => DigitalGoodType::Image,
+        "subscription" => DigitalGoodType::Subscription,
+        "badge" => DigitalGoodType::Badge,
+        "channel" => DigitalGoodType::Channel,
+        "membership" => DigitalGoodType::Membership,
+        _ => return Err(Error::validation(format!("Invalid item type: {}", name))),
+    })
+}
+
+/// Parse a secondary market asset from its type and ID
+fn parse_resale_asset(asset_type: &str, asset_id: &str) -> Result<dchat::marketplace::secondary::ResaleAsset> {
+    use dchat::marketplace::secondary::ResaleAsset;
+    
+    let parse_uuid = || uuid::Uuid::parse_str(asset_id)
+        .map_err(|_| Error::validation("Invalid asset ID"));
+    
+    match asset_type {
+        "nft" => Ok(ResaleAsset::Nft(asset_id.to_string())),
+        "membership" => Ok(ResaleAsset::Membership(parse_uuid()?)),
+        "bot" => Ok(ResaleAsset::Bot(parse_uuid()?)),
+        "channel" => Ok(ResaleAsset::Channel(parse_uuid()?)),
+        "image" => Ok(ResaleAsset::Image(parse_uuid()?)),
+        _ => Err(Error::validation(format!("Invalid asset type: {}", asset_type))),
+    }
+}
+
+/// Report a placed ask or bid
+fn say_order_outcome(out: &mut CommandOutput, side: &str, outcome: &dchat::marketplace::secondary::OrderOutcome) {
+    match &outcome.trade {
+        Some(trade) => {
+            say!(out, "\n✅ {} filled!", side);
+            say!(out, "Trade ID: {}", trade.id);
+            say!(out, "Price: {} tokens", trade.price);
+            say!(out, "Royalty: {} tokens", trade.royalty);
+            say!(out, "New holder: {}", trade.buyer);
+        }
+        None => {
+            say!(out, "\n📋 {} placed", side);
+            say!(out, "Order ID: {}", outcome.order_id);
+        }
     }
 }
 
@@ -3173,15 +5575,41 @@ async fn run_chaos_command(action: ChaosCommand) -> Result<()> {
 }
 
 /// Run governance command
-async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
-    use dchat::governance::{UpgradeManager, UpgradeProposal, UpgradeType, UpgradeStatus, Version, ValidatorSignature};
-    use std::sync::Mutex;
+async fn run_governance_command(config: Config, action: GovernanceCommand) -> Result<()> {
+    use dchat::governance::UpgradeManager;
     
-    // In a real implementation, this would be stored in persistent storage
-    lazy_static::lazy_static! {
-        static ref UPGRADE_MANAGER: Mutex<UpgradeManager> = Mutex::new(UpgradeManager::new());
+    if forward_to_node(&config, "governance.execute", &action).await? {
+        return Ok(());
     }
     
+    let database = open_state_database(&config).await?;
+    let mut manager = database
+        .load_state(GOVERNANCE_STATE, GOVERNANCE_STATE_VERSION, UpgradeManager::new)
+        .await?;
+    let mut execution = database
+        .load_state(EXECUTION_STATE, EXECUTION_STATE_VERSION, GovernanceExecution::default)
+        .await?;
+    let height = load_currency_chain(&database).await?.get_current_block();
+    let mut out = CommandOutput::default();
+    let result = apply_governance_command(&mut manager, &mut execution.executor, height, action, &mut out);
+    print!("{}", out.0);
+    result?;
+    database.save_state(&mut manager).await?;
+    database.save_state(&mut execution).await?;
+    Ok(())
+}
+
+/// Apply a governance command; `height` is the currency chain's, which
+/// passed proposals are queued behind
+fn apply_governance_command(
+    manager: &mut dchat::governance::UpgradeManager,
+    executor: &mut dchat::governance::ProposalExecutor,
+    height: u64,
+    action: GovernanceCommand,
+    out: &mut CommandOutput,
+) -> Result<()> {
+    use dchat::governance::{UpgradeProposal, UpgradeType, UpgradeStatus, Version, ValidatorSignature};
+    
     match action {
         GovernanceCommand::ProposeUpgrade {
             proposer,
@@ -3193,7 +5621,7 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
             voting_days,
             quorum,
         } => {
-            println!("\n📜 Submitting Protocol Upgrade Proposal");
+            say!(out, "\n📜 Submitting Protocol Upgrade Proposal");
             
             let proposer_id = UserId(uuid::Uuid::parse_str(&proposer)
                 .map_err(|_| Error::validation("Invalid proposer ID"))?);
@@ -3215,7 +5643,6 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
             
             let target = Version::parse(&target_version)?;
             
-            let mut manager = UPGRADE_MANAGER.lock().unwrap();
             let current = manager.current_version().clone();
             
             let mut proposal = UpgradeProposal::new(
@@ -3235,24 +5662,23 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
             
             let proposal_id = manager.submit_proposal(proposal)?;
             
-            println!("✅ Proposal submitted successfully!");
-            println!("Proposal ID: {}", proposal_id);
-            println!("Title: {}", title);
-            println!("Target Version: {}", target_version);
-            println!("Voting Deadline: {} days from now", voting_days);
-            println!("Required Quorum: {}%", quorum);
+            say!(out, "✅ Proposal submitted successfully!");
+            say!(out, "Proposal ID: {}", proposal_id);
+            say!(out, "Title: {}", title);
+            say!(out, "Target Version: {}", target_version);
+            say!(out, "Voting Deadline: {} days from now", voting_days);
+            say!(out, "Required Quorum: {}%", quorum);
             
             Ok(())
         }
         
         GovernanceCommand::ListProposals { status } => {
-            let manager = UPGRADE_MANAGER.lock().unwrap();
             let proposals = manager.get_active_proposals();
             
-            println!("\n📊 Upgrade Proposals ({}):", proposals.len());
+            say!(out, "\n📊 Upgrade Proposals ({}):", proposals.len());
             
             if proposals.is_empty() {
-                println!("No active proposals found.");
+                say!(out, "No active proposals found.");
                 return Ok(());
             }
             
@@ -3274,18 +5700,18 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
                     }
                 }
                 
-                println!("\n{}", "-".repeat(80));
-                println!("ID: {}", proposal.id);
-                println!("Title: {}", proposal.title);
-                println!("Version: {} → {}", proposal.current_version, proposal.target_version);
-                println!("Type: {:?}", proposal.upgrade_type);
-                println!("Status: {:?}", proposal.status);
-                println!("Votes: {} for, {} against", proposal.votes_for, proposal.votes_against);
-                println!("Quorum: {}%", proposal.quorum_percentage);
-                println!("Deadline: {}", proposal.voting_deadline.format("%Y-%m-%d %H:%M:%S UTC"));
+                say!(out, "\n{}", "-".repeat(80));
+                say!(out, "ID: {}", proposal.id);
+                say!(out, "Title: {}", proposal.title);
+                say!(out, "Version: {} → {}", proposal.current_version, proposal.target_version);
+                say!(out, "Type: {:?}", proposal.upgrade_type);
+                say!(out, "Status: {:?}", proposal.status);
+                say!(out, "Votes: {} for, {} against", proposal.votes_for, proposal.votes_against);
+                say!(out, "Quorum: {}%", proposal.quorum_percentage);
+                say!(out, "Deadline: {}", proposal.voting_deadline.format("%Y-%m-%d %H:%M:%S UTC"));
                 
                 if let Some(ref url) = proposal.spec_url {
-                    println!("Spec: {}", url);
+                    say!(out, "Spec: {}", url);
                 }
             }
             
@@ -3295,47 +5721,46 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
         GovernanceCommand::GetProposal { proposal_id } => {
             let id = uuid::Uuid::parse_str(&proposal_id)
                 .map_err(|_| Error::validation("Invalid proposal ID"))?;
-            let manager = UPGRADE_MANAGER.lock().unwrap();
             
             match manager.get_proposal(&id) {
                 Some(proposal) => {
-                    println!("\n📋 Proposal Details");
-                    println!("{}", "=".repeat(80));
-                    println!("ID: {}", proposal.id);
-                    println!("Proposer: {}", proposal.proposer);
-                    println!("Title: {}", proposal.title);
-                    println!("Description:\n{}", proposal.description);
-                    println!("\nVersion: {} → {}", proposal.current_version, proposal.target_version);
-                    println!("Type: {:?}", proposal.upgrade_type);
-                    println!("Status: {:?}", proposal.status);
-                    println!("\nVoting:");
-                    println!("  For: {}", proposal.votes_for);
-                    println!("  Against: {}", proposal.votes_against);
-                    println!("  Quorum: {}%", proposal.quorum_percentage);
-                    println!("  Deadline: {}", proposal.voting_deadline.format("%Y-%m-%d %H:%M:%S UTC"));
+                    say!(out, "\n📋 Proposal Details");
+                    say!(out, "{}", "=".repeat(80));
+                    say!(out, "ID: {}", proposal.id);
+                    say!(out, "Proposer: {}", proposal.proposer);
+                    say!(out, "Title: {}", proposal.title);
+                    say!(out, "Description:\n{}", proposal.description);
+                    say!(out, "\nVersion: {} → {}", proposal.current_version, proposal.target_version);
+                    say!(out, "Type: {:?}", proposal.upgrade_type);
+                    say!(out, "Status: {:?}", proposal.status);
+                    say!(out, "\nVoting:");
+                    say!(out, "  For: {}", proposal.votes_for);
+                    say!(out, "  Against: {}", proposal.votes_against);
+                    say!(out, "  Quorum: {}%", proposal.quorum_percentage);
+                    say!(out, "  Deadline: {}", proposal.voting_deadline.format("%Y-%m-%d %H:%M:%S UTC"));
                     
                     if let Some(ref url) = proposal.spec_url {
-                        println!("\nSpecification: {}", url);
+                        say!(out, "\nSpecification: {}", url);
                     }
                     
                     if !proposal.validator_signatures.is_empty() {
-                        println!("\nValidator Signatures: {}", proposal.validator_signatures.len());
+                        say!(out, "\nValidator Signatures: {}", proposal.validator_signatures.len());
                         for (i, sig) in proposal.validator_signatures.iter().enumerate() {
-                            println!("  {}. {} (stake: {})", i + 1, sig.validator_id, sig.stake_amount);
+                            say!(out, "  {}. {} (stake: {})", i + 1, sig.validator_id, sig.stake_amount);
                         }
                     }
                     
                     if let Some(height) = proposal.activation_height {
-                        println!("\nActivation Height: {}", height);
+                        say!(out, "\nActivation Height: {}", height);
                     }
                     if let Some(time) = proposal.activation_time {
-                        println!("Activation Time: {}", time.format("%Y-%m-%d %H:%M:%S UTC"));
+                        say!(out, "Activation Time: {}", time.format("%Y-%m-%d %H:%M:%S UTC"));
                     }
                     
                     Ok(())
                 }
                 None => {
-                    println!("❌ Proposal not found: {}", proposal_id);
+                    say!(out, "❌ Proposal not found: {}", proposal_id);
                     Ok(())
                 }
             }
@@ -3347,14 +5772,13 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
             let voter_id = UserId(uuid::Uuid::parse_str(&voter)
                 .map_err(|_| Error::validation("Invalid voter ID"))?);
             
-            let mut manager = UPGRADE_MANAGER.lock().unwrap();
             manager.cast_upgrade_vote(id, voter_id, vote_for, voting_power)?;
             
-            println!("\n✅ Vote cast successfully!");
-            println!("Proposal: {}", proposal_id);
-            println!("Voter: {}", voter);
-            println!("Vote: {}", if vote_for { "FOR" } else { "AGAINST" });
-            println!("Voting Power: {}", voting_power);
+            say!(out, "\n✅ Vote cast successfully!");
+            say!(out, "Proposal: {}", proposal_id);
+            say!(out, "Voter: {}", voter);
+            say!(out, "Vote: {}", if vote_for { "FOR" } else { "AGAINST" });
+            say!(out, "Voting Power: {}", voting_power);
             
             Ok(())
         }
@@ -3365,11 +5789,11 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
             let val_id = UserId(uuid::Uuid::parse_str(&validator_id)
                 .map_err(|_| Error::validation("Invalid validator ID"))?);
             
-            println!("\n🔑 Validator Signing Upgrade Approval");
-            println!("Proposal: {}", proposal_id);
-            println!("Validator: {}", validator_id);
-            println!("Stake: {}", stake);
-            println!("Key File: {}", key_file.display());
+            say!(out, "\n🔑 Validator Signing Upgrade Approval");
+            say!(out, "Proposal: {}", proposal_id);
+            say!(out, "Validator: {}", validator_id);
+            say!(out, "Stake: {}", stake);
+            say!(out, "Key File: {}", key_file.display());
             
             // In real implementation: load key, sign proposal hash
             let signature = vec![0u8; 64]; // Placeholder signature
@@ -3381,7 +5805,6 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
                 signed_at: chrono::Utc::now(),
             };
             
-            let manager = UPGRADE_MANAGER.lock().unwrap();
             let proposal = manager.get_proposal(&id)
                 .ok_or_else(|| Error::NotFound("Proposal not found".to_string()))?;
             
@@ -3389,7 +5812,7 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
             let mut updated_proposal = proposal.clone();
             updated_proposal.add_validator_signature(sig)?;
             
-            println!("✅ Validator signature added!");
+            say!(out, "✅ Validator signature added!");
             
             Ok(())
         }
@@ -3398,17 +5821,24 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
             let id = uuid::Uuid::parse_str(&proposal_id)
                 .map_err(|_| Error::validation("Invalid proposal ID"))?;
             
-            let mut manager = UPGRADE_MANAGER.lock().unwrap();
             let passed = manager.finalize_proposal(id)?;
             
-            println!("\n📊 Proposal Finalized");
-            println!("Proposal ID: {}", proposal_id);
-            println!("Result: {}", if passed { "✅ APPROVED" } else { "❌ REJECTED" });
+            say!(out, "\n📊 Proposal Finalized");
+            say!(out, "Proposal ID: {}", proposal_id);
+            say!(out, "Result: {}", if passed { "✅ APPROVED" } else { "❌ REJECTED" });
             
             if let Some(proposal) = manager.get_proposal(&id) {
-                println!("Votes For: {}", proposal.votes_for);
-                println!("Votes Against: {}", proposal.votes_against);
-                println!("Quorum: {}%", proposal.quorum_percentage);
+                say!(out, "Votes For: {}", proposal.votes_for);
+                say!(out, "Votes Against: {}", proposal.votes_against);
+                say!(out, "Quorum: {}%", proposal.quorum_percentage);
+            }
+            
+            if passed {
+                let execution_id = executor.queue_upgrade(manager, &id, height)?;
+                if let Some(execution) = executor.get_execution(&execution_id) {
+                    say!(out, "\n⏳ Queued for execution: {}", execution_id);
+                    say!(out, "Executes at block: {}", execution.execute_at_height);
+                }
             }
             
             Ok(())
@@ -3421,13 +5851,12 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
                 .map_err(|e| Error::validation(format!("Invalid timestamp: {}", e)))?
                 .with_timezone(&chrono::Utc);
             
-            let mut manager = UPGRADE_MANAGER.lock().unwrap();
             manager.schedule_upgrade(id, activation_height, time)?;
             
-            println!("\n⏰ Upgrade Scheduled");
-            println!("Proposal ID: {}", proposal_id);
-            println!("Activation Height: {}", activation_height);
-            println!("Activation Time: {}", time.format("%Y-%m-%d %H:%M:%S UTC"));
+            say!(out, "\n⏰ Upgrade Scheduled");
+            say!(out, "Proposal ID: {}", proposal_id);
+            say!(out, "Activation Height: {}", activation_height);
+            say!(out, "Activation Time: {}", time.format("%Y-%m-%d %H:%M:%S UTC"));
             
             Ok(())
         }
@@ -3436,13 +5865,12 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
             let id = uuid::Uuid::parse_str(&proposal_id)
                 .map_err(|_| Error::validation("Invalid proposal ID"))?;
             
-            let mut manager = UPGRADE_MANAGER.lock().unwrap();
             manager.activate_upgrade(id, current_height)?;
             
-            println!("\n🚀 Upgrade Activated!");
-            println!("Proposal ID: {}", proposal_id);
-            println!("Block Height: {}", current_height);
-            println!("New Version: {}", manager.current_version());
+            say!(out, "\n🚀 Upgrade Activated!");
+            say!(out, "Proposal ID: {}", proposal_id);
+            say!(out, "Block Height: {}", current_height);
+            say!(out, "New Version: {}", manager.current_version());
             
             Ok(())
         }
@@ -3451,43 +5879,80 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
             let id = uuid::Uuid::parse_str(&proposal_id)
                 .map_err(|_| Error::validation("Invalid proposal ID"))?;
             
-            let mut manager = UPGRADE_MANAGER.lock().unwrap();
             manager.cancel_upgrade(id)?;
             
-            println!("\n❌ Upgrade Cancelled");
-            println!("Proposal ID: {}", proposal_id);
+            say!(out, "\n❌ Upgrade Cancelled");
+            say!(out, "Proposal ID: {}", proposal_id);
+            
+            Ok(())
+        }
+        
+        GovernanceCommand::ListExecutions => {
+            let pending = executor.pending();
+            
+            say!(out, "\n⏳ Queued Executions ({}), current block {}:", pending.len(), height);
+            
+            if pending.is_empty() {
+                say!(out, "No executions pending.");
+                return Ok(());
+            }
+            
+            for execution in pending {
+                say!(out, "\n{}", "-".repeat(80));
+                say!(out, "Execution ID: {}", execution.id);
+                say!(out, "Source: {:?}", execution.source);
+                say!(out, "Payload: {:?}", execution.payload);
+                say!(out, "Queued At: block {}", execution.queued_at_height);
+                say!(out, "Executes At: block {}", execution.execute_at_height);
+            }
+            
+            Ok(())
+        }
+        
+        GovernanceCommand::VetoExecution { execution_id, guardian } => {
+            let id = uuid::Uuid::parse_str(&execution_id)
+                .map_err(|_| Error::validation("Invalid execution ID"))?;
+            let guardian_id = UserId(uuid::Uuid::parse_str(&guardian)
+                .map_err(|_| Error::validation("Invalid guardian ID"))?);
+            
+            let vetoed = executor.veto(&id, &guardian_id, height)?;
+            
+            say!(out, "\n🛑 Veto Recorded");
+            say!(out, "Execution ID: {}", execution_id);
+            say!(out, "Guardian: {}", guardian);
+            if vetoed {
+                say!(out, "✅ Veto threshold reached; execution cancelled");
+            }
             
             Ok(())
         }
         
         GovernanceCommand::Version => {
-            let manager = UPGRADE_MANAGER.lock().unwrap();
-            println!("\n🔖 Current Protocol Version: {}", manager.current_version());
+            say!(out, "\n🔖 Current Protocol Version: {}", manager.current_version());
             Ok(())
         }
         
         GovernanceCommand::ForkHistory => {
-            let manager = UPGRADE_MANAGER.lock().unwrap();
             let forks = manager.get_fork_history();
             
-            println!("\n🌿 Fork History ({} forks):", forks.len());
+            say!(out, "\n🌿 Fork History ({} forks):", forks.len());
             
             if forks.is_empty() {
-                println!("No forks recorded yet.");
+                say!(out, "No forks recorded yet.");
                 return Ok(())
 ;
             }
             
             for fork in forks {
-                println!("\n{}", "-".repeat(80));
-                println!("Fork ID: {}", fork.fork_id);
-                println!("Parent Version: {}", fork.parent_version);
-                println!("Fork Version: {}", fork.fork_version);
-                println!("Fork Height: {}", fork.fork_height);
-                println!("Fork Time: {}", fork.fork_time.format("%Y-%m-%d %H:%M:%S UTC"));
-                println!("Supporting Nodes: {}", fork.supporting_nodes.len());
-                println!("Total Stake: {}", fork.total_stake);
-                println!("Canonical: {}", if fork.is_canonical { "Yes" } else { "No" });
+                say!(out, "\n{}", "-".repeat(80));
+                say!(out, "Fork ID: {}", fork.fork_id);
+                say!(out, "Parent Version: {}", fork.parent_version);
+                say!(out, "Fork Version: {}", fork.fork_version);
+                say!(out, "Fork Height: {}", fork.fork_height);
+                say!(out, "Fork Time: {}", fork.fork_time.format("%Y-%m-%d %H:%M:%S UTC"));
+                say!(out, "Supporting Nodes: {}", fork.supporting_nodes.len());
+                say!(out, "Total Stake: {}", fork.total_stake);
+                say!(out, "Canonical: {}", if fork.is_canonical { "Yes" } else { "No" });
             }
             
             Ok(())
@@ -3495,114 +5960,377 @@ async fn run_governance_command(action: GovernanceCommand) -> Result<()> {
         
         GovernanceCommand::CheckCompatibility { peer_version } => {
             let peer_ver = Version::parse(&peer_version)?;
-            let manager = UPGRADE_MANAGER.lock().unwrap();
             
             let compatible = manager.is_compatible_version(&peer_ver);
             
-            println!("\n🔍 Version Compatibility Check");
-            println!("Current Version: {}", manager.current_version());
-            println!("Peer Version: {}", peer_ver);
-            println!("Compatible: {}", if compatible { "✅ Yes" } else { "❌ No" });
+            say!(out, "\n🔍 Version Compatibility Check");
+            say!(out, "Current Version: {}", manager.current_version());
+            say!(out, "Peer Version: {}", peer_ver);
+            say!(out, "Compatible: {}", if compatible { "✅ Yes" } else { "❌ No" });
             
             if !compatible {
-                println!("\n⚠️  Warning: Incompatible versions may not be able to communicate!");
+                say!(out, "\n⚠️  Warning: Incompatible versions may not be able to communicate!");
             }
             
             Ok(())
         }
         
-        GovernanceCommand::Configure { hard_fork_threshold, total_stake } => {
-            let mut manager = UPGRADE_MANAGER.lock().unwrap();
+        GovernanceCommand::Configure { hard_fork_threshold, total_stake, guardians, veto_threshold } => {
             
             if let Some(threshold) = hard_fork_threshold {
                 manager.set_hard_fork_threshold(threshold)?;
-                println!("✅ Hard fork threshold set to {}%", threshold);
+                say!(out, "✅ Hard fork threshold set to {}%", threshold);
             }
             
             if let Some(stake) = total_stake {
                 manager.update_total_stake(stake);
-                println!("✅ Total stake updated to {}", stake);
+                say!(out, "✅ Total stake updated to {}", stake);
+            }
+            
+            if let Some(guardians) = guardians {
+                let guardian_ids = guardians
+                    .split(',')
+                    .map(|id| uuid::Uuid::parse_str(id.trim()).map(UserId))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|_| Error::validation("Invalid guardian ID"))?;
+                let count = guardian_ids.len();
+                executor.set_guardians(guardian_ids, veto_threshold)?;
+                say!(out, "✅ {} guardians set; {} vetoes cancel an execution", count, veto_threshold);
             }
             
-            println!("\n⚙️  Governance Configuration Updated");
+            say!(out, "\n⚙️  Governance Configuration Updated");
             
             Ok(())
         }
     }
 }
 
-/// Parse hex color string to Color
-fn parse_hex_color(hex: &str) -> Result<Color> {
-    let hex = hex.trim_start_matches('#');
-    
-    if hex.len() != 6 {
-        return Err(Error::validation("Color must be 6 hex digits (e.g., #FFFFFF)"));
+/// Parse hex color string to Color
+fn parse_hex_color(hex: &str) -> Result<Color> {
+    let hex = hex.trim_start_matches('#');
+    
+    if hex.len() != 6 {
+        return Err(Error::validation("Color must be 6 hex digits (e.g., #FFFFFF)"));
+    }
+    
+    let r = u8::from_str_radix(&hex[0..2], 16)
+        .map_err(|_| Error::validation("Invalid hex color"))?;
+    let g = u8::from_str_radix(&hex[2..4], 16)
+        .map_err(|_| Error::validation("Invalid hex color"))?;
+    let b = u8::from_str_radix(&hex[4..6], 16)
+        .map_err(|_| Error::validation("Invalid hex color"))?;
+    
+    Ok(Color::new(r, g, b))
+}
+
+/// Run device pairing commands against the running user node
+async fn run_device_command(config: Config, action: DeviceCommand) -> Result<()> {
+    if forward_to_node(&config, "device.execute", &action).await? {
+        return Ok(());
+    }
+    Err(Error::NotFound(
+        "Device pairing runs inside a user node; start one with `dchat user --daemon`".to_string(),
+    ))
+}
+
+async fn apply_device_command(
+    messaging: &NodeMessaging,
+    peer_id: &PeerId,
+    action: DeviceCommand,
+    out: &mut CommandOutput,
+) -> Result<()> {
+    match action {
+        DeviceCommand::Pair => {
+            let code = messaging.devices.lock().unwrap().open_pairing(peer_id.to_string())?;
+            say!(out, "📱 Pairing code: {}", code.display_code());
+            say!(out, "   QR payload:   {}", code.to_qr_payload());
+            say!(out, "   Valid for {} seconds; enter it on the new device with `dchat device join`", dchat::identity::pairing::PAIRING_CODE_TTL_SECONDS);
+        }
+        DeviceCommand::Join { code, user_id, name, device_type } => {
+            let code = if code.starts_with("dchat-pair:") {
+                PairingCode::from_qr_payload(&code)?
+            } else {
+                let user_id = user_id
+                    .ok_or_else(|| Error::validation("--user-id is required with a typed pairing code"))?;
+                let user_id = UserId(Uuid::parse_str(&user_id).map_err(|_| Error::validation("Invalid user ID"))?);
+                PairingCode::typed(user_id, &code)?
+            };
+            let device_type = match device_type.to_lowercase().as_str() {
+                "desktop" => DeviceType::Desktop,
+                "mobile" => DeviceType::Mobile,
+                "web" => DeviceType::Web,
+                "server" => DeviceType::Server,
+                _ => DeviceType::Other(device_type),
+            };
+
+            let topic = device_pairing_topic(&code.user_id);
+            messaging.network.lock().await.subscribe_to_channel(&topic)?;
+            let identity = code.user_id.clone();
+            let request = messaging.devices.lock().unwrap().start_join(code, name, device_type)?;
+            messaging.publish_pairing(&topic, &request).await?;
+            say!(out, "📱 Pairing request sent to {}; the node logs when the primary answers", identity);
+        }
+        DeviceCommand::List => {
+            let devices = messaging.devices.lock().unwrap();
+            let identity_key = devices.identity.public_key().to_core_public_key();
+            if let Some(paired) = &devices.paired {
+                say!(out, "This node is device {} of {}", paired.certificate.device_id, paired.certificate.user_id);
+            }
+            if devices.issued.is_empty() {
+                say!(out, "No devices paired to this identity");
+            }
+            for certificate in devices.issued.values() {
+                let status = match devices.registry.verify_certificate(certificate, &identity_key) {
+                    Ok(()) => "trusted",
+                    Err(_) => "revoked",
+                };
+                say!(
+                    out,
+                    "  {} {} ({:?}, index {}) - {}",
+                    certificate.device_id, certificate.device_name, certificate.device_type, certificate.device_index, status
+                );
+            }
+        }
+        DeviceCommand::Revoke { device_id } => {
+            let (revocation, rotation) = messaging.devices.lock().unwrap().revoke(&device_id)?;
+            messaging.save_devices().await?;
+            messaging.publish_revocation(&revocation).await?;
+            messaging.publish_pairing(&device_pairing_topic(&messaging.sender), &rotation).await?;
+            say!(out, "📵 Device {} revoked; the remaining devices were sent a new sync key", device_id);
+        }
+    }
+    Ok(())
+}
+
+/// Run token and tokenomics commands
+async fn run_token_command(config: Config, action: TokenCommand) -> Result<()> {
+    if forward_to_node(&config, "token.execute", &action).await? {
+        return Ok(());
     }
     
-    let r = u8::from_str_radix(&hex[0..2], 16)
-        .map_err(|_| Error::validation("Invalid hex color"))?;
-    let g = u8::from_str_radix(&hex[2..4], 16)
-        .map_err(|_| Error::validation("Invalid hex color"))?;
-    let b = u8::from_str_radix(&hex[4..6], 16)
-        .map_err(|_| Error::validation("Invalid hex color"))?;
-    
-    Ok(Color::new(r, g, b))
+    let database = open_state_database(&config).await?;
+    let mut currency = load_currency_chain(&database).await?;
+    let mut out = CommandOutput::default();
+    let result = apply_token_command(&currency, action, &mut out);
+    print!("{}", out.0);
+    result?;
+    database.save_state(&mut currency).await?;
+    Ok(())
 }
 
-/// Run token and tokenomics commands
-async fn run_token_command(action: TokenCommand) -> Result<()> {
-    use dchat::blockchain::{TokenomicsManager, TokenSupplyConfig, MintReason, BurnReason, RecipientType};
-    use std::sync::{Arc, Mutex};
-    
-    // In production, this would be stored in persistent storage
-    lazy_static::lazy_static! {
-        static ref TOKENOMICS: Arc<Mutex<TokenomicsManager>> = {
-            let config = TokenSupplyConfig::default();
-            Arc::new(Mutex::new(TokenomicsManager::new(config)))
-        };
-        static ref CURRENCY_CLIENT: Arc<Mutex<CurrencyChainClient>> = {
-            let _tokenomics = TOKENOMICS.lock().unwrap();
+/// Load the currency chain, whose tokenomics is the one token ledger
+///
+/// A chain that has never been saved starts from tokenomics saved on its own
+/// by an older version, so existing grants and supply carry over. Tips are
+/// paid into the fee pool, which each block shares with the insurance fund,
+/// and governance pays treasury transfers from the treasury account.
+async fn load_currency_chain(database: &Database) -> Result<dchat_storage::Persisted<std::sync::Arc<CurrencyChainClient>>> {
+    use dchat::blockchain::{TokenomicsManager, TokenSupplyConfig};
+    
+    let tokenomics = database
+        .load_state(TOKENOMICS_STATE, TOKENOMICS_STATE_VERSION, || {
+            TokenomicsManager::new(TokenSupplyConfig::default())
+        })
+        .await?
+        .into_inner();
+    let mut currency = database
+        .load_state(CURRENCY_STATE, CURRENCY_STATE_VERSION, || {
             let config = CurrencyChainConfig::default();
-            // Create a new tokenomics instance for currency client
-            let tokenomics_for_currency = Arc::new(TokenomicsManager::new(TokenSupplyConfig::default()));
-            Arc::new(Mutex::new(CurrencyChainClient::with_tokenomics(config, tokenomics_for_currency)))
+            std::sync::Arc::new(CurrencyChainClient::with_tokenomics(config, std::sync::Arc::new(tokenomics)))
+        })
+        .await?;
+    
+    for domain in [FEE_POOL_ACCOUNT_DOMAIN, INSURANCE_ACCOUNT_DOMAIN, TREASURY_ACCOUNT_DOMAIN] {
+        let account = module_account_id(domain);
+        if !currency.is_module_account(&account) {
+            currency.create_module_account(&account, 0)?;
+        }
+    }
+    currency.set_fee_recipient(module_account_id(FEE_POOL_ACCOUNT_DOMAIN));
+    database.save_state(&mut currency).await?;
+    Ok(currency)
+}
+
+/// Timelocked queue of passed proposals, with the config fields that
+/// executed proposals have set
+#[derive(Default, Serialize, Deserialize)]
+struct GovernanceExecution {
+    executor: dchat::governance::ProposalExecutor,
+    /// Values by JSON pointer, applied over the node's config file
+    config_overrides: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+/// `config` with the fields governance has set
+fn governed_config(config: &Config, overrides: &std::collections::BTreeMap<String, serde_json::Value>) -> Result<Config> {
+    let mut document = serde_json::to_value(config)?;
+    for (key, value) in overrides {
+        if let Some(field) = document.pointer_mut(key) {
+            *field = value.clone();
+        }
+    }
+    serde_json::from_value(document).map_err(|e| Error::Config(format!("Invalid governed config: {}", e)))
+}
+
+/// Execute the passed proposals due at the currency chain's height
+///
+/// Treasury transfers are paid from the treasury module account. A payload
+/// that fails is logged and marked failed by the executor; it does not fail
+/// the block.
+fn execute_due_proposals(
+    execution: &mut GovernanceExecution,
+    upgrades: &mut dchat::governance::UpgradeManager,
+    moderation: &mut dchat::governance::ModerationManager,
+    config: &Config,
+    currency: &CurrencyChainClient,
+) -> Result<()> {
+    use dchat::governance::{ExecutionContext, ExecutionStatus, ProposalPayload};
+    
+    let tokenomics = currency.get_tokenomics()
+        .ok_or_else(|| Error::chain("Currency chain has no tokenomics manager"))?;
+    let treasury = module_account_id(TREASURY_ACCOUNT_DOMAIN);
+    let mut config = governed_config(config, &execution.config_overrides)?;
+    let mut ctx = ExecutionContext {
+        config: &mut config,
+        tokenomics: &tokenomics,
+        currency,
+        treasury: &treasury,
+        moderation,
+        upgrades,
+    };
+    
+    for execution_id in execution.executor.execute_due(currency.get_current_block(), &mut ctx) {
+        let Some(executed) = execution.executor.get_execution(&execution_id) else {
+            continue;
         };
+        match (&executed.status, &executed.payload) {
+            (ExecutionStatus::Failed(reason), _) => {
+                warn!("⚠️  Proposal execution {} failed: {}", execution_id, reason);
+            }
+            (_, Some(ProposalPayload::ConfigChange { key, value })) => {
+                info!("🏛️  Executed {}: config {} = {}", execution_id, key, value);
+                execution.config_overrides.insert(key.clone(), value.clone());
+            }
+            _ => info!("🏛️  Executed {}", execution_id),
+        }
+    }
+    Ok(())
+}
+
+/// ID a gossip channel's access policy is kept under, the same on every node
+fn gossip_channel_id(channel: &str) -> dchat_core::types::ChannelId {
+    let digest = dchat_crypto::hash(format!("dchat-channel-v1:{}", channel).as_bytes());
+    dchat_core::types::ChannelId(uuid::Builder::from_custom_bytes(digest[..16].try_into().expect("16 bytes")).into_uuid())
+}
+
+/// Make `user` a member of a gated channel; channels without a policy are
+/// open to everyone and keep no members
+fn join_channel(
+    access: &mut dchat::messaging::ChannelAccessManager,
+    ledger: &dyn dchat::messaging::TokenLedger,
+    user: &UserId,
+    channel: &str,
+) -> Result<()> {
+    let channel_id = gossip_channel_id(channel);
+    if access.get_policy(&channel_id).is_none() {
+        return Ok(());
+    }
+    access.grant_access(user.clone(), channel_id, ledger)
+}
+
+/// Whether `user` may post to a channel: any channel without a policy, or a
+/// gated one it joined and still qualifies for
+fn is_channel_member(
+    access: &dchat::messaging::ChannelAccessManager,
+    ledger: &dyn dchat::messaging::TokenLedger,
+    user: &UserId,
+    channel: &str,
+) -> Result<bool> {
+    let channel_id = gossip_channel_id(channel);
+    if access.get_policy(&channel_id).is_none() {
+        return Ok(true);
+    }
+    access.is_member(user, &channel_id, ledger)
+}
+
+/// ID of a protocol account, the same on every node
+fn module_account_id(domain: &[u8]) -> UserId {
+    let digest = dchat_crypto::hash(domain);
+    UserId(uuid::Builder::from_custom_bytes(digest[..16].try_into().expect("16 bytes")).into_uuid())
+}
+
+/// Move the fees a block took into the fee pool out of it: the insurance
+/// fund's share to the fund, the rest burned
+fn collect_block_fees(insurance: &mut dchat::chain::InsuranceFund, currency: &CurrencyChainClient) -> Result<()> {
+    use dchat::chain::PremiumSource;
+    
+    let fee_pool = module_account_id(FEE_POOL_ACCOUNT_DOMAIN);
+    let fees = currency.get_balance(&fee_pool)?;
+    if fees == 0 {
+        return Ok(());
+    }
+    let source = PremiumSource::Block { height: currency.get_current_block() };
+    insurance.collect_premium(currency, &fee_pool, fees, source)?;
+    let rest = currency.get_balance(&fee_pool)?;
+    currency.module_transfer_and_burn(&fee_pool, &[], rest)?;
+    Ok(())
+}
+
+/// Pay every approved insurance claim the fund can cover
+///
+/// A claim that cannot be paid yet, e.g. while the fund is below its
+/// reserve, stays approved and is retried next block.
+fn pay_insurance_claims(insurance: &mut dchat::chain::InsuranceFund, currency: &CurrencyChainClient) {
+    let approved: Vec<Uuid> = insurance.get_approved_claims().iter().map(|claim| claim.id).collect();
+    for claim_id in approved {
+        match insurance.execute_payout(claim_id, currency) {
+            Ok(tx_id) => info!("🛡️  Paid insurance claim {} in {}", claim_id, tx_id),
+            Err(e) => tracing::debug!("Insurance claim {} not paid yet: {}", claim_id, e),
+        }
     }
+}
+
+fn apply_token_command(
+    currency_client: &CurrencyChainClient,
+    action: TokenCommand,
+    out: &mut CommandOutput,
+) -> Result<()> {
+    use dchat::blockchain::{MintReason, BurnReason, RecipientType};
+    
+    let manager = currency_client.get_tokenomics()
+        .ok_or_else(|| Error::chain("Currency chain has no tokenomics manager"))?;
     
     match action {
         TokenCommand::Stats => {
-            let manager = TOKENOMICS.lock().unwrap();
             let stats = manager.get_statistics();
             
-            println!("\n💰 Token Supply Statistics");
-            println!("{}", "=".repeat(60));
-            println!("Circulating Supply: {:>20}", format_tokens(stats.circulating_supply));
-            println!("Total Minted:       {:>20}", format_tokens(stats.total_minted));
-            println!("Total Burned:       {:>20}", format_tokens(stats.total_burned));
-            println!("Effective Supply:   {:>20}", format_tokens(stats.effective_supply));
+            say!(out, "\n💰 Token Supply Statistics");
+            say!(out, "{}", "=".repeat(60));
+            say!(out, "Circulating Supply: {:>20}", format_tokens(stats.circulating_supply));
+            say!(out, "Total Minted:       {:>20}", format_tokens(stats.total_minted));
+            say!(out, "Total Burned:       {:>20}", format_tokens(stats.total_burned));
+            say!(out, "Effective Supply:   {:>20}", format_tokens(stats.effective_supply));
             
             if let Some(max) = stats.max_supply {
                 let percentage = (stats.circulating_supply as f64 / max as f64) * 100.0;
-                println!("Max Supply:         {:>20} ({:.2}% issued)", format_tokens(max), percentage);
+                say!(out, "Max Supply:         {:>20} ({:.2}% issued)", format_tokens(max), percentage);
             } else {
-                println!("Max Supply:         {:>20}", "Unlimited");
+                say!(out, "Max Supply:         {:>20}", "Unlimited");
             }
             
-            println!("\n📊 Economics");
-            println!("{}", "=".repeat(60));
-            println!("Inflation Rate:     {:>20}", format!("{}%", stats.inflation_rate_bps as f64 / 100.0));
-            println!("Burn Rate:          {:>20}", format!("{}%", stats.burn_rate_bps as f64 / 100.0));
+            say!(out, "\n📊 Economics");
+            say!(out, "{}", "=".repeat(60));
+            say!(out, "Inflation Rate:     {:>20}", format!("{}%", stats.inflation_rate_bps as f64 / 100.0));
+            say!(out, "Burn Rate:          {:>20}", format!("{}%", stats.burn_rate_bps as f64 / 100.0));
             
-            println!("\n🏪 Marketplace Liquidity");
-            println!("{}", "=".repeat(60));
-            println!("Total Pool Liquidity: {:>18}", format_tokens(stats.total_pool_liquidity));
-            println!("Active Pools:         {:>18}", stats.active_pools);
+            say!(out, "\n🏪 Marketplace Liquidity");
+            say!(out, "{}", "=".repeat(60));
+            say!(out, "Total Pool Liquidity: {:>18}", format_tokens(stats.total_pool_liquidity));
+            say!(out, "Active Pools:         {:>18}", stats.active_pools);
             
             Ok(())
         }
         
         TokenCommand::Mint { amount, reason, recipient } => {
-            let manager = TOKENOMICS.lock().unwrap();
             
             let mint_reason = match reason.to_lowercase().as_str() {
                 "genesis" => MintReason::Genesis,
@@ -3623,16 +6351,15 @@ async fn run_token_command(action: TokenCommand) -> Result<()> {
             
             let mint_id = manager.mint_tokens(amount, mint_reason, recipient_id)?;
             
-            println!("\n✅ Tokens Minted Successfully");
-            println!("Mint ID: {}", mint_id);
-            println!("Amount: {}", format_tokens(amount));
-            println!("New Supply: {}", format_tokens(manager.get_circulating_supply()));
+            say!(out, "\n✅ Tokens Minted Successfully");
+            say!(out, "Mint ID: {}", mint_id);
+            say!(out, "Amount: {}", format_tokens(amount));
+            say!(out, "New Supply: {}", format_tokens(manager.get_circulating_supply()));
             
             Ok(())
         }
         
         TokenCommand::Burn { user_id, amount, reason } => {
-            let manager = TOKENOMICS.lock().unwrap();
             
             let burn_reason = match reason.to_lowercase().as_str() {
                 "fee" | "transaction-fee" => BurnReason::TransactionFee,
@@ -3647,38 +6374,36 @@ async fn run_token_command(action: TokenCommand) -> Result<()> {
             
             let burn_id = manager.burn_tokens(amount, burn_reason, user)?;
             
-            println!("\n🔥 Tokens Burned Successfully");
-            println!("Burn ID: {}", burn_id);
-            println!("Amount: {}", format_tokens(amount));
-            println!("New Supply: {}", format_tokens(manager.get_circulating_supply()));
-            println!("Total Burned: {}", format_tokens(manager.get_total_burned()));
+            say!(out, "\n🔥 Tokens Burned Successfully");
+            say!(out, "Burn ID: {}", burn_id);
+            say!(out, "Amount: {}", format_tokens(amount));
+            say!(out, "New Supply: {}", format_tokens(manager.get_circulating_supply()));
+            say!(out, "Total Burned: {}", format_tokens(manager.get_total_burned()));
             
             Ok(())
         }
         
         TokenCommand::CreatePool { name, initial_amount } => {
-            let manager = TOKENOMICS.lock().unwrap();
             let pool_id = manager.create_liquidity_pool(name.clone(), initial_amount)?;
             
-            println!("\n🏊 Liquidity Pool Created");
-            println!("Pool ID: {}", pool_id);
-            println!("Name: {}", name);
-            println!("Initial Tokens: {}", format_tokens(initial_amount));
+            say!(out, "\n🏊 Liquidity Pool Created");
+            say!(out, "Pool ID: {}", pool_id);
+            say!(out, "Name: {}", name);
+            say!(out, "Initial Tokens: {}", format_tokens(initial_amount));
             
             Ok(())
         }
         
         TokenCommand::ListPools => {
-            let manager = TOKENOMICS.lock().unwrap();
             let pools = manager.get_all_pools();
             
-            println!("\n🏪 Marketplace Liquidity Pools ({}):", pools.len());
-            println!("{}", "=".repeat(100));
-            println!("{:<40} {:<20} {:<20} {:<20}", "Name", "Total", "Available", "Reserved");
-            println!("{}", "=".repeat(100));
+            say!(out, "\n🏪 Marketplace Liquidity Pools ({}):", pools.len());
+            say!(out, "{}", "=".repeat(100));
+            say!(out, "{:<40} {:<20} {:<20} {:<20}", "Name", "Total", "Available", "Reserved");
+            say!(out, "{}", "=".repeat(100));
             
             for pool in pools {
-                println!("{:<40} {:>19} {:>19} {:>19}",
+                say!(out, "{:<40} {:>19} {:>19} {:>19}",
                     pool.name,
                     format_tokens(pool.total_tokens),
                     format_tokens(pool.available_tokens),
@@ -3690,62 +6415,59 @@ async fn run_token_command(action: TokenCommand) -> Result<()> {
         }
         
         TokenCommand::PoolInfo { pool_id } => {
-            let manager = TOKENOMICS.lock().unwrap();
             let id = Uuid::parse_str(&pool_id)
                 .map_err(|_| Error::validation("Invalid pool ID"))?;
             
             let pool = manager.get_pool(&id)
                 .ok_or_else(|| Error::NotFound("Pool not found".to_string()))?;
             
-            println!("\n🏊 Pool Details: {}", pool.name);
-            println!("{}", "=".repeat(60));
-            println!("Pool ID: {}", pool.id);
-            println!("Total Tokens: {}", format_tokens(pool.total_tokens));
-            println!("Available: {}", format_tokens(pool.available_tokens));
-            println!("Reserved: {}", format_tokens(pool.reserved_tokens));
-            println!("Pending Allocations: {}", format_tokens(pool.pending_allocations));
-            println!("Created: {}", pool.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
-            println!("Last Replenish: {}", pool.last_replenish.format("%Y-%m-%d %H:%M:%S UTC"));
+            say!(out, "\n🏊 Pool Details: {}", pool.name);
+            say!(out, "{}", "=".repeat(60));
+            say!(out, "Pool ID: {}", pool.id);
+            say!(out, "Total Tokens: {}", format_tokens(pool.total_tokens));
+            say!(out, "Available: {}", format_tokens(pool.available_tokens));
+            say!(out, "Reserved: {}", format_tokens(pool.reserved_tokens));
+            say!(out, "Pending Allocations: {}", format_tokens(pool.pending_allocations));
+            say!(out, "Created: {}", pool.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+            say!(out, "Last Replenish: {}", pool.last_replenish.format("%Y-%m-%d %H:%M:%S UTC"));
             
             let utilization = if pool.total_tokens > 0 {
                 ((pool.reserved_tokens + pool.pending_allocations) as f64 / pool.total_tokens as f64) * 100.0
             } else {
                 0.0
             };
-            println!("Utilization: {:.2}%", utilization);
+            say!(out, "Utilization: {:.2}%", utilization);
             
             Ok(())
         }
         
         TokenCommand::ReplenishPool { pool_id, amount } => {
-            let manager = TOKENOMICS.lock().unwrap();
             let id = Uuid::parse_str(&pool_id)
                 .map_err(|_| Error::validation("Invalid pool ID"))?;
             
             manager.replenish_pool(&id, amount)?;
             
-            println!("\n💧 Pool Replenished");
-            println!("Pool ID: {}", pool_id);
-            println!("Amount Added: {}", format_tokens(amount));
+            say!(out, "\n💧 Pool Replenished");
+            say!(out, "Pool ID: {}", pool_id);
+            say!(out, "Amount Added: {}", format_tokens(amount));
             
             Ok(())
         }
         
         TokenCommand::MintHistory { limit } => {
-            let manager = TOKENOMICS.lock().unwrap();
             let history = manager.get_mint_history(limit);
             
-            println!("\n📜 Mint History (last {}):", limit);
-            println!("{}", "=".repeat(120));
-            println!("{:<38} {:<20} {:<20} {:<25} {:<15}", "Event ID", "Amount", "Reason", "Recipient", "Block");
-            println!("{}", "=".repeat(120));
+            say!(out, "\n📜 Mint History (last {}):", limit);
+            say!(out, "{}", "=".repeat(120));
+            say!(out, "{:<38} {:<20} {:<20} {:<25} {:<15}", "Event ID", "Amount", "Reason", "Recipient", "Block");
+            say!(out, "{}", "=".repeat(120));
             
             for event in history {
                 let recipient_str = event.recipient
                     .map(|u| u.0.to_string()[..8].to_string())
                     .unwrap_or_else(|| "N/A".to_string());
                 
-                println!("{:<38} {:>19} {:<20} {:<25} {:>14}",
+                say!(out, "{:<38} {:>19} {:<20} {:<25} {:>14}",
                     event.id.to_string(),
                     format_tokens(event.amount),
                     format!("{:?}", event.reason),
@@ -3758,18 +6480,17 @@ async fn run_token_command(action: TokenCommand) -> Result<()> {
         }
         
         TokenCommand::BurnHistory { limit } => {
-            let manager = TOKENOMICS.lock().unwrap();
             let history = manager.get_burn_history(limit);
             
-            println!("\n🔥 Burn History (last {}):", limit);
-            println!("{}", "=".repeat(120));
-            println!("{:<38} {:<20} {:<20} {:<25} {:<15}", "Event ID", "Amount", "Reason", "Burner", "Block");
-            println!("{}", "=".repeat(120));
+            say!(out, "\n🔥 Burn History (last {}):", limit);
+            say!(out, "{}", "=".repeat(120));
+            say!(out, "{:<38} {:<20} {:<20} {:<25} {:<15}", "Event ID", "Amount", "Reason", "Burner", "Block");
+            say!(out, "{}", "=".repeat(120));
             
             for event in history {
                 let burner_str = event.burner.0.to_string()[..8].to_string();
                 
-                println!("{:<38} {:>19} {:<20} {:<25} {:>14}",
+                say!(out, "{:<38} {:>19} {:<20} {:<25} {:>14}",
                     event.id.to_string(),
                     format_tokens(event.amount),
                     format!("{:?}", event.reason),
@@ -3782,7 +6503,6 @@ async fn run_token_command(action: TokenCommand) -> Result<()> {
         }
         
         TokenCommand::CreateSchedule { recipient_type, amount, interval_blocks, duration_blocks } => {
-            let manager = TOKENOMICS.lock().unwrap();
             
             let recip_type = match recipient_type.to_lowercase().as_str() {
                 "validators" => RecipientType::Validators,
@@ -3800,87 +6520,450 @@ async fn run_token_command(action: TokenCommand) -> Result<()> {
                 duration_blocks,
             )?;
             
-            println!("\n📅 Distribution Schedule Created");
-            println!("Schedule ID: {}", schedule_id);
-            println!("Recipient Type: {}", recipient_type);
-            println!("Amount per Interval: {}", format_tokens(amount));
-            println!("Interval: {} blocks", interval_blocks);
+            say!(out, "\n📅 Distribution Schedule Created");
+            say!(out, "Schedule ID: {}", schedule_id);
+            say!(out, "Recipient Type: {}", recipient_type);
+            say!(out, "Amount per Interval: {}", format_tokens(amount));
+            say!(out, "Interval: {} blocks", interval_blocks);
             if let Some(duration) = duration_blocks {
-                println!("Duration: {} blocks", duration);
+                say!(out, "Duration: {} blocks", duration);
             } else {
-                println!("Duration: Indefinite");
+                say!(out, "Duration: Indefinite");
             }
             
             Ok(())
         }
         
         TokenCommand::ProcessInflation => {
-            let manager = TOKENOMICS.lock().unwrap();
-            let mint_ids = manager.process_block_inflation()?;
+            let mint_ids = currency_client.process_block_inflation()?;
+            
+            say!(out, "\n⚡ Block Inflation Processed");
+            say!(out, "Minted {} events", mint_ids.len());
+            say!(out, "Current Block: {}", manager.get_current_block());
+            say!(out, "Current Supply: {}", format_tokens(manager.get_circulating_supply()));
+            
+            Ok(())
+        }
+        
+        TokenCommand::CreateGrant { beneficiary, grantor, amount, schedule, start, cliff, end, milestones, revocable } => {
+            use dchat::blockchain::{Milestone, ReleaseSchedule};
+            
+            let beneficiary_id = UserId(Uuid::parse_str(&beneficiary)
+                .map_err(|_| Error::validation("Invalid beneficiary ID"))?);
+            let grantor_id = UserId(Uuid::parse_str(&grantor)
+                .map_err(|_| Error::validation("Invalid grantor ID"))?);
+            
+            let release = match schedule.to_lowercase().as_str() {
+                "cliff" => ReleaseSchedule::Cliff {
+                    at: cliff.ok_or_else(|| Error::validation("Cliff grants need --cliff"))?,
+                },
+                "linear" => {
+                    let start = start.ok_or_else(|| Error::validation("Linear grants need --start"))?;
+                    let end = end.ok_or_else(|| Error::validation("Linear grants need --end"))?;
+                    ReleaseSchedule::Linear { start, cliff: cliff.unwrap_or(start), end }
+                }
+                "milestones" => ReleaseSchedule::Milestones(milestones.iter().map(|milestone| {
+                    let (amount, description) = milestone.split_once(':')
+                        .ok_or_else(|| Error::validation(format!("Invalid milestone: {}", milestone)))?;
+                    let amount = amount.parse::<u64>()
+                        .map_err(|_| Error::validation(format!("Invalid milestone amount: {}", amount)))?;
+                    Ok(Milestone { description: description.to_string(), amount, reached_at: None })
+                }).collect::<Result<Vec<_>>>()?),
+                _ => return Err(Error::validation(format!("Unknown schedule: {}", schedule))),
+            };
+            
+            let grant_id = manager.create_vesting_grant(&beneficiary_id, &grantor_id, amount, release, revocable)?;
+            
+            say!(out, "\n📜 Vesting Grant Created");
+            say!(out, "Grant ID: {}", grant_id);
+            say!(out, "Beneficiary: {}", beneficiary);
+            say!(out, "Amount: {}", format_tokens(amount));
+            say!(out, "Schedule: {}", schedule);
+            say!(out, "Revocable: {}", revocable);
+            
+            Ok(())
+        }
+        
+        TokenCommand::Claim { user_id, grant_id, key_file, max_fee, tip } => {
+            let id = UserId(Uuid::parse_str(&user_id)
+                .map_err(|_| Error::validation("Invalid user ID"))?);
+            let grant_id = grant_id
+                .map(|g| Uuid::parse_str(&g).map_err(|_| Error::validation("Invalid grant ID")))
+                .transpose()?;
+            
+            let payload = TransactionPayload::ClaimVested { grant_id };
+            let tx_id = submit_signed(currency_client, &id, &key_file, max_fee, tip, payload)?;
+            let claimed = currency_client.get_transaction(&tx_id)?.map(|tx| tx.received).unwrap_or(0);
+            
+            say!(out, "\n🔓 Vested Tokens Claimed");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "User ID: {}", user_id);
+            say!(out, "Amount: {}", format_tokens(claimed));
+            say!(out, "Balance: {}", format_tokens(currency_client.get_balance(&id)?));
+            say!(out, "Block: {}", currency_client.get_current_block());
+            
+            Ok(())
+        }
+        
+        TokenCommand::RevokeGrant { grant_id, grantor } => {
+            let grant_id = Uuid::parse_str(&grant_id)
+                .map_err(|_| Error::validation("Invalid grant ID"))?;
+            let grantor_id = UserId(Uuid::parse_str(&grantor)
+                .map_err(|_| Error::validation("Invalid grantor ID"))?);
+            
+            let cancelled = manager.revoke_vesting_grant(&grant_id, &grantor_id)?;
+            
+            say!(out, "\n⛔ Vesting Grant Revoked");
+            say!(out, "Grant ID: {}", grant_id);
+            say!(out, "Cancelled: {}", format_tokens(cancelled));
+            
+            Ok(())
+        }
+        
+        TokenCommand::ReachMilestone { grant_id, grantor, index } => {
+            let grant_id = Uuid::parse_str(&grant_id)
+                .map_err(|_| Error::validation("Invalid grant ID"))?;
+            let grantor_id = UserId(Uuid::parse_str(&grantor)
+                .map_err(|_| Error::validation("Invalid grantor ID"))?);
+            
+            manager.reach_vesting_milestone(&grant_id, &grantor_id, index)?;
             
-            println!("\n⚡ Block Inflation Processed");
-            println!("Minted {} events", mint_ids.len());
-            println!("Current Block: {}", manager.get_current_block());
-            println!("Current Supply: {}", format_tokens(manager.get_circulating_supply()));
+            say!(out, "\n🏁 Milestone {} reached for grant {}", index, grant_id);
             
             Ok(())
         }
         
-        TokenCommand::Transfer { from, to, amount } => {
-            let currency_client = CURRENCY_CLIENT.lock().unwrap();
+        TokenCommand::Grants { user_id } => {
+            let id = UserId(Uuid::parse_str(&user_id)
+                .map_err(|_| Error::validation("Invalid user ID"))?);
+            let height = manager.get_current_block();
+            let grants = manager.get_vesting_grants(&id);
+            
+            say!(out, "\n📜 Vesting Grants ({}) at block {}", grants.len(), height);
+            say!(out, "{}", "=".repeat(60));
+            for grant in grants {
+                say!(out, "\nGrant ID: {}", grant.id);
+                say!(out, "  Total: {}", format_tokens(grant.total));
+                say!(out, "  Vested: {}", format_tokens(grant.vested_at(height)));
+                say!(out, "  Claimed: {}", format_tokens(grant.claimed));
+                say!(out, "  Claimable: {}", format_tokens(grant.claimable(height)));
+                if let Some(revoked) = grant.revoked_at {
+                    say!(out, "  Revoked at block {}", revoked);
+                }
+            }
             
+            Ok(())
+        }
+        
+        TokenCommand::Transfer { from, to, amount, key_file, max_fee, tip } => {
             let from_id = UserId(Uuid::parse_str(&from)
                 .map_err(|_| Error::validation("Invalid from user ID"))?);
             let to_id = UserId(Uuid::parse_str(&to)
                 .map_err(|_| Error::validation("Invalid to user ID"))?);
             
-            // Ensure wallets exist
-            if currency_client.get_wallet(&from_id)?.is_none() {
-                currency_client.create_wallet(&from_id, 0)?;
-            }
             if currency_client.get_wallet(&to_id)?.is_none() {
                 currency_client.create_wallet(&to_id, 0)?;
             }
             
-            let tx_id = currency_client.transfer(&from_id, &to_id, amount)?;
+            let payload = TransactionPayload::Transfer { to: to_id.clone(), amount };
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
             
-            println!("\n💸 Transfer Completed");
-            println!("Transaction ID: {}", tx_id);
-            println!("From: {}", from);
-            println!("To: {}", to);
-            println!("Amount: {}", format_tokens(amount));
+            say!(out, "\n💸 Transfer Completed");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "From: {}", from);
+            say!(out, "To: {}", to);
+            say!(out, "Amount: {}", format_tokens(amount));
             
             let from_balance = currency_client.get_balance(&from_id)?;
             let to_balance = currency_client.get_balance(&to_id)?;
-            println!("\nNew Balances:");
-            println!("  From: {}", format_tokens(from_balance));
-            println!("  To: {}", format_tokens(to_balance));
+            say!(out, "\nNew Balances:");
+            say!(out, "  From: {}", format_tokens(from_balance));
+            say!(out, "  To: {}", format_tokens(to_balance));
             
             Ok(())
         }
         
-        TokenCommand::Balance { user_id } => {
-            let currency_client = CURRENCY_CLIENT.lock().unwrap();
+        TokenCommand::Swap { from, token, direction, amount, min_out, slippage_bps, key_file, max_fee, tip } => {
+            let from_id = UserId(Uuid::parse_str(&from)
+                .map_err(|_| Error::validation("Invalid from user ID"))?);
+            let direction = match direction.as_str() {
+                "buy" => SwapDirection::NativeToToken,
+                "sell" => SwapDirection::TokenToNative,
+                _ => return Err(Error::validation("Direction must be buy or sell")),
+            };
+            if slippage_bps > 10_000 {
+                return Err(Error::validation("Slippage tolerance cannot exceed 10000 bps"));
+            }
+            
+            let quoted = currency_client.quote_swap(&token, direction, amount)?;
+            let min_out = min_out.unwrap_or(quoted - quoted * slippage_bps as u64 / 10_000);
+            let payload = TransactionPayload::Swap { token: token.clone(), direction, amount_in: amount, min_out };
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
+            let received = currency_client.get_transaction(&tx_id)?.map(|tx| tx.received).unwrap_or(0);
+            
+            let (paid, got) = match direction {
+                SwapDirection::NativeToToken => (format_tokens(amount), format!("{} {}", received, token)),
+                SwapDirection::TokenToNative => (format!("{} {}", amount, token), format_tokens(received)),
+            };
+            say!(out, "\n🔄 Swap Completed");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "Paid: {}", paid);
+            say!(out, "Received: {} (minimum {})", got, min_out);
+            
+            Ok(())
+        }
+        
+        TokenCommand::AddLiquidity { from, token, native_amount, token_amount, min_shares, key_file, max_fee, tip } => {
+            let from_id = UserId(Uuid::parse_str(&from)
+                .map_err(|_| Error::validation("Invalid from user ID"))?);
+            let shares_before = currency_client.get_pool(&token).map(|p| p.shares_of(&from_id)).unwrap_or(0);
+            
+            let payload = TransactionPayload::AddLiquidity { token: token.clone(), native_amount, token_amount, min_shares };
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
+            let pool = currency_client.get_pool(&token)
+                .ok_or_else(|| Error::NotFound(format!("No liquidity pool for {}", token)))?;
+            
+            say!(out, "\n🏊 Liquidity Added");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "Shares minted: {}", pool.shares_of(&from_id) - shares_before);
+            say!(out, "Shares held: {} of {}", pool.shares_of(&from_id), pool.total_shares);
+            say!(out, "Reserves: {} / {} {}", format_tokens(pool.native_reserve), pool.token_reserve, token);
+            
+            Ok(())
+        }
+        
+        TokenCommand::RemoveLiquidity { from, token, shares, min_native, min_token, key_file, max_fee, tip } => {
+            let from_id = UserId(Uuid::parse_str(&from)
+                .map_err(|_| Error::validation("Invalid from user ID"))?);
+            let native_before = currency_client.get_balance(&from_id)?;
+            let token_before = currency_client.get_token_balance(&from_id, &token);
+            
+            let payload = TransactionPayload::RemoveLiquidity { token: token.clone(), shares, min_native, min_token };
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
+            let fee = currency_client.get_transaction(&tx_id)?.map(|tx| tx.fee).unwrap_or(0);
+            
+            say!(out, "\n🏊 Liquidity Removed");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "Shares burned: {}", shares);
+            say!(out, "Received: {} and {} {}",
+                format_tokens(currency_client.get_balance(&from_id)? + fee - native_before),
+                currency_client.get_token_balance(&from_id, &token) - token_before,
+                token);
+            
+            Ok(())
+        }
+        
+        TokenCommand::SwapPool { token, window } => {
+            let pool = currency_client.get_pool(&token)
+                .ok_or_else(|| Error::NotFound(format!("No liquidity pool for {}", token)))?;
+            let to_f64 = |price: u128| price as f64 / Q64 as f64;
+            
+            say!(out, "\n🏊 Swap Pool: native / {}", token);
+            say!(out, "{}", "=".repeat(60));
+            say!(out, "Native Reserve: {}", format_tokens(pool.native_reserve));
+            say!(out, "Token Reserve: {} {}", pool.token_reserve, token);
+            say!(out, "LP Shares: {}", pool.total_shares);
+            say!(out, "Swap Fee: {:.2}%", pool.swap_fee_bps as f64 / 100.0);
+            if let Some(spot) = pool.spot_price() {
+                say!(out, "Spot Price: {:.6} native per {}", to_f64(spot), token);
+            }
+            match currency_client.get_twap(&token, window) {
+                Some(twap) => say!(out, "TWAP ({} blocks): {:.6} native per {}", window, twap.token_price_f64(), token),
+                None => say!(out, "TWAP ({} blocks): not enough history", window),
+            }
+            
+            Ok(())
+        }
+        
+        TokenCommand::IssueToken { from, symbol, name, decimals, max_supply, initial_supply, key_file, max_fee, tip } => {
+            let from_id = UserId(Uuid::parse_str(&from)
+                .map_err(|_| Error::validation("Invalid from user ID"))?);
+            
+            let token = IssueToken { symbol: symbol.clone(), name, decimals, max_supply, initial_supply };
+            let payload = TransactionPayload::IssueToken(token);
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
+            
+            say!(out, "\n🪙 Token Issued");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "Symbol: {}", symbol);
+            say!(out, "Initial Supply: {}", initial_supply);
+            if let Some(max_supply) = max_supply {
+                say!(out, "Max Supply: {}", max_supply);
+            }
+            
+            Ok(())
+        }
+        
+        TokenCommand::MintToken { from, symbol, to, amount, key_file, max_fee, tip } => {
+            let from_id = UserId(Uuid::parse_str(&from)
+                .map_err(|_| Error::validation("Invalid from user ID"))?);
+            let to_id = UserId(Uuid::parse_str(&to)
+                .map_err(|_| Error::validation("Invalid to user ID"))?);
+            
+            let payload = TransactionPayload::MintToken { symbol: symbol.clone(), to: to_id.clone(), amount };
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
+            
+            say!(out, "\n🪙 Tokens Minted");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "Minted: {} {} to {}", amount, symbol, to);
+            say!(out, "Recipient Balance: {} {}", currency_client.get_token_balance(&to_id, &symbol), symbol);
+            
+            Ok(())
+        }
+        
+        TokenCommand::BurnToken { from, symbol, amount, key_file, max_fee, tip } => {
+            let from_id = UserId(Uuid::parse_str(&from)
+                .map_err(|_| Error::validation("Invalid from user ID"))?);
+            
+            let payload = TransactionPayload::BurnToken { symbol: symbol.clone(), amount };
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
+            
+            say!(out, "\n🔥 Tokens Burned");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "Burned: {} {}", amount, symbol);
+            say!(out, "Remaining Balance: {} {}", currency_client.get_token_balance(&from_id, &symbol), symbol);
+            
+            Ok(())
+        }
+        
+        TokenCommand::SendToken { from, symbol, to, amount, key_file, max_fee, tip } => {
+            let from_id = UserId(Uuid::parse_str(&from)
+                .map_err(|_| Error::validation("Invalid from user ID"))?);
+            let to_id = UserId(Uuid::parse_str(&to)
+                .map_err(|_| Error::validation("Invalid to user ID"))?);
+            
+            let payload = TransactionPayload::TransferToken { symbol: symbol.clone(), to: to_id.clone(), amount };
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
+            
+            say!(out, "\n✅ Tokens Sent");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "Sent: {} {} to {}", amount, symbol, to);
+            say!(out, "\nNew Balances:");
+            say!(out, "  From: {} {}", currency_client.get_token_balance(&from_id, &symbol), symbol);
+            say!(out, "  To: {} {}", currency_client.get_token_balance(&to_id, &symbol), symbol);
+            
+            Ok(())
+        }
+        
+        TokenCommand::Bond { from, token, amount, lock_blocks, key_file, max_fee, tip } => {
+            let from_id = UserId(Uuid::parse_str(&from)
+                .map_err(|_| Error::validation("Invalid from user ID"))?);
+            
+            let payload = TransactionPayload::Bond { token: token.clone(), amount, lock_blocks };
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
+            let bond = currency_client.get_bond(&from_id, &token)
+                .ok_or_else(|| Error::NotFound(format!("No {} bond for {}", token, from)))?;
+            
+            say!(out, "\n🔒 Tokens Bonded");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "Bonded: {} {}", bond.amount, token);
+            say!(out, "Unlocks At Block: {}", bond.unlock_at);
+            
+            Ok(())
+        }
+        
+        TokenCommand::Unbond { from, token, amount, key_file, max_fee, tip } => {
+            let from_id = UserId(Uuid::parse_str(&from)
+                .map_err(|_| Error::validation("Invalid from user ID"))?);
+            
+            let payload = TransactionPayload::Unbond { token: token.clone(), amount };
+            let tx_id = submit_signed(currency_client, &from_id, &key_file, max_fee, tip, payload)?;
+            let remaining = currency_client.get_bond(&from_id, &token).map(|bond| bond.amount).unwrap_or(0);
+            
+            say!(out, "\n🔓 Tokens Unbonded");
+            say!(out, "Transaction ID: {}", tx_id);
+            say!(out, "Released: {} {}", amount, token);
+            say!(out, "Still Bonded: {} {}", remaining, token);
+            
+            Ok(())
+        }
+        
+        TokenCommand::TokenInfo { symbol } => {
+            let token = currency_client.get_token_metadata(&symbol)
+                .ok_or_else(|| Error::NotFound(format!("Token not found: {}", symbol)))?;
+            
+            say!(out, "\n🪙 Token: {} ({})", token.symbol, token.name);
+            say!(out, "{}", "=".repeat(60));
+            say!(out, "Issuer: {}", token.issuer);
+            say!(out, "Decimals: {}", token.decimals);
+            say!(out, "Total Supply: {}", token.total_supply);
+            match token.max_supply {
+                Some(max_supply) => say!(out, "Max Supply: {}", max_supply),
+                None => say!(out, "Max Supply: uncapped"),
+            }
+            say!(out, "Issued At Block: {}", token.issued_at);
             
+            Ok(())
+        }
+        
+        TokenCommand::Balance { user_id } => {
             let id = UserId(Uuid::parse_str(&user_id)
                 .map_err(|_| Error::validation("Invalid user ID"))?);
             
             let wallet = currency_client.get_wallet(&id)?
                 .ok_or_else(|| Error::NotFound("Wallet not found".to_string()))?;
             
-            println!("\n💰 Wallet Balance");
-            println!("{}", "=".repeat(60));
-            println!("User ID: {}", user_id);
-            println!("Balance: {}", format_tokens(wallet.balance));
-            println!("Staked: {}", format_tokens(wallet.staked));
-            println!("Pending Rewards: {}", format_tokens(wallet.rewards_pending));
-            println!("Total Assets: {}", format_tokens(wallet.balance + wallet.staked + wallet.rewards_pending));
+            say!(out, "\n💰 Wallet Balance");
+            say!(out, "{}", "=".repeat(60));
+            say!(out, "User ID: {}", user_id);
+            say!(out, "Balance: {}", format_tokens(wallet.balance));
+            say!(out, "Staked: {}", format_tokens(wallet.staked));
+            say!(out, "Pending Rewards: {}", format_tokens(wallet.rewards_pending));
+            say!(out, "Total Assets: {}", format_tokens(wallet.balance + wallet.staked + wallet.rewards_pending));
+            let mut tokens: Vec<_> = wallet.tokens.iter().collect();
+            tokens.sort();
+            for (symbol, amount) in tokens {
+                say!(out, "{}: {}", symbol, amount);
+            }
+            let symbols = std::iter::once(NATIVE_TOKEN.to_string())
+                .chain(currency_client.get_issued_tokens().into_iter().map(|token| token.symbol));
+            for symbol in symbols {
+                if let Some(bond) = currency_client.get_bond(&id, &symbol) {
+                    say!(out, "Bonded {}: {} (unlocks at block {})", symbol, bond.amount, bond.unlock_at);
+                }
+            }
             
             Ok(())
         }
     }
 }
 
+/// Sign `payload` as `from` with the key in `key_file` and submit it
+///
+/// A new account is bound to the key; an existing one must already be bound
+/// to it.
+fn submit_signed(
+    currency_client: &CurrencyChainClient,
+    from: &UserId,
+    key_file: &Path,
+    max_fee: Option<u64>,
+    tip: u64,
+    payload: TransactionPayload,
+) -> Result<Uuid> {
+    let contents = std::fs::read_to_string(key_file).map_err(Error::Io)?;
+    let private_key = parse_private_key(&contents)?;
+    
+    match currency_client.get_wallet(from)? {
+        None => {
+            currency_client.create_account(from, private_key.public_key(), 0)?;
+        }
+        Some(wallet) if wallet.public_key.is_none() => {
+            return Err(Error::PermissionDenied(format!("Account {} has no signing key", from)));
+        }
+        Some(_) => {}
+    }
+    
+    let tx = TransactionEnvelope {
+        from: from.clone(),
+        nonce: currency_client.get_nonce(from),
+        max_fee: max_fee.unwrap_or(currency_client.base_fee() + tip),
+        tip,
+        payload,
+    }
+    .sign(&private_key);
+    currency_client.submit_transaction(&tx)
+}
+
 /// Format tokens with thousands separators
 fn format_tokens(amount: u64) -> String {
     let s = amount.to_string();
@@ -4148,4 +7231,277 @@ mod tests {
     fn test_version() {
         assert!(!VERSION.is_empty());
     }
+
+    #[test]
+    fn test_node_devices_pair_and_revoke() {
+        let mut primary = NodeDevices::new(UserId::new(), PrivateKey::generate()).unwrap();
+        let mut phone = NodeDevices::new(UserId::new(), PrivateKey::generate()).unwrap();
+        let topic = device_pairing_topic(&primary.user_id);
+
+        // Relay each reply to the other node until the handshake settles
+        let code = primary.open_pairing("peer".to_string()).unwrap();
+        let mut message = phone.start_join(code, "Phone".to_string(), DeviceType::Mobile).unwrap();
+        let nodes = [&mut primary, &mut phone];
+        let mut turn = 0;
+        loop {
+            let Some((reply_topic, reply)) = nodes[turn].handle_pairing(message).unwrap() else { break };
+            assert_eq!(reply_topic, topic);
+            message = reply;
+            turn = 1 - turn;
+        }
+        let device_id = phone.paired.as_ref().unwrap().certificate.device_id.clone();
+        assert!(primary.issued.contains_key(&device_id));
+        assert_eq!(primary.next_device_index, 2);
+
+        let (revocation, rotation) = primary.revoke(&device_id).unwrap();
+        assert!(primary.revoke(&device_id).is_err());
+        assert!(phone.registry.apply_revocation(revocation).unwrap());
+        assert!(phone.handle_pairing(rotation).is_err());
+        assert_eq!(primary.sync_epoch, 1);
+    }
+
+    #[test]
+    fn test_node_devices_survive_a_restart() {
+        let master_key = PrivateKey::generate();
+        let mut primary = NodeDevices::new(UserId::new(), master_key.clone()).unwrap();
+        let mut phone = NodeDevices::new(UserId::new(), PrivateKey::generate()).unwrap();
+
+        let code = primary.open_pairing("peer".to_string()).unwrap();
+        let mut message = phone.start_join(code, "Phone".to_string(), DeviceType::Mobile).unwrap();
+        let nodes = [&mut primary, &mut phone];
+        let mut turn = 0;
+        while let Some((_, reply)) = nodes[turn].handle_pairing(message).unwrap() {
+            message = reply;
+            turn = 1 - turn;
+        }
+        let device_id = phone.paired.as_ref().unwrap().certificate.device_id.clone();
+        let (revocation, _) = primary.revoke(&device_id).unwrap();
+
+        // Records go through JSON like the state store
+        let round_trip = |devices: &NodeDevices| -> DeviceRecords {
+            serde_json::from_str(&serde_json::to_string(&devices.records().unwrap()).unwrap()).unwrap()
+        };
+        let restored = NodeDevices::restore(primary.user_id.clone(), master_key, &round_trip(&primary), None).unwrap();
+        assert_eq!(restored.next_device_index, 2);
+        assert_eq!(restored.sync_epoch, 1);
+        assert!(restored.registry.is_revoked(&restored.issued[&device_id]));
+        assert_eq!(restored.registry.revocations().next(), Some(&revocation));
+
+        let paired = phone.paired.as_ref().unwrap();
+        let key = KeyPair::from_private_key(paired.keypair.private_key().clone());
+        let phone_records = round_trip(&phone);
+        assert!(NodeDevices::restore(phone.user_id.clone(), PrivateKey::generate(), &phone_records, None).is_err());
+        let restored = NodeDevices::restore(phone.user_id.clone(), PrivateKey::generate(), &phone_records, Some(key)).unwrap();
+        assert_eq!(restored.paired.unwrap().certificate.device_id, device_id);
+    }
+
+    #[tokio::test]
+    async fn test_validator_blocks_survive_a_restart() {
+        use dchat::blockchain::BridgeMessagePayload;
+        use dchat::bridge::ChainId;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.storage.data_dir = dir.path().to_path_buf();
+        let open = || NodeControl::open(&config, "validator", PeerId::random(), None);
+
+        let key = PrivateKey::generate();
+        let account = validator_account_id(&key.public_key());
+        let sign = |envelope: TransactionEnvelope| Ok(envelope.sign(&key));
+        let control = open().await.unwrap();
+        control.currency.lock().await.create_account(&account, key.public_key(), 20_000).unwrap();
+        assert!(control.register_validator(&account, &key.public_key(), 10_000, sign).await.unwrap().is_some());
+
+        // A message queued on the currency chain is committed by the next block
+        {
+            let mut state = control.bridge.lock().await;
+            let currency = control.currency.lock().await;
+            let bridge = CrossChainBridge::with_state(control.chat_chain.clone(), currency.clone(), state.clone());
+            let payload = BridgeMessagePayload::UpdateReputation { user: account.clone(), delta: 1 };
+            bridge.send_message(ChainId::CurrencyChain, ChainId::ChatChain, &account, payload).unwrap();
+            **state = bridge.state();
+        }
+        control.produce_block().await.unwrap();
+        drop(control);
+
+        // A restarted validator keeps its height, registration and bridge outbox
+        let control = open().await.unwrap();
+        assert!(control.register_validator(&account, &key.public_key(), 10_000, sign).await.unwrap().is_none());
+        let currency = control.currency.lock().await;
+        assert_eq!(currency.get_current_block(), 2);
+        assert_eq!(currency.get_validator(&account).unwrap().self_bond, 10_000);
+        let state = control.bridge.lock().await.clone();
+        let bridge = CrossChainBridge::with_state(control.chat_chain.clone(), currency.clone(), state);
+        assert!(bridge.prove_message(&ChainId::CurrencyChain, 0).is_some());
+    }
+
+    #[test]
+    fn test_node_type_option() {
+        let cli = Cli::parse_from(["dchat", "validator", "--key", "k", "--chain-rpc", "r", "--node-type", "archive"]);
+        assert_eq!(cli.node_type, Some(NodeType::Archive));
+        assert!(Cli::try_parse_from(["dchat", "--node-type", "tiny", "relay"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_bootstraps_a_new_node() {
+        use dchat::storage::BlockRow;
+
+        let (source, target) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let config_in = |dir: &Path| {
+            let mut config = Config::default();
+            config.storage.data_dir = dir.to_path_buf();
+            config
+        };
+        let db = open_state_database(&config_in(source.path())).await.unwrap();
+        let genesis = BlockRow::new(0, vec![0; 32], 0, b"genesis".to_vec());
+        db.insert_block(&genesis).await.unwrap();
+        db.insert_block(&BlockRow::new(1, genesis.hash.clone(), 1, b"next".to_vec())).await.unwrap();
+        db.close().await.unwrap();
+
+        let path = source.path().join("snapshot.json");
+        let snapshot = DatabaseCommand::Snapshot { output: path.clone() };
+        run_database_command(config_in(source.path()), snapshot, NodeType::Full).await.unwrap();
+        let bootstrap = || DatabaseCommand::Bootstrap { input: path.clone() };
+        run_database_command(config_in(target.path()), bootstrap(), NodeType::Light).await.unwrap();
+
+        let db = open_state_database(&config_in(target.path())).await.unwrap();
+        assert_eq!(db.latest_block().await.unwrap().unwrap().height, 1);
+        db.close().await.unwrap();
+
+        // A node that already has blocks cannot bootstrap again
+        assert!(run_database_command(config_in(target.path()), bootstrap(), NodeType::Light).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocks_execute_queued_proposals() {
+        use dchat::governance::{ProposalExecutor, ProposalPayload, UpgradeProposal, UpgradeStatus, UpgradeType, Version};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.storage.data_dir = dir.path().to_path_buf();
+        let open = || NodeControl::open(&config, "validator", PeerId::random(), None);
+        let guardian = UserId(Uuid::new_v4());
+
+        let control = open().await.unwrap();
+        let (config_change, toggle) = {
+            let mut governance = control.governance.lock().await;
+            let mut execution = control.execution.lock().await;
+            execution.executor = ProposalExecutor::new(1);
+            let mut queue = |upgrade_type, payload: Option<ProposalPayload>| {
+                let mut upgrade = UpgradeProposal::new(
+                    UserId(Uuid::new_v4()),
+                    upgrade_type,
+                    governance.current_version().clone(),
+                    Version::new(1, 1, 0),
+                    "Change".to_string(),
+                    "Test".to_string(),
+                    7,
+                    10,
+                ).unwrap();
+                upgrade.payload = payload;
+                upgrade.status = UpgradeStatus::Approved;
+                let upgrade_id = governance.submit_proposal(upgrade).unwrap();
+                execution.executor.queue_upgrade(&governance, &upgrade_id, 1).unwrap()
+            };
+            let payload = ProposalPayload::ConfigChange {
+                key: "/governance/quorum_threshold".to_string(),
+                value: serde_json::json!(0.75),
+            };
+            let config_change = queue(UpgradeType::SoftFork, Some(payload));
+            let toggle = queue(UpgradeType::FeatureToggle { feature: "stealth_payloads".to_string() }, None);
+            (config_change, toggle)
+        };
+
+        // A guardian vetoes the toggle before its height
+        for action in [
+            GovernanceCommand::Configure {
+                hard_fork_threshold: None,
+                total_stake: None,
+                guardians: Some(guardian.to_string()),
+                veto_threshold: 1,
+            },
+            GovernanceCommand::VetoExecution { execution_id: toggle.to_string(), guardian: guardian.to_string() },
+        ] {
+            let mut governance = control.governance.lock().await;
+            let mut execution = control.execution.lock().await;
+            apply_governance_command(&mut governance, &mut execution.executor, 1, action, &mut CommandOutput::default()).unwrap();
+        }
+        control.produce_block().await.unwrap();
+        drop(control);
+
+        // The config change executed and survives a restart; the toggle did not
+        let control = open().await.unwrap();
+        let execution = control.execution.lock().await;
+        assert!(execution.executor.pending().is_empty());
+        assert!(execution.executor.verify_log());
+        let governed = governed_config(&control.config, &execution.config_overrides).unwrap();
+        assert_eq!(governed.governance.quorum_threshold, 0.75);
+        assert!(execution.executor.get_execution(&config_change).is_some());
+        assert!(!control.governance.lock().await.is_feature_enabled("stealth_payloads"));
+    }
+
+    #[tokio::test]
+    async fn test_blocks_fund_the_insurance_fund() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.storage.data_dir = dir.path().to_path_buf();
+        let control = NodeControl::open(&config, "validator", PeerId::random(), None).await.unwrap();
+        let fee_pool = module_account_id(FEE_POOL_ACCOUNT_DOMAIN);
+        let fund_account = module_account_id(INSURANCE_ACCOUNT_DOMAIN);
+
+        // Tips land in the fee pool
+        {
+            let currency = control.currency.lock().await;
+            let key = PrivateKey::generate();
+            let user = UserId(Uuid::new_v4());
+            currency.create_account(&user, key.public_key(), 10_000).unwrap();
+            let tipped = TransactionEnvelope {
+                from: user.clone(),
+                nonce: 0,
+                max_fee: currency.base_fee() + 1_000,
+                tip: 1_000,
+                payload: TransactionPayload::ClaimRewards,
+            };
+            currency.submit_transaction(&tipped.sign(&key)).unwrap();
+            assert_eq!(currency.get_balance(&fee_pool).unwrap(), 1_000);
+        }
+
+        // The next block moves the fund's share to it and burns the rest
+        control.produce_block().await.unwrap();
+        let share = 1_000 * dchat::chain::FundConfiguration::default().fee_allocation_percent as u64 / 100;
+        let currency = control.currency.lock().await;
+        assert_eq!(currency.get_balance(&fee_pool).unwrap(), 0);
+        assert_eq!(currency.get_balance(&fund_account).unwrap(), share);
+        assert_eq!(control.insurance.lock().await.balance(), share);
+    }
+
+    #[test]
+    fn test_token_gated_channel_membership() {
+        use dchat::messaging::{AccessPolicy, ChannelAccessManager};
+
+        let currency = CurrencyChainClient::new(CurrencyChainConfig::default());
+        let mut access = ChannelAccessManager::new();
+        let user = UserId(Uuid::new_v4());
+        currency.create_account(&user, PrivateKey::generate().public_key(), 0).unwrap();
+
+        // Ungated channels are open
+        join_channel(&mut access, &currency, &user, "general").unwrap();
+        assert!(is_channel_member(&access, &currency, &user, "general").unwrap());
+
+        access.set_policy(gossip_channel_id("holders"), AccessPolicy::TokenGated {
+            token_id: NATIVE_TOKEN.to_string(),
+            minimum_amount: 100,
+            requires_bonding: false,
+        });
+        assert!(join_channel(&mut access, &currency, &user, "holders").is_err());
+        assert!(!is_channel_member(&access, &currency, &user, "holders").unwrap());
+    }
+
+    #[test]
+    fn test_stealth_address_round_trip() {
+        let address = StealthScanner::generate(&mut rand::rngs::OsRng).address();
+        let parsed = parse_stealth_address(&encode_stealth_address(&address)).unwrap();
+        assert_eq!((parsed.view_key, parsed.spend_key), (address.view_key, address.spend_key));
+        assert!(parse_stealth_address("abcd").is_err());
+    }
 }
\ No newline at end of file
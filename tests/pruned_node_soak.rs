@@ -0,0 +1,103 @@
+//! Soak test: a pruned node's disk usage stays bounded over a long run
+
+use dchat_chain::pruning::{NodeType, PruningConfig};
+use dchat_storage::database::{Database, DatabaseConfig, MessageRow};
+use dchat_storage::{BlockRow, NodePruner};
+use tempfile::tempdir;
+
+const ROUNDS: i64 = 120;
+const MESSAGES_PER_ROUND: usize = 100;
+const BLOCKS_PER_ROUND: u64 = 10;
+const SECONDS_PER_ROUND: i64 = 60;
+const RETENTION_SECS: u64 = 600;
+
+fn message(index: usize, timestamp: i64) -> MessageRow {
+    MessageRow {
+        id: uuid::Uuid::new_v4().to_string(),
+        sender_id: "soak".to_string(),
+        recipient_id: None,
+        channel_id: Some(format!("channel-{}", index % 8)),
+        content_type: "text".to_string(),
+        content: "x".repeat(256),
+        encrypted_payload: vec![0xAB; 1024],
+        timestamp,
+        sequence_num: Some(index as i64),
+        status: "delivered".to_string(),
+        expires_at: None,
+        size: 1024,
+        content_hash: None,
+    }
+}
+
+async fn run_soak(node_type: NodeType) -> (Vec<u64>, usize) {
+    let dir = tempdir().unwrap();
+    let db = Database::new(DatabaseConfig {
+        path: dir.path().join("soak.db"),
+        ..DatabaseConfig::default()
+    })
+    .await
+    .unwrap();
+    db.insert_user("soak", "soak", &[0; 32]).await.unwrap();
+
+    let mut pruner = NodePruner::open(
+        db.clone(),
+        PruningConfig {
+            node_type,
+            retention_period: RETENTION_SECS,
+            checkpoint_interval: 50,
+            ..PruningConfig::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let mut usage = Vec::new();
+    let mut parent = Vec::new();
+    let mut height = 0;
+    for round in 0..ROUNDS {
+        let now = round * SECONDS_PER_ROUND;
+        for i in 0..MESSAGES_PER_ROUND {
+            db.insert_message(&message(i, now)).await.unwrap();
+        }
+        for _ in 0..BLOCKS_PER_ROUND {
+            height += 1;
+            let block = BlockRow::new(height, parent, now, vec![0xCD; 512]);
+            parent = block.hash.clone();
+            db.insert_block(&block).await.unwrap();
+        }
+
+        pruner.prune(now).await.unwrap();
+        usage.push(db.disk_usage().await.unwrap());
+    }
+
+    let messages = db.stats().await.unwrap().message_count;
+    (usage, messages)
+}
+
+#[tokio::test]
+async fn test_pruned_node_disk_usage_is_bounded() {
+    let window_rounds = (RETENTION_SECS as i64 / SECONDS_PER_ROUND) as usize;
+
+    for node_type in [NodeType::Light, NodeType::Full] {
+        let (usage, messages) = run_soak(node_type).await;
+
+        // Only the retention window's messages remain
+        assert!(messages <= (window_rounds + 1) * MESSAGES_PER_ROUND, "{:?}: {} messages", node_type, messages);
+
+        // Once the window is full, usage plateaus: the second half of the
+        // run stays within a small margin of where the first half ended
+        let warmed_up = usage[ROUNDS as usize / 2];
+        let peak = *usage[ROUNDS as usize / 2..].iter().max().unwrap();
+        assert!(
+            peak <= warmed_up + warmed_up / 5,
+            "{:?}: disk usage grew from {} to {} bytes",
+            node_type,
+            warmed_up,
+            peak
+        );
+    }
+
+    // Without pruning the same load keeps growing
+    let (usage, _) = run_soak(NodeType::Archive).await;
+    assert!(usage[ROUNDS as usize - 1] > usage[ROUNDS as usize / 2] * 3 / 2);
+}